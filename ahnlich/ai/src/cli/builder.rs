@@ -0,0 +1,222 @@
+use super::server::{
+    AIProxyConfig, AnswerModel, ExecutionProvider, ModelExecutionProviders, SupportedModels,
+};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use utils::cli::{CommandLineConfig, ConfigError};
+use utils::filters::ClientFilter;
+use utils::limits::LimitOverride;
+
+/// Builds the configuration of an AI proxy embedded in another program, starting from the
+/// defaults of the command line and validating the settings clap would otherwise check
+///
+/// ```ignore
+/// let config = AIProxyConfig::builder()
+///     .port(0)
+///     .db("127.0.0.1", 1369)
+///     .supported_models(vec![SupportedModels::AllMiniLML6V2])
+///     .build()?;
+/// let server = AIProxyServer::new(config).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AIProxyConfigBuilder {
+    config: AIProxyConfig,
+}
+
+impl AIProxyConfig {
+    pub fn builder() -> AIProxyConfigBuilder {
+        AIProxyConfigBuilder::default()
+    }
+}
+
+impl AIProxyConfigBuilder {
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.config.common.host = host.into();
+        self
+    }
+
+    /// Port to serve clients on, 0 lets the OS pick one
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Serves the HTTP gateway on `port`, 0 lets the OS pick one
+    pub fn http_gateway(mut self, port: u16) -> Self {
+        self.config.common.enable_http_gateway = true;
+        self.config.http_port = port;
+        self
+    }
+
+    pub fn unix_socket(mut self, path: PathBuf) -> Self {
+        self.config.common.unix_socket = Some(path);
+        self
+    }
+
+    /// Database the proxy stores embeddings in
+    pub fn db(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.config.db_host = host.into();
+        self.config.db_port = port;
+        self
+    }
+
+    /// Connects to the database over a Unix domain socket instead of its host and port
+    pub fn db_unix_socket(mut self, path: PathBuf) -> Self {
+        self.config.db_unix_socket = Some(path);
+        self
+    }
+
+    pub fn db_client_pool_size(mut self, size: usize) -> Self {
+        self.config.db_client_pool_size = size;
+        self
+    }
+
+    /// Persists the stores to `location` every `interval` milliseconds and loads them from it
+    /// on startup
+    pub fn persistence(mut self, location: PathBuf, interval: u64) -> Self {
+        self.config.common.enable_persistence = true;
+        self.config.common.persist_location = Some(location);
+        self.config.common.persistence_interval = interval;
+        self
+    }
+
+    pub fn allocator_size(mut self, size: usize) -> Self {
+        self.config.common.allocator_size = size;
+        self
+    }
+
+    pub fn threadpool_size(mut self, threadpool_size: usize) -> Self {
+        self.config.common.threadpool_size = threadpool_size;
+        self
+    }
+
+    pub fn maximum_clients(mut self, maximum_clients: usize) -> Self {
+        self.config.common.maximum_clients = maximum_clients;
+        self
+    }
+
+    pub fn maximum_clients_per_host(mut self, maximum_clients_per_host: usize) -> Self {
+        self.config.common.maximum_clients_per_host = Some(maximum_clients_per_host);
+        self
+    }
+
+    pub fn message_size(mut self, message_size: usize) -> Self {
+        self.config.common.message_size = message_size;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.config.common.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn client_limit(mut self, limit: LimitOverride) -> Self {
+        self.config.common.client_limits.push(limit);
+        self
+    }
+
+    pub fn store_limit(mut self, limit: LimitOverride) -> Self {
+        self.config.common.store_limits.push(limit);
+        self
+    }
+
+    pub fn client_filter(mut self, filter: ClientFilter) -> Self {
+        self.config.common.client_filters.push(filter);
+        self
+    }
+
+    pub fn log_level(mut self, log_level: impl Into<String>) -> Self {
+        self.config.common.log_level = log_level.into();
+        self
+    }
+
+    pub fn supported_models(mut self, models: Vec<SupportedModels>) -> Self {
+        self.config.supported_models = models;
+        self
+    }
+
+    pub fn model_registry(mut self, path: PathBuf) -> Self {
+        self.config.model_registry = Some(path);
+        self
+    }
+
+    pub fn model_cache_location(mut self, location: PathBuf) -> Self {
+        self.config.model_cache_location = location;
+        self
+    }
+
+    /// Seconds an unused model is kept loaded
+    pub fn model_idle_time(mut self, idle_time: u64) -> Self {
+        self.config.ai_model_idle_time = idle_time;
+        self
+    }
+
+    pub fn execution_providers(mut self, execution_providers: Vec<ExecutionProvider>) -> Self {
+        self.config.execution_providers = execution_providers;
+        self
+    }
+
+    pub fn model_execution_providers(
+        mut self,
+        model_execution_providers: ModelExecutionProviders,
+    ) -> Self {
+        self.config
+            .model_execution_providers
+            .push(model_execution_providers);
+        self
+    }
+
+    pub fn gpu_memory_limit(mut self, limit: usize) -> Self {
+        self.config.gpu_memory_limit = Some(limit);
+        self
+    }
+
+    /// Most inputs a model embeds at once and the milliseconds it waits to fill a batch
+    pub fn model_batching(mut self, batch_size: usize, latency: u64) -> Self {
+        self.config.model_batch_size = batch_size;
+        self.config.model_batch_latency = latency;
+        self
+    }
+
+    pub fn replicas_per_model(mut self, replicas: NonZeroUsize) -> Self {
+        self.config.replicas_per_model = replicas;
+        self
+    }
+
+    pub fn answer_model(mut self, answer_model: AnswerModel) -> Self {
+        self.config.answer_model = Some(answer_model);
+        self
+    }
+
+    /// Replaces the settings shared with the database, for those without a method of their own
+    pub fn common(mut self, common: CommandLineConfig) -> Self {
+        self.config.common = common;
+        self
+    }
+
+    pub fn build(self) -> Result<AIProxyConfig, ConfigError> {
+        let config = self.config;
+        config.common.validate()?;
+        if config.supported_models.is_empty() && config.model_registry.is_none() {
+            return Err(ConfigError::invalid(
+                "supported_models",
+                "at least one model has to be supported when there is no model registry",
+            ));
+        }
+        if config.execution_providers.is_empty() {
+            return Err(ConfigError::invalid(
+                "execution_providers",
+                "at least one execution provider has to be attempted",
+            ));
+        }
+        for (setting, value) in [
+            ("db_client_pool_size", config.db_client_pool_size),
+            ("model_batch_size", config.model_batch_size),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::invalid(setting, "it must be above 0"));
+            }
+        }
+        Ok(config)
+    }
+}
@@ -1,3 +1,5 @@
+mod builder;
 pub mod server;
 
+pub use builder::AIProxyConfigBuilder;
 pub use server::{AIProxyConfig, Cli, Commands};
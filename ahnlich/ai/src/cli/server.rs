@@ -1,16 +1,22 @@
-use ahnlich_types::ai::AIModel;
+use ahnlich_types::ai::{AIExecutionProvider, AIModel};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use dirs::home_dir;
 use std::fmt;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::sync::Arc;
 use strum::VariantArray;
 
 use crate::engine::ai::models::{Model, ModelInfo};
+use crate::engine::ai::registry::{CustomModel, ProcessingPreset};
 use std::io::Write;
 use std::sync::OnceLock;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-use utils::cli::CommandLineConfig;
+use utils::cli::{CommandLineConfig, VerifyBackupArgs};
+use utils::filters::ClientFilter;
+use utils::limits::LimitOverride;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Hash, Ord, ValueEnum, VariantArray)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash, Ord, ValueEnum)]
 pub enum SupportedModels {
     #[clap(name = "all-minilm-l6-v2")]
     AllMiniLML6V2,
@@ -26,6 +32,103 @@ pub enum SupportedModels {
     ClipVitB32Image,
     #[clap(name = "clip-vit-b32-text")]
     ClipVitB32Text,
+    // Declared in the model registry, which makes it supported without listing it in
+    // `supported_models`
+    #[clap(skip)]
+    Custom(Arc<CustomModel>),
+}
+
+impl VariantArray for SupportedModels {
+    const VARIANTS: &'static [Self] = &[
+        SupportedModels::AllMiniLML6V2,
+        SupportedModels::AllMiniLML12V2,
+        SupportedModels::BGEBaseEnV15,
+        SupportedModels::BGELargeEnV15,
+        SupportedModels::Resnet50,
+        SupportedModels::ClipVitB32Image,
+        SupportedModels::ClipVitB32Text,
+    ];
+}
+
+impl SupportedModels {
+    /// Finds the supported model an AIModel refers to
+    pub(crate) fn find<'a>(
+        supported_models: &'a [SupportedModels],
+        model: &AIModel,
+    ) -> Option<&'a SupportedModels> {
+        supported_models
+            .iter()
+            .find(|supported_model| AIModel::from(*supported_model) == *model)
+    }
+
+    pub(crate) fn preset(&self) -> ProcessingPreset {
+        match self {
+            SupportedModels::AllMiniLML6V2 | SupportedModels::AllMiniLML12V2 => {
+                ProcessingPreset::MeanPooling
+            }
+            SupportedModels::BGEBaseEnV15 | SupportedModels::BGELargeEnV15 => {
+                ProcessingPreset::ClsPooling
+            }
+            SupportedModels::ClipVitB32Text => ProcessingPreset::ClipText,
+            SupportedModels::Resnet50 => ProcessingPreset::Resnet,
+            SupportedModels::ClipVitB32Image => ProcessingPreset::ClipImage,
+            SupportedModels::Custom(model) => model.preset,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Hash, Ord, ValueEnum)]
+pub enum ExecutionProvider {
+    #[clap(name = "tensorrt")]
+    TensorRT,
+    #[clap(name = "cuda")]
+    CUDA,
+    #[clap(name = "directml")]
+    DirectML,
+    #[clap(name = "coreml")]
+    CoreML,
+    #[clap(name = "cpu")]
+    CPU,
+}
+
+/// Models that answer questions from the entries retrieved for them
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Hash, Ord, ValueEnum)]
+pub enum AnswerModel {
+    // Extracts the span of an entry that best answers the question
+    #[clap(name = "distilbert-base-cased-squad")]
+    DistilBertBaseCasedSquad,
+    // Generates an answer from all the entries, only available with the `generative` feature
+    #[cfg(feature = "generative")]
+    #[clap(name = "flan-t5-small")]
+    FlanT5Small,
+}
+
+/// Execution providers to attempt for a single model, parsed from `MODEL=PROVIDER[,PROVIDER...]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelExecutionProviders {
+    pub model: SupportedModels,
+    pub execution_providers: Vec<ExecutionProvider>,
+}
+
+impl FromStr for ModelExecutionProviders {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (model, execution_providers) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected MODEL=PROVIDER[,PROVIDER...], got {s}"))?;
+        let model = SupportedModels::from_str(model.trim(), true)
+            .map_err(|err| format!("Invalid model in {s}: {err}"))?;
+        let execution_providers = execution_providers
+            .split(',')
+            .map(|provider| ExecutionProvider::from_str(provider.trim(), true))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("Invalid execution provider in {s}: {err}"))?;
+        Ok(Self {
+            model,
+            execution_providers,
+        })
+    }
 }
 
 #[derive(Parser)]
@@ -43,6 +146,10 @@ pub enum Commands {
 
     /// Outputs all supported models by aiproxy
     SupportedModels(SupportedModelArgs),
+
+    /// Verifies the checksums of a persisted snapshot and that it loads, without starting
+    /// the proxy
+    VerifyBackup(VerifyBackupArgs),
 }
 
 static DEFAULT_CONFIG: OnceLock<AIProxyConfig> = OnceLock::new();
@@ -54,6 +161,11 @@ pub struct AIProxyConfig {
     DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).port.clone())]
     pub port: u16,
 
+    /// Port of the HTTP gateway, only used with `enable_http_gateway`
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).http_port.clone())]
+    pub http_port: u16,
+
     /// Ahnlich Database Host
     #[arg(long, default_value_t =
     DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).db_host.clone())]
@@ -64,6 +176,11 @@ pub struct AIProxyConfig {
     DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).db_port.clone())]
     pub db_port: u16,
 
+    /// Unix domain socket of the Ahnlich Database, connected to in place of the database host
+    /// and port when given
+    #[arg(long)]
+    pub db_unix_socket: Option<std::path::PathBuf>,
+
     /// Ahnlich Database Client Connection Pool Size
     #[arg(long, default_value_t =
     DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).db_client_pool_size.clone())]
@@ -87,6 +204,48 @@ pub struct AIProxyConfig {
     DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).model_cache_location.clone())]
     pub(crate) model_cache_location: std::path::PathBuf,
 
+    /// JSON file declaring custom ONNX models, which are supported in addition to
+    /// `supported_models` and referred to by name
+    #[arg(long)]
+    pub(crate) model_registry: Option<std::path::PathBuf>,
+
+    /// Execution providers to create model sessions with, attempted in order until one
+    /// registers successfully
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t =
+    DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).execution_providers.clone())]
+    pub(crate) execution_providers: Vec<ExecutionProvider>,
+
+    /// Execution providers for a single model in the format MODEL=PROVIDER[,PROVIDER...],
+    /// overriding `execution_providers` for that model. Can be repeated
+    #[arg(long = "model-execution-providers")]
+    pub(crate) model_execution_providers: Vec<ModelExecutionProviders>,
+
+    /// Maximum memory in bytes the CUDA and TensorRT execution providers may allocate on the GPU
+    /// per model
+    #[arg(long)]
+    pub(crate) gpu_memory_limit: Option<usize>,
+
+    /// Maximum number of inputs from concurrent requests a model embeds in a single batch
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).model_batch_size.clone())]
+    pub(crate) model_batch_size: usize,
+
+    /// Time in milliseconds a model waits for more requests to batch with the first request it
+    /// receives. Defaults to 0 so only requests already queued are batched together
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).model_batch_latency.clone())]
+    pub(crate) model_batch_latency: u64,
+
+    /// Number of threads, each with its own copy of the model, that serve requests for every
+    /// model. Idle replicas pick up the next queued request
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(AIProxyConfig::default).replicas_per_model.clone())]
+    pub(crate) replicas_per_model: NonZeroUsize,
+
+    /// Model that answers AnswerQuestion queries, which fail when it is not set
+    #[arg(long, value_enum)]
+    pub(crate) answer_model: Option<AnswerModel>,
+
     #[clap(flatten)]
     pub common: CommandLineConfig,
 }
@@ -96,6 +255,38 @@ pub struct ModelConfig {
     pub(crate) supported_models: Vec<SupportedModels>,
     pub(crate) model_cache_location: std::path::PathBuf,
     pub(crate) model_idle_time: u64,
+    pub(crate) execution_providers: Vec<ExecutionProvider>,
+    pub(crate) model_execution_providers: Vec<ModelExecutionProviders>,
+    pub(crate) gpu_memory_limit: Option<usize>,
+    pub(crate) batch_size: usize,
+    pub(crate) batch_latency: u64,
+    pub(crate) replicas_per_model: NonZeroUsize,
+    pub(crate) answer_model: Option<AnswerModel>,
+}
+
+impl ModelConfig {
+    /// Returns the execution providers to attempt in order for `model`
+    pub(crate) fn execution_providers(&self, model: &SupportedModels) -> Vec<ExecutionProvider> {
+        self.model_execution_providers
+            .iter()
+            .rev()
+            .find(|entry| entry.model == *model)
+            .map(|entry| entry.execution_providers.clone())
+            .unwrap_or_else(|| self.execution_providers.clone())
+    }
+}
+
+fn default_execution_providers() -> Vec<ExecutionProvider> {
+    vec![
+        // Prefer TensorRT over CUDA.
+        ExecutionProvider::TensorRT,
+        ExecutionProvider::CUDA,
+        // Use DirectML on Windows if NVIDIA EPs are not available
+        ExecutionProvider::DirectML,
+        // Or use ANE on Apple platforms
+        ExecutionProvider::CoreML,
+        ExecutionProvider::CPU,
+    ]
 }
 
 impl Default for ModelConfig {
@@ -110,6 +301,13 @@ impl Default for ModelConfig {
                 })
                 .expect("Default directory could not be resolved."),
             model_idle_time: 60 * 5,
+            execution_providers: default_execution_providers(),
+            model_execution_providers: vec![],
+            gpu_memory_limit: None,
+            batch_size: 128,
+            batch_latency: 0,
+            replicas_per_model: NonZeroUsize::MIN,
+            answer_model: None,
         }
     }
 }
@@ -120,6 +318,13 @@ impl From<&AIProxyConfig> for ModelConfig {
             supported_models: config.supported_models.clone(),
             model_cache_location: config.model_cache_location.clone(),
             model_idle_time: config.ai_model_idle_time,
+            execution_providers: config.execution_providers.clone(),
+            model_execution_providers: config.model_execution_providers.clone(),
+            gpu_memory_limit: config.gpu_memory_limit,
+            batch_size: config.model_batch_size,
+            batch_latency: config.model_batch_latency,
+            replicas_per_model: config.replicas_per_model,
+            answer_model: config.answer_model,
         }
     }
 }
@@ -128,8 +333,10 @@ impl Default for AIProxyConfig {
     fn default() -> Self {
         Self {
             port: 1370,
+            http_port: 1380,
             db_host: String::from("127.0.0.1"),
             db_port: 1369,
+            db_unix_socket: None,
             db_client_pool_size: 10,
             supported_models: vec![
                 SupportedModels::AllMiniLML6V2,
@@ -147,7 +354,15 @@ impl Default for AIProxyConfig {
                     path
                 })
                 .expect("Default directory could not be resolved."),
+            model_registry: None,
             ai_model_idle_time: 60 * 5,
+            execution_providers: default_execution_providers(),
+            model_execution_providers: vec![],
+            gpu_memory_limit: None,
+            model_batch_size: 128,
+            model_batch_latency: 0,
+            replicas_per_model: NonZeroUsize::MIN,
+            answer_model: None,
             common: CommandLineConfig::default(),
         }
     }
@@ -157,6 +372,12 @@ impl AIProxyConfig {
     pub fn os_select_port(mut self) -> Self {
         // allow OS to pick a port
         self.port = 0;
+        self.http_port = 0;
+        self
+    }
+
+    pub fn enable_http_gateway(mut self) -> Self {
+        self.common.enable_http_gateway = true;
         self
     }
 
@@ -176,11 +397,95 @@ impl AIProxyConfig {
         self
     }
 
+    pub fn set_maximum_clients_per_host(mut self, maximum_clients_per_host: usize) -> Self {
+        self.common.maximum_clients_per_host = Some(maximum_clients_per_host);
+        self
+    }
+
+    pub fn set_unix_socket(mut self, unix_socket: std::path::PathBuf) -> Self {
+        self.common.unix_socket = Some(unix_socket);
+        self
+    }
+
+    pub fn set_config_file(mut self, config_file: std::path::PathBuf) -> Self {
+        self.common.config_file = Some(config_file);
+        self
+    }
+
+    pub fn set_db_unix_socket(mut self, db_unix_socket: std::path::PathBuf) -> Self {
+        self.db_unix_socket = Some(db_unix_socket);
+        self
+    }
+
+    pub fn set_batch_size(mut self, batch_size: usize) -> Self {
+        self.common.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn set_client_limit(mut self, limit: LimitOverride) -> Self {
+        self.common.client_limits.push(limit);
+        self
+    }
+
+    pub fn set_store_limit(mut self, limit: LimitOverride) -> Self {
+        self.common.store_limits.push(limit);
+        self
+    }
+
+    pub fn set_client_filter(mut self, filter: ClientFilter) -> Self {
+        self.common.client_filters.push(filter);
+        self
+    }
+
     pub fn set_model_cache_location(mut self, location: std::path::PathBuf) -> Self {
         self.model_cache_location = location;
         self
     }
 
+    pub fn set_execution_providers(mut self, execution_providers: Vec<ExecutionProvider>) -> Self {
+        self.execution_providers = execution_providers;
+        self
+    }
+
+    pub fn set_model_execution_providers(
+        mut self,
+        model_execution_providers: ModelExecutionProviders,
+    ) -> Self {
+        self.model_execution_providers
+            .push(model_execution_providers);
+        self
+    }
+
+    pub fn set_gpu_memory_limit(mut self, limit: usize) -> Self {
+        self.gpu_memory_limit = Some(limit);
+        self
+    }
+
+    pub fn set_model_batch_size(mut self, batch_size: usize) -> Self {
+        self.model_batch_size = batch_size;
+        self
+    }
+
+    pub fn set_model_batch_latency(mut self, latency: u64) -> Self {
+        self.model_batch_latency = latency;
+        self
+    }
+
+    pub fn set_replicas_per_model(mut self, replicas: NonZeroUsize) -> Self {
+        self.replicas_per_model = replicas;
+        self
+    }
+
+    pub fn set_model_registry(mut self, path: std::path::PathBuf) -> Self {
+        self.model_registry = Some(path);
+        self
+    }
+
+    pub fn set_answer_model(mut self, answer_model: AnswerModel) -> Self {
+        self.answer_model = Some(answer_model);
+        self
+    }
+
     #[cfg(test)]
     pub fn set_supported_models(mut self, models: Vec<SupportedModels>) -> Self {
         self.supported_models = models;
@@ -198,20 +503,36 @@ impl fmt::Display for SupportedModels {
             SupportedModels::Resnet50 => write!(f, "Resnet-50"),
             SupportedModels::ClipVitB32Image => write!(f, "ClipVit-B32-Image"),
             SupportedModels::ClipVitB32Text => write!(f, "ClipVit-B32-Text"),
+            SupportedModels::Custom(model) => write!(f, "{}", model.name),
+        }
+    }
+}
+
+impl fmt::Display for AnswerModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnswerModel::DistilBertBaseCasedSquad => write!(f, "DistilBert-Base-Cased-Squad"),
+            #[cfg(feature = "generative")]
+            AnswerModel::FlanT5Small => write!(f, "Flan-T5-Small"),
         }
     }
 }
 
-impl From<&AIModel> for SupportedModels {
-    fn from(value: &AIModel) -> Self {
+impl fmt::Display for ExecutionProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let provider: AIExecutionProvider = self.into();
+        write!(f, "{provider}")
+    }
+}
+
+impl From<&ExecutionProvider> for AIExecutionProvider {
+    fn from(value: &ExecutionProvider) -> Self {
         match value {
-            AIModel::AllMiniLML6V2 => SupportedModels::AllMiniLML6V2,
-            AIModel::AllMiniLML12V2 => SupportedModels::AllMiniLML12V2,
-            AIModel::BGEBaseEnV15 => SupportedModels::BGEBaseEnV15,
-            AIModel::BGELargeEnV15 => SupportedModels::BGELargeEnV15,
-            AIModel::Resnet50 => SupportedModels::Resnet50,
-            AIModel::ClipVitB32Image => SupportedModels::ClipVitB32Image,
-            AIModel::ClipVitB32Text => SupportedModels::ClipVitB32Text,
+            ExecutionProvider::TensorRT => AIExecutionProvider::TensorRT,
+            ExecutionProvider::CUDA => AIExecutionProvider::CUDA,
+            ExecutionProvider::DirectML => AIExecutionProvider::DirectML,
+            ExecutionProvider::CoreML => AIExecutionProvider::CoreML,
+            ExecutionProvider::CPU => AIExecutionProvider::CPU,
         }
     }
 }
@@ -226,17 +547,11 @@ impl From<&SupportedModels> for AIModel {
             SupportedModels::Resnet50 => AIModel::Resnet50,
             SupportedModels::ClipVitB32Image => AIModel::ClipVitB32Image,
             SupportedModels::ClipVitB32Text => AIModel::ClipVitB32Text,
+            SupportedModels::Custom(model) => AIModel::Custom(model.name.clone()),
         }
     }
 }
 
-impl From<&SupportedModels> for Model {
-    fn from(value: &SupportedModels) -> Self {
-        let ai_model: AIModel = value.into();
-        (&ai_model).into()
-    }
-}
-
 #[derive(Args, Debug, Clone)]
 pub struct SupportedModelArgs {
     ///  Models to display information about
@@ -249,8 +564,7 @@ impl SupportedModelArgs {
         let mut output = String::new();
 
         for supported_model in SupportedModels::VARIANTS.iter() {
-            let aimodel: AIModel = supported_model.into();
-            let model: Model = (&aimodel).into();
+            let model: Model = supported_model.into();
             output.push_str(format!("{}, ", model.model_name()).as_str())
         }
         output
@@ -259,8 +573,7 @@ impl SupportedModelArgs {
         let mut output = vec![];
 
         for supported_model in self.names.iter() {
-            let aimodel: AIModel = supported_model.into();
-            let model: Model = (&aimodel).into();
+            let model: Model = supported_model.into();
             output.push(ModelInfo::build(&model))
         }
         serde_json::to_string_pretty(&output)
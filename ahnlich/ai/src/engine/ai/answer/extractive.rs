@@ -0,0 +1,193 @@
+use super::{create_session, inference_error, load_tokenizer, tokenization_error};
+use crate::engine::ai::providers::ort_helper::ModelRepo;
+use crate::error::AIProxyError;
+use ahnlich_types::ai::{AnswerSpan, QuestionAnswer};
+use ahnlich_types::similarity::Similarity;
+use ndarray::{Array, ArrayView1, Axis, Ix2};
+use ort::{Session, Value};
+use tokenizers::utils::padding::pad_encodings;
+use tokenizers::{
+    Encoding, PaddingParams, Tokenizer, TruncationDirection, TruncationParams, TruncationStrategy,
+};
+
+// Texts longer than a sequence are read in windows that overlap by the stride, so an answer
+// that falls on the edge of a window is whole in the next one
+const MAX_SEQUENCE_TOKENS: usize = 384;
+const STRIDE: usize = 128;
+const MAX_ANSWER_TOKENS: usize = 30;
+
+// a row of logits per window with a column per token
+type Logits = Array<f32, Ix2>;
+
+/// A SQuAD model predicting the start and end token of the answer within a text
+pub(crate) struct ExtractiveModel {
+    session: Session,
+    tokenizer: Tokenizer,
+}
+
+impl ExtractiveModel {
+    pub(crate) fn load(repo: &ModelRepo, weights_file: &str) -> Result<Self, AIProxyError> {
+        let session = create_session(repo, weights_file)?;
+        let mut tokenizer = load_tokenizer(repo)?;
+        // only the text is truncated into windows, each of them paired with the whole question
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_SEQUENCE_TOKENS,
+                strategy: TruncationStrategy::OnlySecond,
+                stride: STRIDE,
+                direction: TruncationDirection::Right,
+            }))
+            .map_err(|e| AIProxyError::ModelTokenizerLoadError {
+                message: e.to_string(),
+            })?;
+        tokenizer.with_padding(None);
+        Ok(Self { session, tokenizer })
+    }
+
+    /// Runs every window of every text in one batch and returns the most likely span
+    pub(crate) fn answer(
+        &self,
+        question: &str,
+        contexts: &[String],
+    ) -> Result<Option<QuestionAnswer>, AIProxyError> {
+        let mut sources = vec![];
+        let mut windows = vec![];
+        for (source, context) in contexts.iter().enumerate() {
+            let mut encoding = self
+                .tokenizer
+                .encode((question, context.as_str()), true)
+                .map_err(tokenization_error)?;
+            let overflowing = encoding.take_overflowing();
+            for window in std::iter::once(encoding).chain(overflowing) {
+                sources.push(source);
+                windows.push(window);
+            }
+        }
+        pad_encodings(&mut windows, &PaddingParams::default()).map_err(tokenization_error)?;
+        let (start_logits, end_logits) = self.run(&windows)?;
+
+        let mut best: Option<QuestionAnswer> = None;
+        for (index, (source, window)) in sources.into_iter().zip(&windows).enumerate() {
+            let Some((start, end, score)) = best_span(
+                window,
+                start_logits.index_axis(Axis(0), index),
+                end_logits.index_axis(Axis(0), index),
+            ) else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .and_then(|best| best.score)
+                .is_some_and(|best| best.0 >= score)
+            {
+                continue;
+            }
+            let offsets = window.get_offsets();
+            let (start, end) = (offsets[start].0, offsets[end].1);
+            let Some(text) = contexts[source].get(start..end) else {
+                continue;
+            };
+            best = Some(QuestionAnswer {
+                text: text.to_string(),
+                span: Some(AnswerSpan { source, start, end }),
+                score: Some(Similarity(score)),
+            });
+        }
+        Ok(best)
+    }
+
+    fn run(&self, windows: &[Encoding]) -> Result<(Logits, Logits), AIProxyError> {
+        let shape = (windows.len(), windows[0].len());
+        let ids = windows
+            .iter()
+            .flat_map(|window| window.get_ids().iter().map(|&id| id as i64))
+            .collect();
+        let mask = windows
+            .iter()
+            .flat_map(|window| window.get_attention_mask().iter().map(|&m| m as i64))
+            .collect();
+        let ids = Array::from_shape_vec(shape, ids).map_err(inference_error)?;
+        let mask = Array::from_shape_vec(shape, mask).map_err(inference_error)?;
+        let inputs = ort::inputs![
+            "input_ids" => Value::from_array(ids)?,
+            "attention_mask" => Value::from_array(mask)?
+        ]?;
+        let outputs = self.session.run(inputs).map_err(inference_error)?;
+        let logits = |name: &str| -> Result<Logits, AIProxyError> {
+            outputs[name]
+                .try_extract_tensor::<f32>()?
+                .into_dimensionality::<Ix2>()
+                .map(|logits| logits.to_owned())
+                .map_err(inference_error)
+        };
+        Ok((logits("start_logits")?, logits("end_logits")?))
+    }
+}
+
+fn softmax(logits: impl Iterator<Item = f32> + Clone) -> Vec<f32> {
+    let max = logits.clone().fold(f32::NEG_INFINITY, f32::max);
+    let exponentials: Vec<f32> = logits.map(|logit| (logit - max).exp()).collect();
+    let total: f32 = exponentials.iter().sum();
+    exponentials.into_iter().map(|e| e / total).collect()
+}
+
+/// Finds the start and end token of the most likely answer in the text of a window, scored by
+/// the product of their probabilities among the tokens of the text
+fn best_span(
+    window: &Encoding,
+    start_logits: ArrayView1<f32>,
+    end_logits: ArrayView1<f32>,
+) -> Option<(usize, usize, f32)> {
+    let text_tokens: Vec<usize> = window
+        .get_sequence_ids()
+        .iter()
+        .enumerate()
+        .filter_map(|(token, sequence)| (*sequence == Some(1)).then_some(token))
+        .collect();
+    let start_probabilities = softmax(text_tokens.iter().map(|&token| start_logits[token]));
+    let end_probabilities = softmax(text_tokens.iter().map(|&token| end_logits[token]));
+    let mut best: Option<(usize, usize, f32)> = None;
+    for (i, start_probability) in start_probabilities.iter().enumerate() {
+        for (j, end_probability) in end_probabilities
+            .iter()
+            .enumerate()
+            .skip(i)
+            .take(MAX_ANSWER_TOKENS)
+        {
+            let score = start_probability * end_probability;
+            if best.map_or(true, |(_, _, best)| score > best) {
+                best = Some((text_tokens[i], text_tokens[j], score));
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_best_span_stays_within_the_text() {
+        // [CLS] question [SEP] text text text [SEP]
+        let window = Encoding::new(
+            vec![101, 7, 102, 8, 9, 10, 102],
+            vec![0; 7],
+            vec![String::new(); 7],
+            vec![None; 7],
+            vec![(0, 0), (0, 4), (0, 0), (0, 3), (4, 8), (9, 12), (0, 0)],
+            vec![0; 7],
+            vec![1; 7],
+            vec![],
+            [(0, 0..3), (1, 3..7)].into_iter().collect(),
+        );
+        // the question token has the highest start logit but is not part of the text
+        let start_logits = array![0.0, 9.0, 0.0, 1.0, 5.0, 0.0, 0.0];
+        let end_logits = array![0.0, 0.0, 0.0, 3.0, 0.0, 6.0, 0.0];
+        let (start, end, score) =
+            best_span(&window, start_logits.view(), end_logits.view()).unwrap();
+        assert_eq!((start, end), (4, 5));
+        assert!(score > 0.0 && score <= 1.0);
+    }
+}
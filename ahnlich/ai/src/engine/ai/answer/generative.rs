@@ -0,0 +1,133 @@
+use super::{create_session, inference_error, load_tokenizer, tokenization_error};
+use crate::engine::ai::providers::ort_helper::{HFConfigReader, ModelRepo};
+use crate::error::AIProxyError;
+use ahnlich_types::ai::QuestionAnswer;
+use ndarray::{Array, Axis, Ix3};
+use ort::{Session, Value};
+use tokenizers::{Tokenizer, TruncationParams};
+
+const MAX_INPUT_TOKENS: usize = 512;
+const MAX_ANSWER_TOKENS: usize = 64;
+
+/// An encoder-decoder model that writes the answer greedily a token at a time
+pub(crate) struct GenerativeModel {
+    encoder: Session,
+    decoder: Session,
+    tokenizer: Tokenizer,
+    decoder_start_token_id: i64,
+    eos_token_id: i64,
+}
+
+impl GenerativeModel {
+    pub(crate) fn load(
+        repo: ModelRepo,
+        encoder_file: &str,
+        decoder_file: &str,
+    ) -> Result<Self, AIProxyError> {
+        let encoder = create_session(&repo, encoder_file)?;
+        let decoder = create_session(&repo, decoder_file)?;
+        let mut tokenizer = load_tokenizer(&repo)?;
+        // the question leads the prompt so only the end of the texts is cut off
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_INPUT_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(|e| AIProxyError::ModelTokenizerLoadError {
+                message: e.to_string(),
+            })?;
+        tokenizer.with_padding(None);
+        let config = HFConfigReader::new(repo).read("config.json")?;
+        let token_id = |key: &str| {
+            config[key]
+                .as_i64()
+                .ok_or_else(|| AIProxyError::ModelConfigLoadError {
+                    message: format!("{key} is missing from config.json"),
+                })
+        };
+        Ok(Self {
+            decoder_start_token_id: token_id("decoder_start_token_id")?,
+            eos_token_id: token_id("eos_token_id")?,
+            encoder,
+            decoder,
+            tokenizer,
+        })
+    }
+
+    pub(crate) fn answer(
+        &self,
+        question: &str,
+        contexts: &[String],
+    ) -> Result<QuestionAnswer, AIProxyError> {
+        let prompt = format!("question: {question} context: {}", contexts.join("\n"));
+        let encoding = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(tokenization_error)?;
+        let shape = (1, encoding.len());
+        let ids = Array::from_shape_vec(
+            shape,
+            encoding.get_ids().iter().map(|&id| id as i64).collect(),
+        )
+        .map_err(inference_error)?;
+        let mask = Array::from_shape_vec(
+            shape,
+            encoding
+                .get_attention_mask()
+                .iter()
+                .map(|&m| m as i64)
+                .collect(),
+        )
+        .map_err(inference_error)?;
+
+        let encoder_outputs = self.encoder.run(ort::inputs![
+            "input_ids" => Value::from_array(ids)?,
+            "attention_mask" => Value::from_array(mask.view())?
+        ]?)?;
+        let hidden_states = encoder_outputs["last_hidden_state"]
+            .try_extract_tensor::<f32>()?
+            .into_dimensionality::<Ix3>()
+            .map_err(inference_error)?
+            .to_owned();
+
+        // without cached attention the whole answer so far is decoded at every step, which is
+        // fine for the few tokens of an answer
+        let mut answer_ids = vec![self.decoder_start_token_id];
+        while answer_ids.len() <= MAX_ANSWER_TOKENS {
+            let decoder_ids = Array::from_shape_vec((1, answer_ids.len()), answer_ids.clone())
+                .map_err(inference_error)?;
+            let outputs = self.decoder.run(ort::inputs![
+                "input_ids" => Value::from_array(decoder_ids)?,
+                "encoder_attention_mask" => Value::from_array(mask.view())?,
+                "encoder_hidden_states" => Value::from_array(hidden_states.view())?
+            ]?)?;
+            let logits = outputs["logits"]
+                .try_extract_tensor::<f32>()?
+                .into_dimensionality::<Ix3>()
+                .map_err(inference_error)?;
+            let last = logits.index_axis(Axis(0), 0);
+            let last = last.index_axis(Axis(0), answer_ids.len() - 1);
+            let next = last
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(token, _)| token as i64)
+                .unwrap_or(self.eos_token_id);
+            if next == self.eos_token_id {
+                break;
+            }
+            answer_ids.push(next);
+        }
+
+        let answer_ids: Vec<u32> = answer_ids[1..].iter().map(|&id| id as u32).collect();
+        let text = self
+            .tokenizer
+            .decode(&answer_ids, true)
+            .map_err(tokenization_error)?;
+        Ok(QuestionAnswer {
+            text: text.trim().to_string(),
+            span: None,
+            score: None,
+        })
+    }
+}
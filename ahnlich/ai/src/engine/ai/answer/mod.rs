@@ -0,0 +1,114 @@
+//! Question answering over the text inputs retrieved for a question from a store. Extractive
+//! models pick the span of a single input that best answers the question while generative
+//! models, behind the `generative` feature, write an answer from all of them
+mod extractive;
+#[cfg(feature = "generative")]
+mod generative;
+
+use crate::cli::server::AnswerModel;
+use crate::engine::ai::providers::ort_helper::ModelRepo;
+use crate::error::AIProxyError;
+use ahnlich_types::ai::QuestionAnswer;
+use extractive::ExtractiveModel;
+use hf_hub::{api::sync::ApiBuilder, Cache};
+use ort::Session;
+use std::fmt;
+use std::path::Path;
+use std::thread::available_parallelism;
+use tokenizers::Tokenizer;
+
+pub(crate) enum AnswerEngine {
+    Extractive(ExtractiveModel),
+    #[cfg(feature = "generative")]
+    Generative(generative::GenerativeModel),
+}
+
+impl fmt::Debug for AnswerEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Extractive(_) => write!(f, "AnswerEngine::Extractive"),
+            #[cfg(feature = "generative")]
+            Self::Generative(_) => write!(f, "AnswerEngine::Generative"),
+        }
+    }
+}
+
+impl AnswerEngine {
+    /// Downloads the model into the model cache location if needed and loads it
+    pub(crate) fn load(model: AnswerModel, cache_location: &Path) -> Result<Self, AIProxyError> {
+        ort::init().commit()?;
+        let cache = Cache::new(cache_location.join("huggingface"));
+        let api = ApiBuilder::from_cache(cache)
+            .with_progress(true)
+            .build()
+            .map_err(|e| AIProxyError::APIBuilderError(e.to_string()))?;
+        let engine = match model {
+            AnswerModel::DistilBertBaseCasedSquad => {
+                let repo = ModelRepo::Hub(Box::new(
+                    api.model("Xenova/distilbert-base-cased-distilled-squad".to_string()),
+                ));
+                Self::Extractive(ExtractiveModel::load(&repo, "onnx/model.onnx")?)
+            }
+            #[cfg(feature = "generative")]
+            AnswerModel::FlanT5Small => {
+                let repo = ModelRepo::Hub(Box::new(api.model("Xenova/flan-t5-small".to_string())));
+                Self::Generative(generative::GenerativeModel::load(
+                    repo,
+                    "onnx/encoder_model.onnx",
+                    "onnx/decoder_model.onnx",
+                )?)
+            }
+        };
+        log::info!("{model} loaded for answering questions");
+        Ok(engine)
+    }
+
+    /// Answers a question from the texts retrieved for it, returning None when they are all empty
+    pub(crate) fn answer(
+        &self,
+        question: &str,
+        contexts: &[String],
+    ) -> Result<Option<QuestionAnswer>, AIProxyError> {
+        if contexts.iter().all(String::is_empty) {
+            return Ok(None);
+        }
+        match self {
+            Self::Extractive(model) => model.answer(question, contexts),
+            #[cfg(feature = "generative")]
+            Self::Generative(model) => model.answer(question, contexts).map(Some),
+        }
+    }
+}
+
+fn create_session(repo: &ModelRepo, weights_file: &str) -> Result<Session, AIProxyError> {
+    let model_file = repo
+        .get(weights_file)
+        .map_err(AIProxyError::APIBuilderError)?;
+    let threads = available_parallelism()
+        .map_err(|e| AIProxyError::APIBuilderError(e.to_string()))?
+        .get();
+    Ok(Session::builder()?
+        .with_intra_threads(threads)?
+        .commit_from_file(model_file)?)
+}
+
+fn load_tokenizer(repo: &ModelRepo) -> Result<Tokenizer, AIProxyError> {
+    let tokenizer_file =
+        repo.get("tokenizer.json")
+            .map_err(|e| AIProxyError::ModelTokenizerLoadError {
+                message: format!("failed to fetch tokenizer.json, {e}"),
+            })?;
+    Tokenizer::from_file(tokenizer_file).map_err(|e| AIProxyError::ModelTokenizerLoadError {
+        message: e.to_string(),
+    })
+}
+
+fn tokenization_error(error: impl fmt::Display) -> AIProxyError {
+    AIProxyError::ModelTokenizationError {
+        message: error.to_string(),
+    }
+}
+
+fn inference_error(error: impl fmt::Display) -> AIProxyError {
+    AIProxyError::ModelProviderRunInferenceError(error.to_string())
+}
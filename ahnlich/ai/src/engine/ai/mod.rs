@@ -1,2 +1,4 @@
+pub(crate) mod answer;
 pub mod models;
 pub mod providers;
+pub mod registry;
@@ -1,13 +1,16 @@
-use crate::cli::server::SupportedModels;
+use crate::cli::server::{ExecutionProvider, SupportedModels};
 use crate::engine::ai::providers::ort::ORTProvider;
 use crate::engine::ai::providers::ModelProviders;
 use crate::engine::ai::providers::ProviderTrait;
+use crate::engine::ai::registry::Modality;
 use crate::error::AIProxyError;
 use ahnlich_types::{
-    ai::{AIModel, AIStoreInputType},
+    ai::{AIStoreInputType, ImageFormat as AIImageFormat},
     keyval::StoreKey,
 };
-use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader, RgbImage};
 use ndarray::{Array, Ix3};
 use ndarray::{ArrayView, Ix4};
 use nonzero_ext::nonzero;
@@ -40,10 +43,10 @@ pub struct Model {
     pub embedding_size: NonZeroUsize,
 }
 
-impl From<&AIModel> for Model {
-    fn from(value: &AIModel) -> Self {
+impl From<&SupportedModels> for Model {
+    fn from(value: &SupportedModels) -> Self {
         match value {
-            AIModel::AllMiniLML6V2 => Self {
+            SupportedModels::AllMiniLML6V2 => Self {
                 model_type: ModelType::Text {
                     max_input_tokens: nonzero!(256usize),
                 },
@@ -52,7 +55,7 @@ impl From<&AIModel> for Model {
                 description: String::from("Sentence Transformer model, with 6 layers, version 2"),
                 embedding_size: nonzero!(384usize),
             },
-            AIModel::AllMiniLML12V2 => Self {
+            SupportedModels::AllMiniLML12V2 => Self {
                 model_type: ModelType::Text {
                     // Token size source: https://huggingface.co/sentence-transformers/all-MiniLM-L12-v2#intended-uses
                     max_input_tokens: nonzero!(256usize),
@@ -62,7 +65,7 @@ impl From<&AIModel> for Model {
                 description: String::from("Sentence Transformer model, with 12 layers, version 2."),
                 embedding_size: nonzero!(384usize),
             },
-            AIModel::BGEBaseEnV15 => Self {
+            SupportedModels::BGEBaseEnV15 => Self {
                 model_type: ModelType::Text {
                     // Token size source: https://huggingface.co/BAAI/bge-large-en/discussions/11#64e44de1623074ac850aa1ae
                     max_input_tokens: nonzero!(512usize),
@@ -74,7 +77,7 @@ impl From<&AIModel> for Model {
                 ),
                 embedding_size: nonzero!(768usize),
             },
-            AIModel::BGELargeEnV15 => Self {
+            SupportedModels::BGELargeEnV15 => Self {
                 model_type: ModelType::Text {
                     max_input_tokens: nonzero!(512usize),
                 },
@@ -85,7 +88,7 @@ impl From<&AIModel> for Model {
                 ),
                 embedding_size: nonzero!(1024usize),
             },
-            AIModel::Resnet50 => Self {
+            SupportedModels::Resnet50 => Self {
                 model_type: ModelType::Image {
                     expected_image_dimensions: (nonzero!(224usize), nonzero!(224usize)),
                 },
@@ -94,7 +97,7 @@ impl From<&AIModel> for Model {
                 description: String::from("Residual Networks model, with 50 layers."),
                 embedding_size: nonzero!(2048usize),
             },
-            AIModel::ClipVitB32Image => Self {
+            SupportedModels::ClipVitB32Image => Self {
                 model_type: ModelType::Image {
                     expected_image_dimensions: (nonzero!(224usize), nonzero!(224usize)),
                 },
@@ -105,7 +108,7 @@ impl From<&AIModel> for Model {
                 ),
                 embedding_size: nonzero!(512usize),
             },
-            AIModel::ClipVitB32Text => Self {
+            SupportedModels::ClipVitB32Text => Self {
                 model_type: ModelType::Text {
                     // Token size source: https://github.com/UKPLab/sentence-transformers/issues/1269
                     max_input_tokens: nonzero!(77usize),
@@ -118,6 +121,25 @@ impl From<&AIModel> for Model {
                 ),
                 embedding_size: nonzero!(512usize),
             },
+            SupportedModels::Custom(model) => Self {
+                // both are checked to be set for their modality when the registry is loaded
+                model_type: match model.modality {
+                    Modality::Text => ModelType::Text {
+                        max_input_tokens: model
+                            .max_input_tokens
+                            .expect("Text models must set max_input_tokens"),
+                    },
+                    Modality::Image => ModelType::Image {
+                        expected_image_dimensions: model
+                            .image_dimensions
+                            .expect("Image models must set image_dimensions"),
+                    },
+                },
+                provider: ModelProviders::ORT(ORTProvider::new()),
+                supported_model: value.clone(),
+                description: model.description.clone(),
+                embedding_size: model.embedding_size,
+            },
         }
     }
 }
@@ -168,15 +190,40 @@ impl Model {
     }
 
     pub fn setup_provider(&mut self, cache_location: &Path) {
-        let supported_model = self.supported_model;
         match &mut self.provider {
             ModelProviders::ORT(provider) => {
-                provider.set_model(&supported_model);
+                provider.set_model(&self.supported_model);
                 provider.set_cache_location(cache_location);
             }
         }
     }
 
+    pub fn setup_execution_providers(
+        &mut self,
+        execution_providers: Vec<ExecutionProvider>,
+        gpu_memory_limit: Option<usize>,
+    ) {
+        match &mut self.provider {
+            ModelProviders::ORT(provider) => {
+                provider.set_execution_providers(execution_providers, gpu_memory_limit);
+            }
+        }
+    }
+
+    /// Execution provider the model was loaded with
+    pub fn execution_provider(&self) -> Option<ExecutionProvider> {
+        match &self.provider {
+            ModelProviders::ORT(provider) => provider.execution_provider(),
+        }
+    }
+
+    /// Bytes of the weights the model was loaded from
+    pub fn weights_bytes(&self) -> usize {
+        match &self.provider {
+            ModelProviders::ORT(provider) => provider.weights_bytes(),
+        }
+    }
+
     pub fn load(&mut self) -> Result<(), AIProxyError> {
         match &mut self.provider {
             ModelProviders::ORT(provider) => {
@@ -254,6 +301,7 @@ pub struct ImageArray {
     array: Array<f32, Ix3>,
     image: DynamicImage,
     image_format: ImageFormat,
+    orientation: Orientation,
     onnx_transformed: bool,
 }
 
@@ -263,17 +311,21 @@ impl ImageArray {
             .with_guessed_format()
             .map_err(|_| AIProxyError::ImageBytesDecodeError)?;
 
-        let image_format = &img_reader
+        let image_format = img_reader
             .format()
             .ok_or(AIProxyError::ImageBytesDecodeError)?;
 
-        let image = img_reader
-            .decode()
+        let mut decoder = img_reader
+            .into_decoder()
             .map_err(|_| AIProxyError::ImageBytesDecodeError)?;
+        // images without readable EXIF metadata are left as is
+        let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+        let image =
+            DynamicImage::from_decoder(decoder).map_err(|_| AIProxyError::ImageBytesDecodeError)?;
 
         // Always convert to RGB8 format
         // https://github.com/Anush008/fastembed-rs/blob/cea92b6c8b877efda762393848d1c449a4eea126/src/image_embedding/utils.rs#L198
-        let image: DynamicImage = image.to_owned().into_rgb8().into();
+        let image: DynamicImage = image.into_rgb8().into();
         let (width, height) = image.dimensions();
 
         if width == 0 || height == 0 {
@@ -292,7 +344,8 @@ impl ImageArray {
         Ok(ImageArray {
             array,
             image,
-            image_format: image_format.to_owned(),
+            image_format,
+            orientation,
             onnx_transformed: false,
         })
     }
@@ -325,9 +378,9 @@ impl ImageArray {
         &self,
         width: u32,
         height: u32,
-        filter: Option<image::imageops::FilterType>,
+        filter: Option<FilterType>,
     ) -> Result<Self, AIProxyError> {
-        let filter_type = filter.unwrap_or(image::imageops::FilterType::CatmullRom);
+        let filter_type = filter.unwrap_or(FilterType::CatmullRom);
         let resized_img = self.image.resize_exact(width, height, filter_type);
         let channels = resized_img.color().channel_count();
         let shape = (height as usize, width as usize, channels as usize);
@@ -340,6 +393,7 @@ impl ImageArray {
             array,
             image: resized_img,
             image_format: self.image_format,
+            orientation: self.orientation,
             onnx_transformed: false,
         })
     }
@@ -357,10 +411,73 @@ impl ImageArray {
             array,
             image: cropped_img,
             image_format: self.image_format,
+            orientation: self.orientation,
             onnx_transformed: false,
         })
     }
 
+    /// Rotates and flips the image as described by its EXIF orientation
+    pub fn apply_exif_orientation(&self) -> Result<Self, AIProxyError> {
+        let mut oriented_img = self.image.clone();
+        oriented_img.apply_orientation(self.orientation);
+        let (width, height) = oriented_img.dimensions();
+        let channels = oriented_img.color().channel_count();
+        let shape = (height as usize, width as usize, channels as usize);
+
+        let flattened_pixels = oriented_img.clone().into_bytes();
+        let array = Array::from_shape_vec(shape, flattened_pixels)
+            .map_err(|_| AIProxyError::ImageOrientationError)?
+            .mapv(f32::from);
+        Ok(ImageArray {
+            array,
+            image: oriented_img,
+            image_format: self.image_format,
+            orientation: Orientation::NoTransforms,
+            onnx_transformed: false,
+        })
+    }
+
+    /// Scales the image to fit within width and height while keeping its aspect ratio, padding
+    /// the remaining area in black
+    pub fn letterbox(
+        &self,
+        width: u32,
+        height: u32,
+        filter: Option<FilterType>,
+    ) -> Result<Self, AIProxyError> {
+        let filter_type = filter.unwrap_or(FilterType::CatmullRom);
+        let scaled_img = self.image.resize(width, height, filter_type).into_rgb8();
+        let mut canvas = RgbImage::new(width, height);
+        let x = (width - scaled_img.width()) / 2;
+        let y = (height - scaled_img.height()) / 2;
+        image::imageops::overlay(&mut canvas, &scaled_img, x.into(), y.into());
+        let letterboxed_img: DynamicImage = canvas.into();
+        let channels = letterboxed_img.color().channel_count();
+        let shape = (height as usize, width as usize, channels as usize);
+
+        let flattened_pixels = letterboxed_img.clone().into_bytes();
+        let array = Array::from_shape_vec(shape, flattened_pixels)
+            .map_err(|_| AIProxyError::ImageResizeError)?
+            .mapv(f32::from);
+        Ok(ImageArray {
+            array,
+            image: letterboxed_img,
+            image_format: self.image_format,
+            orientation: self.orientation,
+            onnx_transformed: false,
+        })
+    }
+
+    /// Changes the format the image is encoded to by `get_bytes`
+    pub fn convert_format(mut self, format: AIImageFormat) -> Self {
+        self.image_format = match format {
+            AIImageFormat::Png => ImageFormat::Png,
+            AIImageFormat::Jpeg => ImageFormat::Jpeg,
+            AIImageFormat::Webp => ImageFormat::WebP,
+        };
+        self
+    }
+
     pub fn image_dim(&self) -> (NonZeroUsize, NonZeroUsize) {
         let shape = self.array.shape();
         match self.onnx_transformed {
@@ -1,8 +1,8 @@
 pub(crate) mod ort;
-mod ort_helper;
+pub(crate) mod ort_helper;
 pub mod processors;
 
-use crate::cli::server::SupportedModels;
+use crate::cli::server::{ExecutionProvider, SupportedModels};
 use crate::engine::ai::models::{InputAction, ModelInput};
 use crate::engine::ai::providers::ort::ORTProvider;
 use crate::error::AIProxyError;
@@ -18,6 +18,14 @@ pub enum ModelProviders {
 pub trait ProviderTrait: std::fmt::Debug + Send + Sync {
     fn set_cache_location(&mut self, location: &Path);
     fn set_model(&mut self, model: &SupportedModels);
+    fn set_execution_providers(
+        &mut self,
+        execution_providers: Vec<ExecutionProvider>,
+        gpu_memory_limit: Option<usize>,
+    );
+    fn execution_provider(&self) -> Option<ExecutionProvider>;
+    /// Bytes of the weights the model was loaded from, none before it is loaded
+    fn weights_bytes(&self) -> usize;
     fn load_model(&mut self) -> Result<(), AIProxyError>;
     fn get_model(&self) -> Result<(), AIProxyError>;
     fn run_inference(
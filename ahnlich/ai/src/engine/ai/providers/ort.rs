@@ -1,13 +1,18 @@
-use crate::cli::server::SupportedModels;
+use crate::cli::server::{ExecutionProvider, SupportedModels};
 use crate::engine::ai::models::{ImageArray, InputAction, ModelInput};
+use crate::engine::ai::providers::ort_helper::ModelRepo;
 use crate::engine::ai::providers::ProviderTrait;
+use crate::engine::ai::registry::{Modality, ModelSource, ProcessingPreset};
 use crate::error::AIProxyError;
 use fallible_collections::FallibleVec;
-use hf_hub::{api::sync::ApiBuilder, Cache};
+use hf_hub::{
+    api::sync::{Api, ApiBuilder},
+    Cache,
+};
 use itertools::Itertools;
 use ort::{
-    CUDAExecutionProvider, CoreMLExecutionProvider, DirectMLExecutionProvider,
-    TensorRTExecutionProvider,
+    CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
+    DirectMLExecutionProvider, ExecutionProviderDispatch, TensorRTExecutionProvider,
 };
 use ort::{Session, SessionOutputs, Value};
 use rayon::prelude::*;
@@ -25,13 +30,19 @@ use std::default::Default;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::thread::available_parallelism;
-use tokenizers::Encoding;
+use tokenizers::{Encoding, TruncationDirection};
 
 #[derive(Default)]
 pub struct ORTProvider {
     cache_location: Option<PathBuf>,
     cache_location_extension: PathBuf,
     supported_models: Option<SupportedModels>,
+    execution_providers: Vec<ExecutionProvider>,
+    gpu_memory_limit: Option<usize>,
+    // the first of `execution_providers` a session could be created with
+    execution_provider: Option<ExecutionProvider>,
+    // size of the weights file the session was created from
+    weights_bytes: usize,
     pub preprocessor: Option<ORTPreprocessor>,
     pub postprocessor: Option<ORTPostprocessor>,
     pub model: Option<ORTModel>,
@@ -43,21 +54,24 @@ impl fmt::Debug for ORTProvider {
             .field("cache_location", &self.cache_location)
             .field("cache_location_extension", &self.cache_location_extension)
             .field("supported_models", &self.supported_models)
+            .field("execution_providers", &self.execution_providers)
+            .field("execution_provider", &self.execution_provider)
+            .field("weights_bytes", &self.weights_bytes)
             .finish()
     }
 }
 
-#[derive(Default)]
 pub struct ORTImageModel {
-    repo_name: String,
+    source: ModelSource,
     weights_file: String,
     session: Option<Session>,
 }
 
-#[derive(Default)]
 pub struct ORTTextModel {
-    repo_name: String,
+    source: ModelSource,
     weights_file: String,
+    // where the tokenizer files are read from when they are not alongside the weights
+    tokenizer_source: Option<ModelSource>,
     session: Option<Session>,
 }
 
@@ -72,46 +86,71 @@ impl TryFrom<&SupportedModels> for ORTModel {
     fn try_from(model: &SupportedModels) -> Result<Self, Self::Error> {
         let model_type: Result<ORTModel, AIProxyError> = match model {
             SupportedModels::Resnet50 => Ok(ORTModel::Image(ORTImageModel {
-                repo_name: "Qdrant/resnet50-onnx".to_string(),
+                source: ModelSource::HfRepo("Qdrant/resnet50-onnx".to_string()),
                 weights_file: "model.onnx".to_string(),
-                ..Default::default()
+                session: None,
             })),
             SupportedModels::ClipVitB32Image => Ok(ORTModel::Image(ORTImageModel {
-                repo_name: "Qdrant/clip-ViT-B-32-vision".to_string(),
+                source: ModelSource::HfRepo("Qdrant/clip-ViT-B-32-vision".to_string()),
                 weights_file: "model.onnx".to_string(),
-                ..Default::default()
+                session: None,
             })),
             SupportedModels::ClipVitB32Text => Ok(ORTModel::Text(ORTTextModel {
-                repo_name: "Qdrant/clip-ViT-B-32-text".to_string(),
+                source: ModelSource::HfRepo("Qdrant/clip-ViT-B-32-text".to_string()),
                 weights_file: "model.onnx".to_string(),
-                ..Default::default()
+                tokenizer_source: None,
+                session: None,
             })),
             SupportedModels::AllMiniLML6V2 => Ok(ORTModel::Text(ORTTextModel {
-                repo_name: "Qdrant/all-MiniLM-L6-v2-onnx".to_string(),
+                source: ModelSource::HfRepo("Qdrant/all-MiniLM-L6-v2-onnx".to_string()),
                 weights_file: "model.onnx".to_string(),
-                ..Default::default()
+                tokenizer_source: None,
+                session: None,
             })),
             SupportedModels::AllMiniLML12V2 => Ok(ORTModel::Text(ORTTextModel {
-                repo_name: "Xenova/all-MiniLM-L12-v2".to_string(),
+                source: ModelSource::HfRepo("Xenova/all-MiniLM-L12-v2".to_string()),
                 weights_file: "onnx/model.onnx".to_string(),
-                ..Default::default()
+                tokenizer_source: None,
+                session: None,
             })),
             SupportedModels::BGEBaseEnV15 => Ok(ORTModel::Text(ORTTextModel {
-                repo_name: "Xenova/bge-base-en-v1.5".to_string(),
+                source: ModelSource::HfRepo("Xenova/bge-base-en-v1.5".to_string()),
                 weights_file: "onnx/model.onnx".to_string(),
-                ..Default::default()
+                tokenizer_source: None,
+                session: None,
             })),
             SupportedModels::BGELargeEnV15 => Ok(ORTModel::Text(ORTTextModel {
-                repo_name: "Xenova/bge-large-en-v1.5".to_string(),
+                source: ModelSource::HfRepo("Xenova/bge-large-en-v1.5".to_string()),
                 weights_file: "onnx/model.onnx".to_string(),
-                ..Default::default()
+                tokenizer_source: None,
+                session: None,
             })),
+            SupportedModels::Custom(model) => Ok(match model.modality {
+                Modality::Text => ORTModel::Text(ORTTextModel {
+                    source: model.source.clone(),
+                    weights_file: model.weights_file.clone(),
+                    tokenizer_source: model.tokenizer.clone(),
+                    session: None,
+                }),
+                Modality::Image => ORTModel::Image(ORTImageModel {
+                    source: model.source.clone(),
+                    weights_file: model.weights_file.clone(),
+                    session: None,
+                }),
+            }),
         };
 
         model_type
     }
 }
 
+fn open_model_repo(api: &Api, source: &ModelSource) -> ModelRepo {
+    match source {
+        ModelSource::HfRepo(repo_name) => ModelRepo::Hub(Box::new(api.model(repo_name.clone()))),
+        ModelSource::Path(directory) => ModelRepo::Local(directory.clone()),
+    }
+}
+
 impl ORTProvider {
     pub(crate) fn new() -> Self {
         Self {
@@ -119,11 +158,77 @@ impl ORTProvider {
             cache_location_extension: PathBuf::from("huggingface"),
             preprocessor: None,
             supported_models: None,
+            execution_providers: vec![ExecutionProvider::CPU],
+            gpu_memory_limit: None,
+            execution_provider: None,
+            weights_bytes: 0,
             model: None,
             postprocessor: None,
         }
     }
 
+    fn execution_provider_dispatch(
+        &self,
+        execution_provider: ExecutionProvider,
+    ) -> ExecutionProviderDispatch {
+        let dispatch = match execution_provider {
+            ExecutionProvider::TensorRT => {
+                let mut provider = TensorRTExecutionProvider::default();
+                if let Some(limit) = self.gpu_memory_limit {
+                    provider = provider.with_max_workspace_size(limit);
+                }
+                provider.build()
+            }
+            ExecutionProvider::CUDA => {
+                let mut provider = CUDAExecutionProvider::default();
+                if let Some(limit) = self.gpu_memory_limit {
+                    provider = provider.with_memory_limit(limit);
+                }
+                provider.build()
+            }
+            ExecutionProvider::DirectML => DirectMLExecutionProvider::default().build(),
+            ExecutionProvider::CoreML => CoreMLExecutionProvider::default().build(),
+            ExecutionProvider::CPU => CPUExecutionProvider::default().build(),
+        };
+        dispatch.error_on_failure()
+    }
+
+    /// Creates a session with the first execution provider that can be registered, so a model
+    /// falls back to the next provider when e.g. CUDA fails to initialize
+    fn create_session(
+        &mut self,
+        supported_model: &SupportedModels,
+        model_file: &Path,
+        threads: usize,
+    ) -> Result<Session, AIProxyError> {
+        for execution_provider in self.execution_providers.clone() {
+            let session = Session::builder()
+                .and_then(|builder| builder.with_intra_threads(threads))
+                .and_then(|builder| {
+                    builder.with_execution_providers([
+                        self.execution_provider_dispatch(execution_provider)
+                    ])
+                })
+                .and_then(|builder| builder.commit_from_file(model_file));
+            match session {
+                Ok(session) => {
+                    log::info!("{supported_model} is running on {execution_provider}");
+                    self.execution_provider = Some(execution_provider);
+                    self.weights_bytes = std::fs::metadata(model_file)
+                        .map(|metadata| metadata.len() as usize)
+                        .unwrap_or_default();
+                    return Ok(session);
+                }
+                Err(err) => log::warn!(
+                    "{supported_model} could not be run on {execution_provider}, falling back: {err}"
+                ),
+            }
+        }
+        Err(AIProxyError::ExecutionProviderError(
+            self.execution_providers.iter().join(", "),
+        ))
+    }
+
     pub fn preprocess_images(
         &self,
         data: Vec<ImageArray>,
@@ -133,7 +238,7 @@ impl ORTProvider {
                 let output_data = preprocessor.process(data).map_err(|e| {
                     AIProxyError::ModelProviderPreprocessingError(format!(
                         "Preprocessing failed for {:?} with error: {}",
-                        self.supported_models.unwrap().to_string(),
+                        self.supported_models.as_ref().unwrap().to_string(),
                         e
                     ))
                 })?;
@@ -146,21 +251,35 @@ impl ORTProvider {
     pub fn preprocess_texts(
         &self,
         data: Vec<String>,
-        truncate: bool,
+        truncation: Option<TruncationDirection>,
+        max_length: usize,
     ) -> Result<Vec<Encoding>, AIProxyError> {
         match &self.preprocessor {
             Some(ORTPreprocessor::Text(preprocessor)) => {
-                let output_data = preprocessor.process(data, truncate).map_err(|e| {
-                    AIProxyError::ModelProviderPreprocessingError(format!(
-                        "Preprocessing failed for {:?} with error: {}",
-                        self.supported_models.unwrap().to_string(),
-                        e
-                    ))
-                })?;
+                let output_data =
+                    preprocessor
+                        .process(data, truncation, max_length)
+                        .map_err(|e| {
+                            AIProxyError::ModelProviderPreprocessingError(format!(
+                                "Preprocessing failed for {:?} with error: {}",
+                                self.supported_models.as_ref().unwrap().to_string(),
+                                e
+                            ))
+                        })?;
                 Ok(output_data)
             }
             _ => Err(AIProxyError::ModelPreprocessingError {
-                model_name: self.supported_models.unwrap().to_string(),
+                model_name: self.supported_models.as_ref().unwrap().to_string(),
+                message: "Preprocessor not initialized".to_string(),
+            }),
+        }
+    }
+
+    pub fn pad_encodings(&self, encodings: &mut [Encoding]) -> Result<(), AIProxyError> {
+        match &self.preprocessor {
+            Some(ORTPreprocessor::Text(preprocessor)) => preprocessor.pad(encodings),
+            _ => Err(AIProxyError::ModelPreprocessingError {
+                model_name: self.supported_models.as_ref().unwrap().to_string(),
                 message: "Preprocessor not initialized".to_string(),
             }),
         }
@@ -178,14 +297,14 @@ impl ORTProvider {
                     .map_err(|e| {
                         AIProxyError::ModelProviderPostprocessingError(format!(
                             "Postprocessing failed for {:?} with error: {}",
-                            self.supported_models.unwrap().to_string(),
+                            self.supported_models.as_ref().unwrap().to_string(),
                             e
                         ))
                     })?;
                 Ok(output_data)
             }
             _ => Err(AIProxyError::ModelPostprocessingError {
-                model_name: self.supported_models.unwrap().to_string(),
+                model_name: self.supported_models.as_ref().unwrap().to_string(),
                 message: "Postprocessor not initialized".to_string(),
             }),
         }
@@ -200,14 +319,14 @@ impl ORTProvider {
                 let output_data = postprocessor.process(session_output).map_err(|e| {
                     AIProxyError::ModelProviderPostprocessingError(format!(
                         "Postprocessing failed for {:?} with error: {}",
-                        self.supported_models.unwrap().to_string(),
+                        self.supported_models.as_ref().unwrap().to_string(),
                         e
                     ))
                 })?;
                 Ok(output_data)
             }
             _ => Err(AIProxyError::ModelPostprocessingError {
-                model_name: self.supported_models.unwrap().to_string(),
+                model_name: self.supported_models.as_ref().unwrap().to_string(),
                 message: "Postprocessor not initialized".to_string(),
             }),
         }
@@ -221,18 +340,18 @@ impl ORTProvider {
             Some(ORTModel::Image(model)) => model,
             _ => {
                 return Err(AIProxyError::AIModelNotSupported {
-                    model_name: self.supported_models.unwrap().to_string(),
+                    model_name: self.supported_models.as_ref().unwrap().to_string(),
                 })
             }
         };
         match &model.session {
             Some(session) => {
-                let input_param = match self.supported_models.unwrap() {
-                    SupportedModels::Resnet50 => "input",
-                    SupportedModels::ClipVitB32Image => "pixel_values",
+                let input_param = match self.supported_models.as_ref().map(|m| m.preset()) {
+                    Some(ProcessingPreset::Resnet) => "input",
+                    Some(ProcessingPreset::ClipImage) => "pixel_values",
                     _ => {
                         return Err(AIProxyError::AIModelNotSupported {
-                            model_name: self.supported_models.unwrap().to_string(),
+                            model_name: self.supported_models.as_ref().unwrap().to_string(),
                         })
                     }
                 };
@@ -260,7 +379,7 @@ impl ORTProvider {
             Some(ORTModel::Text(model)) => model,
             _ => {
                 return Err(AIProxyError::AIModelNotSupported {
-                    model_name: self.supported_models.unwrap().to_string(),
+                    model_name: self.supported_models.as_ref().unwrap().to_string(),
                 })
             }
         };
@@ -350,26 +469,33 @@ impl ProviderTrait for ORTProvider {
     }
 
     fn set_model(&mut self, model: &SupportedModels) {
-        self.supported_models = Some(*model);
+        self.supported_models = Some(model.clone());
+    }
+
+    fn set_execution_providers(
+        &mut self,
+        execution_providers: Vec<ExecutionProvider>,
+        gpu_memory_limit: Option<usize>,
+    ) {
+        self.execution_providers = execution_providers;
+        self.gpu_memory_limit = gpu_memory_limit;
+    }
+
+    fn execution_provider(&self) -> Option<ExecutionProvider> {
+        self.execution_provider
+    }
+
+    fn weights_bytes(&self) -> usize {
+        self.weights_bytes
     }
 
     fn load_model(&mut self) -> Result<(), AIProxyError> {
-        ort::init()
-            .with_execution_providers([
-                // Prefer TensorRT over CUDA.
-                TensorRTExecutionProvider::default().build(),
-                CUDAExecutionProvider::default().build(),
-                // Use DirectML on Windows if NVIDIA EPs are not available
-                DirectMLExecutionProvider::default().build(),
-                // Or use ANE on Apple platforms
-                CoreMLExecutionProvider::default().build(),
-            ])
-            .commit()?;
+        ort::init().commit()?;
 
         let Some(cache_location) = self.cache_location.clone() else {
             return Err(AIProxyError::CacheLocationNotInitiailized);
         };
-        let Some(supported_model) = self.supported_models else {
+        let Some(supported_model) = self.supported_models.clone() else {
             return Err(AIProxyError::AIModelNotInitialized);
         };
         let ort_model = ORTModel::try_from(&supported_model)?;
@@ -387,46 +513,49 @@ impl ProviderTrait for ORTProvider {
         match ort_model {
             ORTModel::Image(ORTImageModel {
                 weights_file,
-                repo_name,
+                source,
                 ..
             }) => {
-                let model_repo = api.model(repo_name.clone());
+                let model_repo = open_model_repo(&api, &source);
                 let model_file_reference = model_repo
                     .get(&weights_file)
-                    .map_err(|e| AIProxyError::APIBuilderError(e.to_string()))?;
-                let session = Session::builder()?
-                    .with_intra_threads(threads)?
-                    .commit_from_file(model_file_reference)?;
+                    .map_err(AIProxyError::APIBuilderError)?;
+                let session =
+                    self.create_session(&supported_model, &model_file_reference, threads)?;
                 self.model = Some(ORTModel::Image(ORTImageModel {
-                    repo_name,
+                    source,
                     weights_file,
                     session: Some(session),
                 }));
-                let preprocessor =
-                    ORTImagePreprocessor::load(self.supported_models.unwrap(), model_repo)?;
+                let preprocessor = ORTImagePreprocessor::load(supported_model.clone(), model_repo)?;
                 self.preprocessor = Some(ORTPreprocessor::Image(preprocessor));
                 let postprocessor = ORTImagePostprocessor::load(supported_model)?;
                 self.postprocessor = Some(ORTPostprocessor::Image(postprocessor));
             }
             ORTModel::Text(ORTTextModel {
                 weights_file,
-                repo_name,
+                source,
+                tokenizer_source,
                 ..
             }) => {
-                let model_repo = api.model(repo_name.clone());
+                let model_repo = open_model_repo(&api, &source);
                 let model_file_reference = model_repo
                     .get(&weights_file)
-                    .map_err(|e| AIProxyError::APIBuilderError(e.to_string()))?;
-                let session = Session::builder()?
-                    .with_intra_threads(threads)?
-                    .commit_from_file(model_file_reference)?;
+                    .map_err(AIProxyError::APIBuilderError)?;
+                let session =
+                    self.create_session(&supported_model, &model_file_reference, threads)?;
+                let tokenizer_repo = match &tokenizer_source {
+                    Some(tokenizer_source) => open_model_repo(&api, tokenizer_source),
+                    None => model_repo,
+                };
                 self.model = Some(ORTModel::Text(ORTTextModel {
-                    repo_name,
+                    source,
                     weights_file,
+                    tokenizer_source,
                     session: Some(session),
                 }));
                 let preprocessor =
-                    ORTTextPreprocessor::load(self.supported_models.unwrap(), model_repo)?;
+                    ORTTextPreprocessor::load(supported_model.clone(), tokenizer_repo)?;
                 self.preprocessor = Some(ORTPreprocessor::Text(preprocessor));
                 let postprocessor = ORTTextPostprocessor::load(supported_model)?;
                 self.postprocessor = Some(ORTPostprocessor::Text(postprocessor));
@@ -441,8 +570,9 @@ impl ProviderTrait for ORTProvider {
         };
         let supported_model = self
             .supported_models
+            .as_ref()
             .ok_or(AIProxyError::AIModelNotInitialized)?;
-        let ort_model = ORTModel::try_from(&supported_model)?;
+        let ort_model = ORTModel::try_from(supported_model)?;
 
         let cache = Cache::new(cache_location);
         let api = ApiBuilder::from_cache(cache)
@@ -450,22 +580,21 @@ impl ProviderTrait for ORTProvider {
             .build()
             .map_err(|e| AIProxyError::APIBuilderError(e.to_string()))?;
 
-        let (repo_name, weights_file) = match ort_model {
+        let (source, weights_file) = match ort_model {
             ORTModel::Image(ORTImageModel {
-                repo_name,
+                source,
                 weights_file,
                 ..
-            }) => (repo_name, weights_file),
+            }) => (source, weights_file),
             ORTModel::Text(ORTTextModel {
-                repo_name,
+                source,
                 weights_file,
                 ..
-            }) => (repo_name, weights_file),
+            }) => (source, weights_file),
         };
-        let model_repo = api.model(repo_name);
-        model_repo
+        open_model_repo(&api, &source)
             .get(&weights_file)
-            .map_err(|e| AIProxyError::APIBuilderError(e.to_string()))?;
+            .map_err(AIProxyError::APIBuilderError)?;
         Ok(())
     }
 
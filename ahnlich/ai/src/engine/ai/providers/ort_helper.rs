@@ -26,13 +26,35 @@ pub fn read_file_to_bytes(file: &PathBuf) -> Result<Vec<u8>, AIProxyError> {
     Ok(buffer)
 }
 
+/// The files of a model, fetched from a Hugging Face repository or read from a local directory
+pub enum ModelRepo {
+    Hub(Box<ApiRepo>),
+    Local(PathBuf),
+}
+
+impl ModelRepo {
+    pub fn get(&self, filename: &str) -> Result<PathBuf, String> {
+        match self {
+            ModelRepo::Hub(repo) => repo.get(filename).map_err(|e| e.to_string()),
+            ModelRepo::Local(directory) => {
+                let file = directory.join(filename);
+                if file.is_file() {
+                    Ok(file)
+                } else {
+                    Err(format!("{} does not exist", file.display()))
+                }
+            }
+        }
+    }
+}
+
 pub struct HFConfigReader {
-    model_repo: ApiRepo,
+    model_repo: ModelRepo,
     cache: HashMap<String, Result<serde_json::Value, AIProxyError>>,
 }
 
 impl HFConfigReader {
-    pub fn new(model_repo: ApiRepo) -> Self {
+    pub fn new(model_repo: ModelRepo) -> Self {
         Self {
             model_repo,
             cache: HashMap::new(),
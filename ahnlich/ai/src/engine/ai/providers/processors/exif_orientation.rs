@@ -0,0 +1,23 @@
+use crate::engine::ai::models::ImageArray;
+use crate::engine::ai::providers::processors::{Preprocessor, PreprocessorData};
+use crate::error::AIProxyError;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+pub struct ExifOrientation;
+
+impl Preprocessor for ExifOrientation {
+    fn process(&self, data: PreprocessorData) -> Result<PreprocessorData, AIProxyError> {
+        match data {
+            PreprocessorData::ImageArray(mut arrays) => {
+                let processed = arrays
+                    .par_iter_mut()
+                    .map(|image| image.apply_exif_orientation())
+                    .collect::<Result<Vec<ImageArray>, AIProxyError>>();
+                Ok(PreprocessorData::ImageArray(processed?))
+            }
+            _ => Err(AIProxyError::ImageArrayToNdArrayError {
+                message: "ExifOrientation failed. Expected ImageArray, got NdArray3C".to_string(),
+            }),
+        }
+    }
+}
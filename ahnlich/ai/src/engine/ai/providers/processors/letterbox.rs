@@ -0,0 +1,36 @@
+use crate::engine::ai::models::ImageArray;
+use crate::engine::ai::providers::processors::{Preprocessor, PreprocessorData};
+use crate::error::AIProxyError;
+use image::imageops::FilterType;
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+
+pub struct Letterbox {
+    size: (u32, u32), // (width, height)
+    resample: FilterType,
+}
+
+impl Letterbox {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            size: (width, height),
+            resample: FilterType::CatmullRom,
+        }
+    }
+}
+
+impl Preprocessor for Letterbox {
+    fn process(&self, data: PreprocessorData) -> Result<PreprocessorData, AIProxyError> {
+        match data {
+            PreprocessorData::ImageArray(mut arrays) => {
+                let processed = arrays
+                    .par_iter_mut()
+                    .map(|image| image.letterbox(self.size.0, self.size.1, Some(self.resample)))
+                    .collect::<Result<Vec<ImageArray>, AIProxyError>>();
+                Ok(PreprocessorData::ImageArray(processed?))
+            }
+            _ => Err(AIProxyError::ImageArrayToNdArrayError {
+                message: "Letterbox failed. Expected ImageArray, got NdArray3C".to_string(),
+            }),
+        }
+    }
+}
@@ -5,7 +5,9 @@ use ort::SessionOutputs;
 use tokenizers::Encoding;
 
 pub mod center_crop;
+pub mod exif_orientation;
 pub mod imagearray_to_ndarray;
+pub mod letterbox;
 pub mod normalize;
 mod onnx_output_transform;
 pub mod pooling;
@@ -33,6 +35,15 @@ pub enum PreprocessorData {
 }
 
 impl PreprocessorData {
+    pub fn into_image_array(self) -> Result<Vec<ImageArray>, AIProxyError> {
+        match self {
+            PreprocessorData::ImageArray(arrays) => Ok(arrays),
+            _ => Err(AIProxyError::ModelProviderPreprocessingError(
+                "`into_image_array` only works for PreprocessorData::ImageArray".to_string(),
+            )),
+        }
+    }
+
     pub fn into_ndarray3c(self) -> Result<Array<f32, Ix4>, AIProxyError> {
         match self {
             PreprocessorData::NdArray3C(array) => Ok(array),
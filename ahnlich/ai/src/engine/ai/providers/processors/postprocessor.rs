@@ -3,6 +3,7 @@ use crate::engine::ai::providers::processors::normalize::VectorNormalize;
 use crate::engine::ai::providers::processors::onnx_output_transform::OnnxOutputTransform;
 use crate::engine::ai::providers::processors::pooling::{MeanPooling, Pooling, RegularPooling};
 use crate::engine::ai::providers::processors::{Postprocessor, PostprocessorData};
+use crate::engine::ai::registry::ProcessingPreset;
 use crate::error::AIProxyError;
 use ndarray::{Array, Ix2};
 use ort::SessionOutputs;
@@ -23,25 +24,24 @@ pub struct ORTTextPostprocessor {
 
 impl ORTTextPostprocessor {
     pub fn load(supported_model: SupportedModels) -> Result<Self, AIProxyError> {
-        let output_transform = match supported_model {
-            SupportedModels::AllMiniLML6V2
-            | SupportedModels::AllMiniLML12V2
-            | SupportedModels::BGEBaseEnV15
-            | SupportedModels::BGELargeEnV15 => OnnxOutputTransform::new("last_hidden_state"),
-            SupportedModels::ClipVitB32Text => OnnxOutputTransform::new("text_embeds"),
+        let output_transform = match supported_model.preset() {
+            ProcessingPreset::MeanPooling | ProcessingPreset::ClsPooling => {
+                OnnxOutputTransform::new("last_hidden_state")
+            }
+            ProcessingPreset::ClipText => OnnxOutputTransform::new("text_embeds"),
             _ => Err(AIProxyError::ModelPostprocessingError {
                 model_name: supported_model.to_string(),
                 message: "Unsupported model for ORTTextPostprocessor".to_string(),
             })?,
         };
-        let (pooling, normalize) = match supported_model {
-            SupportedModels::AllMiniLML6V2 | SupportedModels::AllMiniLML12V2 => {
+        let (pooling, normalize) = match supported_model.preset() {
+            ProcessingPreset::MeanPooling => {
                 (Pooling::Mean(MeanPoolingBuilder), Some(VectorNormalize))
             }
-            SupportedModels::BGEBaseEnV15 | SupportedModels::BGELargeEnV15 => {
+            ProcessingPreset::ClsPooling => {
                 (Pooling::Regular(RegularPooling), Some(VectorNormalize))
             }
-            SupportedModels::ClipVitB32Text => (Pooling::Mean(MeanPoolingBuilder), None),
+            ProcessingPreset::ClipText => (Pooling::Mean(MeanPoolingBuilder), None),
             _ => {
                 return Err(AIProxyError::ModelPostprocessingError {
                     model_name: supported_model.to_string(),
@@ -102,17 +102,17 @@ pub struct ORTImagePostprocessor {
 
 impl ORTImagePostprocessor {
     pub fn load(supported_model: SupportedModels) -> Result<Self, AIProxyError> {
-        let output_transform = match supported_model {
-            SupportedModels::Resnet50 => OnnxOutputTransform::new("output"),
-            SupportedModels::ClipVitB32Image => OnnxOutputTransform::new("image_embeds"),
+        let output_transform = match supported_model.preset() {
+            ProcessingPreset::Resnet => OnnxOutputTransform::new("output"),
+            ProcessingPreset::ClipImage => OnnxOutputTransform::new("image_embeds"),
             _ => Err(AIProxyError::ModelPostprocessingError {
                 model_name: supported_model.to_string(),
                 message: "Unsupported model for ORTImagePostprocessor".to_string(),
             })?,
         };
-        let normalize = match supported_model {
-            SupportedModels::Resnet50 => Ok(Some(VectorNormalize)),
-            SupportedModels::ClipVitB32Image => Ok(None),
+        let normalize = match supported_model.preset() {
+            ProcessingPreset::Resnet => Ok(Some(VectorNormalize)),
+            ProcessingPreset::ClipImage => Ok(None),
             _ => Err(AIProxyError::ModelPostprocessingError {
                 model_name: supported_model.to_string(),
                 message: "Unsupported model for ORTImagePostprocessor".to_string(),
@@ -1,6 +1,6 @@
 use crate::cli::server::SupportedModels;
 use crate::engine::ai::models::ImageArray;
-use crate::engine::ai::providers::ort_helper::HFConfigReader;
+use crate::engine::ai::providers::ort_helper::{HFConfigReader, ModelRepo};
 use crate::engine::ai::providers::processors::center_crop::CenterCrop;
 use crate::engine::ai::providers::processors::imagearray_to_ndarray::ImageArrayToNdArray;
 use crate::engine::ai::providers::processors::normalize::ImageNormalize;
@@ -9,10 +9,9 @@ use crate::engine::ai::providers::processors::resize::Resize;
 use crate::engine::ai::providers::processors::tokenize::{Tokenize, TokenizerFiles};
 use crate::engine::ai::providers::processors::{Preprocessor, PreprocessorData};
 use crate::error::AIProxyError;
-use hf_hub::api::sync::ApiRepo;
 use ndarray::{Array, Ix4};
 use std::sync::{Arc, Mutex};
-use tokenizers::Encoding;
+use tokenizers::{Encoding, TruncationDirection};
 
 pub enum ORTPreprocessor {
     Image(ORTImagePreprocessor),
@@ -31,7 +30,7 @@ pub struct ORTImagePreprocessor {
 impl ORTImagePreprocessor {
     pub fn load(
         supported_model: SupportedModels,
-        model_repo: ApiRepo,
+        model_repo: ModelRepo,
     ) -> Result<Self, AIProxyError> {
         let imagearray_to_ndarray = ImageArrayToNdArray;
 
@@ -127,7 +126,7 @@ pub struct ORTTextPreprocessor {
 impl ORTTextPreprocessor {
     pub fn load(
         supported_models: SupportedModels,
-        model_repo: ApiRepo,
+        model_repo: ModelRepo,
     ) -> Result<ORTTextPreprocessor, AIProxyError> {
         let tokenizer_files = TokenizerFiles {
             tokenizer_file: "tokenizer.json".to_string(),
@@ -148,7 +147,8 @@ impl ORTTextPreprocessor {
     pub fn process(
         &self,
         data: Vec<String>,
-        truncate: bool,
+        truncation: Option<TruncationDirection>,
+        max_length: usize,
     ) -> Result<Vec<Encoding>, AIProxyError> {
         let mut data = PreprocessorData::Text(data);
         let mut tokenize =
@@ -158,7 +158,7 @@ impl ORTTextPreprocessor {
                     model_name: self.model.to_string(),
                     message: "Failed to acquire lock on tokenize.".to_string(),
                 })?;
-        let _ = tokenize.set_truncate(truncate);
+        tokenize.set_truncation(truncation, max_length)?;
         data = tokenize
             .process(data)
             .map_err(|e| AIProxyError::ModelPreprocessingError {
@@ -174,4 +174,15 @@ impl ORTTextPreprocessor {
             }),
         }
     }
+
+    pub fn pad(&self, encodings: &mut [Encoding]) -> Result<(), AIProxyError> {
+        let tokenize = self
+            .tokenize
+            .lock()
+            .map_err(|_| AIProxyError::ModelPreprocessingError {
+                model_name: self.model.to_string(),
+                message: "Failed to acquire lock on tokenize.".to_string(),
+            })?;
+        tokenize.pad(encodings)
+    }
 }
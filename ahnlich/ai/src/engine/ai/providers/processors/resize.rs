@@ -12,6 +12,13 @@ pub struct Resize {
 }
 
 impl Resize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            size: (width, height),
+            resample: FilterType::CatmullRom,
+        }
+    }
+
     pub fn initialize(config: &serde_json::Value) -> Result<Option<Self>, AIProxyError> {
         if !config["do_resize"].as_bool().unwrap_or(false) {
             return Ok(None);
@@ -1,15 +1,17 @@
-use crate::engine::ai::providers::ort_helper::{read_file_to_bytes, HFConfigReader};
+use crate::engine::ai::providers::ort_helper::{read_file_to_bytes, HFConfigReader, ModelRepo};
 use crate::engine::ai::providers::processors::{Preprocessor, PreprocessorData};
 use crate::error::AIProxyError;
-use hf_hub::api::sync::ApiRepo;
 use serde_json::Value;
 use tokenizers::decoders::bpe::BPEDecoder;
-use tokenizers::{AddedToken, PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+use tokenizers::utils::padding::pad_encodings;
+use tokenizers::{
+    AddedToken, Encoding, PaddingParams, PaddingStrategy, Tokenizer, TruncationDirection,
+    TruncationParams,
+};
 
 pub struct Tokenize {
     tokenizer: Tokenizer,
     model_max_length: usize,
-    truncate: bool,
 }
 
 pub struct TokenizeArtifacts {
@@ -22,7 +24,7 @@ pub struct TokenizeArtifacts {
 impl Tokenize {
     pub fn download_artifacts(
         tokenizer_files: TokenizerFiles,
-        model_repo: ApiRepo,
+        model_repo: ModelRepo,
     ) -> Result<TokenizeArtifacts, AIProxyError> {
         let tokenizer_bytes = read_file_to_bytes(
             &model_repo
@@ -45,7 +47,7 @@ impl Tokenize {
 
     pub fn initialize(
         tokenizer_files: TokenizerFiles,
-        model_repo: ApiRepo,
+        model_repo: ModelRepo,
     ) -> Result<Self, AIProxyError> {
         let artifacts = Self::download_artifacts(tokenizer_files, model_repo)?;
         let mut tokenizer = Tokenizer::from_bytes(artifacts.tokenizer_bytes).map_err(|_| {
@@ -111,15 +113,32 @@ impl Tokenize {
         Ok(Self {
             tokenizer: tokenizer.into(),
             model_max_length,
-            truncate: true,
         })
     }
 
-    pub fn set_truncate(&mut self, truncate: bool) -> Result<(), AIProxyError> {
-        let tokenizer = if truncate {
+    /// Pads encodings tokenized separately to the same length, as if they were tokenized together
+    pub fn pad(&self, encodings: &mut [Encoding]) -> Result<(), AIProxyError> {
+        if let Some(params) = self.tokenizer.get_padding() {
+            pad_encodings(encodings, params).map_err(|_| AIProxyError::ModelTokenizationError {
+                message: "Padding encodings failed.".to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Truncates texts longer than `max_length` tokens, or the max length of the tokenizer if it
+    /// is shorter, from the given direction. The truncated tokens are kept in the overflowing
+    /// encodings of each encoding
+    pub fn set_truncation(
+        &mut self,
+        direction: Option<TruncationDirection>,
+        max_length: usize,
+    ) -> Result<(), AIProxyError> {
+        let tokenizer = if let Some(direction) = direction {
             self.tokenizer
                 .with_truncation(Some(TruncationParams {
-                    max_length: self.model_max_length,
+                    max_length: max_length.min(self.model_max_length),
+                    direction,
                     ..Default::default()
                 }))
                 .map_err(|_| AIProxyError::ModelTokenizerLoadError {
@@ -132,7 +151,6 @@ impl Tokenize {
                 }
             })?
         };
-        self.truncate = truncate;
         self.tokenizer = tokenizer.clone().into();
         Ok(())
     }
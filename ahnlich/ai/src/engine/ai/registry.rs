@@ -0,0 +1,224 @@
+//! The model registry lets operators serve their own ONNX embedding models next to the built in
+//! ones. Models are declared in a JSON file passed with `--model-registry`, e.g.
+//!
+//! ```json
+//! {
+//!   "models": [
+//!     {
+//!       "name": "multi-qa-minilm",
+//!       "source": { "hf_repo": "Xenova/multi-qa-MiniLM-L6-cos-v1" },
+//!       "weights_file": "onnx/model.onnx",
+//!       "modality": "text",
+//!       "embedding_size": 384,
+//!       "max_input_tokens": 512,
+//!       "preset": "mean_pooling"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! and are then referred to by name as `AIModel::Custom`.
+use crate::cli::server::SupportedModels;
+use crate::error::AIProxyError;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Where the ONNX weights and configs of a model are read from
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelSource {
+    // A local directory
+    Path(PathBuf),
+    // A Hugging Face repository, downloaded into the model cache location
+    HfRepo(String),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Modality {
+    Text,
+    Image,
+}
+
+/// The pre and post processing of a built in model, which custom models reuse
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingPreset {
+    // `last_hidden_state` mean pooled and normalized, as all-MiniLM models
+    MeanPooling,
+    // `last_hidden_state` pooled on the CLS token and normalized, as BGE models
+    ClsPooling,
+    // `text_embeds` mean pooled, as clip-vit-b32-text
+    ClipText,
+    // `input` embedded into a normalized `output`, as resnet-50
+    Resnet,
+    // `pixel_values` embedded into `image_embeds`, as clip-vit-b32-image
+    ClipImage,
+}
+
+impl ProcessingPreset {
+    fn modality(&self) -> Modality {
+        match self {
+            Self::MeanPooling | Self::ClsPooling | Self::ClipText => Modality::Text,
+            Self::Resnet | Self::ClipImage => Modality::Image,
+        }
+    }
+}
+
+fn default_weights_file() -> String {
+    "model.onnx".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CustomModel {
+    pub name: String,
+    pub source: ModelSource,
+    #[serde(default = "default_weights_file")]
+    pub weights_file: String,
+    // where the tokenizer files of a text model are read from, defaults to `source`
+    #[serde(default)]
+    pub tokenizer: Option<ModelSource>,
+    pub modality: Modality,
+    pub embedding_size: NonZeroUsize,
+    // required for text models
+    #[serde(default)]
+    pub max_input_tokens: Option<NonZeroUsize>,
+    // width and height, required for image models
+    #[serde(default)]
+    pub image_dimensions: Option<(NonZeroUsize, NonZeroUsize)>,
+    pub preset: ProcessingPreset,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl CustomModel {
+    fn validate(&self) -> Result<(), AIProxyError> {
+        let invalid = |message: &str| {
+            Err(AIProxyError::ModelRegistryError(format!(
+                "{}: {message}",
+                self.name
+            )))
+        };
+        if self.name.is_empty() {
+            return Err(AIProxyError::ModelRegistryError(
+                "models must have a name".to_string(),
+            ));
+        }
+        if SupportedModels::from_str(&self.name, true).is_ok() {
+            return invalid("name is taken by a built in model");
+        }
+        if self.preset.modality() != self.modality {
+            return invalid("preset does not match the modality of the model");
+        }
+        match self.modality {
+            Modality::Text if self.max_input_tokens.is_none() => {
+                invalid("text models must set max_input_tokens")
+            }
+            Modality::Image if self.image_dimensions.is_none() => {
+                invalid("image models must set image_dimensions")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ModelRegistry {
+    pub models: Vec<CustomModel>,
+}
+
+impl ModelRegistry {
+    pub fn load(path: &Path) -> Result<Self, AIProxyError> {
+        let contents = std::fs::read(path).map_err(|e| {
+            AIProxyError::ModelRegistryError(format!("failed to read {}, {e}", path.display()))
+        })?;
+        Self::from_slice(&contents)
+    }
+
+    fn from_slice(contents: &[u8]) -> Result<Self, AIProxyError> {
+        let registry: Self = serde_json::from_slice(contents)
+            .map_err(|e| AIProxyError::ModelRegistryError(format!("failed to parse, {e}")))?;
+        let mut names = HashSet::new();
+        for model in &registry.models {
+            model.validate()?;
+            if !names.insert(model.name.as_str()) {
+                return Err(AIProxyError::ModelRegistryError(format!(
+                    "{} is declared more than once",
+                    model.name
+                )));
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn supported_models(&self) -> impl Iterator<Item = SupportedModels> + '_ {
+        self.models
+            .iter()
+            .map(|model| SupportedModels::Custom(Arc::new(model.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(model: &str) -> Result<ModelRegistry, AIProxyError> {
+        ModelRegistry::from_slice(format!(r#"{{"models": [{model}]}}"#).as_bytes())
+    }
+
+    #[test]
+    fn test_registry_loads_custom_models() {
+        let registry = registry(
+            r#"{
+                "name": "scibert",
+                "source": {"path": "/models/scibert"},
+                "tokenizer": {"hf_repo": "allenai/scibert_scivocab_uncased"},
+                "modality": "text",
+                "embedding_size": 768,
+                "max_input_tokens": 512,
+                "preset": "cls_pooling"
+            }"#,
+        )
+        .unwrap();
+        let model = &registry.models[0];
+        assert_eq!(model.weights_file, "model.onnx");
+        assert_eq!(
+            model.source,
+            ModelSource::Path(PathBuf::from("/models/scibert"))
+        );
+        assert_eq!(
+            registry.supported_models().next().unwrap().to_string(),
+            "scibert"
+        );
+    }
+
+    #[test]
+    fn test_registry_rejects_invalid_models() {
+        // taken by a built in model
+        assert!(registry(
+            r#"{"name": "all-minilm-l6-v2", "source": {"hf_repo": "a/b"}, "modality": "text",
+            "embedding_size": 384, "max_input_tokens": 256, "preset": "mean_pooling"}"#
+        )
+        .is_err());
+        // an image preset for a text model
+        assert!(registry(
+            r#"{"name": "text", "source": {"hf_repo": "a/b"}, "modality": "text",
+            "embedding_size": 384, "max_input_tokens": 256, "preset": "resnet"}"#
+        )
+        .is_err());
+        // image models need their dimensions
+        assert!(registry(
+            r#"{"name": "image", "source": {"hf_repo": "a/b"}, "modality": "image",
+            "embedding_size": 512, "preset": "clip_image"}"#
+        )
+        .is_err());
+        let model = r#"{"name": "text", "source": {"hf_repo": "a/b"}, "modality": "text",
+            "embedding_size": 384, "max_input_tokens": 256, "preset": "mean_pooling"}"#;
+        assert!(registry(model).is_ok());
+        assert!(registry(&format!("{model}, {model}")).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! Zero-shot classification scores an input against candidate labels the way CLIP does, by
+//! turning the cosine similarities between their embeddings into a softmax distribution
+use ahnlich_types::keyval::StoreKey;
+use ahnlich_types::similarity::Similarity;
+
+// CLIP learns to scale its similarities by 100 before the softmax, without which the cosine
+// similarities of embeddings are too close together to tell labels apart
+const LOGIT_SCALE: f32 = 100.0;
+
+fn cosine_similarity(first: &StoreKey, second: &StoreKey) -> f32 {
+    let norm = first.0.dot(&first.0).sqrt() * second.0.dot(&second.0).sqrt();
+    if norm == 0.0 {
+        return 0.0;
+    }
+    first.0.dot(&second.0) / norm
+}
+
+/// Scores each label by the softmax of its scaled similarity to the input, ordered from the
+/// highest score
+pub(crate) fn label_scores(
+    input: &StoreKey,
+    labels: Vec<String>,
+    label_keys: &[StoreKey],
+) -> Vec<(String, Similarity)> {
+    let logits: Vec<f32> = label_keys
+        .iter()
+        .map(|label| cosine_similarity(input, label) * LOGIT_SCALE)
+        .collect();
+    // shifting by the largest logit keeps the exponentials from overflowing
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exponentials: Vec<f32> = logits.iter().map(|logit| (logit - max).exp()).collect();
+    let total: f32 = exponentials.iter().sum();
+    let mut scores: Vec<_> = labels
+        .into_iter()
+        .zip(exponentials)
+        .map(|(label, exponential)| (label, Similarity(exponential / total)))
+        .collect();
+    scores.sort_by(|(_, a), (_, b)| b.0.total_cmp(&a.0));
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_label_scores_are_softmax_normalized() {
+        let input = StoreKey(array![1.0, 0.0]);
+        let label_keys = vec![
+            StoreKey(array![0.0, 1.0]),
+            StoreKey(array![0.9, 0.1]),
+            StoreKey(array![0.0, 0.0]),
+        ];
+        let labels = vec!["dog".to_string(), "cat".to_string(), "empty".to_string()];
+        let scores = label_scores(&input, labels, &label_keys);
+        assert_eq!(scores[0].0, "cat");
+        assert!(scores[0].1 .0 > 0.99);
+        let total: f32 = scores.iter().map(|(_, score)| score.0).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,59 @@
+//! Multimodal fusion turns the embeddings of the text and image of an input, made by the two
+//! towers of a model such as CLIP, into the single key of an entry
+use ahnlich_types::ai::MultimodalFusion;
+use ahnlich_types::keyval::StoreKey;
+use ndarray::{concatenate, Array1, Axis};
+
+// the towers may embed with different magnitudes, so each embedding is scaled to unit length
+// before they are combined
+fn normalized(key: &StoreKey) -> Array1<f32> {
+    let norm = key.0.dot(&key.0).sqrt();
+    if norm == 0.0 {
+        return key.0.clone();
+    }
+    &key.0 / norm
+}
+
+/// Fuses the text and image embeddings of an input, whose dimensions are checked to match when
+/// the store is created
+pub(crate) fn fuse(fusion: MultimodalFusion, text: &StoreKey, image: &StoreKey) -> StoreKey {
+    let (text, image) = (normalized(text), normalized(image));
+    match fusion {
+        MultimodalFusion::Concat => StoreKey(
+            concatenate(Axis(0), &[text.view(), image.view()])
+                .expect("embeddings are one dimensional"),
+        ),
+        MultimodalFusion::Average => StoreKey((text + image) * 0.5),
+        MultimodalFusion::Weighted { text_weight } => {
+            StoreKey(text * text_weight + image * (1.0 - text_weight))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_fuse_normalizes_each_tower() {
+        let text = StoreKey(array![3.0, 0.0]);
+        let image = StoreKey(array![0.0, 0.5]);
+        assert_eq!(
+            fuse(MultimodalFusion::Concat, &text, &image),
+            StoreKey(array![1.0, 0.0, 0.0, 1.0])
+        );
+        assert_eq!(
+            fuse(MultimodalFusion::Average, &text, &image),
+            StoreKey(array![0.5, 0.5])
+        );
+        assert_eq!(
+            fuse(
+                MultimodalFusion::Weighted { text_weight: 0.75 },
+                &text,
+                &image
+            ),
+            StoreKey(array![0.75, 0.25])
+        );
+    }
+}
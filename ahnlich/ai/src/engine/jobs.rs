@@ -0,0 +1,135 @@
+use super::store::{original_input_condition, AIStoreHandler};
+use crate::error::AIProxyError;
+use crate::manager::ModelManager;
+use ahnlich_client_rs::{builders::db as db_params, db::DbClient};
+use ahnlich_types::ai::PreprocessAction;
+use ahnlich_types::db::ServerResponse;
+use ahnlich_types::jobs::JobState;
+use ahnlich_types::keyval::{StoreInput, StoreName, StoreValue};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::sync::Mutex;
+use task_manager::Task;
+use task_manager::TaskState;
+use utils::jobs::Job;
+
+/// Re-embeds the original inputs of a store into another store in batches in the background,
+/// reporting progress to a job
+#[derive(Debug)]
+pub(crate) struct MigrateStoreTask {
+    job: Arc<Job>,
+    store_handler: Arc<AIStoreHandler>,
+    model_manager: Arc<ModelManager>,
+    db_client: Arc<DbClient>,
+    destination: StoreName,
+    batch_size: NonZeroUsize,
+    remaining: Mutex<Vec<(StoreInput, StoreValue)>>,
+    tracing_id: Option<String>,
+}
+
+impl MigrateStoreTask {
+    pub(crate) fn new(
+        job: Arc<Job>,
+        store_handler: Arc<AIStoreHandler>,
+        model_manager: Arc<ModelManager>,
+        db_client: Arc<DbClient>,
+        destination: StoreName,
+        batch_size: NonZeroUsize,
+        inputs: Vec<(StoreInput, StoreValue)>,
+    ) -> Self {
+        Self {
+            job,
+            store_handler,
+            model_manager,
+            db_client,
+            destination,
+            batch_size,
+            remaining: Mutex::new(inputs),
+            tracing_id: None,
+        }
+    }
+
+    /// Traces the requests made to the database under the query that started the job
+    pub(crate) fn with_tracing_id(mut self, tracing_id: Option<String>) -> Self {
+        self.tracing_id = tracing_id;
+        self
+    }
+
+    /// Embeds a batch with the index model of the destination and stores it, replacing entries
+    /// of the same input like SET does
+    async fn migrate(&self, batch: Vec<(StoreInput, StoreValue)>) -> Result<(), AIProxyError> {
+        let (db_inputs, delete_hashset, usage) = self
+            .store_handler
+            .set(
+                &self.destination,
+                batch,
+                &self.model_manager,
+                PreprocessAction::ModelPreprocessing,
+            )
+            .await?;
+        self.model_manager
+            .usage_handler()
+            .record(&self.destination, None, usage);
+        let mut pipeline = self
+            .db_client
+            .pipeline(2, self.tracing_id.clone())
+            .await
+            .map_err(|err| AIProxyError::DatabaseClientError(err.to_string()))?;
+        if let Some(del_hashset) = delete_hashset {
+            let del_pred_params = db_params::DelPredParams::builder()
+                .store(self.destination.to_string())
+                .condition(original_input_condition(del_hashset))
+                .tracing_id(self.tracing_id.clone())
+                .build();
+            pipeline.del_pred(del_pred_params);
+        }
+        let set_params = db_params::SetParams::builder()
+            .store(self.destination.to_string())
+            .inputs(db_inputs)
+            .tracing_id(self.tracing_id.clone())
+            .build();
+        pipeline.set(set_params);
+        let results = pipeline
+            .exec()
+            .await
+            .map_err(|err| AIProxyError::DatabaseClientError(err.to_string()))?;
+        match results.into_inner().as_slice() {
+            [Ok(ServerResponse::Set(_))] | [Ok(_), Ok(ServerResponse::Set(_))] => Ok(()),
+            e => Err(AIProxyError::UnexpectedDBResponse(format!("{e:?}"))),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for MigrateStoreTask {
+    fn task_name(&self) -> String {
+        format!("ai-migratestore-job-{}", self.job.id())
+    }
+
+    async fn run(&self) -> TaskState {
+        // job was cancelled with CANCELJOB
+        if !self.job.is_running() {
+            return TaskState::Break;
+        }
+        let batch = {
+            let mut remaining = self.remaining.lock().expect("job batch lock poisoned");
+            let at = remaining.len().saturating_sub(self.batch_size.get());
+            remaining.split_off(at)
+        };
+        if batch.is_empty() {
+            self.job.finish(JobState::Completed);
+            return TaskState::Break;
+        }
+        let batch_len = batch.len();
+        if let Err(e) = self.migrate(batch).await {
+            self.job.finish(JobState::Failed(format!("{e}")));
+            return TaskState::Break;
+        }
+        self.job.progress(batch_len);
+        TaskState::Continue
+    }
+
+    async fn cleanup(&self) {
+        self.job.finish(JobState::Cancelled);
+    }
+}
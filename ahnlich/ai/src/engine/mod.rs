@@ -1,2 +1,6 @@
 pub mod ai;
+pub(crate) mod classify;
+pub(crate) mod fusion;
+pub(crate) mod jobs;
 pub mod store;
+pub mod usage;
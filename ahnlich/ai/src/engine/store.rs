@@ -1,15 +1,25 @@
 use crate::cli::server::SupportedModels;
+use crate::engine::ai::models::ImageArray;
 use crate::engine::ai::models::InputAction;
 use crate::engine::ai::models::Model;
+use crate::engine::usage::ModelUsage;
 use crate::error::AIProxyError;
-use crate::manager::ModelManager;
-use crate::AHNLICH_AI_RESERVED_META_KEY;
-use ahnlich_types::ai::{AIModel, AIStoreInfo, AIStoreInputType, PreprocessAction};
+use crate::manager::{ModelManager, ModelResponse};
+use crate::{
+    is_reserved_meta_key, AHNLICH_AI_LEGACY_RESERVED_META_KEY, AHNLICH_AI_MULTIMODAL_TEXT_META_KEY,
+    AHNLICH_AI_RESERVED_META_KEY, AHNLICH_AI_TRUNCATION_META_KEY,
+};
+use ahnlich_types::ai::{
+    AIModel, AIStoreDescription, AIStoreInfo, AIStoreInputType, ImageFormat, ImagePreprocessing,
+    MultimodalFusion, PreprocessAction, TextTruncation,
+};
 use ahnlich_types::keyval::StoreInput;
 use ahnlich_types::keyval::StoreKey;
 use ahnlich_types::keyval::StoreName;
 use ahnlich_types::keyval::StoreValue;
+use ahnlich_types::metadata::MetadataKey;
 use ahnlich_types::metadata::MetadataValue;
+use ahnlich_types::predicate::{Predicate, PredicateCondition};
 use fallible_collections::FallibleVec;
 use flurry::HashMap as ConcurrentHashMap;
 use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
@@ -19,9 +29,50 @@ use std::collections::HashSet as StdHashSet;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use utils::limits::LimitHandler;
 use utils::parallel;
 use utils::persistence::AhnlichPersistenceUtils;
 
+/// Metadata saving the original of an input, the text of a multimodal input being saved apart
+/// from its image
+fn original_input_metadata(store_input: StoreInput) -> Vec<(MetadataKey, MetadataValue)> {
+    let (value, text) = store_input.into_metadata();
+    let mut metadata = vec![(AHNLICH_AI_RESERVED_META_KEY.clone(), value)];
+    if let Some(text) = text {
+        metadata.push((
+            AHNLICH_AI_MULTIMODAL_TEXT_META_KEY.clone(),
+            MetadataValue::RawString(text),
+        ));
+    }
+    metadata
+}
+
+/// Condition matching the entries saving any of `store_inputs` as their original input. Both the
+/// image and the text of a multimodal input have to match
+pub(crate) fn original_input_condition(store_inputs: StdHashSet<StoreInput>) -> PredicateCondition {
+    let mut values = StdHashSet::new();
+    let mut multimodal = Vec::new();
+    for store_input in store_inputs {
+        match store_input.into_metadata() {
+            (value, None) => {
+                values.insert(value);
+            }
+            (image, Some(text)) => multimodal.push(
+                Predicate::equals(AHNLICH_AI_RESERVED_META_KEY.clone(), image).and(
+                    Predicate::equals(
+                        AHNLICH_AI_MULTIMODAL_TEXT_META_KEY.clone(),
+                        MetadataValue::RawString(text),
+                    ),
+                ),
+            ),
+        }
+    }
+    let values = Predicate::in_(AHNLICH_AI_RESERVED_META_KEY.clone(), values);
+    multimodal
+        .into_iter()
+        .fold(values.into(), PredicateCondition::or)
+}
+
 /// Contains all the stores that have been created in memory
 #[derive(Debug)]
 pub struct AIStoreHandler {
@@ -35,11 +86,12 @@ pub type AIStores = Arc<ConcurrentHashMap<StoreName, Arc<AIStore>>>;
 
 type StoreSetResponse = (
     Vec<(StoreKey, StoreValue)>,
-    Option<StdHashSet<MetadataValue>>,
+    Option<StdHashSet<StoreInput>>,
+    ModelUsage,
 );
 type StoreValidateResponse = (
     Vec<(StoreInput, StoreValue)>,
-    Option<StdHashSet<MetadataValue>>,
+    Option<StdHashSet<StoreInput>>,
 );
 impl AhnlichPersistenceUtils for AIStoreHandler {
     type PersistenceObject = AIStores;
@@ -71,12 +123,20 @@ impl AIStoreHandler {
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst);
     }
 
+    /// Returns the supported model an AIModel refers to, custom models being looked up by name
+    pub(crate) fn model(&self, model: &AIModel) -> Result<Model, AIProxyError> {
+        SupportedModels::find(&self.supported_models, model)
+            .map(Model::from)
+            .ok_or(AIProxyError::AIModelNotInitialized)
+    }
+
     #[tracing::instrument(skip(self))]
     pub(crate) fn use_snapshot(&mut self, stores_snapshot: AIStores) {
         self.stores = stores_snapshot;
     }
 
     #[tracing::instrument(skip(self))]
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn create_store(
         &self,
         store_name: StoreName,
@@ -84,22 +144,10 @@ impl AIStoreHandler {
         index_model: AIModel,
         error_if_exists: bool,
         store_original: bool,
+        preprocessing: StorePreprocessing,
+        multimodal_fusion: Option<MultimodalFusion>,
     ) -> Result<(), AIProxyError> {
-        if !self.supported_models.contains(&(&query_model).into())
-            || !self.supported_models.contains(&(&index_model).into())
-        {
-            return Err(AIProxyError::AIModelNotInitialized);
-        }
-
-        let index_model_repr: Model = (&index_model).into();
-        let query_model_repr: Model = (&query_model).into();
-
-        if index_model_repr.embedding_size != query_model_repr.embedding_size {
-            return Err(AIProxyError::DimensionsMismatchError {
-                index_model_dim: index_model_repr.embedding_size.into(),
-                query_model_dim: query_model_repr.embedding_size.into(),
-            });
-        }
+        self.store_dimension(&store_name, &query_model, &index_model, multimodal_fusion)?;
 
         if self
             .stores
@@ -110,6 +158,8 @@ impl AIStoreHandler {
                     query_model,
                     index_model,
                     store_original,
+                    preprocessing,
+                    multimodal_fusion,
                 )),
                 &self.stores.guard(),
             )
@@ -122,24 +172,136 @@ impl AIStoreHandler {
         Ok(())
     }
 
+    /// Returns the dimension of the keys of a store, checking that its models can be used
+    /// together
+    pub(crate) fn store_dimension(
+        &self,
+        store_name: &StoreName,
+        query_model: &AIModel,
+        index_model: &AIModel,
+        multimodal_fusion: Option<MultimodalFusion>,
+    ) -> Result<usize, AIProxyError> {
+        let index_model_repr = self.model(index_model)?;
+        let query_model_repr = self.model(query_model)?;
+
+        if index_model_repr.embedding_size != query_model_repr.embedding_size {
+            return Err(AIProxyError::DimensionsMismatchError {
+                index_model_dim: index_model_repr.embedding_size.into(),
+                query_model_dim: query_model_repr.embedding_size.into(),
+            });
+        }
+        let embedding_size = usize::from(index_model_repr.embedding_size);
+        let Some(fusion) = multimodal_fusion else {
+            return Ok(embedding_size);
+        };
+        self.multimodal_models(store_name, query_model, index_model)?;
+        match fusion {
+            MultimodalFusion::Concat => Ok(embedding_size * 2),
+            MultimodalFusion::Weighted { text_weight } if !(0.0..=1.0).contains(&text_weight) => {
+                Err(AIProxyError::MultimodalFusionError {
+                    store: store_name.clone(),
+                    message: format!("text weight {text_weight} is not between 0 and 1"),
+                })
+            }
+            MultimodalFusion::Average | MultimodalFusion::Weighted { .. } => Ok(embedding_size),
+        }
+    }
+
+    /// Returns the text and image models of a store with a multimodal fusion, which are its index
+    /// and query models in either order
+    fn multimodal_models<'a>(
+        &self,
+        store_name: &StoreName,
+        query_model: &'a AIModel,
+        index_model: &'a AIModel,
+    ) -> Result<(&'a AIModel, &'a AIModel), AIProxyError> {
+        match (
+            self.model(index_model)?.input_type(),
+            self.model(query_model)?.input_type(),
+        ) {
+            (AIStoreInputType::RawString, AIStoreInputType::Image) => {
+                Ok((index_model, query_model))
+            }
+            (AIStoreInputType::Image, AIStoreInputType::RawString) => {
+                Ok((query_model, index_model))
+            }
+            _ => Err(AIProxyError::MultimodalFusionError {
+                store: store_name.clone(),
+                message: "one of the index and query models must take texts and the other images"
+                    .to_string(),
+            }),
+        }
+    }
+
     /// matches LISTSTORES - to return statistics of all stores
-    #[tracing::instrument(skip(self))]
-    pub(crate) fn list_stores(&self) -> StdHashSet<AIStoreInfo> {
+    #[tracing::instrument(skip(self, limit_handler))]
+    pub(crate) fn list_stores(&self, limit_handler: &LimitHandler) -> StdHashSet<AIStoreInfo> {
         self.stores
             .iter(&self.stores.guard())
             .map(|(store_name, store)| {
-                let model: Model = (&store.index_model).into();
+                // stores of custom models no longer in the registry report no embedding size
+                let embedding_size = self
+                    .store_dimension(
+                        store_name,
+                        &store.query_model,
+                        &store.index_model,
+                        store.multimodal_fusion,
+                    )
+                    .unwrap_or_default();
 
                 AIStoreInfo {
                     name: store_name.clone(),
-                    query_model: store.query_model,
-                    index_model: store.index_model,
-                    embedding_size: model.embedding_size.into(),
+                    query_model: store.query_model.clone(),
+                    index_model: store.index_model.clone(),
+                    embedding_size,
+                    request_limits: limit_handler.store(store_name),
                 }
             })
             .collect()
     }
 
+    /// Matches DESCRIBESTORE - returns the inputs a store accepts and the dimension of its keys
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn describe_store(
+        &self,
+        store_name: &StoreName,
+    ) -> Result<AIStoreDescription, AIProxyError> {
+        let store = self.get(store_name)?;
+        let embedding_size = self.store_dimension(
+            store_name,
+            &store.query_model,
+            &store.index_model,
+            store.multimodal_fusion,
+        )?;
+        let (index_inputs, query_inputs) = match store.multimodal_fusion {
+            Some(MultimodalFusion::Concat) => (
+                vec![AIStoreInputType::Multimodal],
+                vec![AIStoreInputType::Multimodal],
+            ),
+            Some(MultimodalFusion::Average | MultimodalFusion::Weighted { .. }) => (
+                vec![AIStoreInputType::Multimodal],
+                vec![
+                    AIStoreInputType::RawString,
+                    AIStoreInputType::Image,
+                    AIStoreInputType::Multimodal,
+                ],
+            ),
+            None => (
+                vec![self.model(&store.index_model)?.input_type()],
+                vec![self.model(&store.query_model)?.input_type()],
+            ),
+        };
+        Ok(AIStoreDescription {
+            name: store_name.clone(),
+            query_model: store.query_model.clone(),
+            index_model: store.index_model.clone(),
+            embedding_size,
+            index_inputs,
+            query_inputs,
+            multimodal_fusion: store.multimodal_fusion,
+        })
+    }
+
     /// Returns a store using the store name, else returns an error
     #[tracing::instrument(skip(self))]
     pub(crate) fn get(&self, store_name: &StoreName) -> Result<Arc<AIStore>, AIProxyError> {
@@ -160,10 +322,7 @@ impl AIStoreHandler {
         mut store_value: StoreValue,
         preprocess_action: &PreprocessAction,
     ) -> Result<(StoreInput, StoreValue), AIProxyError> {
-        let metadata_key = &*AHNLICH_AI_RESERVED_META_KEY;
-
-        let metadata_value: MetadataValue = store_input.clone().into();
-        store_value.insert(metadata_key.clone(), metadata_value);
+        store_value.extend(original_input_metadata(store_input.clone()));
         return Ok((store_input, store_value));
     }
 
@@ -175,12 +334,22 @@ impl AIStoreHandler {
         inputs: Vec<(StoreInput, StoreValue)>,
     ) -> Result<StoreValidateResponse, AIProxyError> {
         let store = self.get(store_name)?;
-        let index_model = store.index_model;
+        let index_model_type = match store.multimodal_fusion {
+            Some(_) => AIStoreInputType::Multimodal,
+            None => self.model(&store.index_model)?.input_type(),
+        };
         let chunk_size = parallel::chunk_size(inputs.len());
         inputs
             .into_par_iter()
             .chunks(chunk_size)
-            .map(|input| Self::preprocess_store_input(index_model, input, store.store_original))
+            .map(|input| {
+                Self::preprocess_store_input(
+                    index_model_type.clone(),
+                    input,
+                    store.store_original,
+                    store.image_preprocessing,
+                )
+            })
             .try_reduce(
                 || (Vec::new(), None),
                 |(mut acc_vec, mut acc_set), chunk_res| {
@@ -196,30 +365,38 @@ impl AIStoreHandler {
 
     #[tracing::instrument(skip(inputs))]
     pub(crate) fn preprocess_store_input(
-        index_model: AIModel,
+        index_model_type: AIStoreInputType,
         inputs: Vec<(StoreInput, StoreValue)>,
         store_original: bool,
+        image_preprocessing: ImagePreprocessing,
     ) -> Result<StoreValidateResponse, AIProxyError> {
         let mut output: Vec<_> = FallibleVec::try_with_capacity(inputs.len())?;
         let mut delete_hashset = StdHashSet::new();
         for (store_input, mut store_value) in inputs {
             let store_input_type: AIStoreInputType = (&store_input).into();
-            let index_model_repr: Model = (&index_model).into();
-            if store_input_type != index_model_repr.input_type() {
+            if store_input_type != index_model_type {
                 return Err(AIProxyError::StoreTypeMismatchError {
                     action: InputAction::Index,
-                    index_model_type: index_model_repr.input_type(),
+                    index_model_type,
                     storeinput_type: store_input_type,
                 });
             }
+            if let Some(reserved_key) = store_value.keys().find(|key| is_reserved_meta_key(key)) {
+                return Err(AIProxyError::ReservedError(reserved_key.to_string()));
+            }
+            let store_input = match (store_input, image_preprocessing.convert_format) {
+                (StoreInput::Image(bytes), Some(format)) => StoreInput::Image(
+                    Self::convert_image_format(bytes, format, image_preprocessing)?,
+                ),
+                (StoreInput::Multimodal { text, image }, Some(format)) => StoreInput::Multimodal {
+                    text,
+                    image: Self::convert_image_format(image, format, image_preprocessing)?,
+                },
+                (store_input, _) => store_input,
+            };
             if store_original {
-                let metadata_key = &*AHNLICH_AI_RESERVED_META_KEY;
-                if store_value.contains_key(metadata_key) {
-                    return Err(AIProxyError::ReservedError(metadata_key.to_string()));
-                }
-                let metadata_value: MetadataValue = store_input.clone().into();
-                store_value.insert(metadata_key.clone(), metadata_value.clone());
-                delete_hashset.insert(metadata_value);
+                store_value.extend(original_input_metadata(store_input.clone()));
+                delete_hashset.insert(store_input.clone());
             }
             output.try_push((store_input, store_value))?;
         }
@@ -227,6 +404,19 @@ impl AIStoreHandler {
         Ok((output, delete_hashset))
     }
 
+    fn convert_image_format(
+        bytes: Vec<u8>,
+        format: ImageFormat,
+        image_preprocessing: ImagePreprocessing,
+    ) -> Result<Vec<u8>, AIProxyError> {
+        let mut image = ImageArray::try_new(bytes)?;
+        // re-encoding drops EXIF metadata so the orientation has to be applied first
+        if image_preprocessing.exif_orientation {
+            image = image.apply_exif_orientation()?;
+        }
+        image.convert_format(format).get_bytes()
+    }
+
     /// Stores storeinput into ahnlich db
     #[tracing::instrument(skip(self, inputs), fields(input_length=inputs.len()))]
     pub(crate) async fn set(
@@ -238,62 +428,159 @@ impl AIStoreHandler {
     ) -> Result<StoreSetResponse, AIProxyError> {
         let store = self.get(store_name)?;
         if inputs.is_empty() {
-            return Ok((vec![], None));
+            return Ok((vec![], None, ModelUsage::default()));
         }
         let (validated_data, delete_hashset) =
             self.validate_and_prepare_store_data(store_name, inputs)?;
 
         let (store_inputs, store_values): (Vec<_>, Vec<_>) = validated_data.into_iter().unzip();
-        let store_keys = model_manager
-            .handle_request(
-                &store.index_model,
-                store_inputs,
-                preprocess_action,
-                InputAction::Index,
-            )
-            .await?;
+        let response = match store.multimodal_fusion {
+            Some(fusion) => {
+                let (text_model, image_model) =
+                    self.multimodal_models(store_name, &store.query_model, &store.index_model)?;
+                model_manager
+                    .handle_multimodal_request(
+                        text_model,
+                        image_model,
+                        store_inputs,
+                        fusion,
+                        preprocess_action,
+                        store.preprocessing(),
+                        InputAction::Index,
+                    )
+                    .await?
+            }
+            None => {
+                model_manager
+                    .handle_request(
+                        &store.index_model,
+                        store_inputs,
+                        preprocess_action,
+                        store.preprocessing(),
+                        InputAction::Index,
+                    )
+                    .await?
+            }
+        };
 
-        let output = std::iter::zip(store_keys.into_iter(), store_values.into_iter()).collect();
-        Ok((output, delete_hashset))
+        let mut store_values = store_values;
+        for index in response.truncated {
+            if let Some(store_value) = store_values.get_mut(index) {
+                store_value.insert(
+                    AHNLICH_AI_TRUNCATION_META_KEY.clone(),
+                    MetadataValue::RawString(store.text_truncation.to_string()),
+                );
+            }
+        }
+        let output = std::iter::zip(response.store_keys, store_values).collect();
+        Ok((output, delete_hashset, response.usage))
     }
 
     /// Converts (storekey, storevalue) into (storeinput, storevalue)
-    /// by removing the reserved_key from storevalue
+    /// by removing the reserved_key from storevalue.
+    /// Other system metadata is stripped from storevalue unless `include_system_metadata` is set
     #[tracing::instrument(skip(self, output), fields(output_len=output.len()))]
     pub(crate) fn store_key_val_to_store_input_val(
         &self,
         output: Vec<(StoreKey, StoreValue)>,
+        include_system_metadata: bool,
     ) -> Vec<(Option<StoreInput>, StoreValue)> {
         let metadata_key = &*AHNLICH_AI_RESERVED_META_KEY;
+        let legacy_metadata_key = &*AHNLICH_AI_LEGACY_RESERVED_META_KEY;
 
         output
             .into_par_iter()
             .map(|(_, mut store_value)| {
-                let store_input = store_value.remove(metadata_key).map(|val| val.into());
+                let text = match store_value.remove(&*AHNLICH_AI_MULTIMODAL_TEXT_META_KEY) {
+                    Some(MetadataValue::RawString(text)) => Some(text),
+                    _ => None,
+                };
+                let store_input = store_value
+                    .remove(metadata_key)
+                    .or_else(|| store_value.remove(legacy_metadata_key))
+                    .map(|val| StoreInput::from_metadata(val, text));
+                if !include_system_metadata {
+                    store_value.retain(|key, _| !is_reserved_meta_key(key));
+                }
                 (store_input, store_value)
             })
             .collect()
     }
 
+    /// Embeds search inputs using the query model of a store. Stores with a multimodal fusion fuse
+    /// multimodal inputs the way they were indexed, and unless they concatenate embeddings may
+    /// also be searched with only texts or only images, embedded by the model taking them
     #[tracing::instrument(skip(self))]
     pub(crate) async fn get_ndarray_repr_for_store(
         &self,
         store_name: &StoreName,
-        store_input: StoreInput,
+        store_inputs: Vec<StoreInput>,
         model_manager: &ModelManager,
         preprocess_action: PreprocessAction,
-    ) -> Result<StoreKey, AIProxyError> {
+    ) -> Result<ModelResponse, AIProxyError> {
         let store = self.get(store_name)?;
-        let mut store_keys = model_manager
+        let input_types: StdHashSet<AIStoreInputType> =
+            store_inputs.iter().map(AIStoreInputType::from).collect();
+        let Some(fusion) = store.multimodal_fusion else {
+            if input_types.contains(&AIStoreInputType::Multimodal) {
+                return Err(AIProxyError::StoreTypeMismatchError {
+                    action: InputAction::Query,
+                    index_model_type: self.model(&store.query_model)?.input_type(),
+                    storeinput_type: AIStoreInputType::Multimodal,
+                });
+            }
+            return model_manager
+                .handle_request(
+                    &store.query_model,
+                    store_inputs,
+                    preprocess_action,
+                    store.preprocessing(),
+                    InputAction::Query,
+                )
+                .await;
+        };
+        let (text_model, image_model) =
+            self.multimodal_models(store_name, &store.query_model, &store.index_model)?;
+        let mut input_types = input_types.into_iter();
+        let (Some(input_type), None) = (input_types.next(), input_types.next()) else {
+            return Err(AIProxyError::MultimodalFusionError {
+                store: store_name.clone(),
+                message: "search inputs must all be of the same type".to_string(),
+            });
+        };
+        let model = match (input_type, fusion) {
+            (AIStoreInputType::Multimodal, _) => {
+                return model_manager
+                    .handle_multimodal_request(
+                        text_model,
+                        image_model,
+                        store_inputs,
+                        fusion,
+                        preprocess_action,
+                        store.preprocessing(),
+                        InputAction::Query,
+                    )
+                    .await;
+            }
+            (storeinput_type, MultimodalFusion::Concat) => {
+                return Err(AIProxyError::StoreTypeMismatchError {
+                    action: InputAction::Query,
+                    index_model_type: AIStoreInputType::Multimodal,
+                    storeinput_type,
+                })
+            }
+            (AIStoreInputType::RawString, _) => text_model,
+            (AIStoreInputType::Image, _) => image_model,
+        };
+        model_manager
             .handle_request(
-                &store.query_model,
-                vec![store_input],
+                model,
+                store_inputs,
                 preprocess_action,
+                store.preprocessing(),
                 InputAction::Query,
             )
-            .await?;
-
-        Ok(store_keys.pop().expect("Expected an embedding value."))
+            .await
     }
 
     /// Matches DROPSTORE - Drops a store if exist, else returns an error
@@ -317,12 +604,56 @@ impl AIStoreHandler {
         Ok(removed)
     }
 
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn preprocessing(
+        &self,
+        store_name: &StoreName,
+    ) -> Result<StorePreprocessing, AIProxyError> {
+        let store = self.get(store_name)?;
+        Ok(store.preprocessing())
+    }
+
     #[tracing::instrument(skip(self))]
     pub(crate) fn store_original(&self, store_name: StoreName) -> Result<bool, AIProxyError> {
         let store = self.get(&store_name)?;
         Ok(store.store_original)
     }
 
+    /// Checks that `store_name` can be migrated to `new_index_model`, returning the query model
+    /// of the destination store. Stores queried with their index model keep doing so with the new
+    /// model, otherwise the query model is kept
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn migration_query_model(
+        &self,
+        store_name: &StoreName,
+        new_index_model: &AIModel,
+    ) -> Result<AIModel, AIProxyError> {
+        let store = self.get(store_name)?;
+        if !store.store_original {
+            return Err(AIProxyError::MigrateStoreError(store_name.clone()));
+        }
+        if store.multimodal_fusion.is_some() {
+            return Err(AIProxyError::MultimodalFusionError {
+                store: store_name.clone(),
+                message: "stores with a multimodal fusion cannot be migrated".to_string(),
+            });
+        }
+        let index_model_repr = self.model(&store.index_model)?;
+        let new_index_model_repr = self.model(new_index_model)?;
+        if index_model_repr.input_type() != new_index_model_repr.input_type() {
+            return Err(AIProxyError::StoreTypeMismatchError {
+                action: InputAction::Index,
+                index_model_type: new_index_model_repr.input_type(),
+                storeinput_type: index_model_repr.input_type(),
+            });
+        }
+        if store.query_model == store.index_model {
+            Ok(new_index_model.clone())
+        } else {
+            Ok(store.query_model.clone())
+        }
+    }
+
     /// Matches DestroyDatabase - Drops all the stores in the database
     #[tracing::instrument(skip(self))]
     pub(crate) fn purge_stores(&self) -> usize {
@@ -340,6 +671,12 @@ pub struct AIStore {
     query_model: AIModel,
     index_model: AIModel,
     store_original: bool,
+    #[serde(default)]
+    image_preprocessing: ImagePreprocessing,
+    #[serde(default)]
+    text_truncation: TextTruncation,
+    #[serde(default)]
+    multimodal_fusion: Option<MultimodalFusion>,
 }
 
 impl AIStore {
@@ -348,12 +685,31 @@ impl AIStore {
         query_model: AIModel,
         index_model: AIModel,
         store_original: bool,
+        preprocessing: StorePreprocessing,
+        multimodal_fusion: Option<MultimodalFusion>,
     ) -> Self {
         Self {
             name: store_name,
             query_model,
             index_model,
             store_original,
+            image_preprocessing: preprocessing.image,
+            text_truncation: preprocessing.text_truncation,
+            multimodal_fusion,
+        }
+    }
+
+    fn preprocessing(&self) -> StorePreprocessing {
+        StorePreprocessing {
+            image: self.image_preprocessing,
+            text_truncation: self.text_truncation,
         }
     }
 }
+
+/// How the inputs of a store are prepared before being embedded by its models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorePreprocessing {
+    pub image: ImagePreprocessing,
+    pub text_truncation: TextTruncation,
+}
@@ -0,0 +1,246 @@
+//! Accounting of model usage per store, client and model. Counters live in memory from the
+//! start of the proxy, or their last reset, and are read with GETUSAGESTATS or scraped as
+//! Prometheus metrics from the `/metrics` route of the HTTP gateway
+use ahnlich_types::ai::{AIModel, Usage, UsageStats};
+use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::keyval::StoreName;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::Hash;
+use std::ops::AddAssign;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Usage of a single model request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModelUsage {
+    pub inputs: u64,
+    // tokens of text inputs, excluding padding
+    pub tokens: u64,
+    pub images: u64,
+    pub inference_time: Duration,
+}
+
+impl AddAssign for ModelUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.inputs += other.inputs;
+        self.tokens += other.tokens;
+        self.images += other.images;
+        self.inference_time += other.inference_time;
+    }
+}
+
+impl From<ModelUsage> for Usage {
+    fn from(usage: ModelUsage) -> Self {
+        Self {
+            inputs: usage.inputs,
+            tokens: usage.tokens,
+            images: usage.images,
+            inference_ms: usage.inference_time.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct UsageCounters {
+    stores: HashMap<StoreName, ModelUsage>,
+    clients: HashMap<String, ModelUsage>,
+    models: HashMap<AIModel, ModelUsage>,
+}
+
+fn into_usage<K: Eq + Hash>(counters: HashMap<K, ModelUsage>) -> HashMap<K, Usage> {
+    counters
+        .into_iter()
+        .map(|(key, usage)| (key, usage.into()))
+        .collect()
+}
+
+impl From<UsageCounters> for UsageStats {
+    fn from(counters: UsageCounters) -> Self {
+        Self {
+            stores: into_usage(counters.stores),
+            clients: into_usage(counters.clients),
+            models: into_usage(counters.models),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UsageHandler {
+    counters: Mutex<UsageCounters>,
+}
+
+impl UsageHandler {
+    /// Counts usage of a model, whether or not it was on behalf of a store
+    pub(crate) fn record_model(&self, model: &AIModel, usage: ModelUsage) {
+        let mut counters = self.counters.lock().expect("usage lock poisoned");
+        *counters.models.entry(model.clone()).or_default() += usage;
+    }
+
+    /// Attributes usage to the store it was embedded for and the client that requested it.
+    /// Background jobs are not attributed to any client
+    pub(crate) fn record(
+        &self,
+        store: &StoreName,
+        client: Option<&ConnectedClient>,
+        usage: ModelUsage,
+    ) {
+        let mut counters = self.counters.lock().expect("usage lock poisoned");
+        *counters.stores.entry(store.clone()).or_default() += usage;
+        if let Some(client) = client {
+            *counters.clients.entry(client.host()).or_default() += usage;
+        }
+    }
+
+    /// Counters since the start or the last reset, which `reset` clears after reading them
+    pub fn stats(&self, reset: bool) -> UsageStats {
+        let mut counters = self.counters.lock().expect("usage lock poisoned");
+        let counters = if reset {
+            std::mem::take(&mut *counters)
+        } else {
+            counters.clone()
+        };
+        counters.into()
+    }
+
+    /// Renders the counters in the Prometheus text exposition format
+    pub fn prometheus(&self) -> String {
+        let stats = self.stats(false);
+        let mut output = String::new();
+        write_metrics(
+            &mut output,
+            "store",
+            stats
+                .stores
+                .iter()
+                .map(|(store, usage)| (store.to_string(), usage)),
+        );
+        write_metrics(
+            &mut output,
+            "client",
+            stats
+                .clients
+                .iter()
+                .map(|(client, usage)| (client.clone(), usage)),
+        );
+        write_metrics(
+            &mut output,
+            "model",
+            stats
+                .models
+                .iter()
+                .map(|(model, usage)| (model_label(model), usage)),
+        );
+        output
+    }
+}
+
+/// Name, help text and value of each usage metric
+type Counter = (&'static str, &'static str, fn(&Usage) -> u64);
+
+const COUNTERS: [Counter; 4] = [
+    ("inputs_total", "Inputs embedded", |usage| usage.inputs),
+    ("tokens_total", "Tokens of text inputs processed", |usage| {
+        usage.tokens
+    }),
+    ("images_total", "Images processed", |usage| usage.images),
+    (
+        "inference_milliseconds_total",
+        "Time spent running inference",
+        |usage| usage.inference_ms,
+    ),
+];
+
+fn write_metrics<'a>(
+    output: &mut String,
+    label: &str,
+    entries: impl Iterator<Item = (String, &'a Usage)>,
+) {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, help, value) in COUNTERS {
+        let _ = writeln!(
+            output,
+            "# HELP ahnlich_ai_{label}_{name} {help} per {label}"
+        );
+        let _ = writeln!(output, "# TYPE ahnlich_ai_{label}_{name} counter");
+        for (key, usage) in &entries {
+            let _ = writeln!(
+                output,
+                "ahnlich_ai_{label}_{name}{{{label}=\"{}\"}} {}",
+                escape_label(key),
+                value(usage)
+            );
+        }
+    }
+}
+
+fn model_label(model: &AIModel) -> String {
+    match model {
+        AIModel::Custom(name) => name.clone(),
+        model => format!("{model:?}"),
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_usage_is_attributed_and_reset() {
+        let handler = UsageHandler::default();
+        let store = StoreName("Docs".to_string());
+        let client = ConnectedClient {
+            address: "127.0.0.1:4000".to_string(),
+            time_connected: SystemTime::now(),
+        };
+        let usage = ModelUsage {
+            inputs: 2,
+            tokens: 10,
+            images: 0,
+            inference_time: Duration::from_millis(5),
+        };
+        handler.record_model(&AIModel::AllMiniLML6V2, usage);
+        handler.record(&store, Some(&client), usage);
+        handler.record(&store, None, usage);
+
+        let stats = handler.stats(true);
+        assert_eq!(
+            stats.stores[&store],
+            Usage {
+                inputs: 4,
+                tokens: 20,
+                images: 0,
+                inference_ms: 10,
+            }
+        );
+        assert_eq!(stats.clients["127.0.0.1"], usage.into());
+        assert_eq!(stats.models[&AIModel::AllMiniLML6V2], usage.into());
+        assert_eq!(handler.stats(false), UsageStats::default());
+    }
+
+    #[test]
+    fn test_usage_renders_as_prometheus_metrics() {
+        let handler = UsageHandler::default();
+        let usage = ModelUsage {
+            inputs: 1,
+            tokens: 0,
+            images: 1,
+            inference_time: Duration::from_millis(30),
+        };
+        handler.record(&StoreName("Ima\"ges".to_string()), None, usage);
+        let metrics = handler.prometheus();
+        assert!(metrics.contains("# TYPE ahnlich_ai_store_images_total counter\n"));
+        assert!(metrics.contains("ahnlich_ai_store_images_total{store=\"Ima\\\"ges\"} 1\n"));
+        assert!(metrics
+            .contains("ahnlich_ai_store_inference_milliseconds_total{store=\"Ima\\\"ges\"} 30\n"));
+    }
+}
@@ -1,5 +1,6 @@
 use ahnlich_types::{
     ai::{AIStoreInputType, PreprocessAction},
+    error::{ErrorCode, ErrorResponse},
     keyval::StoreName,
 };
 use fallible_collections::TryReserveError;
@@ -20,6 +21,12 @@ pub enum AIProxyError {
 
     #[error("Proxy Errored with {0} ")]
     DatabaseClientError(String),
+    #[error("Job {0} not found")]
+    JobNotFound(u64),
+    #[error("Chunked transfer {0} not found")]
+    TransferNotFound(u64),
+    #[error("Chunked transfer {transfer_id} failed: {message}")]
+    ChunkedTransferError { transfer_id: u64, message: String },
     #[error("Reserved key {0} used")]
     ReservedError(String),
     #[error("Unexpected DB Response {0} ")]
@@ -136,6 +143,12 @@ pub enum AIProxyError {
     #[error("Image could not be cropped.")]
     ImageCropError,
 
+    #[error("Image could not be reoriented.")]
+    ImageOrientationError,
+
+    #[error("None of the execution providers [{0}] could be registered")]
+    ExecutionProviderError(String),
+
     #[error("Model provider failed on preprocessing the input {0}")]
     ModelProviderPreprocessingError(String),
 
@@ -151,11 +164,43 @@ pub enum AIProxyError {
     #[error("Cannot call DelKey on store with `store_original` as false")]
     DelKeyError,
 
+    #[error("Cannot migrate store {0} with `store_original` as false")]
+    MigrateStoreError(StoreName),
+
+    #[error("Invalid multimodal fusion for store {store}: {message}")]
+    MultimodalFusionError { store: StoreName, message: String },
+
+    #[error("Set of {size} bytes into store {store} exceeds the limit of {limit} bytes")]
+    RequestTooLarge {
+        store: StoreName,
+        size: usize,
+        limit: usize,
+    },
+
+    #[error("Set of {len} entries into store {store} exceeds the limit of {limit} entries")]
+    BatchTooLarge {
+        store: StoreName,
+        len: usize,
+        limit: usize,
+    },
+
     #[error("Tokenizer for model failed to load: {message}")]
     ModelTokenizerLoadError { message: String },
 
     #[error("Unable to load config: [{message}].")]
     ModelConfigLoadError { message: String },
+
+    #[error("Invalid model registry: {0}")]
+    ModelRegistryError(String),
+
+    #[error("Classify requires at least one label")]
+    ClassifyLabelsEmpty,
+
+    #[error("AnswerQuestion requires the proxy to be started with an answer model")]
+    AnswerModelNotConfigured,
+
+    #[error("Timeout of the request passed before the query ran")]
+    DeadlineExceeded,
 }
 
 impl From<TryReserveError> for AIProxyError {
@@ -169,3 +214,65 @@ impl From<ort::Error> for AIProxyError {
         Self::ORTError(input.to_string())
     }
 }
+
+impl From<AIProxyError> for ErrorResponse {
+    fn from(input: AIProxyError) -> Self {
+        let code = match &input {
+            AIProxyError::StoreNotFound(_) => ErrorCode::StoreNotFound,
+            AIProxyError::StoreAlreadyExists(_) => ErrorCode::StoreAlreadyExists,
+            AIProxyError::StandardError(_) => ErrorCode::Internal,
+            AIProxyError::DatabaseClientError(_) | AIProxyError::UnexpectedDBResponse(_) => {
+                ErrorCode::Unavailable
+            }
+            AIProxyError::ReservedError(_) => ErrorCode::ReservedKey,
+            AIProxyError::JobNotFound(_) => ErrorCode::JobNotFound,
+            AIProxyError::DimensionsMismatchError { .. }
+            | AIProxyError::ImageDimensionsMismatchError { .. } => ErrorCode::DimensionMismatch,
+            AIProxyError::StoreTypeMismatchError { .. }
+            | AIProxyError::TokenExceededError { .. }
+            | AIProxyError::PreprocessingMismatchError { .. }
+            | AIProxyError::ImageNonzeroDimensionError { .. }
+            | AIProxyError::ImageBytesDecodeError
+            | AIProxyError::DelKeyError
+            | AIProxyError::MigrateStoreError(_)
+            | AIProxyError::MultimodalFusionError { .. }
+            | AIProxyError::TransferNotFound(_)
+            | AIProxyError::ChunkedTransferError { .. }
+            | AIProxyError::ClassifyLabelsEmpty
+            | AIProxyError::AnswerModelNotConfigured => ErrorCode::InvalidArgument,
+            AIProxyError::RequestTooLarge { .. } | AIProxyError::BatchTooLarge { .. } => {
+                ErrorCode::LimitExceeded
+            }
+            AIProxyError::Allocation(_) => ErrorCode::ResourceExhausted,
+            AIProxyError::DeadlineExceeded => ErrorCode::DeadlineExceeded,
+            // the remaining errors come from loading or running models
+            _ => ErrorCode::ModelError,
+        };
+        let response = ErrorResponse::new(code, &input);
+        match input {
+            AIProxyError::StoreNotFound(store)
+            | AIProxyError::StoreAlreadyExists(store)
+            | AIProxyError::MigrateStoreError(store)
+            | AIProxyError::MultimodalFusionError { store, .. } => {
+                response.with_metadata("store", store)
+            }
+            AIProxyError::ReservedError(key) => response.with_metadata("key", key),
+            AIProxyError::JobNotFound(job_id) => response.with_metadata("job_id", job_id),
+            AIProxyError::RequestTooLarge { store, limit, .. }
+            | AIProxyError::BatchTooLarge { store, limit, .. } => response
+                .with_metadata("store", store)
+                .with_metadata("limit", limit),
+            AIProxyError::TransferNotFound(transfer_id)
+            | AIProxyError::ChunkedTransferError { transfer_id, .. } => {
+                response.with_metadata("transfer_id", transfer_id)
+            }
+            AIProxyError::DimensionsMismatchError {
+                index_model_dim,
+                query_model_dim,
+            } => response
+                .with_metadata("index_model_dim", index_model_dim)
+                .with_metadata("query_model_dim", query_model_dim),
+            _ => response,
+        }
+    }
+}
@@ -9,5 +9,34 @@ use once_cell::sync::Lazy;
 #[cfg(test)]
 mod tests;
 
+/// Metadata key under which the original input is saved for stores created with `store_original`
 pub(crate) static AHNLICH_AI_RESERVED_META_KEY: Lazy<MetadataKey> =
+    Lazy::new(|| MetadataKey::system("input_key"));
+
+/// Metadata key recording the truncation strategy applied to entries whose text was longer than
+/// the max input tokens of the index model
+pub(crate) static AHNLICH_AI_TRUNCATION_META_KEY: Lazy<MetadataKey> =
+    Lazy::new(|| MetadataKey::system("truncation"));
+
+/// Metadata key under which the text of a multimodal input is saved, its image being saved under
+/// the key of the original input
+pub(crate) static AHNLICH_AI_MULTIMODAL_TEXT_META_KEY: Lazy<MetadataKey> =
+    Lazy::new(|| MetadataKey::system("input_text"));
+
+/// Metadata key under which the response cached for a prompt is saved by CacheStore
+pub(crate) static AHNLICH_AI_CACHE_RESPONSE_META_KEY: Lazy<MetadataKey> =
+    Lazy::new(|| MetadataKey::system("cache_response"));
+
+/// Metadata key recording the Unix timestamp in seconds a cached response expires at
+pub(crate) static AHNLICH_AI_CACHE_EXPIRES_META_KEY: Lazy<MetadataKey> =
+    Lazy::new(|| MetadataKey::system("cache_expires_at"));
+
+/// Key used to save original inputs before the system metadata namespace was introduced. It is
+/// still treated as reserved and recognised when reading entries from previously persisted stores
+pub(crate) static AHNLICH_AI_LEGACY_RESERVED_META_KEY: Lazy<MetadataKey> =
     Lazy::new(|| MetadataKey::new(String::from("_ahnlich_input_key")));
+
+/// Returns true if a metadata key may only be written by the AI proxy itself
+pub(crate) fn is_reserved_meta_key(key: &MetadataKey) -> bool {
+    key.is_system() || key == &*AHNLICH_AI_LEGACY_RESERVED_META_KEY
+}
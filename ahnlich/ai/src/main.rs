@@ -19,6 +19,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
             server.start().await?;
         }
         ahnlich_ai_proxy::cli::Commands::SupportedModels(config) => config.output(),
+        ahnlich_ai_proxy::cli::Commands::VerifyBackup(args) => {
+            args.output::<ahnlich_ai_proxy::engine::store::AIStoreHandler>()
+        }
     }
     Ok(())
 }
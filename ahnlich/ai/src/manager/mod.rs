@@ -1,86 +1,365 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use crate::cli::server::{ModelConfig, SupportedModels};
+use crate::cli::server::{ExecutionProvider, ModelConfig, SupportedModels};
+use crate::engine::ai::answer::AnswerEngine;
 use crate::engine::ai::models::{ImageArray, InputAction};
 /// The ModelManager is a wrapper around all the AI models running on various green threads. It
 /// lets AIProxyTasks communicate with any model to receive immediate responses via a oneshot
 /// channel
 use crate::engine::ai::models::{Model, ModelInput};
+use crate::engine::ai::providers::processors::exif_orientation::ExifOrientation;
 use crate::engine::ai::providers::processors::imagearray_to_ndarray::ImageArrayToNdArray;
+use crate::engine::ai::providers::processors::letterbox::Letterbox;
+use crate::engine::ai::providers::processors::resize::Resize;
 use crate::engine::ai::providers::processors::{Preprocessor, PreprocessorData};
 use crate::engine::ai::providers::ModelProviders;
+use crate::engine::classify::label_scores;
+use crate::engine::fusion::fuse;
+use crate::engine::store::StorePreprocessing;
+use crate::engine::usage::{ModelUsage, UsageHandler};
 use crate::error::AIProxyError;
-use ahnlich_types::ai::{AIModel, PreprocessAction};
+use ahnlich_types::ai::{
+    AIModel, AIModelInfo, AIStoreInputType, ImagePreprocessing, ImageResize, ModelMemory,
+    MultimodalFusion, PreprocessAction, QuestionAnswer, TextTruncation,
+};
 use ahnlich_types::keyval::{StoreInput, StoreKey};
+use ahnlich_types::similarity::Similarity;
+use clap::ValueEnum;
 use fallible_collections::FallibleVec;
 use moka::future::Cache;
-use ndarray::{Array, Ix4};
+use moka::Expiry;
+use ndarray::{concatenate, Array, Axis, Ix4};
 use rayon::prelude::*;
 use task_manager::Task;
 use task_manager::TaskManager;
 use task_manager::TaskState;
-use tokenizers::Encoding;
+use tokenizers::TruncationDirection;
+use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::Mutex;
 use tokio::sync::{mpsc, oneshot};
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-type ModelThreadResponse = Result<Vec<StoreKey>, AIProxyError>;
+/// Embeddings of a request in the order of its inputs
+#[derive(Debug)]
+pub struct ModelResponse {
+    pub store_keys: Vec<StoreKey>,
+    // positions of the texts that were longer than the model allows and were truncated or split
+    pub truncated: Vec<usize>,
+    pub usage: ModelUsage,
+}
+
+type ModelThreadResponse = Result<ModelResponse, AIProxyError>;
 
 struct ModelThreadRequest {
     inputs: Vec<StoreInput>,
     response: oneshot::Sender<ModelThreadResponse>,
     preprocess_action: PreprocessAction,
+    preprocessing: StorePreprocessing,
     action_type: InputAction,
     trace_span: tracing::Span,
 }
 
+/// Requests of a batch that share an action and input type, so their inputs can be run through
+/// the model together
+struct BatchGroup {
+    action_type: InputAction,
+    inputs: Vec<ModelInput>,
+    responses: Vec<PendingResponse>,
+}
+
+/// The inputs of a request after preprocessing
+pub(crate) struct PreprocessedInput {
+    input: ModelInput,
+    // number of model inputs each text was split into when texts are split and averaged
+    chunks: Option<Vec<usize>>,
+    truncated: Vec<usize>,
+}
+
+/// A request of a batch group waiting on the inference of the group
+struct PendingResponse {
+    sender: oneshot::Sender<ModelThreadResponse>,
+    usage: ModelUsage,
+    chunks: Option<Vec<usize>>,
+    truncated: Vec<usize>,
+}
+
+impl PendingResponse {
+    /// Number of embeddings the model returns for the request
+    fn output_count(&self) -> usize {
+        match &self.chunks {
+            Some(chunks) => chunks.iter().sum(),
+            None => self.usage.inputs as usize,
+        }
+    }
+}
+
+/// Averages the embeddings of the chunks of each split text into a single embedding
+fn average_chunks(store_keys: Vec<StoreKey>, chunks: &[usize]) -> Vec<StoreKey> {
+    let mut store_keys = store_keys.into_iter();
+    chunks
+        .iter()
+        .filter_map(|&count| {
+            let sum = store_keys
+                .by_ref()
+                .take(count)
+                .map(|key| key.0)
+                .reduce(|sum, key| sum + key)?;
+            Some(StoreKey(sum / count as f32))
+        })
+        .collect()
+}
+
 struct ModelThread {
     model: Model,
-    request_receiver: Mutex<mpsc::Receiver<ModelThreadRequest>>,
+    replica: usize,
+    // shared by all replicas of a model so whichever is idle picks up the next request
+    request_receiver: Arc<Mutex<mpsc::Receiver<ModelThreadRequest>>>,
+    batch_size: usize,
+    batch_latency: Duration,
 }
 
 impl ModelThread {
     fn new(
         supported_model: SupportedModels,
-        cache_location: &Path,
-        request_receiver: mpsc::Receiver<ModelThreadRequest>,
+        config: &ModelConfig,
+        replica: usize,
+        request_receiver: Arc<Mutex<mpsc::Receiver<ModelThreadRequest>>>,
     ) -> Result<Self, AIProxyError> {
         let supported_model = &supported_model;
         let mut model: Model = (supported_model).into();
-        model.setup_provider(cache_location);
+        model.setup_provider(&config.model_cache_location);
+        model.setup_execution_providers(
+            config.execution_providers(supported_model),
+            config.gpu_memory_limit,
+        );
         model.load()?;
         Ok(Self {
-            request_receiver: Mutex::new(request_receiver),
+            request_receiver,
             model,
+            replica,
+            batch_size: config.batch_size,
+            batch_latency: Duration::from_millis(config.batch_latency),
         })
     }
 
     fn name(&self) -> String {
-        format!("{:?}-model-thread", self.model.model_name())
+        format!(
+            "{:?}-model-thread-{}",
+            self.model.model_name(),
+            self.replica
+        )
     }
 
-    #[tracing::instrument(skip(self, inputs))]
-    fn input_to_response(
+    /// Waits for a request and then collects any others that arrive within the batch latency,
+    /// until the batch holds at least batch size inputs
+    async fn receive_batch(&self) -> Vec<ModelThreadRequest> {
+        let mut guard = self.request_receiver.lock().await;
+        let Some(first) = guard.recv().await else {
+            return vec![];
+        };
+        let deadline = Instant::now() + self.batch_latency;
+        let mut input_count = first.inputs.len();
+        let mut batch = vec![first];
+        while input_count < self.batch_size {
+            let request = match guard.try_recv() {
+                Ok(request) => request,
+                Err(TryRecvError::Disconnected) => break,
+                Err(TryRecvError::Empty) => {
+                    match tokio::time::timeout_at(deadline, guard.recv()).await {
+                        Ok(Some(request)) => request,
+                        _ => break,
+                    }
+                }
+            };
+            input_count += request.inputs.len();
+            batch.push(request);
+        }
+        batch
+    }
+
+    /// Preprocesses every request of the batch on its own so a bad input only fails its own
+    /// request, then runs a single inference per group and splits the keys back to each caller
+    fn process_batch(&self, batch: Vec<ModelThreadRequest>) {
+        let mut groups: Vec<BatchGroup> = Vec::new();
+        for request in batch {
+            let ModelThreadRequest {
+                inputs,
+                response,
+                preprocess_action,
+                preprocessing,
+                action_type,
+                trace_span,
+            } = request;
+            let child_span = tracing::info_span!("model-thread-run", model = self.task_name());
+            child_span.set_parent(trace_span.context());
+
+            let mut usage = ModelUsage {
+                inputs: inputs.len() as u64,
+                ..Default::default()
+            };
+            let processed = child_span
+                .in_scope(|| self.preprocess_store_input(preprocess_action, preprocessing, inputs));
+            let PreprocessedInput {
+                input: processed,
+                chunks,
+                truncated,
+            } = match processed {
+                Ok(processed) => processed,
+                Err(err) => {
+                    self.send_response(response, Err(err));
+                    continue;
+                }
+            };
+            match &processed {
+                ModelInput::Texts(encodings) => {
+                    usage.tokens = encodings
+                        .iter()
+                        .map(|encoding| {
+                            encoding
+                                .get_attention_mask()
+                                .iter()
+                                .map(|&m| m as u64)
+                                .sum::<u64>()
+                        })
+                        .sum();
+                }
+                ModelInput::Images(images) => usage.images = images.len_of(Axis(0)) as u64,
+            }
+            let group = groups.iter_mut().find(|group| {
+                group.action_type == action_type
+                    && matches!(
+                        (&group.inputs[0], &processed),
+                        (ModelInput::Texts(_), ModelInput::Texts(_))
+                            | (ModelInput::Images(_), ModelInput::Images(_))
+                    )
+            });
+            let pending = PendingResponse {
+                sender: response,
+                usage,
+                chunks,
+                truncated,
+            };
+            match group {
+                Some(group) => {
+                    group.inputs.push(processed);
+                    group.responses.push(pending);
+                }
+                None => groups.push(BatchGroup {
+                    action_type,
+                    inputs: vec![processed],
+                    responses: vec![pending],
+                }),
+            }
+        }
+
+        for group in groups {
+            let BatchGroup {
+                action_type,
+                inputs,
+                responses,
+            } = group;
+            let started = Instant::now();
+            let store_keys = self
+                .merge_model_inputs(inputs)
+                .and_then(|inputs| self.model.model_ndarray(inputs, &action_type));
+            let inference_time = started.elapsed();
+            match store_keys {
+                Ok(mut store_keys) => {
+                    // the inference time of a group is shared out by the number of model inputs
+                    let total_outputs = store_keys.len().max(1) as f64;
+                    // keys are returned in the order of the merged inputs, so each request
+                    // takes its share from the front
+                    for pending in responses {
+                        let output_count = pending.output_count();
+                        let rest = store_keys.split_off(output_count.min(store_keys.len()));
+                        let mut keys = std::mem::replace(&mut store_keys, rest);
+                        if let Some(chunks) = &pending.chunks {
+                            keys = average_chunks(keys, chunks);
+                        }
+                        let mut usage = pending.usage;
+                        usage.inference_time =
+                            inference_time.mul_f64(output_count as f64 / total_outputs);
+                        self.send_response(
+                            pending.sender,
+                            Ok(ModelResponse {
+                                store_keys: keys,
+                                truncated: pending.truncated,
+                                usage,
+                            }),
+                        );
+                    }
+                }
+                Err(err) => {
+                    for pending in responses {
+                        self.send_response(pending.sender, Err(err.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn merge_model_inputs(&self, inputs: Vec<ModelInput>) -> Result<ModelInput, AIProxyError> {
+        let mut inputs = inputs.into_iter();
+        let first = inputs.next().ok_or(AIProxyError::ModelPreprocessingError {
+            model_name: self.model.model_name(),
+            message: "Batch is empty".to_string(),
+        })?;
+        match first {
+            ModelInput::Texts(mut encodings) => {
+                for input in inputs {
+                    if let ModelInput::Texts(mut other) = input {
+                        encodings.append(&mut other);
+                    }
+                }
+                match &self.model.provider {
+                    ModelProviders::ORT(provider) => provider.pad_encodings(&mut encodings)?,
+                }
+                Ok(ModelInput::Texts(encodings))
+            }
+            ModelInput::Images(images) => {
+                let rest: Vec<_> = inputs
+                    .filter_map(|input| match input {
+                        ModelInput::Images(images) => Some(images),
+                        ModelInput::Texts(_) => None,
+                    })
+                    .collect();
+                if rest.is_empty() {
+                    return Ok(ModelInput::Images(images));
+                }
+                let mut views: Vec<_> = FallibleVec::try_with_capacity(rest.len() + 1)?;
+                views.push(images.view());
+                views.extend(rest.iter().map(|images| images.view()));
+                let images = concatenate(Axis(0), &views).map_err(|err| {
+                    AIProxyError::ModelPreprocessingError {
+                        model_name: self.model.model_name(),
+                        message: format!("Could not batch images: {err}"),
+                    }
+                })?;
+                Ok(ModelInput::Images(images))
+            }
+        }
+    }
+
+    fn send_response(
         &self,
-        inputs: Vec<StoreInput>,
-        process_action: PreprocessAction,
-        action_type: InputAction,
-    ) -> ModelThreadResponse {
-        let mut response: Vec<_> = FallibleVec::try_with_capacity(inputs.len())?;
-        let processed_inputs = self.preprocess_store_input(process_action, inputs)?;
-        let mut store_key = self.model.model_ndarray(processed_inputs, &action_type)?;
-        response.append(&mut store_key);
-        Ok(response)
+        response: oneshot::Sender<ModelThreadResponse>,
+        result: ModelThreadResponse,
+    ) {
+        if let Err(e) = response.send(result) {
+            log::error!("{} could not send response to channel {e:?}", self.name());
+        }
     }
 
     #[tracing::instrument(skip(self, inputs))]
     pub(crate) fn preprocess_store_input(
         &self,
         process_action: PreprocessAction,
+        preprocessing: StorePreprocessing,
         inputs: Vec<StoreInput>,
-    ) -> Result<ModelInput, AIProxyError> {
+    ) -> Result<PreprocessedInput, AIProxyError> {
         let sample = inputs
             .first()
             .ok_or(AIProxyError::ModelPreprocessingError {
@@ -96,8 +375,7 @@ impl ModelThread {
                         _ => None,
                     })
                     .collect();
-                let output = self.preprocess_raw_string(inputs, process_action)?;
-                Ok(ModelInput::Texts(output))
+                self.preprocess_raw_string(inputs, process_action, preprocessing.text_truncation)
             }
             StoreInput::Image(_) => {
                 let inputs = inputs
@@ -109,9 +387,17 @@ impl ModelThread {
                         _ => None,
                     })
                     .collect();
-                let output = self.preprocess_image(inputs, process_action)?;
-                Ok(ModelInput::Images(output))
+                let output = self.preprocess_image(inputs, process_action, preprocessing.image)?;
+                Ok(PreprocessedInput {
+                    input: ModelInput::Images(output),
+                    chunks: None,
+                    truncated: vec![],
+                })
             }
+            StoreInput::Multimodal { .. } => Err(AIProxyError::ModelPreprocessingError {
+                model_name: self.model.model_name(),
+                message: "Multimodal inputs are embedded by a text and an image model".to_string(),
+            }),
         }
     }
     #[tracing::instrument(skip(self, inputs))]
@@ -119,7 +405,8 @@ impl ModelThread {
         &self,
         inputs: Vec<String>,
         process_action: PreprocessAction,
-    ) -> Result<Vec<Encoding>, AIProxyError> {
+        text_truncation: TextTruncation,
+    ) -> Result<PreprocessedInput, AIProxyError> {
         let max_token_size = usize::from(self.model.max_input_token().ok_or_else(|| {
             AIProxyError::ModelPreprocessingError {
                 model_name: self.model.model_name(),
@@ -129,23 +416,61 @@ impl ModelThread {
 
         match &self.model.provider {
             ModelProviders::ORT(provider) => {
-                let truncate = matches!(process_action, PreprocessAction::ModelPreprocessing);
-                let outputs = provider.preprocess_texts(inputs, truncate)?;
-                let token_size = outputs
-                    .first()
-                    .ok_or(AIProxyError::ModelPreprocessingError {
-                        model_name: self.model.model_name(),
-                        message: "Processed output is empty".to_string(),
-                    })?
-                    .len();
-                if token_size > max_token_size {
-                    return Err(AIProxyError::TokenExceededError {
-                        max_token_size,
-                        input_token_size: token_size,
+                // texts sent without model preprocessing are expected to already fit the model
+                let text_truncation = match process_action {
+                    PreprocessAction::ModelPreprocessing => text_truncation,
+                    PreprocessAction::NoPreprocessing => TextTruncation::Error,
+                };
+                let direction = match text_truncation {
+                    TextTruncation::Error => None,
+                    TextTruncation::TruncateHead => Some(TruncationDirection::Left),
+                    TextTruncation::TruncateTail | TextTruncation::SplitAndAverage => {
+                        Some(TruncationDirection::Right)
+                    }
+                };
+                let outputs = provider.preprocess_texts(inputs, direction, max_token_size)?;
+                if text_truncation == TextTruncation::Error {
+                    let token_size = outputs
+                        .first()
+                        .ok_or(AIProxyError::ModelPreprocessingError {
+                            model_name: self.model.model_name(),
+                            message: "Processed output is empty".to_string(),
+                        })?
+                        .len();
+                    if token_size > max_token_size {
+                        return Err(AIProxyError::TokenExceededError {
+                            max_token_size,
+                            input_token_size: token_size,
+                        });
+                    }
+                    return Ok(PreprocessedInput {
+                        input: ModelInput::Texts(outputs),
+                        chunks: None,
+                        truncated: vec![],
                     });
-                } else {
-                    return Ok(outputs);
                 }
+                // the tokenizer keeps whatever was cut off a text as overflowing encodings,
+                // which are dropped when truncating and embedded on their own when splitting
+                let split = text_truncation == TextTruncation::SplitAndAverage;
+                let mut encodings = Vec::with_capacity(outputs.len());
+                let mut chunks = Vec::with_capacity(outputs.len());
+                let mut truncated = vec![];
+                for (index, mut encoding) in outputs.into_iter().enumerate() {
+                    let overflowing = encoding.take_overflowing();
+                    if !overflowing.is_empty() {
+                        truncated.push(index);
+                    }
+                    encodings.push(encoding);
+                    if split {
+                        chunks.push(overflowing.len() + 1);
+                        encodings.extend(overflowing);
+                    }
+                }
+                Ok(PreprocessedInput {
+                    input: ModelInput::Texts(encodings),
+                    chunks: split.then_some(chunks),
+                    truncated,
+                })
             }
         }
     }
@@ -155,6 +480,7 @@ impl ModelThread {
         &self,
         inputs: Vec<ImageArray>,
         process_action: PreprocessAction,
+        image_preprocessing: ImagePreprocessing,
     ) -> Result<Array<f32, Ix4>, AIProxyError> {
         // process image, return error if max dimensions exceeded
         let (expected_width, expected_height) = self.model.expected_image_dimensions().ok_or(
@@ -166,6 +492,23 @@ impl ModelThread {
         let expected_width = usize::from(expected_width);
         let expected_height = usize::from(expected_height);
 
+        // store policies are applied before any model preprocessing so images that would
+        // otherwise mismatch the model dimensions can be accepted
+        let mut inputs = PreprocessorData::ImageArray(inputs);
+        if image_preprocessing.exif_orientation {
+            inputs = ExifOrientation.process(inputs)?;
+        }
+        inputs = match image_preprocessing.resize {
+            ImageResize::None => inputs,
+            ImageResize::Stretch => {
+                Resize::new(expected_width as u32, expected_height as u32).process(inputs)?
+            }
+            ImageResize::Letterbox => {
+                Letterbox::new(expected_width as u32, expected_height as u32).process(inputs)?
+            }
+        };
+        let inputs = inputs.into_image_array()?;
+
         match &self.model.provider {
             ModelProviders::ORT(provider) => {
                 let outputs = match process_action {
@@ -197,36 +540,76 @@ impl Task for ModelThread {
     }
 
     async fn run(&self) -> TaskState {
-        let mut guard = self.request_receiver.lock().await;
-        let request = guard.recv().await;
-        drop(guard);
-        if let Some(model_request) = request {
-            let ModelThreadRequest {
-                inputs,
-                response,
-                preprocess_action,
-                action_type,
-                trace_span,
-            } = model_request;
-            let child_span = tracing::info_span!("model-thread-run", model = self.task_name());
-            child_span.set_parent(trace_span.context());
-
-            let responses = self.input_to_response(inputs, preprocess_action, action_type);
-            if let Err(e) = response.send(responses) {
-                log::error!("{} could not send response to channel {e:?}", self.name());
-            }
-            return TaskState::Continue;
+        let batch = self.receive_batch().await;
+        if batch.is_empty() {
+            return TaskState::Break;
         }
-        TaskState::Break
+        self.process_batch(batch);
+        TaskState::Continue
+    }
+}
+
+/// A loaded model thread along with the execution provider its model runs on
+#[derive(Debug, Clone)]
+struct ModelThreadHandle {
+    sender: mpsc::Sender<ModelThreadRequest>,
+    execution_provider: Option<ExecutionProvider>,
+    // bytes of the weights held by each replica
+    weights_bytes: usize,
+}
+
+/// Unloads models left idle for the model idle time, read as a model is loaded or last used so
+/// that a reloaded idle time applies from the next use of each model
+struct IdleExpiry(Arc<AtomicU64>);
+
+impl IdleExpiry {
+    fn idle_time(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl Expiry<SupportedModels, ModelThreadHandle> for IdleExpiry {
+    fn expire_after_create(
+        &self,
+        _model: &SupportedModels,
+        _handle: &ModelThreadHandle,
+        _created_at: std::time::Instant,
+    ) -> Option<Duration> {
+        self.idle_time()
+    }
+
+    fn expire_after_read(
+        &self,
+        _model: &SupportedModels,
+        _handle: &ModelThreadHandle,
+        _read_at: std::time::Instant,
+        _duration_until_expiry: Option<Duration>,
+        _last_modified_at: std::time::Instant,
+    ) -> Option<Duration> {
+        self.idle_time()
+    }
+
+    fn expire_after_update(
+        &self,
+        _model: &SupportedModels,
+        _handle: &ModelThreadHandle,
+        _updated_at: std::time::Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        self.idle_time()
     }
 }
 
 #[derive(Debug)]
 pub struct ModelManager {
-    models: Cache<SupportedModels, mpsc::Sender<ModelThreadRequest>>,
+    models: Cache<SupportedModels, ModelThreadHandle>,
+    // seconds a model is kept loaded without being used, changes as the config is reloaded
+    idle_time: Arc<AtomicU64>,
     supported_models: Vec<SupportedModels>,
     task_manager: Arc<TaskManager>,
     config: ModelConfig,
+    usage_handler: Arc<UsageHandler>,
+    answer_engine: Option<Arc<AnswerEngine>>,
 }
 
 impl ModelManager {
@@ -234,21 +617,31 @@ impl ModelManager {
         model_config: ModelConfig,
         task_manager: Arc<TaskManager>,
     ) -> Result<Self, AIProxyError> {
+        let idle_time = Arc::new(AtomicU64::new(model_config.model_idle_time));
         let models = Cache::builder()
             .max_capacity(model_config.supported_models.len() as u64)
-            .time_to_idle(Duration::from_secs(model_config.model_idle_time))
+            .expire_after(IdleExpiry(idle_time.clone()))
             .build();
+        let answer_engine = model_config
+            .answer_model
+            .map(|answer_model| {
+                AnswerEngine::load(answer_model, &model_config.model_cache_location).map(Arc::new)
+            })
+            .transpose()?;
         let model_manager = ModelManager {
             models,
+            idle_time,
             task_manager,
             supported_models: model_config.supported_models.to_vec(),
             config: model_config,
+            usage_handler: Arc::new(UsageHandler::default()),
+            answer_engine,
         };
 
         for model in &model_manager.supported_models {
             let _ = model_manager
                 .models
-                .try_get_with(*model, model_manager.try_initialize_model(model))
+                .try_get_with(model.clone(), model_manager.try_initialize_model(model))
                 .await
                 .map_err(|err| AIProxyError::ModelInitializationError(err.to_string()))?;
         }
@@ -259,13 +652,111 @@ impl ModelManager {
     async fn try_initialize_model(
         &self,
         model: &SupportedModels,
-    ) -> Result<mpsc::Sender<ModelThreadRequest>, AIProxyError> {
+    ) -> Result<ModelThreadHandle, AIProxyError> {
         let (request_sender, request_receiver) = mpsc::channel(10000);
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+        let mut execution_provider = None;
+        let mut weights_bytes = 0;
         // There may be other things needed to load a model thread
-        let model_thread =
-            ModelThread::new(*model, &self.config.model_cache_location, request_receiver)?;
-        let _ = &self.task_manager.spawn_task_loop(model_thread).await;
-        Ok(request_sender)
+        for replica in 0..self.config.replicas_per_model.get() {
+            let model_thread = ModelThread::new(
+                model.clone(),
+                &self.config,
+                replica,
+                request_receiver.clone(),
+            )?;
+            execution_provider = model_thread.model.execution_provider();
+            weights_bytes = model_thread.model.weights_bytes();
+            let _ = &self.task_manager.spawn_task_loop(model_thread).await;
+        }
+        Ok(ModelThreadHandle {
+            sender: request_sender,
+            execution_provider,
+            weights_bytes,
+        })
+    }
+
+    /// Finds a supported model by the name it is configured with, a `--supported-models` value or
+    /// the name of a model in the registry
+    pub(crate) fn supported_model(&self, name: &str) -> Option<&SupportedModels> {
+        let builtin = SupportedModels::from_str(name, true).ok();
+        self.supported_models.iter().find(|model| match model {
+            SupportedModels::Custom(custom) => custom.name == name,
+            model => Some(*model) == builtin.as_ref(),
+        })
+    }
+
+    /// Loads the models, or every supported model when none are given, so that requests do not
+    /// wait on a model that was unloaded after sitting idle
+    #[tracing::instrument(skip(self))]
+    pub async fn warmup(&self, models: &HashSet<AIModel>) -> Result<(), AIProxyError> {
+        let supported: Vec<&SupportedModels> = if models.is_empty() {
+            self.supported_models.iter().collect()
+        } else {
+            models
+                .iter()
+                .map(|model| {
+                    SupportedModels::find(&self.supported_models, model)
+                        .ok_or(AIProxyError::AIModelNotInitialized)
+                })
+                .collect::<Result<_, _>>()?
+        };
+        for model in supported {
+            self.models
+                .try_get_with(model.clone(), self.try_initialize_model(model))
+                .await
+                .map_err(|err| AIProxyError::ModelInitializationError(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Seconds a model is kept loaded without being used, which can change while the proxy runs
+    pub fn idle_time(&self) -> Arc<AtomicU64> {
+        self.idle_time.clone()
+    }
+
+    /// Usage of the models, which callers attribute to the stores and clients they serve
+    pub fn usage_handler(&self) -> &Arc<UsageHandler> {
+        &self.usage_handler
+    }
+
+    /// Lists the supported models, with the execution provider of those currently loaded
+    pub async fn list_supported_models(&self) -> Vec<AIModelInfo> {
+        let mut output = Vec::with_capacity(self.supported_models.len());
+        for supported_model in &self.supported_models {
+            let model: Model = supported_model.into();
+            let execution_provider = self
+                .models
+                .get(supported_model)
+                .await
+                .and_then(|handle| handle.execution_provider)
+                .map(|execution_provider| (&execution_provider).into());
+            output.push(AIModelInfo {
+                model: supported_model.into(),
+                input_type: model.input_type(),
+                embedding_size: model.embedding_size.into(),
+                execution_provider,
+            });
+        }
+        output
+    }
+
+    /// The weights held by the replicas of each supported model, none for those not loaded
+    pub async fn models_memory(&self) -> Vec<ModelMemory> {
+        let replicas = self.config.replicas_per_model.get();
+        let mut output = Vec::with_capacity(self.supported_models.len());
+        for supported_model in &self.supported_models {
+            let handle = self.models.get(supported_model).await;
+            output.push(ModelMemory {
+                model: supported_model.into(),
+                loaded: handle.is_some(),
+                replicas: handle.as_ref().map(|_| replicas).unwrap_or_default(),
+                weights_bytes: handle
+                    .map(|handle| handle.weights_bytes * replicas)
+                    .unwrap_or_default(),
+            });
+        }
+        output
     }
 
     #[tracing::instrument(skip(self, inputs))]
@@ -274,16 +765,14 @@ impl ModelManager {
         model: &AIModel,
         inputs: Vec<StoreInput>,
         preprocess_action: PreprocessAction,
+        preprocessing: StorePreprocessing,
         action_type: InputAction,
-    ) -> Result<Vec<StoreKey>, AIProxyError> {
-        let supported = model.into();
-
-        if !self.supported_models.contains(&supported) {
-            return Err(AIProxyError::AIModelNotInitialized);
-        }
-        let sender = self
+    ) -> Result<ModelResponse, AIProxyError> {
+        let supported = SupportedModels::find(&self.supported_models, model)
+            .ok_or(AIProxyError::AIModelNotInitialized)?;
+        let handle = self
             .models
-            .try_get_with(supported, self.try_initialize_model(&supported))
+            .try_get_with(supported.clone(), self.try_initialize_model(supported))
             .await
             .map_err(|err| AIProxyError::ModelInitializationError(err.to_string()))?;
 
@@ -292,24 +781,148 @@ impl ModelManager {
             inputs,
             response: response_tx,
             preprocess_action,
+            preprocessing,
             action_type,
             trace_span: tracing::Span::current(),
         };
         // TODO: Add potential timeouts for send and recieve in case threads are unresponsive
-        if sender.send(request).await.is_ok() {
-            response_rx
-                .await
-                .map_err(|e| e.into())
-                .and_then(|inner| inner)
-        } else {
+        if handle.sender.send(request).await.is_err() {
             return Err(AIProxyError::AIModelThreadSendError);
         }
+        let response = response_rx.await??;
+        self.usage_handler.record_model(model, response.usage);
+        Ok(response)
+    }
+
+    /// Embeds the texts and images of multimodal inputs with their models concurrently and fuses
+    /// the embeddings of each input into one
+    #[tracing::instrument(skip(self, inputs))]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn handle_multimodal_request(
+        &self,
+        text_model: &AIModel,
+        image_model: &AIModel,
+        inputs: Vec<StoreInput>,
+        fusion: MultimodalFusion,
+        preprocess_action: PreprocessAction,
+        preprocessing: StorePreprocessing,
+        action_type: InputAction,
+    ) -> Result<ModelResponse, AIProxyError> {
+        let mut texts = Vec::with_capacity(inputs.len());
+        let mut images = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let StoreInput::Multimodal { text, image } = input else {
+                return Err(AIProxyError::StoreTypeMismatchError {
+                    action: action_type,
+                    index_model_type: AIStoreInputType::Multimodal,
+                    storeinput_type: (&input).into(),
+                });
+            };
+            texts.push(StoreInput::RawString(text));
+            images.push(StoreInput::Image(image));
+        }
+        let (text_response, image_response) = tokio::try_join!(
+            self.handle_request(
+                text_model,
+                texts,
+                preprocess_action,
+                preprocessing,
+                action_type,
+            ),
+            self.handle_request(
+                image_model,
+                images,
+                preprocess_action,
+                preprocessing,
+                action_type,
+            ),
+        )?;
+        let store_keys = std::iter::zip(&text_response.store_keys, &image_response.store_keys)
+            .map(|(text, image)| fuse(fusion, text, image))
+            .collect();
+        let mut usage = text_response.usage;
+        usage += image_response.usage;
+        Ok(ModelResponse {
+            store_keys,
+            truncated: text_response.truncated,
+            usage,
+        })
+    }
+
+    /// Answers a question from the texts retrieved for it with the answer model of the proxy
+    #[tracing::instrument(skip(self, contexts))]
+    pub async fn answer(
+        &self,
+        question: String,
+        contexts: Vec<String>,
+    ) -> Result<Option<QuestionAnswer>, AIProxyError> {
+        let answer_engine = self
+            .answer_engine
+            .clone()
+            .ok_or(AIProxyError::AnswerModelNotConfigured)?;
+        tokio::task::spawn_blocking(move || answer_engine.answer(&question, &contexts))
+            .await
+            .map_err(|e| AIProxyError::ModelProviderRunInferenceError(e.to_string()))?
+    }
+
+    /// Embeds an input and its candidate labels concurrently and scores the labels against it
+    #[tracing::instrument(skip(self, input, labels))]
+    pub async fn classify(
+        &self,
+        input: StoreInput,
+        input_model: &AIModel,
+        labels: Vec<String>,
+        label_model: &AIModel,
+        preprocess_action: PreprocessAction,
+    ) -> Result<Vec<(String, Similarity)>, AIProxyError> {
+        if labels.is_empty() {
+            return Err(AIProxyError::ClassifyLabelsEmpty);
+        }
+        let model_repr = |model| {
+            SupportedModels::find(&self.supported_models, model)
+                .map(Model::from)
+                .ok_or(AIProxyError::AIModelNotInitialized)
+        };
+        let input_model_repr = model_repr(input_model)?;
+        let label_model_repr = model_repr(label_model)?;
+        if input_model_repr.embedding_size != label_model_repr.embedding_size {
+            return Err(AIProxyError::DimensionsMismatchError {
+                index_model_dim: input_model_repr.embedding_size.into(),
+                query_model_dim: label_model_repr.embedding_size.into(),
+            });
+        }
+        let (input_response, label_response) = tokio::try_join!(
+            self.handle_request(
+                input_model,
+                vec![input],
+                preprocess_action,
+                StorePreprocessing::default(),
+                InputAction::Index,
+            ),
+            self.handle_request(
+                label_model,
+                labels.iter().cloned().map(StoreInput::RawString).collect(),
+                PreprocessAction::ModelPreprocessing,
+                StorePreprocessing::default(),
+                InputAction::Query,
+            ),
+        )?;
+        let input_key =
+            input_response
+                .store_keys
+                .first()
+                .ok_or(AIProxyError::ModelPostprocessingError {
+                    model_name: input_model_repr.model_name(),
+                    message: "Input was not embedded".to_string(),
+                })?;
+        Ok(label_scores(input_key, labels, &label_response.store_keys))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::num::NonZeroUsize;
 
     #[tokio::test]
     async fn test_model_manager_setup_works() {
@@ -340,7 +953,7 @@ mod tests {
             SupportedModels::AllMiniLML12V2,
         ];
         let sample_ai_model = AIModel::AllMiniLML6V2;
-        let sample_supported_model: SupportedModels = (&sample_ai_model).into();
+        let sample_supported_model = SupportedModels::AllMiniLML6V2;
 
         let task_manager = Arc::new(TaskManager::new());
         let time_to_idle: u64 = 1;
@@ -357,7 +970,13 @@ mod tests {
         let inputs = vec![StoreInput::RawString(String::from("Hello"))];
         let action = PreprocessAction::ModelPreprocessing;
         let _ = model_manager
-            .handle_request(&sample_ai_model, inputs, action, InputAction::Query)
+            .handle_request(
+                &sample_ai_model,
+                inputs,
+                action,
+                StorePreprocessing::default(),
+                InputAction::Query,
+            )
             .await
             .unwrap();
         let recreated_model = model_manager.models.get(&sample_supported_model).await;
@@ -365,4 +984,150 @@ mod tests {
         assert!(evicted_model.is_none());
         assert!(recreated_model.is_some());
     }
+
+    #[tokio::test]
+    async fn test_model_manager_replicas_serve_requests() {
+        let sample_ai_model = AIModel::AllMiniLML6V2;
+        let task_manager = Arc::new(TaskManager::new());
+        let model_config = ModelConfig {
+            supported_models: vec![SupportedModels::AllMiniLML6V2],
+            replicas_per_model: NonZeroUsize::new(2).unwrap(),
+            ..Default::default()
+        };
+        let model_manager = Arc::new(ModelManager::new(model_config, task_manager).await.unwrap());
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let model_manager = model_manager.clone();
+                let sample_ai_model = sample_ai_model.clone();
+                tokio::spawn(async move {
+                    model_manager
+                        .handle_request(
+                            &sample_ai_model,
+                            vec![StoreInput::RawString(format!("Hello {i}"))],
+                            PreprocessAction::ModelPreprocessing,
+                            StorePreprocessing::default(),
+                            InputAction::Query,
+                        )
+                        .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap().store_keys.len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_model_manager_batches_concurrent_requests() {
+        let sample_ai_model = AIModel::AllMiniLML6V2;
+        let task_manager = Arc::new(TaskManager::new());
+        let model_config = ModelConfig {
+            supported_models: vec![SupportedModels::AllMiniLML6V2],
+            batch_latency: 50,
+            ..Default::default()
+        };
+        let model_manager = Arc::new(ModelManager::new(model_config, task_manager).await.unwrap());
+
+        let requests = vec![
+            vec![StoreInput::RawString(String::from("Hello"))],
+            vec![
+                StoreInput::RawString(String::from("A much longer sentence than the first")),
+                StoreInput::RawString(String::from("Jordan")),
+            ],
+            vec![StoreInput::RawString(String::from("Another one"))],
+        ];
+
+        let mut expected = Vec::new();
+        for inputs in requests.clone() {
+            expected.push(
+                model_manager
+                    .handle_request(
+                        &sample_ai_model,
+                        inputs,
+                        PreprocessAction::ModelPreprocessing,
+                        StorePreprocessing::default(),
+                        InputAction::Query,
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let handles: Vec<_> = requests
+            .into_iter()
+            .map(|inputs| {
+                let model_manager = model_manager.clone();
+                let sample_ai_model = sample_ai_model.clone();
+                tokio::spawn(async move {
+                    model_manager
+                        .handle_request(
+                            &sample_ai_model,
+                            inputs,
+                            PreprocessAction::ModelPreprocessing,
+                            StorePreprocessing::default(),
+                            InputAction::Query,
+                        )
+                        .await
+                })
+            })
+            .collect();
+
+        for (handle, expected) in handles.into_iter().zip(expected) {
+            let response = handle.await.unwrap().unwrap();
+            // padding added to batch requests together is not counted
+            assert_eq!(response.usage.tokens, expected.usage.tokens);
+            assert_eq!(response.usage.inputs, expected.store_keys.len() as u64);
+            assert_eq!(response.store_keys.len(), expected.store_keys.len());
+            for (key, expected) in response.store_keys.iter().zip(expected.store_keys.iter()) {
+                let diff = (&key.0 - &expected.0).mapv(f32::abs);
+                assert!(diff.iter().all(|d| *d < 1e-4));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_model_manager_truncates_long_texts() {
+        let sample_ai_model = AIModel::AllMiniLML6V2;
+        let task_manager = Arc::new(TaskManager::new());
+        let model_config = ModelConfig {
+            supported_models: vec![SupportedModels::AllMiniLML6V2],
+            ..Default::default()
+        };
+        let model_manager = ModelManager::new(model_config, task_manager).await.unwrap();
+        let long_text = "a long sentence about nothing ".repeat(200);
+        let inputs = vec![
+            StoreInput::RawString(String::from("Short")),
+            StoreInput::RawString(long_text),
+        ];
+
+        let embed = |text_truncation| {
+            model_manager.handle_request(
+                &sample_ai_model,
+                inputs.clone(),
+                PreprocessAction::ModelPreprocessing,
+                StorePreprocessing {
+                    text_truncation,
+                    ..Default::default()
+                },
+                InputAction::Index,
+            )
+        };
+        assert!(matches!(
+            embed(TextTruncation::Error).await,
+            Err(AIProxyError::TokenExceededError { .. })
+        ));
+        let tail = embed(TextTruncation::TruncateTail).await.unwrap();
+        assert_eq!(tail.store_keys.len(), 2);
+        assert_eq!(tail.truncated, vec![1]);
+        let head = embed(TextTruncation::TruncateHead).await.unwrap();
+        assert_eq!(head.truncated, vec![1]);
+        let split = embed(TextTruncation::SplitAndAverage).await.unwrap();
+        assert_eq!(split.store_keys.len(), 2);
+        assert_eq!(split.truncated, vec![1]);
+        // every chunk of a split text is embedded
+        assert!(split.usage.tokens > tail.usage.tokens);
+        let diff = (&split.store_keys[0].0 - &tail.store_keys[0].0).mapv(f32::abs);
+        assert!(diff.iter().all(|d| *d < 1e-4));
+    }
 }
@@ -0,0 +1,312 @@
+use crate::manager::ModelManager;
+use crate::server::openai;
+use ahnlich_types::ai::{
+    AIModel, AIQuery, AIServerQuery, AIServerResult, ImagePreprocessing, MultimodalFusion,
+    PreprocessAction, TextTruncation,
+};
+use ahnlich_types::keyval::{StoreEviction, StoreInput, StoreName, StoreValue};
+use ahnlich_types::metadata::MetadataKey;
+use ahnlich_types::predicate::PredicateCondition;
+use ahnlich_types::similarity::{
+    Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm, RecencyBoost, Similarity,
+};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use utils::gateway::{
+    json_response, parse_body, query_response, trace_parent, GatewayError, Upstream,
+};
+
+/// Routes of the ai HTTP gateway
+/// - `GET /ping`, `GET /info`
+/// - `GET /memory` breaks down the memory of the server
+/// - `GET /stores` lists stores, `POST /stores` creates a store
+/// - `GET /models` lists supported models
+/// - `GET /stores/{store}` describes the inputs a store accepts
+/// - `DELETE /stores/{store}` drops a store
+/// - `POST /stores/{store}/entries` sets entries in a store
+/// - `POST /stores/{store}/query` gets the closest entries to a search input
+/// - `POST /query` runs a JSON list of any queries as a pipeline
+/// - `POST /v1/embeddings` generates embeddings in the shape of the OpenAI embeddings API
+/// - `GET /metrics` exposes model usage per store, client and model as Prometheus metrics
+pub(super) fn router(upstream: Upstream, model_manager: Arc<ModelManager>) -> Router {
+    Router::new()
+        .route("/ping", get(ping))
+        .route("/info", get(info))
+        .route("/memory", get(memory_breakdown))
+        .route("/stores", get(list_stores).post(create_store))
+        .route("/models", get(list_supported_models))
+        .route("/stores/:store", get(describe_store).delete(drop_store))
+        .route("/stores/:store/entries", post(set))
+        .route("/stores/:store/query", post(get_sim_n))
+        .route("/query", post(pipeline))
+        .route(
+            "/v1/embeddings",
+            post(openai::embeddings).with_state(model_manager.clone()),
+        )
+        .route("/metrics", get(metrics).with_state(model_manager))
+        .with_state(upstream)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_model() -> AIModel {
+    AIModel::AllMiniLML6V2
+}
+
+fn default_closest_n() -> NonZeroUsize {
+    NonZeroUsize::MIN
+}
+
+fn default_set_preprocess_action() -> PreprocessAction {
+    PreprocessAction::ModelPreprocessing
+}
+
+fn default_get_sim_n_preprocess_action() -> PreprocessAction {
+    PreprocessAction::NoPreprocessing
+}
+
+fn default_algorithm() -> Algorithm {
+    Algorithm::CosineSimilarity
+}
+
+#[derive(Deserialize)]
+struct CreateStoreBody {
+    store: StoreName,
+    #[serde(default = "default_model")]
+    query_model: AIModel,
+    #[serde(default = "default_model")]
+    index_model: AIModel,
+    #[serde(default)]
+    predicates: HashSet<MetadataKey>,
+    #[serde(default)]
+    non_linear_indices: HashSet<NonLinearAlgorithm>,
+    #[serde(default = "default_true")]
+    error_if_exists: bool,
+    #[serde(default = "default_true")]
+    store_original: bool,
+    #[serde(default)]
+    image_preprocessing: ImagePreprocessing,
+    #[serde(default)]
+    text_truncation: TextTruncation,
+    #[serde(default)]
+    timestamp_key: Option<MetadataKey>,
+    #[serde(default)]
+    multimodal_fusion: Option<MultimodalFusion>,
+    #[serde(default)]
+    eviction: Option<StoreEviction>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    key: StoreInput,
+    #[serde(default)]
+    value: StoreValue,
+}
+
+#[derive(Deserialize)]
+struct SetBody {
+    inputs: Vec<Entry>,
+    #[serde(default = "default_set_preprocess_action")]
+    preprocess_action: PreprocessAction,
+}
+
+#[derive(Deserialize)]
+struct GetSimNBody {
+    search_input: StoreInput,
+    #[serde(default = "default_closest_n")]
+    closest_n: NonZeroUsize,
+    #[serde(default = "default_algorithm")]
+    algorithm: Algorithm,
+    #[serde(default)]
+    condition: Option<PredicateCondition>,
+    #[serde(default)]
+    min_score: Option<Similarity>,
+    #[serde(default)]
+    max_distance: Option<Similarity>,
+    #[serde(default)]
+    normalize_scores: bool,
+    #[serde(default)]
+    recency_boost: Option<RecencyBoost>,
+    #[serde(default)]
+    filter_strategy: FilterStrategy,
+    #[serde(default = "default_get_sim_n_preprocess_action")]
+    preprocess_action: PreprocessAction,
+}
+
+async fn send(
+    upstream: &Upstream,
+    headers: &HeaderMap,
+    queries: Vec<AIQuery>,
+) -> Result<AIServerResult, GatewayError> {
+    let mut server_query =
+        AIServerQuery::with_capacity_and_tracing_id(queries.len(), trace_parent(headers));
+    for query in queries {
+        server_query.push(query);
+    }
+    upstream.forward(server_query).await
+}
+
+async fn single(
+    upstream: &Upstream,
+    headers: &HeaderMap,
+    query: AIQuery,
+) -> Result<Response, GatewayError> {
+    let result = send(upstream, headers, vec![query]).await?;
+    query_response(result.pop())
+}
+
+async fn ping(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, AIQuery::Ping).await
+}
+
+async fn info(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, AIQuery::InfoServer).await
+}
+
+async fn memory_breakdown(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, AIQuery::GetMemoryBreakdown).await
+}
+
+async fn list_stores(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, AIQuery::ListStores).await
+}
+
+async fn list_supported_models(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, AIQuery::ListSupportedModels).await
+}
+
+async fn metrics(State(model_manager): State<Arc<ModelManager>>) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        model_manager.usage_handler().prometheus(),
+    )
+        .into_response()
+}
+
+async fn create_store(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: CreateStoreBody = parse_body(&body)?;
+    let query = AIQuery::CreateStore {
+        store: body.store,
+        query_model: body.query_model,
+        index_model: body.index_model,
+        predicates: body.predicates,
+        non_linear_indices: body.non_linear_indices,
+        error_if_exists: body.error_if_exists,
+        store_original: body.store_original,
+        image_preprocessing: body.image_preprocessing,
+        text_truncation: body.text_truncation,
+        timestamp_key: body.timestamp_key,
+        multimodal_fusion: body.multimodal_fusion,
+        eviction: body.eviction,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn describe_store(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    let query = AIQuery::DescribeStore {
+        store: StoreName(store),
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn drop_store(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    let query = AIQuery::DropStore {
+        store: StoreName(store),
+        error_if_not_exists: true,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn set(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: SetBody = parse_body(&body)?;
+    let query = AIQuery::Set {
+        store: StoreName(store),
+        inputs: body
+            .inputs
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect(),
+        preprocess_action: body.preprocess_action,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn get_sim_n(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: GetSimNBody = parse_body(&body)?;
+    let query = AIQuery::GetSimN {
+        store: StoreName(store),
+        search_input: body.search_input,
+        condition: body.condition,
+        closest_n: body.closest_n,
+        algorithm: body.algorithm,
+        preprocess_action: body.preprocess_action,
+        include_system_metadata: false,
+        min_score: body.min_score,
+        max_distance: body.max_distance,
+        normalize_scores: body.normalize_scores,
+        group_by: None,
+        group_size: NonZeroUsize::MIN,
+        additional_search_inputs: vec![],
+        fusion: FusionStrategy::Mean,
+        recency_boost: body.recency_boost,
+        filter_strategy: body.filter_strategy,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn pipeline(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let queries: Vec<AIQuery> = parse_body(&body)?;
+    let result = send(&upstream, &headers, queries).await?;
+    Ok(json_response(StatusCode::OK, &result.into_inner()))
+}
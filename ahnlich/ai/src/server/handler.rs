@@ -1,26 +1,41 @@
 use crate::cli::server::ModelConfig;
 use crate::cli::AIProxyConfig;
 use crate::engine::ai::models::Model;
+use crate::engine::ai::registry::ModelRegistry;
 use crate::engine::store::AIStoreHandler;
 use crate::manager::ModelManager;
+use crate::server::gateway;
 use crate::server::task::AIProxyTask;
+use crate::server::transfer::Transfers;
+use ahnlich_types::ai::AIServerResult;
 use ahnlich_types::client::ConnectedClient;
 use std::error::Error;
 use std::io::Result as IoResult;
 use std::net::SocketAddr;
-use std::sync::atomic::AtomicBool;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 use std::sync::Arc;
+use std::time::Duration;
 use task_manager::Task;
 use task_manager::TaskManager;
 use task_manager::TaskState;
 use tokio::io::BufReader;
-use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
-use utils::client::ClientHandler;
-use utils::persistence::Persistence;
+use utils::audit::AuditLog;
+use utils::client::{ClientHandler, CONNECTION_BUFFER_SIZE};
+use utils::encryption::KeyProvider;
+use utils::filters::FilterHandler;
+use utils::gateway::{HttpGateway, Upstream};
+use utils::jobs::JobHandler;
+use utils::limits::LimitHandler;
+use utils::persistence::{Persistence, PersistenceTaskError};
+use utils::protocol::reject_connection;
+use utils::reload::{self, ConfigReloader};
+use utils::scheduler::Scheduler;
 use utils::server::AhnlichServerUtils;
 use utils::server::ServerUtilsConfig;
+use utils::transport::{Listener, MemoryConnector, ServerStream};
 
 use ahnlich_client_rs::db::{DbClient, DbConnManager};
 use deadpool::managed::Pool;
@@ -29,13 +44,22 @@ const SERVICE_NAME: &str = "ahnlich-ai";
 
 #[derive(Debug, Clone)]
 pub struct AIProxyServer {
-    listener: Arc<TcpListener>,
+    listener: Arc<Listener>,
     config: AIProxyConfig,
     client_handler: Arc<ClientHandler>,
     store_handler: Arc<AIStoreHandler>,
+    job_handler: Arc<JobHandler>,
+    limit_handler: Arc<LimitHandler>,
+    config_reloader: Arc<ConfigReloader>,
+    filter_handler: Arc<FilterHandler>,
     task_manager: Arc<TaskManager>,
     db_client: Arc<DbClient>,
     model_manager: Arc<ModelManager>,
+    http_gateway: Option<HttpGateway>,
+    audit_log: Option<Arc<AuditLog>>,
+    deadlines_exceeded: Arc<AtomicU64>,
+    scheduler: Arc<Scheduler>,
+    key_provider: Option<Arc<dyn KeyProvider>>,
 }
 
 #[async_trait::async_trait]
@@ -46,17 +70,17 @@ impl Task for AIProxyServer {
 
     async fn run(&self) -> TaskState {
         if let Ok((stream, connect_addr)) = self.listener.accept().await {
-            if let Some(connected_client) = self
-                .client_handler
-                .connect(stream.peer_addr().expect("Could not get peer addr"))
-            {
-                log::info!("Connecting to {}", connect_addr);
-                let task = self.create_task(
-                    stream,
-                    self.local_addr().expect("Could not get server addr"),
-                    connected_client,
-                );
-                self.task_manager.spawn_task_loop(task).await;
+            match self.client_handler.connect(connect_addr.clone()) {
+                Ok(connected_client) => {
+                    log::info!("Connecting to {}", connect_addr);
+                    let task = self.create_task(
+                        stream,
+                        self.local_addr().expect("Could not get server addr"),
+                        connected_client,
+                    );
+                    self.task_manager.spawn_task_loop(task).await;
+                }
+                Err(rejected) => reject_connection::<AIServerResult>(stream, rejected.into()).await,
             }
         }
         TaskState::Continue
@@ -73,6 +97,9 @@ impl AhnlichServerUtils for AIProxyServer {
             persistence_interval: self.config.common.persistence_interval,
             allocator_size: self.config.common.allocator_size,
             threadpool_size: self.config.common.threadpool_size,
+            maintenance_threadpool_size: None,
+            key_provider: self.key_provider.clone(),
+            systemd_notify: self.config.common.systemd_notify,
         }
     }
 
@@ -87,6 +114,14 @@ impl AhnlichServerUtils for AIProxyServer {
     fn task_manager(&self) -> Arc<TaskManager> {
         self.task_manager.clone()
     }
+
+    fn http_gateway(&self) -> Option<HttpGateway> {
+        self.http_gateway.clone()
+    }
+
+    fn config_reloader(&self) -> Option<Arc<ConfigReloader>> {
+        Some(self.config_reloader.clone())
+    }
 }
 
 impl AIProxyServer {
@@ -94,26 +129,39 @@ impl AIProxyServer {
         Self::build(config).await
     }
 
-    pub async fn build(config: AIProxyConfig) -> Result<Self, Box<dyn Error>> {
+    pub async fn build(mut config: AIProxyConfig) -> Result<Self, Box<dyn Error>> {
         // Enable log and tracing
         tracer::init_log_or_trace(
             config.common.enable_tracing,
             SERVICE_NAME,
             &config.common.otel_endpoint,
             &config.common.log_level,
+            config.common.log_format,
+            &config.common.sampling(),
         );
-        let listener =
-            tokio::net::TcpListener::bind(format!("{}:{}", &config.common.host, &config.port))
-                .await?;
+        if let Some(ref model_registry) = config.model_registry {
+            let registry = ModelRegistry::load(model_registry)?;
+            config.supported_models.extend(registry.supported_models());
+        }
+        let listener = Listener::bind(
+            &config.common.host,
+            config.port,
+            config.common.unix_socket.as_deref(),
+        )
+        .await?;
         let write_flag = Arc::new(AtomicBool::new(false));
         let db_client = Self::build_db_client(&config).await;
         let mut store_handler =
             AIStoreHandler::new(write_flag.clone(), config.supported_models.clone());
+        let key_provider = config.common.key_provider()?;
         if let Some(ref persist_location) = config.common.persist_location {
-            match Persistence::load_snapshot(persist_location) {
+            match Persistence::load_snapshot(persist_location, key_provider.as_deref()) {
                 Err(e) => {
                     log::error!("Failed to load snapshot from persist location {e}");
-                    if config.common.fail_on_startup_if_persist_load_fails {
+                    // starting without the snapshot would overwrite it once persisted
+                    if config.common.fail_on_startup_if_persist_load_fails
+                        || matches!(e, PersistenceTaskError::Encryption(_))
+                    {
                         return Err(Box::new(e));
                     }
                 }
@@ -122,7 +170,10 @@ impl AIProxyServer {
                 }
             }
         };
-        let client_handler = Arc::new(ClientHandler::new(config.common.maximum_clients));
+        let client_handler = Arc::new(ClientHandler::new(
+            config.common.maximum_clients,
+            config.common.maximum_clients_per_host,
+        ));
         let task_manager = Arc::new(TaskManager::new());
         let mut models: Vec<Model> = Vec::with_capacity(config.supported_models.len());
         for supported_model in &config.supported_models {
@@ -134,21 +185,54 @@ impl AIProxyServer {
         }
 
         let model_config = ModelConfig::from(&config);
-        let model_manager = ModelManager::new(model_config, task_manager.clone()).await?;
+        let model_manager = Arc::new(ModelManager::new(model_config, task_manager.clone()).await?);
+        let limit_handler = Arc::new(LimitHandler::new(&config.common));
+        let config_reloader = Arc::new(
+            ConfigReloader::new(
+                &config.common,
+                reload::flags::<AIProxyConfig>(),
+                limit_handler.clone(),
+                client_handler.clone(),
+            )
+            .with_setting("ai-model-idle-time", model_manager.idle_time()),
+        );
+        config_reloader.load()?;
+        let http_gateway = if config.common.enable_http_gateway {
+            Some(HttpGateway::bind(
+                SERVICE_NAME,
+                &config.common.host,
+                config.http_port,
+                gateway::router(Upstream::new(listener.local_addr()?), model_manager.clone()),
+            )?)
+        } else {
+            None
+        };
 
         Ok(Self {
             listener: Arc::new(listener),
             client_handler,
             store_handler: Arc::new(store_handler),
+            job_handler: Arc::new(JobHandler::new(Duration::from_secs(config.common.job_ttl))),
+            limit_handler,
+            config_reloader,
+            filter_handler: Arc::new(FilterHandler::new(&config.common)),
+            audit_log: AuditLog::open(&config.common)?.map(Arc::new),
+            deadlines_exceeded: Arc::new(AtomicU64::new(0)),
+            scheduler: Arc::new(config.common.scheduler()),
+            key_provider,
             config,
             db_client: Arc::new(db_client),
             task_manager,
-            model_manager: Arc::new(model_manager),
+            model_manager,
+            http_gateway,
         })
     }
 
     async fn build_db_client(config: &AIProxyConfig) -> DbClient {
-        let manager = DbConnManager::new(config.db_host.clone(), config.db_port);
+        let manager = match config.db_unix_socket {
+            Some(ref db_unix_socket) => DbConnManager::unix(db_unix_socket),
+            None => DbConnManager::new(config.db_host.clone(), config.db_port),
+        };
         let pool = Pool::builder(manager)
             .max_size(config.db_client_pool_size)
             .build()
@@ -159,26 +243,54 @@ impl AIProxyServer {
 
     fn create_task(
         &self,
-        stream: TcpStream,
+        stream: ServerStream,
         server_addr: SocketAddr,
         connected_client: ConnectedClient,
     ) -> AIProxyTask {
-        let reader = BufReader::new(stream);
+        let reader = BufReader::with_capacity(CONNECTION_BUFFER_SIZE, stream);
+        let maximum_message_size = self.limit_handler.client(&connected_client).message_size as u64;
         // add client to client_handler
         AIProxyTask {
             reader: Arc::new(Mutex::new(reader)),
             server_addr,
             connected_client,
-            maximum_message_size: self.config.common.message_size as u64,
+            maximum_message_size,
             // "inexpensive" to clone handlers they can be passed around in an Arc
             client_handler: self.client_handler.clone(),
             store_handler: self.store_handler.clone(),
+            job_handler: self.job_handler.clone(),
+            limit_handler: self.limit_handler.clone(),
+            config_reloader: self.config_reloader.clone(),
+            filter_handler: self.filter_handler.clone(),
+            task_manager: self.task_manager.clone(),
             db_client: self.db_client.clone(),
             model_manager: self.model_manager.clone(),
+            transfers: Transfers::default(),
+            audit_log: self.audit_log.clone(),
+            deadlines_exceeded: self.deadlines_exceeded.clone(),
+            cancelled: AtomicBool::new(false),
+            scheduler: self.scheduler.clone(),
         }
     }
 
     pub fn local_addr(&self) -> IoResult<SocketAddr> {
         self.listener.local_addr()
     }
+
+    /// Unix domain socket clients are served over when one is configured
+    pub fn unix_socket(&self) -> Option<&Path> {
+        self.listener.unix_socket()
+    }
+
+    /// Opens in-memory connections to the server from within the same process
+    pub fn memory_connector(&self) -> MemoryConnector {
+        self.listener.memory_connector()
+    }
+
+    /// address of the HTTP gateway when enabled
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http_gateway
+            .as_ref()
+            .and_then(|http_gateway| http_gateway.local_addr().ok())
+    }
 }
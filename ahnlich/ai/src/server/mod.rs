@@ -1,2 +1,5 @@
+mod gateway;
 pub mod handler;
+mod openai;
 pub mod task;
+mod transfer;
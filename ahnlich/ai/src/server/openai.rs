@@ -0,0 +1,198 @@
+//! OpenAI compatible endpoints served on the HTTP gateway so that existing OpenAI SDKs and tools
+//! can be pointed at the proxy to generate embeddings with the locally loaded models
+use crate::engine::ai::models::{InputAction, Model};
+use crate::engine::store::StorePreprocessing;
+use crate::manager::ModelManager;
+use ahnlich_types::ai::{AIStoreInputType, PreprocessAction};
+use ahnlich_types::error::ErrorResponse;
+use ahnlich_types::keyval::StoreInput;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Response;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utils::gateway::{json_response, parse_body, status_code};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum EncodingFormat {
+    #[default]
+    Float,
+    Base64,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsBody {
+    model: String,
+    input: EmbeddingInput,
+    #[serde(default)]
+    encoding_format: EncodingFormat,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Embedding {
+    Float(Vec<f32>),
+    Base64(String),
+}
+
+#[derive(Serialize)]
+struct EmbeddingData {
+    object: &'static str,
+    index: usize,
+    embedding: Embedding,
+}
+
+#[derive(Serialize)]
+struct Usage {
+    prompt_tokens: usize,
+    total_tokens: usize,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsResponse {
+    object: &'static str,
+    data: Vec<EmbeddingData>,
+    model: String,
+    usage: Usage,
+}
+
+#[derive(Serialize)]
+struct OpenAIErrorDetails {
+    message: String,
+    #[serde(rename = "type")]
+    error_type: &'static str,
+    param: Option<&'static str>,
+    code: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct OpenAIError {
+    error: OpenAIErrorDetails,
+}
+
+fn error_response(
+    status: StatusCode,
+    message: String,
+    param: Option<&'static str>,
+    code: Option<&'static str>,
+) -> Response {
+    let error_type = if status.is_server_error() {
+        "server_error"
+    } else {
+        "invalid_request_error"
+    };
+    json_response(
+        status,
+        &OpenAIError {
+            error: OpenAIErrorDetails {
+                message,
+                error_type,
+                param,
+                code,
+            },
+        },
+    )
+}
+
+/// `POST /v1/embeddings` runs the inputs through the requested model, which is any of the names
+/// accepted by `--supported-models` or a model of the registry. Token counts are not tracked by the models so usage is
+/// always reported as zero
+pub(super) async fn embeddings(
+    State(model_manager): State<Arc<ModelManager>>,
+    body: Bytes,
+) -> Response {
+    let body: EmbeddingsBody = match parse_body(&body) {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string(), None, None),
+    };
+    let Some(supported) = model_manager.supported_model(&body.model) else {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            format!("The model `{}` does not exist", body.model),
+            Some("model"),
+            Some("model_not_found"),
+        );
+    };
+    if Model::from(supported).input_type() != AIStoreInputType::RawString {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            format!("The model `{}` does not accept text input", body.model),
+            Some("model"),
+            None,
+        );
+    }
+    let inputs = match body.input {
+        EmbeddingInput::Single(input) => vec![input],
+        EmbeddingInput::Batch(inputs) => inputs,
+    };
+    if inputs.is_empty() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "input must not be empty".to_string(),
+            Some("input"),
+            None,
+        );
+    }
+    let result = model_manager
+        .handle_request(
+            &supported.into(),
+            inputs.into_iter().map(StoreInput::RawString).collect(),
+            PreprocessAction::ModelPreprocessing,
+            StorePreprocessing::default(),
+            InputAction::Index,
+        )
+        .await;
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            let error: ErrorResponse = e.into();
+            return error_response(status_code(error.code), error.message, None, None);
+        }
+    };
+    let data = response
+        .store_keys
+        .into_iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let embedding = match body.encoding_format {
+                EncodingFormat::Float => Embedding::Float(key.0.to_vec()),
+                EncodingFormat::Base64 => Embedding::Base64(
+                    STANDARD.encode(
+                        key.0
+                            .iter()
+                            .flat_map(|value| value.to_le_bytes())
+                            .collect::<Vec<u8>>(),
+                    ),
+                ),
+            };
+            EmbeddingData {
+                object: "embedding",
+                index,
+                embedding,
+            }
+        })
+        .collect();
+    json_response(
+        StatusCode::OK,
+        &EmbeddingsResponse {
+            object: "list",
+            data,
+            model: body.model,
+            usage: Usage {
+                prompt_tokens: response.usage.tokens as usize,
+                total_tokens: response.usage.tokens as usize,
+            },
+        },
+    )
+}
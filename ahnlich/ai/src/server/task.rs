@@ -1,40 +1,80 @@
-use crate::engine::ai::models::Model;
+use crate::engine::jobs::MigrateStoreTask;
 use ahnlich_client_rs::{builders::db as db_params, db::DbClient};
-use ahnlich_types::ai::{AIQuery, AIServerQuery, AIServerResponse, AIServerResult};
+use ahnlich_types::ai::{
+    AIMemoryBreakdown, AIModel, AIQuery, AIServerQuery, AIServerResponse, AIServerResult,
+    CachedResponse, PreprocessAction,
+};
+use ahnlich_types::bincode::serialized_size;
 use ahnlich_types::client::ConnectedClient;
-use ahnlich_types::db::{ServerInfo, ServerResponse};
+use ahnlich_types::db::{ServerInfo, ServerResponse, StoreUpsert};
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::jobs::JobKind;
+use ahnlich_types::keyval::{StoreInput, StoreKey, StoreName, StoreValue};
 use ahnlich_types::metadata::MetadataValue;
 use ahnlich_types::predicate::{Predicate, PredicateCondition};
+use ahnlich_types::similarity::{Algorithm, Similarity};
+use ahnlich_types::version::MIN_CLIENT_VERSION;
 use ahnlich_types::version::VERSION;
+use ahnlich_types::ErrorPolicy;
 use rayon::prelude::*;
 use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use task_manager::Task;
+use task_manager::TaskManager;
 use task_manager::TaskState;
 use tokio::io::BufReader;
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tracing::Instrument;
 use utils::allocator::GLOBAL_ALLOCATOR;
+use utils::audit::{AuditCategory, AuditLog, AuditOperation};
 use utils::client::ClientHandler;
-use utils::protocol::AhnlichProtocol;
+use utils::deadline::Deadline;
+use utils::filters::FilterHandler;
+use utils::jobs::JobHandler;
+use utils::limits::LimitHandler;
+use utils::protocol::{log_query, AhnlichProtocol};
+use utils::reload::ConfigReloader;
+use utils::scheduler::Scheduler;
+use utils::transport::ServerStream;
 
-use crate::engine::store::AIStoreHandler;
+use super::transfer::Transfers;
+use crate::engine::store::{original_input_condition, AIStoreHandler, StorePreprocessing};
 use crate::error::AIProxyError;
 use crate::manager::ModelManager;
-use crate::AHNLICH_AI_RESERVED_META_KEY;
+use crate::{
+    is_reserved_meta_key, AHNLICH_AI_CACHE_EXPIRES_META_KEY, AHNLICH_AI_CACHE_RESPONSE_META_KEY,
+    AHNLICH_AI_LEGACY_RESERVED_META_KEY, AHNLICH_AI_MULTIMODAL_TEXT_META_KEY,
+    AHNLICH_AI_RESERVED_META_KEY,
+};
+
+/// Closest prompts a cache lookup goes through for one that has not expired
+const CACHE_LOOKUP_CANDIDATES: usize = 8;
 
 #[derive(Debug)]
 pub struct AIProxyTask {
     pub(super) server_addr: SocketAddr,
-    pub(super) reader: Arc<Mutex<BufReader<TcpStream>>>,
+    pub(super) reader: Arc<Mutex<BufReader<ServerStream>>>,
     pub(super) client_handler: Arc<ClientHandler>,
     pub(super) store_handler: Arc<AIStoreHandler>,
+    pub(super) job_handler: Arc<JobHandler>,
+    pub(super) limit_handler: Arc<LimitHandler>,
+    pub(super) config_reloader: Arc<ConfigReloader>,
+    pub(super) filter_handler: Arc<FilterHandler>,
+    pub(super) task_manager: Arc<TaskManager>,
     pub(super) connected_client: ConnectedClient,
     pub(super) maximum_message_size: u64,
     pub(super) db_client: Arc<DbClient>,
     pub(super) model_manager: Arc<ModelManager>,
+    pub(super) transfers: Transfers,
+    pub(super) audit_log: Option<Arc<AuditLog>>,
+    // queries of every client abandoned because their deadline passed
+    pub(super) deadlines_exceeded: Arc<AtomicU64>,
+    pub(super) cancelled: AtomicBool,
+    pub(super) scheduler: Arc<Scheduler>,
 }
 
 #[async_trait::async_trait]
@@ -48,20 +88,56 @@ impl AhnlichProtocol for AIProxyTask {
     fn maximum_message_size(&self) -> u64 {
         self.maximum_message_size
     }
-    fn reader(&self) -> Arc<Mutex<BufReader<TcpStream>>> {
+    fn reader(&self) -> Arc<Mutex<BufReader<ServerStream>>> {
         self.reader.clone()
     }
+    fn cancelled(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+    fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
 
-    async fn handle(&self, queries: Vec<AIQuery>) -> AIServerResult {
+    async fn handle(
+        &self,
+        queries: Vec<AIQuery>,
+        error_policy: ErrorPolicy,
+        deadline: Deadline,
+    ) -> AIServerResult {
         let mut result = AIServerResult::with_capacity(queries.len());
         let parent_id = tracer::span_to_trace_parent(tracing::Span::current());
         for query in queries {
-            result.push(match query {
+            if self.hung_up().await {
+                break;
+            }
+            let query = self.filter_query(query);
+            let audited = self
+                .audit_log
+                .as_ref()
+                .and_then(|_| audit_operation(&query));
+            let query_type: &'static str = (&query).into();
+            let store = query.store().cloned();
+            let started = Instant::now();
+            let response: Result<AIServerResponse, ErrorResponse> = match query {
+                _ if deadline.expired() => Err(AIProxyError::DeadlineExceeded.into()),
                 AIQuery::Ping => Ok(AIServerResponse::Pong),
                 AIQuery::ListStores => Ok(AIServerResponse::StoreList(
-                    self.store_handler.list_stores(),
+                    self.store_handler.list_stores(&self.limit_handler),
                 )),
                 AIQuery::InfoServer => Ok(AIServerResponse::InfoServer(self.server_info())),
+                AIQuery::GetMemoryBreakdown => Ok(AIServerResponse::MemoryBreakdown(
+                    self.memory_breakdown().await,
+                )),
+                AIQuery::ReloadConfig => self
+                    .config_reloader
+                    .reload()
+                    .map(AIServerResponse::ConfigReloaded)
+                    .map_err(ErrorResponse::from),
+                AIQuery::DescribeStore { store } => self
+                    .store_handler
+                    .describe_store(&store)
+                    .map(AIServerResponse::StoreDescription)
+                    .map_err(Into::into),
 
                 AIQuery::CreateStore {
                     store,
@@ -71,33 +147,57 @@ impl AhnlichProtocol for AIProxyTask {
                     non_linear_indices,
                     error_if_exists,
                     store_original,
+                    image_preprocessing,
+                    text_truncation,
+                    timestamp_key,
+                    multimodal_fusion,
+                    eviction,
                 } => {
                     let default_metadata_key = &*AHNLICH_AI_RESERVED_META_KEY;
                     if store_original {
                         predicates.insert(default_metadata_key.clone());
+                        if multimodal_fusion.is_some() {
+                            predicates.insert(AHNLICH_AI_MULTIMODAL_TEXT_META_KEY.clone());
+                        }
                     }
-                    let model: Model = (&index_model).into();
-                    let create_store_params = db_params::CreateStoreParams::builder()
-                        .store(store.clone().to_string())
-                        .dimension(model.embedding_size.into())
-                        .create_predicates(predicates)
-                        .non_linear_indices(non_linear_indices)
-                        .error_if_exists(false)
-                        .tracing_id(parent_id.clone())
-                        .build();
-                    match self.db_client.create_store(create_store_params).await {
-                        Err(err) => Err(err.to_string()),
-                        Ok(_) => self
-                            .store_handler
-                            .create_store(
-                                store,
-                                query_model,
-                                index_model,
-                                error_if_exists,
-                                store_original,
-                            )
-                            .map(|_| AIServerResponse::Unit)
-                            .map_err(|e| e.to_string()),
+                    match self.store_handler.store_dimension(
+                        &store,
+                        &query_model,
+                        &index_model,
+                        multimodal_fusion,
+                    ) {
+                        Err(err) => Err(err.into()),
+                        Ok(dimension) => {
+                            let create_store_params = db_params::CreateStoreParams::builder()
+                                .store(store.clone().to_string())
+                                .dimension(dimension)
+                                .create_predicates(predicates)
+                                .non_linear_indices(non_linear_indices)
+                                .timestamp_key(timestamp_key)
+                                .eviction(eviction)
+                                .error_if_exists(false)
+                                .tracing_id(parent_id.clone())
+                                .build();
+                            match self.db_client.create_store(create_store_params).await {
+                                Err(err) => Err(err.into()),
+                                Ok(_) => self
+                                    .store_handler
+                                    .create_store(
+                                        store,
+                                        query_model,
+                                        index_model,
+                                        error_if_exists,
+                                        store_original,
+                                        StorePreprocessing {
+                                            image: image_preprocessing,
+                                            text_truncation,
+                                        },
+                                        multimodal_fusion,
+                                    )
+                                    .map(|_| AIServerResponse::Unit)
+                                    .map_err(ErrorResponse::from),
+                            }
+                        }
                     }
                 }
 
@@ -105,70 +205,46 @@ impl AhnlichProtocol for AIProxyTask {
                     store,
                     inputs,
                     preprocess_action,
-                } => {
-                    let model_manager = &self.model_manager;
-
-                    match self
-                        .store_handler
-                        .set(&store, inputs, model_manager, preprocess_action)
+                } => match self.check_set_limits(&store, inputs.len(), Some(inputs.as_slice())) {
+                    Ok(()) => self
+                        .set(store, inputs, preprocess_action, parent_id.clone())
                         .await
-                    {
-                        Ok((db_inputs, delete_hashset)) => {
-                            match self.db_client.pipeline(2, parent_id.clone()).await {
-                                Err(err) => Err(err.to_string()),
-                                Ok(mut pipeline) => {
-                                    if let Some(del_hashset) = delete_hashset {
-                                        let default_metadatakey = &*AHNLICH_AI_RESERVED_META_KEY;
-                                        let delete_condition =
-                                            PredicateCondition::Value(Predicate::In {
-                                                key: default_metadatakey.clone(),
-                                                value: del_hashset,
-                                            });
-                                        let del_pred_params = db_params::DelPredParams::builder()
-                                            .store(store.clone().to_string())
-                                            .condition(delete_condition)
-                                            .tracing_id(parent_id.clone())
-                                            .build();
-                                        pipeline.del_pred(del_pred_params);
-                                    }
-                                    let set_params = db_params::SetParams::builder()
-                                        .store(store.to_string())
-                                        .inputs(db_inputs)
-                                        .tracing_id(parent_id.clone())
-                                        .build();
+                        .map(AIServerResponse::Set),
+                    Err(err) => Err(err.into()),
+                },
 
-                                    pipeline.set(set_params);
-                                    match pipeline.exec().await {
-                                        Ok(res) => match res.into_inner().as_slice() {
-                                            [Ok(ServerResponse::Set(upsert))]
-                                            | [Ok(_), Ok(ServerResponse::Set(upsert))] => {
-                                                Ok(AIServerResponse::Set(upsert.clone()))
-                                            }
-                                            e => Err(AIProxyError::UnexpectedDBResponse(format!(
-                                                "{e:?}"
-                                            ))
-                                            .to_string()),
-                                        },
-                                        Err(err) => Err(format!("{err}")),
-                                    }
-                                }
-                            }
-                        }
-                        Err(err) => Err(err.to_string()),
-                    }
-                }
+                AIQuery::CacheLookup {
+                    store,
+                    prompt,
+                    threshold,
+                } => self
+                    .cache_lookup(store, prompt, threshold, parent_id.clone())
+                    .await
+                    .map(AIServerResponse::CacheHit),
+                AIQuery::CacheStore {
+                    store,
+                    prompt,
+                    response,
+                    ttl_secs,
+                } => match self.check_set_limits(&store, 1, None) {
+                    Ok(()) => self
+                        .cache_store(store, prompt, response, ttl_secs, parent_id.clone())
+                        .await
+                        .map(AIServerResponse::Set),
+                    Err(err) => Err(err.into()),
+                },
 
                 AIQuery::DelKey { store, key } => {
                     match self.store_handler.store_original(store.clone()) {
-                        Err(err) => Err(err.to_string()),
-                        Ok(false) => Err(AIProxyError::DelKeyError.to_string()),
+                        Err(err) => Err(err.into()),
+                        Ok(false) => Err(AIProxyError::DelKeyError.into()),
                         Ok(true) => {
-                            let default_metadatakey = &*AHNLICH_AI_RESERVED_META_KEY;
-                            let metadata_value: MetadataValue = key.into();
-                            let delete_condition = PredicateCondition::Value(Predicate::In {
-                                key: default_metadatakey.clone(),
-                                value: HashSet::from_iter([metadata_value]),
-                            });
+                            // clients held to a filter only delete the entries it allows
+                            let delete_condition = self.filter_handler.restrict(
+                                &self.connected_client,
+                                &store,
+                                original_input_condition(HashSet::from_iter([key])),
+                            );
                             let del_pred_params = db_params::DelPredParams::builder()
                                 .store(store.to_string())
                                 .condition(delete_condition)
@@ -183,10 +259,10 @@ impl AhnlichProtocol for AIProxyTask {
                                             "{:?}",
                                             res
                                         ))
-                                        .to_string())
+                                        .into())
                                     }
                                 }
-                                Err(err) => Err(format!("{err}")),
+                                Err(err) => Err(err.into()),
                             }
                         }
                     }
@@ -205,8 +281,8 @@ impl AhnlichProtocol for AIProxyTask {
                             .store_handler
                             .drop_store(store, error_if_not_exists)
                             .map(AIServerResponse::Del)
-                            .map_err(|e| e.to_string()),
-                        Err(err) => Err(format!("{err}")),
+                            .map_err(ErrorResponse::from),
+                        Err(err) => Err(err.into()),
                     }
                 }
                 AIQuery::CreatePredIndex { store, predicates } => {
@@ -224,11 +300,10 @@ impl AhnlichProtocol for AIProxyTask {
                             if let ServerResponse::CreateIndex(num) = res {
                                 Ok(AIServerResponse::CreateIndex(num))
                             } else {
-                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
-                                    .to_string())
+                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res)).into())
                             }
                         }
-                        Err(err) => Err(format!("{err}")),
+                        Err(err) => Err(err.into()),
                     }
                 }
                 AIQuery::CreateNonLinearAlgorithmIndex {
@@ -250,11 +325,10 @@ impl AhnlichProtocol for AIProxyTask {
                             if let ServerResponse::CreateIndex(num) = res {
                                 Ok(AIServerResponse::CreateIndex(num))
                             } else {
-                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
-                                    .to_string())
+                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res)).into())
                             }
                         }
-                        Err(err) => Err(format!("{err}")),
+                        Err(err) => Err(err.into()),
                     }
                 }
                 AIQuery::DropPredIndex {
@@ -262,10 +336,7 @@ impl AhnlichProtocol for AIProxyTask {
                     mut predicates,
                     error_if_not_exists,
                 } => {
-                    let default_metadatakey = &*AHNLICH_AI_RESERVED_META_KEY;
-                    if predicates.contains(default_metadatakey) {
-                        let _ = predicates.remove(default_metadatakey);
-                    }
+                    predicates.retain(|key| !is_reserved_meta_key(key));
                     {
                         let drop_pred_index_params = db_params::DropPredIndexParams::builder()
                             .store(store.to_string())
@@ -279,10 +350,10 @@ impl AhnlichProtocol for AIProxyTask {
                                     Ok(AIServerResponse::Del(num))
                                 } else {
                                     Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
-                                        .to_string())
+                                        .into())
                                 }
                             }
-                            Err(err) => Err(format!("{err}")),
+                            Err(err) => Err(err.into()),
                         }
                     }
                 }
@@ -307,35 +378,20 @@ impl AhnlichProtocol for AIProxyTask {
                             if let ServerResponse::Del(num) = res {
                                 Ok(AIServerResponse::Del(num))
                             } else {
-                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
-                                    .to_string())
-                            }
-                        }
-                        Err(err) => Err(format!("{err}")),
-                    }
-                }
-                AIQuery::GetPred { store, condition } => {
-                    let get_pred_params = db_params::GetPredParams::builder()
-                        .store(store.to_string())
-                        .condition(condition)
-                        .tracing_id(parent_id.clone())
-                        .build();
-                    match self.db_client.get_pred(get_pred_params).await {
-                        Ok(res) => {
-                            if let ServerResponse::Get(response) = res {
-                                // conversion to store input here
-                                let output = self
-                                    .store_handler
-                                    .store_key_val_to_store_input_val(response);
-                                Ok(AIServerResponse::Get(output))
-                            } else {
-                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
-                                    .to_string())
+                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res)).into())
                             }
                         }
-                        Err(err) => Err(format!("{err}")),
+                        Err(err) => Err(err.into()),
                     }
                 }
+                AIQuery::GetPred {
+                    store,
+                    condition,
+                    include_system_metadata,
+                } => self
+                    .get_pred(store, condition, include_system_metadata, parent_id.clone())
+                    .await
+                    .map(AIServerResponse::Get),
                 AIQuery::GetSimN {
                     store,
                     search_input,
@@ -343,24 +399,53 @@ impl AhnlichProtocol for AIProxyTask {
                     closest_n,
                     algorithm,
                     preprocess_action,
+                    include_system_metadata,
+                    min_score,
+                    max_distance,
+                    normalize_scores,
+                    group_by,
+                    group_size,
+                    additional_search_inputs,
+                    fusion,
+                    recency_boost,
+                    filter_strategy,
                 } => {
                     let repr = self
                         .store_handler
                         .get_ndarray_repr_for_store(
                             &store,
-                            search_input,
+                            std::iter::once(search_input)
+                                .chain(additional_search_inputs)
+                                .collect(),
                             &self.model_manager,
                             preprocess_action,
                         )
                         .await;
                     match repr {
-                        Ok(store_key) => {
+                        Ok(response) => {
+                            self.model_manager.usage_handler().record(
+                                &store,
+                                Some(&self.connected_client),
+                                response.usage,
+                            );
+                            let mut store_keys = response.store_keys.into_iter();
+                            let store_key =
+                                store_keys.next().expect("Expected an embedding value.");
                             let get_sim_n_params = db_params::GetSimNParams::builder()
                                 .store(store.to_string())
                                 .search_input(store_key)
+                                .additional_search_inputs(store_keys.collect())
+                                .fusion(fusion)
                                 .closest_n(closest_n.into())
                                 .algorithm(algorithm)
                                 .condition(condition)
+                                .min_score(min_score)
+                                .max_distance(max_distance)
+                                .normalize_scores(normalize_scores)
+                                .group_by(group_by)
+                                .group_size(group_size.into())
+                                .recency_boost(recency_boost)
+                                .filter_strategy(filter_strategy)
                                 .tracing_id(parent_id.clone())
                                 .build();
                             match self.db_client.get_sim_n(get_sim_n_params).await {
@@ -373,7 +458,10 @@ impl AhnlichProtocol for AIProxyTask {
                                                 .unzip();
                                         Ok(AIServerResponse::GetSimN(
                                             self.store_handler
-                                                .store_key_val_to_store_input_val(store_key_input)
+                                                .store_key_val_to_store_input_val(
+                                                    store_key_input,
+                                                    include_system_metadata,
+                                                )
                                                 .into_par_iter()
                                                 .zip(similarities.into_par_iter())
                                                 .map(|((a, b), c)| (a, b, c))
@@ -384,13 +472,144 @@ impl AhnlichProtocol for AIProxyTask {
                                             "{:?}",
                                             res
                                         ))
-                                        .to_string())
+                                        .into())
                                     }
                                 }
-                                Err(err) => Err(format!("{err}")),
+                                Err(err) => Err(err.into()),
                             }
                         }
-                        Err(err) => Err(AIProxyError::StandardError(err.to_string()).to_string()),
+                        Err(err) => Err(AIProxyError::StandardError(err.to_string()).into()),
+                    }
+                }
+                AIQuery::AnswerQuestion {
+                    store,
+                    question,
+                    condition,
+                    closest_n,
+                    algorithm,
+                    include_system_metadata,
+                } => {
+                    let repr = self
+                        .store_handler
+                        .get_ndarray_repr_for_store(
+                            &store,
+                            vec![StoreInput::RawString(question.clone())],
+                            &self.model_manager,
+                            PreprocessAction::ModelPreprocessing,
+                        )
+                        .await;
+                    match repr {
+                        Ok(response) => {
+                            self.model_manager.usage_handler().record(
+                                &store,
+                                Some(&self.connected_client),
+                                response.usage,
+                            );
+                            let store_key = response
+                                .store_keys
+                                .into_iter()
+                                .next()
+                                .expect("Expected an embedding value.");
+                            let get_sim_n_params = db_params::GetSimNParams::builder()
+                                .store(store.to_string())
+                                .search_input(store_key)
+                                .closest_n(closest_n.into())
+                                .algorithm(algorithm)
+                                .condition(condition)
+                                .tracing_id(parent_id.clone())
+                                .build();
+                            match self.db_client.get_sim_n(get_sim_n_params).await {
+                                Ok(ServerResponse::GetSimN(response)) => {
+                                    let (store_key_input, similarities): (Vec<_>, Vec<_>) =
+                                        response.into_iter().map(|(a, b, c)| ((a, b), c)).unzip();
+                                    let sources: Vec<_> = self
+                                        .store_handler
+                                        .store_key_val_to_store_input_val(
+                                            store_key_input,
+                                            include_system_metadata,
+                                        )
+                                        .into_iter()
+                                        .zip(similarities)
+                                        .map(|((a, b), c)| (a, b, c))
+                                        .collect();
+                                    // only text inputs can be read for the answer, so a span
+                                    // points at the source it was found in
+                                    let contexts = sources
+                                        .iter()
+                                        .map(|(input, _, _)| match input {
+                                            Some(
+                                                StoreInput::RawString(text)
+                                                | StoreInput::Multimodal { text, .. },
+                                            ) => text.clone(),
+                                            _ => String::new(),
+                                        })
+                                        .collect();
+                                    self.model_manager
+                                        .answer(question, contexts)
+                                        .await
+                                        .map(|answer| AIServerResponse::Answer { answer, sources })
+                                        .map_err(Into::into)
+                                }
+                                Ok(res) => {
+                                    Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
+                                        .into())
+                                }
+                                Err(err) => Err(err.into()),
+                            }
+                        }
+                        Err(err) => Err(err.into()),
+                    }
+                }
+                AIQuery::Classify {
+                    input,
+                    input_model,
+                    labels,
+                    label_model,
+                    preprocess_action,
+                } => self
+                    .model_manager
+                    .classify(input, &input_model, labels, &label_model, preprocess_action)
+                    .await
+                    .map(AIServerResponse::Classify)
+                    .map_err(Into::into),
+                AIQuery::Warmup { stores, models } => {
+                    // every store of the proxy rather than every store of the database
+                    let stores = if stores.is_empty() {
+                        self.store_handler
+                            .list_stores(&self.limit_handler)
+                            .into_iter()
+                            .map(|store_info| store_info.name)
+                            .collect()
+                    } else {
+                        stores
+                    };
+                    let warmup_stores = match stores
+                        .iter()
+                        .try_for_each(|store| self.store_handler.get(store).map(|_| ()))
+                    {
+                        Err(err) => Err(err.into()),
+                        // an empty set would warm up every store of the database
+                        Ok(()) if stores.is_empty() => Ok(()),
+                        Ok(()) => {
+                            let warmup_params = db_params::WarmupParams::builder()
+                                .stores(stores)
+                                .tracing_id(parent_id.clone())
+                                .build();
+                            self.db_client
+                                .warmup(warmup_params)
+                                .await
+                                .map(|_| ())
+                                .map_err(Into::into)
+                        }
+                    };
+                    match warmup_stores {
+                        Ok(()) => self
+                            .model_manager
+                            .warmup(&models)
+                            .await
+                            .map(|_| AIServerResponse::Unit)
+                            .map_err(ErrorResponse::from),
+                        Err(err) => Err(err),
                     }
                 }
                 AIQuery::PurgeStores => {
@@ -400,13 +619,117 @@ impl AhnlichProtocol for AIProxyTask {
                 AIQuery::ListClients => {
                     Ok(AIServerResponse::ClientList(self.client_handler.list()))
                 }
-                AIQuery::GetKey { store, keys } => {
-                    let metadata_values: HashSet<MetadataValue> =
-                        keys.into_iter().map(|value| value.into()).collect();
-                    let get_key_condition = PredicateCondition::Value(Predicate::In {
-                        key: AHNLICH_AI_RESERVED_META_KEY.clone(),
-                        value: metadata_values,
-                    });
+                AIQuery::GetJob { job_id } => self
+                    .job_handler
+                    .get(job_id)
+                    .map(AIServerResponse::JobStatus)
+                    .ok_or_else(|| AIProxyError::JobNotFound(job_id).into()),
+                AIQuery::CancelJob { job_id } => self
+                    .job_handler
+                    .cancel(job_id)
+                    .map(AIServerResponse::JobStatus)
+                    .ok_or_else(|| AIProxyError::JobNotFound(job_id).into()),
+                AIQuery::ListJobs => Ok(AIServerResponse::JobList(self.job_handler.list())),
+                AIQuery::ListSupportedModels => Ok(AIServerResponse::SupportedModelList(
+                    self.model_manager.list_supported_models().await,
+                )),
+                AIQuery::GetUsageStats { reset } => Ok(AIServerResponse::UsageStats(
+                    self.model_manager.usage_handler().stats(reset),
+                )),
+                AIQuery::MigrateStore {
+                    source,
+                    destination,
+                    new_index_model,
+                    batch_size,
+                } => self
+                    .migrate_store(
+                        source,
+                        destination,
+                        new_index_model,
+                        batch_size,
+                        parent_id.clone(),
+                    )
+                    .await
+                    .map(AIServerResponse::JobStarted)
+                    .map_err(ErrorResponse::from),
+                AIQuery::StartChunkedSet {
+                    store,
+                    entries,
+                    preprocess_action,
+                } => match self
+                    .store_handler
+                    .get(&store)
+                    .and_then(|_| self.check_set_limits(&store, entries.len(), None))
+                {
+                    Ok(_) => Ok(AIServerResponse::ChunkedSetStarted(
+                        self.transfers
+                            .start_upload(store, entries, preprocess_action),
+                    )),
+                    Err(err) => Err(err.into()),
+                },
+                AIQuery::SetChunk {
+                    transfer_id,
+                    entry,
+                    data,
+                } => self
+                    .transfers
+                    .append(transfer_id, entry, data)
+                    .map(|_| AIServerResponse::Unit)
+                    .map_err(ErrorResponse::from),
+                AIQuery::FinishChunkedSet { transfer_id } => {
+                    match self.transfers.finish_upload(transfer_id) {
+                        Ok((store, inputs, preprocess_action)) => self
+                            .set(store, inputs, preprocess_action, parent_id.clone())
+                            .await
+                            .map(AIServerResponse::Set),
+                        Err(err) => Err(err.into()),
+                    }
+                }
+                AIQuery::StartChunkedGet {
+                    store,
+                    condition,
+                    include_system_metadata,
+                } => self
+                    .get_pred(store, condition, include_system_metadata, parent_id.clone())
+                    .await
+                    .map(|entries| {
+                        // entries stored without their original input have nothing to transfer
+                        let entries = entries
+                            .into_iter()
+                            .filter_map(|(input, value)| Some((input?, value)))
+                            .collect();
+                        let (transfer_id, entries) = self.transfers.start_download(entries);
+                        AIServerResponse::ChunkedGetStarted {
+                            transfer_id,
+                            entries,
+                        }
+                    }),
+                AIQuery::GetChunk {
+                    transfer_id,
+                    entry,
+                    offset,
+                    length,
+                } => self
+                    .transfers
+                    .chunk(transfer_id, entry, offset, length)
+                    .map(AIServerResponse::Chunk)
+                    .map_err(ErrorResponse::from),
+                AIQuery::EndChunkedTransfer { transfer_id } => self
+                    .transfers
+                    .end(transfer_id)
+                    .map(|_| AIServerResponse::Unit)
+                    .map_err(ErrorResponse::from),
+                AIQuery::GetKey {
+                    store,
+                    keys,
+                    include_system_metadata,
+                } => {
+                    // clients held to a filter only get the entries it allows
+                    let get_key_condition = self.filter_handler.restrict(
+                        &self.connected_client,
+                        &store,
+                        original_input_condition(keys.into_iter().collect()),
+                    );
 
                     let get_pred_params = db_params::GetPredParams::builder()
                         .store(store.to_string())
@@ -418,35 +741,473 @@ impl AhnlichProtocol for AIProxyTask {
                         Ok(res) => {
                             if let ServerResponse::Get(response) = res {
                                 // conversion to store input here
-                                let output = self
-                                    .store_handler
-                                    .store_key_val_to_store_input_val(response);
+                                let output = self.store_handler.store_key_val_to_store_input_val(
+                                    response,
+                                    include_system_metadata,
+                                );
                                 Ok(AIServerResponse::Get(output))
                             } else {
-                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res))
-                                    .to_string())
+                                Err(AIProxyError::UnexpectedDBResponse(format!("{:?}", res)).into())
                             }
                         }
-                        Err(err) => Err(format!("{err}")),
+                        Err(err) => Err(err.into()),
                     }
                 }
-            })
+            };
+            if let (Some(audit_log), Some(operation)) = (&self.audit_log, audited) {
+                audit_log.record(
+                    &self.connected_client,
+                    operation,
+                    response.as_ref().map(|_| ()),
+                );
+            }
+            log_query(query_type, store.as_ref(), started, &response);
+            if matches!(&response, Err(err) if err.code == ErrorCode::DeadlineExceeded) {
+                self.deadlines_exceeded.fetch_add(1, Ordering::Relaxed);
+            }
+            let failed = response.is_err();
+            result.push(response);
+            if failed && error_policy == ErrorPolicy::FailFast {
+                break;
+            }
         }
         result
     }
 }
 
+/// The operation a query is recorded as in the audit log, reads are not recorded
+fn audit_operation(query: &AIQuery) -> Option<AuditOperation> {
+    let operation = match query {
+        AIQuery::CreateStore { store, .. } => AuditOperation::admin("CREATESTORE", [store.clone()]),
+        AIQuery::ReloadConfig => AuditOperation::admin("RELOADCONFIG", []),
+        AIQuery::DropStore { store, .. } => AuditOperation::admin("DROPSTORE", [store.clone()]),
+        AIQuery::CreatePredIndex { store, .. } => {
+            AuditOperation::admin("CREATEPREDINDEX", [store.clone()])
+        }
+        AIQuery::CreateNonLinearAlgorithmIndex { store, .. } => {
+            AuditOperation::admin("CREATENONLINEARALGORITHMINDEX", [store.clone()])
+        }
+        AIQuery::DropPredIndex { store, .. } => {
+            AuditOperation::admin("DROPPREDINDEX", [store.clone()])
+        }
+        AIQuery::DropNonLinearAlgorithmIndex { store, .. } => {
+            AuditOperation::admin("DROPNONLINEARALGORITHMINDEX", [store.clone()])
+        }
+        AIQuery::MigrateStore {
+            source,
+            destination,
+            ..
+        } => AuditOperation::admin("MIGRATESTORE", [source.clone(), destination.clone()]),
+        AIQuery::PurgeStores => AuditOperation::admin("PURGESTORES", []),
+        AIQuery::Warmup { stores, .. } => AuditOperation::admin("WARMUP", stores.iter().cloned()),
+        AIQuery::CancelJob { .. } => AuditOperation::admin("CANCELJOB", []),
+        AIQuery::Set { store, .. } => AuditOperation::write("SET", store.clone()),
+        AIQuery::DelKey { store, .. } => AuditOperation::write("DELKEY", store.clone()),
+        AIQuery::CacheStore { store, .. } => AuditOperation::write("CACHESTORE", store.clone()),
+        // the store of a chunked set is only known to the transfer
+        AIQuery::FinishChunkedSet { .. } => AuditOperation {
+            category: AuditCategory::Write,
+            name: "FINISHCHUNKEDSET",
+            stores: vec![],
+        },
+        AIQuery::GetKey { .. }
+        | AIQuery::GetPred { .. }
+        | AIQuery::GetSimN { .. }
+        | AIQuery::Classify { .. }
+        | AIQuery::AnswerQuestion { .. }
+        | AIQuery::CacheLookup { .. }
+        | AIQuery::GetJob { .. }
+        | AIQuery::ListJobs
+        | AIQuery::StartChunkedSet { .. }
+        | AIQuery::SetChunk { .. }
+        | AIQuery::StartChunkedGet { .. }
+        | AIQuery::GetChunk { .. }
+        | AIQuery::EndChunkedTransfer { .. }
+        | AIQuery::InfoServer
+        | AIQuery::GetMemoryBreakdown
+        | AIQuery::ListClients
+        | AIQuery::ListStores
+        | AIQuery::DescribeStore { .. }
+        | AIQuery::ListSupportedModels
+        | AIQuery::GetUsageStats { .. }
+        | AIQuery::Ping => return None,
+    };
+    Some(operation)
+}
+
 impl AIProxyTask {
+    /// Narrows the conditions of predicate gets and similarity searches to the entries the client
+    /// is allowed before they are passed on to the database. Gets and deletes by key are narrowed
+    /// where they are turned into predicates
+    fn filter_query(&self, mut query: AIQuery) -> AIQuery {
+        let client = &self.connected_client;
+        match &mut query {
+            AIQuery::GetPred {
+                store, condition, ..
+            }
+            | AIQuery::StartChunkedGet {
+                store, condition, ..
+            } => {
+                *condition = self
+                    .filter_handler
+                    .restrict(client, store, condition.clone());
+            }
+            AIQuery::GetSimN {
+                store, condition, ..
+            }
+            | AIQuery::AnswerQuestion {
+                store, condition, ..
+            } => {
+                *condition = self
+                    .filter_handler
+                    .restrict_optional(client, store, condition.take());
+            }
+            _ => {}
+        }
+        query
+    }
+
+    /// Embeds and stores `inputs`, replacing the entries of inputs already in the store
+    #[tracing::instrument(skip(self, inputs))]
+    async fn set(
+        &self,
+        store: StoreName,
+        inputs: Vec<(StoreInput, StoreValue)>,
+        preprocess_action: PreprocessAction,
+        parent_id: Option<String>,
+    ) -> Result<StoreUpsert, ErrorResponse> {
+        let (db_inputs, delete_hashset, usage) = self
+            .store_handler
+            .set(&store, inputs, &self.model_manager, preprocess_action)
+            .await?;
+        self.model_manager
+            .usage_handler()
+            .record(&store, Some(&self.connected_client), usage);
+        self.write(store, db_inputs, delete_hashset, parent_id)
+            .await
+    }
+
+    /// Writes embedded entries to the database, first deleting the entries whose original
+    /// inputs are in `delete_hashset`
+    async fn write(
+        &self,
+        store: StoreName,
+        db_inputs: Vec<(StoreKey, StoreValue)>,
+        delete_hashset: Option<HashSet<StoreInput>>,
+        parent_id: Option<String>,
+    ) -> Result<StoreUpsert, ErrorResponse> {
+        let mut pipeline = self.db_client.pipeline(2, parent_id.clone()).await?;
+        if let Some(del_hashset) = delete_hashset {
+            let delete_condition = original_input_condition(del_hashset);
+            let del_pred_params = db_params::DelPredParams::builder()
+                .store(store.to_string())
+                .condition(delete_condition)
+                .tracing_id(parent_id.clone())
+                .build();
+            pipeline.del_pred(del_pred_params);
+        }
+        let set_params = db_params::SetParams::builder()
+            .store(store.to_string())
+            .inputs(db_inputs)
+            .tracing_id(parent_id)
+            .build();
+        pipeline.set(set_params);
+        match pipeline.exec().await?.into_inner().as_slice() {
+            [Ok(ServerResponse::Set(upsert))] | [Ok(_), Ok(ServerResponse::Set(upsert))] => {
+                Ok(upsert.clone())
+            }
+            e => Err(AIProxyError::UnexpectedDBResponse(format!("{e:?}")).into()),
+        }
+    }
+
+    /// Caches `response` for `prompt`, embedded with the index model of the store
+    #[tracing::instrument(skip(self, response))]
+    async fn cache_store(
+        &self,
+        store: StoreName,
+        prompt: String,
+        response: String,
+        ttl_secs: Option<u64>,
+        parent_id: Option<String>,
+    ) -> Result<StoreUpsert, ErrorResponse> {
+        let (mut db_inputs, delete_hashset, usage) = self
+            .store_handler
+            .set(
+                &store,
+                vec![(StoreInput::RawString(prompt), StoreValue::new())],
+                &self.model_manager,
+                PreprocessAction::ModelPreprocessing,
+            )
+            .await?;
+        self.model_manager
+            .usage_handler()
+            .record(&store, Some(&self.connected_client), usage);
+        let expires_at = ttl_secs.map(|ttl| unix_now().saturating_add(ttl));
+        for (_, value) in db_inputs.iter_mut() {
+            value.insert(
+                AHNLICH_AI_CACHE_RESPONSE_META_KEY.clone(),
+                MetadataValue::RawString(response.clone()),
+            );
+            if let Some(expires_at) = expires_at {
+                value.insert(
+                    AHNLICH_AI_CACHE_EXPIRES_META_KEY.clone(),
+                    MetadataValue::RawString(expires_at.to_string()),
+                );
+            }
+        }
+        self.write(store, db_inputs, delete_hashset, parent_id)
+            .await
+    }
+
+    /// Returns the unexpired response cached for the prompt closest to `prompt` that is at least
+    /// `threshold` similar, deleting the expired responses found closer to it
+    #[tracing::instrument(skip(self))]
+    async fn cache_lookup(
+        &self,
+        store: StoreName,
+        prompt: String,
+        threshold: Similarity,
+        parent_id: Option<String>,
+    ) -> Result<Option<CachedResponse>, ErrorResponse> {
+        let response = self
+            .store_handler
+            .get_ndarray_repr_for_store(
+                &store,
+                vec![StoreInput::RawString(prompt)],
+                &self.model_manager,
+                PreprocessAction::ModelPreprocessing,
+            )
+            .await?;
+        self.model_manager.usage_handler().record(
+            &store,
+            Some(&self.connected_client),
+            response.usage,
+        );
+        let store_key = response
+            .store_keys
+            .into_iter()
+            .next()
+            .expect("Expected an embedding value.");
+        let condition = self
+            .filter_handler
+            .restrict_optional(&self.connected_client, &store, None);
+        let get_sim_n_params = db_params::GetSimNParams::builder()
+            .store(store.to_string())
+            .search_input(store_key)
+            .closest_n(CACHE_LOOKUP_CANDIDATES)
+            .algorithm(Algorithm::CosineSimilarity)
+            .condition(condition)
+            .min_score(Some(threshold))
+            .tracing_id(parent_id.clone())
+            .build();
+        let candidates = match self.db_client.get_sim_n(get_sim_n_params).await {
+            Ok(ServerResponse::GetSimN(candidates)) => candidates,
+            Ok(res) => return Err(AIProxyError::UnexpectedDBResponse(format!("{res:?}")).into()),
+            Err(err) => return Err(err.into()),
+        };
+        let now = unix_now();
+        let mut expired = vec![];
+        let mut hit = None;
+        for (key, mut value, similarity) in candidates {
+            // entries set by other queries than CacheStore are not cached responses
+            let Some(MetadataValue::RawString(response)) =
+                value.remove(&*AHNLICH_AI_CACHE_RESPONSE_META_KEY)
+            else {
+                continue;
+            };
+            let expires_at = match value.get(&*AHNLICH_AI_CACHE_EXPIRES_META_KEY) {
+                Some(MetadataValue::RawString(expires_at)) => expires_at.parse::<u64>().ok(),
+                _ => None,
+            };
+            if expires_at.is_some_and(|expires_at| expires_at <= now) {
+                expired.push(key);
+                continue;
+            }
+            let prompt = match value.remove(&*AHNLICH_AI_RESERVED_META_KEY) {
+                Some(MetadataValue::RawString(prompt)) => Some(prompt),
+                _ => None,
+            };
+            hit = Some(CachedResponse {
+                prompt,
+                response,
+                similarity,
+                expires_at,
+            });
+            break;
+        }
+        if !expired.is_empty() {
+            let del_key_params = db_params::DelKeyParams::builder()
+                .store(store.to_string())
+                .keys(expired)
+                .tracing_id(parent_id)
+                .build();
+            self.db_client.del_key(del_key_params).await?;
+        }
+        Ok(hit)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_pred(
+        &self,
+        store: StoreName,
+        condition: PredicateCondition,
+        include_system_metadata: bool,
+        parent_id: Option<String>,
+    ) -> Result<Vec<(Option<StoreInput>, StoreValue)>, ErrorResponse> {
+        let get_pred_params = db_params::GetPredParams::builder()
+            .store(store.to_string())
+            .condition(condition)
+            .tracing_id(parent_id)
+            .build();
+        match self.db_client.get_pred(get_pred_params).await {
+            // conversion to store input here
+            Ok(ServerResponse::Get(response)) => Ok(self
+                .store_handler
+                .store_key_val_to_store_input_val(response, include_system_metadata)),
+            Ok(res) => Err(AIProxyError::UnexpectedDBResponse(format!("{res:?}")).into()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Creates the destination store and starts a job re-embedding the original inputs of the
+    /// source store into it
+    #[tracing::instrument(skip(self))]
+    async fn migrate_store(
+        &self,
+        source: StoreName,
+        destination: StoreName,
+        new_index_model: AIModel,
+        batch_size: NonZeroUsize,
+        parent_id: Option<String>,
+    ) -> Result<u64, AIProxyError> {
+        let query_model = self
+            .store_handler
+            .migration_query_model(&source, &new_index_model)?;
+        let preprocessing = self.store_handler.preprocessing(&source)?;
+        // entries saved before the system metadata namespace keep their input under the legacy key
+        let all_originals = PredicateCondition::Value(Predicate::NotIn {
+            key: AHNLICH_AI_RESERVED_META_KEY.clone(),
+            value: HashSet::new(),
+        })
+        .or(PredicateCondition::Value(Predicate::NotIn {
+            key: AHNLICH_AI_LEGACY_RESERVED_META_KEY.clone(),
+            value: HashSet::new(),
+        }));
+        let get_pred_params = db_params::GetPredParams::builder()
+            .store(source.to_string())
+            .condition(all_originals)
+            .tracing_id(parent_id.clone())
+            .build();
+        let entries = match self.db_client.get_pred(get_pred_params).await {
+            Ok(ServerResponse::Get(entries)) => entries,
+            Ok(res) => return Err(AIProxyError::UnexpectedDBResponse(format!("{res:?}"))),
+            Err(err) => return Err(AIProxyError::DatabaseClientError(err.to_string())),
+        };
+        let inputs: Vec<_> = self
+            .store_handler
+            .store_key_val_to_store_input_val(entries, false)
+            .into_iter()
+            .filter_map(|(input, value)| Some((input?, value)))
+            .collect();
+
+        let model = self.store_handler.model(&new_index_model)?;
+        let create_store_params = db_params::CreateStoreParams::builder()
+            .store(destination.to_string())
+            .dimension(model.embedding_size.into())
+            .create_predicates(HashSet::from_iter([AHNLICH_AI_RESERVED_META_KEY.clone()]))
+            .error_if_exists(false)
+            .tracing_id(parent_id.clone())
+            .build();
+        self.db_client
+            .create_store(create_store_params)
+            .await
+            .map_err(|err| AIProxyError::DatabaseClientError(err.to_string()))?;
+        self.store_handler.create_store(
+            destination.clone(),
+            query_model,
+            new_index_model,
+            true,
+            true,
+            preprocessing,
+            None,
+        )?;
+
+        let job = self
+            .job_handler
+            .register(JobKind::MigrateStore, inputs.len());
+        let job_id = job.id();
+        let task = MigrateStoreTask::new(
+            job,
+            self.store_handler.clone(),
+            self.model_manager.clone(),
+            self.db_client.clone(),
+            destination,
+            batch_size,
+            inputs,
+        )
+        .with_tracing_id(parent_id);
+        self.task_manager.spawn_task_loop(task).await;
+        Ok(job_id)
+    }
+
     #[tracing::instrument(skip(self))]
     fn server_info(&self) -> ServerInfo {
         ServerInfo {
             address: format!("{}", self.server_addr),
             version: *VERSION,
+            min_client_version: *MIN_CLIENT_VERSION,
+            max_client_version: *VERSION,
             r#type: ahnlich_types::ServerType::AI,
             limit: GLOBAL_ALLOCATOR.limit(),
             remaining: GLOBAL_ALLOCATOR.remaining(),
+            request_limits: self.limit_handler.client(&self.connected_client),
+            // requests to the ai proxy are not admitted by their memory
+            in_flight_memory: 0,
+            deadlines_exceeded: self.deadlines_exceeded.load(Ordering::Relaxed),
+            priorities: self.scheduler.stats(),
+            connections: self.client_handler.stats(),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn memory_breakdown(&self) -> AIMemoryBreakdown {
+        AIMemoryBreakdown {
+            limit: GLOBAL_ALLOCATOR.limit(),
+            remaining: GLOBAL_ALLOCATOR.remaining(),
+            models: self.model_manager.models_memory().await,
+            connections: self.client_handler.memory(),
         }
     }
+
+    /// Checks a set of `len` entries against the limits of the client and store. The size of
+    /// chunked sets is not checked as their inputs are sent over several messages
+    fn check_set_limits(
+        &self,
+        store: &StoreName,
+        len: usize,
+        inputs: Option<&[(StoreInput, StoreValue)]>,
+    ) -> Result<(), AIProxyError> {
+        let limits = self.limit_handler.request(&self.connected_client, store);
+        if let Some(limit) = limits.batch_size.filter(|limit| len > *limit) {
+            return Err(AIProxyError::BatchTooLarge {
+                store: store.clone(),
+                len,
+                limit,
+            });
+        }
+        // inputs that cannot be sized would have failed to deserialize in the first place
+        let size = inputs
+            .map(|inputs| serialized_size(inputs).unwrap_or_default() as usize)
+            .unwrap_or_default();
+        if size > limits.message_size {
+            return Err(AIProxyError::RequestTooLarge {
+                store: store.clone(),
+                size,
+                limit: limits.message_size,
+            });
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -462,6 +1223,14 @@ impl Task for AIProxyTask {
     }
 }
 
+/// Seconds since the Unix epoch, cached responses expire by
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
 impl Drop for AIProxyTask {
     fn drop(&mut self) {
         self.client_handler.disconnect(&self.connected_client);
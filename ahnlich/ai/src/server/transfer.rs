@@ -0,0 +1,178 @@
+//! Chunked transfers let inputs larger than `message_size` be stored and read over several
+//! messages. A transfer lives on the connection that started it and is dropped with it.
+use crate::error::AIProxyError;
+use ahnlich_types::ai::{AIStoreInputType, ChunkedEntry, PreprocessAction};
+use ahnlich_types::keyval::{StoreInput, StoreName, StoreValue};
+use fallible_collections::vec::FallibleVec;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+type FinishedUpload = (StoreName, Vec<(StoreInput, StoreValue)>, PreprocessAction);
+
+#[derive(Debug)]
+struct Upload {
+    store: StoreName,
+    preprocess_action: PreprocessAction,
+    entries: Vec<(ChunkedEntry, Vec<u8>)>,
+}
+
+#[derive(Debug)]
+enum Transfer {
+    Upload(Upload),
+    Download(Vec<Vec<u8>>),
+}
+
+/// Chunked sets and gets in progress on a connection
+#[derive(Debug, Default)]
+pub(crate) struct Transfers {
+    next_id: AtomicU64,
+    transfers: Mutex<HashMap<u64, Transfer>>,
+}
+
+impl Transfers {
+    fn insert(&self, transfer: Transfer) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.transfers
+            .lock()
+            .expect("transfers lock poisoned")
+            .insert(id, transfer);
+        id
+    }
+
+    pub(crate) fn start_upload(
+        &self,
+        store: StoreName,
+        entries: Vec<ChunkedEntry>,
+        preprocess_action: PreprocessAction,
+    ) -> u64 {
+        self.insert(Transfer::Upload(Upload {
+            store,
+            preprocess_action,
+            entries: entries.into_iter().map(|entry| (entry, vec![])).collect(),
+        }))
+    }
+
+    /// Appends a chunk to the input of `entry`, chunks of an entry are expected in order
+    pub(crate) fn append(
+        &self,
+        transfer_id: u64,
+        entry: usize,
+        data: Vec<u8>,
+    ) -> Result<(), AIProxyError> {
+        let mut transfers = self.transfers.lock().expect("transfers lock poisoned");
+        let Some(Transfer::Upload(upload)) = transfers.get_mut(&transfer_id) else {
+            return Err(AIProxyError::TransferNotFound(transfer_id));
+        };
+        let Some((manifest, input)) = upload.entries.get_mut(entry) else {
+            return Err(AIProxyError::ChunkedTransferError {
+                transfer_id,
+                message: format!("entry {entry} was not part of the set"),
+            });
+        };
+        if input.len() + data.len() > manifest.size {
+            return Err(AIProxyError::ChunkedTransferError {
+                transfer_id,
+                message: format!(
+                    "input of entry {entry} exceeds its size of {} bytes",
+                    manifest.size
+                ),
+            });
+        }
+        input.try_extend_from_slice(&data)?;
+        Ok(())
+    }
+
+    /// Ends an upload, returning its entries once the input of every entry has been received
+    pub(crate) fn finish_upload(&self, transfer_id: u64) -> Result<FinishedUpload, AIProxyError> {
+        let mut transfers = self.transfers.lock().expect("transfers lock poisoned");
+        if !matches!(transfers.get(&transfer_id), Some(Transfer::Upload(_))) {
+            return Err(AIProxyError::TransferNotFound(transfer_id));
+        }
+        let Some(Transfer::Upload(upload)) = transfers.remove(&transfer_id) else {
+            unreachable!("transfer was checked to be an upload");
+        };
+        let inputs = upload
+            .entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, (manifest, input))| {
+                if input.len() != manifest.size {
+                    return Err(AIProxyError::ChunkedTransferError {
+                        transfer_id,
+                        message: format!(
+                            "received {} of {} bytes for entry {index}",
+                            input.len(),
+                            manifest.size
+                        ),
+                    });
+                }
+                let input =
+                    StoreInput::from_bytes(&manifest.input_type, input).ok_or_else(|| {
+                        AIProxyError::ChunkedTransferError {
+                            transfer_id,
+                            message: format!(
+                                "input of entry {index} is not a valid {} input",
+                                manifest.input_type
+                            ),
+                        }
+                    })?;
+                Ok((input, manifest.value))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok((upload.store, inputs, upload.preprocess_action))
+    }
+
+    /// Holds the inputs of `entries` to be read in chunks, returning the transfer id with the
+    /// manifest of each entry
+    pub(crate) fn start_download(
+        &self,
+        entries: Vec<(StoreInput, StoreValue)>,
+    ) -> (u64, Vec<ChunkedEntry>) {
+        let (manifest, inputs): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .map(|(input, value)| {
+                let manifest = ChunkedEntry {
+                    input_type: AIStoreInputType::from(&input),
+                    size: input.len(),
+                    value,
+                };
+                let input = input.as_bytes().into_owned();
+                (manifest, input)
+            })
+            .unzip();
+        (self.insert(Transfer::Download(inputs)), manifest)
+    }
+
+    /// Reads up to `length` bytes of the input of `entry` from `offset`
+    pub(crate) fn chunk(
+        &self,
+        transfer_id: u64,
+        entry: usize,
+        offset: usize,
+        length: usize,
+    ) -> Result<Vec<u8>, AIProxyError> {
+        let transfers = self.transfers.lock().expect("transfers lock poisoned");
+        let Some(Transfer::Download(inputs)) = transfers.get(&transfer_id) else {
+            return Err(AIProxyError::TransferNotFound(transfer_id));
+        };
+        let Some(input) = inputs.get(entry) else {
+            return Err(AIProxyError::ChunkedTransferError {
+                transfer_id,
+                message: format!("entry {entry} was not part of the get"),
+            });
+        };
+        let start = offset.min(input.len());
+        let end = start.saturating_add(length).min(input.len());
+        Ok(input[start..end].to_vec())
+    }
+
+    pub(crate) fn end(&self, transfer_id: u64) -> Result<(), AIProxyError> {
+        self.transfers
+            .lock()
+            .expect("transfers lock poisoned")
+            .remove(&transfer_id)
+            .map(|_| ())
+            .ok_or(AIProxyError::TransferNotFound(transfer_id))
+    }
+}
@@ -2,14 +2,19 @@ use ahnlich_db::cli::ServerConfig;
 use ahnlich_db::server::handler::Server;
 use ahnlich_types::{
     ai::{
-        AIModel, AIQuery, AIServerQuery, AIServerResponse, AIServerResult, AIStoreInfo,
-        PreprocessAction,
+        AIExecutionProvider, AIModel, AIModelInfo, AIQuery, AIServerQuery, AIServerResponse,
+        AIServerResult, AIStoreDescription, AIStoreInfo, AIStoreInputType, ChunkedEntry,
+        ImagePreprocessing, ImageResize, MultimodalFusion, PreprocessAction, TextTruncation,
+        UsageStats,
     },
     db::StoreUpsert,
-    keyval::{StoreInput, StoreName, StoreValue},
+    error::ErrorCode,
+    jobs::{JobKind, JobState, JobStatus},
+    keyval::{EvictionPolicy, StoreEviction, StoreInput, StoreName, StoreValue},
     metadata::{MetadataKey, MetadataValue},
     predicate::{Predicate, PredicateCondition},
-    similarity::Algorithm,
+    similarity::{Algorithm, FilterStrategy, FusionStrategy, Similarity},
+    RequestLimits,
 };
 // use flurry::HashMap;
 use utils::server::AhnlichServerUtils;
@@ -24,9 +29,10 @@ use std::{
 
 use crate::{
     cli::{server::SupportedModels, AIProxyConfig},
-    engine::ai::models::Model,
+    engine::ai::models::{InputAction, Model},
     error::AIProxyError,
     server::handler::AIProxyServer,
+    AHNLICH_AI_TRUNCATION_META_KEY,
 };
 use ahnlich_types::bincode::BinCodeSerAndDeser;
 use std::net::SocketAddr;
@@ -35,6 +41,11 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::time::{timeout, Duration};
 
+const DEFAULT_REQUEST_LIMITS: RequestLimits = RequestLimits {
+    message_size: 1_048_576,
+    batch_size: None,
+};
+
 static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
 static AI_CONFIG: Lazy<AIProxyConfig> = Lazy::new(|| AIProxyConfig::default().os_select_port());
 
@@ -54,6 +65,29 @@ static AI_CONFIG_LIMITED_MODELS: Lazy<AIProxyConfig> = Lazy::new(|| {
         .set_supported_models(vec![SupportedModels::AllMiniLML6V2])
 });
 
+static AI_CONFIG_WITH_CPU_MODEL: Lazy<AIProxyConfig> = Lazy::new(|| {
+    AIProxyConfig::default()
+        .os_select_port()
+        .set_supported_models(vec![SupportedModels::AllMiniLML6V2])
+        .set_model_execution_providers("all-minilm-l6-v2=cpu".parse().unwrap())
+});
+
+static AI_CONFIG_WITH_MODEL_REGISTRY: Lazy<AIProxyConfig> = Lazy::new(|| {
+    AIProxyConfig::default()
+        .os_select_port()
+        .set_supported_models(vec![SupportedModels::AllMiniLML6V2])
+        .set_model_registry(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/model_registry.json"),
+        )
+});
+
+static AI_CONFIG_WITH_REQUEST_LIMITS: Lazy<AIProxyConfig> = Lazy::new(|| {
+    AIProxyConfig::default()
+        .os_select_port()
+        .set_supported_models(vec![SupportedModels::AllMiniLML6V2])
+        .set_store_limit("Small=1048576,1".parse().unwrap())
+});
+
 async fn get_server_response(
     reader: &mut BufReader<TcpStream>,
     query: AIServerQuery,
@@ -87,6 +121,28 @@ async fn get_server_response(
     response
 }
 
+async fn http_request(
+    address: SocketAddr,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> (u16, serde_json::Value) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
 async fn query_server_assert_result(
     reader: &mut BufReader<TcpStream>,
     query: AIServerQuery,
@@ -130,6 +186,94 @@ async fn test_simple_ai_proxy_ping() {
     query_server_assert_result(&mut reader, message, expected.clone()).await;
 }
 
+#[tokio::test]
+async fn test_ai_proxy_http_gateway() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let mut config = AI_CONFIG.clone().enable_http_gateway();
+    config.db_port = server.local_addr().unwrap().port();
+    let ai_server = AIProxyServer::new(config)
+        .await
+        .expect("Could not initialize ai proxy");
+    let address = ai_server.http_addr().expect("Http gateway not enabled");
+    let _ = tokio::spawn(async move { server.start().await });
+    let _ = tokio::spawn(async move { ai_server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let create_store = r#"{"store": "Main", "predicates": ["brand"]}"#;
+    let (status, body) = http_request(address, "POST", "/stores", create_store).await;
+    assert_eq!((status, body), (200, serde_json::json!("Unit")));
+    let (status, body) = http_request(address, "POST", "/stores", create_store).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["code"], "StoreAlreadyExists");
+
+    let set = r#"{"inputs": [
+        {"key": {"RawString": "Jordan One"}, "value": {"brand": {"RawString": "Nike"}}},
+        {"key": {"RawString": "Yeezey"}, "value": {"brand": {"RawString": "Adidas"}}}
+    ]}"#;
+    let (status, body) = http_request(address, "POST", "/stores/Main/entries", set).await;
+    assert_eq!(
+        (status, body),
+        (
+            200,
+            serde_json::json!({"Set": {"inserted": 2, "updated": 0}})
+        )
+    );
+
+    let get_sim_n = r#"{"search_input": {"RawString": "Jordan"}, "closest_n": 1}"#;
+    let (status, body) = http_request(address, "POST", "/stores/Main/query", get_sim_n).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["GetSimN"][0][0]["RawString"], "Jordan One");
+
+    let (status, body) = http_request(address, "POST", "/stores/Other/query", get_sim_n).await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], "StoreNotFound");
+}
+
+#[tokio::test]
+async fn test_ai_proxy_openai_embeddings() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let mut config = AI_CONFIG.clone().enable_http_gateway();
+    config.db_port = server.local_addr().unwrap().port();
+    let ai_server = AIProxyServer::new(config)
+        .await
+        .expect("Could not initialize ai proxy");
+    let address = ai_server.http_addr().expect("Http gateway not enabled");
+    let _ = tokio::spawn(async move { server.start().await });
+    let _ = tokio::spawn(async move { ai_server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let request = r#"{"model": "all-minilm-l6-v2", "input": ["Jordan One", "Yeezey"]}"#;
+    let (status, body) = http_request(address, "POST", "/v1/embeddings", request).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["object"], "list");
+    assert_eq!(body["model"], "all-minilm-l6-v2");
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+    assert_eq!(body["data"][1]["index"], 1);
+    assert_eq!(body["data"][0]["embedding"].as_array().unwrap().len(), 384);
+
+    let request =
+        r#"{"model": "all-minilm-l6-v2", "input": "Jordan One", "encoding_format": "base64"}"#;
+    let (status, body) = http_request(address, "POST", "/v1/embeddings", request).await;
+    assert_eq!(status, 200);
+    assert!(body["data"][0]["embedding"].is_string());
+
+    let request = r#"{"model": "text-embedding-3-small", "input": "Jordan One"}"#;
+    let (status, body) = http_request(address, "POST", "/v1/embeddings", request).await;
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["code"], "model_not_found");
+
+    let request = r#"{"model": "resnet-50", "input": "Jordan One"}"#;
+    let (status, body) = http_request(address, "POST", "/v1/embeddings", request).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+}
+
 #[tokio::test]
 async fn test_ai_proxy_create_store_success() {
     let address = provision_test_servers().await;
@@ -144,6 +288,11 @@ async fn test_ai_proxy_create_store_success() {
         non_linear_indices: HashSet::new(),
         error_if_exists: true,
         store_original: true,
+        image_preprocessing: ImagePreprocessing::default(),
+        text_truncation: TextTruncation::default(),
+        timestamp_key: None,
+        multimodal_fusion: None,
+        eviction: None,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
@@ -154,13 +303,14 @@ async fn test_ai_proxy_create_store_success() {
     // list stores to verify it's present.
     let message = AIServerQuery::from_queries(&[AIQuery::ListStores]);
     let mut expected = AIServerResult::with_capacity(1);
-    let ai_model: Model = (&AIModel::AllMiniLML6V2).into();
+    let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
     expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
         AIStoreInfo {
             name: store_name.clone(),
             query_model: AIModel::AllMiniLML6V2,
             index_model: AIModel::AllMiniLML6V2,
             embedding_size: ai_model.embedding_size.into(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     let mut reader = BufReader::new(second_stream);
@@ -186,6 +336,11 @@ async fn test_ai_store_get_key_works() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: false,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::Set {
             store: store_name.clone(),
@@ -199,6 +354,7 @@ async fn test_ai_store_get_key_works() {
     let message = AIServerQuery::from_queries(&[AIQuery::GetKey {
         store: store_name,
         keys: vec![store_input.clone()],
+        include_system_metadata: false,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
@@ -234,6 +390,308 @@ async fn test_list_clients_works() {
     };
 }
 
+#[tokio::test]
+async fn test_ai_proxy_usage_stats() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let store_name = StoreName(String::from("Usage Store"));
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![
+                (
+                    StoreInput::RawString(String::from("Jordan One")),
+                    StoreValue::new(),
+                ),
+                (
+                    StoreInput::RawString(String::from("Yeezey")),
+                    StoreValue::new(),
+                ),
+            ],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::GetSimN {
+            store: store_name.clone(),
+            search_input: StoreInput::RawString(String::from("Jordan")),
+            condition: None,
+            closest_n: NonZeroUsize::new(1).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+            include_system_metadata: false,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+        },
+        AIQuery::GetUsageStats { reset: true },
+        AIQuery::GetUsageStats { reset: false },
+    ]);
+    let response = get_server_response(&mut reader, message).await;
+    let inner = response.into_inner();
+
+    match inner.as_slice() {
+        [Ok(AIServerResponse::Unit), Ok(AIServerResponse::Set(_)), Ok(AIServerResponse::GetSimN(_)), Ok(AIServerResponse::UsageStats(stats)), Ok(AIServerResponse::UsageStats(after_reset))] =>
+        {
+            let store_usage = stats.stores[&store_name];
+            assert_eq!(store_usage.inputs, 3);
+            assert_eq!(store_usage.images, 0);
+            assert!(store_usage.tokens >= 3);
+            assert_eq!(stats.clients["127.0.0.1"], store_usage);
+            assert_eq!(stats.models[&AIModel::AllMiniLML6V2], store_usage);
+            assert_eq!(after_reset, &UsageStats::default());
+        }
+        a => panic!("Unexpected result for usage stats {a:?}"),
+    };
+}
+
+#[tokio::test]
+async fn test_ai_proxy_classify() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let labels = vec![String::from("finance"), String::from("pets")];
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::Classify {
+            input: StoreInput::RawString(String::from("My cat sleeps on the sofa all day")),
+            input_model: AIModel::AllMiniLML6V2,
+            labels: labels.clone(),
+            label_model: AIModel::AllMiniLML6V2,
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::Classify {
+            input: StoreInput::RawString(String::from("My cat")),
+            input_model: AIModel::AllMiniLML6V2,
+            labels: labels.clone(),
+            label_model: AIModel::BGEBaseEnV15,
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::Classify {
+            input: StoreInput::RawString(String::from("My cat")),
+            input_model: AIModel::AllMiniLML6V2,
+            labels: vec![],
+            label_model: AIModel::AllMiniLML6V2,
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+    ]);
+    let response = get_server_response(&mut reader, message).await;
+
+    match response.into_inner().as_slice() {
+        [Ok(AIServerResponse::Classify(scores)), Err(mismatch), Err(no_labels)] => {
+            assert_eq!(scores.len(), 2);
+            assert_eq!(scores[0].0, "pets");
+            let total: f32 = scores.iter().map(|(_, score)| score.0).sum();
+            assert!((total - 1.0).abs() < 1e-4);
+            assert_eq!(mismatch.code, ErrorCode::DimensionMismatch);
+            assert_eq!(no_labels.code, ErrorCode::InvalidArgument);
+        }
+        a => panic!("Unexpected result for classify {a:?}"),
+    };
+}
+
+#[tokio::test]
+async fn test_ai_proxy_answer_question_requires_answer_model() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let store_name = StoreName(String::from("Answers"));
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![(
+                StoreInput::RawString(String::from("Ahnlich is written in Rust")),
+                StoreValue::new(),
+            )],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::AnswerQuestion {
+            store: store_name.clone(),
+            question: String::from("What is Ahnlich written in?"),
+            condition: None,
+            closest_n: NonZeroUsize::new(1).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            include_system_metadata: false,
+        },
+    ]);
+    let response = get_server_response(&mut reader, message).await;
+
+    match response.into_inner().as_slice() {
+        [Ok(AIServerResponse::Unit), Ok(AIServerResponse::Set(_)), Err(err)] => {
+            assert_eq!(err.code, ErrorCode::InvalidArgument);
+        }
+        a => panic!("Unexpected result for answer question {a:?}"),
+    };
+}
+
+#[tokio::test]
+async fn test_ai_proxy_semantic_cache() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let store_name = StoreName(String::from("Prompt Cache"));
+    let cache_lookup = |prompt: &str| AIQuery::CacheLookup {
+        store: store_name.clone(),
+        prompt: prompt.to_string(),
+        threshold: Similarity(0.9),
+    };
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: Some(StoreEviction {
+                max_entries: NonZeroUsize::new(100),
+                max_bytes: None,
+                policy: EvictionPolicy::LRU,
+            }),
+        },
+        AIQuery::CacheStore {
+            store: store_name.clone(),
+            prompt: String::from("What is the capital of France?"),
+            response: String::from("Paris"),
+            ttl_secs: None,
+        },
+        // expired as soon as it is stored
+        AIQuery::CacheStore {
+            store: store_name.clone(),
+            prompt: String::from("Who wrote Hamlet?"),
+            response: String::from("Shakespeare"),
+            ttl_secs: Some(0),
+        },
+        cache_lookup("What is the capital of France?"),
+        cache_lookup("How do I bake bread?"),
+        cache_lookup("Who wrote Hamlet?"),
+    ]);
+    let response = get_server_response(&mut reader, message).await;
+
+    match response.into_inner().as_slice() {
+        [Ok(AIServerResponse::Unit), Ok(AIServerResponse::Set(_)), Ok(AIServerResponse::Set(_)), Ok(AIServerResponse::CacheHit(Some(hit))), Ok(AIServerResponse::CacheHit(None)), Ok(AIServerResponse::CacheHit(None))] =>
+        {
+            assert_eq!(hit.response, "Paris");
+            assert_eq!(
+                hit.prompt.as_deref(),
+                Some("What is the capital of France?")
+            );
+            assert_eq!(hit.expires_at, None);
+        }
+        a => panic!("Unexpected result for semantic cache {a:?}"),
+    };
+
+    // the expired response was deleted by the lookup that found it
+    let message = AIServerQuery::from_queries(&[AIQuery::GetKey {
+        store: store_name.clone(),
+        keys: vec![StoreInput::RawString(String::from("Who wrote Hamlet?"))],
+        include_system_metadata: false,
+    }]);
+    let mut expected = AIServerResult::with_capacity(1);
+    expected.push(Ok(AIServerResponse::Get(vec![])));
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_records_text_truncation() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let store_name = StoreName(String::from("Long Texts"));
+    let long_text = "a long sentence about nothing ".repeat(200);
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::SplitAndAverage,
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![(StoreInput::RawString(long_text.clone()), StoreValue::new())],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::GetSimN {
+            store: store_name.clone(),
+            search_input: StoreInput::RawString(long_text),
+            condition: None,
+            closest_n: NonZeroUsize::new(1).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+            include_system_metadata: true,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+        },
+    ]);
+    let response = get_server_response(&mut reader, message).await;
+
+    match response.into_inner().as_slice() {
+        [Ok(AIServerResponse::Unit), Ok(AIServerResponse::Set(_)), Ok(AIServerResponse::GetSimN(results))] =>
+        {
+            let (_, store_value, _) = &results[0];
+            assert_eq!(
+                store_value.get(&*AHNLICH_AI_TRUNCATION_META_KEY),
+                Some(&MetadataValue::RawString(
+                    TextTruncation::SplitAndAverage.to_string()
+                ))
+            );
+        }
+        a => panic!("Unexpected result for truncated texts {a:?}"),
+    };
+}
+
 // TODO: Same issues with random storekeys, changing the order of expected response
 #[tokio::test]
 async fn test_ai_store_no_original() {
@@ -276,6 +734,11 @@ async fn test_ai_store_no_original() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: false,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::Set {
             store: store_name.clone(),
@@ -293,6 +756,7 @@ async fn test_ai_store_no_original() {
             key: matching_metadatakey.clone(),
             value: matching_metadatavalue,
         }),
+        include_system_metadata: false,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
@@ -350,6 +814,11 @@ async fn test_ai_proxy_get_pred_succeeds() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::Set {
             store: store_name.clone(),
@@ -367,6 +836,7 @@ async fn test_ai_proxy_get_pred_succeeds() {
             key: matching_metadatakey.clone(),
             value: matching_metadatavalue,
         }),
+        include_system_metadata: false,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
@@ -430,6 +900,11 @@ async fn test_ai_proxy_get_sim_n_succeeds() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::Set {
             store: store_name.clone(),
@@ -448,6 +923,16 @@ async fn test_ai_proxy_get_sim_n_succeeds() {
         closest_n: NonZeroUsize::new(1).unwrap(),
         algorithm: Algorithm::DotProductSimilarity,
         preprocess_action: PreprocessAction::ModelPreprocessing,
+        include_system_metadata: false,
+        min_score: None,
+        max_distance: None,
+        normalize_scores: false,
+        group_by: None,
+        group_size: NonZeroUsize::new(1).unwrap(),
+        additional_search_inputs: vec![],
+        fusion: FusionStrategy::Mean,
+        recency_boost: None,
+        filter_strategy: FilterStrategy::Auto,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
@@ -489,11 +974,17 @@ async fn test_ai_proxy_create_drop_pred_index() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         // returns nothing
         AIQuery::GetPred {
             store: store_name.clone(),
             condition: predicate_cond.clone(),
+            include_system_metadata: false,
         },
         AIQuery::CreatePredIndex {
             store: store_name.clone(),
@@ -507,6 +998,7 @@ async fn test_ai_proxy_create_drop_pred_index() {
         AIQuery::GetPred {
             store: store_name.clone(),
             condition: predicate_cond,
+            include_system_metadata: false,
         },
         AIQuery::DropPredIndex {
             store: store_name.clone(),
@@ -560,6 +1052,11 @@ async fn test_ai_proxy_del_key_drop_store() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::CreateStore {
             store: store_name.clone(),
@@ -569,6 +1066,11 @@ async fn test_ai_proxy_del_key_drop_store() {
             non_linear_indices: HashSet::new(),
             error_if_exists: false,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::Set {
             store: store_name.clone(),
@@ -582,6 +1084,7 @@ async fn test_ai_proxy_del_key_drop_store() {
         AIQuery::GetPred {
             store: store_name.clone(),
             condition: predicate_cond,
+            include_system_metadata: false,
         },
         AIQuery::DropStore {
             store: store_name.clone(),
@@ -605,68 +1108,529 @@ async fn test_ai_proxy_del_key_drop_store() {
 }
 
 #[tokio::test]
-async fn test_ai_proxy_fails_db_server_unavailable() {
-    let ai_server = AIProxyServer::new(AI_CONFIG.clone())
-        .await
-        .expect("Could not initialize ai proxy");
-
-    let address = ai_server.local_addr().expect("Could not get local addr");
-    // start up ai proxy
-    let _ = tokio::spawn(async move { ai_server.start().await });
-    // Allow some time for the servers to start
-    tokio::time::sleep(Duration::from_millis(100)).await;
-
-    let second_stream = TcpStream::connect(address).await.unwrap();
-
-    let store_name = StoreName(String::from("Main"));
+async fn test_ai_proxy_migrate_store() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let store_name = StoreName(String::from("Deven Kicks"));
+    let migrated_store_name = StoreName(String::from("Deven Kicks L12"));
+    let no_original_store_name = StoreName(String::from("No Original"));
+    let nike_store_value = StoreValue::from_iter([(
+        MetadataKey::new("Brand".to_owned()),
+        MetadataValue::RawString("Nike".to_owned()),
+    )]);
     let message = AIServerQuery::from_queries(&[
-        AIQuery::Ping,
         AIQuery::CreateStore {
             store: store_name.clone(),
             query_model: AIModel::AllMiniLML6V2,
             index_model: AIModel::AllMiniLML6V2,
-            predicates: HashSet::from_iter([]),
+            predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![
+                (
+                    StoreInput::RawString(String::from("Jordan 3")),
+                    nike_store_value.clone(),
+                ),
+                (
+                    StoreInput::RawString(String::from("Air Force 1")),
+                    nike_store_value.clone(),
+                ),
+            ],
+            preprocess_action: PreprocessAction::NoPreprocessing,
+        },
+        AIQuery::CreateStore {
+            store: no_original_store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: false,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        // originals are needed to re-embed a store
+        AIQuery::MigrateStore {
+            source: no_original_store_name.clone(),
+            destination: migrated_store_name.clone(),
+            new_index_model: AIModel::AllMiniLML12V2,
+            batch_size: NonZeroUsize::new(1).unwrap(),
+        },
+        // text originals cannot be embedded by an image model
+        AIQuery::MigrateStore {
+            source: store_name.clone(),
+            destination: migrated_store_name.clone(),
+            new_index_model: AIModel::Resnet50,
+            batch_size: NonZeroUsize::new(1).unwrap(),
+        },
+        // should return a job id immediately
+        AIQuery::MigrateStore {
+            source: store_name.clone(),
+            destination: migrated_store_name.clone(),
+            new_index_model: AIModel::AllMiniLML12V2,
+            batch_size: NonZeroUsize::new(1).unwrap(),
         },
     ]);
-
-    let mut reader = BufReader::new(second_stream);
-
-    // NOTE: on windows, streams seem to wait indefinitely rather
-    // than returning an EOF to indicate a closed stream, this
-    // then tends to make the AI server's DB pool unable to immediately
-    // communicate disconnection to it's client and so we catch that as a timeout
-    // for now
-    #[cfg(windows)]
-    {
-        let serialized_message = message.serialize().unwrap();
-
-        reader.write_all(&serialized_message).await.unwrap();
-
-        let mut header = [0u8; ahnlich_types::bincode::RESPONSE_HEADER_LEN];
-        assert!(
-            timeout(Duration::from_secs(1), reader.read_exact(&mut header))
-                .await
-                .is_err()
-        )
-    }
-
-    #[cfg(not(windows))]
-    {
-        let response = get_server_response(&mut reader, message).await;
-        let res = response.pop().unwrap();
-        assert!(res.is_err());
-        // Err("deadpool error Backend(Standard(Os { code: 61, kind: ConnectionRefused, message: \"Connection refused\" }))")] }
-        let err = res.err().unwrap();
-        assert!(err.contains(" kind: ConnectionRefused,"))
+    let mut expected = AIServerResult::with_capacity(6);
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Ok(AIServerResponse::Set(StoreUpsert {
+        inserted: 2,
+        updated: 0,
+    })));
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Err(AIProxyError::MigrateStoreError(
+        no_original_store_name.clone(),
+    )
+    .into()));
+    expected.push(Err(AIProxyError::StoreTypeMismatchError {
+        action: InputAction::Index,
+        index_model_type: AIStoreInputType::Image,
+        storeinput_type: AIStoreInputType::RawString,
     }
+    .into()));
+    expected.push(Ok(AIServerResponse::JobStarted(1)));
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+    // Allow some time for the background migration to complete
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::GetJob { job_id: 1 },
+        AIQuery::GetKey {
+            store: migrated_store_name.clone(),
+            keys: vec![StoreInput::RawString(String::from("Jordan 3"))],
+            include_system_metadata: false,
+        },
+    ]);
+    let mut expected = AIServerResult::with_capacity(2);
+    expected.push(Ok(AIServerResponse::JobStatus(JobStatus {
+        id: 1,
+        kind: JobKind::MigrateStore,
+        state: JobState::Completed,
+        processed: 2,
+        total: 2,
+    })));
+    expected.push(Ok(AIServerResponse::Get(vec![(
+        Some(StoreInput::RawString(String::from("Jordan 3"))),
+        nike_store_value,
+    )])));
+    query_server_assert_result(&mut reader, message, expected).await;
 }
 
 #[tokio::test]
-async fn test_ai_proxy_test_with_persistence() {
-    let server = Server::new(&CONFIG)
+async fn test_ai_proxy_chunked_transfer() {
+    let address = provision_test_servers().await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let store_name = StoreName(String::from("Deven Kicks"));
+    let brand = MetadataKey::new("Brand".to_owned());
+    let nike_store_value =
+        StoreValue::from_iter([(brand.clone(), MetadataValue::RawString("Nike".to_owned()))]);
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::from_iter([brand.clone()]),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::StartChunkedSet {
+            store: store_name.clone(),
+            entries: vec![ChunkedEntry {
+                input_type: AIStoreInputType::RawString,
+                size: 8,
+                value: nike_store_value.clone(),
+            }],
+            preprocess_action: PreprocessAction::NoPreprocessing,
+        },
+        AIQuery::SetChunk {
+            transfer_id: 0,
+            entry: 0,
+            data: b"Jordan".to_vec(),
+        },
+        AIQuery::SetChunk {
+            transfer_id: 0,
+            entry: 1,
+            data: b" 3".to_vec(),
+        },
+        AIQuery::SetChunk {
+            transfer_id: 0,
+            entry: 0,
+            data: b" 3".to_vec(),
+        },
+        // input is already complete
+        AIQuery::SetChunk {
+            transfer_id: 0,
+            entry: 0,
+            data: b"!".to_vec(),
+        },
+        AIQuery::FinishChunkedSet { transfer_id: 0 },
+        AIQuery::FinishChunkedSet { transfer_id: 0 },
+        AIQuery::StartChunkedGet {
+            store: store_name.clone(),
+            condition: PredicateCondition::Value(Predicate::Equals {
+                key: brand,
+                value: MetadataValue::RawString("Nike".to_owned()),
+            }),
+            include_system_metadata: false,
+        },
+        AIQuery::GetChunk {
+            transfer_id: 1,
+            entry: 0,
+            offset: 0,
+            length: 6,
+        },
+        AIQuery::GetChunk {
+            transfer_id: 1,
+            entry: 0,
+            offset: 6,
+            length: 6,
+        },
+        AIQuery::EndChunkedTransfer { transfer_id: 1 },
+        AIQuery::GetChunk {
+            transfer_id: 1,
+            entry: 0,
+            offset: 0,
+            length: 6,
+        },
+    ]);
+    let mut expected = AIServerResult::with_capacity(13);
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Ok(AIServerResponse::ChunkedSetStarted(0)));
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Err(AIProxyError::ChunkedTransferError {
+        transfer_id: 0,
+        message: "entry 1 was not part of the set".to_string(),
+    }
+    .into()));
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Err(AIProxyError::ChunkedTransferError {
+        transfer_id: 0,
+        message: "input of entry 0 exceeds its size of 8 bytes".to_string(),
+    }
+    .into()));
+    expected.push(Ok(AIServerResponse::Set(StoreUpsert {
+        inserted: 1,
+        updated: 0,
+    })));
+    expected.push(Err(AIProxyError::TransferNotFound(0).into()));
+    expected.push(Ok(AIServerResponse::ChunkedGetStarted {
+        transfer_id: 1,
+        entries: vec![ChunkedEntry {
+            input_type: AIStoreInputType::RawString,
+            size: 8,
+            value: nike_store_value,
+        }],
+    }));
+    expected.push(Ok(AIServerResponse::Chunk(b"Jordan".to_vec())));
+    expected.push(Ok(AIServerResponse::Chunk(b" 3".to_vec())));
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Err(AIProxyError::TransferNotFound(1).into()));
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_list_supported_models() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let db_port = server.local_addr().unwrap().port();
+    let mut config = AI_CONFIG_WITH_CPU_MODEL.clone();
+    config.db_port = db_port;
+
+    let ai_server = AIProxyServer::new(config)
+        .await
+        .expect("Could not initialize ai proxy");
+
+    let address = ai_server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // start up ai proxy
+    let _ = tokio::spawn(async move { ai_server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let message = AIServerQuery::from_queries(&[AIQuery::ListSupportedModels]);
+    let model: Model = (&SupportedModels::AllMiniLML6V2).into();
+    let mut expected = AIServerResult::with_capacity(1);
+    expected.push(Ok(AIServerResponse::SupportedModelList(vec![
+        AIModelInfo {
+            model: AIModel::AllMiniLML6V2,
+            input_type: AIStoreInputType::RawString,
+            embedding_size: model.embedding_size.into(),
+            execution_provider: Some(AIExecutionProvider::CPU),
+        },
+    ])));
+
+    let connected_stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(connected_stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_custom_model_from_registry() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let db_port = server.local_addr().unwrap().port();
+    let mut config = AI_CONFIG_WITH_MODEL_REGISTRY.clone();
+    config.db_port = db_port;
+
+    let ai_server = AIProxyServer::new(config)
+        .await
+        .expect("Could not initialize ai proxy");
+
+    let address = ai_server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // start up ai proxy
+    let _ = tokio::spawn(async move { ai_server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let custom_model = AIModel::Custom("custom-minilm".to_string());
+    let store_name = StoreName(String::from("Custom Model Store"));
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: custom_model.clone(),
+            index_model: custom_model.clone(),
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![(
+                StoreInput::RawString(String::from("Jordan One")),
+                StoreValue::new(),
+            )],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        // models that are neither built in nor declared in the registry are rejected
+        AIQuery::CreateStore {
+            store: StoreName(String::from("Unknown Model Store")),
+            query_model: AIModel::Custom("unknown".to_string()),
+            index_model: AIModel::Custom("unknown".to_string()),
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::PurgeStores,
+    ]);
+
+    let mut expected = AIServerResult::with_capacity(4);
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Ok(AIServerResponse::Set(StoreUpsert {
+        inserted: 1,
+        updated: 0,
+    })));
+    expected.push(Err(AIProxyError::AIModelNotInitialized.into()));
+    expected.push(Ok(AIServerResponse::Del(1)));
+
+    let connected_stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(connected_stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_request_limits() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let db_port = server.local_addr().unwrap().port();
+    let mut config = AI_CONFIG_WITH_REQUEST_LIMITS.clone();
+    config.db_port = db_port;
+
+    let ai_server = AIProxyServer::new(config)
+        .await
+        .expect("Could not initialize ai proxy");
+
+    let address = ai_server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // start up ai proxy
+    let _ = tokio::spawn(async move { ai_server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let store_name = StoreName(String::from("Small"));
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![
+                (
+                    StoreInput::RawString("Jordan One".into()),
+                    StoreValue::new(),
+                ),
+                (
+                    StoreInput::RawString("Air Force 1".into()),
+                    StoreValue::new(),
+                ),
+            ],
+            preprocess_action: PreprocessAction::NoPreprocessing,
+        },
+        AIQuery::StartChunkedSet {
+            store: store_name.clone(),
+            entries: vec![
+                ChunkedEntry {
+                    input_type: AIStoreInputType::RawString,
+                    size: 10,
+                    value: StoreValue::new(),
+                },
+                ChunkedEntry {
+                    input_type: AIStoreInputType::RawString,
+                    size: 11,
+                    value: StoreValue::new(),
+                },
+            ],
+            preprocess_action: PreprocessAction::NoPreprocessing,
+        },
+        AIQuery::ListStores,
+    ]);
+
+    let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
+    let mut expected = AIServerResult::with_capacity(4);
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Err(AIProxyError::BatchTooLarge {
+        store: store_name.clone(),
+        len: 2,
+        limit: 1,
+    }
+    .into()));
+    expected.push(Err(AIProxyError::BatchTooLarge {
+        store: store_name.clone(),
+        len: 2,
+        limit: 1,
+    }
+    .into()));
+    expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
+        AIStoreInfo {
+            name: store_name,
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            embedding_size: ai_model.embedding_size.into(),
+            request_limits: RequestLimits {
+                message_size: 1_048_576,
+                batch_size: Some(1),
+            },
+        },
+    ]))));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_fails_db_server_unavailable() {
+    let ai_server = AIProxyServer::new(AI_CONFIG.clone())
+        .await
+        .expect("Could not initialize ai proxy");
+
+    let address = ai_server.local_addr().expect("Could not get local addr");
+    // start up ai proxy
+    let _ = tokio::spawn(async move { ai_server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let second_stream = TcpStream::connect(address).await.unwrap();
+
+    let store_name = StoreName(String::from("Main"));
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::Ping,
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::from_iter([]),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+    ]);
+
+    let mut reader = BufReader::new(second_stream);
+
+    // NOTE: on windows, streams seem to wait indefinitely rather
+    // than returning an EOF to indicate a closed stream, this
+    // then tends to make the AI server's DB pool unable to immediately
+    // communicate disconnection to it's client and so we catch that as a timeout
+    // for now
+    #[cfg(windows)]
+    {
+        let serialized_message = message.serialize().unwrap();
+
+        reader.write_all(&serialized_message).await.unwrap();
+
+        let mut header = [0u8; ahnlich_types::bincode::RESPONSE_HEADER_LEN];
+        assert!(
+            timeout(Duration::from_secs(1), reader.read_exact(&mut header))
+                .await
+                .is_err()
+        )
+    }
+
+    #[cfg(not(windows))]
+    {
+        let response = get_server_response(&mut reader, message).await;
+        let res = response.pop().unwrap();
+        assert!(res.is_err());
+        // Err("deadpool error Backend(Standard(Os { code: 61, kind: ConnectionRefused, message: \"Connection refused\" }))")] }
+        let err = res.err().unwrap();
+        assert_eq!(err.code, ErrorCode::Unavailable);
+        assert!(err.message.contains(" kind: ConnectionRefused,"))
+    }
+}
+
+#[tokio::test]
+async fn test_ai_proxy_test_with_persistence() {
+    let server = Server::new(&CONFIG)
         .await
         .expect("Could not initialize server");
 
@@ -699,6 +1663,11 @@ async fn test_ai_proxy_test_with_persistence() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::CreateStore {
             store: store_name_2.clone(),
@@ -708,6 +1677,11 @@ async fn test_ai_proxy_test_with_persistence() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::DropStore {
             store: store_name,
@@ -748,7 +1722,7 @@ async fn test_ai_proxy_test_with_persistence() {
     let message = AIServerQuery::from_queries(&[AIQuery::ListStores]);
 
     let mut expected = AIServerResult::with_capacity(1);
-    let ai_model: Model = (&AIModel::AllMiniLML6V2).into();
+    let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
 
     expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
         AIStoreInfo {
@@ -756,6 +1730,7 @@ async fn test_ai_proxy_test_with_persistence() {
             query_model: AIModel::AllMiniLML6V2,
             index_model: AIModel::AllMiniLML6V2,
             embedding_size: ai_model.embedding_size.into(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
 
@@ -779,6 +1754,11 @@ async fn test_ai_proxy_destroy_database() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::ListStores,
         AIQuery::PurgeStores,
@@ -786,7 +1766,7 @@ async fn test_ai_proxy_destroy_database() {
     ]);
     let mut expected = AIServerResult::with_capacity(4);
 
-    let ai_model: Model = (&AIModel::AllMiniLML6V2).into();
+    let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
     expected.push(Ok(AIServerResponse::Unit));
     expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
         AIStoreInfo {
@@ -794,6 +1774,7 @@ async fn test_ai_proxy_destroy_database() {
             query_model: AIModel::AllMiniLML6V2,
             index_model: AIModel::AllMiniLML6V2,
             embedding_size: ai_model.embedding_size.into(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     expected.push(Ok(AIServerResponse::Del(1)));
@@ -852,6 +1833,11 @@ async fn test_ai_proxy_binary_store_actions() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::ListStores,
         AIQuery::CreatePredIndex {
@@ -884,12 +1870,13 @@ async fn test_ai_proxy_binary_store_actions() {
                 key: matching_metadatakey.clone(),
                 value: matching_metadatavalue,
             }),
+            include_system_metadata: false,
         },
         AIQuery::PurgeStores,
     ]);
 
     let mut expected = AIServerResult::with_capacity(8);
-    let resnet_model: Model = (&AIModel::Resnet50).into();
+    let resnet_model: Model = (&SupportedModels::Resnet50).into();
 
     expected.push(Ok(AIServerResponse::Unit));
     expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
@@ -898,6 +1885,7 @@ async fn test_ai_proxy_binary_store_actions() {
             query_model: AIModel::Resnet50,
             index_model: AIModel::Resnet50,
             embedding_size: resnet_model.embedding_size.into(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     expected.push(Ok(AIServerResponse::CreateIndex(2)));
@@ -905,10 +1893,11 @@ async fn test_ai_proxy_binary_store_actions() {
         inserted: 3,
         updated: 0,
     })));
-    expected.push(Err(
-        "Image Dimensions [(547, 821)] does not match the expected model dimensions [(224, 224)]"
-            .to_string(),
-    ));
+    expected.push(Err(AIProxyError::ImageDimensionsMismatchError {
+        image_dimensions: (547, 821),
+        expected_dimensions: (224, 224),
+    }
+    .into()));
     expected.push(Ok(AIServerResponse::Del(1)));
     expected.push(Ok(AIServerResponse::Get(vec![(
         Some(StoreInput::Image(
@@ -924,6 +1913,63 @@ async fn test_ai_proxy_binary_store_actions() {
     query_server_assert_result(&mut reader, message, expected).await;
 }
 
+#[tokio::test]
+async fn test_ai_proxy_binary_store_image_preprocessing() {
+    let address = provision_test_servers().await;
+
+    let store_name = StoreName(String::from("Deven Letterbox Store"));
+    let matching_metadatakey = MetadataKey::new("Name".to_owned());
+
+    let oversize_data = vec![(
+        StoreInput::Image(include_bytes!("./images/large.webp").to_vec()),
+        StoreValue::from_iter([(
+            matching_metadatakey.clone(),
+            MetadataValue::RawString("Oversized".to_owned()),
+        )]),
+    )];
+
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::Resnet50,
+            index_model: AIModel::Resnet50,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: false,
+            image_preprocessing: ImagePreprocessing {
+                exif_orientation: true,
+                resize: ImageResize::Letterbox,
+                convert_format: None,
+            },
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        // the image is letterboxed to 224x224 instead of failing with a dimensions mismatch
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: oversize_data,
+            preprocess_action: PreprocessAction::NoPreprocessing,
+        },
+        AIQuery::PurgeStores,
+    ]);
+
+    let mut expected = AIServerResult::with_capacity(3);
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Ok(AIServerResponse::Set(StoreUpsert {
+        inserted: 1,
+        updated: 0,
+    })));
+    expected.push(Ok(AIServerResponse::Del(1)));
+
+    let connected_stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(connected_stream);
+
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
 #[tokio::test]
 async fn test_ai_proxy_binary_store_set_text_and_binary_fails() {
     let address = provision_test_servers().await;
@@ -958,6 +2004,11 @@ async fn test_ai_proxy_binary_store_set_text_and_binary_fails() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         },
         AIQuery::Set {
             store: store_name.clone(),
@@ -970,10 +2021,65 @@ async fn test_ai_proxy_binary_store_set_text_and_binary_fails() {
     let mut expected = AIServerResult::with_capacity(3);
 
     expected.push(Ok(AIServerResponse::Unit));
-    expected.push(Err(
-        "Cannot index Input. Store expects [RawString], input type [Image] was provided"
-            .to_string(),
-    ));
+    expected.push(Err(AIProxyError::StoreTypeMismatchError {
+        action: InputAction::Index,
+        index_model_type: AIStoreInputType::RawString,
+        storeinput_type: AIStoreInputType::Image,
+    }
+    .into()));
+    expected.push(Ok(AIServerResponse::Del(1)));
+
+    let connected_stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(connected_stream);
+
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_set_system_metadata_fails() {
+    let address = provision_test_servers().await;
+
+    let store_name = StoreName(String::from("Deven System Store"));
+    let system_metadatakey = MetadataKey::system("input_key");
+
+    let store_data = vec![(
+        StoreInput::RawString(String::from("Buster Matthews is the name")),
+        StoreValue::from_iter([(
+            system_metadatakey.clone(),
+            MetadataValue::RawString("Clobbered".to_owned()),
+        )]),
+    )];
+
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: false,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: store_data,
+            preprocess_action: PreprocessAction::NoPreprocessing,
+        },
+        AIQuery::PurgeStores,
+    ]);
+
+    let mut expected = AIServerResult::with_capacity(3);
+
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Err(AIProxyError::ReservedError(
+        system_metadatakey.to_string(),
+    )
+    .into()));
     expected.push(Ok(AIServerResponse::Del(1)));
 
     let connected_stream = TcpStream::connect(address).await.unwrap();
@@ -1011,11 +2117,16 @@ async fn test_ai_proxy_create_store_errors_unsupported_models() {
         non_linear_indices: HashSet::new(),
         error_if_exists: true,
         store_original: true,
+        image_preprocessing: ImagePreprocessing::default(),
+        text_truncation: TextTruncation::default(),
+        timestamp_key: None,
+        multimodal_fusion: None,
+        eviction: None,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
 
-    expected.push(Err(AIProxyError::AIModelNotInitialized.to_string()));
+    expected.push(Err(AIProxyError::AIModelNotInitialized.into()));
 
     let connected_stream = TcpStream::connect(address).await.unwrap();
     let mut reader = BufReader::new(connected_stream);
@@ -1036,18 +2147,253 @@ async fn test_ai_proxy_embedding_size_mismatch_error() {
         non_linear_indices: HashSet::new(),
         error_if_exists: true,
         store_original: true,
+        image_preprocessing: ImagePreprocessing::default(),
+        text_truncation: TextTruncation::default(),
+        timestamp_key: None,
+        multimodal_fusion: None,
+        eviction: None,
     }]);
 
     let mut expected = AIServerResult::with_capacity(1);
 
-    let lml12_model: Model = (&AIModel::AllMiniLML12V2).into();
-    let bge_model: Model = (&AIModel::BGEBaseEnV15).into();
+    let lml12_model: Model = (&SupportedModels::AllMiniLML12V2).into();
+    let bge_model: Model = (&SupportedModels::BGEBaseEnV15).into();
 
     let error_message = AIProxyError::DimensionsMismatchError {
         index_model_dim: bge_model.embedding_size.into(),
         query_model_dim: lml12_model.embedding_size.into(),
     };
-    expected.push(Err(error_message.to_string()));
+    expected.push(Err(error_message.into()));
+    let connected_stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(connected_stream);
+
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_multimodal_fusion_store() {
+    let address = provision_test_servers().await;
+
+    let store_name = StoreName(String::from("Multimodal Store"));
+    let multimodal_dog = StoreInput::Multimodal {
+        text: String::from("a dog sitting on the grass"),
+        image: include_bytes!("./images/dog.jpg").to_vec(),
+    };
+    let store_value = StoreValue::from_iter([(
+        MetadataKey::new("Name".to_owned()),
+        MetadataValue::RawString("Dog".to_owned()),
+    )]);
+    let create_store =
+        |store: &str, index_model: AIModel, fusion: MultimodalFusion| AIQuery::CreateStore {
+            store: StoreName(store.to_string()),
+            query_model: AIModel::ClipVitB32Text,
+            index_model,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: Some(fusion),
+            eviction: None,
+        };
+
+    let message = AIServerQuery::from_queries(&[
+        create_store(
+            "Text Only Store",
+            AIModel::ClipVitB32Text,
+            MultimodalFusion::Average,
+        ),
+        create_store(
+            "Overweighted Store",
+            AIModel::ClipVitB32Image,
+            MultimodalFusion::Weighted { text_weight: 1.5 },
+        ),
+        create_store(
+            &store_name.to_string(),
+            AIModel::ClipVitB32Image,
+            MultimodalFusion::Concat,
+        ),
+        AIQuery::ListStores,
+        AIQuery::DescribeStore {
+            store: store_name.clone(),
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![(multimodal_dog.clone(), store_value.clone())],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![(
+                StoreInput::RawString("a cat".to_string()),
+                StoreValue::new(),
+            )],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        // concatenated embeddings can only be searched with multimodal inputs
+        AIQuery::GetSimN {
+            store: store_name.clone(),
+            search_input: StoreInput::RawString("a dog".to_string()),
+            condition: None,
+            closest_n: NonZeroUsize::new(1).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+            include_system_metadata: false,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::default(),
+        },
+        AIQuery::GetPred {
+            store: store_name.clone(),
+            condition: PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new("Name".to_owned()),
+                value: MetadataValue::RawString("Dog".to_owned()),
+            }),
+            include_system_metadata: false,
+        },
+    ]);
+
+    let clip_model: Model = (&SupportedModels::ClipVitB32Text).into();
+    let mut expected = AIServerResult::with_capacity(9);
+    expected.push(Err(AIProxyError::MultimodalFusionError {
+        store: StoreName("Text Only Store".to_string()),
+        message: "one of the index and query models must take texts and the other images"
+            .to_string(),
+    }
+    .into()));
+    expected.push(Err(AIProxyError::MultimodalFusionError {
+        store: StoreName("Overweighted Store".to_string()),
+        message: "text weight 1.5 is not between 0 and 1".to_string(),
+    }
+    .into()));
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
+        AIStoreInfo {
+            name: store_name.clone(),
+            query_model: AIModel::ClipVitB32Text,
+            index_model: AIModel::ClipVitB32Image,
+            embedding_size: usize::from(clip_model.embedding_size) * 2,
+            request_limits: DEFAULT_REQUEST_LIMITS,
+        },
+    ]))));
+    expected.push(Ok(AIServerResponse::StoreDescription(AIStoreDescription {
+        name: store_name.clone(),
+        query_model: AIModel::ClipVitB32Text,
+        index_model: AIModel::ClipVitB32Image,
+        embedding_size: usize::from(clip_model.embedding_size) * 2,
+        index_inputs: vec![AIStoreInputType::Multimodal],
+        query_inputs: vec![AIStoreInputType::Multimodal],
+        multimodal_fusion: Some(MultimodalFusion::Concat),
+    })));
+    expected.push(Ok(AIServerResponse::Set(StoreUpsert {
+        inserted: 1,
+        updated: 0,
+    })));
+    expected.push(Err(AIProxyError::StoreTypeMismatchError {
+        action: InputAction::Index,
+        index_model_type: AIStoreInputType::Multimodal,
+        storeinput_type: AIStoreInputType::RawString,
+    }
+    .into()));
+    expected.push(Err(AIProxyError::StoreTypeMismatchError {
+        action: InputAction::Query,
+        index_model_type: AIStoreInputType::Multimodal,
+        storeinput_type: AIStoreInputType::RawString,
+    }
+    .into()));
+    expected.push(Ok(AIServerResponse::Get(vec![(
+        Some(multimodal_dog),
+        store_value,
+    )])));
+
+    let connected_stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(connected_stream);
+
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_ai_proxy_multimodal_original_round_trip() {
+    let address = provision_test_servers().await;
+
+    let store_name = StoreName(String::from("Multimodal Originals"));
+    let image = include_bytes!("./images/dog.jpg").to_vec();
+    let dog_on_grass = StoreInput::Multimodal {
+        text: String::from("a dog sitting on the grass"),
+        image: image.clone(),
+    };
+    let dog_in_snow = StoreInput::Multimodal {
+        text: String::from("a dog playing in the snow"),
+        image,
+    };
+
+    let message = AIServerQuery::from_queries(&[
+        AIQuery::CreateStore {
+            store: store_name.clone(),
+            query_model: AIModel::ClipVitB32Text,
+            index_model: AIModel::ClipVitB32Image,
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: Some(MultimodalFusion::Average),
+            eviction: None,
+        },
+        // inputs sharing an image are told apart by their texts
+        AIQuery::Set {
+            store: store_name.clone(),
+            inputs: vec![
+                (dog_on_grass.clone(), StoreValue::new()),
+                (dog_in_snow.clone(), StoreValue::new()),
+            ],
+            preprocess_action: PreprocessAction::ModelPreprocessing,
+        },
+        AIQuery::GetKey {
+            store: store_name.clone(),
+            keys: vec![dog_in_snow.clone()],
+            include_system_metadata: false,
+        },
+        AIQuery::DelKey {
+            store: store_name.clone(),
+            key: dog_in_snow,
+        },
+        AIQuery::GetKey {
+            store: store_name.clone(),
+            keys: vec![dog_on_grass.clone()],
+            include_system_metadata: false,
+        },
+    ]);
+
+    let mut expected = AIServerResult::with_capacity(5);
+    expected.push(Ok(AIServerResponse::Unit));
+    expected.push(Ok(AIServerResponse::Set(StoreUpsert {
+        inserted: 2,
+        updated: 0,
+    })));
+    expected.push(Ok(AIServerResponse::Get(vec![(
+        Some(StoreInput::Multimodal {
+            text: String::from("a dog playing in the snow"),
+            image: include_bytes!("./images/dog.jpg").to_vec(),
+        }),
+        StoreValue::new(),
+    )])));
+    expected.push(Ok(AIServerResponse::Del(1)));
+    expected.push(Ok(AIServerResponse::Get(vec![(
+        Some(dog_on_grass),
+        StoreValue::new(),
+    )])));
+
     let connected_stream = TcpStream::connect(address).await.unwrap();
     let mut reader = BufReader::new(connected_stream);
 
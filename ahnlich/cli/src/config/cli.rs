@@ -1,4 +1,5 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -10,6 +11,19 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     Ahnlich(AhnlichCliConfig),
+    /// Run a script of semicolon separated queries and print the results as json
+    Exec(ExecConfig),
+    /// Page stores and models into memory, meant to be run after a deploy before taking traffic
+    Warmup(WarmupConfig),
+    /// Set the rows of a numpy matrix into a DB store, along with metadata from a CSV file
+    ImportNpy(ImportNpyConfig),
+    /// Create the stores of a TOML manifest and bring the indices of existing ones in line with
+    /// it, printing the changes. With `--dry-run` the DB server reports the changes without
+    /// making them
+    ApplyManifest(ManifestConfig),
+    /// Compare the stores of a TOML manifest with those of a DB server and print how they differ
+    /// as json, exiting with status 1 when they differ and 2 when they cannot be compared
+    DiffManifest(ManifestConfig),
 }
 
 #[derive(Debug, Copy, Clone, Hash, ValueEnum)]
@@ -31,4 +45,69 @@ pub struct AhnlichCliConfig {
     /// Host to connect to Ahnlich AI or DB
     #[arg(long)]
     pub port: Option<u16>,
+
+    /// Parse and check queries without connecting to or running them on the server
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExecConfig {
+    #[command(flatten)]
+    pub connection: AhnlichCliConfig,
+
+    /// File containing the queries to run, queries are read from stdin when not set
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WarmupConfig {
+    #[command(flatten)]
+    pub connection: AhnlichCliConfig,
+
+    /// Stores to warm up, every store is warmed up when not set
+    #[arg(long, value_delimiter = ',')]
+    pub stores: Vec<String>,
+
+    /// Models to load on an AI server, every supported model is loaded when not set
+    #[arg(long, value_delimiter = ',')]
+    pub models: Vec<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ImportNpyConfig {
+    #[command(flatten)]
+    pub connection: AhnlichCliConfig,
+
+    /// Store to set the rows into, which must already exist with the dimension of the matrix
+    #[arg(long)]
+    pub store: String,
+
+    /// `.npy` file or `.npz` archive holding a 2 dimensional float32 or float64 matrix
+    #[arg(long)]
+    pub file: PathBuf,
+
+    /// Array of a `.npz` archive to import, the first one when not set
+    #[arg(long)]
+    pub array: Option<String>,
+
+    /// CSV file with a header naming the metadata keys and a record for each row of the matrix,
+    /// in the same order. Empty fields are left out of the metadata of their row
+    #[arg(long)]
+    pub metadata: Option<PathBuf>,
+
+    /// Rows set into the store at a time
+    #[arg(long, default_value_t = 1000)]
+    pub batch_size: usize,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ManifestConfig {
+    #[command(flatten)]
+    pub connection: AhnlichCliConfig,
+
+    /// TOML file declaring stores under `[[stores]]`
+    #[arg(long)]
+    pub file: PathBuf,
 }
@@ -1,15 +1,38 @@
-use super::config::cli::Agent;
+use super::config::cli::{Agent, ImportNpyConfig, ManifestConfig};
 use ahnlich_client_rs::{
     ai::{AIClient, AIConnManager, AIPipeline},
+    builders::{ai as ai_params, db as db_params},
     db::{DbClient, DbConnManager, DbPipeline},
     prelude::{AIServerResponse, ServerResponse},
 };
-use ahnlich_types::{ai::AIServerQuery, db::ServerDBQuery, ServerType};
+use ahnlich_types::{
+    ai::AIServerQuery,
+    db::{ManifestChange, ManifestDrift, ServerDBQuery, StoreManifest, StoreUpsert},
+    error::{ErrorCode, ErrorResponse},
+    keyval::StoreName,
+    ServerType,
+};
 use deadpool::managed::Pool;
-use dsl::{ai::parse_ai_query, db::parse_db_query};
+use dsl::error::suggest;
 
 use crossterm::style::Stylize;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+use std::path::Path;
+
+/// Manifest file given to `apply-manifest` and `diff-manifest`
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    stores: Vec<StoreManifest>,
+}
+
+impl Manifest {
+    fn read(path: &Path) -> Result<Self, String> {
+        let manifest = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        toml::from_str(&manifest).map_err(|err| err.to_string())
+    }
+}
 
 #[derive(Debug)]
 pub enum AgentPool {
@@ -84,37 +107,189 @@ impl AgentPool {
     pub async fn parse_queries(&self, input: &str) -> Result<Vec<String>, String> {
         match self {
             AgentPool::AI(pool) => {
-                let queries = parse_ai_query(input).map_err(|err| err.to_string())?;
-
-                let server_query = AIServerQuery::from_queries(&queries);
+                let results = run_ai_queries(pool, input).await?;
+                let stores = if has_store_not_found(&results) {
+                    self.store_names().await
+                } else {
+                    vec![]
+                };
 
-                let conn = pool
-                    .get()
-                    .await
-                    .map_err(|err| format!("Could not get ai client connection {err}"))?;
+                Ok(render(results, &stores))
+            }
+            AgentPool::DB(pool) => {
+                let results = run_db_queries(pool, input).await?;
+                let stores = if has_store_not_found(&results) {
+                    self.store_names().await
+                } else {
+                    vec![]
+                };
 
-                let pipeline = AIPipeline::new_from_queries_and_conn(server_query, conn);
+                Ok(render(results, &stores))
+            }
+        }
+    }
 
-                let response = pipeline.exec().await.map_err(|err| err.to_string())?;
+    /// Runs a script of queries non-interactively, returning the results as a json array along
+    /// with whether every query succeeded. With `dry_run` the queries are only validated and the
+    /// array holds the queries that would run
+    pub async fn exec_script(&self, script: &str, dry_run: bool) -> Result<(String, bool), String> {
+        let input = flatten_script(script);
+        match (self, dry_run) {
+            (AgentPool::AI(_), true) => dsl::ai::validate(&input)
+                .map(|queries| (to_json(&queries), true))
+                .map_err(|err| err.to_string()),
+            (AgentPool::DB(_), true) => dsl::db::validate(&input)
+                .map(|queries| (to_json(&queries), true))
+                .map_err(|err| err.to_string()),
+            (AgentPool::AI(pool), false) => {
+                let results = run_ai_queries(pool, &input).await?;
+                Ok((to_json(&results), results.iter().all(Result::is_ok)))
+            }
+            (AgentPool::DB(pool), false) => {
+                let results = run_db_queries(pool, &input).await?;
+                Ok((to_json(&results), results.iter().all(Result::is_ok)))
+            }
+        }
+    }
 
-                Ok(render(response.into_inner()))
+    /// Reads the stores and loads the models of the server into memory. Models only apply to an
+    /// AI server
+    pub async fn warmup(&self, stores: &[String], models: &[String]) -> Result<(), String> {
+        let stores = stores
+            .iter()
+            .map(|store| StoreName(store.to_string()))
+            .collect();
+        match self {
+            AgentPool::AI(pool) => {
+                let models = models
+                    .iter()
+                    .map(|model| dsl::ai::parse_to_ai_model(model))
+                    .collect::<Result<_, _>>()
+                    .map_err(|err| err.to_string())?;
+                let client = AIClient::new_with_pool(pool.clone());
+                client
+                    .warmup(
+                        ai_params::WarmupParams::builder()
+                            .stores(stores)
+                            .models(models)
+                            .build(),
+                    )
+                    .await
+                    .map_err(|err| err.to_string())?;
             }
             AgentPool::DB(pool) => {
-                let queries = parse_db_query(input).map_err(|err| err.to_string())?;
+                if !models.is_empty() {
+                    return Err("Models can only be warmed up on an AI server".to_string());
+                }
+                let client = DbClient::new_with_pool(pool.clone());
+                client
+                    .warmup(db_params::WarmupParams::builder().stores(stores).build())
+                    .await
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(())
+    }
 
-                let server_query = ServerDBQuery::from_queries(&queries);
+    /// Sets the rows of a numpy matrix into a store of a DB server in batches
+    pub async fn import_npy(&self, config: &ImportNpyConfig) -> Result<StoreUpsert, String> {
+        let AgentPool::DB(pool) = self else {
+            return Err("Numpy matrices can only be imported into a DB server".to_string());
+        };
+        let batch_size = NonZeroUsize::new(config.batch_size)
+            .ok_or_else(|| "Batch size must be greater than 0".to_string())?;
+        DbClient::new_with_pool(pool.clone())
+            .import_npy(
+                db_params::ImportNpyParams::builder()
+                    .store(config.store.clone())
+                    .matrix(config.file.clone())
+                    .array(config.array.clone())
+                    .metadata(config.metadata.clone())
+                    .batch_size(batch_size)
+                    .build(),
+            )
+            .await
+            .map_err(|err| err.to_string())
+    }
 
-                let conn = pool
-                    .get()
-                    .await
-                    .map_err(|err| format!("Could not get db client connection {err}"))?;
+    /// Applies the stores of a manifest file to a DB server, returning the changes made or that
+    /// would be made on a dry run
+    pub async fn apply_manifest(
+        &self,
+        config: &ManifestConfig,
+    ) -> Result<Vec<ManifestChange>, String> {
+        let AgentPool::DB(pool) = self else {
+            return Err("Manifests can only be applied to a DB server".to_string());
+        };
+        match DbClient::new_with_pool(pool.clone())
+            .apply_manifest(
+                db_params::ApplyManifestParams::builder()
+                    .stores(Manifest::read(&config.file)?.stores)
+                    .dry_run(config.connection.dry_run)
+                    .build(),
+            )
+            .await
+        {
+            Ok(ServerResponse::ManifestChanges(changes)) => Ok(changes),
+            Ok(response) => Err(format!("Unexpected response {response:?}")),
+            Err(err) => Err(err.to_string()),
+        }
+    }
 
-                let pipeline = DbPipeline::new_from_queries_and_conn(server_query, conn);
+    /// Compares the stores of a manifest file with those of a DB server
+    pub async fn diff_manifest(&self, file: &Path) -> Result<ManifestDrift, String> {
+        let AgentPool::DB(pool) = self else {
+            return Err("Manifests can only be compared with a DB server".to_string());
+        };
+        match DbClient::new_with_pool(pool.clone())
+            .diff_manifest(
+                db_params::DiffManifestParams::builder()
+                    .stores(Manifest::read(file)?.stores)
+                    .build(),
+            )
+            .await
+        {
+            Ok(ServerResponse::ManifestDrift(drift)) => Ok(drift),
+            Ok(response) => Err(format!("Unexpected response {response:?}")),
+            Err(err) => Err(err.to_string()),
+        }
+    }
 
-                let response = pipeline.exec().await.map_err(|err| err.to_string())?;
+    /// Parses and checks queries without sending them, rendering the queries that would run
+    pub fn validate_queries(&self, input: &str) -> Result<Vec<String>, String> {
+        match self {
+            AgentPool::AI(_) => dsl::ai::validate(input)
+                .map(|queries| render_queries(&queries))
+                .map_err(|err| err.to_string()),
+            AgentPool::DB(_) => dsl::db::validate(input)
+                .map(|queries| render_queries(&queries))
+                .map_err(|err| err.to_string()),
+        }
+    }
 
-                Ok(render(response.into_inner()))
-            }
+    /// Names of the stores on the server, used to suggest a store when one is not found
+    async fn store_names(&self) -> Vec<String> {
+        match self {
+            AgentPool::AI(pool) => match AIClient::new_with_pool(pool.clone())
+                .list_stores(None)
+                .await
+            {
+                Ok(AIServerResponse::StoreList(stores)) => stores
+                    .into_iter()
+                    .map(|info| info.name.to_string())
+                    .collect(),
+                _ => vec![],
+            },
+            AgentPool::DB(pool) => match DbClient::new_with_pool(pool.clone())
+                .list_stores(None)
+                .await
+            {
+                Ok(ServerResponse::StoreList(stores)) => stores
+                    .into_iter()
+                    .map(|info| info.name.to_string())
+                    .collect(),
+                _ => vec![],
+            },
         }
     }
 }
@@ -128,7 +303,67 @@ impl std::fmt::Display for AgentPool {
     }
 }
 
-fn render(input: Vec<Result<impl Serialize, String>>) -> Vec<String> {
+async fn run_ai_queries(
+    pool: &Pool<AIConnManager>,
+    input: &str,
+) -> Result<Vec<Result<AIServerResponse, ErrorResponse>>, String> {
+    let queries = dsl::ai::parse_with_diagnostics(input).map_err(|err| err.to_string())?;
+
+    let server_query = AIServerQuery::from_queries(&queries);
+
+    let conn = pool
+        .get()
+        .await
+        .map_err(|err| format!("Could not get ai client connection {err}"))?;
+
+    let pipeline = AIPipeline::new_from_queries_and_conn(server_query, conn);
+
+    let response = pipeline.exec().await.map_err(|err| err.to_string())?;
+    Ok(response.into_inner())
+}
+
+async fn run_db_queries(
+    pool: &Pool<DbConnManager>,
+    input: &str,
+) -> Result<Vec<Result<ServerResponse, ErrorResponse>>, String> {
+    let queries = dsl::db::parse_with_diagnostics(input).map_err(|err| err.to_string())?;
+
+    let server_query = ServerDBQuery::from_queries(&queries);
+
+    let conn = pool
+        .get()
+        .await
+        .map_err(|err| format!("Could not get db client connection {err}"))?;
+
+    let pipeline = DbPipeline::new_from_queries_and_conn(server_query, conn);
+
+    let response = pipeline.exec().await.map_err(|err| err.to_string())?;
+    Ok(response.into_inner())
+}
+
+/// Statements in a script can be spread over several lines but the query language only allows
+/// spaces between tokens, so lines are joined and a trailing `;` is dropped
+fn flatten_script(script: &str) -> String {
+    let input = script
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    input.trim_end_matches(';').to_string()
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string_pretty(value).expect("Failed to parse response to json")
+}
+
+fn has_store_not_found<T>(input: &[Result<T, ErrorResponse>]) -> bool {
+    input
+        .iter()
+        .any(|val| matches!(val, Err(err) if err.code == ErrorCode::StoreNotFound))
+}
+
+fn render(input: Vec<Result<impl Serialize, ErrorResponse>>, stores: &[String]) -> Vec<String> {
     input
         .into_iter()
         .map(|val| match val {
@@ -137,7 +372,31 @@ fn render(input: Vec<Result<impl Serialize, String>>) -> Vec<String> {
                     .map_err(|err| err.to_string())
                     .expect("Failed to parse success response to json"),
             ),
-            Err(err) => format_error(err),
+            Err(err) => {
+                let suggestion = err
+                    .metadata
+                    .get("store")
+                    .filter(|_| err.code == ErrorCode::StoreNotFound)
+                    .and_then(|store| suggest(store, stores.iter().map(String::as_str)));
+                match suggestion {
+                    Some(store) => format_error(format!(
+                        "{:?}: {err}\n  = help: did you mean `{store}`?",
+                        err.code
+                    )),
+                    None => format_error(format!("{:?}: {err}", err.code)),
+                }
+            }
+        })
+        .collect()
+}
+
+fn render_queries(queries: &[impl Serialize]) -> Vec<String> {
+    queries
+        .iter()
+        .map(|query| {
+            format_success(
+                serde_json::to_string_pretty(query).expect("Failed to parse query to json"),
+            )
         })
         .collect()
 }
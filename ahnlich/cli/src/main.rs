@@ -1,29 +1,135 @@
-use ahnlich_cli::{connect::AgentPool, term::Term};
+use ahnlich_cli::{
+    config::cli::{AhnlichCliConfig, Cli, Commands},
+    connect::AgentPool,
+    term::Term,
+};
+use ahnlich_client_rs::import::NpyMatrix;
 use clap::Parser;
-use std::io;
+use std::io::{self, Read};
+
+async fn connect(config: &AhnlichCliConfig) -> io::Result<AgentPool> {
+    let agent_pool = AgentPool::create_pool(config.agent, &config.host, config.port)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    if !config.dry_run
+        && !agent_pool
+            .is_valid_connection()
+            .await
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("Connected Server is not a valid {} Server", agent_pool),
+        ));
+    }
+    Ok(agent_pool)
+}
 
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let cli = ahnlich_cli::config::cli::Cli::parse();
+    let cli = Cli::parse();
 
     match cli.commands {
-        ahnlich_cli::config::cli::Commands::Ahnlich(config) => {
-            let agent_pool = AgentPool::create_pool(config.agent, &config.host, config.port)
-                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-
-            if !agent_pool
-                .is_valid_connection()
+        Commands::Ahnlich(config) => {
+            let agent_pool = connect(&config).await?;
+            let term = Term::new(agent_pool).dry_run(config.dry_run);
+            term.welcome_message()?;
+            term.run().await?;
+        }
+        Commands::Warmup(config) => {
+            let agent_pool = connect(&config.connection).await?;
+            if let Err(err) = agent_pool.warmup(&config.stores, &config.models).await {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+            println!("Warmed up {agent_pool} server");
+        }
+        Commands::ImportNpy(config) if config.connection.dry_run => {
+            match NpyMatrix::open(&config.file, config.array.as_deref()) {
+                Ok(matrix) => println!(
+                    "Would import {} rows of dimension {} into store {}",
+                    matrix.rows(),
+                    matrix.dimension(),
+                    config.store
+                ),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::ImportNpy(config) => {
+            let agent_pool = connect(&config.connection).await?;
+            match agent_pool.import_npy(&config).await {
+                Ok(imported) => println!(
+                    "Imported {} new and {} existing entries into store {}",
+                    imported.inserted, imported.updated, config.store
+                ),
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::ApplyManifest(config) => {
+            let agent_pool = connect(&config.connection).await?;
+            match agent_pool.apply_manifest(&config).await {
+                Ok(changes) if changes.is_empty() => println!("Stores match the manifest"),
+                Ok(changes) => {
+                    for change in changes {
+                        if config.connection.dry_run {
+                            println!("Would {change}");
+                        } else {
+                            println!("Applied: {change}");
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::DiffManifest(config) => {
+            let agent_pool = connect(&config.connection).await?;
+            match agent_pool.diff_manifest(&config.file).await {
+                Ok(drift) => {
+                    println!("{}", serde_json::to_string_pretty(&drift)?);
+                    if !drift.is_empty() {
+                        std::process::exit(1);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::Exec(config) => {
+            let script = match &config.file {
+                Some(file) => std::fs::read_to_string(file)?,
+                None => {
+                    let mut script = String::new();
+                    io::stdin().read_to_string(&mut script)?;
+                    script
+                }
+            };
+            let agent_pool = connect(&config.connection).await?;
+            match agent_pool
+                .exec_script(&script, config.connection.dry_run)
                 .await
-                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
             {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Connected Server is not a valid {} Server", agent_pool),
-                ));
+                Ok((results, succeeded)) => {
+                    println!("{results}");
+                    if !succeeded {
+                        std::process::exit(1);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
             }
-            let term = Term::new(agent_pool);
-            term.welcome_message()?;
-            term.run().await?;
         }
     }
     Ok(())
@@ -46,11 +46,21 @@ enum LineResult {
 
 pub struct Term {
     client_pool: AgentPool,
+    dry_run: bool,
 }
 
 impl Term {
     pub fn new(client_pool: AgentPool) -> Self {
-        Self { client_pool }
+        Self {
+            client_pool,
+            dry_run: false,
+        }
+    }
+
+    /// Only validate queries instead of running them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
     }
 
     fn read_char(&self) -> io::Result<Entry> {
@@ -85,7 +95,11 @@ impl Term {
             terminal::Clear(terminal::ClearType::All),
             cursor::MoveTo(0, 0),
             SetForegroundColor(Color::White),
-            Print(format!("Welcome To Ahnlich {}\n\n", self.client_pool)),
+            Print(format!(
+                "Welcome To Ahnlich {}{}\n\n",
+                self.client_pool,
+                if self.dry_run { " (dry run)" } else { "" }
+            )),
             SetForegroundColor(Color::White),
         )?;
         stdout.flush()?;
@@ -277,7 +291,11 @@ impl Term {
                 LineResult::Command(input) => match input.as_str() {
                     "quit" | "exit" | "exit()" => break,
                     command => {
-                        let response = self.client_pool.parse_queries(command).await;
+                        let response = if self.dry_run {
+                            self.client_pool.validate_queries(command)
+                        } else {
+                            self.client_pool.parse_queries(command).await
+                        };
 
                         match response {
                             Ok(success) => {
@@ -293,11 +311,15 @@ impl Term {
                                 enable_raw_mode()?
                             }
                             Err(err) => {
-                                queue!(
-                                    stdout,
-                                    Print(format!("{}\n", err.red())),
-                                    cursor::MoveToColumn(0)
-                                )?;
+                                // diagnostics span several lines which raw mode does not return
+                                // to the start of
+                                for line in err.lines() {
+                                    queue!(
+                                        stdout,
+                                        Print(format!("{}\n", line.red())),
+                                        cursor::MoveToColumn(0)
+                                    )?;
+                                }
                                 stdout.flush()?;
                             }
                         }
@@ -1,6 +1,9 @@
 use crate::builders::ai as ai_params;
-use crate::conn::{AIConn, Connection};
+use crate::conn::{AIConn, Connection, Endpoint, MemoryConnect};
 use crate::error::AhnlichError;
+use crate::instrument::{instrumented, Instrumentation};
+use crate::intercept::{Interceptor, Interceptors};
+use crate::pipeline::{PipelineHandle, PipelineResult};
 use crate::prelude::*;
 use deadpool::managed::Manager;
 use deadpool::managed::Metrics;
@@ -8,17 +11,36 @@ use deadpool::managed::Object;
 use deadpool::managed::Pool;
 use deadpool::managed::RecycleError;
 use deadpool::managed::RecycleResult;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-/// TCP Connection manager to ahnlich db
+/// Connection manager to ahnlich ai proxy
 #[derive(Debug)]
 pub struct AIConnManager {
-    host: String,
-    port: u16,
+    endpoint: Endpoint,
 }
 
 impl AIConnManager {
     pub fn new(host: String, port: u16) -> Self {
-        Self { host, port }
+        Self {
+            endpoint: Endpoint::Tcp { host, port },
+        }
+    }
+
+    /// connects over the Unix domain socket at `path`
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Self {
+            endpoint: Endpoint::Unix(path.into()),
+        }
+    }
+
+    /// connects in memory to a server running in the same process, opening each connection
+    /// with `connect`
+    pub fn in_memory(connect: MemoryConnect) -> Self {
+        Self {
+            endpoint: Endpoint::Memory(connect),
+        }
     }
 }
 
@@ -28,7 +50,7 @@ impl Manager for AIConnManager {
     type Error = AhnlichError;
 
     async fn create(&self) -> Result<AIConn, AhnlichError> {
-        AIConn::new(&self.host, self.port).await
+        AIConn::new(&self.endpoint).await
     }
 
     async fn recycle(&self, conn: &mut AIConn, _metrics: &Metrics) -> RecycleResult<AhnlichError> {
@@ -41,11 +63,18 @@ impl Manager for AIConnManager {
 pub struct AIPipeline {
     queries: AIServerQuery,
     conn: Object<AIConnManager>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    interceptors: Interceptors<AIServerQuery>,
 }
 
 impl AIPipeline {
     pub fn new_from_queries_and_conn(queries: AIServerQuery, conn: Object<AIConnManager>) -> Self {
-        Self { queries, conn }
+        Self {
+            queries,
+            conn,
+            instrumentation: None,
+            interceptors: Interceptors::default(),
+        }
     }
     /// push create store command to pipeline
     pub fn create_store(&mut self, params: ai_params::CreateStoreParams) {
@@ -57,6 +86,11 @@ impl AIPipeline {
             non_linear_indices: params.non_linear_indices,
             error_if_exists: params.error_if_exists,
             store_original: params.store_original,
+            image_preprocessing: params.image_preprocessing,
+            text_truncation: params.text_truncation,
+            timestamp_key: params.timestamp_key,
+            multimodal_fusion: params.multimodal_fusion,
+            eviction: params.eviction,
         })
     }
 
@@ -65,6 +99,7 @@ impl AIPipeline {
         self.queries.push(AIQuery::GetPred {
             store: params.store,
             condition: params.condition,
+            include_system_metadata: params.include_system_metadata,
         })
     }
 
@@ -77,6 +112,16 @@ impl AIPipeline {
             closest_n: params.closest_n,
             algorithm: params.algorithm,
             preprocess_action: params.preprocess_action,
+            include_system_metadata: params.include_system_metadata,
+            min_score: params.min_score,
+            max_distance: params.max_distance,
+            normalize_scores: params.normalize_scores,
+            group_by: params.group_by,
+            group_size: params.group_size,
+            additional_search_inputs: params.additional_search_inputs,
+            fusion: params.fusion,
+            recency_boost: params.recency_boost,
+            filter_strategy: params.filter_strategy,
         })
     }
 
@@ -133,21 +178,127 @@ impl AIPipeline {
         })
     }
 
+    /// Push get job command to pipeline
+    pub fn get_job(&mut self, params: ai_params::JobParams) {
+        self.queries.push(AIQuery::GetJob {
+            job_id: params.job_id,
+        })
+    }
+
+    /// Push cancel job command to pipeline
+    pub fn cancel_job(&mut self, params: ai_params::JobParams) {
+        self.queries.push(AIQuery::CancelJob {
+            job_id: params.job_id,
+        })
+    }
+
+    /// Push list jobs command to pipeline
+    pub fn list_jobs(&mut self) {
+        self.queries.push(AIQuery::ListJobs)
+    }
+
+    /// Push migrate store command to pipeline
+    pub fn migrate_store(&mut self, params: ai_params::MigrateStoreParams) {
+        self.queries.push(AIQuery::MigrateStore {
+            source: params.source,
+            destination: params.destination,
+            new_index_model: params.new_index_model,
+            batch_size: params.batch_size,
+        })
+    }
+
+    /// Push answer question command to pipeline
+    pub fn answer_question(&mut self, params: ai_params::AnswerQuestionParams) {
+        self.queries.push(AIQuery::AnswerQuestion {
+            store: params.store,
+            question: params.question,
+            condition: params.condition,
+            closest_n: params.closest_n,
+            algorithm: params.algorithm,
+            include_system_metadata: params.include_system_metadata,
+        })
+    }
+
+    /// Push cache lookup command to pipeline
+    pub fn cache_lookup(&mut self, params: ai_params::CacheLookupParams) {
+        self.queries.push(AIQuery::CacheLookup {
+            store: params.store,
+            prompt: params.prompt,
+            threshold: params.threshold,
+        })
+    }
+
+    /// Push cache store command to pipeline
+    pub fn cache_store(&mut self, params: ai_params::CacheStoreParams) {
+        self.queries.push(AIQuery::CacheStore {
+            store: params.store,
+            prompt: params.prompt,
+            response: params.response,
+            ttl_secs: params.ttl_secs,
+        })
+    }
+
+    /// Push classify command to pipeline
+    pub fn classify(&mut self, params: ai_params::ClassifyParams) {
+        self.queries.push(AIQuery::Classify {
+            input: params.input,
+            input_model: params.input_model,
+            labels: params.labels,
+            label_model: params.label_model,
+            preprocess_action: params.preprocess_action,
+        })
+    }
+
     /// Push info server command to pipeline
     pub fn info_server(&mut self) {
         self.queries.push(AIQuery::InfoServer)
     }
 
+    /// Push get memory breakdown command to pipeline
+    pub fn get_memory_breakdown(&mut self) {
+        self.queries.push(AIQuery::GetMemoryBreakdown)
+    }
+
+    /// Push reload config command to pipeline
+    pub fn reload_config(&mut self) {
+        self.queries.push(AIQuery::ReloadConfig)
+    }
+
     /// Push list stores command to pipeline
     pub fn list_stores(&mut self) {
         self.queries.push(AIQuery::ListStores)
     }
 
+    /// push describe store command to pipeline
+    pub fn describe_store(&mut self, params: ai_params::DescribeStoreParams) {
+        self.queries.push(AIQuery::DescribeStore {
+            store: params.store,
+        })
+    }
+
+    /// Push list supported models command to pipeline
+    pub fn list_supported_models(&mut self) {
+        self.queries.push(AIQuery::ListSupportedModels)
+    }
+
+    /// Push get usage stats command to pipeline
+    pub fn get_usage_stats(&mut self, reset: bool) {
+        self.queries.push(AIQuery::GetUsageStats { reset })
+    }
+
     /// Push purge stores command to pipeline
     pub fn purge_stores(&mut self) {
         self.queries.push(AIQuery::PurgeStores)
     }
 
+    /// Push warmup command to pipeline
+    pub fn warmup(&mut self, params: ai_params::WarmupParams) {
+        self.queries.push(AIQuery::Warmup {
+            stores: params.stores,
+            models: params.models,
+        })
+    }
+
     /// Push ping command to pipeline
     pub fn ping(&mut self) {
         self.queries.push(AIQuery::Ping)
@@ -156,7 +307,82 @@ impl AIPipeline {
     /// execute queries all at once and return ordered list of results matching the order in which
     /// queries were pushed
     pub async fn exec(mut self) -> Result<AIServerResult, AhnlichError> {
-        self.conn.send_query(self.queries).await
+        instrumented(
+            self.instrumentation.as_ref(),
+            "pipeline",
+            Self::send(&mut self.conn, &self.interceptors, self.queries),
+        )
+        .await
+    }
+
+    /// execute queries all at once in the background, returning a handle that resolves to the
+    /// results and can cancel the pipeline while it runs
+    pub fn exec_with_handle(self) -> PipelineHandle<AIServerResult> {
+        PipelineHandle::spawn(|cancelled| async move {
+            let Self {
+                queries,
+                mut conn,
+                instrumentation,
+                interceptors,
+            } = self;
+            let request = instrumented(
+                instrumentation.as_ref(),
+                "pipeline",
+                Self::send(&mut conn, &interceptors, queries),
+            );
+            let result = tokio::select! {
+                biased;
+                _ = cancelled.notified() => None,
+                result = request => Some(result),
+            };
+            result.unwrap_or_else(|| {
+                // the connection is closed rather than returned to the pool as the response may
+                // still be on its way, and closing it is what tells the server to stop
+                drop(Object::take(conn));
+                Err(AhnlichError::Cancelled)
+            })
+        })
+    }
+
+    /// set whether the server stops at the first failed query or runs the rest of the pipeline
+    pub fn error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.queries.set_error_policy(error_policy)
+    }
+
+    /// set how long the server may spend on the pipeline before failing the queries left with
+    /// a deadline exceeded error
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.queries.set_timeout(timeout)
+    }
+
+    /// set the priority the server schedules the pipeline at when it is busy
+    pub fn priority(&mut self, priority: Priority) {
+        self.queries.set_priority(priority)
+    }
+
+    /// execute queries all at once and return a typed result for each query in the order in
+    /// which queries were pushed, queries the server did not run are marked as skipped
+    pub async fn exec_entries(
+        mut self,
+    ) -> Result<Vec<PipelineResult<AIServerResponse>>, AhnlichError> {
+        let len = self.queries.len();
+        let results = instrumented(
+            self.instrumentation.as_ref(),
+            "pipeline",
+            Self::send(&mut self.conn, &self.interceptors, self.queries),
+        )
+        .await?;
+        Ok(PipelineResult::from_results(results.into_inner(), len))
+    }
+
+    /// sends the queries once the interceptors have run on them
+    async fn send(
+        conn: &mut Object<AIConnManager>,
+        interceptors: &Interceptors<AIServerQuery>,
+        mut queries: AIServerQuery,
+    ) -> Result<AIServerResult, AhnlichError> {
+        interceptors.apply(&mut queries)?;
+        conn.send_query(queries).await
     }
 }
 
@@ -164,18 +390,57 @@ impl AIPipeline {
 #[derive(Debug)]
 pub struct AIClient {
     pool: Pool<AIConnManager>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    interceptors: Interceptors<AIServerQuery>,
 }
 
 impl AIClient {
     pub async fn new(host: String, port: u16) -> Result<Self, AhnlichError> {
-        let manager = AIConnManager::new(host, port);
+        Self::new_with_manager(AIConnManager::new(host, port))
+    }
+
+    /// Create new ai client connecting over the Unix domain socket at `path`
+    pub async fn new_unix(path: impl Into<PathBuf>) -> Result<Self, AhnlichError> {
+        Self::new_with_manager(AIConnManager::unix(path))
+    }
+
+    /// Create new ai client connecting in memory to a proxy running in the same process
+    pub async fn new_in_memory(connect: MemoryConnect) -> Result<Self, AhnlichError> {
+        Self::new_with_manager(AIConnManager::in_memory(connect))
+    }
+
+    fn new_with_manager(manager: AIConnManager) -> Result<Self, AhnlichError> {
         let pool = Pool::builder(manager).build()?;
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            instrumentation: None,
+            interceptors: Interceptors::default(),
+        })
     }
 
     /// Create new ai client with custom deadpool pool
     pub fn new_with_pool(pool: Pool<AIConnManager>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            instrumentation: None,
+            interceptors: Interceptors::default(),
+        }
+    }
+
+    /// report every request made by this client and its pipelines to `instrumentation`
+    pub fn with_instrumentation(mut self, instrumentation: Arc<dyn Instrumentation>) -> Self {
+        self.instrumentation = Some(instrumentation);
+        self
+    }
+
+    /// run `interceptor` on every request made by this client and its pipelines before it is
+    /// sent, after the interceptors added before it
+    pub fn with_interceptor(
+        mut self,
+        interceptor: impl Interceptor<AIServerQuery> + 'static,
+    ) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
     }
 
     /// Instantiate a new pipeline with a given capacity. Runs commands sequentially on
@@ -185,10 +450,13 @@ impl AIClient {
         capacity: usize,
         tracing_id: Option<String>,
     ) -> Result<AIPipeline, AhnlichError> {
-        Ok(AIPipeline::new_from_queries_and_conn(
+        let mut pipeline = AIPipeline::new_from_queries_and_conn(
             AIServerQuery::with_capacity_and_tracing_id(capacity, tracing_id),
             self.pool.get().await?,
-        ))
+        );
+        pipeline.instrumentation = self.instrumentation.clone();
+        pipeline.interceptors = self.interceptors.clone();
+        Ok(pipeline)
     }
 
     pub async fn create_store(
@@ -196,6 +464,7 @@ impl AIClient {
         store_params: ai_params::CreateStoreParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "create_store",
             AIQuery::CreateStore {
                 store: store_params.store,
                 query_model: store_params.query_model,
@@ -204,6 +473,11 @@ impl AIClient {
                 non_linear_indices: store_params.non_linear_indices,
                 error_if_exists: store_params.error_if_exists,
                 store_original: store_params.store_original,
+                image_preprocessing: store_params.image_preprocessing,
+                text_truncation: store_params.text_truncation,
+                timestamp_key: store_params.timestamp_key,
+                multimodal_fusion: store_params.multimodal_fusion,
+                eviction: store_params.eviction,
             },
             store_params.tracing_id,
         )
@@ -215,9 +489,11 @@ impl AIClient {
         params: ai_params::GetPredParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "get_pred",
             AIQuery::GetPred {
                 store: params.store,
                 condition: params.condition,
+                include_system_metadata: params.include_system_metadata,
             },
             params.tracing_id,
         )
@@ -229,6 +505,7 @@ impl AIClient {
         params: ai_params::GetSimNParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "get_sim_n",
             AIQuery::GetSimN {
                 store: params.store,
                 search_input: params.search_input,
@@ -236,6 +513,16 @@ impl AIClient {
                 closest_n: params.closest_n,
                 algorithm: params.algorithm,
                 preprocess_action: params.preprocess_action,
+                include_system_metadata: params.include_system_metadata,
+                min_score: params.min_score,
+                max_distance: params.max_distance,
+                normalize_scores: params.normalize_scores,
+                group_by: params.group_by,
+                group_size: params.group_size,
+                additional_search_inputs: params.additional_search_inputs,
+                fusion: params.fusion,
+                recency_boost: params.recency_boost,
+                filter_strategy: params.filter_strategy,
             },
             params.tracing_id,
         )
@@ -247,6 +534,7 @@ impl AIClient {
         params: ai_params::CreatePredIndexParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "create_pred_index",
             AIQuery::CreatePredIndex {
                 store: params.store,
                 predicates: params.predicates,
@@ -261,6 +549,7 @@ impl AIClient {
         params: ai_params::CreateNonLinearAlgorithmIndexParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "create_non_linear_algorithm_index",
             AIQuery::CreateNonLinearAlgorithmIndex {
                 store: params.store,
                 non_linear_indices: params.non_linear_indices,
@@ -275,6 +564,7 @@ impl AIClient {
         params: ai_params::DropPredIndexParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "drop_pred_index",
             AIQuery::DropPredIndex {
                 store: params.store,
                 predicates: params.predicates,
@@ -290,6 +580,7 @@ impl AIClient {
         params: ai_params::SetParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "set",
             AIQuery::Set {
                 store: params.store,
                 inputs: params.inputs,
@@ -300,11 +591,133 @@ impl AIClient {
         .await
     }
 
+    /// Stores inputs too large for a single message, sending them in chunks of `chunk_size`
+    /// bytes over one connection. Returns the response of the set once every chunk is stored
+    pub async fn set_chunked(
+        &self,
+        params: ai_params::SetChunkedParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        instrumented(self.instrumentation.as_ref(), "set_chunked", async {
+            let mut conn = self.pool.get().await?;
+            let tracing_id = params.tracing_id;
+            let entries = params
+                .inputs
+                .iter()
+                .map(|(input, value)| ChunkedEntry {
+                    input_type: input.into(),
+                    size: input.len(),
+                    value: value.clone(),
+                })
+                .collect();
+            let start = AIQuery::StartChunkedSet {
+                store: params.store,
+                entries,
+                preprocess_action: params.preprocess_action,
+            };
+            let transfer_id = match self.send(&mut conn, start, tracing_id.clone()).await? {
+                AIServerResponse::ChunkedSetStarted(transfer_id) => transfer_id,
+                response => return Err(AhnlichError::UnexpectedResponse(format!("{response:?}"))),
+            };
+            let chunk_size = params.chunk_size.get();
+            let mut sent = Ok(());
+            'entries: for (entry, (input, _)) in params.inputs.iter().enumerate() {
+                for chunk in input.as_bytes().chunks(chunk_size) {
+                    let query = AIQuery::SetChunk {
+                        transfer_id,
+                        entry,
+                        data: chunk.to_vec(),
+                    };
+                    if let Err(err) = self.send(&mut conn, query, tracing_id.clone()).await {
+                        sent = Err(err);
+                        break 'entries;
+                    }
+                }
+            }
+            if let Err(err) = sent {
+                // the connection goes back to the pool so the unfinished set is discarded
+                let end = AIQuery::EndChunkedTransfer { transfer_id };
+                let _ = self.send(&mut conn, end, tracing_id).await;
+                return Err(err);
+            }
+            self.send(
+                &mut conn,
+                AIQuery::FinishChunkedSet { transfer_id },
+                tracing_id,
+            )
+            .await
+        })
+        .await
+    }
+
+    /// Gets the entries matching a condition along with inputs too large for a single message,
+    /// reading the inputs in chunks of `chunk_size` bytes over one connection. Entries stored
+    /// without their original input are left out
+    pub async fn get_chunked(
+        &self,
+        params: ai_params::GetChunkedParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        instrumented(self.instrumentation.as_ref(), "get_chunked", async {
+            let mut conn = self.pool.get().await?;
+            let tracing_id = params.tracing_id;
+            let start = AIQuery::StartChunkedGet {
+                store: params.store,
+                condition: params.condition,
+                include_system_metadata: params.include_system_metadata,
+            };
+            let (transfer_id, entries) = match self
+                .send(&mut conn, start, tracing_id.clone())
+                .await?
+            {
+                AIServerResponse::ChunkedGetStarted {
+                    transfer_id,
+                    entries,
+                } => (transfer_id, entries),
+                response => return Err(AhnlichError::UnexpectedResponse(format!("{response:?}"))),
+            };
+            let chunk_size = params.chunk_size.get();
+            let mut output = Vec::with_capacity(entries.len());
+            for (entry, manifest) in entries.into_iter().enumerate() {
+                let mut input = Vec::with_capacity(manifest.size);
+                while input.len() < manifest.size {
+                    let query = AIQuery::GetChunk {
+                        transfer_id,
+                        entry,
+                        offset: input.len(),
+                        length: chunk_size,
+                    };
+                    match self.send(&mut conn, query, tracing_id.clone()).await? {
+                        AIServerResponse::Chunk(chunk) if !chunk.is_empty() => input.extend(chunk),
+                        response => {
+                            return Err(AhnlichError::UnexpectedResponse(format!("{response:?}")))
+                        }
+                    }
+                }
+                let input =
+                    StoreInput::from_bytes(&manifest.input_type, input).ok_or_else(|| {
+                        AhnlichError::UnexpectedResponse(format!(
+                            "invalid {} input",
+                            manifest.input_type
+                        ))
+                    })?;
+                output.push((Some(input), manifest.value));
+            }
+            self.send(
+                &mut conn,
+                AIQuery::EndChunkedTransfer { transfer_id },
+                tracing_id,
+            )
+            .await?;
+            Ok(AIServerResponse::Get(output))
+        })
+        .await
+    }
+
     pub async fn del_key(
         &self,
         params: ai_params::DelKeyParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "del_key",
             AIQuery::DelKey {
                 store: params.store,
                 key: params.key,
@@ -319,6 +732,7 @@ impl AIClient {
         params: ai_params::DropStoreParams,
     ) -> Result<AIServerResponse, AhnlichError> {
         self.exec(
+            "drop_store",
             AIQuery::DropStore {
                 store: params.store,
                 error_if_not_exists: params.error_if_not_exists,
@@ -328,43 +742,268 @@ impl AIClient {
         .await
     }
 
+    pub async fn get_job(
+        &self,
+        params: ai_params::JobParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "get_job",
+            AIQuery::GetJob {
+                job_id: params.job_id,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn cancel_job(
+        &self,
+        params: ai_params::JobParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "cancel_job",
+            AIQuery::CancelJob {
+                job_id: params.job_id,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn list_jobs(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec("list_jobs", AIQuery::ListJobs, tracing_id).await
+    }
+
+    /// Starts re-embedding the original inputs of a store with a new index model into a new
+    /// store, the returned job id can be polled with [`AIClient::get_job`]
+    pub async fn migrate_store(
+        &self,
+        params: ai_params::MigrateStoreParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "migrate_store",
+            AIQuery::MigrateStore {
+                source: params.source,
+                destination: params.destination,
+                new_index_model: params.new_index_model,
+                batch_size: params.batch_size,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    /// Answers a question from the entries of a store closest to it, returning the answer along
+    /// with the entries it was read from
+    pub async fn answer_question(
+        &self,
+        params: ai_params::AnswerQuestionParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "answer_question",
+            AIQuery::AnswerQuestion {
+                store: params.store,
+                question: params.question,
+                condition: params.condition,
+                closest_n: params.closest_n,
+                algorithm: params.algorithm,
+                include_system_metadata: params.include_system_metadata,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    /// Looks up the response cached for the prompt of a store most similar to a prompt, when it
+    /// is similar enough and has not expired
+    pub async fn cache_lookup(
+        &self,
+        params: ai_params::CacheLookupParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "cache_lookup",
+            AIQuery::CacheLookup {
+                store: params.store,
+                prompt: params.prompt,
+                threshold: params.threshold,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    /// Caches a response for a prompt, optionally expiring after a number of seconds
+    pub async fn cache_store(
+        &self,
+        params: ai_params::CacheStoreParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "cache_store",
+            AIQuery::CacheStore {
+                store: params.store,
+                prompt: params.prompt,
+                response: params.response,
+                ttl_secs: params.ttl_secs,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    /// Scores candidate labels against an input by the softmax of their embedding similarities,
+    /// with the labels embedded by a text model sharing the embedding size of the input model
+    pub async fn classify(
+        &self,
+        params: ai_params::ClassifyParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "classify",
+            AIQuery::Classify {
+                input: params.input,
+                input_model: params.input_model,
+                labels: params.labels,
+                label_model: params.label_model,
+                preprocess_action: params.preprocess_action,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
     pub async fn info_server(
         &self,
         tracing_id: Option<String>,
     ) -> Result<AIServerResponse, AhnlichError> {
-        self.exec(AIQuery::InfoServer, tracing_id).await
+        self.exec("info_server", AIQuery::InfoServer, tracing_id)
+            .await
+    }
+
+    pub async fn get_memory_breakdown(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "get_memory_breakdown",
+            AIQuery::GetMemoryBreakdown,
+            tracing_id,
+        )
+        .await
+    }
+
+    pub async fn reload_config(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec("reload_config", AIQuery::ReloadConfig, tracing_id)
+            .await
     }
 
     pub async fn list_stores(
         &self,
         tracing_id: Option<String>,
     ) -> Result<AIServerResponse, AhnlichError> {
-        self.exec(AIQuery::ListStores, tracing_id).await
+        self.exec("list_stores", AIQuery::ListStores, tracing_id)
+            .await
+    }
+
+    /// Returns the inputs a store accepts to index and search along with the dimension of its
+    /// keys
+    pub async fn describe_store(
+        &self,
+        params: ai_params::DescribeStoreParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "describe_store",
+            AIQuery::DescribeStore {
+                store: params.store,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    /// Lists the models supported by the proxy along with the execution provider each loaded
+    /// model runs on
+    pub async fn list_supported_models(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "list_supported_models",
+            AIQuery::ListSupportedModels,
+            tracing_id,
+        )
+        .await
+    }
+
+    /// Returns model usage per store, client and model, clearing the counters after reading
+    /// them if `reset` is set
+    pub async fn get_usage_stats(
+        &self,
+        reset: bool,
+        tracing_id: Option<String>,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "get_usage_stats",
+            AIQuery::GetUsageStats { reset },
+            tracing_id,
+        )
+        .await
     }
 
     pub async fn purge_stores(
         &self,
         tracing_id: Option<String>,
     ) -> Result<AIServerResponse, AhnlichError> {
-        self.exec(AIQuery::PurgeStores, tracing_id).await
+        self.exec("purge_stores", AIQuery::PurgeStores, tracing_id)
+            .await
+    }
+
+    pub async fn warmup(
+        &self,
+        params: ai_params::WarmupParams,
+    ) -> Result<AIServerResponse, AhnlichError> {
+        self.exec(
+            "warmup",
+            AIQuery::Warmup {
+                stores: params.stores,
+                models: params.models,
+            },
+            params.tracing_id,
+        )
+        .await
     }
 
     pub async fn ping(&self, tracing_id: Option<String>) -> Result<AIServerResponse, AhnlichError> {
-        self.exec(AIQuery::Ping, tracing_id).await
+        self.exec("ping", AIQuery::Ping, tracing_id).await
     }
 
     async fn exec(
         &self,
+        method: &'static str,
         query: AIQuery,
         tracing_id: Option<String>,
     ) -> Result<AIServerResponse, AhnlichError> {
-        let mut conn = self.pool.get().await?;
+        instrumented(self.instrumentation.as_ref(), method, async {
+            let mut conn = self.pool.get().await?;
+            self.send(&mut conn, query, tracing_id).await
+        })
+        .await
+    }
 
+    async fn send(
+        &self,
+        conn: &mut Object<AIConnManager>,
+        query: AIQuery,
+        tracing_id: Option<String>,
+    ) -> Result<AIServerResponse, AhnlichError> {
         let mut queries = AIServerQuery::with_capacity_and_tracing_id(1, tracing_id);
         queries.push(query);
 
-        let res = conn
-            .send_query(queries)
+        let res = AIPipeline::send(conn, &self.interceptors, queries)
             .await?
             .pop()
             .transpose()
@@ -376,10 +1015,12 @@ impl AIClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ahnlich_ai_proxy::cli::server::SupportedModels;
     use ahnlich_ai_proxy::cli::AIProxyConfig;
     use ahnlich_ai_proxy::{engine::ai::models::Model, server::handler::AIProxyServer};
     use ahnlich_db::cli::ServerConfig;
     use ahnlich_db::server::handler::Server;
+    use ahnlich_types::RequestLimits;
     use once_cell::sync::Lazy;
     use pretty_assertions::assert_eq;
     use std::collections::HashMap;
@@ -388,6 +1029,11 @@ mod tests {
     use tokio::time::Duration;
     use utils::server::AhnlichServerUtils;
 
+    const DEFAULT_REQUEST_LIMITS: RequestLimits = RequestLimits {
+        message_size: 1_048_576,
+        batch_size: None,
+    };
+
     static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default());
     static AI_CONFIG: Lazy<AIProxyConfig> = Lazy::new(|| {
         let mut ai_proxy = AIProxyConfig::default().os_select_port();
@@ -505,20 +1151,26 @@ mod tests {
         pipeline.list_stores();
         let mut expected = AIServerResult::with_capacity(5);
         expected.push(Ok(AIServerResponse::Unit));
-        expected.push(Err("Store Main already exists".to_string()));
+        expected.push(Err(ErrorResponse::new(
+            ErrorCode::StoreAlreadyExists,
+            "Store Main already exists",
+        )
+        .with_metadata("store", "Main")));
         expected.push(Ok(AIServerResponse::Unit));
         expected.push(Ok(AIServerResponse::Unit));
-        let ai_model: Model = (&AIModel::AllMiniLML6V2).into();
+        let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
         expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
             AIStoreInfo {
                 name: StoreName("Main".to_string()),
                 embedding_size: ai_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
                 query_model: AIModel::AllMiniLML6V2,
                 index_model: AIModel::AllMiniLML6V2,
             },
             AIStoreInfo {
                 name: StoreName("Less".to_string()),
                 embedding_size: ai_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
                 query_model: AIModel::AllMiniLML6V2,
                 index_model: AIModel::AllMiniLML6V2,
             },
@@ -614,23 +1266,26 @@ mod tests {
         expected.push(Ok(AIServerResponse::Unit));
         expected.push(Ok(AIServerResponse::Unit));
 
-        let ai_model: Model = (&AIModel::AllMiniLML6V2).into();
+        let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
         expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
             AIStoreInfo {
                 name: StoreName("Main".to_string()),
                 embedding_size: ai_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
                 query_model: AIModel::AllMiniLML6V2,
                 index_model: AIModel::AllMiniLML6V2,
             },
             AIStoreInfo {
                 name: StoreName("Main2".to_string()),
                 embedding_size: ai_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
                 query_model: AIModel::AllMiniLML6V2,
                 index_model: AIModel::AllMiniLML6V2,
             },
             AIStoreInfo {
                 name: StoreName("Less".to_string()),
                 embedding_size: ai_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
                 query_model: AIModel::AllMiniLML6V2,
                 index_model: AIModel::AllMiniLML6V2,
             },
@@ -718,7 +1373,7 @@ mod tests {
         let mut expected = AIServerResult::with_capacity(6);
 
         expected.push(Ok(AIServerResponse::Unit));
-        let ai_model: Model = (&AIModel::AllMiniLML6V2).into();
+        let ai_model: Model = (&SupportedModels::AllMiniLML6V2).into();
         expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
             AIStoreInfo {
                 name: store_name.clone(),
@@ -726,6 +1381,7 @@ mod tests {
                 index_model: AIModel::AllMiniLML6V2,
 
                 embedding_size: ai_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
             },
         ]))));
         expected.push(Ok(AIServerResponse::CreateIndex(2)));
@@ -852,13 +1508,14 @@ mod tests {
         let mut expected = AIServerResult::with_capacity(7);
 
         expected.push(Ok(AIServerResponse::Unit));
-        let resnet_model: Model = (&AIModel::Resnet50).into();
+        let resnet_model: Model = (&SupportedModels::Resnet50).into();
         expected.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
             AIStoreInfo {
                 name: store_name,
                 query_model: AIModel::Resnet50,
                 index_model: AIModel::Resnet50,
                 embedding_size: resnet_model.embedding_size.into(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
             },
         ]))));
         expected.push(Ok(AIServerResponse::CreateIndex(2)));
@@ -879,4 +1536,64 @@ mod tests {
 
         assert_eq!(res, expected);
     }
+
+    #[tokio::test]
+    async fn test_ai_client_chunked_set_and_get() {
+        let address = provision_test_servers().await;
+
+        let host = address.ip();
+        let port = address.port();
+        let ai_client = AIClient::new(host.to_string(), port)
+            .await
+            .expect("Could not initialize client");
+
+        let store_name = StoreName(String::from("Deven Chunked Image Store"));
+        let matching_metadatakey = MetadataKey::new("Name".to_owned());
+        let store_value = StoreValue::from_iter([(
+            matching_metadatakey.clone(),
+            MetadataValue::RawString("Daniel".to_owned()),
+        )]);
+        let image = include_bytes!("../../ai/src/tests/images/cat.png").to_vec();
+
+        let create_store_params = ai_params::CreateStoreParams::builder()
+            .store(store_name.clone().to_string())
+            .index_model(AIModel::Resnet50)
+            .query_model(AIModel::Resnet50)
+            .predicates(HashSet::from_iter([matching_metadatakey.clone()]))
+            .build();
+        assert_eq!(
+            ai_client.create_store(create_store_params).await.unwrap(),
+            AIServerResponse::Unit
+        );
+
+        let set_chunked_params = ai_params::SetChunkedParams::builder()
+            .store(store_name.clone().to_string())
+            .inputs(vec![(
+                StoreInput::Image(image.clone()),
+                store_value.clone(),
+            )])
+            .preprocess_action(PreprocessAction::NoPreprocessing)
+            .chunk_size(4096)
+            .build();
+        assert_eq!(
+            ai_client.set_chunked(set_chunked_params).await.unwrap(),
+            AIServerResponse::Set(StoreUpsert {
+                inserted: 1,
+                updated: 0,
+            })
+        );
+
+        let get_chunked_params = ai_params::GetChunkedParams::builder()
+            .store(store_name.to_string())
+            .condition(PredicateCondition::Value(Predicate::Equals {
+                key: matching_metadatakey,
+                value: MetadataValue::RawString("Daniel".to_owned()),
+            }))
+            .chunk_size(4096)
+            .build();
+        assert_eq!(
+            ai_client.get_chunked(get_chunked_params).await.unwrap(),
+            AIServerResponse::Get(vec![(Some(StoreInput::Image(image)), store_value)])
+        );
+    }
 }
@@ -0,0 +1,171 @@
+//! Handles to AI stores that know which inputs their store accepts.
+//!
+//! An [`AIStoreHandle`] is created from the description of its store, so inputs of a type the
+//! store does not index or search with are rejected on the client with
+//! [`AhnlichError::UnsupportedInput`] rather than sent to the proxy.
+//!
+//! ```rust
+//! use ahnlich_client_rs::ai::AIClient;
+//! use ahnlich_client_rs::prelude::*;
+//! use std::collections::HashMap;
+//!
+//! let ai_client = AIClient::new("127.0.0.1".into(), 1370).await.unwrap();
+//! let store = ai_client.store("Main").await.unwrap();
+//! store
+//!     .set(vec![(StoreInput::RawString("Jordan".into()), HashMap::new())])
+//!     .await
+//!     .unwrap();
+//! let closest = store
+//!     .get_sim_n(StoreInput::RawString("Michael".into()), 1)
+//!     .await
+//!     .unwrap();
+//! ```
+use crate::ai::AIClient;
+use crate::builders::ai as ai_params;
+use crate::error::AhnlichError;
+use crate::prelude::*;
+
+fn unexpected(response: AIServerResponse) -> AhnlichError {
+    AhnlichError::UnexpectedResponse(format!("{response:?}"))
+}
+
+fn check_input(
+    store: &StoreName,
+    action: &'static str,
+    accepted: &[AIStoreInputType],
+    input: &StoreInput,
+) -> Result<(), AhnlichError> {
+    let found = AIStoreInputType::from(input);
+    if accepted.contains(&found) {
+        return Ok(());
+    }
+    Err(AhnlichError::UnsupportedInput {
+        store: store.clone(),
+        action,
+        accepted: accepted.to_vec(),
+        found,
+    })
+}
+
+/// Handle to an AI store, checking inputs against the description of the store
+#[derive(Debug)]
+pub struct AIStoreHandle<'a> {
+    client: &'a AIClient,
+    description: AIStoreDescription,
+}
+
+impl<'a> AIStoreHandle<'a> {
+    pub(crate) fn new(client: &'a AIClient, description: AIStoreDescription) -> Self {
+        Self {
+            client,
+            description,
+        }
+    }
+
+    pub fn name(&self) -> &StoreName {
+        &self.description.name
+    }
+
+    /// Description of the store as it was when the handle was created
+    pub fn description(&self) -> &AIStoreDescription {
+        &self.description
+    }
+
+    /// Checks that the store indexes inputs of the type of `input`
+    pub fn check_index_input(&self, input: &StoreInput) -> Result<(), AhnlichError> {
+        check_input(self.name(), "index", &self.description.index_inputs, input)
+    }
+
+    /// Checks that the store can be searched with inputs of the type of `input`
+    pub fn check_query_input(&self, input: &StoreInput) -> Result<(), AhnlichError> {
+        check_input(self.name(), "search", &self.description.query_inputs, input)
+    }
+
+    pub async fn set(
+        &self,
+        inputs: Vec<(StoreInput, StoreValue)>,
+    ) -> Result<StoreUpsert, AhnlichError> {
+        for (input, _) in &inputs {
+            self.check_index_input(input)?;
+        }
+        let params = ai_params::SetParams::builder()
+            .store(self.name().to_string())
+            .inputs(inputs)
+            .build();
+        match self.client.set(params).await? {
+            AIServerResponse::Set(upsert) => Ok(upsert),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns the `closest_n` entries to `search_input` by cosine similarity, see
+    /// [`AIStoreHandle::get_sim_n_with`] to set the other options of the query
+    pub async fn get_sim_n(
+        &self,
+        search_input: StoreInput,
+        closest_n: usize,
+    ) -> Result<Vec<(Option<StoreInput>, StoreValue, Similarity)>, AhnlichError> {
+        self.get_sim_n_with(search_input, closest_n, Algorithm::CosineSimilarity, None)
+            .await
+    }
+
+    pub async fn get_sim_n_with(
+        &self,
+        search_input: StoreInput,
+        closest_n: usize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+    ) -> Result<Vec<(Option<StoreInput>, StoreValue, Similarity)>, AhnlichError> {
+        self.check_query_input(&search_input)?;
+        if closest_n == 0 {
+            return Ok(vec![]);
+        }
+        let params = ai_params::GetSimNParams::builder()
+            .store(self.name().to_string())
+            .search_input(search_input)
+            .closest_n(closest_n)
+            .algorithm(algorithm)
+            .condition(condition)
+            .preprocess_action(PreprocessAction::ModelPreprocessing)
+            .build();
+        match self.client.get_sim_n(params).await? {
+            AIServerResponse::GetSimN(entries) => Ok(entries),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+impl AIClient {
+    /// Returns a handle to an existing store along with the inputs it accepts
+    pub async fn store(&self, store: impl Into<String>) -> Result<AIStoreHandle<'_>, AhnlichError> {
+        let params = ai_params::DescribeStoreParams::builder()
+            .store(store.into())
+            .build();
+        match self.describe_store(params).await? {
+            AIServerResponse::StoreDescription(description) => {
+                Ok(AIStoreHandle::new(self, description))
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_input_rejects_unsupported_types() {
+        let store = StoreName("Main".to_string());
+        let text = StoreInput::RawString("Jordan".to_string());
+        assert!(check_input(&store, "index", &[AIStoreInputType::RawString], &text).is_ok());
+        assert!(matches!(
+            check_input(&store, "search", &[AIStoreInputType::Image], &text),
+            Err(AhnlichError::UnsupportedInput {
+                action: "search",
+                found: AIStoreInputType::RawString,
+                ..
+            })
+        ));
+    }
+}
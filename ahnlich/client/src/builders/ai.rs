@@ -1,11 +1,13 @@
 use std::{collections::HashSet, num::NonZeroUsize};
 
 use ahnlich_types::{
-    ai::{AIModel, PreprocessAction},
-    keyval::{StoreInput, StoreName, StoreValue},
+    ai::{AIModel, ImagePreprocessing, MultimodalFusion, PreprocessAction, TextTruncation},
+    keyval::{StoreEviction, StoreInput, StoreName, StoreValue},
     metadata::MetadataKey,
     predicate::PredicateCondition,
-    similarity::{Algorithm, NonLinearAlgorithm},
+    similarity::{
+        Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm, RecencyBoost, Similarity,
+    },
 };
 use typed_builder::TypedBuilder;
 
@@ -32,6 +34,24 @@ pub struct CreateStoreParams {
     #[builder(default = true)]
     pub store_original: bool,
 
+    #[builder(default = ImagePreprocessing::default())]
+    pub image_preprocessing: ImagePreprocessing,
+
+    #[builder(default = TextTruncation::default())]
+    pub text_truncation: TextTruncation,
+
+    /// Metadata key holding the Unix timestamp in seconds of each entry
+    #[builder(default = None)]
+    pub timestamp_key: Option<MetadataKey>,
+
+    /// Fusion of the text and image embeddings of multimodal inputs
+    #[builder(default = None)]
+    pub multimodal_fusion: Option<MultimodalFusion>,
+
+    /// Bounds on the entries of the store past which it evicts them
+    #[builder(default = None)]
+    pub eviction: Option<StoreEviction>,
+
     #[builder(default = None)]
     pub tracing_id: Option<String>,
 }
@@ -40,8 +60,12 @@ pub struct CreateStoreParams {
 pub struct GetPredParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
     pub store: StoreName,
+    #[builder(setter(into))]
     pub condition: PredicateCondition,
 
+    #[builder(default = false)]
+    pub include_system_metadata: bool,
+
     #[builder(default = None)]
     pub tracing_id: Option<String>,
 }
@@ -65,6 +89,28 @@ pub struct GetSimNParams {
     pub tracing_id: Option<String>,
     #[builder(default = PreprocessAction::NoPreprocessing)]
     pub preprocess_action: PreprocessAction,
+    #[builder(default = false)]
+    pub include_system_metadata: bool,
+    #[builder(default = None)]
+    pub min_score: Option<Similarity>,
+    #[builder(default = None)]
+    pub max_distance: Option<Similarity>,
+    #[builder(default = false)]
+    pub normalize_scores: bool,
+    #[builder(default = None)]
+    pub group_by: Option<MetadataKey>,
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(1).unwrap())]
+    pub group_size: NonZeroUsize,
+    #[builder(default = vec![])]
+    pub additional_search_inputs: Vec<StoreInput>,
+    #[builder(default = FusionStrategy::Mean)]
+    pub fusion: FusionStrategy,
+    /// Blend scores with the recency of entries, which needs the store to have a timestamp key
+    #[builder(default = None)]
+    pub recency_boost: Option<RecencyBoost>,
+    /// How the condition is applied when searching a non linear algorithm index
+    #[builder(default = FilterStrategy::Auto)]
+    pub filter_strategy: FilterStrategy,
 }
 
 #[derive(TypedBuilder)]
@@ -118,6 +164,44 @@ pub struct SetParams {
     pub tracing_id: Option<String>,
 }
 
+/// bytes of input sent or read per message by chunked sets and gets, kept well below the default
+/// `message_size` of the server
+pub const DEFAULT_CHUNK_SIZE: usize = 512 * 1024;
+
+#[derive(TypedBuilder)]
+pub struct SetChunkedParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    pub inputs: Vec<(StoreInput, StoreValue)>,
+
+    #[builder(default = PreprocessAction::ModelPreprocessing)]
+    pub preprocess_action: PreprocessAction,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(DEFAULT_CHUNK_SIZE).unwrap())]
+    pub chunk_size: NonZeroUsize,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct GetChunkedParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+    #[builder(setter(into))]
+    pub condition: PredicateCondition,
+
+    #[builder(default = false)]
+    pub include_system_metadata: bool,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(DEFAULT_CHUNK_SIZE).unwrap())]
+    pub chunk_size: NonZeroUsize,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
 #[derive(TypedBuilder)]
 pub struct DelKeyParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
@@ -129,6 +213,29 @@ pub struct DelKeyParams {
     pub tracing_id: Option<String>,
 }
 
+#[derive(TypedBuilder)]
+pub struct WarmupParams {
+    /// Stores to warm up, every store of the proxy when empty
+    #[builder(default = HashSet::new())]
+    pub stores: HashSet<StoreName>,
+
+    /// Models to load, every supported model when empty
+    #[builder(default = HashSet::new())]
+    pub models: HashSet<AIModel>,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct DescribeStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
 #[derive(TypedBuilder)]
 pub struct DropStoreParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
@@ -140,3 +247,102 @@ pub struct DropStoreParams {
     #[builder(default = None)]
     pub tracing_id: Option<String>,
 }
+
+#[derive(TypedBuilder)]
+pub struct JobParams {
+    pub job_id: u64,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct MigrateStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub source: StoreName,
+
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub destination: StoreName,
+
+    pub new_index_model: AIModel,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(100).unwrap())]
+    pub batch_size: NonZeroUsize,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct ClassifyParams {
+    pub input: StoreInput,
+
+    #[builder(default = AIModel::ClipVitB32Image)]
+    pub input_model: AIModel,
+
+    pub labels: Vec<String>,
+
+    #[builder(default = AIModel::ClipVitB32Text)]
+    pub label_model: AIModel,
+
+    #[builder(default = PreprocessAction::ModelPreprocessing)]
+    pub preprocess_action: PreprocessAction,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct CacheLookupParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    pub prompt: String,
+
+    /// Least cosine similarity of a cached prompt to be returned
+    #[builder(setter(transform = |threshold: f32| Similarity(threshold)))]
+    pub threshold: Similarity,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct CacheStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    pub prompt: String,
+
+    pub response: String,
+
+    /// Seconds after which the response expires, never when None
+    #[builder(default = None)]
+    pub ttl_secs: Option<u64>,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct AnswerQuestionParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    pub question: String,
+
+    #[builder(default = None)]
+    pub condition: Option<PredicateCondition>,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(3).unwrap())]
+    pub closest_n: NonZeroUsize,
+
+    #[builder(default=Algorithm::CosineSimilarity)]
+    pub algorithm: Algorithm,
+
+    #[builder(default = false)]
+    pub include_system_metadata: bool,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
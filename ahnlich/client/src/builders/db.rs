@@ -1,12 +1,20 @@
 use std::collections::HashSet;
 use std::num::NonZeroUsize;
+use std::time::Duration;
 use typed_builder::TypedBuilder;
 
 use ahnlich_types::{
-    keyval::{StoreKey, StoreName, StoreValue},
+    db::{MirrorAction, NamespaceQuota, StoreManifest},
+    keyval::{
+        KeyElementType, NonFiniteVectors, StorageTier, StoreEviction, StoreKey, StoreName,
+        StoreValue, VectorNormalization,
+    },
     metadata::MetadataKey,
     predicate::PredicateCondition,
-    similarity::{Algorithm, NonLinearAlgorithm},
+    similarity::{
+        Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm, RecencyBoost, SearchTerm,
+        Similarity, TermVector,
+    },
 };
 
 #[derive(TypedBuilder)]
@@ -26,6 +34,41 @@ pub struct CreateStoreParams {
     #[builder(default = true)]
     pub error_if_exists: bool,
 
+    /// Metadata key holding the Unix timestamp in seconds of each entry
+    #[builder(default = None)]
+    pub timestamp_key: Option<MetadataKey>,
+
+    /// Keep the vectors of the store on disk, which needs the server to have a vector storage
+    /// location
+    #[builder(default = StorageTier::Memory)]
+    pub storage_tier: StorageTier,
+
+    /// Ignore `dimension` and take that of the first keys set into the store
+    #[builder(default = false)]
+    pub infer_dimension: bool,
+
+    /// Hold the store keys in half precision, halving the memory they take at the cost of
+    /// rounding them
+    #[builder(default = KeyElementType::Float32)]
+    pub key_element_type: KeyElementType,
+
+    /// Scale keys to unit length as they are set, which makes cosine similarity cheaper
+    #[builder(default = VectorNormalization::None)]
+    pub normalization: VectorNormalization,
+
+    /// Seeds the sampling of the store so that replicas sample it the same. Derived from the
+    /// store name when not given
+    #[builder(default = None)]
+    pub index_seed: Option<u64>,
+
+    /// Sanitize keys and search inputs holding NaN or infinite values instead of rejecting them
+    #[builder(default = NonFiniteVectors::Reject)]
+    pub non_finite_vectors: NonFiniteVectors,
+
+    /// Bounds the store as a cache that evicts entries past `max_entries` or `max_bytes`
+    #[builder(default = None)]
+    pub eviction: Option<StoreEviction>,
+
     #[builder(default = None)]
     pub tracing_id: Option<String>,
 }
@@ -44,11 +87,26 @@ pub struct GetPredParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
     pub store: StoreName,
 
+    #[builder(setter(into))]
     pub condition: PredicateCondition,
     #[builder(default = None)]
     pub tracing_id: Option<String>,
 }
 
+#[derive(TypedBuilder)]
+pub struct CountPredParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(setter(into))]
+    pub condition: PredicateCondition,
+    /// Match every entry instead of estimating the count
+    #[builder(default = false)]
+    pub exact: bool,
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
 #[derive(TypedBuilder)]
 pub struct GetSimNParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
@@ -65,6 +123,90 @@ pub struct GetSimNParams {
     pub condition: Option<PredicateCondition>,
     #[builder(default = None)]
     pub tracing_id: Option<String>,
+    #[builder(default = None)]
+    pub min_score: Option<Similarity>,
+    #[builder(default = None)]
+    pub max_distance: Option<Similarity>,
+    #[builder(default = false)]
+    pub normalize_scores: bool,
+    #[builder(default = None)]
+    pub group_by: Option<MetadataKey>,
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(1).unwrap())]
+    pub group_size: NonZeroUsize,
+    #[builder(default = vec![])]
+    pub additional_search_inputs: Vec<StoreKey>,
+    #[builder(default = FusionStrategy::Mean)]
+    pub fusion: FusionStrategy,
+    /// Blend scores with the recency of entries, which needs the store to have a timestamp key
+    #[builder(default = None)]
+    pub recency_boost: Option<RecencyBoost>,
+    /// How the condition is applied when searching a non linear algorithm index
+    #[builder(default = FilterStrategy::Auto)]
+    pub filter_strategy: FilterStrategy,
+    /// Vectors added to the search input with their weights, e.g to search with the centroid
+    /// of several keys
+    #[builder(default = vec![])]
+    pub search_terms: Vec<SearchTerm>,
+    /// Entries to leave out of the results, by key or key id
+    #[builder(default = vec![])]
+    pub exclude_keys: Vec<TermVector>,
+    /// Skips ordering results that score the same by key id
+    #[builder(default = false)]
+    pub unordered_ties: bool,
+}
+
+#[derive(TypedBuilder)]
+pub struct BatchGetSimNParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+    pub search_inputs: Vec<StoreKey>,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(1).unwrap())]
+    pub closest_n: NonZeroUsize,
+
+    #[builder(default=Algorithm::CosineSimilarity)]
+    pub algorithm: Algorithm,
+
+    #[builder(default = None)]
+    pub condition: Option<PredicateCondition>,
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct GetSimNByKeyParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+    /// Key id of the entry to find entries similar to, as listed by list entries
+    #[builder(setter(into))]
+    pub key_id: String,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(1).unwrap())]
+    pub closest_n: NonZeroUsize,
+
+    #[builder(default=Algorithm::CosineSimilarity)]
+    pub algorithm: Algorithm,
+
+    #[builder(default = None)]
+    pub condition: Option<PredicateCondition>,
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct ListEntriesParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(100).unwrap())]
+    pub limit: NonZeroUsize,
+    /// Next cursor of the previous page, None for the first page
+    #[builder(default = None)]
+    pub cursor: Option<String>,
+    #[builder(default = false)]
+    pub include_vectors: bool,
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
 }
 
 #[derive(TypedBuilder)]
@@ -137,12 +279,225 @@ pub struct DelPredParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
     pub store: StoreName,
 
+    #[builder(setter(into))]
     pub condition: PredicateCondition,
 
     #[builder(default = None)]
     pub tracing_id: Option<String>,
 }
 
+#[derive(TypedBuilder)]
+pub struct DelPredAsyncParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(setter(into))]
+    pub condition: PredicateCondition,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct JobParams {
+    pub job_id: u64,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct DescribeStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct CompactStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct CheckStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    /// Repairs the indices found to disagree with the store in the background
+    #[builder(default = false)]
+    pub repair: bool,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct ScrubStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    /// Deletes the entries found instead of only listing them
+    #[builder(default = false)]
+    pub delete: bool,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct BenchmarkStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    /// Entries of the store whose vectors are searched with
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::new(100).unwrap())]
+    pub sample_queries: NonZeroUsize,
+
+    /// Searches run at a time
+    #[builder(setter(into, transform = |n: usize| NonZeroUsize::new(n).unwrap()),default=NonZeroUsize::MIN)]
+    pub concurrency: NonZeroUsize,
+
+    /// How long each search path of the store is searched for, in milliseconds
+    #[builder(setter(transform = |duration: Duration| duration.as_millis() as u64), default = 5000)]
+    pub duration: u64,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct ExportStoreParquetParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(setter(into))]
+    pub path: String,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct ApplyManifestParams {
+    pub stores: Vec<StoreManifest>,
+
+    /// Return the changes the manifest needs without making them
+    #[builder(default = false)]
+    pub dry_run: bool,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct DiffManifestParams {
+    pub stores: Vec<StoreManifest>,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[cfg(feature = "npy")]
+#[derive(TypedBuilder)]
+pub struct ImportNpyParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    /// `.npy` file or `.npz` archive holding the matrix
+    #[builder(setter(into))]
+    pub matrix: std::path::PathBuf,
+
+    /// Array of a `.npz` archive to import, the first one when not set
+    #[builder(default = None)]
+    pub array: Option<String>,
+
+    /// CSV file holding the metadata of each row of the matrix
+    #[builder(default = None)]
+    pub metadata: Option<std::path::PathBuf>,
+
+    #[builder(default = NonZeroUsize::new(1000).unwrap())]
+    pub batch_size: NonZeroUsize,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[cfg(feature = "local-embed")]
+#[derive(TypedBuilder)]
+pub struct SetTextsParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    /// Texts to embed, each with the metadata it is stored with
+    pub inputs: Vec<(String, StoreValue)>,
+
+    /// Metadata key the text itself is stored under, not stored when not set
+    #[builder(default = None)]
+    pub text_key: Option<MetadataKey>,
+
+    #[builder(default = NonZeroUsize::new(64).unwrap())]
+    pub batch_size: NonZeroUsize,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct SetBulkWriteParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    pub enabled: bool,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct ControlMirrorParams {
+    pub action: MirrorAction,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct WarmupParams {
+    /// Stores to warm up, every store when empty
+    #[builder(default = HashSet::new())]
+    pub stores: HashSet<StoreName>,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct RestoreStoreParams {
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    pub store: StoreName,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
+#[derive(TypedBuilder)]
+pub struct SetQuotaParams {
+    #[builder(setter(into))]
+    pub namespace: String,
+
+    /// Replaces the quota of the namespace, an unbounded quota removes it
+    pub quota: NamespaceQuota,
+
+    #[builder(default = None)]
+    pub tracing_id: Option<String>,
+}
+
 #[derive(TypedBuilder)]
 pub struct DropStoreParams {
     #[builder(setter(into, transform = |s: String| StoreName(s)))]
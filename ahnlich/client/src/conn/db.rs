@@ -1,18 +1,24 @@
-use crate::conn::Connection;
+use crate::conn::{ClientStream, Connection, Endpoint};
 use crate::error::AhnlichError;
 use ahnlich_types::db::{DBQuery, ServerDBQuery, ServerResponse, ServerResult};
-use tokio::net::TcpStream;
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::version::{Version, VERSION};
 
-/// Simple TCP Connection to a host and port
+/// Simple connection to a server over TCP, a Unix domain socket or an in-memory channel
 #[derive(Debug)]
 pub struct DBConn {
-    stream: TcpStream,
+    stream: ClientStream,
+    // version sent in the header of queries, lowered to that of an older server
+    version: Version,
 }
 
 impl DBConn {
-    pub(crate) async fn new(host: &str, port: u16) -> Result<Self, AhnlichError> {
-        let stream = TcpStream::connect(format!("{host}:{port}")).await?;
-        Ok(Self { stream })
+    pub(crate) async fn new(endpoint: &Endpoint) -> Result<Self, AhnlichError> {
+        let stream = endpoint.connect().await?;
+        Ok(Self {
+            stream,
+            version: *VERSION,
+        })
     }
 }
 
@@ -21,10 +27,18 @@ impl Connection for DBConn {
     type ServerQuery = ServerDBQuery;
     type ServerResult = ServerResult;
 
-    fn stream(&mut self) -> &mut TcpStream {
+    fn stream(&mut self) -> &mut ClientStream {
         &mut self.stream
     }
 
+    fn version(&self) -> Version {
+        self.version
+    }
+
+    fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
     async fn is_conn_valid(&mut self) -> Result<(), AhnlichError> {
         let mut queries = Self::ServerQuery::with_capacity(1)?;
         queries.push(DBQuery::Ping);
@@ -32,7 +46,10 @@ impl Connection for DBConn {
         let mut expected_response = ServerResult::with_capacity(1);
         expected_response.push(Ok(ServerResponse::Pong));
         if response != expected_response {
-            return Err(AhnlichError::DbError(format!("{:#?}", response)));
+            return Err(AhnlichError::DbError(ErrorResponse::new(
+                ErrorCode::Unavailable,
+                format!("{:#?}", response),
+            )));
         }
         Ok(())
     }
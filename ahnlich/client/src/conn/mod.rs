@@ -1,12 +1,14 @@
 mod ai;
 mod db;
+mod stream;
 pub use ai::AIConn;
 pub use db::DBConn;
+pub use stream::{ClientStream, Endpoint, MemoryConnect};
 
 use crate::error::AhnlichError;
 use ahnlich_types::bincode::BinCodeSerAndDeser;
+use ahnlich_types::version::{Version, MIN_CLIENT_VERSION};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
 
 #[async_trait::async_trait]
 pub(crate) trait Connection
@@ -17,7 +19,12 @@ where
     type ServerQuery;
     type ServerResult;
 
-    fn stream(&mut self) -> &mut TcpStream;
+    fn stream(&mut self) -> &mut ClientStream;
+
+    /// version the connection speaks to the server
+    fn version(&self) -> Version;
+
+    fn set_version(&mut self, version: Version);
 
     async fn is_conn_valid(&mut self) -> Result<(), AhnlichError>;
 
@@ -25,21 +32,41 @@ where
         &mut self,
         query: Self::ServerQuery,
     ) -> Result<Self::ServerResult, AhnlichError> {
-        let serialized_message = query.serialize()?;
-        self.stream().write_all(&serialized_message).await?;
-        let response: Self::ServerResult = self.deserialize_from_stream().await?;
+        let version = self.version();
+        let (server_version, mut response) = self
+            .exchange(query.serialize_with_version(&version)?)
+            .await?;
+        // servers from before the layout of queries last changed cannot decode them
+        if server_version.major != version.major || server_version < *MIN_CLIENT_VERSION {
+            return Err(AhnlichError::IncompatibleVersion {
+                client: version,
+                server: server_version,
+            });
+        }
+        // an older server rejects queries from newer clients so resend the query as the server
+        // version, which works as long as the query is one the older server understands
+        if server_version < version {
+            self.set_version(server_version);
+            (_, response) = self
+                .exchange(query.serialize_with_version(&server_version)?)
+                .await?;
+        }
+        let response = <Self::ServerResult as BinCodeSerAndDeser>::deserialize(&response)?;
         Ok(response)
     }
 
-    async fn deserialize_from_stream<T: BinCodeSerAndDeser>(&mut self) -> Result<T, AhnlichError> {
+    /// sends a serialized query, returning the server version from the response header along
+    /// with the response yet to be deserialized
+    async fn exchange(&mut self, message: Vec<u8>) -> Result<(Version, Vec<u8>), AhnlichError> {
+        self.stream().write_all(&message).await?;
         let mut header = [0u8; ahnlich_types::bincode::RESPONSE_HEADER_LEN];
         self.stream().read_exact(&mut header).await?;
+        let server_version = Version::deserialize_magic_bytes(&header[8..13])?;
         let mut length_header = [0u8; ahnlich_types::bincode::LENGTH_HEADER_SIZE];
         length_header.copy_from_slice(&header[13..=20]);
         let data_length = u64::from_le_bytes(length_header);
         let mut response = vec![0u8; data_length as usize];
         self.stream().read_exact(&mut response).await?;
-        let response = <T as BinCodeSerAndDeser>::deserialize(&response)?;
-        Ok(response)
+        Ok((server_version, response))
     }
 }
@@ -0,0 +1,90 @@
+use std::fmt;
+use std::io::Result as IoResult;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::net::{TcpStream, UnixStream};
+
+/// Opens an in-memory connection to a server running in the same process
+pub type MemoryConnect = Arc<dyn Fn() -> IoResult<DuplexStream> + Send + Sync>;
+
+/// Where a server is reached, over TCP, over a Unix domain socket or in memory when the server
+/// runs in the same process
+#[derive(Clone)]
+pub enum Endpoint {
+    Tcp { host: String, port: u16 },
+    Unix(PathBuf),
+    Memory(MemoryConnect),
+}
+
+impl fmt::Debug for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp { host, port } => write!(f, "Tcp({host}:{port})"),
+            Endpoint::Unix(path) => write!(f, "Unix({})", path.display()),
+            Endpoint::Memory(_) => write!(f, "Memory"),
+        }
+    }
+}
+
+impl Endpoint {
+    pub(crate) async fn connect(&self) -> IoResult<ClientStream> {
+        Ok(match self {
+            Endpoint::Tcp { host, port } => {
+                ClientStream::Tcp(TcpStream::connect(format!("{host}:{port}")).await?)
+            }
+            Endpoint::Unix(path) => ClientStream::Unix(UnixStream::connect(path).await?),
+            Endpoint::Memory(connect) => ClientStream::Memory(connect()?),
+        })
+    }
+}
+
+/// Stream a connection speaks the protocol over
+#[derive(Debug)]
+pub enum ClientStream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+    Memory(DuplexStream),
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            ClientStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            ClientStream::Memory(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            ClientStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            ClientStream::Memory(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            ClientStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            ClientStream::Memory(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut() {
+            ClientStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            ClientStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            ClientStream::Memory(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
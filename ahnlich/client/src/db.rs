@@ -1,6 +1,9 @@
 use crate::builders::db as db_params;
-use crate::conn::{Connection, DBConn};
+use crate::conn::{Connection, DBConn, Endpoint, MemoryConnect};
 use crate::error::AhnlichError;
+use crate::instrument::{instrumented, Instrumentation};
+use crate::intercept::{Interceptor, Interceptors};
+use crate::pipeline::{PipelineHandle, PipelineResult};
 use crate::prelude::*;
 use deadpool::managed::Manager;
 use deadpool::managed::Metrics;
@@ -8,17 +11,36 @@ use deadpool::managed::Object;
 use deadpool::managed::Pool;
 use deadpool::managed::RecycleError;
 use deadpool::managed::RecycleResult;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-/// TCP Connection manager to ahnlich db
+/// Connection manager to ahnlich db
 #[derive(Debug)]
 pub struct DbConnManager {
-    host: String,
-    port: u16,
+    endpoint: Endpoint,
 }
 
 impl DbConnManager {
     pub fn new(host: String, port: u16) -> Self {
-        Self { host, port }
+        Self {
+            endpoint: Endpoint::Tcp { host, port },
+        }
+    }
+
+    /// connects over the Unix domain socket at `path`
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Self {
+            endpoint: Endpoint::Unix(path.into()),
+        }
+    }
+
+    /// connects in memory to a server running in the same process, opening each connection
+    /// with `connect`
+    pub fn in_memory(connect: MemoryConnect) -> Self {
+        Self {
+            endpoint: Endpoint::Memory(connect),
+        }
     }
 }
 
@@ -28,7 +50,7 @@ impl Manager for DbConnManager {
     type Error = AhnlichError;
 
     async fn create(&self) -> Result<DBConn, AhnlichError> {
-        DBConn::new(&self.host, self.port).await
+        DBConn::new(&self.endpoint).await
     }
 
     async fn recycle(&self, conn: &mut DBConn, _metrics: &Metrics) -> RecycleResult<AhnlichError> {
@@ -41,11 +63,23 @@ impl Manager for DbConnManager {
 pub struct DbPipeline {
     queries: ServerDBQuery,
     conn: Object<DbConnManager>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    interceptors: Interceptors<ServerDBQuery>,
 }
 
 impl DbPipeline {
     pub fn new_from_queries_and_conn(queries: ServerDBQuery, conn: Object<DbConnManager>) -> Self {
-        Self { queries, conn }
+        Self {
+            queries,
+            conn,
+            instrumentation: None,
+            interceptors: Interceptors::default(),
+        }
+    }
+
+    /// push a query to the pipeline as it is
+    pub fn push(&mut self, query: DBQuery) {
+        self.queries.push(query)
     }
 
     /// push create store command to pipeline
@@ -56,6 +90,14 @@ impl DbPipeline {
             create_predicates: params.create_predicates,
             non_linear_indices: params.non_linear_indices,
             error_if_exists: params.error_if_exists,
+            timestamp_key: params.timestamp_key,
+            storage_tier: params.storage_tier,
+            infer_dimension: params.infer_dimension,
+            key_element_type: params.key_element_type,
+            normalization: params.normalization,
+            index_seed: params.index_seed,
+            non_finite_vectors: params.non_finite_vectors,
+            eviction: params.eviction,
         })
     }
 
@@ -75,6 +117,15 @@ impl DbPipeline {
         })
     }
 
+    /// push count pred command to pipeline
+    pub fn count_pred(&mut self, params: db_params::CountPredParams) {
+        self.queries.push(DBQuery::CountPred {
+            store: params.store,
+            condition: params.condition,
+            exact: params.exact,
+        })
+    }
+
     /// push get sim n command to pipeline
     pub fn get_sim_n(&mut self, params: db_params::GetSimNParams) {
         self.queries.push(DBQuery::GetSimN {
@@ -83,6 +134,50 @@ impl DbPipeline {
             closest_n: params.closest_n,
             algorithm: params.algorithm,
             condition: params.condition,
+            min_score: params.min_score,
+            max_distance: params.max_distance,
+            normalize_scores: params.normalize_scores,
+            group_by: params.group_by,
+            group_size: params.group_size,
+            additional_search_inputs: params.additional_search_inputs,
+            fusion: params.fusion,
+            recency_boost: params.recency_boost,
+            filter_strategy: params.filter_strategy,
+            search_terms: params.search_terms,
+            exclude_keys: params.exclude_keys,
+            unordered_ties: params.unordered_ties,
+        })
+    }
+
+    /// push batch get sim n command to pipeline
+    pub fn batch_get_sim_n(&mut self, params: db_params::BatchGetSimNParams) {
+        self.queries.push(DBQuery::BatchGetSimN {
+            store: params.store,
+            search_inputs: params.search_inputs,
+            closest_n: params.closest_n,
+            algorithm: params.algorithm,
+            condition: params.condition,
+        })
+    }
+
+    /// push get sim n by key command to pipeline
+    pub fn get_sim_n_by_key(&mut self, params: db_params::GetSimNByKeyParams) {
+        self.queries.push(DBQuery::GetSimNByKey {
+            store: params.store,
+            key_id: params.key_id,
+            closest_n: params.closest_n,
+            algorithm: params.algorithm,
+            condition: params.condition,
+        })
+    }
+
+    /// push list entries command to pipeline
+    pub fn list_entries(&mut self, params: db_params::ListEntriesParams) {
+        self.queries.push(DBQuery::ListEntries {
+            store: params.store,
+            limit: params.limit,
+            cursor: params.cursor,
+            include_vectors: params.include_vectors,
         })
     }
 
@@ -150,6 +245,78 @@ impl DbPipeline {
         })
     }
 
+    /// push del pred async command to pipeline
+    pub fn del_pred_async(&mut self, params: db_params::DelPredAsyncParams) {
+        self.queries.push(DBQuery::DelPredAsync {
+            store: params.store,
+            condition: params.condition,
+        })
+    }
+
+    /// push get job command to pipeline
+    pub fn get_job(&mut self, params: db_params::JobParams) {
+        self.queries.push(DBQuery::GetJob {
+            job_id: params.job_id,
+        })
+    }
+
+    /// push cancel job command to pipeline
+    pub fn cancel_job(&mut self, params: db_params::JobParams) {
+        self.queries.push(DBQuery::CancelJob {
+            job_id: params.job_id,
+        })
+    }
+
+    /// push list jobs command to pipeline
+    pub fn list_jobs(&mut self) {
+        self.queries.push(DBQuery::ListJobs)
+    }
+
+    /// push list trashed stores command to pipeline
+    pub fn list_trashed_stores(&mut self) {
+        self.queries.push(DBQuery::ListTrashedStores)
+    }
+
+    /// push set bulk write command to pipeline
+    pub fn set_bulk_write(&mut self, params: db_params::SetBulkWriteParams) {
+        self.queries.push(DBQuery::SetBulkWrite {
+            store: params.store,
+            enabled: params.enabled,
+        })
+    }
+
+    /// push mirror status command to pipeline
+    pub fn mirror_status(&mut self) {
+        self.queries.push(DBQuery::MirrorStatus)
+    }
+
+    /// push control mirror command to pipeline
+    pub fn control_mirror(&mut self, params: db_params::ControlMirrorParams) {
+        self.queries.push(DBQuery::ControlMirror {
+            action: params.action,
+        })
+    }
+
+    /// push restore store command to pipeline
+    pub fn restore_store(&mut self, params: db_params::RestoreStoreParams) {
+        self.queries.push(DBQuery::RestoreStore {
+            store: params.store,
+        })
+    }
+
+    /// push list quotas command to pipeline
+    pub fn list_quotas(&mut self) {
+        self.queries.push(DBQuery::ListQuotas)
+    }
+
+    /// push set quota command to pipeline
+    pub fn set_quota(&mut self, params: db_params::SetQuotaParams) {
+        self.queries.push(DBQuery::SetQuota {
+            namespace: params.namespace,
+            quota: params.quota,
+        })
+    }
+
     /// push drop store command to pipeline
     pub fn drop_store(&mut self, params: db_params::DropStoreParams) {
         self.queries.push(DBQuery::DropStore {
@@ -157,6 +324,70 @@ impl DbPipeline {
             error_if_not_exists: params.error_if_not_exists,
         })
     }
+
+    /// push compact store command to pipeline
+    pub fn compact_store(&mut self, params: db_params::CompactStoreParams) {
+        self.queries.push(DBQuery::CompactStore {
+            store: params.store,
+        })
+    }
+
+    /// push check store command to pipeline
+    pub fn check_store(&mut self, params: db_params::CheckStoreParams) {
+        self.queries.push(DBQuery::CheckStore {
+            store: params.store,
+            repair: params.repair,
+        })
+    }
+
+    /// push scrub store command to pipeline
+    pub fn scrub_store(&mut self, params: db_params::ScrubStoreParams) {
+        self.queries.push(DBQuery::ScrubStore {
+            store: params.store,
+            delete: params.delete,
+        })
+    }
+
+    /// push benchmark store command to pipeline
+    pub fn benchmark_store(&mut self, params: db_params::BenchmarkStoreParams) {
+        self.queries.push(DBQuery::BenchmarkStore {
+            store: params.store,
+            sample_queries: params.sample_queries,
+            concurrency: params.concurrency,
+            duration: params.duration,
+        })
+    }
+
+    /// push export store parquet command to pipeline
+    pub fn export_store_parquet(&mut self, params: db_params::ExportStoreParquetParams) {
+        self.queries.push(DBQuery::ExportStoreParquet {
+            store: params.store,
+            path: params.path,
+        })
+    }
+
+    /// push apply manifest command to pipeline
+    pub fn apply_manifest(&mut self, params: db_params::ApplyManifestParams) {
+        self.queries.push(DBQuery::ApplyManifest {
+            stores: params.stores,
+            dry_run: params.dry_run,
+        })
+    }
+
+    /// push diff manifest command to pipeline
+    pub fn diff_manifest(&mut self, params: db_params::DiffManifestParams) {
+        self.queries.push(DBQuery::DiffManifest {
+            stores: params.stores,
+        })
+    }
+
+    /// push warmup command to pipeline
+    pub fn warmup(&mut self, params: db_params::WarmupParams) {
+        self.queries.push(DBQuery::Warmup {
+            stores: params.stores,
+        })
+    }
+
     /// push ping command to pipeline
     pub fn ping(&mut self) {
         self.queries.push(DBQuery::Ping)
@@ -167,11 +398,28 @@ impl DbPipeline {
         self.queries.push(DBQuery::InfoServer)
     }
 
+    /// push get memory breakdown command to pipeline
+    pub fn get_memory_breakdown(&mut self) {
+        self.queries.push(DBQuery::GetMemoryBreakdown)
+    }
+
+    /// push reload config command to pipeline
+    pub fn reload_config(&mut self) {
+        self.queries.push(DBQuery::ReloadConfig)
+    }
+
     /// push list stores command to pipeline
     pub fn list_stores(&mut self) {
         self.queries.push(DBQuery::ListStores)
     }
 
+    /// push describe store command to pipeline
+    pub fn describe_store(&mut self, params: db_params::DescribeStoreParams) {
+        self.queries.push(DBQuery::DescribeStore {
+            store: params.store,
+        })
+    }
+
     /// push list clients command to pipeline
     pub fn list_clients(&mut self) {
         self.queries.push(DBQuery::ListClients)
@@ -180,7 +428,82 @@ impl DbPipeline {
     /// execute queries all at once and return ordered list of results matching the order in which
     /// queries were pushed
     pub async fn exec(mut self) -> Result<ServerResult, AhnlichError> {
-        self.conn.send_query(self.queries).await
+        instrumented(
+            self.instrumentation.as_ref(),
+            "pipeline",
+            Self::send(&mut self.conn, &self.interceptors, self.queries),
+        )
+        .await
+    }
+
+    /// execute queries all at once in the background, returning a handle that resolves to the
+    /// results and can cancel the pipeline while it runs
+    pub fn exec_with_handle(self) -> PipelineHandle<ServerResult> {
+        PipelineHandle::spawn(|cancelled| async move {
+            let Self {
+                queries,
+                mut conn,
+                instrumentation,
+                interceptors,
+            } = self;
+            let request = instrumented(
+                instrumentation.as_ref(),
+                "pipeline",
+                Self::send(&mut conn, &interceptors, queries),
+            );
+            let result = tokio::select! {
+                biased;
+                _ = cancelled.notified() => None,
+                result = request => Some(result),
+            };
+            result.unwrap_or_else(|| {
+                // the connection is closed rather than returned to the pool as the response may
+                // still be on its way, and closing it is what tells the server to stop
+                drop(Object::take(conn));
+                Err(AhnlichError::Cancelled)
+            })
+        })
+    }
+
+    /// set whether the server stops at the first failed query or runs the rest of the pipeline
+    pub fn error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.queries.set_error_policy(error_policy)
+    }
+
+    /// set how long the server may spend on the pipeline before failing the queries left with
+    /// a deadline exceeded error
+    pub fn timeout(&mut self, timeout: Duration) {
+        self.queries.set_timeout(timeout)
+    }
+
+    /// set the priority the server schedules the pipeline at when it is busy
+    pub fn priority(&mut self, priority: Priority) {
+        self.queries.set_priority(priority)
+    }
+
+    /// execute queries all at once and return a typed result for each query in the order in
+    /// which queries were pushed, queries the server did not run are marked as skipped
+    pub async fn exec_entries(
+        mut self,
+    ) -> Result<Vec<PipelineResult<ServerResponse>>, AhnlichError> {
+        let len = self.queries.len();
+        let results = instrumented(
+            self.instrumentation.as_ref(),
+            "pipeline",
+            Self::send(&mut self.conn, &self.interceptors, self.queries),
+        )
+        .await?;
+        Ok(PipelineResult::from_results(results.into_inner(), len))
+    }
+
+    /// sends the queries once the interceptors have run on them
+    async fn send(
+        conn: &mut Object<DbConnManager>,
+        interceptors: &Interceptors<ServerDBQuery>,
+        mut queries: ServerDBQuery,
+    ) -> Result<ServerResult, AhnlichError> {
+        interceptors.apply(&mut queries)?;
+        conn.send_query(queries).await
     }
 }
 
@@ -188,6 +511,8 @@ impl DbPipeline {
 #[derive(Debug)]
 pub struct DbClient {
     pool: Pool<DbConnManager>,
+    instrumentation: Option<Arc<dyn Instrumentation>>,
+    interceptors: Interceptors<ServerDBQuery>,
 }
 
 impl DbClient {
@@ -195,14 +520,51 @@ impl DbClient {
     /// only made async because Pool::builder(...).build() can throw an error if not run within a
     /// runtime context like tokio
     pub async fn new(host: String, port: u16) -> Result<Self, AhnlichError> {
-        let manager = DbConnManager::new(host, port);
+        Self::new_with_manager(DbConnManager::new(host, port))
+    }
+
+    /// create new DB client connecting over the Unix domain socket at `path`
+    pub async fn new_unix(path: impl Into<PathBuf>) -> Result<Self, AhnlichError> {
+        Self::new_with_manager(DbConnManager::unix(path))
+    }
+
+    /// create new DB client connecting in memory to a server running in the same process
+    pub async fn new_in_memory(connect: MemoryConnect) -> Result<Self, AhnlichError> {
+        Self::new_with_manager(DbConnManager::in_memory(connect))
+    }
+
+    fn new_with_manager(manager: DbConnManager) -> Result<Self, AhnlichError> {
         let pool = Pool::builder(manager).build()?;
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            instrumentation: None,
+            interceptors: Interceptors::default(),
+        })
     }
 
     /// create new DB client with custom deadpool pool
     pub fn new_with_pool(pool: Pool<DbConnManager>) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            instrumentation: None,
+            interceptors: Interceptors::default(),
+        }
+    }
+
+    /// report every request made by this client and its pipelines to `instrumentation`
+    pub fn with_instrumentation(mut self, instrumentation: Arc<dyn Instrumentation>) -> Self {
+        self.instrumentation = Some(instrumentation);
+        self
+    }
+
+    /// run `interceptor` on every request made by this client and its pipelines before it is
+    /// sent, after the interceptors added before it
+    pub fn with_interceptor(
+        mut self,
+        interceptor: impl Interceptor<ServerDBQuery> + 'static,
+    ) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
     }
 
     /// Instantiate a new pipeline of a given capacity for which commands would be run sequentially
@@ -212,10 +574,13 @@ impl DbClient {
         capacity: usize,
         tracing_id: Option<String>,
     ) -> Result<DbPipeline, AhnlichError> {
-        Ok(DbPipeline::new_from_queries_and_conn(
+        let mut pipeline = DbPipeline::new_from_queries_and_conn(
             ServerDBQuery::with_capacity_and_tracing_id(capacity, tracing_id)?,
             self.pool.get().await?,
-        ))
+        );
+        pipeline.instrumentation = self.instrumentation.clone();
+        pipeline.interceptors = self.interceptors.clone();
+        Ok(pipeline)
     }
 
     pub async fn create_store(
@@ -223,12 +588,21 @@ impl DbClient {
         params: db_params::CreateStoreParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "create_store",
             DBQuery::CreateStore {
                 store: params.store,
                 dimension: params.dimension,
                 create_predicates: params.create_predicates,
                 non_linear_indices: params.non_linear_indices,
                 error_if_exists: params.error_if_exists,
+                timestamp_key: params.timestamp_key,
+                storage_tier: params.storage_tier,
+                infer_dimension: params.infer_dimension,
+                key_element_type: params.key_element_type,
+                normalization: params.normalization,
+                index_seed: params.index_seed,
+                non_finite_vectors: params.non_finite_vectors,
+                eviction: params.eviction,
             },
             params.tracing_id,
         )
@@ -240,6 +614,7 @@ impl DbClient {
         params: db_params::GetKeyParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "get_key",
             DBQuery::GetKey {
                 store: params.store,
                 keys: params.keys,
@@ -254,6 +629,7 @@ impl DbClient {
         params: db_params::GetPredParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "get_pred",
             DBQuery::GetPred {
                 store: params.store,
                 condition: params.condition,
@@ -263,17 +639,99 @@ impl DbClient {
         .await
     }
 
+    pub async fn count_pred(
+        &self,
+        params: db_params::CountPredParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "count_pred",
+            DBQuery::CountPred {
+                store: params.store,
+                condition: params.condition,
+                exact: params.exact,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
     pub async fn get_sim_n(
         &self,
         params: db_params::GetSimNParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "get_sim_n",
             DBQuery::GetSimN {
                 store: params.store,
                 search_input: params.search_input,
                 closest_n: params.closest_n,
                 algorithm: params.algorithm,
                 condition: params.condition,
+                min_score: params.min_score,
+                max_distance: params.max_distance,
+                normalize_scores: params.normalize_scores,
+                group_by: params.group_by,
+                group_size: params.group_size,
+                additional_search_inputs: params.additional_search_inputs,
+                fusion: params.fusion,
+                recency_boost: params.recency_boost,
+                filter_strategy: params.filter_strategy,
+                search_terms: params.search_terms,
+                exclude_keys: params.exclude_keys,
+                unordered_ties: params.unordered_ties,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn batch_get_sim_n(
+        &self,
+        params: db_params::BatchGetSimNParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "batch_get_sim_n",
+            DBQuery::BatchGetSimN {
+                store: params.store,
+                search_inputs: params.search_inputs,
+                closest_n: params.closest_n,
+                algorithm: params.algorithm,
+                condition: params.condition,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn get_sim_n_by_key(
+        &self,
+        params: db_params::GetSimNByKeyParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "get_sim_n_by_key",
+            DBQuery::GetSimNByKey {
+                store: params.store,
+                key_id: params.key_id,
+                closest_n: params.closest_n,
+                algorithm: params.algorithm,
+                condition: params.condition,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn list_entries(
+        &self,
+        params: db_params::ListEntriesParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "list_entries",
+            DBQuery::ListEntries {
+                store: params.store,
+                limit: params.limit,
+                cursor: params.cursor,
+                include_vectors: params.include_vectors,
             },
             params.tracing_id,
         )
@@ -285,6 +743,7 @@ impl DbClient {
         params: db_params::CreatePredIndexParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "create_pred_index",
             DBQuery::CreatePredIndex {
                 store: params.store,
                 predicates: params.predicates,
@@ -299,6 +758,7 @@ impl DbClient {
         params: db_params::CreateNonLinearAlgorithmIndexParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "create_non_linear_algorithm_index",
             DBQuery::CreateNonLinearAlgorithmIndex {
                 store: params.store,
                 non_linear_indices: params.non_linear_indices,
@@ -313,6 +773,7 @@ impl DbClient {
         params: db_params::DropPredIndexParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "drop_pred_index",
             DBQuery::DropPredIndex {
                 store: params.store,
                 predicates: params.predicates,
@@ -328,6 +789,7 @@ impl DbClient {
         params: db_params::DropNonLinearAlgorithmIndexParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "drop_non_linear_algorithm_index",
             DBQuery::DropNonLinearAlgorithmIndex {
                 store: params.store,
                 non_linear_indices: params.non_linear_indices,
@@ -340,6 +802,7 @@ impl DbClient {
 
     pub async fn set(&self, params: db_params::SetParams) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "set",
             DBQuery::Set {
                 store: params.store,
                 inputs: params.inputs,
@@ -354,6 +817,7 @@ impl DbClient {
         params: db_params::DelKeyParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "del_key",
             DBQuery::DelKey {
                 store: params.store,
                 keys: params.keys,
@@ -368,6 +832,7 @@ impl DbClient {
         params: db_params::DelPredParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "del_pred",
             DBQuery::DelPred {
                 store: params.store,
                 condition: params.condition,
@@ -377,11 +842,55 @@ impl DbClient {
         .await
     }
 
+    pub async fn del_pred_async(
+        &self,
+        params: db_params::DelPredAsyncParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "del_pred_async",
+            DBQuery::DelPredAsync {
+                store: params.store,
+                condition: params.condition,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn get_job(
+        &self,
+        params: db_params::JobParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "get_job",
+            DBQuery::GetJob {
+                job_id: params.job_id,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn cancel_job(
+        &self,
+        params: db_params::JobParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "cancel_job",
+            DBQuery::CancelJob {
+                job_id: params.job_id,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
     pub async fn drop_store(
         &self,
         params: db_params::DropStoreParams,
     ) -> Result<ServerResponse, AhnlichError> {
         self.exec(
+            "drop_store",
             DBQuery::DropStore {
                 store: params.store,
                 error_if_not_exists: params.error_if_not_exists,
@@ -391,62 +900,324 @@ impl DbClient {
         .await
     }
 
-    pub async fn ping(&self, tracing_id: Option<String>) -> Result<ServerResponse, AhnlichError> {
-        self.exec(DBQuery::Ping, tracing_id).await
+    pub async fn compact_store(
+        &self,
+        params: db_params::CompactStoreParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "compact_store",
+            DBQuery::CompactStore {
+                store: params.store,
+            },
+            params.tracing_id,
+        )
+        .await
     }
 
-    pub async fn info_server(
+    pub async fn check_store(
         &self,
-        tracing_id: Option<String>,
+        params: db_params::CheckStoreParams,
     ) -> Result<ServerResponse, AhnlichError> {
-        self.exec(DBQuery::InfoServer, tracing_id).await
+        self.exec(
+            "check_store",
+            DBQuery::CheckStore {
+                store: params.store,
+                repair: params.repair,
+            },
+            params.tracing_id,
+        )
+        .await
     }
 
-    pub async fn list_stores(
+    pub async fn scrub_store(
         &self,
-        tracing_id: Option<String>,
+        params: db_params::ScrubStoreParams,
     ) -> Result<ServerResponse, AhnlichError> {
-        self.exec(DBQuery::ListStores, tracing_id).await
+        self.exec(
+            "scrub_store",
+            DBQuery::ScrubStore {
+                store: params.store,
+                delete: params.delete,
+            },
+            params.tracing_id,
+        )
+        .await
     }
 
-    pub async fn list_clients(
+    pub async fn benchmark_store(
         &self,
-        tracing_id: Option<String>,
+        params: db_params::BenchmarkStoreParams,
     ) -> Result<ServerResponse, AhnlichError> {
-        self.exec(DBQuery::ListClients, tracing_id).await
+        self.exec(
+            "benchmark_store",
+            DBQuery::BenchmarkStore {
+                store: params.store,
+                sample_queries: params.sample_queries,
+                concurrency: params.concurrency,
+                duration: params.duration,
+            },
+            params.tracing_id,
+        )
+        .await
     }
 
-    async fn exec(
+    pub async fn export_store_parquet(
         &self,
-        query: DBQuery,
-        tracing_id: Option<String>,
+        params: db_params::ExportStoreParquetParams,
     ) -> Result<ServerResponse, AhnlichError> {
-        let mut conn = self.pool.get().await?;
-        let mut queries = ServerDBQuery::with_capacity_and_tracing_id(1, tracing_id)?;
-        queries.push(query);
-        let res = conn
-            .send_query(queries)
-            .await?
-            .pop()
-            .transpose()
-            .map_err(AhnlichError::DbError)?;
-        res.ok_or(AhnlichError::EmptyResponse)
+        self.exec(
+            "export_store_parquet",
+            DBQuery::ExportStoreParquet {
+                store: params.store,
+                path: params.path,
+            },
+            params.tracing_id,
+        )
+        .await
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use ahnlich_db::cli::ServerConfig;
-    use ahnlich_db::server::handler::Server;
-    use ndarray::array;
-    use once_cell::sync::Lazy;
+    pub async fn apply_manifest(
+        &self,
+        params: db_params::ApplyManifestParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "apply_manifest",
+            DBQuery::ApplyManifest {
+                stores: params.stores,
+                dry_run: params.dry_run,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn diff_manifest(
+        &self,
+        params: db_params::DiffManifestParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "diff_manifest",
+            DBQuery::DiffManifest {
+                stores: params.stores,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn warmup(
+        &self,
+        params: db_params::WarmupParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "warmup",
+            DBQuery::Warmup {
+                stores: params.stores,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn ping(&self, tracing_id: Option<String>) -> Result<ServerResponse, AhnlichError> {
+        self.exec("ping", DBQuery::Ping, tracing_id).await
+    }
+
+    pub async fn info_server(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("info_server", DBQuery::InfoServer, tracing_id)
+            .await
+    }
+
+    pub async fn get_memory_breakdown(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "get_memory_breakdown",
+            DBQuery::GetMemoryBreakdown,
+            tracing_id,
+        )
+        .await
+    }
+
+    pub async fn reload_config(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("reload_config", DBQuery::ReloadConfig, tracing_id)
+            .await
+    }
+
+    pub async fn list_stores(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("list_stores", DBQuery::ListStores, tracing_id)
+            .await
+    }
+
+    pub async fn describe_store(
+        &self,
+        params: db_params::DescribeStoreParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "describe_store",
+            DBQuery::DescribeStore {
+                store: params.store,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn list_clients(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("list_clients", DBQuery::ListClients, tracing_id)
+            .await
+    }
+
+    pub async fn list_jobs(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("list_jobs", DBQuery::ListJobs, tracing_id).await
+    }
+
+    pub async fn list_trashed_stores(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "list_trashed_stores",
+            DBQuery::ListTrashedStores,
+            tracing_id,
+        )
+        .await
+    }
+
+    pub async fn set_bulk_write(
+        &self,
+        params: db_params::SetBulkWriteParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "set_bulk_write",
+            DBQuery::SetBulkWrite {
+                store: params.store,
+                enabled: params.enabled,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn mirror_status(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("mirror_status", DBQuery::MirrorStatus, tracing_id)
+            .await
+    }
+
+    pub async fn control_mirror(
+        &self,
+        params: db_params::ControlMirrorParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "control_mirror",
+            DBQuery::ControlMirror {
+                action: params.action,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn restore_store(
+        &self,
+        params: db_params::RestoreStoreParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "restore_store",
+            DBQuery::RestoreStore {
+                store: params.store,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    pub async fn list_quotas(
+        &self,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec("list_quotas", DBQuery::ListQuotas, tracing_id)
+            .await
+    }
+
+    pub async fn set_quota(
+        &self,
+        params: db_params::SetQuotaParams,
+    ) -> Result<ServerResponse, AhnlichError> {
+        self.exec(
+            "set_quota",
+            DBQuery::SetQuota {
+                namespace: params.namespace,
+                quota: params.quota,
+            },
+            params.tracing_id,
+        )
+        .await
+    }
+
+    async fn exec(
+        &self,
+        method: &'static str,
+        query: DBQuery,
+        tracing_id: Option<String>,
+    ) -> Result<ServerResponse, AhnlichError> {
+        instrumented(self.instrumentation.as_ref(), method, async {
+            let mut conn = self.pool.get().await?;
+            let mut queries = ServerDBQuery::with_capacity_and_tracing_id(1, tracing_id)?;
+            queries.push(query);
+            let res = DbPipeline::send(&mut conn, &self.interceptors, queries)
+                .await?
+                .pop()
+                .transpose()
+                .map_err(AhnlichError::DbError)?;
+            res.ok_or(AhnlichError::EmptyResponse)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instrument::ClientMetrics;
+    use ahnlich_db::cli::ServerConfig;
+    use ahnlich_db::server::handler::Server;
+    use ahnlich_types::version::{Version, VERSION};
+    use ahnlich_types::RequestLimits;
+    use ndarray::array;
+    use once_cell::sync::Lazy;
     use pretty_assertions::assert_eq;
     use std::collections::HashMap;
     use std::collections::HashSet;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use tokio::time::Duration;
     use utils::server::AhnlichServerUtils;
 
+    const DEFAULT_REQUEST_LIMITS: RequestLimits = RequestLimits {
+        message_size: 1_048_576,
+        batch_size: None,
+    };
+
     static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
 
     #[tokio::test]
@@ -466,6 +1237,104 @@ mod tests {
         assert!(db_client.ping(None).await.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_client_instrumentation() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        tokio::spawn(async { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let metrics = Arc::new(ClientMetrics::default());
+        let db_client = DbClient::new(address.ip().to_string(), address.port())
+            .await
+            .expect("Could not initialize client")
+            .with_instrumentation(metrics.clone());
+        assert!(db_client.ping(None).await.is_ok());
+        assert!(db_client.ping(None).await.is_ok());
+        let drop_store_params = db_params::DropStoreParams::builder()
+            .store("Main".to_string())
+            .build();
+        assert!(db_client.drop_store(drop_store_params).await.is_err());
+        let mut pipeline = db_client.pipeline(1, None).await.unwrap();
+        pipeline.list_stores();
+        assert!(pipeline.exec().await.is_ok());
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot["ping"].requests, 2);
+        assert_eq!(snapshot["ping"].failures, 0);
+        assert_eq!(snapshot["drop_store"].requests, 1);
+        assert_eq!(snapshot["drop_store"].failures, 1);
+        assert_eq!(snapshot["pipeline"].requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_interceptors() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        tokio::spawn(async { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        let db_client = DbClient::new(address.ip().to_string(), address.port())
+            .await
+            .expect("Could not initialize client")
+            .with_interceptor(move |_: &mut ServerDBQuery| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .with_interceptor(|request: &mut ServerDBQuery| {
+                for query in request.queries_mut() {
+                    if let DBQuery::DropStore {
+                        error_if_not_exists,
+                        ..
+                    } = query
+                    {
+                        *error_if_not_exists = false;
+                    }
+                }
+                Ok(())
+            })
+            .with_interceptor(|request: &mut ServerDBQuery| {
+                let lists_clients = request
+                    .queries_mut()
+                    .iter()
+                    .any(|query| matches!(query, DBQuery::ListClients));
+                if lists_clients {
+                    return Err(AhnlichError::Intercepted("clients are private".to_string()));
+                }
+                Ok(())
+            });
+        // the store does not exist but the interceptor stops the drop from failing
+        let drop_store_params = db_params::DropStoreParams::builder()
+            .store("Main".to_string())
+            .build();
+        assert_eq!(
+            db_client.drop_store(drop_store_params).await.unwrap(),
+            ServerResponse::Del(0)
+        );
+        assert!(matches!(
+            db_client.list_clients(None).await,
+            Err(AhnlichError::Intercepted(_))
+        ));
+        let mut pipeline = db_client.pipeline(2, None).await.unwrap();
+        pipeline.list_stores();
+        pipeline.list_clients();
+        assert!(matches!(
+            pipeline.exec().await,
+            Err(AhnlichError::Intercepted(_))
+        ));
+        let mut pipeline = db_client.pipeline(1, None).await.unwrap();
+        pipeline.list_stores();
+        assert!(pipeline.exec().await.is_ok());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+    }
+
     #[tokio::test]
     async fn test_simple_pipeline() {
         let server = Server::new(&CONFIG)
@@ -493,6 +1362,142 @@ mod tests {
         assert_eq!(res, expected);
     }
 
+    #[tokio::test]
+    async fn test_pipeline_fail_fast() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        let _ = tokio::spawn(async move { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let host = address.ip();
+        let port = address.port();
+        let db_client = DbClient::new(host.to_string(), port)
+            .await
+            .expect("Could not initialize client");
+        let mut pipeline = db_client
+            .pipeline(4, None)
+            .await
+            .expect("Could not create pipeline");
+        pipeline.error_policy(ErrorPolicy::FailFast);
+        pipeline.ping();
+        pipeline.create_store(
+            db_params::CreateStoreParams::builder()
+                .store("Main".to_string())
+                .dimension(3)
+                .build(),
+        );
+        pipeline.create_store(
+            db_params::CreateStoreParams::builder()
+                .store("Main".to_string())
+                .dimension(3)
+                .build(),
+        );
+        pipeline.list_stores();
+        let res = pipeline
+            .exec_entries()
+            .await
+            .expect("Could not execute pipeline");
+        assert_eq!(
+            res,
+            vec![
+                PipelineResult::Success(ServerResponse::Pong),
+                PipelineResult::Success(ServerResponse::Unit),
+                PipelineResult::Error(
+                    ErrorResponse::new(ErrorCode::StoreAlreadyExists, "Store Main already exists",)
+                        .with_metadata("store", "Main")
+                ),
+                PipelineResult::Skipped,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_cancel() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        let _ = tokio::spawn(async move { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let host = address.ip();
+        let port = address.port();
+        let db_client = DbClient::new(host.to_string(), port)
+            .await
+            .expect("Could not initialize client");
+        let mut pipeline = db_client
+            .pipeline(2, None)
+            .await
+            .expect("Could not create pipeline");
+        pipeline.ping();
+        pipeline.list_stores();
+        let handle = pipeline.exec_with_handle();
+        handle.cancel();
+        assert!(matches!(handle.await, Err(AhnlichError::Cancelled)));
+
+        let mut pipeline = db_client
+            .pipeline(1, None)
+            .await
+            .expect("Could not create pipeline");
+        pipeline.ping();
+        let res = pipeline
+            .exec_with_handle()
+            .await
+            .expect("Could not execute pipeline");
+        let mut expected = ServerResult::with_capacity(1);
+        expected.push(Ok(ServerResponse::Pong));
+        assert_eq!(res, expected);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_downgrades_to_older_server_version() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        let _ = tokio::spawn(async move { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let endpoint = Endpoint::Tcp {
+            host: address.ip().to_string(),
+            port: address.port(),
+        };
+        let mut conn = DBConn::new(&endpoint).await.expect("Could not connect");
+        // pretend to be a client on a newer minor version than the server
+        let newer = Version {
+            minor: VERSION.minor + 1,
+            ..*VERSION
+        };
+        conn.set_version(newer);
+        let mut queries = ServerDBQuery::with_capacity(2).expect("Could not create queries");
+        queries.push(DBQuery::Ping);
+        queries.push(DBQuery::ListClients);
+        let res = conn
+            .send_query(queries)
+            .await
+            .expect("Could not execute pipeline");
+        let res = res.into_inner();
+        assert_eq!(res.len(), 2);
+        assert!(res.into_iter().all(|res| res.is_ok()));
+        assert_eq!(conn.version(), *VERSION);
+
+        // a different major version cannot be downgraded to
+        let incompatible = Version {
+            major: VERSION.major + 1,
+            ..*VERSION
+        };
+        conn.set_version(incompatible);
+        let mut queries = ServerDBQuery::with_capacity(1).expect("Could not create queries");
+        queries.push(DBQuery::Ping);
+        let err = conn.send_query(queries).await.unwrap_err();
+        assert!(matches!(
+            err,
+            AhnlichError::IncompatibleVersion { client, server } if client == incompatible && server == *VERSION
+        ));
+    }
+
     #[tokio::test]
     async fn test_pool_commands_fail_if_server_not_exist() {
         let host = "127.0.0.1";
@@ -542,13 +1547,19 @@ mod tests {
         pipeline.list_stores();
         let mut expected = ServerResult::with_capacity(4);
         expected.push(Ok(ServerResponse::Unit));
-        expected.push(Err("Store Main already exists".to_string()));
+        expected.push(Err(ErrorResponse::new(
+            ErrorCode::StoreAlreadyExists,
+            "Store Main already exists",
+        )
+        .with_metadata("store", "Main")));
         expected.push(Ok(ServerResponse::Unit));
         expected.push(Ok(ServerResponse::StoreList(HashSet::from_iter([
             StoreInfo {
                 name: StoreName("Main".to_string()),
                 len: 0,
                 size_in_bytes: 1720,
+                dimension: NonZeroUsize::new(3).unwrap(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
             },
         ]))));
         let res = pipeline.exec().await.expect("Could not execute pipeline");
@@ -604,7 +1615,9 @@ mod tests {
             ServerResponse::StoreList(HashSet::from_iter([StoreInfo {
                 name: StoreName("Main".to_string()),
                 len: 2,
-                size_in_bytes: 2160,
+                size_in_bytes: 2184,
+                dimension: NonZeroUsize::new(4).unwrap(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
             },]))
         );
         // error as different dimensions
@@ -629,11 +1642,86 @@ mod tests {
             ServerResponse::StoreList(HashSet::from_iter([StoreInfo {
                 name: StoreName("Main".to_string()),
                 len: 1,
-                size_in_bytes: 1976,
+                size_in_bytes: 1988,
+                dimension: NonZeroUsize::new(4).unwrap(),
+                request_limits: DEFAULT_REQUEST_LIMITS,
             },]))
         );
     }
 
+    #[tokio::test]
+    async fn test_get_pred_with_built_condition() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        let _ = tokio::spawn(async move { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let host = address.ip();
+        let port = address.port();
+        let db_client = DbClient::new(host.to_string(), port)
+            .await
+            .expect("Could not initialize client");
+        let create_store_params = db_params::CreateStoreParams::builder()
+            .store("Main".to_string())
+            .dimension(2)
+            .create_predicates(HashSet::from_iter([MetadataKey::new("role".into())]))
+            .build();
+        assert!(db_client.create_store(create_store_params).await.is_ok());
+        let entry = |key: StoreKey, role: &str, lang: &str| {
+            (
+                key,
+                HashMap::from_iter([
+                    (
+                        MetadataKey::new("role".into()),
+                        MetadataValue::RawString(role.into()),
+                    ),
+                    (
+                        MetadataKey::new("lang".into()),
+                        MetadataValue::RawString(lang.into()),
+                    ),
+                ]),
+            )
+        };
+        let set_params = db_params::SetParams::builder()
+            .store("Main".to_string())
+            .inputs(vec![
+                entry(StoreKey(array![1.0, 1.1]), "mage", "de"),
+                entry(StoreKey(array![1.2, 1.3]), "mage", "fr"),
+                entry(StoreKey(array![1.4, 1.5]), "knight", "en"),
+            ])
+            .build();
+        assert!(db_client.set(set_params).await.is_ok());
+
+        // conditions built from predicates are taken by the params without wrapping them
+        let get_pred_params = db_params::GetPredParams::builder()
+            .store("Main".to_string())
+            .condition(Predicate::equals("role", "mage").and(Predicate::in_("lang", ["de", "en"])))
+            .build();
+        assert_eq!(
+            db_client.get_pred(get_pred_params).await.unwrap(),
+            ServerResponse::Get(vec![entry(StoreKey(array![1.0, 1.1]), "mage", "de")])
+        );
+        let count_pred_params = db_params::CountPredParams::builder()
+            .store("Main".to_string())
+            .condition(Predicate::not_equals("role", "mage"))
+            .exact(true)
+            .build();
+        assert_eq!(
+            db_client.count_pred(count_pred_params).await.unwrap(),
+            ServerResponse::Count(1)
+        );
+        let del_pred_params = db_params::DelPredParams::builder()
+            .store("Main".to_string())
+            .condition(Predicate::not_in("lang", ["de", "en"]))
+            .build();
+        assert_eq!(
+            db_client.del_pred(del_pred_params).await.unwrap(),
+            ServerResponse::Del(1)
+        );
+    }
+
     #[tokio::test]
     async fn test_get_sim_n() {
         let server = Server::new(&CONFIG)
@@ -1,4 +1,8 @@
+use ahnlich_types::ai::AIStoreInputType;
 use ahnlich_types::bincode::BincodeSerError;
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::keyval::StoreName;
+use ahnlich_types::version::Version;
 use fallible_collections::TryReserveError;
 use thiserror::Error;
 
@@ -13,13 +17,40 @@ pub enum AhnlichError {
     #[error("bincode deserialize error {0}")]
     Bincode(#[from] bincode::Error),
     #[error("db error {0}")]
-    DbError(String),
+    DbError(ErrorResponse),
     #[error("empty response")]
     EmptyResponse,
     #[error("deadpool error {0}")]
     PoolError(String),
     #[error("ai proxy error {0}")]
-    AIProxyError(String),
+    AIProxyError(ErrorResponse),
+    #[error("expected a vector of dimension {expected} but got {found}")]
+    DimensionMismatch { expected: usize, found: usize },
+    #[error("unexpected response {0}")]
+    UnexpectedResponse(String),
+    #[error("sharded client has no shards")]
+    NoShards,
+    #[error("shard {0} already exists")]
+    ShardAlreadyExists(String),
+    #[error("shard {0} not found")]
+    ShardNotFound(String),
+    #[error("client version {client} is incompatible with server version {server}")]
+    IncompatibleVersion { client: Version, server: Version },
+    #[error("pipeline cancelled")]
+    Cancelled,
+    #[error("request intercepted {0}")]
+    Intercepted(String),
+    #[error("import error {0}")]
+    Import(String),
+    #[error("local embedding error {0}")]
+    LocalEmbed(String),
+    #[error("store {store} takes {accepted:?} inputs to {action} but got {found}")]
+    UnsupportedInput {
+        store: StoreName,
+        action: &'static str,
+        accepted: Vec<AIStoreInputType>,
+        found: AIStoreInputType,
+    },
 }
 
 impl<E: std::fmt::Debug> From<deadpool::managed::PoolError<E>> for AhnlichError {
@@ -39,3 +70,21 @@ impl From<TryReserveError> for AhnlichError {
         Self::Allocation(input)
     }
 }
+
+impl From<AhnlichError> for ErrorResponse {
+    fn from(input: AhnlichError) -> Self {
+        match input {
+            AhnlichError::DbError(err) | AhnlichError::AIProxyError(err) => err,
+            err @ AhnlichError::IncompatibleVersion { .. } => {
+                ErrorResponse::new(ErrorCode::IncompatibleVersion, err)
+            }
+            err @ AhnlichError::DimensionMismatch { .. } => {
+                ErrorResponse::new(ErrorCode::DimensionMismatch, err)
+            }
+            err @ (AhnlichError::Import(_)
+            | AhnlichError::UnsupportedInput { .. }
+            | AhnlichError::Intercepted(_)) => ErrorResponse::new(ErrorCode::InvalidArgument, err),
+            err => ErrorResponse::new(ErrorCode::Unavailable, err),
+        }
+    }
+}
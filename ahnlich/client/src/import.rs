@@ -0,0 +1,370 @@
+//! Imports embeddings from numpy `.npy` and `.npz` files into a store.
+//!
+//! Each row of a 2 dimensional float matrix becomes a key of the store. Metadata of the rows can
+//! be given as a CSV file whose header names the metadata keys and whose records follow the rows
+//! of the matrix in order, e.g the ids of the embeddings. Entries are set in batches so that a
+//! matrix never has to fit within a single request, and `.npy` files are streamed from disk
+//! rather than read whole. Arrays of `.npz` archives are decompressed into memory first.
+//!
+//! ```rust
+//! use ahnlich_client_rs::db::DbClient;
+//! use ahnlich_client_rs::builders::db as db_params;
+//!
+//! let db_client = DbClient::new("127.0.0.1".into(), 1369).await.unwrap();
+//! let params = db_params::ImportNpyParams::builder()
+//!     .store("Main".to_string())
+//!     .matrix("embeddings.npy")
+//!     .metadata(Some("ids.csv".into()))
+//!     .build();
+//! let imported = db_client.import_npy(params).await.unwrap();
+//! ```
+use crate::builders::db as db_params;
+use crate::db::DbClient;
+use crate::error::AhnlichError;
+use crate::prelude::*;
+use ndarray::Array1;
+use npyz::npz::NpzArchive;
+use npyz::{DType, NpyFile, Order, TypeChar};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+fn import_error(message: impl std::fmt::Display) -> AhnlichError {
+    AhnlichError::Import(message.to_string())
+}
+
+/// Float matrix of a numpy file, read a row at a time
+pub struct NpyMatrix {
+    rows: usize,
+    dimension: usize,
+    values: Box<dyn Iterator<Item = io::Result<f32>> + Send>,
+}
+
+impl std::fmt::Debug for NpyMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NpyMatrix")
+            .field("rows", &self.rows)
+            .field("dimension", &self.dimension)
+            .finish()
+    }
+}
+
+impl NpyMatrix {
+    /// Opens the matrix of a `.npy` file, or an array of a `.npz` archive which is the first one
+    /// when no name is given
+    pub fn open(path: impl AsRef<Path>, array: Option<&str>) -> Result<Self, AhnlichError> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|extension| extension == "npz") {
+            let mut archive = NpzArchive::open(path)?;
+            let name = match array {
+                Some(name) => name.to_string(),
+                None => archive
+                    .array_names()
+                    .next()
+                    .ok_or_else(|| import_error(format!("{} holds no arrays", path.display())))?
+                    .to_string(),
+            };
+            let file = archive
+                .by_name(&name)?
+                .ok_or_else(|| import_error(format!("{} has no array {name}", path.display())))?;
+            let (rows, dimension) = shape(&file)?;
+            let values: Vec<f32> = match file.dtype() {
+                DType::Plain(dtype) if dtype.size_field() == 8 => file
+                    .into_vec::<f64>()?
+                    .into_iter()
+                    .map(|value| value as f32)
+                    .collect(),
+                _ => file.into_vec::<f32>()?,
+            };
+            return Ok(Self {
+                rows,
+                dimension,
+                values: Box::new(values.into_iter().map(Ok)),
+            });
+        }
+        let file = NpyFile::new(BufReader::new(File::open(path)?))?;
+        let (rows, dimension) = shape(&file)?;
+        let values: Box<dyn Iterator<Item = io::Result<f32>> + Send> = match file.dtype() {
+            DType::Plain(dtype) if dtype.size_field() == 8 => Box::new(
+                file.data::<f64>()
+                    .map_err(import_error)?
+                    .map(|value| value.map(|value| value as f32)),
+            ),
+            _ => Box::new(file.data::<f32>().map_err(import_error)?),
+        };
+        Ok(Self {
+            rows,
+            dimension,
+            values,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn next_row(&mut self) -> Result<Vec<f32>, AhnlichError> {
+        let row = (&mut self.values)
+            .take(self.dimension)
+            .collect::<io::Result<Vec<f32>>>()?;
+        if row.len() != self.dimension {
+            return Err(import_error("matrix ends before its last row"));
+        }
+        Ok(row)
+    }
+}
+
+/// Rows and columns of a matrix that can be read as keys
+fn shape<R: Read>(file: &NpyFile<R>) -> Result<(usize, usize), AhnlichError> {
+    match file.dtype() {
+        DType::Plain(dtype)
+            if dtype.type_char() == TypeChar::Float && matches!(dtype.size_field(), 4 | 8) => {}
+        dtype => {
+            return Err(import_error(format!(
+                "expected a matrix of float32 or float64 but found {}",
+                dtype.descr()
+            )))
+        }
+    }
+    if file.order() == Order::Fortran {
+        return Err(import_error(
+            "matrix is in fortran order, save it with numpy.ascontiguousarray first",
+        ));
+    }
+    match file.shape() {
+        [rows, dimension] if *dimension > 0 => Ok((*rows as usize, *dimension as usize)),
+        shape => Err(import_error(format!(
+            "expected a 2 dimensional matrix but found shape {shape:?}"
+        ))),
+    }
+}
+
+/// Metadata of the rows of a matrix, read a record at a time from a CSV file
+struct Metadata {
+    keys: Vec<MetadataKey>,
+    records: csv::StringRecordsIntoIter<File>,
+}
+
+impl Metadata {
+    /// Opens a CSV file after checking that it holds a record for each of the rows
+    fn open(path: &Path, rows: usize) -> Result<Self, AhnlichError> {
+        let mut records = 0;
+        for record in csv::Reader::from_path(path)
+            .map_err(import_error)?
+            .into_records()
+        {
+            record.map_err(import_error)?;
+            records += 1;
+        }
+        if records != rows {
+            return Err(import_error(format!(
+                "metadata has {records} records for the {rows} rows of the matrix"
+            )));
+        }
+        let mut reader = csv::Reader::from_path(path).map_err(import_error)?;
+        let keys = reader
+            .headers()
+            .map_err(import_error)?
+            .iter()
+            .map(|key| MetadataKey::new(key.to_string()))
+            .collect();
+        Ok(Self {
+            keys,
+            records: reader.into_records(),
+        })
+    }
+
+    /// Metadata of the next row, leaving out empty fields
+    fn next_value(&mut self) -> Result<StoreValue, AhnlichError> {
+        let record = self
+            .records
+            .next()
+            .ok_or_else(|| import_error("metadata ends before the matrix"))?
+            .map_err(import_error)?;
+        Ok(self
+            .keys
+            .iter()
+            .zip(record.iter())
+            .filter(|(_, field)| !field.is_empty())
+            .map(|(key, field)| (key.clone(), MetadataValue::RawString(field.to_string())))
+            .collect())
+    }
+}
+
+impl DbClient {
+    /// Sets the rows of a numpy matrix into an existing store in batches, along with the
+    /// metadata of each row when given. The dimension of the matrix and the number of metadata
+    /// records are checked before anything is set
+    pub async fn import_npy(
+        &self,
+        params: db_params::ImportNpyParams,
+    ) -> Result<StoreUpsert, AhnlichError> {
+        let mut matrix = NpyMatrix::open(&params.matrix, params.array.as_deref())?;
+        let mut metadata = params
+            .metadata
+            .as_deref()
+            .map(|path| Metadata::open(path, matrix.rows()))
+            .transpose()?;
+        let describe = db_params::DescribeStoreParams::builder()
+            .store(params.store.to_string())
+            .tracing_id(params.tracing_id.clone())
+            .build();
+        let dimension = match self.describe_store(describe).await? {
+            ServerResponse::StoreDescription(description) => description.info.dimension.get(),
+            response => {
+                return Err(AhnlichError::UnexpectedResponse(format!("{response:?}")));
+            }
+        };
+        if dimension != matrix.dimension() {
+            return Err(AhnlichError::DimensionMismatch {
+                expected: dimension,
+                found: matrix.dimension(),
+            });
+        }
+        let mut imported = StoreUpsert {
+            inserted: 0,
+            updated: 0,
+        };
+        let mut row = 0;
+        while row < matrix.rows() {
+            let end = matrix.rows().min(row + params.batch_size.get());
+            let mut inputs = Vec::with_capacity(end - row);
+            for _ in row..end {
+                let key = StoreKey(Array1::from_vec(matrix.next_row()?));
+                let value = match metadata.as_mut() {
+                    Some(metadata) => metadata.next_value()?,
+                    None => StoreValue::new(),
+                };
+                inputs.push((key, value));
+            }
+            let set = db_params::SetParams::builder()
+                .store(params.store.to_string())
+                .inputs(inputs)
+                .tracing_id(params.tracing_id.clone())
+                .build();
+            match self.set(set).await? {
+                ServerResponse::Set(upsert) => {
+                    imported.inserted += upsert.inserted;
+                    imported.updated += upsert.updated;
+                }
+                response => {
+                    return Err(AhnlichError::UnexpectedResponse(format!("{response:?}")));
+                }
+            }
+            row = end;
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahnlich_db::cli::ServerConfig;
+    use ahnlich_db::server::handler::Server;
+    use npyz::WriterBuilder;
+    use once_cell::sync::Lazy;
+    use pretty_assertions::assert_eq;
+    use std::num::NonZeroUsize;
+    use std::path::PathBuf;
+    use tokio::time::Duration;
+    use utils::server::AhnlichServerUtils;
+
+    static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
+
+    fn write_matrix(name: &str, shape: &[u64], values: &[f64]) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let mut writer = npyz::WriteOptions::new()
+            .default_dtype()
+            .shape(shape)
+            .writer(File::create(&path).unwrap())
+            .begin_nd()
+            .unwrap();
+        writer.extend(values.iter().copied()).unwrap();
+        writer.finish().unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_import_npy() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        tokio::spawn(async { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let db_client = DbClient::new(address.ip().to_string(), address.port())
+            .await
+            .expect("Could not initialize client");
+        let create_store_params = db_params::CreateStoreParams::builder()
+            .store("Main".to_string())
+            .dimension(2)
+            .build();
+        db_client.create_store(create_store_params).await.unwrap();
+
+        let matrix = write_matrix(
+            "ahnlich_test_import.npy",
+            &[3, 2],
+            &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0],
+        );
+        let metadata = std::env::temp_dir().join("ahnlich_test_import.csv");
+        std::fs::write(&metadata, "id,label\na,first\nb,\nc,third\n").unwrap();
+        let params = db_params::ImportNpyParams::builder()
+            .store("Main".to_string())
+            .matrix(matrix.clone())
+            .metadata(Some(metadata.clone()))
+            .batch_size(NonZeroUsize::new(2).unwrap())
+            .build();
+        assert_eq!(
+            db_client.import_npy(params).await.unwrap(),
+            StoreUpsert {
+                inserted: 3,
+                updated: 0
+            }
+        );
+        let get_key_params = db_params::GetKeyParams::builder()
+            .store("Main".to_string())
+            .keys(vec![StoreKey(Array1::from_vec(vec![3.0, 4.0]))])
+            .build();
+        assert_eq!(
+            db_client.get_key(get_key_params).await.unwrap(),
+            ServerResponse::Get(vec![(
+                StoreKey(Array1::from_vec(vec![3.0, 4.0])),
+                StoreValue::from_iter([(
+                    MetadataKey::new("id".into()),
+                    MetadataValue::RawString("b".into())
+                )])
+            )])
+        );
+
+        // metadata has to have a record for every row
+        std::fs::write(&metadata, "id\na\nb\n").unwrap();
+        let params = db_params::ImportNpyParams::builder()
+            .store("Main".to_string())
+            .matrix(matrix.clone())
+            .metadata(Some(metadata.clone()))
+            .build();
+        assert!(matches!(
+            db_client.import_npy(params).await,
+            Err(AhnlichError::Import(_))
+        ));
+
+        let matrix = write_matrix("ahnlich_test_import_wide.npy", &[1, 3], &[1.0, 2.0, 3.0]);
+        let params = db_params::ImportNpyParams::builder()
+            .store("Main".to_string())
+            .matrix(matrix)
+            .build();
+        assert!(matches!(
+            db_client.import_npy(params).await,
+            Err(AhnlichError::DimensionMismatch {
+                expected: 2,
+                found: 3
+            })
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+//! Hooks for observing requests made by the db and ai clients.
+//!
+//! An [`Instrumentation`] registered on a client is told when each request starts and ends so
+//! client side metrics can be wired into any telemetry system. [`ClientMetrics`] is a ready made
+//! hook that counts requests, failures and latency per method. Enabling the `tracing` feature
+//! additionally wraps each request in a `tracing` span named after the method.
+//!
+//! ```rust
+//! use ahnlich_client_rs::db::DbClient;
+//! use ahnlich_client_rs::instrument::ClientMetrics;
+//! use std::sync::Arc;
+//!
+//! let metrics = Arc::new(ClientMetrics::default());
+//! let db_client = DbClient::new("127.0.0.1".into(), 1369)
+//!     .await
+//!     .unwrap()
+//!     .with_instrumentation(metrics.clone());
+//! db_client.ping(None).await.unwrap();
+//! let ping = metrics.snapshot()["ping"];
+//! ```
+use crate::error::AhnlichError;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How a request ended
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome<'a> {
+    Success,
+    Error(&'a AhnlichError),
+}
+
+impl Outcome<'_> {
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Success)
+    }
+}
+
+/// Called around every request made by a client. `method` is the name of the client method, or
+/// `pipeline` for pipelines, and both hooks are no-ops by default
+pub trait Instrumentation: std::fmt::Debug + Send + Sync {
+    fn on_request_start(&self, _method: &'static str) {}
+
+    fn on_request_end(&self, _method: &'static str, _duration: Duration, _outcome: Outcome<'_>) {}
+}
+
+/// Counters for requests made through a single client method
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MethodMetrics {
+    pub requests: u64,
+    pub failures: u64,
+    pub total_duration: Duration,
+}
+
+/// Instrumentation counting requests, failures and time spent per client method
+#[derive(Debug, Default)]
+pub struct ClientMetrics {
+    methods: Mutex<HashMap<&'static str, MethodMetrics>>,
+}
+
+impl ClientMetrics {
+    /// current counters of every method that has completed at least one request
+    pub fn snapshot(&self) -> HashMap<&'static str, MethodMetrics> {
+        self.methods
+            .lock()
+            .expect("client metrics lock poisoned")
+            .clone()
+    }
+}
+
+impl Instrumentation for ClientMetrics {
+    fn on_request_end(&self, method: &'static str, duration: Duration, outcome: Outcome<'_>) {
+        let mut methods = self.methods.lock().expect("client metrics lock poisoned");
+        let metrics = methods.entry(method).or_default();
+        metrics.requests += 1;
+        if !outcome.is_success() {
+            metrics.failures += 1;
+        }
+        metrics.total_duration += duration;
+    }
+}
+
+/// runs a request, reporting it to the instrumentation if any
+pub(crate) async fn instrumented<T>(
+    instrumentation: Option<&Arc<dyn Instrumentation>>,
+    method: &'static str,
+    request: impl Future<Output = Result<T, AhnlichError>>,
+) -> Result<T, AhnlichError> {
+    if let Some(instrumentation) = instrumentation {
+        instrumentation.on_request_start(method);
+    }
+    let start = Instant::now();
+    #[cfg(feature = "tracing")]
+    let request = tracing::Instrument::instrument(
+        request,
+        tracing::info_span!("ahnlich_client.request", method),
+    );
+    let result = request.await;
+    if let Some(instrumentation) = instrumentation {
+        let outcome = match &result {
+            Ok(_) => Outcome::Success,
+            Err(err) => Outcome::Error(err),
+        };
+        instrumentation.on_request_end(method, start.elapsed(), outcome);
+    }
+    result
+}
@@ -0,0 +1,88 @@
+//! Hooks for changing requests made by the db and ai clients before they are sent.
+//!
+//! An [`Interceptor`] registered on a client is handed every request it sends, from its methods
+//! and its pipelines alike, right before the request goes out. Interceptors can change the trace
+//! id, timeout, priority or error policy of a request, rewrite its queries or refuse to send it
+//! by returning an error, which the request then fails with. They run in the order they were
+//! added, each seeing the changes of the ones before it. Closures taking the request can be used
+//! as interceptors.
+//!
+//! ```rust
+//! use ahnlich_client_rs::db::DbClient;
+//! use ahnlich_client_rs::error::AhnlichError;
+//! use ahnlich_client_rs::prelude::*;
+//! use std::time::Duration;
+//!
+//! let db_client = DbClient::new("127.0.0.1".into(), 1369)
+//!     .await
+//!     .unwrap()
+//!     .with_interceptor(|request: &mut ServerDBQuery| {
+//!         request.set_timeout(Duration::from_secs(5));
+//!         Ok(())
+//!     })
+//!     .with_interceptor(|request: &mut ServerDBQuery| {
+//!         // keep this client from dropping stores
+//!         let drops_store = request
+//!             .queries_mut()
+//!             .iter()
+//!             .any(|query| matches!(query, DBQuery::DropStore { .. }));
+//!         if drops_store {
+//!             return Err(AhnlichError::Intercepted("stores cannot be dropped".to_string()));
+//!         }
+//!         Ok(())
+//!     });
+//! db_client.ping(None).await.unwrap();
+//! ```
+use crate::error::AhnlichError;
+use std::fmt;
+use std::sync::Arc;
+
+/// Called on every request sent by a client, `Q` being the request type of the client
+pub trait Interceptor<Q>: Send + Sync {
+    fn intercept(&self, request: &mut Q) -> Result<(), AhnlichError>;
+}
+
+impl<Q, F> Interceptor<Q> for F
+where
+    F: Fn(&mut Q) -> Result<(), AhnlichError> + Send + Sync,
+{
+    fn intercept(&self, request: &mut Q) -> Result<(), AhnlichError> {
+        self(request)
+    }
+}
+
+/// Interceptors of a client, shared with the pipelines it creates
+pub(crate) struct Interceptors<Q>(Vec<Arc<dyn Interceptor<Q>>>);
+
+impl<Q> Interceptors<Q> {
+    pub(crate) fn push(&mut self, interceptor: Arc<dyn Interceptor<Q>>) {
+        self.0.push(interceptor)
+    }
+
+    /// runs every interceptor on `request` in turn, stopping at the first to fail
+    pub(crate) fn apply(&self, request: &mut Q) -> Result<(), AhnlichError> {
+        self.0
+            .iter()
+            .try_for_each(|interceptor| interceptor.intercept(request))
+    }
+}
+
+impl<Q> Default for Interceptors<Q> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<Q> Clone for Interceptors<Q> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<Q> fmt::Debug for Interceptors<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interceptors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
@@ -118,9 +118,36 @@
 //!
 //! let results = pipeline.exec().await.unwrap();
 //! ```
+//!
+//! ## Predicates
+//!
+//! Conditions on metadata can be built up from the helpers on Predicate rather than spelling out
+//! each variant
+//!
+//! ```rust
+//! use ahnlich_client_rs::{builders::db as db_params};
+//! use ahnlich_client_rs::prelude::*;
+//!
+//! let condition = Predicate::equals("role", "mage").and(Predicate::in_("lang", ["de", "en"]));
+//! let get_pred_params = db_params::GetPredParams::builder()
+//!     .store("Main".to_string())
+//!     .condition(condition)
+//!     .build();
+//! ```
 pub mod ai;
+pub mod ai_store;
 pub mod builders;
 pub mod conn;
 pub mod db;
 pub mod error;
+#[cfg(feature = "npy")]
+pub mod import;
+pub mod instrument;
+pub mod intercept;
+#[cfg(feature = "local-embed")]
+pub mod local_embed;
+pub mod pipeline;
 pub mod prelude;
+pub mod retriever;
+pub mod shard;
+pub mod store;
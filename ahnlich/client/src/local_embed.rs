@@ -0,0 +1,405 @@
+//! Embeds text locally with the ONNX models the AI proxy runs, for offline tools that have no AI
+//! proxy to reach.
+//!
+//! The vectors match the ones the AI proxy produces for the same model: texts are tokenized,
+//! padded and truncated the same way, then pooled and normalized like the proxy does, so a store
+//! filled locally can be queried with embeddings from the proxy and the other way around. Only the
+//! built-in text models are supported. Models are downloaded from Hugging Face the first time they
+//! are loaded into the same cache the AI proxy uses by default, `~/.ahnlich/models`, so a machine
+//! that has run the proxy can embed without network access. Applications that prefer the proxy
+//! can fall back to a local model when `AIClient::ping` fails.
+//!
+//! ```rust
+//! use ahnlich_client_rs::db::DbClient;
+//! use ahnlich_client_rs::builders::db as db_params;
+//! use ahnlich_client_rs::local_embed::LocalEmbedder;
+//! use ahnlich_client_rs::prelude::*;
+//!
+//! let embedder = LocalEmbedder::load(AIModel::AllMiniLML6V2, None).unwrap();
+//! let db_client = DbClient::new("127.0.0.1".into(), 1369).await.unwrap();
+//! let params = db_params::SetTextsParams::builder()
+//!     .store("Main".to_string())
+//!     .inputs(vec![("Jordan One".to_string(), StoreValue::new())])
+//!     .build();
+//! let upsert = db_client.set_texts(&embedder, params).await.unwrap();
+//! ```
+use crate::builders::db as db_params;
+use crate::db::DbClient;
+use crate::error::AhnlichError;
+use crate::prelude::*;
+use hf_hub::{api::sync::ApiBuilder, Cache};
+use ndarray::{s, Array, Array1, Array2, Axis, Ix2, Ix3};
+use ort::{Session, Value};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::thread::available_parallelism;
+use tokenizers::{
+    AddedToken, PaddingParams, PaddingStrategy, Tokenizer, TruncationDirection, TruncationParams,
+};
+
+fn local_embed_error(message: impl std::fmt::Display) -> AhnlichError {
+    AhnlichError::LocalEmbed(message.to_string())
+}
+
+/// Default cache of the models, the one the AI proxy downloads its models into
+pub fn default_cache_location() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ahnlich").join("models"))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Pooling {
+    Mean,
+    Cls,
+}
+
+/// Where a model is fetched from and how its output becomes an embedding
+struct TextModel {
+    repo: &'static str,
+    weights_file: &'static str,
+    output: &'static str,
+    pooling: Pooling,
+    normalize: bool,
+    max_input_tokens: usize,
+    dimension: usize,
+}
+
+impl TryFrom<&AIModel> for TextModel {
+    type Error = AhnlichError;
+
+    fn try_from(model: &AIModel) -> Result<Self, Self::Error> {
+        let text_model = match model {
+            AIModel::AllMiniLML6V2 => TextModel {
+                repo: "Qdrant/all-MiniLM-L6-v2-onnx",
+                weights_file: "model.onnx",
+                output: "last_hidden_state",
+                pooling: Pooling::Mean,
+                normalize: true,
+                max_input_tokens: 256,
+                dimension: 384,
+            },
+            AIModel::AllMiniLML12V2 => TextModel {
+                repo: "Xenova/all-MiniLM-L12-v2",
+                weights_file: "onnx/model.onnx",
+                output: "last_hidden_state",
+                pooling: Pooling::Mean,
+                normalize: true,
+                max_input_tokens: 256,
+                dimension: 384,
+            },
+            AIModel::BGEBaseEnV15 => TextModel {
+                repo: "Xenova/bge-base-en-v1.5",
+                weights_file: "onnx/model.onnx",
+                output: "last_hidden_state",
+                pooling: Pooling::Cls,
+                normalize: true,
+                max_input_tokens: 512,
+                dimension: 768,
+            },
+            AIModel::BGELargeEnV15 => TextModel {
+                repo: "Xenova/bge-large-en-v1.5",
+                weights_file: "onnx/model.onnx",
+                output: "last_hidden_state",
+                pooling: Pooling::Cls,
+                normalize: true,
+                max_input_tokens: 512,
+                dimension: 1024,
+            },
+            AIModel::ClipVitB32Text => TextModel {
+                repo: "Qdrant/clip-ViT-B-32-text",
+                weights_file: "model.onnx",
+                output: "text_embeds",
+                pooling: Pooling::Mean,
+                normalize: false,
+                max_input_tokens: 77,
+                dimension: 512,
+            },
+            model => {
+                return Err(local_embed_error(format!(
+                    "{model:?} cannot be run locally, only the built-in text models can"
+                )))
+            }
+        };
+        Ok(text_model)
+    }
+}
+
+/// Text model loaded into an ONNX runtime session within the client
+pub struct LocalEmbedder {
+    model: AIModel,
+    text_model: TextModel,
+    tokenizer: Tokenizer,
+    session: Session,
+}
+
+impl std::fmt::Debug for LocalEmbedder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalEmbedder")
+            .field("model", &self.model)
+            .field("dimension", &self.text_model.dimension)
+            .finish()
+    }
+}
+
+impl LocalEmbedder {
+    /// Loads a text model from the cache, downloading it first when it is not cached. The default
+    /// cache is used when no location is given
+    pub fn load(model: AIModel, cache_location: Option<PathBuf>) -> Result<Self, AhnlichError> {
+        let text_model = TextModel::try_from(&model)?;
+        let cache_location = cache_location
+            .or_else(default_cache_location)
+            .ok_or_else(|| local_embed_error("no cache location given and no home directory"))?;
+        let api = ApiBuilder::from_cache(Cache::new(cache_location.join("huggingface")))
+            .with_progress(true)
+            .build()
+            .map_err(local_embed_error)?;
+        let repo = api.model(text_model.repo.to_string());
+        let fetch = |filename: &str| {
+            repo.get(filename)
+                .map_err(|e| local_embed_error(format!("failed to fetch {filename}, {e}")))
+        };
+        let tokenizer = load_tokenizer(
+            &fetch("tokenizer.json")?,
+            &read_json(&fetch("config.json")?)?,
+            &read_json(&fetch("special_tokens_map.json")?)?,
+            &read_json(&fetch("tokenizer_config.json")?)?,
+            text_model.max_input_tokens,
+        )?;
+        let weights_file = fetch(text_model.weights_file)?;
+        let threads = available_parallelism().map_or(1, NonZeroUsize::get);
+        let session = Session::builder()
+            .and_then(|builder| builder.with_intra_threads(threads))
+            .and_then(|builder| builder.commit_from_file(weights_file))
+            .map_err(local_embed_error)?;
+        Ok(Self {
+            model,
+            text_model,
+            tokenizer,
+            session,
+        })
+    }
+
+    pub fn model(&self) -> &AIModel {
+        &self.model
+    }
+
+    /// Dimension of the embeddings of the model
+    pub fn dimension(&self) -> NonZeroUsize {
+        NonZeroUsize::new(self.text_model.dimension).expect("models have a non zero dimension")
+    }
+
+    /// Embeds each text into a key, truncating texts longer than the model takes from the end.
+    /// Inference runs on the calling thread
+    pub fn embed(&self, texts: Vec<String>) -> Result<Vec<StoreKey>, AhnlichError> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts, true)
+            .map_err(local_embed_error)?;
+        let batch_size = encodings.len();
+        let encoding_length = encodings[0].len();
+        let need_token_type_ids = self
+            .session
+            .inputs
+            .iter()
+            .any(|input| input.name == "token_type_ids");
+        let mut ids = Vec::with_capacity(batch_size * encoding_length);
+        let mut mask = Vec::with_capacity(batch_size * encoding_length);
+        let mut type_ids = Vec::with_capacity(batch_size * encoding_length);
+        for encoding in &encodings {
+            ids.extend(encoding.get_ids().iter().map(|x| *x as i64));
+            mask.extend(encoding.get_attention_mask().iter().map(|x| *x as i64));
+            if need_token_type_ids {
+                type_ids.extend(encoding.get_type_ids().iter().map(|x| *x as i64));
+            }
+        }
+        let shape = (batch_size, encoding_length);
+        let ids = Array::from_shape_vec(shape, ids).map_err(local_embed_error)?;
+        let mask = Array::from_shape_vec(shape, mask).map_err(local_embed_error)?;
+        let mut inputs = ort::inputs![
+            "input_ids" => Value::from_array(ids)?,
+            "attention_mask" => Value::from_array(mask.view())?,
+        ]
+        .map_err(local_embed_error)?;
+        if need_token_type_ids {
+            let type_ids = Array::from_shape_vec(shape, type_ids).map_err(local_embed_error)?;
+            inputs.push((
+                "token_type_ids".into(),
+                Value::from_array(type_ids)
+                    .map_err(local_embed_error)?
+                    .into(),
+            ));
+        }
+        let outputs = self.session.run(inputs).map_err(local_embed_error)?;
+        let output = outputs
+            .get(self.text_model.output)
+            .ok_or_else(|| local_embed_error(format!("no {} output", self.text_model.output)))?
+            .try_extract_tensor::<f32>()
+            .map_err(local_embed_error)?;
+        let embeddings = match output.ndim() {
+            2 => output
+                .into_dimensionality::<Ix2>()
+                .map_err(local_embed_error)?
+                .to_owned(),
+            3 => {
+                let output = output
+                    .into_dimensionality::<Ix3>()
+                    .map_err(local_embed_error)?;
+                match self.text_model.pooling {
+                    Pooling::Mean => mean_pooling(output.to_owned(), &mask),
+                    Pooling::Cls => output.slice(s![.., 0, ..]).to_owned(),
+                }
+            }
+            ndim => {
+                return Err(local_embed_error(format!(
+                    "expected a 2 or 3 dimensional output but got {ndim} dimensions"
+                )))
+            }
+        };
+        let embeddings = if self.text_model.normalize {
+            normalize(embeddings)
+        } else {
+            embeddings
+        };
+        Ok(embeddings
+            .axis_iter(Axis(0))
+            .map(|embedding| StoreKey(Array1::from_iter(embedding.iter().copied())))
+            .collect())
+    }
+}
+
+fn read_json(path: &Path) -> Result<serde_json::Value, AhnlichError> {
+    let contents = std::fs::read(path)?;
+    serde_json::from_slice(&contents)
+        .map_err(|e| local_embed_error(format!("failed to parse {}, {e}", path.display())))
+}
+
+/// Sets up the tokenizer of a model with the padding, truncation and special tokens the AI proxy
+/// uses for it
+fn load_tokenizer(
+    tokenizer_file: &Path,
+    config: &serde_json::Value,
+    special_tokens_map: &serde_json::Value,
+    tokenizer_config: &serde_json::Value,
+    max_input_tokens: usize,
+) -> Result<Tokenizer, AhnlichError> {
+    let mut tokenizer = Tokenizer::from_file(tokenizer_file).map_err(local_embed_error)?;
+    let model_max_length = tokenizer_config["model_max_length"]
+        .as_f64()
+        .ok_or_else(|| local_embed_error("no model_max_length in tokenizer_config.json"))?
+        as usize;
+    let pad_token = tokenizer_config["pad_token"]
+        .as_str()
+        .ok_or_else(|| local_embed_error("no pad_token in tokenizer_config.json"))?
+        .to_string();
+    let pad_id = config["pad_token_id"].as_u64().unwrap_or(0) as u32;
+    tokenizer
+        .with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            pad_token,
+            pad_id,
+            ..Default::default()
+        }))
+        .with_truncation(Some(TruncationParams {
+            max_length: max_input_tokens.min(model_max_length),
+            direction: TruncationDirection::Right,
+            ..Default::default()
+        }))
+        .map_err(local_embed_error)?;
+    if let serde_json::Value::Object(tokens) = special_tokens_map {
+        for value in tokens.values() {
+            let token = match value {
+                serde_json::Value::String(content) => AddedToken {
+                    content: content.clone(),
+                    special: true,
+                    ..Default::default()
+                },
+                serde_json::Value::Object(_) => AddedToken {
+                    content: value["content"].as_str().unwrap_or_default().to_string(),
+                    special: true,
+                    single_word: value["single_word"].as_bool().unwrap_or_default(),
+                    lstrip: value["lstrip"].as_bool().unwrap_or_default(),
+                    rstrip: value["rstrip"].as_bool().unwrap_or_default(),
+                    normalized: value["normalized"].as_bool().unwrap_or_default(),
+                },
+                _ => continue,
+            };
+            tokenizer.add_special_tokens(&[token]);
+        }
+    }
+    Ok(tokenizer)
+}
+
+/// Averages the token embeddings of each text, leaving out padding
+fn mean_pooling(output: Array<f32, Ix3>, mask: &Array2<i64>) -> Array2<f32> {
+    let mask = mask.mapv(|x| x as f32).insert_axis(Axis(2));
+    let masked = &output * &mask;
+    let counts = mask.sum_axis(Axis(1)).mapv(|x| x.max(1e-9));
+    masked.sum_axis(Axis(1)) / counts
+}
+
+fn normalize(embeddings: Array2<f32>) -> Array2<f32> {
+    let norms = (&embeddings * &embeddings)
+        .sum_axis(Axis(1))
+        .mapv(|x| x.sqrt() + 1e-12)
+        .insert_axis(Axis(1));
+    embeddings / norms
+}
+
+impl DbClient {
+    /// Embeds texts with a local model and sets them into an existing store in batches. The
+    /// dimension of the store is checked against the model before anything is embedded
+    pub async fn set_texts(
+        &self,
+        embedder: &LocalEmbedder,
+        params: db_params::SetTextsParams,
+    ) -> Result<StoreUpsert, AhnlichError> {
+        let describe = db_params::DescribeStoreParams::builder()
+            .store(params.store.to_string())
+            .tracing_id(params.tracing_id.clone())
+            .build();
+        let dimension = match self.describe_store(describe).await? {
+            ServerResponse::StoreDescription(description) => description.info.dimension.get(),
+            response => {
+                return Err(AhnlichError::UnexpectedResponse(format!("{response:?}")));
+            }
+        };
+        if dimension != embedder.dimension().get() {
+            return Err(AhnlichError::DimensionMismatch {
+                expected: dimension,
+                found: embedder.dimension().get(),
+            });
+        }
+        let mut upserted = StoreUpsert {
+            inserted: 0,
+            updated: 0,
+        };
+        let mut inputs = params.inputs.into_iter().peekable();
+        while inputs.peek().is_some() {
+            let (texts, mut values): (Vec<_>, Vec<_>) =
+                inputs.by_ref().take(params.batch_size.get()).unzip();
+            if let Some(text_key) = &params.text_key {
+                for (text, value) in texts.iter().zip(values.iter_mut()) {
+                    value.insert(text_key.clone(), MetadataValue::RawString(text.clone()));
+                }
+            }
+            let keys = embedder.embed(texts)?;
+            let set = db_params::SetParams::builder()
+                .store(params.store.to_string())
+                .inputs(keys.into_iter().zip(values).collect())
+                .tracing_id(params.tracing_id.clone())
+                .build();
+            match self.set(set).await? {
+                ServerResponse::Set(upsert) => {
+                    upserted.inserted += upsert.inserted;
+                    upserted.updated += upsert.updated;
+                }
+                response => {
+                    return Err(AhnlichError::UnexpectedResponse(format!("{response:?}")));
+                }
+            }
+        }
+        Ok(upserted)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::error::AhnlichError;
+use ahnlich_types::error::ErrorResponse;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Outcome of a single query sent in a pipeline, in the order the query was pushed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineResult<T> {
+    Success(T),
+    Error(ErrorResponse),
+    // Not run by the server as an earlier query failed in a pipeline set to
+    // `ErrorPolicy::FailFast`
+    Skipped,
+}
+
+impl<T> PipelineResult<T> {
+    /// Pairs server results with the `len` queries that were sent, marking queries the server
+    /// stopped before as skipped
+    pub(crate) fn from_results(results: Vec<Result<T, ErrorResponse>>, len: usize) -> Vec<Self> {
+        let mut entries: Vec<_> = results
+            .into_iter()
+            .map(|result| match result {
+                Ok(response) => Self::Success(response),
+                Err(error) => Self::Error(error),
+            })
+            .collect();
+        if entries.len() < len {
+            entries.resize_with(len, || Self::Skipped);
+        }
+        entries
+    }
+}
+
+/// A pipeline sent with `exec_with_handle`, which resolves to the results of the pipeline once
+/// awaited. Cancelling it closes the connection it was sent on, and the server stops before the
+/// next query of the pipeline once it notices
+#[derive(Debug)]
+pub struct PipelineHandle<T> {
+    canceller: PipelineCanceller,
+    task: JoinHandle<Result<T, AhnlichError>>,
+}
+
+impl<T: Send + 'static> PipelineHandle<T> {
+    /// Runs `exec` in the background, which should stop sending the pipeline once it is notified
+    pub(crate) fn spawn<F, Fut>(exec: F) -> Self
+    where
+        F: FnOnce(Arc<Notify>) -> Fut,
+        Fut: Future<Output = Result<T, AhnlichError>> + Send + 'static,
+    {
+        let canceller = PipelineCanceller(Arc::new(Notify::new()));
+        Self {
+            task: tokio::spawn(exec(canceller.0.clone())),
+            canceller,
+        }
+    }
+}
+
+impl<T> PipelineHandle<T> {
+    /// Cancels the pipeline, after which awaiting it fails with `AhnlichError::Cancelled` unless
+    /// the results came back first
+    pub fn cancel(&self) {
+        self.canceller.cancel()
+    }
+
+    /// Cancels the pipeline from elsewhere while it is being awaited
+    pub fn canceller(&self) -> PipelineCanceller {
+        self.canceller.clone()
+    }
+}
+
+impl<T> Future for PipelineHandle<T> {
+    type Output = Result<T, AhnlichError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.task)
+            .poll(cx)
+            .map(|joined| joined.unwrap_or_else(|error| Err(AhnlichError::Standard(error.into()))))
+    }
+}
+
+/// Cancels the pipeline of a [`PipelineHandle`]
+#[derive(Debug, Clone)]
+pub struct PipelineCanceller(Arc<Notify>);
+
+impl PipelineCanceller {
+    pub fn cancel(&self) {
+        // a permit is stored when the pipeline is yet to wait on it
+        self.0.notify_one()
+    }
+}
@@ -1,6 +1,9 @@
 pub use ahnlich_types::ai::*;
 pub use ahnlich_types::db::*;
+pub use ahnlich_types::error::*;
 pub use ahnlich_types::keyval::*;
 pub use ahnlich_types::metadata::*;
 pub use ahnlich_types::predicate::*;
 pub use ahnlich_types::similarity::*;
+pub use ahnlich_types::ErrorPolicy;
+pub use ahnlich_types::Priority;
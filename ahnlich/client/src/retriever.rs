@@ -0,0 +1,284 @@
+//! Document retrieval over an ahnlich AI store for RAG pipelines.
+//!
+//! A [`Retriever`] stores text documents along with their metadata and returns the closest
+//! documents to a query, optionally filtered on metadata, without callers having to manage
+//! stores or build queries themselves.
+//!
+//! ```rust
+//! use ahnlich_client_rs::ai::AIClient;
+//! use ahnlich_client_rs::retriever::{match_metadata, AIRetriever, Document, Retriever};
+//!
+//! let ai_client = AIClient::new("127.0.0.1".into(), 1370).await.unwrap();
+//! let retriever = AIRetriever::builder()
+//!     .client(ai_client)
+//!     .store("Docs".to_string())
+//!     .build();
+//! retriever.create_store().await.unwrap();
+//! retriever
+//!     .add_documents(vec![
+//!         Document::new("Nike Air Jordans").with_metadata("brand", "Nike"),
+//!         Document::new("Adidas Yeezy").with_metadata("brand", "Adidas"),
+//!     ])
+//!     .await
+//!     .unwrap();
+//! let results = retriever
+//!     .similarity_search("Jordans", 1, match_metadata([("brand", "Nike")]))
+//!     .await
+//!     .unwrap();
+//! ```
+use crate::ai::AIClient;
+use crate::builders::ai as ai_params;
+use crate::error::AhnlichError;
+use ahnlich_types::ai::{AIModel, AIServerResponse, PreprocessAction};
+use ahnlich_types::db::StoreUpsert;
+use ahnlich_types::keyval::{StoreInput, StoreName, StoreValue};
+use ahnlich_types::metadata::{MetadataKey, MetadataValue};
+use ahnlich_types::predicate::{Predicate, PredicateCondition};
+use ahnlich_types::similarity::{Algorithm, Similarity};
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use typed_builder::TypedBuilder;
+
+/// Text content and the metadata it can be filtered on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    pub content: String,
+    pub metadata: StoreValue,
+}
+
+impl Document {
+    pub fn new(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            metadata: StoreValue::new(),
+        }
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(
+            MetadataKey::new(key.into()),
+            MetadataValue::RawString(value.into()),
+        );
+        self
+    }
+}
+
+/// Condition matching documents whose metadata contains all of the given key value pairs, or
+/// `None` when no pairs are given
+pub fn match_metadata<K, V>(pairs: impl IntoIterator<Item = (K, V)>) -> Option<PredicateCondition>
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    pairs
+        .into_iter()
+        .map(|(key, value)| {
+            PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new(key.into()),
+                value: MetadataValue::RawString(value.into()),
+            })
+        })
+        .reduce(PredicateCondition::and)
+}
+
+#[async_trait::async_trait]
+pub trait Retriever {
+    /// Embeds and stores documents, documents with the same content replace the earlier ones
+    async fn add_documents(&self, documents: Vec<Document>) -> Result<StoreUpsert, AhnlichError>;
+
+    /// Returns up to `k` documents closest to `query` that match `filter`, most similar first
+    async fn similarity_search(
+        &self,
+        query: &str,
+        k: usize,
+        filter: Option<PredicateCondition>,
+    ) -> Result<Vec<(Document, Similarity)>, AhnlichError>;
+}
+
+/// [`Retriever`] backed by a single store on the AI proxy
+#[derive(TypedBuilder)]
+pub struct AIRetriever {
+    client: AIClient,
+
+    #[builder(setter(into, transform = |s: String| StoreName(s)))]
+    store: StoreName,
+
+    #[builder(default = AIModel::AllMiniLML6V2)]
+    model: AIModel,
+
+    /// metadata keys to index for faster filtering
+    #[builder(default = HashSet::new())]
+    predicates: HashSet<MetadataKey>,
+
+    #[builder(default = Algorithm::CosineSimilarity)]
+    algorithm: Algorithm,
+
+    #[builder(default = None)]
+    tracing_id: Option<String>,
+}
+
+impl AIRetriever {
+    /// Creates the backing store if it does not exist yet. Documents are embedded and queried
+    /// with the same model
+    pub async fn create_store(&self) -> Result<(), AhnlichError> {
+        let params = ai_params::CreateStoreParams::builder()
+            .store(self.store.to_string())
+            .query_model(self.model.clone())
+            .index_model(self.model.clone())
+            .predicates(self.predicates.clone())
+            .error_if_exists(false)
+            .tracing_id(self.tracing_id.clone())
+            .build();
+        self.client.create_store(params).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Retriever for AIRetriever {
+    async fn add_documents(&self, documents: Vec<Document>) -> Result<StoreUpsert, AhnlichError> {
+        let params = ai_params::SetParams::builder()
+            .store(self.store.to_string())
+            .inputs(
+                documents
+                    .into_iter()
+                    .map(|document| (StoreInput::RawString(document.content), document.metadata))
+                    .collect(),
+            )
+            .preprocess_action(PreprocessAction::ModelPreprocessing)
+            .tracing_id(self.tracing_id.clone())
+            .build();
+        match self.client.set(params).await? {
+            AIServerResponse::Set(upsert) => Ok(upsert),
+            response => Err(AhnlichError::UnexpectedResponse(format!("{response:?}"))),
+        }
+    }
+
+    async fn similarity_search(
+        &self,
+        query: &str,
+        k: usize,
+        filter: Option<PredicateCondition>,
+    ) -> Result<Vec<(Document, Similarity)>, AhnlichError> {
+        let Some(k) = NonZeroUsize::new(k) else {
+            return Ok(vec![]);
+        };
+        let params = ai_params::GetSimNParams::builder()
+            .store(self.store.to_string())
+            .search_input(StoreInput::RawString(query.to_string()))
+            .condition(filter)
+            .closest_n(k.get())
+            .algorithm(self.algorithm)
+            .preprocess_action(PreprocessAction::ModelPreprocessing)
+            .tracing_id(self.tracing_id.clone())
+            .build();
+        let results = match self.client.get_sim_n(params).await? {
+            AIServerResponse::GetSimN(results) => results,
+            response => return Err(AhnlichError::UnexpectedResponse(format!("{response:?}"))),
+        };
+        Ok(results
+            .into_iter()
+            .filter_map(|(input, metadata, similarity)| match input {
+                // documents are always text, anything else was not added by a retriever
+                Some(StoreInput::RawString(content)) => {
+                    Some((Document { content, metadata }, similarity))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahnlich_ai_proxy::cli::AIProxyConfig;
+    use ahnlich_ai_proxy::server::handler::AIProxyServer;
+    use ahnlich_db::cli::ServerConfig;
+    use ahnlich_db::server::handler::Server;
+    use once_cell::sync::Lazy;
+    use pretty_assertions::assert_eq;
+    use std::net::SocketAddr;
+    use tokio::time::Duration;
+    use utils::server::AhnlichServerUtils;
+
+    static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
+    static AI_CONFIG: Lazy<AIProxyConfig> = Lazy::new(|| AIProxyConfig::default().os_select_port());
+
+    async fn provision_test_servers() -> SocketAddr {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let mut config = AI_CONFIG.clone();
+        config.db_port = server.local_addr().unwrap().port();
+        let ai_server = AIProxyServer::new(config)
+            .await
+            .expect("Could not initialize ai proxy");
+        let ai_address = ai_server.local_addr().expect("Could not get local addr");
+        let _ = tokio::spawn(async move { server.start().await });
+        let _ = tokio::spawn(async move { ai_server.start().await });
+        // Allow some time for the servers to start
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        ai_address
+    }
+
+    #[test]
+    fn test_match_metadata_ands_every_pair() {
+        assert_eq!(match_metadata(Vec::<(String, String)>::new()), None);
+        let equals = |key: &str, value: &str| {
+            PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new(key.to_string()),
+                value: MetadataValue::RawString(value.to_string()),
+            })
+        };
+        assert_eq!(
+            match_metadata([("brand", "Nike"), ("color", "red")]),
+            Some(equals("brand", "Nike").and(equals("color", "red")))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retriever_filters_on_metadata() {
+        let address = provision_test_servers().await;
+        let client = AIClient::new(address.ip().to_string(), address.port())
+            .await
+            .expect("Could not initialize client");
+        let retriever = AIRetriever::builder()
+            .client(client)
+            .store("Docs".to_string())
+            .build();
+        retriever.create_store().await.unwrap();
+        // creating the store again is a no-op
+        retriever.create_store().await.unwrap();
+
+        let upsert = retriever
+            .add_documents(vec![
+                Document::new("Nike Air Jordans").with_metadata("brand", "Nike"),
+                Document::new("Nike Air Max").with_metadata("brand", "Nike"),
+                Document::new("Adidas Yeezy").with_metadata("brand", "Adidas"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(upsert.inserted, 3);
+
+        let results = retriever
+            .similarity_search("Yeezy", 3, match_metadata([("brand", "Nike")]))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|(document, _)| document.content.starts_with("Nike")));
+
+        let results = retriever.similarity_search("Yeezy", 1, None).await.unwrap();
+        assert_eq!(
+            results[0].0,
+            Document::new("Adidas Yeezy").with_metadata("brand", "Adidas")
+        );
+        assert!(retriever
+            .similarity_search("Yeezy", 0, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
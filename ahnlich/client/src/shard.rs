@@ -0,0 +1,553 @@
+//! Client side sharding of a logical store over stores on several servers.
+//!
+//! A [`ShardedDbClient`] places every key on one of its shards by consistent hashing, so writes
+//! and key lookups only reach the shard holding the key while similarity searches and predicate
+//! queries are sent to every shard and their results merged. Adding or removing a shard moves
+//! only the entries whose shard changed.
+//!
+//! ```rust
+//! use ahnlich_client_rs::db::DbClient;
+//! use ahnlich_client_rs::prelude::*;
+//! use ahnlich_client_rs::shard::{Shard, ShardedDbClient};
+//! use std::collections::{HashMap, HashSet};
+//! use ndarray::array;
+//!
+//! let first = DbClient::new("127.0.0.1".into(), 1369).await.unwrap();
+//! let second = DbClient::new("127.0.0.1".into(), 1370).await.unwrap();
+//! let mut sharded = ShardedDbClient::new(vec![
+//!     Shard::new("first", first, "Main"),
+//!     Shard::new("second", second, "Main"),
+//! ]);
+//! sharded.create_store(3, HashSet::new(), HashSet::new()).await.unwrap();
+//! sharded
+//!     .set(vec![(StoreKey(array![1.0, 2.0, 3.0]), HashMap::new())])
+//!     .await
+//!     .unwrap();
+//! let closest = sharded
+//!     .get_sim_n(StoreKey(array![1.0, 2.0, 3.0]), 1, Algorithm::CosineSimilarity, None)
+//!     .await
+//!     .unwrap();
+//!
+//! let third = DbClient::new("127.0.0.1".into(), 1371).await.unwrap();
+//! let moved = sharded.add_shard(Shard::new("third", third, "Main")).await.unwrap();
+//! ```
+use crate::builders::db as db_params;
+use crate::db::DbClient;
+use crate::error::AhnlichError;
+use crate::prelude::*;
+use futures::future::try_join_all;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Points each shard takes on the hash ring unless set with
+/// [`ShardedDbClient::with_virtual_nodes`]. More points spread keys more evenly between shards
+pub const DEFAULT_VIRTUAL_NODES: usize = 64;
+
+// metadata key that no store is expected to index, so that a predicate on it scans the store
+const SCAN_KEY: &str = "__ahnlich_shard_scan__";
+
+fn unexpected(response: ServerResponse) -> AhnlichError {
+    AhnlichError::UnexpectedResponse(format!("{response:?}"))
+}
+
+/// 64 bit FNV-1a hash followed by a finalizer to spread out inputs that only differ slightly,
+/// such as the names of the virtual nodes of a shard. Unlike the std hashers it gives the same
+/// hash across processes and releases, which every client of a sharded store has to agree on
+fn hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash = bytes
+        .into_iter()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+fn key_hash(key: &StoreKey) -> u64 {
+    hash(key.0.iter().flat_map(|element| element.to_le_bytes()))
+}
+
+/// A store on a server holding a share of the entries of a sharded store
+#[derive(Debug)]
+pub struct Shard {
+    name: String,
+    client: DbClient,
+    store: StoreName,
+}
+
+impl Shard {
+    /// `name` places the shard on the hash ring so it has to stay the same across restarts and
+    /// be unique among the shards
+    pub fn new(name: impl Into<String>, client: DbClient, store: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            client,
+            store: StoreName(store.into()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn store(&self) -> &StoreName {
+        &self.store
+    }
+
+    async fn set(&self, inputs: Vec<(StoreKey, StoreValue)>) -> Result<StoreUpsert, AhnlichError> {
+        let params = db_params::SetParams::builder()
+            .store(self.store.to_string())
+            .inputs(inputs)
+            .build();
+        match self.client.set(params).await? {
+            ServerResponse::Set(upsert) => Ok(upsert),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn get_key(
+        &self,
+        keys: Vec<StoreKey>,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, AhnlichError> {
+        let params = db_params::GetKeyParams::builder()
+            .store(self.store.to_string())
+            .keys(keys)
+            .build();
+        match self.client.get_key(params).await? {
+            ServerResponse::Get(entries) => Ok(entries),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn del_key(&self, keys: Vec<StoreKey>) -> Result<usize, AhnlichError> {
+        let params = db_params::DelKeyParams::builder()
+            .store(self.store.to_string())
+            .keys(keys)
+            .build();
+        match self.client.del_key(params).await? {
+            ServerResponse::Del(deleted) => Ok(deleted),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    async fn get_pred(
+        &self,
+        condition: PredicateCondition,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, AhnlichError> {
+        let params = db_params::GetPredParams::builder()
+            .store(self.store.to_string())
+            .condition(condition)
+            .build();
+        match self.client.get_pred(params).await? {
+            ServerResponse::Get(entries) => Ok(entries),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Every entry of the store
+    async fn entries(&self) -> Result<Vec<(StoreKey, StoreValue)>, AhnlichError> {
+        self.get_pred(PredicateCondition::Value(Predicate::NotIn {
+            key: MetadataKey::new(SCAN_KEY.to_string()),
+            value: HashSet::new(),
+        }))
+        .await
+    }
+}
+
+/// Spreads a logical store over the stores of its shards by consistent hashing of keys
+#[derive(Debug)]
+pub struct ShardedDbClient {
+    shards: Vec<Shard>,
+    // points of the shards on the hash ring mapped to their position in `shards`
+    ring: BTreeMap<u64, usize>,
+    virtual_nodes: usize,
+}
+
+impl ShardedDbClient {
+    pub fn new(shards: Vec<Shard>) -> Self {
+        Self::with_virtual_nodes(shards, DEFAULT_VIRTUAL_NODES)
+    }
+
+    /// Creates a sharded client where each shard takes `virtual_nodes` points on the hash ring.
+    /// Every client of a sharded store has to use the same number of points
+    pub fn with_virtual_nodes(shards: Vec<Shard>, virtual_nodes: usize) -> Self {
+        let mut sharded = Self {
+            shards,
+            ring: BTreeMap::new(),
+            virtual_nodes: virtual_nodes.max(1),
+        };
+        sharded.build_ring();
+        sharded
+    }
+
+    pub fn shards(&self) -> &[Shard] {
+        &self.shards
+    }
+
+    fn build_ring(&mut self) {
+        self.ring = self
+            .shards
+            .iter()
+            .enumerate()
+            .flat_map(|(index, shard)| {
+                (0..self.virtual_nodes)
+                    .map(move |node| (hash(format!("{}#{node}", shard.name).into_bytes()), index))
+            })
+            .collect();
+    }
+
+    /// Position in `shards` of the shard holding a key, the first point on the ring at or after
+    /// the hash of the key
+    fn shard_index(&self, key: &StoreKey) -> Result<usize, AhnlichError> {
+        self.ring
+            .range(key_hash(key)..)
+            .chain(self.ring.iter())
+            .map(|(_, index)| *index)
+            .next()
+            .ok_or(AhnlichError::NoShards)
+    }
+
+    /// The shard holding a key
+    pub fn shard_for(&self, key: &StoreKey) -> Result<&Shard, AhnlichError> {
+        Ok(&self.shards[self.shard_index(key)?])
+    }
+
+    /// Groups items by the shard holding their key
+    fn partition<T>(
+        &self,
+        items: Vec<T>,
+        key: impl Fn(&T) -> &StoreKey,
+    ) -> Result<HashMap<usize, Vec<T>>, AhnlichError> {
+        let mut partitions: HashMap<usize, Vec<T>> = HashMap::new();
+        for item in items {
+            partitions
+                .entry(self.shard_index(key(&item))?)
+                .or_default()
+                .push(item);
+        }
+        Ok(partitions)
+    }
+
+    /// Creates the store of every shard that does not have it yet
+    pub async fn create_store(
+        &self,
+        dimension: usize,
+        create_predicates: HashSet<MetadataKey>,
+        non_linear_indices: HashSet<NonLinearAlgorithm>,
+    ) -> Result<(), AhnlichError> {
+        try_join_all(self.shards.iter().map(|shard| {
+            let params = db_params::CreateStoreParams::builder()
+                .store(shard.store.to_string())
+                .dimension(dimension)
+                .create_predicates(create_predicates.clone())
+                .non_linear_indices(non_linear_indices.clone())
+                .error_if_exists(false)
+                .build();
+            shard.client.create_store(params)
+        }))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set(
+        &self,
+        inputs: Vec<(StoreKey, StoreValue)>,
+    ) -> Result<StoreUpsert, AhnlichError> {
+        let partitions = self.partition(inputs, |(key, _)| key)?;
+        let upserts = try_join_all(
+            partitions
+                .into_iter()
+                .map(|(index, inputs)| self.shards[index].set(inputs)),
+        )
+        .await?;
+        Ok(upserts.into_iter().fold(
+            StoreUpsert {
+                inserted: 0,
+                updated: 0,
+            },
+            |total, upsert| StoreUpsert {
+                inserted: total.inserted + upsert.inserted,
+                updated: total.updated + upsert.updated,
+            },
+        ))
+    }
+
+    pub async fn get_key(
+        &self,
+        keys: Vec<StoreKey>,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, AhnlichError> {
+        let partitions = self.partition(keys, |key| key)?;
+        let entries = try_join_all(
+            partitions
+                .into_iter()
+                .map(|(index, keys)| self.shards[index].get_key(keys)),
+        )
+        .await?;
+        Ok(entries.into_iter().flatten().collect())
+    }
+
+    /// Deletes `keys`, returning how many of them were in the store
+    pub async fn del_key(&self, keys: Vec<StoreKey>) -> Result<usize, AhnlichError> {
+        let partitions = self.partition(keys, |key| key)?;
+        let deleted = try_join_all(
+            partitions
+                .into_iter()
+                .map(|(index, keys)| self.shards[index].del_key(keys)),
+        )
+        .await?;
+        Ok(deleted.into_iter().sum())
+    }
+
+    /// Returns the entries of every shard matching `condition`
+    pub async fn get_pred(
+        &self,
+        condition: PredicateCondition,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, AhnlichError> {
+        let entries = try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.get_pred(condition.clone())),
+        )
+        .await?;
+        Ok(entries.into_iter().flatten().collect())
+    }
+
+    /// Returns the `closest_n` entries to `search_input` across the shards, fetching the closest
+    /// `closest_n` of every shard and keeping the best of them
+    pub async fn get_sim_n(
+        &self,
+        search_input: StoreKey,
+        closest_n: usize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+    ) -> Result<Vec<(StoreKey, StoreValue, Similarity)>, AhnlichError> {
+        if closest_n == 0 {
+            return Ok(vec![]);
+        }
+        let results = try_join_all(self.shards.iter().map(|shard| {
+            let params = db_params::GetSimNParams::builder()
+                .store(shard.store.to_string())
+                .search_input(search_input.clone())
+                .closest_n(closest_n)
+                .algorithm(algorithm)
+                .condition(condition.clone())
+                .build();
+            async move {
+                match shard.client.get_sim_n(params).await? {
+                    ServerResponse::GetSimN(entries) => Ok(entries),
+                    response => Err(unexpected(response)),
+                }
+            }
+        }))
+        .await?;
+        let mut merged: Vec<_> = results.into_iter().flatten().collect();
+        // distances are better the smaller they are while similarities are better the larger
+        match algorithm {
+            Algorithm::EuclideanDistance | Algorithm::KDTree => {
+                merged.sort_by(|(_, _, first), (_, _, second)| first.0.total_cmp(&second.0))
+            }
+            Algorithm::CosineSimilarity | Algorithm::DotProductSimilarity => {
+                merged.sort_by(|(_, _, first), (_, _, second)| second.0.total_cmp(&first.0))
+            }
+        }
+        merged.truncate(closest_n);
+        Ok(merged)
+    }
+
+    /// Adds a shard, whose store is expected to exist, and moves the entries it now holds over
+    /// from the other shards. Returns how many entries were moved
+    pub async fn add_shard(&mut self, shard: Shard) -> Result<usize, AhnlichError> {
+        if self
+            .shards
+            .iter()
+            .any(|existing| existing.name == shard.name)
+        {
+            return Err(AhnlichError::ShardAlreadyExists(shard.name));
+        }
+        self.shards.push(shard);
+        self.build_ring();
+        let added = self.shards.len() - 1;
+        let mut moved = 0;
+        for index in 0..added {
+            let entries = self.shards[index].entries().await?;
+            let mut moving = Vec::new();
+            for entry in entries {
+                if self.shard_index(&entry.0)? == added {
+                    moving.push(entry);
+                }
+            }
+            if moving.is_empty() {
+                continue;
+            }
+            let keys = moving.iter().map(|(key, _)| key.clone()).collect();
+            moved += moving.len();
+            // entries are only removed from their old shard once the new one holds them
+            self.shards[added].set(moving).await?;
+            self.shards[index].del_key(keys).await?;
+        }
+        Ok(moved)
+    }
+
+    /// Removes a shard, moving its entries over to the shards now holding them, and returns it
+    /// along with how many entries were moved. The entries are left in the store of the removed
+    /// shard
+    pub async fn remove_shard(&mut self, name: &str) -> Result<(Shard, usize), AhnlichError> {
+        let Some(index) = self.shards.iter().position(|shard| shard.name == name) else {
+            return Err(AhnlichError::ShardNotFound(name.to_string()));
+        };
+        let entries = self.shards[index].entries().await?;
+        if self.shards.len() == 1 && !entries.is_empty() {
+            return Err(AhnlichError::NoShards);
+        }
+        let removed = self.shards.remove(index);
+        self.build_ring();
+        let moved = entries.len();
+        let moving = async {
+            let partitions = self.partition(entries, |(key, _)| key)?;
+            try_join_all(
+                partitions
+                    .into_iter()
+                    .map(|(index, entries)| self.shards[index].set(entries)),
+            )
+            .await
+        };
+        // the shard is kept when its entries could not all be moved so removing it can be retried
+        if let Err(err) = moving.await {
+            self.shards.insert(index, removed);
+            self.build_ring();
+            return Err(err);
+        }
+        Ok((removed, moved))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahnlich_db::cli::ServerConfig;
+    use ahnlich_db::server::handler::Server;
+    use ndarray::array;
+    use once_cell::sync::Lazy;
+    use pretty_assertions::assert_eq;
+    use tokio::time::Duration;
+    use utils::server::AhnlichServerUtils;
+
+    static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
+
+    async fn provision_test_server() -> DbClient {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        tokio::spawn(async { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        DbClient::new(address.ip().to_string(), address.port())
+            .await
+            .expect("Could not initialize client")
+    }
+
+    // a shard whose client never connects as the pool connects on first use
+    async fn unconnected_shard(name: &str) -> Shard {
+        Shard::new(
+            name,
+            DbClient::new("127.0.0.1".into(), 1369).await.unwrap(),
+            "Main",
+        )
+    }
+
+    fn key(i: usize) -> StoreKey {
+        StoreKey(array![i as f32, 1.0, 0.5])
+    }
+
+    #[tokio::test]
+    async fn test_consistent_hashing_moves_keys_to_new_shard_only() {
+        let two = ShardedDbClient::new(vec![
+            unconnected_shard("first").await,
+            unconnected_shard("second").await,
+        ]);
+        let three = ShardedDbClient::new(vec![
+            unconnected_shard("first").await,
+            unconnected_shard("second").await,
+            unconnected_shard("third").await,
+        ]);
+        let mut counts = [0; 3];
+        for i in 0..3000 {
+            let before = two.shard_for(&key(i)).unwrap().name();
+            let after = three.shard_for(&key(i)).unwrap().name();
+            assert!(before == after || after == "third");
+            counts[three.shard_index(&key(i)).unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 500), "{counts:?}");
+        assert!(matches!(
+            ShardedDbClient::new(vec![]).shard_for(&key(0)),
+            Err(AhnlichError::NoShards)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sharded_db_client() {
+        let mut sharded = ShardedDbClient::new(vec![
+            Shard::new("first", provision_test_server().await, "Main"),
+            Shard::new("second", provision_test_server().await, "Main"),
+        ]);
+        sharded
+            .create_store(3, HashSet::new(), HashSet::new())
+            .await
+            .unwrap();
+        let entries: Vec<_> = (0..100).map(|i| (key(i), StoreValue::new())).collect();
+        let upsert = sharded.set(entries.clone()).await.unwrap();
+        assert_eq!(upsert.inserted, 100);
+        let closest = sharded
+            .get_sim_n(key(42), 3, Algorithm::EuclideanDistance, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            closest
+                .into_iter()
+                .map(|(key, _, _)| key)
+                .collect::<Vec<_>>(),
+            vec![key(42), key(41), key(43)]
+        );
+
+        let third = Shard::new("third", provision_test_server().await, "Main");
+        let params = db_params::CreateStoreParams::builder()
+            .store("Main".to_string())
+            .dimension(3)
+            .build();
+        third.client.create_store(params).await.unwrap();
+        assert!(matches!(
+            sharded.add_shard(unconnected_shard("first").await).await,
+            Err(AhnlichError::ShardAlreadyExists(_))
+        ));
+        let moved = sharded.add_shard(third).await.unwrap();
+        assert!(moved > 0);
+        let held = sharded.shards()[2].entries().await.unwrap();
+        assert_eq!(held.len(), moved);
+        let mut found = sharded
+            .get_key(entries.iter().map(|(key, _)| key.clone()).collect())
+            .await
+            .unwrap();
+        found.sort_by(|(first, _), (second, _)| first.0[0].total_cmp(&second.0[0]));
+        assert_eq!(found, entries);
+
+        let (removed, moved) = sharded.remove_shard("first").await.unwrap();
+        assert_eq!(removed.name(), "first");
+        assert!(moved > 0);
+        assert!(matches!(
+            sharded.remove_shard("first").await,
+            Err(AhnlichError::ShardNotFound(_))
+        ));
+        assert_eq!(
+            sharded
+                .get_key(entries.iter().map(|(key, _)| key.clone()).collect())
+                .await
+                .unwrap()
+                .len(),
+            100
+        );
+        assert_eq!(sharded.del_key(vec![key(0), key(1)]).await.unwrap(), 2);
+    }
+}
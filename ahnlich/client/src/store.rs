@@ -0,0 +1,253 @@
+//! Store handles that carry the dimension of their store in their type.
+//!
+//! Keys passed to a [`StoreHandle<N>`] are `[f32; N]` arrays, so a key of the wrong dimension is
+//! a compile error rather than a dimension mismatch returned by the server. Keys only known at
+//! runtime are checked on the client with [`vector`] before they are sent.
+//!
+//! ```rust
+//! use ahnlich_client_rs::db::DbClient;
+//! use ahnlich_client_rs::store::{vector, StoreHandle};
+//! use std::collections::HashMap;
+//!
+//! let db_client = DbClient::new("127.0.0.1".into(), 1369).await.unwrap();
+//! let store: StoreHandle<'_, 3> = db_client.store("Main").await.unwrap();
+//! store.set(vec![([1.0, 2.0, 3.0], HashMap::new())]).await.unwrap();
+//! let search_input = vector::<3>(vec![1.0, 2.0, 3.0]).unwrap();
+//! let closest = store.get_sim_n(search_input, 1).await.unwrap();
+//! ```
+use crate::builders::db as db_params;
+use crate::db::DbClient;
+use crate::error::AhnlichError;
+use crate::prelude::*;
+use ndarray::Array1;
+use std::num::NonZeroUsize;
+
+/// checks that a key only known at runtime has the dimension `N`
+pub fn vector<const N: usize>(key: Vec<f32>) -> Result<[f32; N], AhnlichError> {
+    key.try_into()
+        .map_err(|key: Vec<f32>| AhnlichError::DimensionMismatch {
+            expected: N,
+            found: key.len(),
+        })
+}
+
+fn to_store_key<const N: usize>(key: [f32; N]) -> StoreKey {
+    StoreKey(Array1::from_vec(key.to_vec()))
+}
+
+fn from_store_key<const N: usize>(key: StoreKey) -> Result<[f32; N], AhnlichError> {
+    vector(key.0.to_vec())
+}
+
+fn unexpected(response: ServerResponse) -> AhnlichError {
+    AhnlichError::UnexpectedResponse(format!("{response:?}"))
+}
+
+/// Handle to a store whose keys are of dimension `N`
+#[derive(Debug)]
+pub struct StoreHandle<'a, const N: usize> {
+    client: &'a DbClient,
+    store: StoreName,
+}
+
+impl<'a, const N: usize> StoreHandle<'a, N> {
+    pub(crate) fn new(client: &'a DbClient, store: StoreName) -> Self {
+        Self { client, store }
+    }
+
+    pub fn name(&self) -> &StoreName {
+        &self.store
+    }
+
+    pub async fn set(
+        &self,
+        inputs: Vec<([f32; N], StoreValue)>,
+    ) -> Result<StoreUpsert, AhnlichError> {
+        let params = db_params::SetParams::builder()
+            .store(self.store.to_string())
+            .inputs(
+                inputs
+                    .into_iter()
+                    .map(|(key, value)| (to_store_key(key), value))
+                    .collect(),
+            )
+            .build();
+        match self.client.set(params).await? {
+            ServerResponse::Set(upsert) => Ok(upsert),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub async fn get_key(
+        &self,
+        keys: Vec<[f32; N]>,
+    ) -> Result<Vec<([f32; N], StoreValue)>, AhnlichError> {
+        let params = db_params::GetKeyParams::builder()
+            .store(self.store.to_string())
+            .keys(keys.into_iter().map(to_store_key).collect())
+            .build();
+        match self.client.get_key(params).await? {
+            ServerResponse::Get(entries) => entries
+                .into_iter()
+                .map(|(key, value)| Ok((from_store_key(key)?, value)))
+                .collect(),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Returns the `closest_n` entries to `search_input` by cosine similarity, see
+    /// [`StoreHandle::get_sim_n_with`] to set the other options of the query
+    pub async fn get_sim_n(
+        &self,
+        search_input: [f32; N],
+        closest_n: usize,
+    ) -> Result<Vec<([f32; N], StoreValue, Similarity)>, AhnlichError> {
+        self.get_sim_n_with(search_input, closest_n, Algorithm::CosineSimilarity, None)
+            .await
+    }
+
+    pub async fn get_sim_n_with(
+        &self,
+        search_input: [f32; N],
+        closest_n: usize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+    ) -> Result<Vec<([f32; N], StoreValue, Similarity)>, AhnlichError> {
+        let Some(closest_n) = NonZeroUsize::new(closest_n) else {
+            return Ok(vec![]);
+        };
+        let params = db_params::GetSimNParams::builder()
+            .store(self.store.to_string())
+            .search_input(to_store_key(search_input))
+            .closest_n(closest_n.get())
+            .algorithm(algorithm)
+            .condition(condition)
+            .build();
+        match self.client.get_sim_n(params).await? {
+            ServerResponse::GetSimN(entries) => entries
+                .into_iter()
+                .map(|(key, value, similarity)| Ok((from_store_key(key)?, value, similarity)))
+                .collect(),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Deletes `keys`, returning how many of them were in the store
+    pub async fn del_key(&self, keys: Vec<[f32; N]>) -> Result<usize, AhnlichError> {
+        let params = db_params::DelKeyParams::builder()
+            .store(self.store.to_string())
+            .keys(keys.into_iter().map(to_store_key).collect())
+            .build();
+        match self.client.del_key(params).await? {
+            ServerResponse::Del(deleted) => Ok(deleted),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+impl DbClient {
+    /// Returns a handle to an existing store after checking that its dimension is `N`
+    pub async fn store<const N: usize>(
+        &self,
+        store: impl Into<String>,
+    ) -> Result<StoreHandle<'_, N>, AhnlichError> {
+        let store = StoreName(store.into());
+        let stores = match self.list_stores(None).await? {
+            ServerResponse::StoreList(stores) => stores,
+            response => return Err(unexpected(response)),
+        };
+        let Some(info) = stores.into_iter().find(|info| info.name == store) else {
+            return Err(AhnlichError::DbError(
+                ErrorResponse::new(ErrorCode::StoreNotFound, format!("Store {store} not found"))
+                    .with_metadata("store", &store),
+            ));
+        };
+        if info.dimension.get() != N {
+            return Err(AhnlichError::DimensionMismatch {
+                expected: N,
+                found: info.dimension.get(),
+            });
+        }
+        Ok(StoreHandle::new(self, store))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahnlich_db::cli::ServerConfig;
+    use ahnlich_db::server::handler::Server;
+    use once_cell::sync::Lazy;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use tokio::time::Duration;
+    use utils::server::AhnlichServerUtils;
+
+    static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
+
+    #[test]
+    fn test_vector_checks_dimension() {
+        assert_eq!(vector::<2>(vec![1.0, 2.0]).unwrap(), [1.0, 2.0]);
+        assert!(matches!(
+            vector::<3>(vec![1.0, 2.0]),
+            Err(AhnlichError::DimensionMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_store_handle() {
+        let server = Server::new(&CONFIG)
+            .await
+            .expect("Could not initialize server");
+        let address = server.local_addr().expect("Could not get local addr");
+        tokio::spawn(async { server.start().await });
+        // Allow some time for the server to start
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let db_client = DbClient::new(address.ip().to_string(), address.port())
+            .await
+            .expect("Could not initialize client");
+        let create_store_params = db_params::CreateStoreParams::builder()
+            .store("Main".to_string())
+            .dimension(3)
+            .build();
+        db_client.create_store(create_store_params).await.unwrap();
+
+        let err = db_client.store::<3>("Other").await.unwrap_err();
+        assert!(matches!(
+            err,
+            AhnlichError::DbError(ErrorResponse {
+                code: ErrorCode::StoreNotFound,
+                ..
+            })
+        ));
+        let err = db_client.store::<2>("Main").await.unwrap_err();
+        assert!(matches!(
+            err,
+            AhnlichError::DimensionMismatch {
+                expected: 2,
+                found: 3
+            }
+        ));
+
+        let store = db_client.store::<3>("Main").await.unwrap();
+        let upsert = store
+            .set(vec![
+                ([1.0, 0.0, 0.0], HashMap::new()),
+                ([0.0, 1.0, 0.0], HashMap::new()),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(upsert.inserted, 2);
+        let closest = store.get_sim_n([0.9, 0.1, 0.0], 1).await.unwrap();
+        assert_eq!(closest.len(), 1);
+        assert_eq!(closest[0].0, [1.0, 0.0, 0.0]);
+        assert_eq!(
+            store.get_key(vec![[0.0, 1.0, 0.0]]).await.unwrap(),
+            vec![([0.0, 1.0, 0.0], HashMap::new())]
+        );
+        assert_eq!(store.del_key(vec![[0.0, 1.0, 0.0]]).await.unwrap(), 1);
+    }
+}
@@ -1,4 +1,6 @@
+use ahnlich_db::engine::search::GetSimNOptions;
 use ahnlich_db::engine::store::StoreHandler;
+use ahnlich_db::engine::store::StoreSettings;
 use ahnlich_types::keyval::StoreKey;
 use ahnlich_types::keyval::StoreName;
 use ahnlich_types::similarity::Algorithm;
@@ -39,6 +41,7 @@ fn bench_retrieval(c: &mut Criterion) {
                 NonZeroUsize::new(dimension).unwrap(),
                 vec![],
                 HashSet::new(),
+                StoreSettings::default(),
                 true,
             )
             .unwrap();
@@ -58,6 +61,7 @@ fn bench_retrieval(c: &mut Criterion) {
                         NonZeroUsize::new(50).unwrap(),
                         Algorithm::CosineSimilarity,
                         None,
+                        GetSimNOptions::default(),
                     )
                     .unwrap();
             });
@@ -82,6 +86,7 @@ fn bench_retrieval(c: &mut Criterion) {
                 NonZeroUsize::new(dimension).unwrap(),
                 vec![],
                 HashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
                 true,
             )
             .unwrap();
@@ -101,6 +106,7 @@ fn bench_retrieval(c: &mut Criterion) {
                         NonZeroUsize::new(50).unwrap(),
                         Algorithm::KDTree,
                         None,
+                        GetSimNOptions::default(),
                     )
                     .unwrap();
             });
@@ -124,6 +130,7 @@ fn bench_insertion(c: &mut Criterion) {
                 NonZeroUsize::new(dimension).unwrap(),
                 vec![],
                 HashSet::new(),
+                StoreSettings::default(),
                 true,
             )
             .unwrap();
@@ -164,6 +171,7 @@ fn bench_insertion(c: &mut Criterion) {
                 NonZeroUsize::new(dimension).unwrap(),
                 vec![],
                 HashSet::new(),
+                StoreSettings::default(),
                 true,
             )
             .unwrap();
@@ -2,13 +2,69 @@
 use super::LinearAlgorithm;
 use super::SimilarityVector;
 use ahnlich_types::keyval::StoreKey;
-use std::cmp::Reverse;
+use std::cell::OnceCell;
+use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::num::NonZeroUsize;
 
+/// Item of a heap, greatest when it is the most similar by the order of the heap. Of items
+/// scoring the same the one with the lowest key hash is the greatest when ties are ordered
+struct Ranked<'a> {
+    item: SimilarityVector<'a>,
+    lowest_first: bool,
+    /// Hashed the first time the item ties with another
+    tie_key: Option<OnceCell<[u8; 32]>>,
+}
+
+impl<'a> Ranked<'a> {
+    fn new(item: SimilarityVector<'a>, lowest_first: bool, ordered_ties: bool) -> Self {
+        Self {
+            item,
+            lowest_first,
+            tie_key: ordered_ties.then(OnceCell::new),
+        }
+    }
+
+    fn tie_key(&self) -> Option<&[u8; 32]> {
+        self.tie_key
+            .as_ref()
+            .map(|tie_key| tie_key.get_or_init(|| super::key_hash((self.item.0).0)))
+    }
+}
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_score = self.item.cmp(&other.item);
+        let by_score = if self.lowest_first {
+            by_score.reverse()
+        } else {
+            by_score
+        };
+        by_score.then_with(|| match (self.tie_key(), other.tie_key()) {
+            (Some(tie_key), Some(other_tie_key)) => other_tie_key.cmp(tie_key),
+            _ => Ordering::Equal,
+        })
+    }
+}
+
 pub(crate) struct MinHeap<'a> {
     max_capacity: NonZeroUsize,
-    heap: BinaryHeap<Reverse<SimilarityVector<'a>>>,
+    heap: BinaryHeap<Ranked<'a>>,
+    ordered_ties: bool,
 }
 
 impl<'a> MinHeap<'a> {
@@ -16,6 +72,7 @@ impl<'a> MinHeap<'a> {
         Self {
             heap: BinaryHeap::new(),
             max_capacity: capacity,
+            ordered_ties: true,
         }
     }
     #[tracing::instrument(skip_all)]
@@ -24,11 +81,11 @@ impl<'a> MinHeap<'a> {
     }
     #[tracing::instrument(skip_all)]
     pub(crate) fn push(&mut self, item: SimilarityVector<'a>) {
-        self.heap.push(Reverse(item));
+        self.heap.push(Ranked::new(item, true, self.ordered_ties));
     }
     #[tracing::instrument(skip_all)]
     pub(crate) fn pop(&mut self) -> Option<SimilarityVector<'a>> {
-        self.heap.pop().map(|popped_item| popped_item.0)
+        self.heap.pop().map(|popped_item| popped_item.item)
     }
 
     #[tracing::instrument(skip_all)]
@@ -50,7 +107,8 @@ impl<'a> MinHeap<'a> {
 
 pub(crate) struct MaxHeap<'a> {
     max_capacity: NonZeroUsize,
-    heap: BinaryHeap<SimilarityVector<'a>>,
+    heap: BinaryHeap<Ranked<'a>>,
+    ordered_ties: bool,
 }
 
 impl<'a> MaxHeap<'a> {
@@ -58,15 +116,16 @@ impl<'a> MaxHeap<'a> {
         Self {
             heap: BinaryHeap::new(),
             max_capacity: capacity,
+            ordered_ties: true,
         }
     }
     #[tracing::instrument(skip_all)]
     fn push(&mut self, item: SimilarityVector<'a>) {
-        self.heap.push(item);
+        self.heap.push(Ranked::new(item, false, self.ordered_ties));
     }
     #[tracing::instrument(skip_all)]
     pub(crate) fn pop(&mut self) -> Option<SimilarityVector<'a>> {
-        self.heap.pop()
+        self.heap.pop().map(|popped_item| popped_item.item)
     }
     #[tracing::instrument(skip_all)]
     pub(crate) fn len(&self) -> usize {
@@ -78,7 +137,7 @@ impl<'a> MaxHeap<'a> {
         let mut result: Vec<_> = Vec::with_capacity(self.max_capacity.get());
 
         loop {
-            match self.heap.pop() {
+            match self.pop() {
                 Some(value) if result.len() < self.max_capacity.get() => {
                     let vector_sim = value.0;
                     result.push((vector_sim.0.clone(), vector_sim.1));
@@ -96,11 +155,25 @@ pub(crate) enum AlgorithmHeapType<'a> {
 }
 
 impl<'a> AlgorithmHeapType<'a> {
+    /// Whether items scoring the same are popped by lowest key hash rather than in no particular
+    /// order, which saves hashing their keys. Ties are ordered unless told otherwise
+    pub(crate) fn with_ordered_ties(mut self, ordered_ties: bool) -> Self {
+        match &mut self {
+            Self::Max(h) => h.ordered_ties = ordered_ties,
+            Self::Min(h) => h.ordered_ties = ordered_ties,
+        }
+        self
+    }
+    /// Pushes an item, ranking a NaN score as the least similar there can be so that it comes
+    /// last whatever the order of the heap
     #[tracing::instrument(skip_all)]
     pub(crate) fn push(&mut self, item: SimilarityVector<'a>) {
+        let (store_key, score) = item.into();
         match self {
-            Self::Max(h) => h.push(item),
-            Self::Min(h) => h.push(item),
+            Self::Max(h) if score.is_nan() => h.push((store_key, f32::NEG_INFINITY).into()),
+            Self::Min(h) if score.is_nan() => h.push((store_key, f32::INFINITY).into()),
+            Self::Max(h) => h.push((store_key, score).into()),
+            Self::Min(h) => h.push((store_key, score).into()),
         }
     }
     #[tracing::instrument(skip_all)]
@@ -178,4 +251,46 @@ mod tests {
         assert_eq!(heap.pop(), Some((&first_vector, 4.0).into()));
         assert_eq!(heap.pop(), Some((&first_vector, 3.0).into()));
     }
+
+    #[test]
+    fn test_ties_pop_by_key_hash() {
+        let vectors: Vec<_> = (0..8)
+            .map(|i| StoreKey(ndarray::Array1::<f32>::from_elem(2, i as f32)))
+            .collect();
+        let mut by_key_hash: Vec<_> = vectors.iter().collect();
+        by_key_hash.sort_by_cached_key(|vector| super::super::key_hash(vector));
+        let capacity = NonZeroUsize::new(3).unwrap();
+        for mut heap in [
+            AlgorithmHeapType::Max(MaxHeap::new(capacity)),
+            AlgorithmHeapType::Min(MinHeap::new(capacity)),
+        ] {
+            for vector in vectors.iter().rev() {
+                heap.push((vector, 1.0).into());
+            }
+            let popped: Vec<_> = std::iter::from_fn(|| heap.pop().map(|item| (item.0).0)).collect();
+            assert_eq!(popped, by_key_hash);
+        }
+    }
+
+    #[test]
+    fn test_nan_scores_rank_last() {
+        let vector = StoreKey(ndarray::Array1::<f32>::zeros(2));
+        let capacity = NonZeroUsize::new(3).unwrap();
+        for (mut heap, expected) in [
+            (
+                AlgorithmHeapType::Max(MaxHeap::new(capacity)),
+                vec![2.0, 1.0, f32::NEG_INFINITY, f32::NEG_INFINITY],
+            ),
+            (
+                AlgorithmHeapType::Min(MinHeap::new(capacity)),
+                vec![1.0, 2.0, f32::INFINITY, f32::INFINITY],
+            ),
+        ] {
+            for score in [f32::NAN, 1.0, f32::NAN, 2.0] {
+                heap.push((&vector, score).into());
+            }
+            let scores: Vec<_> = std::iter::from_fn(|| heap.pop().map(|item| (item.0).1)).collect();
+            assert_eq!(scores, expected);
+        }
+    }
 }
@@ -2,11 +2,16 @@ mod heap;
 pub mod non_linear;
 mod similarity;
 
+use std::collections::hash_map::Entry;
+use std::collections::HashMap as StdHashMap;
+use std::hash::Hash;
 use std::num::NonZeroUsize;
 
 use ahnlich_types::keyval::StoreKey;
 use ahnlich_types::similarity::Algorithm;
+use ahnlich_types::similarity::FusionStrategy;
 use ahnlich_types::similarity::NonLinearAlgorithm;
+use ahnlich_types::similarity::RecencyBoost;
 
 use self::{heap::AlgorithmHeapType, similarity::SimilarityFunc};
 
@@ -33,6 +38,33 @@ impl From<Algorithm> for AlgorithmByType {
     }
 }
 
+impl AlgorithmByType {
+    /// Distance based algorithms treat lower scores as more similar
+    pub(crate) fn is_distance(&self) -> bool {
+        matches!(
+            self,
+            AlgorithmByType::Linear(LinearAlgorithm::EuclideanDistance)
+                | AlgorithmByType::NonLinear(NonLinearAlgorithm::KDTree)
+        )
+    }
+
+    /// Maps a raw score into the 0-1 range where 1 is the most similar so that scores are
+    /// comparable across algorithms
+    pub(crate) fn normalize_score(&self, score: f32) -> f32 {
+        match self {
+            AlgorithmByType::Linear(LinearAlgorithm::CosineSimilarity) => {
+                ((score + 1.0) / 2.0).clamp(0.0, 1.0)
+            }
+            // dot product is unbounded so squash it with a logistic function
+            AlgorithmByType::Linear(LinearAlgorithm::DotProductSimilarity) => {
+                1.0 / (1.0 + (-score).exp())
+            }
+            AlgorithmByType::Linear(LinearAlgorithm::EuclideanDistance)
+            | AlgorithmByType::NonLinear(NonLinearAlgorithm::KDTree) => 1.0 / (1.0 + score),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
 pub(crate) enum LinearAlgorithm {
     EuclideanDistance,
@@ -70,10 +102,8 @@ impl PartialOrd for SimilarityVector<'_> {
 
 impl Ord for SimilarityVector<'_> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        (self.0)
-            .1
-            .partial_cmp(&(other.0).1)
-            .unwrap_or(std::cmp::Ordering::Less)
+        // a total order keeps heaps consistent should a NaN score slip through
+        (self.0).1.total_cmp(&(other.0).1)
     }
 }
 
@@ -110,6 +140,204 @@ impl FindSimilarN for LinearAlgorithm {
     }
 }
 
+impl LinearAlgorithm {
+    /// Scores a vector given its norm along with that of the search vector. Only cosine
+    /// similarity makes use of the norms
+    fn similarity_with_norms(
+        &self,
+        similarity_function: &SimilarityFunc,
+        (search_vector, search_norm): (&StoreKey, f32),
+        (second_vector, second_norm): (&StoreKey, f32),
+    ) -> f32 {
+        match self {
+            LinearAlgorithm::CosineSimilarity => similarity::cosine_similarity_with_norms(
+                search_vector,
+                search_norm,
+                second_vector,
+                second_norm,
+            ),
+            LinearAlgorithm::EuclideanDistance | LinearAlgorithm::DotProductSimilarity => {
+                similarity_function(search_vector, second_vector)
+            }
+        }
+    }
+
+    /// Like `find_similar_n` but with the norm of every vector passed along with it so that
+    /// cosine similarity does not compute them on every search. Ties are left in no particular
+    /// order unless `ordered_ties` is set
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn find_similar_n_with_norms<'a>(
+        &self,
+        search_vector: &StoreKey,
+        search_list: impl Iterator<Item = (&'a StoreKey, f32)>,
+        n: NonZeroUsize,
+        ordered_ties: bool,
+    ) -> Vec<(StoreKey, f32)> {
+        let mut heap = AlgorithmHeapType::from((self, n)).with_ordered_ties(ordered_ties);
+        let similarity_function: SimilarityFunc = self.into();
+        let search = (search_vector, search_vector.0.dot(&search_vector.0).sqrt());
+
+        for second in search_list {
+            let similarity = self.similarity_with_norms(&similarity_function, search, second);
+            heap.push((second.0, similarity).into())
+        }
+        heap.output()
+    }
+
+    /// Ranks each group separately keeping at most `group_size` results per group and then
+    /// returns the best `n` results across all groups
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn find_similar_n_grouped<'a, G: Eq + Hash>(
+        &self,
+        search_vector: &StoreKey,
+        search_list: impl Iterator<Item = ((&'a StoreKey, f32), G)>,
+        n: NonZeroUsize,
+        group_size: NonZeroUsize,
+        ordered_ties: bool,
+    ) -> Vec<(StoreKey, f32)> {
+        let similarity_function: SimilarityFunc = self.into();
+        let mut groups: StdHashMap<G, AlgorithmHeapType> = StdHashMap::new();
+        let search = (search_vector, search_vector.0.dot(&search_vector.0).sqrt());
+
+        for ((second_vector, second_norm), group) in search_list {
+            let similarity = self.similarity_with_norms(
+                &similarity_function,
+                search,
+                (second_vector, second_norm),
+            );
+            groups
+                .entry(group)
+                .or_insert_with(|| {
+                    AlgorithmHeapType::from((self, group_size)).with_ordered_ties(ordered_ties)
+                })
+                .push((second_vector, similarity).into());
+        }
+
+        let group_results: Vec<_> = groups
+            .into_values()
+            .flat_map(|mut group_heap| group_heap.output())
+            .collect();
+        let mut heap = AlgorithmHeapType::from((self, n)).with_ordered_ties(ordered_ties);
+        for (store_key, similarity) in group_results.iter() {
+            heap.push((store_key, *similarity).into())
+        }
+        heap.output()
+    }
+}
+
+/// Keeps at most `group_size` results per group from results that are already ordered from most
+/// to least similar, stopping once `n` results have been kept
+pub(crate) fn limit_per_group<G: Eq + Hash>(
+    ranked: impl IntoIterator<Item = (StoreKey, f32)>,
+    group_of: impl Fn(&StoreKey) -> G,
+    n: NonZeroUsize,
+    group_size: NonZeroUsize,
+) -> Vec<(StoreKey, f32)> {
+    let mut group_counts: StdHashMap<G, usize> = StdHashMap::new();
+    ranked
+        .into_iter()
+        .filter(|(store_key, _)| {
+            let count = group_counts.entry(group_of(store_key)).or_default();
+            *count += 1;
+            *count <= group_size.get()
+        })
+        .take(n.get())
+        .collect()
+}
+
+/// Blends the normalized score of each result with the exponential decay of its age in seconds
+/// and orders them from the highest blended score. Results without an age get no recency
+pub(crate) fn boost_recency(
+    results: impl IntoIterator<Item = (StoreKey, f32)>,
+    age_of: impl Fn(&StoreKey) -> Option<f64>,
+    boost: RecencyBoost,
+) -> Vec<(StoreKey, f32)> {
+    let half_life = boost.half_life.get() as f64;
+    let mut boosted: Vec<_> = results
+        .into_iter()
+        .map(|(store_key, score)| {
+            // entries dated in the future count as brand new
+            let recency =
+                age_of(&store_key).map_or(0.0, |age| 0.5_f64.powf(age.max(0.0) / half_life)) as f32;
+            let score = (1.0 - boost.weight) * score + boost.weight * recency;
+            (store_key, score)
+        })
+        .collect();
+    boosted.sort_by(|(_, first), (_, second)| second.total_cmp(first));
+    boosted
+}
+
+/// Fast blake hash of a key, which orders keys the same as the key ids their entries are referred
+/// to by
+pub(crate) fn key_hash(store_key: &StoreKey) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    for element in store_key.0.iter() {
+        hasher.update(&element.to_ne_bytes());
+    }
+    *hasher.finalize().as_bytes()
+}
+
+/// Orders results scoring the same by key hash while leaving the order of differing scores as it
+/// is, so that ties come back in the same order on every search. Only tied keys are hashed
+pub(crate) fn order_ties(results: &mut [(StoreKey, f32)]) {
+    for ties in results.chunk_by_mut(|(_, first), (_, second)| first == second) {
+        if ties.len() > 1 {
+            ties.sort_by_cached_key(|(store_key, _)| key_hash(store_key));
+        }
+    }
+}
+
+/// Constant used to dampen the impact of top ranks in reciprocal rank fusion
+const RRF_K: f32 = 60.0;
+
+/// Combines complete rankings of the same candidates for several search inputs into a single
+/// ranking ordered from most to least similar
+pub(crate) fn fuse_rankings<K: Eq + Hash>(
+    rankings: Vec<Vec<(StoreKey, f32)>>,
+    key_of: impl Fn(&StoreKey) -> K,
+    strategy: FusionStrategy,
+    is_distance: bool,
+) -> Vec<(StoreKey, f32)> {
+    let num_rankings = rankings.len() as f32;
+    let mut fused: StdHashMap<K, (StoreKey, f32)> = StdHashMap::new();
+    for ranking in rankings {
+        for (rank, (store_key, score)) in ranking.into_iter().enumerate() {
+            let score = match strategy {
+                FusionStrategy::Mean => score / num_rankings,
+                FusionStrategy::Max => score,
+                FusionStrategy::ReciprocalRankFusion => 1.0 / (RRF_K + rank as f32 + 1.0),
+            };
+            match fused.entry(key_of(&store_key)) {
+                Entry::Vacant(entry) => {
+                    entry.insert((store_key, score));
+                }
+                Entry::Occupied(mut entry) => {
+                    let (_, fused_score) = entry.get_mut();
+                    *fused_score = match strategy {
+                        FusionStrategy::Max if is_distance => fused_score.min(score),
+                        FusionStrategy::Max => fused_score.max(score),
+                        FusionStrategy::Mean | FusionStrategy::ReciprocalRankFusion => {
+                            *fused_score + score
+                        }
+                    };
+                }
+            }
+        }
+    }
+    // reciprocal rank fusion scores are always higher for more similar results
+    let ascending = is_distance && strategy != FusionStrategy::ReciprocalRankFusion;
+    let mut fused: Vec<_> = fused.into_values().collect();
+    fused.sort_by(|(_, first), (_, second)| {
+        let ordering = first.total_cmp(second);
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    });
+    fused
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,4 +380,113 @@ mod tests {
 
         assert_eq!(most_similar_sentences_vec, similar_n_vecs);
     }
+
+    #[test]
+    fn test_find_similar_n_with_norms() {
+        let sentences_vectors = word_to_vector();
+        let first_vector = sentences_vectors.get(SEACH_TEXT).unwrap().to_owned();
+        let search_list: Vec<_> = SENTENCES
+            .iter()
+            .map(|sentence| {
+                let second_vector = sentences_vectors.get(*sentence).unwrap().to_owned();
+                let norm = second_vector.0.dot(&second_vector.0).sqrt();
+                (second_vector, norm)
+            })
+            .collect();
+        let n = NonZeroUsize::new(3).unwrap();
+
+        for algorithm in [
+            LinearAlgorithm::CosineSimilarity,
+            LinearAlgorithm::EuclideanDistance,
+            LinearAlgorithm::DotProductSimilarity,
+        ] {
+            let with_norms = algorithm.find_similar_n_with_norms(
+                &first_vector,
+                search_list.iter().map(|(vector, norm)| (vector, *norm)),
+                n,
+                true,
+            );
+            let without_norms = algorithm.find_similar_n(
+                &first_vector,
+                search_list.iter().map(|(vector, _)| vector),
+                false,
+                n,
+            );
+            assert_eq!(with_norms.len(), without_norms.len());
+            for ((first, first_score), (second, second_score)) in
+                with_norms.iter().zip(&without_norms)
+            {
+                assert_eq!(first, second);
+                assert!((first_score - second_score).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_boost_recency() {
+        let old = StoreKey(ndarray::array![1.0]);
+        let new = StoreKey(ndarray::array![2.0]);
+        let undated = StoreKey(ndarray::array![3.0]);
+        let results = vec![
+            (old.clone(), 0.9),
+            (new.clone(), 0.7),
+            (undated.clone(), 0.8),
+        ];
+        let age_of = |store_key: &StoreKey| match store_key.0[0] as u8 {
+            1 => Some(7200.0),
+            2 => Some(0.0),
+            _ => None,
+        };
+        let boost = RecencyBoost {
+            half_life: std::num::NonZeroU64::new(3600).unwrap(),
+            weight: 0.5,
+        };
+
+        let boosted = boost_recency(results, age_of, boost);
+        assert_eq!(boosted[0], (new, 0.85));
+        // two half lives old leaves a quarter of the recency
+        assert_eq!(boosted[1], (old, 0.575));
+        assert_eq!(boosted[2], (undated, 0.4));
+    }
+
+    #[test]
+    fn test_fuse_rankings() {
+        let first = StoreKey(ndarray::array![1.0]);
+        let second = StoreKey(ndarray::array![2.0]);
+        let third = StoreKey(ndarray::array![3.0]);
+        let rankings = vec![
+            vec![
+                (first.clone(), 0.9),
+                (second.clone(), 0.5),
+                (third.clone(), 0.1),
+            ],
+            vec![
+                (third.clone(), 0.8),
+                (second.clone(), 0.7),
+                (first.clone(), 0.0),
+            ],
+        ];
+        let key_of = |store_key: &StoreKey| store_key.0[0] as u8;
+
+        let fused = fuse_rankings(rankings.clone(), key_of, FusionStrategy::Mean, false);
+        assert_eq!(fused[0].0, second);
+        assert!((fused[0].1 - 0.6).abs() < f32::EPSILON);
+
+        let fused = fuse_rankings(rankings.clone(), key_of, FusionStrategy::Max, false);
+        assert_eq!(fused[0], (first.clone(), 0.9));
+
+        // every entry has the same average rank but being ranked first outweighs being ranked
+        // last
+        let fused = fuse_rankings(
+            rankings.clone(),
+            key_of,
+            FusionStrategy::ReciprocalRankFusion,
+            false,
+        );
+        assert_eq!(fused[2].0, second);
+
+        // lower scores are better for distance algorithms
+        let fused = fuse_rankings(rankings, key_of, FusionStrategy::Max, true);
+        assert_eq!(fused[0], (first, 0.0));
+    }
 }
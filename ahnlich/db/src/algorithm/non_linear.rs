@@ -38,6 +38,19 @@ impl NonLinearAlgorithmWithIndex {
         }
     }
 
+    /// Builds the index from the entries of a store all at once, which is faster than inserting
+    /// them one by one when the index is empty
+    #[tracing::instrument(skip_all)]
+    fn bulk_load(&self, values: &[Array1<f32>]) {
+        match self {
+            NonLinearAlgorithmWithIndex::KDTree(kdtree) => {
+                kdtree
+                    .bulk_load(values.to_vec())
+                    .expect("Impossible dimension happened during bulk load of kdtree");
+            }
+        }
+    }
+
     #[tracing::instrument(skip_all)]
     fn delete(&self, new: &[Array1<f32>]) {
         match self {
@@ -55,6 +68,42 @@ impl NonLinearAlgorithmWithIndex {
             Self::KDTree(kdtree) => kdtree.size(),
         }
     }
+
+    /// Every vector held by the index
+    #[tracing::instrument(skip_all)]
+    fn points(&self) -> Vec<Array1<f32>> {
+        match self {
+            NonLinearAlgorithmWithIndex::KDTree(kdtree) => kdtree.points(),
+        }
+    }
+
+    /// Finds the n most similar entries that are accepted by searching the whole index for
+    /// `fetch` results and dropping the rest, fetching twice as many each time too few are
+    /// accepted until all `index_len` entries have been fetched
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn find_similar_n_post_filtered(
+        &self,
+        search_vector: &StoreKey,
+        n: NonZeroUsize,
+        mut fetch: NonZeroUsize,
+        index_len: usize,
+        accept: impl Fn(&StoreKey) -> bool,
+    ) -> Vec<(StoreKey, f32)> {
+        loop {
+            let fetched = self.find_similar_n(search_vector, std::iter::empty(), true, fetch);
+            let exhausted = fetched.len() < fetch.get() || fetch.get() >= index_len;
+            let accepted: Vec<_> = fetched
+                .into_iter()
+                .filter(|(store_key, _)| accept(store_key))
+                .take(n.get())
+                .collect();
+            if accepted.len() == n.get() || exhausted {
+                return accepted;
+            }
+            fetch =
+                NonZeroUsize::new(fetch.get().saturating_mul(2).min(index_len)).unwrap_or(fetch);
+        }
+    }
 }
 
 impl FindSimilarN for NonLinearAlgorithmWithIndex {
@@ -127,7 +176,7 @@ impl NonLinearAlgorithmIndices {
         let pinned = self.algorithm_to_index.pin();
         for algo in indices {
             let with_index = NonLinearAlgorithmWithIndex::create(algo, dimension);
-            with_index.insert(values);
+            with_index.bulk_load(values);
             pinned.insert(algo, with_index);
         }
     }
@@ -154,6 +203,15 @@ impl NonLinearAlgorithmIndices {
         Ok(deleted)
     }
 
+    /// Vectors held by the index of an algorithm, None when there is no such index
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn points(&self, algorithm: &NonLinearAlgorithm) -> Option<Vec<Array1<f32>>> {
+        self.algorithm_to_index
+            .pin()
+            .get(algorithm)
+            .map(NonLinearAlgorithmWithIndex::points)
+    }
+
     /// insert new entries into the non linear algorithm indices
     #[tracing::instrument(skip_all)]
     pub(crate) fn insert(&self, new: Vec<Array1<f32>>) {
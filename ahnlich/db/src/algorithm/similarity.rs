@@ -83,6 +83,18 @@ fn cosine_similarity(first: &StoreKey, second: &StoreKey) -> f32 {
     dot_product / (mag_first * mag_second)
 }
 
+/// Cosine similarity of vectors whose magnitudes are already known, which saves computing them
+/// for every comparison
+#[tracing::instrument(skip_all)]
+pub(super) fn cosine_similarity_with_norms(
+    first: &StoreKey,
+    first_norm: f32,
+    second: &StoreKey,
+    second_norm: f32,
+) -> f32 {
+    dot_product(first, second) / (first_norm * second_norm)
+}
+
 ///
 /// ## DOT PRODUCT
 /// The dot product or scalar product is an algebraic operation that takes two equal-length
@@ -0,0 +1,204 @@
+use super::server::{validate_compaction_threshold, QuotaOverride, ServerConfig};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use utils::cli::{CommandLineConfig, ConfigError};
+use utils::filters::ClientFilter;
+use utils::limits::LimitOverride;
+
+/// Builds the configuration of a database embedded in another program, starting from the
+/// defaults of the command line and validating the settings clap would otherwise check
+///
+/// ```ignore
+/// let config = ServerConfig::builder()
+///     .port(0)
+///     .persistence(PathBuf::from("ahnlich.dat"), 60_000)
+///     .build()?;
+/// let server = Server::new(&config).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfigBuilder {
+    config: ServerConfig,
+}
+
+impl ServerConfig {
+    pub fn builder() -> ServerConfigBuilder {
+        ServerConfigBuilder::default()
+    }
+}
+
+impl ServerConfigBuilder {
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.config.common.host = host.into();
+        self
+    }
+
+    /// Port to serve clients on, 0 lets the OS pick one
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Serves the HTTP gateway on `port`, 0 lets the OS pick one
+    pub fn http_gateway(mut self, port: u16) -> Self {
+        self.config.common.enable_http_gateway = true;
+        self.config.http_port = port;
+        self
+    }
+
+    pub fn unix_socket(mut self, path: PathBuf) -> Self {
+        self.config.common.unix_socket = Some(path);
+        self
+    }
+
+    /// Persists the stores to `location` every `interval` milliseconds and loads them from it
+    /// on startup
+    pub fn persistence(mut self, location: PathBuf, interval: u64) -> Self {
+        self.config.common.enable_persistence = true;
+        self.config.common.persist_location = Some(location);
+        self.config.common.persistence_interval = interval;
+        self
+    }
+
+    pub fn allocator_size(mut self, size: usize) -> Self {
+        self.config.common.allocator_size = size;
+        self
+    }
+
+    pub fn threadpool_sizes(mut self, threadpool_size: usize, maintenance_size: usize) -> Self {
+        self.config.common.threadpool_size = threadpool_size;
+        self.config.maintenance_threadpool_size = maintenance_size;
+        self
+    }
+
+    pub fn maximum_clients(mut self, maximum_clients: usize) -> Self {
+        self.config.common.maximum_clients = maximum_clients;
+        self
+    }
+
+    pub fn maximum_clients_per_host(mut self, maximum_clients_per_host: usize) -> Self {
+        self.config.common.maximum_clients_per_host = Some(maximum_clients_per_host);
+        self
+    }
+
+    pub fn message_size(mut self, message_size: usize) -> Self {
+        self.config.common.message_size = message_size;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.config.common.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn client_limit(mut self, limit: LimitOverride) -> Self {
+        self.config.common.client_limits.push(limit);
+        self
+    }
+
+    pub fn store_limit(mut self, limit: LimitOverride) -> Self {
+        self.config.common.store_limits.push(limit);
+        self
+    }
+
+    pub fn client_filter(mut self, filter: ClientFilter) -> Self {
+        self.config.common.client_filters.push(filter);
+        self
+    }
+
+    pub fn log_level(mut self, log_level: impl Into<String>) -> Self {
+        self.config.common.log_level = log_level.into();
+        self
+    }
+
+    pub fn compaction(mut self, threshold: f32, interval: u64) -> Self {
+        self.config.compaction_threshold = Some(threshold);
+        self.config.compaction_interval = interval;
+        self
+    }
+
+    pub fn trash_retention(mut self, retention: u64) -> Self {
+        self.config.trash_retention = Some(retention);
+        self
+    }
+
+    pub fn vector_storage(mut self, location: PathBuf, cache_size: usize) -> Self {
+        self.config.vector_storage_location = Some(location);
+        self.config.vector_cache_size = cache_size;
+        self
+    }
+
+    pub fn namespace_quota(mut self, quota: QuotaOverride) -> Self {
+        self.config.namespace_quotas.push(quota);
+        self
+    }
+
+    pub fn mirror(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.config.mirror_host = Some(host.into());
+        self.config.mirror_port = port;
+        self
+    }
+
+    pub fn mirror_source(mut self, source: IpAddr) -> Self {
+        self.config.mirror_source = Some(source);
+        self
+    }
+
+    pub fn memory_limits(
+        mut self,
+        max_request_memory: Option<usize>,
+        max_in_flight_memory: Option<usize>,
+    ) -> Self {
+        self.config.max_request_memory = max_request_memory;
+        self.config.max_in_flight_memory = max_in_flight_memory;
+        self
+    }
+
+    /// Serves the entries of stores as Arrow record batches over the HTTP gateway
+    pub fn arrow_export(mut self) -> Self {
+        self.config.enable_arrow_export = true;
+        self
+    }
+
+    pub fn export_location(mut self, location: PathBuf) -> Self {
+        self.config.export_location = Some(location);
+        self
+    }
+
+    pub fn manifest(mut self, path: PathBuf) -> Self {
+        self.config.manifest = Some(path);
+        self
+    }
+
+    /// Replaces the settings shared with the AI proxy, for those without a method of their own
+    pub fn common(mut self, common: CommandLineConfig) -> Self {
+        self.config.common = common;
+        self
+    }
+
+    pub fn build(self) -> Result<ServerConfig, ConfigError> {
+        let config = self.config;
+        config.common.validate()?;
+        if let Some(threshold) = config.compaction_threshold {
+            validate_compaction_threshold(&threshold.to_string())
+                .map_err(|err| ConfigError::invalid("compaction_threshold", err))?;
+        }
+        for (setting, value) in [
+            (
+                "maintenance_threadpool_size",
+                config.maintenance_threadpool_size,
+            ),
+            ("mirror_batch_size", config.mirror_batch_size),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::invalid(setting, "it must be above 0"));
+            }
+        }
+        if config.enable_arrow_export && !config.common.enable_http_gateway {
+            return Err(ConfigError::Missing {
+                setting: "enable_arrow_export",
+                requires: "enable_http_gateway",
+            });
+        }
+        Ok(config)
+    }
+}
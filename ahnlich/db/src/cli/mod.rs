@@ -1,3 +1,5 @@
+mod builder;
 pub mod server;
 
+pub use builder::ServerConfigBuilder;
 pub use server::{Cli, Commands, ServerConfig};
@@ -1,5 +1,10 @@
+use ahnlich_types::db::NamespaceQuota;
 use clap::{Args, Parser, Subcommand};
-use utils::cli::CommandLineConfig;
+use std::net::IpAddr;
+use std::str::FromStr;
+use utils::cli::{CommandLineConfig, VerifyBackupArgs};
+use utils::filters::ClientFilter;
+use utils::limits::LimitOverride;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -8,16 +13,91 @@ pub struct Cli {
     pub command: Commands,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     /// Starts Anhlich database
     Run(ServerConfig),
+
+    /// Verifies the checksums of a persisted snapshot and that it loads, without starting
+    /// the database
+    VerifyBackup(VerifyBackupArgs),
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct ServerConfig {
     #[arg(long, default_value_t = 1369)]
     pub port: u16,
+    /// Port of the HTTP gateway, only used with `enable_http_gateway`
+    #[arg(long, default_value_t = 1379)]
+    pub http_port: u16,
+    /// Compacts a store in the background once this fraction of the entries it held since it was
+    /// created or last compacted have been deleted, e.g 0.5
+    #[arg(long, value_parser = validate_compaction_threshold)]
+    pub compaction_threshold: Option<f32>,
+    /// How often in milliseconds stores are checked against `compaction_threshold`
+    #[arg(long, default_value_t = 60_000)]
+    pub compaction_interval: u64,
+    /// Seconds a dropped store is kept in the trash, from where it can be restored, before it is
+    /// purged. Dropped stores are purged right away when not set
+    #[arg(long)]
+    pub trash_retention: Option<u64>,
+    /// Directory holding the vectors of stores created with the disk storage tier. Disk tier
+    /// stores cannot be created without it
+    #[arg(long)]
+    pub vector_storage_location: Option<std::path::PathBuf>,
+    /// Bytes of vectors each disk tier store caches in memory
+    #[arg(long, default_value_t = 64 * 1024 * 1024)]
+    pub vector_cache_size: usize,
+    /// Caps what the stores of a namespace, the part of store names before the first `/`, hold
+    /// together. Any of the caps can be left out, e.g
+    /// --namespace-quota acme=stores:10,vectors:1000000,memory:1073741824
+    #[arg(
+        long = "namespace-quota",
+        value_name = "NAMESPACE=[stores:N][,vectors:N][,memory:BYTES]"
+    )]
+    pub namespace_quotas: Vec<QuotaOverride>,
+    /// Host of a remote ahnlich db that writes are mirrored to in the background. The mirror is
+    /// resynced from the stores of this server on startup
+    #[arg(long)]
+    pub mirror_host: Option<String>,
+    /// Port of the mirror, only used with `mirror_host`
+    #[arg(long, default_value_t = 1369)]
+    pub mirror_port: u16,
+    /// Writes sent to the mirror at once, also the entries per set when a store is copied over.
+    /// Both have to fit within the message size limit the mirror allows this server
+    #[arg(long, default_value_t = 100)]
+    pub mirror_batch_size: usize,
+    /// Writes that can wait to be sent to the mirror, past which the mirror is resynced instead
+    #[arg(long, default_value_t = 100_000)]
+    pub mirror_log_size: usize,
+    /// Makes this server a read only mirror that only accepts writes from this address
+    #[arg(long)]
+    pub mirror_source: Option<IpAddr>,
+    /// Rejects sets, gets and similarity searches estimated to need more than this many bytes
+    #[arg(long)]
+    pub max_request_memory: Option<usize>,
+    /// Rejects requests that would take the estimated bytes of the requests being processed
+    /// past this, until enough of them finish
+    #[arg(long)]
+    pub max_in_flight_memory: Option<usize>,
+    /// Threads compactions, index builds and other maintenance run on, apart from the
+    /// `threadpool_size` threads queries run on so that maintenance does not hold up queries
+    #[arg(long, default_value_t = 4)]
+    pub maintenance_threadpool_size: usize,
+    /// Serves the entries of stores as Arrow record batches at `POST /stores/{store}/export` of
+    /// the HTTP gateway, only used with `enable_http_gateway`. Not served when `client_filters`
+    /// are set, as exports are not held to them
+    #[arg(long)]
+    pub enable_arrow_export: bool,
+    /// Directory stores are written to by EXPORTSTOREPARQUET, which is refused when not set
+    #[arg(long)]
+    pub export_location: Option<std::path::PathBuf>,
+    /// TOML file declaring stores under `[[stores]]`, which are created or have their indices
+    /// brought in line with it on startup as APPLYMANIFEST would. The server does not start when
+    /// the manifest conflicts with a store it holds
+    #[arg(long)]
+    pub manifest: Option<std::path::PathBuf>,
     #[clap(flatten)]
     pub common: CommandLineConfig,
 }
@@ -26,6 +106,24 @@ impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             port: 1369,
+            http_port: 1379,
+            compaction_threshold: None,
+            compaction_interval: 60_000,
+            trash_retention: None,
+            vector_storage_location: None,
+            vector_cache_size: 64 * 1024 * 1024,
+            namespace_quotas: vec![],
+            mirror_host: None,
+            mirror_port: 1369,
+            mirror_batch_size: 100,
+            mirror_log_size: 100_000,
+            mirror_source: None,
+            max_request_memory: None,
+            max_in_flight_memory: None,
+            maintenance_threadpool_size: 4,
+            enable_arrow_export: false,
+            export_location: None,
+            manifest: None,
             common: CommandLineConfig::default(),
         }
     }
@@ -35,6 +133,27 @@ impl ServerConfig {
     pub fn os_select_port(mut self) -> Self {
         // allow OS to pick a port
         self.port = 0;
+        self.http_port = 0;
+        self
+    }
+
+    pub fn enable_http_gateway(mut self) -> Self {
+        self.common.enable_http_gateway = true;
+        self
+    }
+
+    pub fn enable_arrow_export(mut self) -> Self {
+        self.enable_arrow_export = true;
+        self
+    }
+
+    pub fn export_location(mut self, location: std::path::PathBuf) -> Self {
+        self.export_location = Some(location);
+        self
+    }
+
+    pub fn manifest(mut self, path: std::path::PathBuf) -> Self {
+        self.manifest = Some(path);
         self
     }
 
@@ -43,14 +162,163 @@ impl ServerConfig {
         self
     }
 
+    pub fn audit_log(mut self, location: std::path::PathBuf) -> Self {
+        self.common.audit_log = Some(location);
+        self
+    }
+
     pub fn persistence_interval(mut self, interval: u64) -> Self {
         self.common.enable_persistence = true;
         self.common.persistence_interval = interval;
         self
     }
 
+    pub fn compaction_threshold(mut self, threshold: f32, interval: u64) -> Self {
+        self.compaction_threshold = Some(threshold);
+        self.compaction_interval = interval;
+        self
+    }
+
+    pub fn trash_retention(mut self, retention: u64) -> Self {
+        self.trash_retention = Some(retention);
+        self
+    }
+
+    pub fn vector_storage(mut self, location: std::path::PathBuf, cache_size: usize) -> Self {
+        self.vector_storage_location = Some(location);
+        self.vector_cache_size = cache_size;
+        self
+    }
+
+    pub fn namespace_quota(mut self, quota: QuotaOverride) -> Self {
+        self.namespace_quotas.push(quota);
+        self
+    }
+
+    pub fn mirror(mut self, host: String, port: u16) -> Self {
+        self.mirror_host = Some(host);
+        self.mirror_port = port;
+        self
+    }
+
+    pub fn mirror_source(mut self, source: IpAddr) -> Self {
+        self.mirror_source = Some(source);
+        self
+    }
+
+    pub fn memory_limits(
+        mut self,
+        max_request_memory: Option<usize>,
+        max_in_flight_memory: Option<usize>,
+    ) -> Self {
+        self.max_request_memory = max_request_memory;
+        self.max_in_flight_memory = max_in_flight_memory;
+        self
+    }
+
+    pub fn max_concurrent_requests(mut self, limit: usize, priority_aging: u64) -> Self {
+        self.common.max_concurrent_requests = Some(limit);
+        self.common.priority_aging = priority_aging;
+        self
+    }
+
+    pub fn threadpool_sizes(mut self, threadpool_size: usize, maintenance_size: usize) -> Self {
+        self.common.threadpool_size = threadpool_size;
+        self.maintenance_threadpool_size = maintenance_size;
+        self
+    }
+
     pub fn maximum_clients(mut self, maximum_clients: usize) -> Self {
         self.common.maximum_clients = maximum_clients;
         self
     }
+
+    pub fn maximum_clients_per_host(mut self, maximum_clients_per_host: usize) -> Self {
+        self.common.maximum_clients_per_host = Some(maximum_clients_per_host);
+        self
+    }
+
+    pub fn unix_socket(mut self, unix_socket: std::path::PathBuf) -> Self {
+        self.common.unix_socket = Some(unix_socket);
+        self
+    }
+
+    pub fn config_file(mut self, config_file: std::path::PathBuf) -> Self {
+        self.common.config_file = Some(config_file);
+        self
+    }
+
+    pub fn job_ttl(mut self, job_ttl: u64) -> Self {
+        self.common.job_ttl = job_ttl;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.common.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn client_limit(mut self, limit: LimitOverride) -> Self {
+        self.common.client_limits.push(limit);
+        self
+    }
+
+    pub fn store_limit(mut self, limit: LimitOverride) -> Self {
+        self.common.store_limits.push(limit);
+        self
+    }
+
+    pub fn client_filter(mut self, filter: ClientFilter) -> Self {
+        self.common.client_filters.push(filter);
+        self
+    }
+}
+
+/// Quota of a single namespace, parsed from `NAMESPACE=[stores:N][,vectors:N][,memory:BYTES]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaOverride {
+    pub namespace: String,
+    pub quota: NamespaceQuota,
+}
+
+impl FromStr for QuotaOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (namespace, caps) = s.split_once('=').ok_or_else(|| {
+            format!("Expected NAMESPACE=[stores:N][,vectors:N][,memory:BYTES], got {s}")
+        })?;
+        if namespace.is_empty() {
+            return Err(format!("Missing namespace in quota {s}"));
+        }
+        let mut quota = NamespaceQuota::default();
+        for cap in caps.split(',').filter(|cap| !cap.trim().is_empty()) {
+            let (resource, limit) = cap
+                .split_once(':')
+                .ok_or_else(|| format!("Expected RESOURCE:N in quota {s}, got {cap}"))?;
+            let limit = limit
+                .trim()
+                .parse()
+                .map_err(|err| format!("Invalid {resource} in quota {s}: {err}"))?;
+            match resource.trim() {
+                "stores" => quota.max_stores = Some(limit),
+                "vectors" => quota.max_vectors = Some(limit),
+                "memory" => quota.max_memory_bytes = Some(limit),
+                other => return Err(format!("Unknown resource {other} in quota {s}")),
+            }
+        }
+        Ok(Self {
+            namespace: namespace.to_string(),
+            quota,
+        })
+    }
+}
+
+pub(crate) fn validate_compaction_threshold(val: &str) -> Result<f32, String> {
+    let threshold: f32 = val.parse::<f32>().map_err(|err| err.to_string())?;
+    if threshold > 0.0 && threshold <= 1.0 {
+        Ok(threshold)
+    } else {
+        Err("Compaction threshold must be above 0 and at most 1".to_string())
+    }
 }
@@ -0,0 +1,152 @@
+use crate::errors::ServerError;
+use ahnlich_types::db::{SearchPath, SearchPathBenchmark};
+use ahnlich_types::keyval::StoreKey;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+use utils::deadline::Deadline;
+
+/// Longest a benchmark searches each path of a store for
+pub(crate) const MAX_BENCHMARK_DURATION: Duration = Duration::from_secs(60);
+
+/// Most searches a benchmark runs at a time
+pub(crate) const MAX_BENCHMARK_CONCURRENCY: usize = 64;
+
+/// Searches through one path of a store for `duration`, with `concurrency` threads searching
+/// one after the other with the search inputs in turn. Searches run on threads of their own
+/// rather than the query threadpool so that they contend for the store like separate clients
+pub(super) fn benchmark_path<S>(
+    path: SearchPath,
+    search_inputs: &[StoreKey],
+    concurrency: NonZeroUsize,
+    duration: Duration,
+    deadline: Deadline,
+    search: S,
+) -> Result<SearchPathBenchmark, ServerError>
+where
+    S: Fn(&StoreKey) -> Result<(), ServerError> + Sync,
+{
+    let started = Instant::now();
+    let latencies = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..concurrency.get())
+            .map(|worker| {
+                let search = &search;
+                scope.spawn(move || {
+                    let mut latencies = Vec::new();
+                    // workers start from different inputs so that they do not all search with
+                    // the same input at once
+                    for search_input in search_inputs.iter().cycle().skip(worker) {
+                        if started.elapsed() >= duration || deadline.expired() {
+                            break;
+                        }
+                        let searched = Instant::now();
+                        search(search_input)?;
+                        latencies.push(searched.elapsed());
+                    }
+                    Ok(latencies)
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Benchmark search panicked"))
+            .collect::<Result<Vec<_>, ServerError>>()
+    })?;
+    let elapsed = started.elapsed();
+    deadline.check()?;
+    let mut latencies: Vec<_> = latencies.into_iter().flatten().collect();
+    latencies.sort_unstable();
+    Ok(summarize(path, &latencies, elapsed))
+}
+
+/// Throughput and nearest rank percentiles of sorted latencies
+fn summarize(path: SearchPath, latencies: &[Duration], elapsed: Duration) -> SearchPathBenchmark {
+    let percentile = |percent: usize| {
+        let rank = (latencies.len() * percent).div_ceil(100);
+        latencies
+            .get(rank.saturating_sub(1))
+            .map_or(0, |latency| latency.as_micros() as u64)
+    };
+    let queries_per_second = if elapsed.is_zero() {
+        0
+    } else {
+        (latencies.len() as f64 / elapsed.as_secs_f64()).round() as u64
+    };
+    SearchPathBenchmark {
+        path,
+        queries: latencies.len(),
+        queries_per_second,
+        p50_latency: percentile(50),
+        p90_latency: percentile(90),
+        p99_latency: percentile(99),
+        max_latency: percentile(100),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_summarize_percentiles() {
+        let latencies: Vec<_> = (1..=200).map(Duration::from_micros).collect();
+        let summary = summarize(SearchPath::Linear, &latencies, Duration::from_secs(2));
+        assert_eq!(
+            summary,
+            SearchPathBenchmark {
+                path: SearchPath::Linear,
+                queries: 200,
+                queries_per_second: 100,
+                p50_latency: 100,
+                p90_latency: 180,
+                p99_latency: 198,
+                max_latency: 200,
+            }
+        );
+        let empty = summarize(SearchPath::Linear, &[], Duration::ZERO);
+        assert_eq!((empty.queries, empty.max_latency), (0, 0));
+    }
+
+    #[test]
+    fn test_benchmark_path() {
+        let search_inputs = vec![StoreKey(array![1.0]), StoreKey(array![2.0])];
+        let searches = AtomicUsize::new(0);
+        let summary = benchmark_path(
+            SearchPath::Linear,
+            &search_inputs,
+            NonZeroUsize::new(3).unwrap(),
+            Duration::from_millis(50),
+            Deadline::default(),
+            |_| {
+                searches.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert!(summary.queries > 0);
+        assert_eq!(summary.queries, searches.load(Ordering::Relaxed));
+        assert!(summary.p50_latency <= summary.max_latency);
+
+        let failed = benchmark_path(
+            SearchPath::Linear,
+            &search_inputs,
+            NonZeroUsize::MIN,
+            Duration::from_millis(50),
+            Deadline::default(),
+            |_| Err(ServerError::DeadlineExceeded),
+        );
+        assert_eq!(failed, Err(ServerError::DeadlineExceeded));
+        // nothing to search with
+        let idle = benchmark_path(
+            SearchPath::Linear,
+            &[],
+            NonZeroUsize::MIN,
+            Duration::from_secs(60),
+            Deadline::default(),
+            |_| Ok(()),
+        )
+        .unwrap();
+        assert_eq!(idle.queries, 0);
+    }
+}
@@ -0,0 +1,46 @@
+use super::store::StoreHandler;
+use std::sync::Arc;
+use std::time::Duration;
+use task_manager::Task;
+use task_manager::TaskState;
+use tokio::time::sleep;
+
+/// Compacts every store where the fraction of entries deleted since it was created or last
+/// compacted has reached the threshold, checking the stores once every interval
+#[derive(Debug)]
+pub(crate) struct CompactionTask {
+    store_handler: Arc<StoreHandler>,
+    threshold: f32,
+    interval: Duration,
+}
+
+impl CompactionTask {
+    pub(crate) fn new(store_handler: Arc<StoreHandler>, threshold: f32, interval: u64) -> Self {
+        Self {
+            store_handler,
+            threshold,
+            interval: Duration::from_millis(interval),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for CompactionTask {
+    fn task_name(&self) -> String {
+        "db-compaction".to_string()
+    }
+
+    async fn run(&self) -> TaskState {
+        sleep(self.interval).await;
+        for store_name in self.store_handler.fragmented_stores(self.threshold) {
+            match self.store_handler.compact_store(&store_name) {
+                Ok(compaction) => log::info!(
+                    "Compacted store {store_name}, reclaimed {} bytes",
+                    compaction.reclaimed_bytes
+                ),
+                Err(e) => log::error!("Could not compact store {store_name}: {e}"),
+            }
+        }
+        TaskState::Continue
+    }
+}
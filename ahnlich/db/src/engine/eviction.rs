@@ -0,0 +1,364 @@
+use super::predicate;
+use super::store::{entry_memory, Entry, Store, StoreKeyId};
+use super::vectors::{self, DiskVectors};
+use ahnlich_types::keyval::EvictionPolicy;
+use ahnlich_types::keyval::StoreEviction;
+use ahnlich_types::keyval::StoreKey;
+use ahnlich_types::keyval::StoreName;
+use ahnlich_types::metadata::MetadataKey;
+use std::collections::HashSet as StdHashSet;
+use std::mem::size_of_val;
+use std::sync::atomic::Ordering;
+
+/// When an entry of a bounded store was last written or read, as a tick of the access clock of
+/// the store, and how many times it was
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct EntryAccess {
+    last: u64,
+    uses: u64,
+}
+
+impl EntryAccess {
+    /// Entries of the lowest rank are evicted first
+    fn rank(&self, policy: EvictionPolicy) -> (u64, u64) {
+        match policy {
+            EvictionPolicy::LRU => (self.last, self.uses),
+            EvictionPolicy::LFU => (self.uses, self.last),
+        }
+    }
+}
+
+impl Store {
+    /// Records that entries of a bounded store were written or read. Concurrent uses of an entry
+    /// may be counted once as the count is not updated atomically
+    pub(super) fn touch(&self, key_ids: impl IntoIterator<Item = StoreKeyId>) {
+        if self.eviction.is_none() {
+            return;
+        }
+        let access = self.access.pin();
+        for key_id in key_ids {
+            let uses = access
+                .get(&key_id)
+                .map(|entry_access| entry_access.uses)
+                .unwrap_or_default();
+            let entry_access = EntryAccess {
+                last: self.access_clock.fetch_add(1, Ordering::SeqCst) + 1,
+                uses: uses + 1,
+            };
+            access.insert(key_id, entry_access);
+        }
+    }
+
+    /// Records that entries of a bounded store were read
+    pub(super) fn touch_read<'a>(&self, store_keys: impl Iterator<Item = &'a StoreKey>) {
+        if self.eviction.is_some() {
+            self.touch(store_keys.map(StoreKeyId::from));
+        }
+    }
+
+    /// Evicts the entries of a bounded store past its bounds in the order of its policy,
+    /// returning how many were evicted
+    #[tracing::instrument(skip(self))]
+    pub(super) fn evict(&self, store_name: &StoreName) -> usize {
+        let Some(eviction) = self.eviction else {
+            return 0;
+        };
+        let mut evicted = 0;
+        loop {
+            let excess = self.excess(&eviction);
+            if excess == 0 {
+                break;
+            }
+            let mut candidates: Vec<_> = {
+                let access = self.access.pin();
+                self.id_to_value
+                    .pin()
+                    .keys()
+                    .map(|key_id| {
+                        let entry_access = access.get(key_id).copied().unwrap_or_default();
+                        (entry_access.rank(eviction.policy), key_id.clone())
+                    })
+                    .collect()
+            };
+            if excess < candidates.len() {
+                candidates.select_nth_unstable(excess);
+                candidates.truncate(excess);
+            }
+            evicted += self.delete(candidates.into_iter().map(|(_, key_id)| key_id));
+        }
+        if evicted > 0 {
+            self.evicted.fetch_add(evicted as u64, Ordering::SeqCst);
+            log::debug!("Evicted {evicted} entries from {store_name}");
+        }
+        evicted
+    }
+
+    /// Number of entries a bounded store holds past its bounds. Entries are taken to hold an
+    /// equal share of the bytes of the store, which may take another eviction to get under
+    /// `max_bytes`
+    fn excess(&self, eviction: &StoreEviction) -> usize {
+        let len = self.len();
+        let mut excess = eviction
+            .max_entries
+            .map(|max_entries| len.saturating_sub(max_entries.get()))
+            .unwrap_or_default();
+        if let Some(max_bytes) = eviction.max_bytes {
+            let bytes = self.bytes();
+            if bytes > max_bytes.get() {
+                let entry_bytes = bytes.div_ceil(len.max(1));
+                excess = excess.max((bytes - max_bytes.get()).div_ceil(entry_bytes).max(1));
+            }
+        }
+        excess.min(len)
+    }
+
+    /// Predicates indexed by a store bounded by bytes, which the bytes of its entries are
+    /// counted with. None for other stores, which do not count them
+    pub(super) fn byte_counted_predicates(&self) -> Option<StdHashSet<MetadataKey>> {
+        self.eviction
+            .is_some_and(|eviction| eviction.max_bytes.is_some())
+            .then(|| self.predicate_indices.current_predicates())
+    }
+
+    /// Estimated bytes an entry adds to the store, its vector, key id and metadata along with the
+    /// values of it held by the predicate indices. Values shared by entries are counted for each
+    pub(super) fn entry_bytes(
+        &self,
+        key_id: &StoreKeyId,
+        entry: &Entry,
+        predicates: &StdHashSet<MetadataKey>,
+    ) -> usize {
+        let vector = match self.disk_vectors {
+            Some(_) => 0,
+            None => self.dimension.get() * vectors::element_size(self.key_element_type),
+        };
+        let indexed: usize = entry
+            .value
+            .iter()
+            .filter(|(key, _)| predicates.contains(key))
+            .map(|(_, value)| key_id.memory() + predicate::value_memory(value))
+            .sum();
+        vector + entry_memory(key_id, entry) + indexed
+    }
+
+    /// Counts the bytes of the entries of a store bounded by bytes afresh, once it is loaded or
+    /// its predicate indices change
+    pub(super) fn recount_bytes(&self) {
+        let Some(predicates) = self.byte_counted_predicates() else {
+            return;
+        };
+        let bytes = self
+            .id_to_value
+            .pin()
+            .iter()
+            .map(|(key_id, entry)| self.entry_bytes(key_id, entry, &predicates))
+            .sum();
+        self.bytes.store(bytes, Ordering::SeqCst);
+    }
+
+    /// Estimated bytes of a store bounded by bytes, the counted bytes of its entries along with
+    /// its page cache, the points of its non linear indices and the keys of its predicates
+    fn bytes(&self) -> usize {
+        let predicates: usize = self
+            .predicate_indices
+            .current_predicates()
+            .iter()
+            .map(|key| size_of_val(key) + key.to_string().len())
+            .sum();
+        let non_linear_points = self.non_linear_indices.current_keys().len()
+            * self.len()
+            * self.dimension.get()
+            * size_of::<f32>();
+        self.bytes.load(Ordering::SeqCst)
+            + self
+                .disk_vectors
+                .as_ref()
+                .map(DiskVectors::cache_size_in_bytes)
+                .unwrap_or_default()
+            + non_linear_points
+            + predicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::{StoreHandler, StoreSettings};
+    use super::*;
+    use crate::errors::ServerError;
+    use ahnlich_types::metadata::MetadataValue;
+    use itertools::Itertools;
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap as StdHashMap;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use utils::cli::CommandLineConfig;
+    use utils::limits::LimitHandler;
+
+    #[test]
+    fn test_eviction() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let create = |name: &str, eviction: StoreEviction| {
+            handler.create_store(
+                StoreName(name.into()),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings {
+                    eviction: Some(eviction),
+                    ..Default::default()
+                },
+                true,
+            )
+        };
+        let set = |name: &str, keys: &[f32]| {
+            let entries = keys
+                .iter()
+                .map(|key| (StoreKey(array![*key, 1.0]), StdHashMap::new()))
+                .collect();
+            handler
+                .set_in_store(&StoreName(name.into()), entries)
+                .unwrap();
+        };
+        let get = |name: &str, key: f32| {
+            handler
+                .get_key_in_store(&StoreName(name.into()), vec![StoreKey(array![key, 1.0])])
+                .unwrap()
+                .len()
+        };
+        let held = |name: &str| -> Vec<f32> {
+            handler
+                .get(&StoreName(name.into()))
+                .unwrap()
+                .get_all()
+                .into_iter()
+                .map(|(store_key, _)| store_key.0[0])
+                .sorted_by(f32::total_cmp)
+                .collect()
+        };
+
+        // the least recently written or read entry is evicted first
+        create(
+            "LRU",
+            StoreEviction {
+                max_entries: NonZeroUsize::new(3),
+                max_bytes: None,
+                policy: EvictionPolicy::LRU,
+            },
+        )
+        .unwrap();
+        set("LRU", &[1.0, 2.0, 3.0]);
+        assert_eq!(get("LRU", 1.0), 1);
+        set("LRU", &[4.0]);
+        assert_eq!(held("LRU"), vec![1.0, 3.0, 4.0]);
+        set("LRU", &[5.0, 6.0]);
+        assert_eq!(held("LRU"), vec![4.0, 5.0, 6.0]);
+        let description = handler
+            .describe_store(
+                &StoreName("LRU".into()),
+                &LimitHandler::new(&CommandLineConfig::default()),
+            )
+            .unwrap();
+        assert_eq!(description.evicted, 3);
+
+        // the least frequently used entry is evicted first, the least recently used among them
+        create(
+            "LFU",
+            StoreEviction {
+                max_entries: NonZeroUsize::new(2),
+                max_bytes: None,
+                policy: EvictionPolicy::LFU,
+            },
+        )
+        .unwrap();
+        set("LFU", &[1.0, 2.0]);
+        assert_eq!(get("LFU", 1.0), 1);
+        assert_eq!(get("LFU", 1.0), 1);
+        set("LFU", &[3.0]);
+        assert_eq!(held("LFU"), vec![1.0, 3.0]);
+
+        // stores bounded by bytes are kept within their estimated size
+        let max_bytes = 4096;
+        create(
+            "Bytes",
+            StoreEviction {
+                max_entries: None,
+                max_bytes: NonZeroUsize::new(max_bytes),
+                policy: EvictionPolicy::LRU,
+            },
+        )
+        .unwrap();
+        set("Bytes", &(0..100).map(|key| key as f32).collect::<Vec<_>>());
+        let store = handler.get(&StoreName("Bytes".into())).unwrap();
+        assert!(store.len() < 100);
+        assert!(store.memory(StoreName("Bytes".into())).total() <= max_bytes);
+
+        assert_eq!(
+            create(
+                "Unbounded",
+                StoreEviction {
+                    max_entries: None,
+                    max_bytes: None,
+                    policy: EvictionPolicy::LFU,
+                },
+            ),
+            Err(ServerError::UnboundedEviction(StoreName(
+                "Unbounded".into()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_eviction_by_bytes() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Bytes".into());
+        let max_bytes = 8192;
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![MetadataKey::new("text".into())],
+                StdHashSet::new(),
+                StoreSettings {
+                    eviction: Some(StoreEviction {
+                        max_entries: None,
+                        max_bytes: NonZeroUsize::new(max_bytes),
+                        policy: EvictionPolicy::LRU,
+                    }),
+                    ..Default::default()
+                },
+                true,
+            )
+            .unwrap();
+        // entries of very different sizes take more than one pass of the eviction
+        let entry = |i: usize| {
+            (
+                StoreKey(array![i as f32, 1.0]),
+                StdHashMap::from_iter([(
+                    MetadataKey::new("text".into()),
+                    MetadataValue::RawString("a".repeat(if i % 10 == 0 { 2048 } else { 8 })),
+                )]),
+            )
+        };
+        handler
+            .set_in_store(&store_name, (0..100).map(entry).collect())
+            .unwrap();
+        let store = handler.get(&store_name).unwrap();
+        assert!(store.len() < 100);
+        assert!(store.bytes() <= max_bytes);
+        assert!(store.memory(store_name.clone()).total() <= max_bytes);
+
+        // the count kept through updates and deletes is the one made afresh
+        handler
+            .set_in_store(&store_name, (95..105).map(entry).collect())
+            .unwrap();
+        handler
+            .del_key_in_store(&store_name, vec![StoreKey(array![104.0, 1.0])])
+            .unwrap();
+        let bytes = store.bytes();
+        store.recount_bytes();
+        assert_eq!(store.bytes(), bytes);
+        assert!(bytes <= max_bytes);
+    }
+}
@@ -0,0 +1,193 @@
+use ahnlich_types::keyval::{StoreKey, StoreValue};
+use ahnlich_types::metadata::{MetadataKey, MetadataValue};
+use arrow_array::{
+    ArrayRef, BinaryArray, FixedSizeListArray, Float32Array, RecordBatch, StringArray,
+};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use futures::{Stream, StreamExt};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+pub(crate) const CONTENT_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Schema of exported entries: the keys as fixed size lists of floats in a `_ahnlich.key` column,
+/// named within the reserved namespace so that it never clashes with a metadata key, followed by
+/// a nullable column per metadata key. Metadata columns are strings, unless an entry holds an
+/// image under the key in which case the column is binary and strings are kept as their bytes
+pub(crate) fn schema(dimension: NonZeroUsize, entries: &[(StoreKey, StoreValue)]) -> Schema {
+    let mut columns: BTreeMap<&MetadataKey, DataType> = BTreeMap::new();
+    for (key, value) in entries.iter().flat_map(|(_, value)| value) {
+        let column = columns.entry(key).or_insert(DataType::Utf8);
+        if matches!(value, MetadataValue::Image(_)) {
+            *column = DataType::Binary;
+        }
+    }
+    let key = Field::new(
+        MetadataKey::system("key").to_string(),
+        DataType::FixedSizeList(
+            Arc::new(Field::new("item", DataType::Float32, false)),
+            dimension.get() as i32,
+        ),
+        false,
+    );
+    Schema::new(
+        std::iter::once(key)
+            .chain(
+                columns
+                    .into_iter()
+                    .map(|(key, data_type)| Field::new(key.to_string(), data_type, true)),
+            )
+            .collect::<Vec<_>>(),
+    )
+}
+
+pub(crate) fn record_batch(
+    schema: &SchemaRef,
+    dimension: NonZeroUsize,
+    entries: &[(StoreKey, StoreValue)],
+) -> Result<RecordBatch, ArrowError> {
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+    let values = Float32Array::from_iter_values(
+        entries
+            .iter()
+            .flat_map(|(store_key, _)| store_key.0.iter().copied()),
+    );
+    columns.push(Arc::new(FixedSizeListArray::try_new(
+        Arc::new(Field::new("item", DataType::Float32, false)),
+        dimension.get() as i32,
+        Arc::new(values),
+        None,
+    )?));
+    for field in schema.fields().iter().skip(1) {
+        let key = MetadataKey::new(field.name().clone());
+        let values = entries.iter().map(|(_, value)| value.get(&key));
+        let column: ArrayRef = match field.data_type() {
+            DataType::Binary => Arc::new(BinaryArray::from_iter(values.map(|value| {
+                value.map(|value| match value {
+                    MetadataValue::RawString(string) => string.as_bytes(),
+                    MetadataValue::Image(bytes) => bytes.as_slice(),
+                })
+            }))),
+            _ => Arc::new(StringArray::from_iter(values.map(|value| match value {
+                Some(MetadataValue::RawString(string)) => Some(string.as_str()),
+                _ => None,
+            }))),
+        };
+        columns.push(column);
+    }
+    RecordBatch::try_new(schema.clone(), columns)
+}
+
+/// Encodes entries as an Arrow IPC stream, yielding the schema and then a record batch of up to
+/// `batch_size` entries at a time so that the whole stream is never held in memory at once
+pub(crate) fn ipc_stream(
+    dimension: NonZeroUsize,
+    entries: Vec<(StoreKey, StoreValue)>,
+    batch_size: NonZeroUsize,
+) -> Result<impl Stream<Item = Result<Vec<u8>, ArrowError>>, ArrowError> {
+    let schema = Arc::new(schema(dimension, &entries));
+    let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+    let header = std::mem::take(writer.get_mut());
+    let batches = futures::stream::unfold(
+        (Some(writer), entries, 0),
+        move |(writer, entries, offset)| {
+            let schema = schema.clone();
+            async move {
+                let mut writer = writer?;
+                if offset < entries.len() {
+                    let end = entries.len().min(offset + batch_size.get());
+                    let result = record_batch(&schema, dimension, &entries[offset..end])
+                        .and_then(|batch| writer.write(&batch))
+                        .map(|_| std::mem::take(writer.get_mut()));
+                    // the stream ends after an error
+                    let next = result.is_ok().then_some(writer);
+                    Some((result, (next, entries, end)))
+                } else {
+                    let result = writer.finish().map(|_| std::mem::take(writer.get_mut()));
+                    Some((result, (None, entries, offset)))
+                }
+            }
+        },
+    );
+    Ok(futures::stream::once(async { Ok(header) }).chain(batches))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use arrow_ipc::reader::StreamReader;
+    use futures::TryStreamExt;
+    use ndarray::array;
+
+    #[tokio::test]
+    async fn test_ipc_stream() {
+        let entries: Vec<(StoreKey, StoreValue)> = (0..5)
+            .map(|i| {
+                let mut value = StoreValue::new();
+                value.insert(
+                    MetadataKey::new("name".into()),
+                    MetadataValue::RawString(format!("entry {i}")),
+                );
+                if i % 2 == 0 {
+                    value.insert(
+                        MetadataKey::new("image".into()),
+                        MetadataValue::Image(vec![i as u8]),
+                    );
+                }
+                (StoreKey(array![i as f32, 1.0]), value)
+            })
+            .collect();
+        let stream = ipc_stream(
+            NonZeroUsize::new(2).unwrap(),
+            entries,
+            NonZeroUsize::new(2).unwrap(),
+        )
+        .unwrap();
+        let bytes: Vec<Vec<u8>> = stream.try_collect().await.unwrap();
+        let batches: Vec<RecordBatch> = StreamReader::try_new(bytes.concat().as_slice(), None)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            batches
+                .iter()
+                .map(RecordBatch::num_rows)
+                .collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        let schema = batches[0].schema();
+        assert_eq!(
+            schema
+                .fields()
+                .iter()
+                .map(|field| (field.name().as_str(), field.data_type().clone()))
+                .skip(1)
+                .collect::<Vec<_>>(),
+            vec![("image", DataType::Binary), ("name", DataType::Utf8)]
+        );
+        let keys = batches[2]
+            .column(0)
+            .as_any()
+            .downcast_ref::<FixedSizeListArray>()
+            .unwrap()
+            .value(0);
+        let keys = keys.as_any().downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(keys.values().to_vec(), vec![4.0, 1.0]);
+        let images = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        assert_eq!(images.value(0), &[0]);
+        assert!(images.is_null(1));
+        let names = batches[1]
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(1), "entry 3");
+    }
+}
@@ -0,0 +1,301 @@
+use super::export;
+use super::store::StoreHandler;
+use super::store::StoreKeyId;
+use crate::errors::ServerError;
+use ahnlich_types::db::StoreIndex;
+use ahnlich_types::jobs::JobState;
+use ahnlich_types::keyval::{StoreKey, StoreName, StoreValue};
+use arrow_schema::SchemaRef;
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use task_manager::Task;
+use task_manager::TaskState;
+use utils::jobs::Job;
+
+/// Number of entries removed by a background deletion before it yields, so that a large
+/// deletion does not hold up other queries against the store
+const DELETION_BATCH_SIZE: usize = 1000;
+
+/// Number of entries written by an export before it yields
+const EXPORT_BATCH_SIZE: usize = 8192;
+
+/// Deletes entries of a store in batches in the background, reporting progress to a job
+#[derive(Debug)]
+pub(crate) struct DelPredTask {
+    job: Arc<Job>,
+    store_handler: Arc<StoreHandler>,
+    store: StoreName,
+    remaining: Mutex<Vec<StoreKeyId>>,
+}
+
+impl DelPredTask {
+    pub(crate) fn new(
+        job: Arc<Job>,
+        store_handler: Arc<StoreHandler>,
+        store: StoreName,
+        ids: Vec<StoreKeyId>,
+    ) -> Self {
+        Self {
+            job,
+            store_handler,
+            store,
+            remaining: Mutex::new(ids),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for DelPredTask {
+    fn task_name(&self) -> String {
+        format!("db-delpred-job-{}", self.job.id())
+    }
+
+    async fn run(&self) -> TaskState {
+        // job was cancelled with CANCELJOB
+        if !self.job.is_running() {
+            return TaskState::Break;
+        }
+        let batch = {
+            let mut remaining = self.remaining.lock().expect("job batch lock poisoned");
+            let at = remaining.len().saturating_sub(DELETION_BATCH_SIZE);
+            remaining.split_off(at)
+        };
+        if batch.is_empty() {
+            self.job.finish(JobState::Completed);
+            return TaskState::Break;
+        }
+        let batch_len = batch.len();
+        if let Err(e) = self.store_handler.del_ids_in_store(&self.store, batch) {
+            self.job.finish(JobState::Failed(format!("{e}")));
+            return TaskState::Break;
+        }
+        self.job.progress(batch_len);
+        tokio::task::yield_now().await;
+        TaskState::Continue
+    }
+
+    async fn cleanup(&self) {
+        self.job.finish(JobState::Cancelled);
+    }
+}
+
+/// Repairs the indices of a store found to disagree with its entries one at a time in the
+/// background, reporting progress to a job
+#[derive(Debug)]
+pub(crate) struct RepairStoreTask {
+    job: Arc<Job>,
+    store_handler: Arc<StoreHandler>,
+    store: StoreName,
+    remaining: Mutex<Vec<StoreIndex>>,
+}
+
+impl RepairStoreTask {
+    pub(crate) fn new(
+        job: Arc<Job>,
+        store_handler: Arc<StoreHandler>,
+        store: StoreName,
+        indices: Vec<StoreIndex>,
+    ) -> Self {
+        Self {
+            job,
+            store_handler,
+            store,
+            remaining: Mutex::new(indices),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for RepairStoreTask {
+    fn task_name(&self) -> String {
+        format!("db-repairstore-job-{}", self.job.id())
+    }
+
+    async fn run(&self) -> TaskState {
+        // job was cancelled with CANCELJOB
+        if !self.job.is_running() {
+            return TaskState::Break;
+        }
+        let index = self
+            .remaining
+            .lock()
+            .expect("job batch lock poisoned")
+            .pop();
+        let Some(index) = index else {
+            self.job.finish(JobState::Completed);
+            return TaskState::Break;
+        };
+        if let Err(e) = self.store_handler.repair_store_index(&self.store, &index) {
+            self.job.finish(JobState::Failed(format!("{e}")));
+            return TaskState::Break;
+        }
+        self.job.progress(1);
+        tokio::task::yield_now().await;
+        TaskState::Continue
+    }
+
+    async fn cleanup(&self) {
+        self.job.finish(JobState::Cancelled);
+    }
+}
+
+/// Parquet file being written with the entries of a store. Entries are written to a partial file
+/// next to the path, which is only moved to the path once every entry is written
+pub(crate) struct ParquetExport {
+    path: PathBuf,
+    partial_path: PathBuf,
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    dimension: NonZeroUsize,
+    entries: Vec<(StoreKey, StoreValue)>,
+    written: usize,
+}
+
+impl std::fmt::Debug for ParquetExport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParquetExport")
+            .field("path", &self.path)
+            .field("written", &self.written)
+            .field("total", &self.entries.len())
+            .finish()
+    }
+}
+
+impl ParquetExport {
+    pub(crate) fn create(
+        path: PathBuf,
+        dimension: NonZeroUsize,
+        entries: Vec<(StoreKey, StoreValue)>,
+    ) -> Result<Self, ServerError> {
+        let export_error = |e: &dyn std::fmt::Display| ServerError::Export(e.to_string());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| export_error(&e))?;
+        }
+        let mut partial_path = path.clone().into_os_string();
+        partial_path.push(".partial");
+        let partial_path = PathBuf::from(partial_path);
+        let file = File::create(&partial_path).map_err(|e| export_error(&e))?;
+        let schema = Arc::new(export::schema(dimension, &entries));
+        let writer =
+            ArrowWriter::try_new(file, schema.clone(), None).map_err(|e| export_error(&e))?;
+        Ok(Self {
+            path,
+            partial_path,
+            writer,
+            schema,
+            dimension,
+            entries,
+            written: 0,
+        })
+    }
+
+    /// Writes the next batch of entries, returning how many were written
+    fn write_batch(&mut self) -> Result<usize, String> {
+        let end = self.entries.len().min(self.written + EXPORT_BATCH_SIZE);
+        let batch = export::record_batch(
+            &self.schema,
+            self.dimension,
+            &self.entries[self.written..end],
+        )
+        .map_err(|e| e.to_string())?;
+        self.writer.write(&batch).map_err(|e| e.to_string())?;
+        let written = end - self.written;
+        self.written = end;
+        Ok(written)
+    }
+
+    /// Writes the footer of the file and moves it to its path
+    fn finish(self) -> Result<(), String> {
+        self.writer.close().map_err(|e| e.to_string())?;
+        std::fs::rename(&self.partial_path, &self.path).map_err(|e| e.to_string())
+    }
+
+    fn discard(self) {
+        if let Err(e) = std::fs::remove_file(&self.partial_path) {
+            log::error!("Could not remove partial export {e}");
+        }
+    }
+}
+
+/// Writes the entries of a store to a Parquet file in batches in the background, reporting
+/// progress to a job
+#[derive(Debug)]
+pub(crate) struct ExportStoreTask {
+    job: Arc<Job>,
+    export: Mutex<Option<ParquetExport>>,
+}
+
+impl ExportStoreTask {
+    pub(crate) fn new(job: Arc<Job>, export: ParquetExport) -> Self {
+        Self {
+            job,
+            export: Mutex::new(Some(export)),
+        }
+    }
+
+    fn take_export(&self) -> Option<ParquetExport> {
+        self.export.lock().expect("export lock poisoned").take()
+    }
+
+    /// Writes the next batch of the export, taking the export out once it is done with
+    fn step(&self) -> TaskState {
+        let mut guard = self.export.lock().expect("export lock poisoned");
+        let Some(export) = guard.as_mut() else {
+            return TaskState::Break;
+        };
+        if export.written == export.entries.len() {
+            let result = guard.take().map(ParquetExport::finish);
+            self.job.finish(match result {
+                Some(Err(e)) => JobState::Failed(e),
+                _ => JobState::Completed,
+            });
+            return TaskState::Break;
+        }
+        match export.write_batch() {
+            Ok(written) => {
+                self.job.progress(written);
+                TaskState::Continue
+            }
+            Err(e) => {
+                if let Some(export) = guard.take() {
+                    export.discard();
+                }
+                self.job.finish(JobState::Failed(e));
+                TaskState::Break
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for ExportStoreTask {
+    fn task_name(&self) -> String {
+        format!("db-export-job-{}", self.job.id())
+    }
+
+    async fn run(&self) -> TaskState {
+        // job was cancelled with CANCELJOB
+        if !self.job.is_running() {
+            if let Some(export) = self.take_export() {
+                export.discard();
+            }
+            return TaskState::Break;
+        }
+        if let TaskState::Break = self.step() {
+            return TaskState::Break;
+        }
+        tokio::task::yield_now().await;
+        TaskState::Continue
+    }
+
+    async fn cleanup(&self) {
+        self.job.finish(JobState::Cancelled);
+        if let Some(export) = self.take_export() {
+            export.discard();
+        }
+    }
+}
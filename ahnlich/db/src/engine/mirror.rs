@@ -0,0 +1,334 @@
+use super::store::StoreHandler;
+use ahnlich_client_rs::db::DbClient;
+use ahnlich_client_rs::error::AhnlichError;
+use ahnlich_client_rs::pipeline::PipelineResult;
+use ahnlich_types::db::{DBQuery, MirrorAction, MirrorState, MirrorStatus, ServerResponse};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use task_manager::Task;
+use task_manager::TaskState;
+use tokio::sync::Notify;
+use tokio::time::sleep;
+
+/// Longest wait for new writes before checking the log again
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wait before trying again after the mirror could not be reached
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct MirrorLogInner {
+    // writes yet to be applied by the mirror along with when they were logged
+    pending: VecDeque<(Instant, DBQuery)>,
+    paused: bool,
+    // set when the mirror has to be copied over afresh, writes are not logged until it has been
+    resync: bool,
+    // set while the stores are being copied over
+    resyncing: bool,
+    // bumped whenever the log is cleared for a resync so writes sent before are not removed
+    // from the log that replaced it
+    generation: u64,
+    logged: u64,
+    applied: u64,
+    failed: u64,
+    last_error: Option<String>,
+}
+
+/// What the mirror task has to send next
+#[derive(Debug)]
+enum MirrorBatch {
+    Resync,
+    Writes {
+        generation: u64,
+        queries: Vec<DBQuery>,
+    },
+}
+
+/// Writes made to the server that are yet to be sent to its mirror. The log only lives in
+/// memory so a mirror starts out by being resynced, as it is when more writes pile up than the
+/// log holds
+#[derive(Debug)]
+pub(crate) struct MirrorLog {
+    target: String,
+    max_pending: usize,
+    inner: Mutex<MirrorLogInner>,
+    notify: Notify,
+}
+
+impl MirrorLogInner {
+    fn start_resync(&mut self) {
+        self.pending.clear();
+        self.resync = true;
+        self.generation += 1;
+    }
+
+    fn failed(&mut self, errors: Vec<String>) {
+        self.failed += errors.len() as u64;
+        if let Some(error) = errors.into_iter().last() {
+            self.last_error = Some(error);
+        }
+    }
+}
+
+impl MirrorLog {
+    pub(crate) fn new(target: String, max_pending: usize) -> Self {
+        Self {
+            target,
+            max_pending,
+            inner: Mutex::new(MirrorLogInner {
+                pending: VecDeque::new(),
+                paused: false,
+                resync: true,
+                resyncing: false,
+                generation: 0,
+                logged: 0,
+                applied: 0,
+                failed: 0,
+                last_error: None,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MirrorLogInner> {
+        self.inner.lock().expect("mirror log lock poisoned")
+    }
+
+    /// Logs a write that the server applied
+    pub(crate) fn record(&self, query: DBQuery) {
+        let mut inner = self.lock();
+        // the stores are copied over as they are once the resync starts
+        if inner.resync {
+            return;
+        }
+        if inner.pending.len() >= self.max_pending {
+            log::warn!(
+                "Mirror {} fell {} writes behind, resyncing it",
+                self.target,
+                inner.pending.len()
+            );
+            inner.start_resync();
+        } else {
+            inner.pending.push_back((Instant::now(), query));
+            inner.logged += 1;
+        }
+        drop(inner);
+        self.notify.notify_one();
+    }
+
+    pub(crate) fn status(&self) -> MirrorStatus {
+        let inner = self.lock();
+        MirrorStatus {
+            target: self.target.clone(),
+            state: if inner.paused {
+                MirrorState::Paused
+            } else if inner.resync || inner.resyncing {
+                MirrorState::Resyncing
+            } else {
+                MirrorState::Running
+            },
+            logged: inner.logged,
+            applied: inner.applied,
+            failed: inner.failed,
+            pending: inner.pending.len(),
+            lag_ms: inner
+                .pending
+                .front()
+                .map(|(logged_at, _)| logged_at.elapsed().as_millis() as u64)
+                .unwrap_or_default(),
+            last_error: inner.last_error.clone(),
+        }
+    }
+
+    /// Matches CONTROLMIRROR - pauses, resumes or resyncs the mirror
+    pub(crate) fn control(&self, action: MirrorAction) -> MirrorStatus {
+        {
+            let mut inner = self.lock();
+            match action {
+                MirrorAction::Pause => inner.paused = true,
+                MirrorAction::Resume => inner.paused = false,
+                MirrorAction::Resync => inner.start_resync(),
+            }
+        }
+        self.notify.notify_one();
+        self.status()
+    }
+
+    /// The next writes to send, which stay in the log until they are applied
+    fn next_batch(&self, batch_size: usize) -> Option<MirrorBatch> {
+        let mut inner = self.lock();
+        if inner.paused {
+            return None;
+        }
+        if inner.resync {
+            // writes from here on are logged, a store copied over later may hold some of them
+            // already which is fine as applying them again leaves the store the same
+            inner.resync = false;
+            inner.resyncing = true;
+            inner.logged = 0;
+            inner.applied = 0;
+            inner.failed = 0;
+            return Some(MirrorBatch::Resync);
+        }
+        if inner.pending.is_empty() {
+            return None;
+        }
+        Some(MirrorBatch::Writes {
+            generation: inner.generation,
+            queries: inner
+                .pending
+                .iter()
+                .take(batch_size)
+                .map(|(_, query)| query.clone())
+                .collect(),
+        })
+    }
+
+    /// Removes `sent` writes from the log once the mirror responded to them
+    fn applied(&self, generation: u64, sent: usize, errors: Vec<String>) {
+        let mut inner = self.lock();
+        // a resync requested in the meantime already cleared the log
+        if inner.generation != generation {
+            return;
+        }
+        inner.pending.drain(..sent);
+        inner.applied += sent as u64;
+        inner.failed(errors);
+    }
+
+    /// Records the stores were copied over to the mirror
+    fn resynced(&self, errors: Vec<String>) {
+        log::info!("Resynced mirror {}", self.target);
+        let mut inner = self.lock();
+        inner.resyncing = false;
+        inner.failed(errors);
+    }
+
+    /// Records that the mirror could not be reached, a failed resync is started over
+    fn unreachable(&self, error: &AhnlichError, resyncing: bool) {
+        log::error!("Could not reach mirror {}: {error}", self.target);
+        let mut inner = self.lock();
+        inner.last_error = Some(error.to_string());
+        if resyncing {
+            inner.resyncing = false;
+            inner.start_resync();
+        }
+    }
+}
+
+/// Sends the writes logged in a [`MirrorLog`] to the mirror, or copies every store over when
+/// it has to be resynced
+#[derive(Debug)]
+pub(crate) struct MirrorTask {
+    mirror_log: Arc<MirrorLog>,
+    store_handler: Arc<StoreHandler>,
+    client: DbClient,
+    batch_size: usize,
+}
+
+impl MirrorTask {
+    pub(crate) fn new(
+        mirror_log: Arc<MirrorLog>,
+        store_handler: Arc<StoreHandler>,
+        client: DbClient,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            mirror_log,
+            store_handler,
+            client,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Runs queries on the mirror, returning the errors of those that failed
+    async fn send(&self, queries: Vec<DBQuery>) -> Result<Vec<String>, AhnlichError> {
+        let mut pipeline = self.client.pipeline(queries.len(), None).await?;
+        for query in queries {
+            pipeline.push(query);
+        }
+        Ok(pipeline
+            .exec_entries()
+            .await?
+            .into_iter()
+            .filter_map(|result| match result {
+                PipelineResult::Error(error) => Some(error.message),
+                PipelineResult::Success(_) | PipelineResult::Skipped => None,
+            })
+            .collect())
+    }
+
+    /// Drops the stores of the mirror that the server does not hold and copies over the rest
+    async fn resync(&self) -> Result<Vec<String>, AhnlichError> {
+        let mirrored = match self.client.list_stores(None).await? {
+            ServerResponse::StoreList(stores) => stores,
+            response => {
+                return Err(AhnlichError::UnexpectedResponse(format!("{response:?}")));
+            }
+        };
+        let stores = self.store_handler.get_stores();
+        let dropped: Vec<_> = mirrored
+            .into_iter()
+            .filter(|info| !stores.contains_key(&info.name, &stores.guard()))
+            .map(|info| DBQuery::DropStore {
+                store: info.name,
+                error_if_not_exists: false,
+            })
+            .collect();
+        let mut errors = if dropped.is_empty() {
+            vec![]
+        } else {
+            self.send(dropped).await?
+        };
+        let store_names: Vec<_> = stores.keys(&stores.guard()).cloned().collect();
+        for store_name in store_names {
+            let queries = self
+                .store_handler
+                .recreate_queries(&store_name, self.batch_size);
+            for queries in queries.chunks(self.batch_size) {
+                errors.extend(self.send(queries.to_vec()).await?);
+            }
+        }
+        Ok(errors)
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for MirrorTask {
+    fn task_name(&self) -> String {
+        "db-mirror".to_string()
+    }
+
+    async fn run(&self) -> TaskState {
+        match self.mirror_log.next_batch(self.batch_size) {
+            None => {
+                tokio::select! {
+                    _ = self.mirror_log.notify.notified() => {}
+                    _ = sleep(IDLE_INTERVAL) => {}
+                }
+            }
+            Some(MirrorBatch::Resync) => match self.resync().await {
+                Ok(errors) => self.mirror_log.resynced(errors),
+                Err(error) => {
+                    self.mirror_log.unreachable(&error, true);
+                    sleep(RETRY_INTERVAL).await;
+                }
+            },
+            Some(MirrorBatch::Writes {
+                generation,
+                queries,
+            }) => {
+                let sent = queries.len();
+                match self.send(queries).await {
+                    Ok(errors) => self.mirror_log.applied(generation, sent, errors),
+                    Err(error) => {
+                        self.mirror_log.unreachable(&error, false);
+                        sleep(RETRY_INTERVAL).await;
+                    }
+                }
+            }
+        }
+        TaskState::Continue
+    }
+}
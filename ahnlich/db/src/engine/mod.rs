@@ -1,2 +1,14 @@
+mod benchmark;
+pub(crate) mod compaction;
+mod eviction;
+pub(crate) mod export;
+pub mod jobs;
+pub(crate) mod mirror;
+mod optimizer;
 mod predicate;
+mod quota;
+mod scrub;
+pub mod search;
 pub mod store;
+pub(crate) mod trash;
+mod vectors;
@@ -0,0 +1,364 @@
+use ahnlich_types::metadata::MetadataKey;
+use ahnlich_types::metadata::MetadataValue;
+use ahnlich_types::predicate::Predicate;
+use ahnlich_types::predicate::PredicateCondition;
+use itertools::Itertools;
+use std::collections::HashSet as StdHashSet;
+
+/// Rewrites a condition into an equivalent one that is cheaper to evaluate, or None when no entry
+/// can match it.
+///
+/// Chains of And and Or are flattened and the predicates they hold on the same key merged into
+/// one, e.g `a = 1 OR a IN (2, 3)` into `a IN (1, 2, 3)` and `a IN (1, 2) AND a != 1` into
+/// `a = 2`, so that each key is looked up once per chain. Conditions have no NOT, negations only
+/// appear in the predicates themselves, so a negated predicate is folded into a positive one on the
+/// same key where possible as positive predicates match fewer entries. An And holding a predicate
+/// no entry matches, such as `a IN ()` or `a = 1 AND a = 2`, matches nothing and is dropped from
+/// the Or holding it.
+///
+/// Predicates are only merged with those on the same key, as entries without a key match the
+/// negated predicates on it when there is no index for the key but not when there is one.
+pub(super) fn optimize(condition: &PredicateCondition) -> Option<PredicateCondition> {
+    match condition {
+        PredicateCondition::Value(predicate) => KeyPredicate::from(predicate)
+            .into_predicate()
+            .map(PredicateCondition::Value),
+        PredicateCondition::And(..) => {
+            let mut conjuncts = vec![];
+            for conjunct in conjuncts_of(condition) {
+                // an And holding a condition that matches nothing matches nothing
+                conjuncts.extend(conjuncts_of(&optimize(conjunct)?).into_iter().cloned());
+            }
+            merge(conjuncts, Junction::And)
+        }
+        PredicateCondition::Or(..) => {
+            let disjuncts = disjuncts_of(condition)
+                .into_iter()
+                .filter_map(optimize)
+                .flat_map(|disjunct| disjuncts_of(&disjunct).into_iter().cloned().collect_vec())
+                .collect_vec();
+            merge(disjuncts, Junction::Or)
+        }
+    }
+}
+
+/// Conditions of a chain of And, or the condition itself when it is not an And
+pub(super) fn conjuncts_of(condition: &PredicateCondition) -> Vec<&PredicateCondition> {
+    match condition {
+        PredicateCondition::And(first, second) => {
+            let mut conjuncts = conjuncts_of(first);
+            conjuncts.extend(conjuncts_of(second));
+            conjuncts
+        }
+        condition => vec![condition],
+    }
+}
+
+/// Conditions of a chain of Or, or the condition itself when it is not an Or
+pub(super) fn disjuncts_of(condition: &PredicateCondition) -> Vec<&PredicateCondition> {
+    match condition {
+        PredicateCondition::Or(first, second) => {
+            let mut disjuncts = disjuncts_of(first);
+            disjuncts.extend(disjuncts_of(second));
+            disjuncts
+        }
+        condition => vec![condition],
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Junction {
+    And,
+    Or,
+}
+
+/// Merges the predicates of a flattened chain on each key, keeping the keys in the order they
+/// first appear followed by the nested conditions, and joins them back into a chain
+fn merge(conditions: Vec<PredicateCondition>, junction: Junction) -> Option<PredicateCondition> {
+    let mut merged: Vec<KeyPredicate> = vec![];
+    let mut nested: Vec<PredicateCondition> = vec![];
+    for condition in conditions {
+        match condition {
+            PredicateCondition::Value(predicate) => {
+                let predicate = KeyPredicate::from(&predicate);
+                match merged.iter_mut().find(|other| other.key == predicate.key) {
+                    Some(other) => other.merge(predicate, junction),
+                    None => merged.push(predicate),
+                }
+            }
+            condition if !nested.contains(&condition) => nested.push(condition),
+            _ => {}
+        }
+    }
+    let mut conditions = vec![];
+    for predicate in merged {
+        match predicate.into_predicate() {
+            Some(predicate) => conditions.push(PredicateCondition::Value(predicate)),
+            None if junction == Junction::And => return None,
+            None => {}
+        }
+    }
+    conditions.extend(nested);
+    conditions
+        .into_iter()
+        .reduce(|first, second| match junction {
+            Junction::And => first.and(second),
+            Junction::Or => first.or(second),
+        })
+}
+
+/// Predicates on a key merged into the values an entry's value for the key has to be among, or
+/// those it cannot be among
+#[derive(Debug)]
+struct KeyPredicate {
+    key: MetadataKey,
+    values: KeyValues,
+}
+
+#[derive(Debug)]
+enum KeyValues {
+    Among(StdHashSet<MetadataValue>),
+    NotAmong(StdHashSet<MetadataValue>),
+}
+
+impl From<&Predicate> for KeyPredicate {
+    fn from(predicate: &Predicate) -> Self {
+        let values = match predicate {
+            Predicate::Equals { value, .. } => {
+                KeyValues::Among(StdHashSet::from_iter([value.clone()]))
+            }
+            Predicate::In { value, .. } => KeyValues::Among(value.clone()),
+            Predicate::NotEquals { value, .. } => {
+                KeyValues::NotAmong(StdHashSet::from_iter([value.clone()]))
+            }
+            Predicate::NotIn { value, .. } => KeyValues::NotAmong(value.clone()),
+        };
+        Self {
+            key: predicate.get_key().clone(),
+            values,
+        }
+    }
+}
+
+impl KeyPredicate {
+    fn merge(&mut self, other: KeyPredicate, junction: Junction) {
+        let values = std::mem::replace(&mut self.values, KeyValues::Among(StdHashSet::new()));
+        self.values = match (junction, values, other.values) {
+            (Junction::And, KeyValues::Among(first), KeyValues::Among(second)) => {
+                KeyValues::Among(first.intersection(&second).cloned().collect())
+            }
+            (Junction::And, KeyValues::Among(among), KeyValues::NotAmong(not_among))
+            | (Junction::And, KeyValues::NotAmong(not_among), KeyValues::Among(among)) => {
+                KeyValues::Among(among.difference(&not_among).cloned().collect())
+            }
+            (Junction::And, KeyValues::NotAmong(mut first), KeyValues::NotAmong(second)) => {
+                first.extend(second);
+                KeyValues::NotAmong(first)
+            }
+            (Junction::Or, KeyValues::Among(mut first), KeyValues::Among(second)) => {
+                first.extend(second);
+                KeyValues::Among(first)
+            }
+            // entries without the key match neither side of the Or nor the merged predicate when
+            // the negated side does not match them
+            (Junction::Or, KeyValues::Among(among), KeyValues::NotAmong(not_among))
+            | (Junction::Or, KeyValues::NotAmong(not_among), KeyValues::Among(among)) => {
+                KeyValues::NotAmong(not_among.difference(&among).cloned().collect())
+            }
+            (Junction::Or, KeyValues::NotAmong(first), KeyValues::NotAmong(second)) => {
+                KeyValues::NotAmong(first.intersection(&second).cloned().collect())
+            }
+        };
+    }
+
+    /// None when no entry matches
+    fn into_predicate(self) -> Option<Predicate> {
+        let key = self.key;
+        match self.values {
+            KeyValues::Among(value) if value.is_empty() => None,
+            KeyValues::Among(value) if value.len() == 1 => Some(Predicate::Equals {
+                key,
+                value: value
+                    .into_iter()
+                    .next()
+                    .expect("Checked for a single value"),
+            }),
+            KeyValues::Among(value) => Some(Predicate::In { key, value }),
+            KeyValues::NotAmong(value) if value.len() == 1 => Some(Predicate::NotEquals {
+                key,
+                value: value
+                    .into_iter()
+                    .next()
+                    .expect("Checked for a single value"),
+            }),
+            KeyValues::NotAmong(value) => Some(Predicate::NotIn { key, value }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::predicate::PredicateIndices;
+    use super::*;
+    use ahnlich_types::keyval::StoreValue;
+    use pretty_assertions::assert_eq;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    const KEYS: [&str; 3] = ["lang", "role", "rank"];
+    const VALUES: [&str; 3] = ["de", "en", "mage"];
+
+    fn key(key: &str) -> MetadataKey {
+        MetadataKey::new(key.into())
+    }
+
+    fn value(value: &str) -> MetadataValue {
+        MetadataValue::RawString(value.into())
+    }
+
+    fn equals(key_name: &str, value_name: &str) -> PredicateCondition {
+        PredicateCondition::Value(Predicate::Equals {
+            key: key(key_name),
+            value: value(value_name),
+        })
+    }
+
+    fn not_equals(key_name: &str, value_name: &str) -> PredicateCondition {
+        PredicateCondition::Value(Predicate::NotEquals {
+            key: key(key_name),
+            value: value(value_name),
+        })
+    }
+
+    fn in_(key_name: &str, value_names: &[&str]) -> PredicateCondition {
+        PredicateCondition::Value(Predicate::In {
+            key: key(key_name),
+            value: value_names.iter().map(|name| value(name)).collect(),
+        })
+    }
+
+    fn not_in(key_name: &str, value_names: &[&str]) -> PredicateCondition {
+        PredicateCondition::Value(Predicate::NotIn {
+            key: key(key_name),
+            value: value_names.iter().map(|name| value(name)).collect(),
+        })
+    }
+
+    fn random_values(rng: &mut StdRng) -> Vec<&'static str> {
+        let len = rng.gen_range(0..=VALUES.len());
+        VALUES.choose_multiple(rng, len).copied().collect()
+    }
+
+    fn random_condition(rng: &mut StdRng, depth: usize) -> PredicateCondition {
+        let key_name = KEYS.choose(rng).expect("Keys are not empty");
+        // only predicates once the tree is deep enough
+        let kinds = if depth == 0 { 4 } else { 6 };
+        match rng.gen_range(0..kinds) {
+            0 => equals(key_name, VALUES.choose(rng).expect("Values are not empty")),
+            1 => not_equals(key_name, VALUES.choose(rng).expect("Values are not empty")),
+            2 => in_(key_name, &random_values(rng)),
+            3 => not_in(key_name, &random_values(rng)),
+            4 => random_condition(rng, depth - 1).and(random_condition(rng, depth - 1)),
+            _ => random_condition(rng, depth - 1).or(random_condition(rng, depth - 1)),
+        }
+    }
+
+    fn random_entry(rng: &mut StdRng) -> StoreValue {
+        let mut entry = StoreValue::new();
+        for key_name in KEYS {
+            // leave out some keys to cover entries without them
+            if rng.gen_bool(0.8) {
+                let value_name = VALUES.choose(rng).expect("Values are not empty");
+                entry.insert(key(key_name), value(value_name));
+            }
+        }
+        entry
+    }
+
+    #[test]
+    fn test_merges_predicates_on_the_same_key() {
+        assert_eq!(
+            optimize(&equals("lang", "de").or(in_("lang", &["en", "mage"]))),
+            Some(in_("lang", &["de", "en", "mage"]))
+        );
+        assert_eq!(
+            optimize(&in_("lang", &["de", "en"]).and(not_equals("lang", "en"))),
+            Some(equals("lang", "de"))
+        );
+        assert_eq!(
+            optimize(&not_equals("lang", "de").and(not_equals("lang", "en"))),
+            Some(not_in("lang", &["de", "en"]))
+        );
+        assert_eq!(
+            optimize(&not_in("lang", &["de", "en"]).or(equals("lang", "de"))),
+            Some(not_equals("lang", "en"))
+        );
+        // predicates on other keys are left as they are
+        assert_eq!(
+            optimize(
+                &equals("lang", "de")
+                    .and(equals("role", "mage"))
+                    .and(equals("lang", "de"))
+            ),
+            Some(equals("lang", "de").and(equals("role", "mage")))
+        );
+    }
+
+    #[test]
+    fn test_conditions_matching_nothing() {
+        assert_eq!(optimize(&in_("lang", &[])), None);
+        assert_eq!(
+            optimize(&equals("lang", "de").and(equals("lang", "en"))),
+            None
+        );
+        assert_eq!(
+            optimize(
+                &equals("role", "mage")
+                    .or(equals("lang", "de"))
+                    .and(in_("rank", &[]))
+            ),
+            None
+        );
+        // a disjunct matching nothing is dropped
+        assert_eq!(
+            optimize(
+                &equals("lang", "de")
+                    .and(equals("lang", "en"))
+                    .or(equals("role", "mage"))
+            ),
+            Some(equals("role", "mage"))
+        );
+    }
+
+    #[test]
+    fn test_optimized_conditions_match_the_same_entries() {
+        let mut rng = StdRng::seed_from_u64(1369);
+        let entries: Vec<StoreValue> = (0..50).map(|_| random_entry(&mut rng)).collect();
+        // entries without a key match negated predicates on it only when it has no index
+        let indexed = PredicateIndices::init(vec![key("lang")]);
+        indexed.add(
+            entries
+                .iter()
+                .enumerate()
+                .map(|(id, entry)| (format!("{id}").into(), entry.clone()))
+                .collect(),
+        );
+        let not_indexed = PredicateIndices::init(vec![]);
+        for _ in 0..2000 {
+            let condition = random_condition(&mut rng, 4);
+            let optimized = optimize(&condition);
+            for indices in [&indexed, &not_indexed] {
+                for entry in &entries {
+                    assert_eq!(
+                        indices.matches_value(&condition, entry),
+                        optimized
+                            .as_ref()
+                            .is_some_and(|optimized| indices.matches_value(optimized, entry)),
+                        "{condition:?} optimized into {optimized:?} on {entry:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use super::super::errors::ServerError;
+use super::optimizer;
 use super::store::Store;
 use super::store::StoreKeyId;
+use ahnlich_types::db::PredicateIndexStats;
 use ahnlich_types::keyval::StoreValue;
 use ahnlich_types::metadata::MetadataKey;
 use ahnlich_types::metadata::MetadataValue;
@@ -17,6 +19,8 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::collections::HashSet as StdHashSet;
 use std::mem::size_of_val;
+use std::time::{SystemTime, UNIX_EPOCH};
+use utils::deadline::Deadline;
 use utils::parallel;
 
 /// Predicates are essentially nested hashmaps that let us retrieve original keys that match a
@@ -46,12 +50,29 @@ type InnerPredicateIndexVal = ConcurrentHashSet<StoreKeyId>;
 type InnerPredicateIndex = ConcurrentHashMap<MetadataValue, InnerPredicateIndexVal>;
 type InnerPredicateIndices = ConcurrentHashMap<MetadataKey, PredicateIndex>;
 
+/// Number of most frequent values kept in the statistics of a predicate index
+const MOST_FREQUENT_VALUES: usize = 10;
+
+/// A side of an AND condition expected to match this many times more entries than the entries
+/// the other side matched is checked against those entries instead of its index
+const SCAN_OVER_INDEX_RATIO: usize = 4;
+
+/// Values and store key ids a predicate index is missing and those it holds that it should not
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct PredicateDiscrepancies {
+    pub(super) missing: Vec<(MetadataValue, StoreKeyId)>,
+    pub(super) orphaned: Vec<(MetadataValue, StoreKeyId)>,
+}
+
 /// Predicate indices are all the indexes referenced by their names
 #[derive(Debug, Serialize, Deserialize)]
 pub(super) struct PredicateIndices {
     inner: InnerPredicateIndices,
     /// These are the index keys that are meant to generate predicate indexes
     allowed_predicates: ConcurrentHashSet<MetadataKey>,
+    /// When entries were last added to or removed from each predicate index
+    #[serde(default)]
+    last_updated: ConcurrentHashMap<MetadataKey, SystemTime>,
 }
 
 impl PredicateIndices {
@@ -70,15 +91,178 @@ impl PredicateIndices {
                 .sum::<usize>()
     }
 
+    /// Estimated bytes held by the values of the indices and the keys of the entries that have
+    /// them
+    #[tracing::instrument(skip(self))]
+    pub(super) fn memory(&self) -> usize {
+        self.inner
+            .iter(&self.inner.guard())
+            .map(|(key, index)| {
+                size_of_val(key)
+                    + key.to_string().len()
+                    + index
+                        .0
+                        .iter(&index.0.guard())
+                        .map(|(value, key_ids)| {
+                            value_memory(value)
+                                + key_ids
+                                    .iter(&key_ids.guard())
+                                    .map(StoreKeyId::memory)
+                                    .sum::<usize>()
+                        })
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+
     #[tracing::instrument]
     pub(super) fn init(allowed_predicates: Vec<MetadataKey>) -> Self {
         let created = ConcurrentHashSet::new();
         for key in allowed_predicates {
             created.insert(key, &created.guard());
         }
+        let last_updated = ConcurrentHashMap::new();
+        let now = SystemTime::now();
+        for key in created.pin().iter() {
+            last_updated.insert(key.clone(), now, &last_updated.guard());
+        }
         Self {
             inner: InnerPredicateIndices::new(),
             allowed_predicates: created,
+            last_updated,
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn touch<'a>(&self, keys: impl Iterator<Item = &'a MetadataKey>) {
+        let pinned = self.last_updated.pin();
+        let now = SystemTime::now();
+        for key in keys {
+            pinned.insert(key.clone(), now);
+        }
+    }
+
+    /// Statistics of every predicate index ordered by key
+    #[tracing::instrument(skip(self))]
+    pub(super) fn stats(&self) -> Vec<PredicateIndexStats> {
+        let inner = self.inner.pin();
+        let last_updated = self.last_updated.pin();
+        self.current_predicates()
+            .into_iter()
+            .sorted()
+            .map(|key| {
+                let mut value_counts = inner
+                    .get(&key)
+                    .map(PredicateIndex::value_counts)
+                    .unwrap_or_default();
+                value_counts.sort_by(|(first_value, first), (second_value, second)| {
+                    second
+                        .cmp(first)
+                        .then_with(|| first_value.cmp(second_value))
+                });
+                PredicateIndexStats {
+                    entries: value_counts.iter().map(|(_, count)| count).sum(),
+                    distinct_values: value_counts.len(),
+                    most_frequent: value_counts
+                        .into_iter()
+                        .take(MOST_FREQUENT_VALUES)
+                        .collect(),
+                    last_updated: last_updated.get(&key).copied().unwrap_or(UNIX_EPOCH),
+                    key,
+                }
+            })
+            .collect()
+    }
+
+    /// Estimates how many entries match a condition from the sizes of the predicate indices,
+    /// assuming every entry matches predicates on keys without an index
+    #[tracing::instrument(skip(self))]
+    fn estimate_matches(&self, condition: &PredicateCondition, store_len: usize) -> usize {
+        match condition {
+            PredicateCondition::Value(predicate) => self
+                .inner
+                .pin()
+                .get(predicate.get_key())
+                .map_or(store_len, |index| index.estimate_matches(predicate)),
+            PredicateCondition::And(first, second) => self
+                .estimate_matches(first, store_len)
+                .min(self.estimate_matches(second, store_len)),
+            PredicateCondition::Or(first, second) => self
+                .estimate_matches(first, store_len)
+                .saturating_add(self.estimate_matches(second, store_len))
+                .min(store_len),
+        }
+    }
+
+    /// Estimates the share of the entries of a store matching a condition. Predicates on keys with
+    /// an index are estimated from the index and those on other keys from the share of a sample
+    /// of entries matching them. Predicates on different keys are assumed to be independent
+    #[tracing::instrument(skip(self, sample), fields(sample_len=sample.len()))]
+    pub(super) fn estimate_share(
+        &self,
+        condition: &PredicateCondition,
+        store_len: usize,
+        sample: &[&StoreValue],
+    ) -> f64 {
+        if store_len == 0 {
+            return 0.0;
+        }
+        let share = match condition {
+            PredicateCondition::Value(predicate) => {
+                match self.inner.pin().get(predicate.get_key()) {
+                    Some(index) => index.estimate_matches(predicate) as f64 / store_len as f64,
+                    None if sample.is_empty() => 0.0,
+                    None => {
+                        sample
+                            .iter()
+                            .filter(|store_value| self.matches_value(condition, store_value))
+                            .count() as f64
+                            / sample.len() as f64
+                    }
+                }
+            }
+            PredicateCondition::And(first, second) => {
+                self.estimate_share(first, store_len, sample)
+                    * self.estimate_share(second, store_len, sample)
+            }
+            PredicateCondition::Or(first, second) => {
+                let first = self.estimate_share(first, store_len, sample);
+                let second = self.estimate_share(second, store_len, sample);
+                first + second - first * second
+            }
+        };
+        share.min(1.0)
+    }
+
+    /// Checks a condition against the value of a single entry the same way `matches` would
+    #[tracing::instrument(skip(self))]
+    pub(super) fn matches_value(
+        &self,
+        condition: &PredicateCondition,
+        store_value: &StoreValue,
+    ) -> bool {
+        match condition {
+            PredicateCondition::Value(predicate) => {
+                let key = predicate.get_key();
+                match (predicate, store_value.get(key)) {
+                    (Predicate::Equals { value, .. }, Some(found)) => found == value,
+                    (Predicate::NotEquals { value, .. }, Some(found)) => found != value,
+                    (Predicate::In { value, .. }, Some(found)) => value.contains(found),
+                    (Predicate::NotIn { value, .. }, Some(found)) => !value.contains(found),
+                    (Predicate::Equals { .. } | Predicate::In { .. }, None) => false,
+                    // entries without the key are not in its index but a scan counts them as
+                    // different from any value
+                    (Predicate::NotEquals { .. } | Predicate::NotIn { .. }, None) => {
+                        !self.inner.pin().contains_key(key)
+                    }
+                }
+            }
+            PredicateCondition::And(first, second) => {
+                self.matches_value(first, store_value) && self.matches_value(second, store_value)
+            }
+            PredicateCondition::Or(first, second) => {
+                self.matches_value(first, store_value) || self.matches_value(second, store_value)
+            }
         }
     }
 
@@ -92,10 +276,14 @@ impl PredicateIndices {
     /// Removes a store key id when it's corresponding entry in the store is removed
     #[tracing::instrument(skip(self))]
     pub(super) fn remove_store_keys(&self, remove_keys: &[StoreKeyId]) {
+        if remove_keys.is_empty() {
+            return;
+        }
         let pinned = self.inner.pin();
         for (_, values) in pinned.iter() {
             values.remove_store_keys(remove_keys);
         }
+        self.touch(pinned.keys());
     }
 
     /// Removes predicates from being tracked
@@ -122,6 +310,7 @@ impl PredicateIndices {
         for predicate in predicates {
             let removed = pinned_keys.remove(&predicate);
             pinned_predicate_values.remove(&predicate);
+            self.last_updated.pin().remove(&predicate);
 
             if removed {
                 deleted += 1;
@@ -143,11 +332,13 @@ impl PredicateIndices {
         let pinned_inner = self.inner.pin();
         // `insert` implicity adds it to allowed_predicates which is what lets us to be able to
         // search again
-        let mut new_predicates = predicates
+        let new_predicates: Vec<_> = predicates
             .into_iter()
             .filter(|pred| pinned_keys.insert(pred.clone()))
             .unique()
-            .peekable();
+            .collect();
+        self.touch(new_predicates.iter());
+        let mut new_predicates = new_predicates.into_iter().peekable();
         // Only update for new predicates
         if let Some(new_values) = (new_predicates.peek().is_some())
             .then_some(refresh_with_values)
@@ -171,6 +362,50 @@ impl PredicateIndices {
         }
     }
 
+    /// Compares the index of a predicate with the values `expected` of it, leaving out the store
+    /// key ids the index is yet to catch up with
+    #[tracing::instrument(skip(self, expected, pending))]
+    pub(super) fn discrepancies(
+        &self,
+        predicate: &MetadataKey,
+        expected: StdHashSet<(MetadataValue, StoreKeyId)>,
+        pending: &StdHashSet<StoreKeyId>,
+    ) -> PredicateDiscrepancies {
+        let held: StdHashSet<_> = self
+            .inner
+            .pin()
+            .get(predicate)
+            .map(PredicateIndex::entries)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, store_key_id)| !pending.contains(store_key_id))
+            .collect();
+        PredicateDiscrepancies {
+            missing: expected.difference(&held).cloned().collect(),
+            orphaned: held.difference(&expected).cloned().collect(),
+        }
+    }
+
+    /// Adds what the index of a predicate is missing and removes what it should not hold. A
+    /// predicate that is no longer allowed is left alone
+    #[tracing::instrument(skip(self, discrepancies))]
+    pub(super) fn repair(&self, predicate: &MetadataKey, discrepancies: PredicateDiscrepancies) {
+        if !self.allowed_predicates.pin().contains(predicate) {
+            return;
+        }
+        let pinned = self.inner.pin();
+        if let Some(index) = pinned.get(predicate) {
+            index.remove(discrepancies.orphaned);
+        }
+        if !discrepancies.missing.is_empty() {
+            let pred = PredicateIndex::init(discrepancies.missing.clone());
+            if let Err(existing_predicate) = pinned.try_insert(predicate.clone(), pred) {
+                existing_predicate.current.add(discrepancies.missing);
+            }
+        }
+        self.touch(std::iter::once(predicate));
+    }
+
     /// Adds predicates if the key is within allowed_predicates
     #[tracing::instrument(skip(self))]
     pub(super) fn add(&self, new: Vec<(StoreKeyId, StoreValue)>) {
@@ -197,6 +432,7 @@ impl PredicateIndices {
                 acc
             });
 
+        self.touch(iter.keys());
         let predicate_values = self.inner.pin();
         for (key, val) in iter {
             // If there exists a predicate index as we want to update it, just add to that
@@ -215,6 +451,19 @@ impl PredicateIndices {
         condition: &PredicateCondition,
         // used to check original store for things that do not have predicate
         store: &Store,
+        deadline: Deadline,
+    ) -> Result<StdHashSet<StoreKeyId>, ServerError> {
+        match optimizer::optimize(condition) {
+            Some(condition) => self.matches_optimized(&condition, store, deadline),
+            None => Ok(StdHashSet::new()),
+        }
+    }
+
+    fn matches_optimized(
+        &self,
+        condition: &PredicateCondition,
+        store: &Store,
+        deadline: Deadline,
     ) -> Result<StdHashSet<StoreKeyId>, ServerError> {
         match condition {
             PredicateCondition::Value(main_predicate) => {
@@ -224,19 +473,43 @@ impl PredicateIndices {
                     // retrieve the precise predicate if it exists and check against it
                     return Ok(predicate.matches(main_predicate));
                 }
-                store.get_match_without_predicate(main_predicate)
+                store.get_match_without_predicate(main_predicate, deadline)
             }
-            PredicateCondition::And(first, second) => {
-                let first_result = self.matches(first, store)?;
-                let second_result = self.matches(second, store)?;
-                // Get intersection of both conditions
-                Ok(first_result.intersection(&second_result).cloned().collect())
+            PredicateCondition::And(..) => {
+                let store_len = store.len();
+                // start from the conditions expected to match the fewest entries so that the rest
+                // are checked against as few entries as possible
+                let mut conjuncts = optimizer::conjuncts_of(condition)
+                    .into_iter()
+                    .map(|conjunct| (self.estimate_matches(conjunct, store_len), conjunct))
+                    .sorted_by_key(|(estimate, _)| *estimate);
+                let Some((_, first)) = conjuncts.next() else {
+                    return Ok(StdHashSet::new());
+                };
+                let mut result = self.matches_optimized(first, store, deadline)?;
+                for (estimate, conjunct) in conjuncts {
+                    if result.is_empty() {
+                        break;
+                    }
+                    result = if result.len().saturating_mul(SCAN_OVER_INDEX_RATIO) < estimate {
+                        store.filter_ids(result, |store_value| {
+                            self.matches_value(conjunct, store_value)
+                        })
+                    } else {
+                        let conjunct_result = self.matches_optimized(conjunct, store, deadline)?;
+                        // Get intersection of both conditions
+                        result.intersection(&conjunct_result).cloned().collect()
+                    };
+                }
+                Ok(result)
             }
-            PredicateCondition::Or(first, second) => {
-                let first_result = self.matches(first, store)?;
-                let second_result = self.matches(second, store)?;
-                // Get union of both conditions
-                Ok(first_result.union(&second_result).cloned().collect())
+            PredicateCondition::Or(..) => {
+                let mut result = StdHashSet::new();
+                for disjunct in optimizer::disjuncts_of(condition) {
+                    // Get union of all conditions
+                    result.extend(self.matches_optimized(disjunct, store, deadline)?);
+                }
+                Ok(result)
             }
         }
     }
@@ -276,6 +549,15 @@ mod custom_metadata_map {
     }
 }
 
+/// Bytes held by a metadata value along with its contents
+pub(super) fn value_memory(value: &MetadataValue) -> usize {
+    size_of_val(value)
+        + match value {
+            MetadataValue::RawString(string) => string.len(),
+            MetadataValue::Image(bytes) => bytes.len(),
+        }
+}
+
 impl PredicateIndex {
     #[tracing::instrument(skip(self))]
     fn size(&self) -> usize {
@@ -306,6 +588,33 @@ impl PredicateIndex {
         }
     }
 
+    /// Removes store key ids from under the values they are held with
+    #[tracing::instrument(skip(self))]
+    fn remove(&self, stale: Vec<(MetadataValue, StoreKeyId)>) {
+        let inner = self.0.pin();
+        for (predicate_value, store_key_id) in stale {
+            if let Some(values) = inner.get(&predicate_value) {
+                values.pin().remove(&store_key_id);
+            }
+        }
+    }
+
+    /// Every value held by the index along with each store key id held with it
+    #[tracing::instrument(skip(self))]
+    fn entries(&self) -> StdHashSet<(MetadataValue, StoreKeyId)> {
+        self.0
+            .pin()
+            .iter()
+            .flat_map(|(predicate_value, store_key_ids)| {
+                store_key_ids
+                    .pin()
+                    .iter()
+                    .map(|store_key_id| (predicate_value.clone(), store_key_id.clone()))
+                    .collect_vec()
+            })
+            .collect()
+    }
+
     /// adds a store key id to the index using the predicate value
     /// TODO: Optimize stack consumption of this particular call as it seems to consume more than
     /// the default number when ran using Loom, this may cause an issue down the line
@@ -339,6 +648,33 @@ impl PredicateIndex {
             });
     }
 
+    /// Number of entries with each value, leaving out values whose entries were all removed
+    #[tracing::instrument(skip(self))]
+    fn value_counts(&self) -> Vec<(MetadataValue, usize)> {
+        self.0
+            .pin()
+            .iter()
+            .map(|(value, store_key_ids)| (value.clone(), store_key_ids.len()))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Number of entries matching a predicate on the key of the index
+    #[tracing::instrument(skip(self))]
+    fn estimate_matches(&self, predicate: &Predicate) -> usize {
+        let pinned = self.0.pin();
+        let count = |value: &MetadataValue| pinned.get(value).map_or(0, |ids| ids.len());
+        let entries = || pinned.values().map(|ids| ids.len()).sum::<usize>();
+        match predicate {
+            Predicate::Equals { value, .. } => count(value),
+            Predicate::In { value, .. } => value.iter().map(count).sum(),
+            Predicate::NotEquals { value, .. } => entries().saturating_sub(count(value)),
+            Predicate::NotIn { value, .. } => {
+                entries().saturating_sub(value.iter().map(count).sum())
+            }
+        }
+    }
+
     /// checks the predicate index for a predicate op and value. The return type is a StdHashSet<_>
     /// because we do not modify it at any point so we do not need concurrency protection
     #[tracing::instrument(skip(self))]
@@ -483,7 +819,14 @@ mod tests {
                 key: MetadataKey::new("name".into()),
                 value: MetadataValue::RawString("David".into()),
             }),
-            &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+            &Store::create(
+                NonZeroUsize::new(1).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                None,
+                None,
+            ),
+            Deadline::default(),
         );
         // We don't have an index but it should use original store and return empty
         assert!(result.unwrap().is_empty());
@@ -504,7 +847,14 @@ mod tests {
                     key: MetadataKey::new("name".into()),
                     value: MetadataValue::RawString("David".into()),
                 }),
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // Now we expect index to be up to date
@@ -525,7 +875,14 @@ mod tests {
                     key: MetadataKey::new("age".into()),
                     value: MetadataValue::RawString("14".into()),
                 }),
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // There are no entries where age is 14
@@ -536,7 +893,14 @@ mod tests {
                     key: MetadataKey::new("country".into()),
                     value: MetadataValue::RawString("Nigeria".into()),
                 }),
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // only person 1 is not from Nigeria
@@ -547,7 +911,14 @@ mod tests {
                     key: MetadataKey::new("country".into()),
                     value: MetadataValue::RawString("Nigeria".into()),
                 }),
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         assert_eq!(result, StdHashSet::from_iter(["0".into(), "2".into()]),);
@@ -562,7 +933,14 @@ mod tests {
         let result = shared_pred
             .matches(
                 &check,
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // only person 1 is from Washington
@@ -578,7 +956,14 @@ mod tests {
         let result = shared_pred
             .matches(
                 &check,
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // only person 1 is fulfills all
@@ -594,7 +979,14 @@ mod tests {
         let result = shared_pred
             .matches(
                 &check,
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // all 3 fulfill this
@@ -609,7 +1001,14 @@ mod tests {
         let result = shared_pred
             .matches(
                 &check,
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // only person 1 is from Washington with any of those names
@@ -623,7 +1022,14 @@ mod tests {
                     key: MetadataKey::new("country".into()),
                     value: MetadataValue::RawString("Nigeria".into()),
                 }),
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         assert!(result.is_empty());
@@ -634,7 +1040,14 @@ mod tests {
         let result = shared_pred
             .matches(
                 &check,
-                &Store::create(NonZeroUsize::new(1).unwrap(), vec![], StdHashSet::new()),
+                &Store::create(
+                    NonZeroUsize::new(1).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    None,
+                    None,
+                ),
+                Deadline::default(),
             )
             .unwrap();
         // only person 1 is from Washington with any of those names
@@ -772,4 +1185,64 @@ mod tests {
             0
         );
     }
+
+    #[test]
+    fn test_predicate_index_stats_and_estimates() {
+        let shared_pred = create_shared_predicate_indices(vec![
+            MetadataKey::new("country".into()),
+            MetadataKey::new("name".into()),
+        ]);
+        let stats = shared_pred.stats();
+        assert_eq!(
+            stats
+                .iter()
+                .map(|stat| stat.key.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                MetadataKey::new("country".into()),
+                MetadataKey::new("name".into())
+            ]
+        );
+        assert_eq!(stats[0].entries, 3);
+        assert_eq!(stats[0].distinct_values, 2);
+        assert_eq!(
+            stats[0].most_frequent,
+            vec![
+                (MetadataValue::RawString("Nigeria".into()), 2),
+                (MetadataValue::RawString("USA".into()), 1),
+            ]
+        );
+        assert!(stats[0].last_updated > UNIX_EPOCH);
+
+        let nigeria = PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("country".into()),
+            value: MetadataValue::RawString("Nigeria".into()),
+        });
+        let not_david = PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("name".into()),
+            value: MetadataValue::RawString("David".into()),
+        });
+        let plateau = PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("state".into()),
+            value: MetadataValue::RawString("Plateau".into()),
+        });
+        assert_eq!(shared_pred.estimate_matches(&nigeria, 4), 2);
+        assert_eq!(shared_pred.estimate_matches(&not_david, 4), 1);
+        // state has no index so every entry could match
+        assert_eq!(shared_pred.estimate_matches(&plateau, 4), 4);
+        assert_eq!(
+            shared_pred.estimate_matches(&nigeria.clone().and(not_david.clone()), 4),
+            1
+        );
+        assert_eq!(
+            shared_pred.estimate_matches(&nigeria.clone().or(plateau.clone()), 4),
+            4
+        );
+
+        assert!(
+            shared_pred.matches_value(&nigeria.clone().and(not_david.clone()), &store_value_2())
+        );
+        assert!(!shared_pred.matches_value(&nigeria.and(not_david), &store_value_0()));
+        assert!(shared_pred.matches_value(&plateau, &store_value_2()));
+    }
 }
@@ -0,0 +1,426 @@
+use super::store::{Store, StoreHandler};
+use crate::errors::QuotaResource;
+use crate::errors::ServerError;
+use ahnlich_types::db::NamespaceQuota;
+use ahnlich_types::db::NamespaceUsage;
+use ahnlich_types::keyval::StoreKey;
+use ahnlich_types::keyval::StoreName;
+use ahnlich_types::keyval::StoreValue;
+use itertools::Itertools;
+use std::mem::{size_of, size_of_val};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Vectors and estimated bytes of the entries the stores of a namespace hold together, kept up
+/// to date as they are written and deleted so that quotas are checked without going through the
+/// stores. Sets add what they reserve on top until their entries are written
+#[derive(Debug, Default)]
+pub(super) struct NamespaceCounts {
+    vectors: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl NamespaceCounts {
+    pub(super) fn add(&self, vectors: usize, bytes: usize) {
+        self.vectors.fetch_add(vectors, Ordering::SeqCst);
+        self.bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub(super) fn sub(&self, vectors: usize, bytes: usize) {
+        self.vectors.fetch_sub(vectors, Ordering::SeqCst);
+        self.bytes.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+/// Adds `amount` to a count unless that takes it over `limit`, returning the count it would have
+/// reached otherwise. The check and the add are a single compare and swap so that concurrent
+/// reservations cannot together go over the limit
+fn reserve(count: &AtomicUsize, amount: usize, limit: Option<usize>) -> Result<(), usize> {
+    count
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            let usage = current.saturating_add(amount);
+            limit.map_or(true, |limit| usage <= limit).then_some(usage)
+        })
+        .map(|_| ())
+        .map_err(|current| current.saturating_add(amount))
+}
+
+/// What a set has reserved of the quota of its namespace, given back once the set is done as
+/// its entries are then counted
+pub(super) struct QuotaReservation {
+    counts: Arc<NamespaceCounts>,
+    vectors: usize,
+    bytes: usize,
+}
+
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        self.counts.sub(self.vectors, self.bytes);
+    }
+}
+
+impl StoreHandler {
+    /// Matches SETQUOTA - replaces the quota of a namespace, an unbounded quota removes it
+    #[tracing::instrument(skip(self))]
+    pub fn set_quota(&self, namespace: String, quota: NamespaceQuota) {
+        let guard = self.quotas.guard();
+        if quota.is_unbounded() {
+            self.quotas.remove(&namespace, &guard);
+        } else {
+            self.quotas.insert(namespace, quota, &guard);
+        }
+    }
+
+    /// Matches LISTQUOTAS - returns the quota of every namespace with one along with what its
+    /// stores hold
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn list_quotas(&self) -> Vec<NamespaceUsage> {
+        self.quotas
+            .iter(&self.quotas.guard())
+            .map(|(namespace, quota)| {
+                let counts = self.counts(namespace);
+                NamespaceUsage {
+                    namespace: namespace.clone(),
+                    quota: *quota,
+                    stores: self.namespace_stores(namespace).len(),
+                    vectors: counts.vectors.load(Ordering::SeqCst),
+                    memory_bytes: counts.bytes.load(Ordering::SeqCst),
+                }
+            })
+            .sorted()
+            .collect()
+    }
+
+    fn quota<'a>(&self, store_name: &'a StoreName) -> Option<(&'a str, NamespaceQuota)> {
+        let namespace = store_name.namespace()?;
+        self.quotas
+            .get(namespace, &self.quotas.guard())
+            .map(|quota| (namespace, *quota))
+    }
+
+    fn namespace_stores(&self, namespace: &str) -> Vec<Arc<Store>> {
+        self.stores
+            .iter(&self.stores.guard())
+            .filter(|(store_name, _)| store_name.namespace() == Some(namespace))
+            .map(|(_, store)| store.clone())
+            .collect()
+    }
+
+    /// Counts of a namespace, created the first time they are needed
+    fn counts(&self, namespace: &str) -> Arc<NamespaceCounts> {
+        let guard = self.namespace_counts.guard();
+        if let Some(counts) = self.namespace_counts.get(namespace, &guard) {
+            return counts.clone();
+        }
+        match self
+            .namespace_counts
+            .try_insert(namespace.to_string(), Arc::default(), &guard)
+        {
+            Ok(counts) => counts.clone(),
+            Err(err) => err.current.clone(),
+        }
+    }
+
+    /// Counts a store is counted towards, none for stores outside of a namespace
+    pub(super) fn store_counts(&self, store_name: &StoreName) -> Option<Arc<NamespaceCounts>> {
+        store_name
+            .namespace()
+            .map(|namespace| self.counts(namespace))
+    }
+
+    /// Counts the entries of a store put in place towards its namespace from then on, such as a
+    /// restored store or one loaded from a snapshot
+    pub(super) fn count_store(&self, store_name: &StoreName, store: &Store) {
+        let Some(counts) = self.store_counts(store_name) else {
+            return;
+        };
+        // writes are held off so that none is left out of or counted twice in the totals
+        let _writing = store.writing.write().expect("store write lock poisoned");
+        let (vectors, bytes) = store.quota_totals();
+        counts.add(vectors, bytes);
+        *store
+            .namespace_counts
+            .lock()
+            .expect("namespace counts lock poisoned") = Some(counts);
+    }
+
+    /// Takes the entries of a dropped store out of the counts of its namespace
+    pub(super) fn uncount_store(&self, store: &Store) {
+        let _writing = store.writing.write().expect("store write lock poisoned");
+        let counts = store
+            .namespace_counts
+            .lock()
+            .expect("namespace counts lock poisoned")
+            .take();
+        if let Some(counts) = counts {
+            let (vectors, bytes) = store.quota_totals();
+            counts.sub(vectors, bytes);
+        }
+    }
+
+    /// Checks that a new store keeps its namespace within the store count of its quota
+    pub(super) fn check_store_quota(&self, store_name: &StoreName) -> Result<(), ServerError> {
+        let Some((
+            namespace,
+            NamespaceQuota {
+                max_stores: Some(limit),
+                ..
+            },
+        )) = self.quota(store_name)
+        else {
+            return Ok(());
+        };
+        let usage = self.namespace_stores(namespace).len() + 1;
+        if usage > limit {
+            return Err(ServerError::QuotaExceeded {
+                namespace: namespace.to_string(),
+                resource: QuotaResource::Stores,
+                usage,
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reserves what adding entries into a store may add to its namespace, keeping it within the
+    /// vectors and memory of its quota. The reservation is held until the entries are written
+    pub(super) fn reserve_set_quota(
+        &self,
+        store_name: &StoreName,
+        store: &Store,
+        new: &[(StoreKey, StoreValue)],
+    ) -> Result<Option<QuotaReservation>, ServerError> {
+        let Some((namespace, quota)) = self.quota(store_name) else {
+            return Ok(None);
+        };
+        let exceeded = |resource, usage, limit: Option<usize>| ServerError::QuotaExceeded {
+            namespace: namespace.to_string(),
+            resource,
+            usage,
+            limit: limit.unwrap_or_default(),
+        };
+        let counts = self.counts(namespace);
+        // updates of entries already held add no vectors, while their bytes are reserved in full
+        let vectors = quota.max_vectors.map_or(0, |_| store.count_new_keys(new));
+        let bytes = new
+            .iter()
+            .map(|(_, store_value)| store.quota_bytes(store_value))
+            .sum();
+        reserve(&counts.vectors, vectors, quota.max_vectors)
+            .map_err(|usage| exceeded(QuotaResource::Vectors, usage, quota.max_vectors))?;
+        if let Err(usage) = reserve(&counts.bytes, bytes, quota.max_memory_bytes) {
+            counts.vectors.fetch_sub(vectors, Ordering::SeqCst);
+            return Err(exceeded(
+                QuotaResource::MemoryBytes,
+                usage,
+                quota.max_memory_bytes,
+            ));
+        }
+        Ok(Some(QuotaReservation {
+            counts,
+            vectors,
+            bytes,
+        }))
+    }
+}
+
+impl Store {
+    /// Estimated bytes an entry counts for against the memory quota of its namespace, its vector
+    /// and metadata
+    pub(super) fn quota_bytes(&self, store_value: &StoreValue) -> usize {
+        self.dimension.get() * size_of::<f32>()
+            + store_value
+                .iter()
+                .map(|(key, value)| size_of_val(key) + size_of_val(value))
+                .sum::<usize>()
+    }
+
+    /// Counts the store is counted towards, none once it is dropped
+    pub(super) fn counted_in(&self) -> Option<Arc<NamespaceCounts>> {
+        self.namespace_counts
+            .lock()
+            .expect("namespace counts lock poisoned")
+            .clone()
+    }
+
+    /// Vectors and estimated bytes the entries of the store count for against the quota of its
+    /// namespace
+    fn quota_totals(&self) -> (usize, usize) {
+        let pinned = self.id_to_value.pin();
+        let bytes = pinned
+            .iter()
+            .map(|(_, entry)| self.quota_bytes(&entry.value))
+            .sum();
+        (pinned.len(), bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::StoreSettings;
+    use super::*;
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap as StdHashMap;
+    use std::collections::HashSet as StdHashSet;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_namespace_quotas() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        handler.set_quota(
+            "acme".into(),
+            NamespaceQuota {
+                max_stores: Some(2),
+                max_vectors: Some(10),
+                max_memory_bytes: None,
+            },
+        );
+        let create = |store: &str| {
+            handler.create_store(
+                StoreName(store.into()),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings::default(),
+                false,
+            )
+        };
+        create("acme/first").unwrap();
+        create("acme/second").unwrap();
+        // recreating an existing store adds nothing to the namespace
+        create("acme/second").unwrap();
+        assert_eq!(
+            create("acme/third"),
+            Err(ServerError::QuotaExceeded {
+                namespace: "acme".into(),
+                resource: QuotaResource::Stores,
+                usage: 3,
+                limit: 2,
+            })
+        );
+        // stores outside of the namespace are not counted
+        create("globex/first").unwrap();
+        create("acme").unwrap();
+
+        let entry = |i: usize| (StoreKey(array![i as f32, 1.0]), StdHashMap::new());
+        handler
+            .set_in_store(&StoreName("acme/first".into()), (0..6).map(entry).collect())
+            .unwrap();
+        let second = StoreName("acme/second".into());
+        assert_eq!(
+            handler.set_in_store(&second, (0..5).map(entry).collect()),
+            Err(ServerError::QuotaExceeded {
+                namespace: "acme".into(),
+                resource: QuotaResource::Vectors,
+                usage: 11,
+                limit: 10,
+            })
+        );
+        assert_eq!(handler.get(&second).unwrap().len(), 0);
+        handler
+            .set_in_store(&second, (0..4).map(entry).collect())
+            .unwrap();
+        // updates of entries already held do not count towards the quota
+        handler
+            .set_in_store(&second, (0..4).map(entry).collect())
+            .unwrap();
+
+        let usage = handler.list_quotas();
+        assert_eq!(usage.len(), 1);
+        assert_eq!((usage[0].stores, usage[0].vectors), (2, 10), "{usage:?}");
+
+        handler.set_quota(
+            "acme".into(),
+            NamespaceQuota {
+                max_memory_bytes: Some(usage[0].memory_bytes),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            handler.set_in_store(&second, vec![entry(4)]),
+            Err(ServerError::QuotaExceeded {
+                resource: QuotaResource::MemoryBytes,
+                ..
+            })
+        ));
+        handler.set_quota("acme".into(), NamespaceQuota::default());
+        assert!(handler.list_quotas().is_empty());
+        handler.set_in_store(&second, vec![entry(4)]).unwrap();
+    }
+
+    #[test]
+    fn test_namespace_quota_counts() {
+        let handler = Arc::new(StoreHandler::new(Arc::new(AtomicBool::new(false))));
+        handler.set_quota(
+            "acme".into(),
+            NamespaceQuota {
+                max_vectors: Some(100),
+                ..Default::default()
+            },
+        );
+        let stores: Vec<_> = (0..4).map(|i| StoreName(format!("acme/{i}"))).collect();
+        for store_name in &stores {
+            handler
+                .create_store(
+                    store_name.clone(),
+                    NonZeroUsize::new(2).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    StoreSettings::default(),
+                    false,
+                )
+                .unwrap();
+        }
+        let entry = |i: usize| (StoreKey(array![i as f32, 1.0]), StdHashMap::new());
+        // concurrent sets reserve against the same counts so together they stay within the quota
+        let inserted: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = stores
+                .iter()
+                .flat_map(|store_name| (0..10).map(move |batch| (store_name, batch)))
+                .map(|(store_name, batch)| {
+                    let handler = &handler;
+                    scope.spawn(move || {
+                        handler
+                            .set_in_store(
+                                store_name,
+                                (batch * 5..batch * 5 + 5).map(entry).collect(),
+                            )
+                            .map_or(0, |upsert| upsert.inserted)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .sum()
+        });
+        assert!(inserted <= 100, "{inserted}");
+        let held = |handler: &StoreHandler| -> usize {
+            stores
+                .iter()
+                .map(|store_name| handler.get(store_name).map_or(0, |store| store.len()))
+                .sum()
+        };
+        assert_eq!(held(&handler), inserted);
+        assert_eq!(handler.list_quotas()[0].vectors, inserted);
+
+        // deleted and dropped entries are taken off the counts
+        handler
+            .del_key_in_store(&stores[0], (0..50).map(|i| entry(i).0).collect())
+            .unwrap();
+        handler.drop_store(stores[1].clone(), true).unwrap();
+        let usage = handler.list_quotas();
+        assert_eq!(usage[0].vectors, held(&handler));
+        let memory_bytes = usage[0].memory_bytes;
+        handler
+            .set_in_store(
+                &stores[2],
+                (1000..1000 + 100 - held(&handler)).map(entry).collect(),
+            )
+            .unwrap();
+        assert!(handler.list_quotas()[0].memory_bytes > memory_bytes);
+        assert!(handler.set_in_store(&stores[3], vec![entry(2000)]).is_err());
+    }
+}
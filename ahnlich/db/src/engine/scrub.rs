@@ -0,0 +1,56 @@
+use super::store::{Store, StoreHandler, StoreKeyId};
+use super::vectors;
+use crate::errors::ServerError;
+use ahnlich_types::db::ListedEntry;
+use ahnlich_types::keyval::StoreName;
+use utils::parallel;
+
+impl StoreHandler {
+    /// Matches SCRUBSTORE - finds the entries of a store whose vectors hold NaN or infinite
+    /// values, such as those written before stores checked for them, deleting them when asked to
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn scrub_store(
+        &self,
+        store_name: &StoreName,
+        delete: bool,
+    ) -> Result<Vec<ListedEntry>, ServerError> {
+        if !delete {
+            return Ok(self.get(store_name)?.non_finite_entries());
+        }
+        let scrubbed = parallel::maintenance(|| {
+            self.write(store_name, |store| {
+                let scrubbed = store.non_finite_entries();
+                store.delete(
+                    scrubbed
+                        .iter()
+                        .map(|entry| StoreKeyId(entry.key_id.clone())),
+                );
+                Ok(scrubbed)
+            })
+        })?;
+        if !scrubbed.is_empty() {
+            self.set_write_flag();
+        }
+        Ok(scrubbed)
+    }
+}
+
+impl Store {
+    /// Entries whose vectors hold NaN or infinite values, ordered by key id
+    fn non_finite_entries(&self) -> Vec<ListedEntry> {
+        let pinned = self.id_to_value.pin();
+        let mut entries: Vec<_> = pinned
+            .iter()
+            .filter_map(|(key_id, entry)| {
+                let key = self.vector(&entry.vector);
+                (!vectors::is_finite(&key)).then(|| ListedEntry {
+                    key_id: key_id.0.clone(),
+                    key: Some(key),
+                    value: entry.value.clone(),
+                })
+            })
+            .collect();
+        entries.sort_unstable_by(|a, b| a.key_id.cmp(&b.key_id));
+        entries
+    }
+}
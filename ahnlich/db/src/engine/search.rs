@@ -0,0 +1,1420 @@
+use super::super::algorithm::{self, AlgorithmByType, FindSimilarN};
+use super::store::{SimilarEntries, Store, StoreHandler, StoreKeyId};
+use super::vectors;
+use crate::errors::ServerError;
+use ahnlich_types::keyval::StoreKey;
+use ahnlich_types::keyval::StoreName;
+use ahnlich_types::keyval::StoreValue;
+use ahnlich_types::metadata::MetadataKey;
+use ahnlich_types::metadata::MetadataValue;
+use ahnlich_types::predicate::PredicateCondition;
+use ahnlich_types::similarity::Algorithm;
+use ahnlich_types::similarity::FilterStrategy;
+use ahnlich_types::similarity::FusionStrategy;
+use ahnlich_types::similarity::RecencyBoost;
+use ahnlich_types::similarity::SearchTerm;
+use ahnlich_types::similarity::Similarity;
+use ahnlich_types::similarity::TermVector;
+use rayon::prelude::*;
+use std::collections::HashMap as StdHashMap;
+use std::collections::HashSet as StdHashSet;
+use std::num::NonZeroUsize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use utils::deadline::Deadline;
+
+/// Post-filtering first fetches this many times the entries expected to be needed for enough of
+/// them to match
+const POST_FILTER_OVER_FETCH: usize = 2;
+
+/// Share of the store that has to match a condition for `FilterStrategy::Auto` to post-filter.
+/// Below it too many fetched entries get dropped and searching only the matches is cheaper
+const AUTO_POST_FILTER_SELECTIVITY: f32 = 0.25;
+
+/// Optional ranking and post-processing of GETSIMN results
+#[derive(Debug, Clone)]
+pub struct GetSimNOptions {
+    /// Drop results scoring lower than this
+    pub min_score: Option<Similarity>,
+    /// Drop results further away than this
+    pub max_distance: Option<Similarity>,
+    /// Map scores to the 0-1 range where 1 is the most similar
+    pub normalize_scores: bool,
+    /// Group results by the value of this metadata key. Entries without the key share a group
+    pub group_by: Option<MetadataKey>,
+    /// Maximum number of results returned per group when `group_by` is set
+    pub group_size: NonZeroUsize,
+    /// Further search inputs whose results are fused with those of the main search input
+    pub additional_search_inputs: Vec<StoreKey>,
+    /// How scores are combined when there are additional search inputs
+    pub fusion: FusionStrategy,
+    /// Blend normalized scores with the recency of entries, read from the store's timestamp key
+    pub recency_boost: Option<RecencyBoost>,
+    /// How the predicate condition is applied when searching a non linear algorithm index
+    pub filter_strategy: FilterStrategy,
+    /// Vectors added to the main search input in proportion to their weights
+    pub search_terms: Vec<SearchTerm>,
+    /// Entries left out of the candidates before ranking, by key or key id
+    pub exclude_keys: Vec<TermVector>,
+    /// Skips ordering results that score the same by key hash
+    pub unordered_ties: bool,
+    /// Cuts predicate and linear scans short once it passes
+    pub deadline: Deadline,
+    /// Leaves the results out of the reads eviction policies go by, for searches the server
+    /// runs itself
+    pub untracked: bool,
+}
+
+impl Default for GetSimNOptions {
+    fn default() -> Self {
+        Self {
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::MIN,
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+            deadline: Deadline::default(),
+            untracked: false,
+        }
+    }
+}
+
+impl GetSimNOptions {
+    /// max_distance only makes sense for distance algorithms and min_score only makes sense for
+    /// similarity algorithms, except when scores are normalized as they all then mean the same
+    #[tracing::instrument(skip(self))]
+    fn validate(
+        &self,
+        algorithm: Algorithm,
+        algorithm_by_type: &AlgorithmByType,
+    ) -> Result<(), ServerError> {
+        if self.fusion == FusionStrategy::ReciprocalRankFusion
+            && !self.additional_search_inputs.is_empty()
+            && (self.min_score.is_some()
+                || self.max_distance.is_some()
+                || self.normalize_scores
+                || self.recency_boost.is_some())
+        {
+            return Err(ServerError::RankFusionScoreOptions);
+        }
+        if let Some(boost) = self.recency_boost {
+            if !(0.0..=1.0).contains(&boost.weight) {
+                return Err(ServerError::InvalidRecencyWeight(boost.weight.to_string()));
+            }
+        }
+        if let Some(term) = self
+            .search_terms
+            .iter()
+            .find(|term| !term.weight.is_finite())
+        {
+            return Err(ServerError::InvalidTermWeight(term.weight.to_string()));
+        }
+        if self.max_distance.is_some() && !algorithm_by_type.is_distance() {
+            return Err(ServerError::InvalidScoreThreshold {
+                threshold: "max_distance".to_string(),
+                algorithm,
+            });
+        }
+        if self.min_score.is_some() && algorithm_by_type.is_distance() && !self.normalize_scores {
+            return Err(ServerError::InvalidScoreThreshold {
+                threshold: "min_score".to_string(),
+                algorithm,
+            });
+        }
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        algorithm_by_type: &AlgorithmByType,
+        store_key: StoreKey,
+        score: f32,
+    ) -> Option<(StoreKey, f32)> {
+        if matches!(self.max_distance, Some(Similarity(max)) if score > max) {
+            return None;
+        }
+        let score = if self.normalize_scores {
+            algorithm_by_type.normalize_score(score)
+        } else {
+            score
+        };
+        if matches!(self.min_score, Some(Similarity(min)) if score < min) {
+            return None;
+        }
+        Some((store_key, score))
+    }
+}
+
+/// A GETSIMN whose options and search inputs were validated against the store it runs on, the
+/// search inputs conformed to the vectors of the store
+struct Search<'a> {
+    store: &'a Store,
+    search_input: StoreKey,
+    closest_n: NonZeroUsize,
+    algorithm_by_type: AlgorithmByType,
+    /// Algorithm the vectors of the store are ranked with, which differs from the one asked for
+    /// when the store holds normalized vectors
+    kernel: AlgorithmByType,
+    /// Boost along with the metadata key the timestamps of entries are read from
+    recency: Option<(RecencyBoost, MetadataKey)>,
+    options: GetSimNOptions,
+}
+
+/// Entries a search ranks along with their norms
+struct Candidates {
+    entries: Vec<(StoreKey, StoreValue, f32)>,
+    /// Set when the candidates are every entry of the store
+    used_all: bool,
+    /// Position of each candidate within the entries by key id
+    positions: StdHashMap<StoreKeyId, usize>,
+}
+
+impl Candidates {
+    fn new(entries: Vec<(StoreKey, StoreValue, f32)>, used_all: bool) -> Self {
+        let positions = entries
+            .iter()
+            .enumerate()
+            .map(|(position, (store_key, _, _))| (StoreKeyId::from(store_key), position))
+            .collect();
+        Self {
+            entries,
+            used_all,
+            positions,
+        }
+    }
+
+    fn value(&self, store_key: &StoreKey) -> Option<&StoreValue> {
+        self.positions
+            .get(&StoreKeyId::from(store_key))
+            .map(|position| &self.entries[*position].1)
+    }
+
+    /// Value of the metadata key results are grouped by for a candidate
+    fn group_of(
+        &self,
+        group_by: Option<&MetadataKey>,
+        store_key: &StoreKey,
+    ) -> Option<MetadataValue> {
+        group_by.and_then(|group_by| {
+            self.value(store_key)
+                .and_then(|store_value| store_value.get(group_by))
+                .cloned()
+        })
+    }
+}
+
+impl<'a> Search<'a> {
+    /// Checks the options and search inputs, adding the search terms to the search input and
+    /// conforming the search inputs to the store
+    #[tracing::instrument(skip(store, options))]
+    fn validate(
+        store: &'a Store,
+        store_name: &StoreName,
+        mut search_input: StoreKey,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        mut options: GetSimNOptions,
+    ) -> Result<Self, ServerError> {
+        let algorithm_by_type: AlgorithmByType = algorithm.into();
+        options.validate(algorithm, &algorithm_by_type)?;
+        let recency = options
+            .recency_boost
+            .map(|boost| {
+                store
+                    .timestamp_key
+                    .clone()
+                    .map(|timestamp_key| (boost, timestamp_key))
+                    .ok_or_else(|| ServerError::TimestampKeyNotSet(store_name.clone()))
+            })
+            .transpose()?;
+        if !options.search_terms.is_empty() {
+            search_input = store.add_terms(store_name, search_input, &options.search_terms)?;
+        }
+        // the search input comes first followed by the additional search inputs
+        store.check_dimensions(
+            store_name,
+            std::iter::once(&search_input).chain(&options.additional_search_inputs),
+        )?;
+        store.check_finite(
+            store_name,
+            std::iter::once(&search_input).chain(&options.additional_search_inputs),
+        )?;
+        search_input = store.sanitize(search_input);
+        options.additional_search_inputs = std::mem::take(&mut options.additional_search_inputs)
+            .into_iter()
+            .map(|search_input| store.sanitize(search_input))
+            .collect();
+        store.check_norms(
+            store_name,
+            std::iter::once(&search_input).chain(&options.additional_search_inputs),
+        )?;
+        let kernel = store.kernel(algorithm_by_type);
+        if kernel != algorithm_by_type {
+            search_input = vectors::normalize(search_input);
+            options.additional_search_inputs =
+                std::mem::take(&mut options.additional_search_inputs)
+                    .into_iter()
+                    .map(vectors::normalize)
+                    .collect();
+        }
+        Ok(Self {
+            store,
+            search_input,
+            closest_n,
+            algorithm_by_type,
+            kernel,
+            recency,
+            options,
+        })
+    }
+
+    /// Entries matching the condition, leaving out the excluded keys
+    #[tracing::instrument(skip_all)]
+    fn candidates(
+        &self,
+        condition: Option<&PredicateCondition>,
+    ) -> Result<Candidates, ServerError> {
+        let (mut entries, mut used_all) = match condition {
+            Some(condition) => (
+                self.store
+                    .get_matches_with_norms(condition, self.options.deadline)?,
+                false,
+            ),
+            None => (self.store.get_all_with_norms(), true),
+        };
+        if !self.options.exclude_keys.is_empty() {
+            let excluded: StdHashSet<StoreKeyId> = self
+                .options
+                .exclude_keys
+                .iter()
+                .map(|key| match key {
+                    TermVector::Key(store_key) => (&self.store.conform(store_key.clone())).into(),
+                    TermVector::KeyId(key_id) => StoreKeyId(key_id.clone()),
+                })
+                .collect();
+            let candidates = entries.len();
+            entries.retain(|(store_key, _, _)| !excluded.contains(&StoreKeyId::from(store_key)));
+            // non linear indices are then searched among the candidates left
+            used_all &= entries.len() == candidates;
+        }
+        Ok(Candidates::new(entries, used_all))
+    }
+
+    /// Ranks the candidates against the search inputs, fusing the rankings of additional search
+    /// inputs and limiting the results per group. With a recency boost every candidate is ranked
+    /// as results are only limited once boosted
+    #[tracing::instrument(skip_all)]
+    fn rank(&self, candidates: &Candidates) -> Result<Vec<(StoreKey, f32)>, ServerError> {
+        let options = &self.options;
+        let filtered = &candidates.entries;
+        let store_len = self.store.len().max(filtered.len());
+        let post_filter = !candidates.used_all
+            && match options.filter_strategy {
+                FilterStrategy::Pre => false,
+                FilterStrategy::Post => true,
+                FilterStrategy::Auto => {
+                    filtered.len() as f32 / store_len as f32 >= AUTO_POST_FILTER_SELECTIVITY
+                }
+            };
+        let non_linear_indices = self.store.non_linear_indices.algorithm_to_index.pin();
+        let ordered_ties = !options.unordered_ties;
+        let find_similar_n = |search_input: &StoreKey, n: NonZeroUsize| {
+            let filtered_iter = filtered.iter().map(|(key, _, _)| key);
+            match self.kernel {
+                AlgorithmByType::Linear(linear_algo) => Ok(linear_algo.find_similar_n_with_norms(
+                    search_input,
+                    options
+                        .deadline
+                        .bound(filtered.iter().map(|(key, _, norm)| (key, *norm))),
+                    n,
+                    ordered_ties,
+                )),
+                AlgorithmByType::NonLinear(non_linear_algo) => non_linear_indices
+                    .get(&non_linear_algo)
+                    .ok_or(ServerError::NonLinearIndexNotFound(non_linear_algo))
+                    .map(|non_linear_index_with_algo| {
+                        let mut ranking = if post_filter {
+                            // expect a share of the fetched entries as large as the share of
+                            // the store that matches to pass the filter
+                            let fetch = n
+                                .get()
+                                .saturating_mul(store_len)
+                                .div_ceil(filtered.len())
+                                .saturating_mul(POST_FILTER_OVER_FETCH)
+                                .min(store_len);
+                            non_linear_index_with_algo.find_similar_n_post_filtered(
+                                search_input,
+                                n,
+                                NonZeroUsize::new(fetch).unwrap_or(n),
+                                store_len,
+                                |store_key| {
+                                    candidates
+                                        .positions
+                                        .contains_key(&StoreKeyId::from(store_key))
+                                },
+                            )
+                        } else {
+                            non_linear_index_with_algo.find_similar_n(
+                                search_input,
+                                filtered_iter,
+                                candidates.used_all,
+                                n,
+                            )
+                        };
+                        // indices return ties in the order they come across them
+                        if ordered_ties {
+                            algorithm::order_ties(&mut ranking);
+                        }
+                        ranking
+                    }),
+            }
+        };
+        let group_of =
+            |store_key: &StoreKey| candidates.group_of(options.group_by.as_ref(), store_key);
+        // used whenever every candidate has to be ranked before results can be limited
+        let all_candidates = NonZeroUsize::new(filtered.len()).unwrap_or(self.closest_n);
+        // recency can lift any candidate above more similar ones, so every candidate is ranked
+        // and results are only grouped and limited once boosted
+        let (limit, group_by) = match self.recency {
+            Some(_) => (all_candidates, None),
+            None => (self.closest_n, options.group_by.as_ref()),
+        };
+
+        if !options.additional_search_inputs.is_empty() {
+            let rankings = std::iter::once(&self.search_input)
+                .chain(&options.additional_search_inputs)
+                .map(|input| find_similar_n(input, all_candidates))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut fused = algorithm::fuse_rankings(
+                rankings,
+                |store_key| StoreKeyId::from(store_key),
+                options.fusion,
+                self.algorithm_by_type.is_distance(),
+            );
+            if ordered_ties {
+                algorithm::order_ties(&mut fused);
+            }
+            return Ok(match group_by {
+                Some(_) => algorithm::limit_per_group(fused, group_of, limit, options.group_size),
+                None => fused.into_iter().take(limit.get()).collect(),
+            });
+        }
+        Ok(match (self.kernel, group_by) {
+            (_, None) => find_similar_n(&self.search_input, limit)?,
+            (AlgorithmByType::Linear(linear_algo), Some(group_by)) => linear_algo
+                .find_similar_n_grouped(
+                    &self.search_input,
+                    options.deadline.bound(filtered.iter().map(
+                        |(store_key, store_value, norm)| {
+                            ((store_key, *norm), store_value.get(group_by))
+                        },
+                    )),
+                    limit,
+                    options.group_size,
+                    ordered_ties,
+                ),
+            // non linear indices cannot rank per group so rank every candidate and limit each
+            // group afterwards
+            (AlgorithmByType::NonLinear(_), Some(_)) => algorithm::limit_per_group(
+                find_similar_n(&self.search_input, all_candidates)?,
+                group_of,
+                limit,
+                options.group_size,
+            ),
+        })
+    }
+
+    /// Applies the score options to the ranking, boosts it by recency and pairs the results
+    /// with their values
+    #[tracing::instrument(skip_all)]
+    fn post_process(
+        &self,
+        mut candidates: Candidates,
+        ranking: Vec<(StoreKey, f32)>,
+    ) -> SimilarEntries {
+        let options = &self.options;
+        let results = ranking.into_iter().filter_map(|(store_key, score)| {
+            options.apply(&self.algorithm_by_type, store_key, score)
+        });
+        let results: Vec<_> = match &self.recency {
+            Some((boost, timestamp_key)) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0.0, |since_epoch| since_epoch.as_secs_f64());
+                let age_of = |store_key: &StoreKey| {
+                    candidates
+                        .value(store_key)
+                        .and_then(|store_value| store_value.get(timestamp_key))
+                        .and_then(|timestamp| match timestamp {
+                            MetadataValue::RawString(timestamp) => timestamp.trim().parse().ok(),
+                            MetadataValue::Image(_) => None,
+                        })
+                        .map(|timestamp: f64| now - timestamp)
+                };
+                let normalized = results.map(|(store_key, score)| {
+                    if options.normalize_scores {
+                        (store_key, score)
+                    } else {
+                        (store_key, self.algorithm_by_type.normalize_score(score))
+                    }
+                });
+                let mut boosted = algorithm::boost_recency(normalized, age_of, *boost);
+                if !options.unordered_ties {
+                    algorithm::order_ties(&mut boosted);
+                }
+                match &options.group_by {
+                    Some(group_by) => algorithm::limit_per_group(
+                        boosted,
+                        |store_key| candidates.group_of(Some(group_by), store_key),
+                        self.closest_n,
+                        options.group_size,
+                    ),
+                    None => boosted.into_iter().take(self.closest_n.get()).collect(),
+                }
+            }
+            None => results.collect(),
+        };
+
+        results
+            .into_iter()
+            .flat_map(|(store_key, similarity)| {
+                let position = candidates.positions.remove(&StoreKeyId::from(&store_key))?;
+                let value = candidates.entries[position].1.clone();
+                Some((store_key, value, Similarity(similarity)))
+            })
+            .collect()
+    }
+}
+
+impl StoreHandler {
+    /// Matches GETSIMN - gets all similar from a store that also match a predicate
+    #[tracing::instrument(skip(self))]
+    pub fn get_sim_in_store(
+        &self,
+        store_name: &StoreName,
+        search_input: StoreKey,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+        options: GetSimNOptions,
+    ) -> Result<Vec<(StoreKey, StoreValue, Similarity)>, ServerError> {
+        let store = self.get(store_name)?;
+        let search = Search::validate(
+            &store,
+            store_name,
+            search_input,
+            closest_n,
+            algorithm,
+            options,
+        )?;
+        let candidates = search.candidates(condition.as_ref())?;
+        // early stopping: predicate filters everything out so no need to search
+        if candidates.entries.is_empty() {
+            return Ok(vec![]);
+        }
+        let ranking = search.rank(&candidates)?;
+        // a scan cut short by the deadline ranked only some of the candidates
+        search.options.deadline.check()?;
+        let results = search.post_process(candidates, ranking);
+        if !search.options.untracked {
+            store.touch_read(results.iter().map(|(store_key, ..)| store_key));
+        }
+        Ok(results)
+    }
+
+    /// Matches GETSIMNBYKEY - searches with the vector of the entry with a key id, leaving the
+    /// entry out of the results
+    #[tracing::instrument(skip(self, options))]
+    pub(crate) fn get_sim_by_key_in_store(
+        &self,
+        store_name: &StoreName,
+        key_id: &str,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+        mut options: GetSimNOptions,
+    ) -> Result<Vec<(StoreKey, StoreValue, Similarity)>, ServerError> {
+        let seed = self.get(store_name)?.vector_of(store_name, key_id)?;
+        options
+            .exclude_keys
+            .push(TermVector::KeyId(key_id.to_string()));
+        self.get_sim_in_store(store_name, seed, closest_n, algorithm, condition, options)
+    }
+
+    /// Matches BATCHGETSIMN - runs a search for each search input in parallel, sharing the
+    /// candidates matching the condition between them
+    #[tracing::instrument(skip(self, search_inputs), fields(search_inputs_length=search_inputs.len()))]
+    pub(crate) fn batch_get_sim_in_store(
+        &self,
+        store_name: &StoreName,
+        search_inputs: Vec<StoreKey>,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+        deadline: Deadline,
+    ) -> Result<Vec<SimilarEntries>, ServerError> {
+        let algorithm_by_type: AlgorithmByType = algorithm.into();
+        let store = self.get(store_name)?;
+        store.check_dimensions(store_name, &search_inputs)?;
+        store.check_finite(store_name, &search_inputs)?;
+        let search_inputs: Vec<_> = search_inputs
+            .into_iter()
+            .map(|search_input| store.sanitize(search_input))
+            .collect();
+        store.check_norms(store_name, &search_inputs)?;
+        let kernel = store.kernel(algorithm_by_type);
+        let search_inputs = if kernel != algorithm_by_type {
+            search_inputs.into_iter().map(vectors::normalize).collect()
+        } else {
+            search_inputs
+        };
+
+        let (filtered, used_all) = if let Some(ref condition) = condition {
+            (store.get_matches_with_norms(condition, deadline)?, false)
+        } else {
+            (store.get_all_with_norms(), true)
+        };
+        if filtered.is_empty() {
+            return Ok(vec![vec![]; search_inputs.len()]);
+        }
+        let keys_to_value_map: StdHashMap<StoreKeyId, &StoreValue> = StdHashMap::from_iter(
+            filtered
+                .iter()
+                .map(|(store_key, store_value, _)| (StoreKeyId::from(store_key), store_value)),
+        );
+        let non_linear_indices = store.non_linear_indices.algorithm_to_index.pin();
+        let non_linear_index = match kernel {
+            AlgorithmByType::Linear(_) => None,
+            AlgorithmByType::NonLinear(non_linear_algo) => Some(
+                non_linear_indices
+                    .get(&non_linear_algo)
+                    .ok_or(ServerError::NonLinearIndexNotFound(non_linear_algo))?,
+            ),
+        };
+        let rankings: Vec<_> = search_inputs
+            .par_iter()
+            .map(|search_input| match (kernel, non_linear_index) {
+                (AlgorithmByType::Linear(linear_algo), _) => linear_algo.find_similar_n_with_norms(
+                    search_input,
+                    deadline.bound(filtered.iter().map(|(key, _, norm)| (key, *norm))),
+                    closest_n,
+                    true,
+                ),
+                (_, Some(non_linear_index)) => {
+                    let mut ranking = non_linear_index.find_similar_n(
+                        search_input,
+                        filtered.iter().map(|(key, _, _)| key),
+                        used_all,
+                        closest_n,
+                    );
+                    algorithm::order_ties(&mut ranking);
+                    ranking
+                }
+                // the index of a non linear algorithm is found before searching
+                (AlgorithmByType::NonLinear(_), None) => vec![],
+            })
+            .collect();
+        // a scan cut short by the deadline ranked only some of the candidates
+        deadline.check()?;
+
+        let rankings: Vec<Vec<_>> = rankings
+            .into_iter()
+            .map(|ranking| {
+                ranking
+                    .into_iter()
+                    .flat_map(|(store_key, similarity)| {
+                        let value = keys_to_value_map.get(&StoreKeyId::from(&store_key))?;
+                        Some((store_key, (*value).clone(), Similarity(similarity)))
+                    })
+                    .collect()
+            })
+            .collect();
+        store.touch_read(rankings.iter().flatten().map(|(store_key, ..)| store_key));
+        Ok(rankings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::tests::create_store_handler_no_loom;
+    use super::super::store::StoreSettings;
+    use super::*;
+    use crate::tests::*;
+    use ahnlich_types::predicate::Predicate;
+    use ahnlich_types::similarity::NonLinearAlgorithm;
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_get_sim_in_store_with_predicate() {
+        let vectors = word_to_vector();
+
+        let input_arr_1 = vectors.get(MOST_SIMILAR[0]).unwrap();
+        let input_arr_2 = vectors.get(MOST_SIMILAR[1]).unwrap();
+        let input_arr_3 = vectors.get(MOST_SIMILAR[2]).unwrap();
+
+        let handler = create_store_handler_no_loom(
+            vec![MetadataKey::new("rank".into())],
+            Some(input_arr_1.0.len()),
+            Some(input_arr_1.0.len()),
+        );
+        let even_store = StoreName("Even".into());
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    input_arr_1.clone(),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("rank".into()),
+                        MetadataValue::RawString("Chunin".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    input_arr_2.clone(),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("rank".into()),
+                        MetadataValue::RawString("Chunin".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    input_arr_3.clone(),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("rank".into()),
+                        MetadataValue::RawString("Genin".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        let condition = &PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("rank".into()),
+            value: MetadataValue::RawString("Chunin".into()),
+        });
+        let search_input = StoreKey(vectors.get(SEACH_TEXT).unwrap().0.clone());
+        let algorithm = Algorithm::CosineSimilarity;
+
+        let closest_n = NonZeroUsize::new(3).unwrap();
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                algorithm,
+                Some(condition.clone()),
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 2);
+
+        let closest_n = NonZeroUsize::new(1).unwrap();
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                algorithm,
+                None,
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert!(res[0].0 == *vectors.get(MOST_SIMILAR[0]).unwrap());
+
+        let condition = &PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("rank".into()),
+            value: MetadataValue::RawString("Chunin".into()),
+        });
+        let closest_n = NonZeroUsize::new(3).unwrap();
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                algorithm,
+                Some(condition.clone()),
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 1);
+
+        // Add more items storekeys into the store for processing.
+        //
+        let meta_data_key = MetadataKey::new("english".into());
+        let store_values = vectors
+            .iter()
+            .filter(|(sentence, _)| SEACH_TEXT != *sentence)
+            .map(|(sentence, store_key)| {
+                let value: StdHashMap<MetadataKey, MetadataValue> = StdHashMap::from_iter(vec![(
+                    meta_data_key.clone(),
+                    MetadataValue::RawString(sentence.into()),
+                )]);
+                (store_key.clone(), value)
+            })
+            .collect();
+        handler.set_in_store(&even_store, store_values).unwrap();
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                Algorithm::EuclideanDistance,
+                None,
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+
+        assert_eq!(res.len(), 3);
+
+        assert_eq!(
+            res[0].1.get(&meta_data_key).cloned().unwrap(),
+            MetadataValue::RawString(MOST_SIMILAR[0].into())
+        );
+        assert_eq!(
+            res[1].1.get(&meta_data_key).cloned().unwrap(),
+            MetadataValue::RawString(MOST_SIMILAR[1].into())
+        );
+        assert_eq!(
+            res[2].1.get(&meta_data_key).cloned().unwrap(),
+            MetadataValue::RawString(MOST_SIMILAR[2].into())
+        );
+    }
+
+    #[test]
+    fn test_get_sim_in_store_with_search_terms() {
+        let handler = create_store_handler_no_loom(vec![], Some(2), Some(2));
+        let even_store = StoreName("Even".into());
+        let word = |name: &str| {
+            StdHashMap::from_iter([(
+                MetadataKey::new("word".into()),
+                MetadataValue::RawString(name.into()),
+            )])
+        };
+        let king = StoreKey(array![1.0, 1.0]);
+        let man = StoreKey(array![1.0, 0.0]);
+        let woman = StoreKey(array![0.0, 0.2]);
+        let queen = StoreKey(array![0.1, 1.2]);
+        handler
+            .set_in_store(
+                &even_store,
+                vec![
+                    (king.clone(), word("king")),
+                    (man.clone(), word("man")),
+                    (woman.clone(), word("woman")),
+                    (queen.clone(), word("queen")),
+                ],
+            )
+            .unwrap();
+        let search = |search_input: StoreKey, search_terms: Vec<SearchTerm>| {
+            handler.get_sim_in_store(
+                &even_store,
+                search_input,
+                NonZeroUsize::new(1).unwrap(),
+                Algorithm::EuclideanDistance,
+                None,
+                GetSimNOptions {
+                    search_terms,
+                    ..Default::default()
+                },
+            )
+        };
+        let key_id = |store_key: &StoreKey| String::from(StoreKeyId::from(store_key));
+        // king - man + woman
+        let res = search(
+            king.clone(),
+            vec![
+                SearchTerm {
+                    vector: TermVector::KeyId(key_id(&man)),
+                    weight: -1.0,
+                },
+                SearchTerm {
+                    vector: TermVector::Key(woman.clone()),
+                    weight: 1.0,
+                },
+            ],
+        )
+        .unwrap();
+        assert_eq!(res[0].1, word("queen"));
+        // centroid of man, woman and queen is closest to woman
+        let res = search(
+            StoreKey(array![0.0, 0.0]),
+            [&man, &woman, &queen]
+                .into_iter()
+                .map(|store_key| SearchTerm {
+                    vector: TermVector::KeyId(key_id(store_key)),
+                    weight: 1.0 / 3.0,
+                })
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(res[0].1, word("woman"));
+
+        let missing = StoreKey(array![5.0, 5.0]);
+        assert_eq!(
+            search(
+                king.clone(),
+                vec![SearchTerm {
+                    vector: TermVector::KeyId(key_id(&missing)),
+                    weight: 1.0,
+                }],
+            )
+            .unwrap_err(),
+            ServerError::KeyIdNotFound {
+                store: even_store.clone(),
+                key_id: key_id(&missing),
+            }
+        );
+        assert_eq!(
+            search(
+                king.clone(),
+                vec![SearchTerm {
+                    vector: TermVector::Key(StoreKey(array![1.0, 0.0, 0.0])),
+                    weight: 1.0,
+                }],
+            )
+            .unwrap_err(),
+            ServerError::StoreDimensionMismatch {
+                store: even_store.clone(),
+                store_dimension: 2,
+                input_dimension: 3,
+                index: 1,
+            }
+        );
+        assert_eq!(
+            search(
+                king,
+                vec![SearchTerm {
+                    vector: TermVector::Key(man),
+                    weight: f32::NAN,
+                }],
+            )
+            .unwrap_err(),
+            ServerError::InvalidTermWeight("NaN".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_sim_by_key_in_store() {
+        let handler = create_store_handler_no_loom(vec![], Some(2), Some(2));
+        let even_store = StoreName("Even".into());
+        let word = |name: &str| {
+            StdHashMap::from_iter([(
+                MetadataKey::new("word".into()),
+                MetadataValue::RawString(name.into()),
+            )])
+        };
+        let cat = StoreKey(array![1.0, 1.0]);
+        handler
+            .set_in_store(
+                &even_store,
+                vec![
+                    (cat.clone(), word("cat")),
+                    (StoreKey(array![1.0, 1.2]), word("kitten")),
+                    (StoreKey(array![1.4, 1.0]), word("lion")),
+                    (StoreKey(array![8.0, 9.0]), word("car")),
+                ],
+            )
+            .unwrap();
+        let key_id = String::from(StoreKeyId::from(&cat));
+        let search = |closest_n: usize, condition: Option<PredicateCondition>| {
+            handler.get_sim_by_key_in_store(
+                &even_store,
+                &key_id,
+                NonZeroUsize::new(closest_n).unwrap(),
+                Algorithm::EuclideanDistance,
+                condition,
+                GetSimNOptions::default(),
+            )
+        };
+        let words = |res: Vec<(StoreKey, StoreValue, Similarity)>| {
+            res.into_iter()
+                .map(|(_, value, _)| value)
+                .collect::<Vec<_>>()
+        };
+        // the seed entry is left out of its own results
+        assert_eq!(
+            words(search(2, None).unwrap()),
+            vec![word("kitten"), word("lion")]
+        );
+        assert_eq!(words(search(4, None).unwrap()).len(), 3);
+        // as is a condition leaving the seed out
+        let not_kitten = PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("word".into()),
+            value: MetadataValue::RawString("kitten".into()),
+        });
+        assert_eq!(
+            words(search(2, Some(not_kitten)).unwrap()),
+            vec![word("lion"), word("car")]
+        );
+        let only_car = PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("word".into()),
+            value: MetadataValue::RawString("car".into()),
+        });
+        assert_eq!(words(search(2, Some(only_car)).unwrap()), vec![word("car")]);
+
+        assert_eq!(
+            handler
+                .get_sim_by_key_in_store(
+                    &even_store,
+                    "af1349b9",
+                    NonZeroUsize::new(1).unwrap(),
+                    Algorithm::EuclideanDistance,
+                    None,
+                    GetSimNOptions::default(),
+                )
+                .unwrap_err(),
+            ServerError::KeyIdNotFound {
+                store: even_store.clone(),
+                key_id: "af1349b9".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_sim_in_store_with_score_options() {
+        let handler = create_store_handler_no_loom(vec![], Some(2), Some(2));
+        let even_store = StoreName("Even".into());
+        handler
+            .set_in_store(
+                &even_store,
+                vec![
+                    (StoreKey(array![1.0, 0.0]), StdHashMap::new()),
+                    (StoreKey(array![0.0, 1.0]), StdHashMap::new()),
+                    (StoreKey(array![-1.0, 0.0]), StdHashMap::new()),
+                ],
+            )
+            .unwrap();
+        let search_input = StoreKey(array![1.0, 0.0]);
+        let closest_n = NonZeroUsize::new(3).unwrap();
+
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                Algorithm::CosineSimilarity,
+                None,
+                GetSimNOptions {
+                    min_score: Some(Similarity(0.0)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(res.len(), 2);
+
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                Algorithm::CosineSimilarity,
+                None,
+                GetSimNOptions {
+                    normalize_scores: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let scores: Vec<_> = res.into_iter().map(|(_, _, score)| score).collect();
+        assert_eq!(
+            scores,
+            vec![Similarity(1.0), Similarity(0.5), Similarity(0.0)]
+        );
+
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                Algorithm::EuclideanDistance,
+                None,
+                GetSimNOptions {
+                    max_distance: Some(Similarity(1.5)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(res.len(), 2);
+
+        let res = handler
+            .get_sim_in_store(
+                &even_store,
+                search_input.clone(),
+                closest_n,
+                Algorithm::EuclideanDistance,
+                None,
+                GetSimNOptions {
+                    min_score: Some(Similarity(0.4)),
+                    normalize_scores: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].2, Similarity(1.0));
+
+        let res = handler.get_sim_in_store(
+            &even_store,
+            search_input.clone(),
+            closest_n,
+            Algorithm::EuclideanDistance,
+            None,
+            GetSimNOptions {
+                min_score: Some(Similarity(0.4)),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ServerError::InvalidScoreThreshold {
+                threshold: "min_score".to_string(),
+                algorithm: Algorithm::EuclideanDistance,
+            }
+        );
+
+        let res = handler.get_sim_in_store(
+            &even_store,
+            search_input,
+            closest_n,
+            Algorithm::CosineSimilarity,
+            None,
+            GetSimNOptions {
+                max_distance: Some(Similarity(1.0)),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ServerError::InvalidScoreThreshold {
+                threshold: "max_distance".to_string(),
+                algorithm: Algorithm::CosineSimilarity,
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_sim_in_store_with_recency_boost() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let news_store = StoreName("News".into());
+        let published = MetadataKey::new("published".into());
+        handler
+            .create_store(
+                news_store.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings {
+                    timestamp_key: Some(published.clone()),
+                    ..Default::default()
+                },
+                true,
+            )
+            .unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let published_at = |seconds: u64| {
+            StdHashMap::from_iter([(
+                published.clone(),
+                MetadataValue::RawString(seconds.to_string()),
+            )])
+        };
+        handler
+            .set_in_store(
+                &news_store,
+                vec![
+                    // the most similar entry is ten days old
+                    (StoreKey(array![1.0, 0.0]), published_at(now - 10 * 86400)),
+                    (StoreKey(array![0.0, 1.0]), published_at(now)),
+                    (StoreKey(array![-1.0, 0.0]), StdHashMap::new()),
+                ],
+            )
+            .unwrap();
+        let search_input = StoreKey(array![1.0, 0.0]);
+        let boost = RecencyBoost {
+            half_life: std::num::NonZeroU64::new(86400).unwrap(),
+            weight: 0.5,
+        };
+
+        let res = handler
+            .get_sim_in_store(
+                &news_store,
+                search_input.clone(),
+                NonZeroUsize::new(1).unwrap(),
+                Algorithm::CosineSimilarity,
+                None,
+                GetSimNOptions {
+                    recency_boost: Some(boost),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].0, StoreKey(array![0.0, 1.0]));
+        assert!((res[0].2 .0 - 0.75).abs() < 1e-3);
+
+        let res = handler.get_sim_in_store(
+            &news_store,
+            search_input.clone(),
+            NonZeroUsize::new(1).unwrap(),
+            Algorithm::CosineSimilarity,
+            None,
+            GetSimNOptions {
+                recency_boost: Some(RecencyBoost {
+                    weight: 1.5,
+                    ..boost
+                }),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ServerError::InvalidRecencyWeight("1.5".to_string())
+        );
+
+        let other_handler = create_store_handler_no_loom(vec![], Some(2), Some(2));
+        let even_store = StoreName("Even".into());
+        let res = other_handler.get_sim_in_store(
+            &even_store,
+            search_input,
+            NonZeroUsize::new(1).unwrap(),
+            Algorithm::CosineSimilarity,
+            None,
+            GetSimNOptions {
+                recency_boost: Some(boost),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            res.unwrap_err(),
+            ServerError::TimestampKeyNotSet(even_store)
+        );
+    }
+
+    #[test]
+    fn test_get_sim_in_store_with_filter_strategies() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Filtered".into());
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
+            )
+            .unwrap();
+        let parity = MetadataKey::new("parity".into());
+        // the entries nearest to the origin are all odd so post-filtering has to fetch more
+        let entries = (0..40)
+            .map(|i| {
+                let value = if i < 20 || i % 2 == 1 { "odd" } else { "even" };
+                (
+                    StoreKey(array![i as f32, 0.0]),
+                    StdHashMap::from_iter([(
+                        parity.clone(),
+                        MetadataValue::RawString(value.into()),
+                    )]),
+                )
+            })
+            .collect();
+        handler.set_in_store(&store_name, entries).unwrap();
+        let condition = PredicateCondition::Value(Predicate::Equals {
+            key: parity,
+            value: MetadataValue::RawString("even".into()),
+        });
+
+        let results: Vec<_> = [
+            FilterStrategy::Pre,
+            FilterStrategy::Post,
+            FilterStrategy::Auto,
+        ]
+        .into_iter()
+        .map(|filter_strategy| {
+            handler
+                .get_sim_in_store(
+                    &store_name,
+                    StoreKey(array![0.0, 0.0]),
+                    NonZeroUsize::new(3).unwrap(),
+                    Algorithm::KDTree,
+                    Some(condition.clone()),
+                    GetSimNOptions {
+                        filter_strategy,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|(store_key, _, _)| store_key)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+        let expected = vec![
+            StoreKey(array![20.0, 0.0]),
+            StoreKey(array![22.0, 0.0]),
+            StoreKey(array![24.0, 0.0]),
+        ];
+        assert_eq!(results, vec![expected.clone(), expected.clone(), expected]);
+    }
+
+    #[test]
+    fn test_get_sim_in_store_with_exclusions() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Seen".into());
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
+            )
+            .unwrap();
+        let entries = (0..10)
+            .map(|i| (StoreKey(array![i as f32, 0.0]), StdHashMap::new()))
+            .collect();
+        handler.set_in_store(&store_name, entries).unwrap();
+        let exclude_keys = vec![
+            TermVector::Key(StoreKey(array![0.0, 0.0])),
+            TermVector::KeyId(StoreKeyId::from(&StoreKey(array![2.0, 0.0])).into()),
+        ];
+
+        // the entries left are ranked in place of those excluded
+        for algorithm in [Algorithm::EuclideanDistance, Algorithm::KDTree] {
+            let results: Vec<_> = handler
+                .get_sim_in_store(
+                    &store_name,
+                    StoreKey(array![0.0, 0.0]),
+                    NonZeroUsize::new(3).unwrap(),
+                    algorithm,
+                    None,
+                    GetSimNOptions {
+                        exclude_keys: exclude_keys.clone(),
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|(store_key, _, _)| store_key)
+                .collect();
+            assert_eq!(
+                results,
+                vec![
+                    StoreKey(array![1.0, 0.0]),
+                    StoreKey(array![3.0, 0.0]),
+                    StoreKey(array![4.0, 0.0]),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_batch_get_sim_in_store() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Batch".into());
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![MetadataKey::new("parity".into())],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
+            )
+            .unwrap();
+        let entries = (0..20)
+            .map(|i| {
+                let parity = if i % 2 == 0 { "even" } else { "odd" };
+                (
+                    StoreKey(array![i as f32, (i % 3) as f32]),
+                    StdHashMap::from_iter([(
+                        MetadataKey::new("parity".into()),
+                        MetadataValue::RawString(parity.into()),
+                    )]),
+                )
+            })
+            .collect();
+        handler.set_in_store(&store_name, entries).unwrap();
+        let search_inputs = vec![
+            StoreKey(array![0.0, 0.0]),
+            StoreKey(array![9.2, 0.4]),
+            StoreKey(array![30.0, 0.0]),
+        ];
+        let even = PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("parity".into()),
+            value: MetadataValue::RawString("even".into()),
+        });
+
+        // each search input gets the same results as a search of its own
+        for algorithm in [Algorithm::EuclideanDistance, Algorithm::KDTree] {
+            for condition in [None, Some(even.clone())] {
+                let batched = handler
+                    .batch_get_sim_in_store(
+                        &store_name,
+                        search_inputs.clone(),
+                        NonZeroUsize::new(2).unwrap(),
+                        algorithm,
+                        condition.clone(),
+                        Deadline::default(),
+                    )
+                    .unwrap();
+                let single: Vec<_> = search_inputs
+                    .iter()
+                    .map(|search_input| {
+                        handler
+                            .get_sim_in_store(
+                                &store_name,
+                                search_input.clone(),
+                                NonZeroUsize::new(2).unwrap(),
+                                algorithm,
+                                condition.clone(),
+                                GetSimNOptions {
+                                    filter_strategy: FilterStrategy::Pre,
+                                    ..Default::default()
+                                },
+                            )
+                            .unwrap()
+                    })
+                    .collect();
+                assert_eq!(batched, single);
+            }
+        }
+        assert_eq!(
+            handler
+                .batch_get_sim_in_store(
+                    &store_name,
+                    vec![StoreKey(array![0.0, 0.0]), StoreKey(array![0.0])],
+                    NonZeroUsize::new(2).unwrap(),
+                    Algorithm::EuclideanDistance,
+                    None,
+                    Deadline::default(),
+                )
+                .unwrap_err(),
+            ServerError::StoreDimensionMismatch {
+                store: store_name,
+                store_dimension: 2,
+                input_dimension: 1,
+                index: 1,
+            }
+        );
+    }
+}
@@ -2,36 +2,87 @@ use crate::errors::ServerError;
 use rayon::prelude::*;
 
 use super::super::algorithm::non_linear::NonLinearAlgorithmIndices;
-use super::super::algorithm::{AlgorithmByType, FindSimilarN};
+use super::super::algorithm::{self, AlgorithmByType, LinearAlgorithm};
+use super::benchmark;
+use super::eviction::EntryAccess;
 use super::predicate::PredicateIndices;
+use super::predicate::{self, PredicateDiscrepancies};
+use super::quota::NamespaceCounts;
+use super::search::GetSimNOptions;
+use super::trash::Trash;
+use super::vectors::{self, DiscardedFiles, DiskVectors, VectorRef};
+use ahnlich_types::db::DBQuery;
+use ahnlich_types::db::EntryPage;
+use ahnlich_types::db::IndexCheck;
+use ahnlich_types::db::ListedEntry;
+use ahnlich_types::db::ManifestChange;
+use ahnlich_types::db::ManifestDrift;
+use ahnlich_types::db::MemoryBreakdown;
+use ahnlich_types::db::NamespaceQuota;
+use ahnlich_types::db::SearchPath;
+use ahnlich_types::db::SettingDrift;
+use ahnlich_types::db::StoreBenchmark;
+use ahnlich_types::db::StoreCheck;
+use ahnlich_types::db::StoreCompaction;
+use ahnlich_types::db::StoreDescription;
+use ahnlich_types::db::StoreIndex;
 use ahnlich_types::db::StoreInfo;
+use ahnlich_types::db::StoreManifest;
+use ahnlich_types::db::StoreMemory;
 use ahnlich_types::db::StoreUpsert;
+use ahnlich_types::keyval::KeyElementType;
+use ahnlich_types::keyval::NonFiniteVectors;
+use ahnlich_types::keyval::StorageTier;
+use ahnlich_types::keyval::StoreEviction;
 use ahnlich_types::keyval::StoreKey;
 use ahnlich_types::keyval::StoreName;
 use ahnlich_types::keyval::StoreValue;
+use ahnlich_types::keyval::VectorNormalization;
 use ahnlich_types::metadata::MetadataKey;
 use ahnlich_types::predicate::Predicate;
 use ahnlich_types::predicate::PredicateCondition;
 use ahnlich_types::similarity::Algorithm;
 use ahnlich_types::similarity::NonLinearAlgorithm;
+use ahnlich_types::similarity::SearchTerm;
 use ahnlich_types::similarity::Similarity;
+use ahnlich_types::similarity::TermVector;
 use flurry::HashMap as ConcurrentHashMap;
+use itertools::Itertools;
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, SeqAccess, Visitor};
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use serde::Serializer;
 use std::collections::HashMap as StdHashMap;
 use std::collections::HashSet as StdHashSet;
 use std::mem::size_of_val;
 use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
-use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
 use std::sync::Arc;
-use utils::persistence::AhnlichPersistenceUtils;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use utils::deadline::Deadline;
+use utils::limits::LimitHandler;
+use utils::parallel;
+use utils::persistence::{
+    AhnlichPersistenceUtils, PersistenceTaskError, SnapshotReader, SnapshotSections, SnapshotWriter,
+};
 /// A hash of Store key, this is more preferable when passing around references as arrays can be
 /// potentially larger
 /// We should be only able to generate a store key id from a 1D vector except during tests
 
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
-pub(crate) struct StoreKeyId(String);
+pub(crate) struct StoreKeyId(pub(super) String);
+
+impl StoreKeyId {
+    /// Bytes held by the id along with its contents
+    pub(super) fn memory(&self) -> usize {
+        size_of_val(self) + self.0.len()
+    }
+}
 
 #[cfg(test)]
 impl From<String> for StoreKeyId {
@@ -49,28 +100,100 @@ impl From<&str> for StoreKeyId {
 
 impl From<&StoreKey> for StoreKeyId {
     fn from(value: &StoreKey) -> Self {
-        // compute a fast blake hash of the vector to ensure it always gives us the same value
-        // and use that as a reference to the vector
-        let mut hasher = blake3::Hasher::new();
-        for element in value.0.iter() {
-            let bytes = element.to_ne_bytes();
-            hasher.update(&bytes);
-        }
-        let result = hasher.finalize();
-        Self(format!("{result}"))
+        // the hash of the vector always gives us the same value so it is used as a reference
+        // to the vector
+        Self(blake3::Hash::from(algorithm::key_hash(value)).to_string())
+    }
+}
+
+impl From<StoreKeyId> for String {
+    fn from(value: StoreKeyId) -> Self {
+        value.0
+    }
+}
+
+impl StoreKeyId {
+    /// Evenly spread number for the key id that differs with the seed
+    fn mix(&self, seed: u64) -> u64 {
+        let hash = self
+            .0
+            .get(..16)
+            .and_then(|prefix| u64::from_str_radix(prefix, 16).ok())
+            .unwrap_or_default();
+        // splitmix64 finalizer
+        let mut mixed = hash ^ seed;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d049bb133111eb);
+        mixed ^ (mixed >> 31)
     }
 }
 
+/// Seed of a store created without one, which is the same for a store of the same name on every
+/// server
+fn default_index_seed(store_name: &StoreName) -> u64 {
+    let hash = blake3::hash(store_name.0.as_bytes());
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&hash.as_bytes()[..8]);
+    u64::from_le_bytes(seed)
+}
+
+/// Entries an approximate count samples to estimate predicates on keys without an index. Stores
+/// with at most this many entries are counted exactly
+const COUNT_SAMPLE_SIZE: usize = 1024;
+
+/// Results each search of a BENCHMARKSTORE asks for
+const BENCHMARK_CLOSEST_N: usize = 10;
+
+/// Rough bytes an entry takes beyond its vector, for the key id and the metadata it holds
+const ENTRY_OVERHEAD: usize = 256;
+
+/// Optional settings a store is created with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoreSettings {
+    /// Metadata key holding the Unix timestamp in seconds of each entry
+    pub timestamp_key: Option<MetadataKey>,
+    pub storage_tier: StorageTier,
+    /// Ignore the dimension given and take that of the first keys set into the store
+    pub infer_dimension: bool,
+    pub key_element_type: KeyElementType,
+    pub normalization: VectorNormalization,
+    /// Seeds the sampling of the store, derived from the store name when not given
+    pub index_seed: Option<u64>,
+    pub non_finite_vectors: NonFiniteVectors,
+    pub eviction: Option<StoreEviction>,
+}
+
 /// Contains all the stores that have been created in memory
 #[derive(Debug)]
 pub struct StoreHandler {
     /// Making use of a concurrent hashmap, we should be able to create an engine that manages stores
-    stores: Stores,
+    pub(super) stores: Stores,
     pub write_flag: Arc<AtomicBool>,
+    /// Where disk tier stores keep their vectors, without which they cannot be created
+    vector_storage: Option<VectorStorage>,
+    /// Caps on what the stores of a namespace hold together, keyed by namespace
+    pub(super) quotas: ConcurrentHashMap<String, NamespaceQuota>,
+    /// What the stores of each namespace hold together, which sets reserve against its quota
+    pub(super) namespace_counts: ConcurrentHashMap<String, Arc<NamespaceCounts>>,
+    /// Dropped stores, kept for `trash_retention` after they were dropped so that they can be
+    /// restored. Stores are dropped for good when there is no retention
+    pub(super) trash: Trash,
+    pub(super) trash_retention: Option<Duration>,
+    /// Vector files of discarded stores kept until a snapshot leaving them out is persisted. None
+    /// when the stores are not persisted, in which case the files go with their stores
+    pub(super) discarded_files: Option<Arc<DiscardedFiles>>,
+}
+
+/// Directory holding the vector files of disk tier stores and the bytes of vectors each of them
+/// caches in memory
+#[derive(Debug, Clone)]
+struct VectorStorage {
+    location: PathBuf,
+    cache_size: usize,
 }
 
 impl AhnlichPersistenceUtils for StoreHandler {
-    type PersistenceObject = Stores;
+    type PersistenceObject = StoresSnapshot;
 
     #[tracing::instrument(skip_all)]
     fn write_flag(&self) -> Arc<AtomicBool> {
@@ -79,20 +202,159 @@ impl AhnlichPersistenceUtils for StoreHandler {
 
     #[tracing::instrument(skip(self))]
     fn get_snapshot(&self) -> Self::PersistenceObject {
-        self.stores.clone()
+        StoresSnapshot {
+            stores: self.stores.clone(),
+            trash: self.trash.clone(),
+            discarded_files: self.discarded_files.clone(),
+        }
     }
 }
 
 pub type Stores = Arc<ConcurrentHashMap<StoreName, Arc<Store>>>;
 
+/// Entries a similarity search found along with their scores, most similar first
+pub type SimilarEntries = Vec<(StoreKey, StoreValue, Similarity)>;
+
+/// The stores and trash that are persisted, written as a pair. Snapshots from before stores
+/// were trashed are a map of the stores alone and load with an empty trash
+#[derive(Debug, Clone)]
+pub struct StoresSnapshot {
+    stores: Stores,
+    trash: Trash,
+    /// Vector files of discarded stores, removed once a snapshot leaving them out is persisted
+    discarded_files: Option<Arc<DiscardedFiles>>,
+}
+
+impl Serialize for StoresSnapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.stores, &self.trash).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StoresSnapshot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SnapshotVisitor;
+
+        impl<'de> Visitor<'de> for SnapshotVisitor {
+            type Value = StoresSnapshot;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("stores along with the trash or a map of stores")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let stores = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let trash = seq.next_element()?.unwrap_or_default();
+                Ok(StoresSnapshot {
+                    stores,
+                    trash,
+                    discarded_files: None,
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                Ok(StoresSnapshot {
+                    stores: Stores::deserialize(MapAccessDeserializer::new(map))?,
+                    trash: Trash::default(),
+                    discarded_files: None,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(SnapshotVisitor)
+    }
+}
+
+/// Every store and trashed store is a section of its own, named `store/<name>` and
+/// `trash/<name>` respectively
+impl SnapshotSections for StoresSnapshot {
+    fn write_sections<W: std::io::Write>(
+        &self,
+        writer: &mut SnapshotWriter<W>,
+    ) -> Result<(), PersistenceTaskError> {
+        for (name, store) in self.stores.iter(&self.stores.guard()) {
+            writer.section(format!("store/{name}"), store)?;
+        }
+        for (name, trashed) in self.trash.iter(&self.trash.guard()) {
+            writer.section(format!("trash/{name}"), trashed)?;
+        }
+        Ok(())
+    }
+
+    fn read_sections<R: std::io::BufRead>(
+        reader: &mut SnapshotReader<R>,
+    ) -> Result<Self, PersistenceTaskError> {
+        let stores = Stores::default();
+        let trash = Trash::default();
+        while let Some(section) = reader.next_section()? {
+            // a store that cannot be loaded, such as one whose vector file is missing, is left
+            // out rather than failing the other stores
+            match section.name.split_once('/') {
+                Some(("store", name)) => match section.parse() {
+                    Ok(store) => {
+                        stores.insert(StoreName(name.to_string()), store, &stores.guard());
+                    }
+                    Err(err) => log::error!("Skipping store {name} of snapshot: {err}"),
+                },
+                Some(("trash", name)) => match section.parse() {
+                    Ok(trashed) => {
+                        trash.insert(StoreName(name.to_string()), trashed, &trash.guard());
+                    }
+                    Err(err) => log::error!("Skipping trashed store {name} of snapshot: {err}"),
+                },
+                _ => log::warn!("Skipping unknown section {} of snapshot", section.name),
+            }
+        }
+        Ok(Self {
+            stores,
+            trash,
+            discarded_files: None,
+        })
+    }
+
+    fn writing(&self) {
+        if let Some(discarded_files) = &self.discarded_files {
+            discarded_files.snapshot_begun();
+        }
+    }
+
+    fn persisted(&self) {
+        if let Some(discarded_files) = &self.discarded_files {
+            discarded_files.snapshot_persisted();
+        }
+    }
+}
+
 impl StoreHandler {
     pub fn new(write_flag: Arc<AtomicBool>) -> Self {
         Self {
             stores: Arc::new(ConcurrentHashMap::new()),
             write_flag,
+            vector_storage: None,
+            quotas: ConcurrentHashMap::new(),
+            namespace_counts: ConcurrentHashMap::new(),
+            trash: Arc::new(ConcurrentHashMap::new()),
+            trash_retention: None,
+            discarded_files: None,
         }
     }
 
+    /// Keeps the vector files of discarded stores until a snapshot leaving them out is persisted,
+    /// as the snapshot persisted last loads them
+    pub fn keep_discarded_files(&mut self) {
+        self.discarded_files = Some(Arc::default());
+    }
+
+    /// Allows disk tier stores, whose vectors are kept in files within the location
+    pub fn use_vector_storage(&mut self, location: PathBuf, cache_size: usize) {
+        self.vector_storage = Some(VectorStorage {
+            location,
+            cache_size,
+        });
+    }
+
     #[tracing::instrument(skip(self))]
     pub(crate) fn get_stores(&self) -> Stores {
         self.stores.clone()
@@ -104,20 +366,28 @@ impl StoreHandler {
     }
 
     #[tracing::instrument(skip(self))]
-    fn set_write_flag(&self) {
+    pub(super) fn set_write_flag(&self) {
         let _ = self
             .write_flag
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst);
     }
 
     #[tracing::instrument(skip(self))]
-    pub(crate) fn use_snapshot(&mut self, stores_snapshot: Stores) {
-        self.stores = stores_snapshot;
+    pub(crate) fn use_snapshot(&mut self, snapshot: StoresSnapshot) {
+        self.stores = snapshot.stores;
+        self.trash = snapshot.trash;
+        for (store_name, store) in self.stores.pin().iter() {
+            store.recount_bytes();
+            self.count_store(store_name, store);
+        }
+        for trashed in self.trash.pin().values() {
+            trashed.store.recount_bytes();
+        }
     }
 
     /// Returns a store using the store name, else returns an error
     #[tracing::instrument(skip(self))]
-    fn get(&self, store_name: &StoreName) -> Result<Arc<Store>, ServerError> {
+    pub(super) fn get(&self, store_name: &StoreName) -> Result<Arc<Store>, ServerError> {
         let store = self
             .stores
             .get(store_name, &self.stores.guard())
@@ -126,6 +396,26 @@ impl StoreHandler {
         Ok(store)
     }
 
+    /// Runs a write against a store, waiting for a compaction that is swapping the store out so
+    /// that the write lands in the compacted store instead
+    #[tracing::instrument(skip_all)]
+    pub(super) fn write<T>(
+        &self,
+        store_name: &StoreName,
+        write: impl FnOnce(&Store) -> Result<T, ServerError>,
+    ) -> Result<T, ServerError> {
+        loop {
+            let store = self.get(store_name)?;
+            let _writing = store.writing.read().expect("store write lock poisoned");
+            if store.retired.load(Ordering::SeqCst) {
+                continue;
+            }
+            let written = write(&store);
+            store.version.fetch_add(1, Ordering::SeqCst);
+            return written;
+        }
+    }
+
     /// Matches CREATEPREDINDEX - reindexes a store with some predicate values
     #[tracing::instrument(skip(self))]
     pub(crate) fn create_pred_index(
@@ -133,8 +423,9 @@ impl StoreHandler {
         store_name: &StoreName,
         predicates: Vec<MetadataKey>,
     ) -> Result<usize, ServerError> {
-        let store = self.get(store_name)?;
-        let created_predicates = store.create_pred_index(predicates);
+        let created_predicates = parallel::maintenance(|| {
+            self.write(store_name, |store| Ok(store.create_pred_index(predicates)))
+        })?;
         if created_predicates > 0 {
             self.set_write_flag()
         }
@@ -148,8 +439,11 @@ impl StoreHandler {
         store_name: &StoreName,
         non_linear_indices: StdHashSet<NonLinearAlgorithm>,
     ) -> Result<usize, ServerError> {
-        let store = self.get(store_name)?;
-        let created_predicates = store.create_non_linear_algorithm_index(non_linear_indices);
+        let created_predicates = parallel::maintenance(|| {
+            self.write(store_name, |store| {
+                Ok(store.create_non_linear_algorithm_index(non_linear_indices))
+            })
+        })?;
         if created_predicates > 0 {
             self.set_write_flag()
         }
@@ -163,8 +457,10 @@ impl StoreHandler {
         store_name: &StoreName,
         keys: Vec<StoreKey>,
     ) -> Result<usize, ServerError> {
-        let store = self.get(store_name)?;
-        let deleted = store.delete_keys(keys.clone())?;
+        let deleted = self.write(store_name, |store| {
+            store.check_dimensions(store_name, &keys)?;
+            Ok(store.delete_keys(keys))
+        })?;
         if deleted > 0 {
             self.set_write_flag();
         };
@@ -173,97 +469,105 @@ impl StoreHandler {
 
     /// Matches DELPRED - removes keys from a store when value matches predicate
     #[tracing::instrument(skip(self))]
-    pub(crate) fn del_pred_in_store(
+    pub fn del_pred_in_store(
         &self,
         store_name: &StoreName,
         condition: &PredicateCondition,
     ) -> Result<usize, ServerError> {
-        let store = self.get(store_name)?;
-        let deleted = store.delete_matches(condition)?;
+        let deleted = self.write(store_name, |store| store.delete_matches(condition))?;
         if deleted > 0 {
             self.set_write_flag();
         };
         Ok(deleted)
     }
 
-    /// Matches GETSIMN - gets all similar from a store that also match a predicate
+    /// Gets the ids of every entry in a store that matches a predicate, used to validate
+    /// DELPREDASYNC before the entries are deleted in the background
     #[tracing::instrument(skip(self))]
-    pub fn get_sim_in_store(
+    pub(crate) fn get_pred_ids_in_store(
         &self,
         store_name: &StoreName,
-        search_input: StoreKey,
-        closest_n: NonZeroUsize,
-        algorithm: Algorithm,
-        condition: Option<PredicateCondition>,
-    ) -> Result<Vec<(StoreKey, StoreValue, Similarity)>, ServerError> {
+        condition: &PredicateCondition,
+    ) -> Result<Vec<StoreKeyId>, ServerError> {
         let store = self.get(store_name)?;
-        let store_dimension = store.dimension.get();
-        let input_dimension = search_input.dimension();
-
-        if input_dimension != store_dimension {
-            return Err(ServerError::StoreDimensionMismatch {
-                store_dimension,
-                input_dimension,
-            });
-        }
+        Ok(store
+            .predicate_indices
+            .matches(condition, &store, Deadline::default())?
+            .into_iter()
+            .collect())
+    }
 
-        let (filtered, used_all) = if let Some(ref condition) = condition {
-            (store.get_matches(condition)?, false)
-        } else {
-            (store.get_all(), true)
+    /// Deletes a batch of entries by id, returning the number that were still present
+    #[tracing::instrument(skip(self, ids), fields(ids_length=ids.len()))]
+    pub(crate) fn del_ids_in_store(
+        &self,
+        store_name: &StoreName,
+        ids: Vec<StoreKeyId>,
+    ) -> Result<usize, ServerError> {
+        let deleted = self.write(store_name, |store| Ok(store.delete(ids.into_iter())))?;
+        if deleted > 0 {
+            self.set_write_flag();
         };
+        Ok(deleted)
+    }
 
-        // early stopping: predicate filters everything out so no need to search
-        if filtered.is_empty() {
-            return Ok(vec![]);
-        }
-
-        let filtered_iter = filtered.iter().map(|(key, _)| key);
+    /// Matches GETPRED - gets all matching predicates from a store
+    #[tracing::instrument(skip(self))]
+    pub fn get_pred_in_store(
+        &self,
+        store_name: &StoreName,
+        condition: &PredicateCondition,
+        deadline: Deadline,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
+        let store = self.get(store_name)?;
+        let entries = store.get_matches(condition, deadline)?;
+        store.touch_read(entries.iter().map(|(store_key, _)| store_key));
+        Ok(entries)
+    }
 
-        let algorithm_by_type: AlgorithmByType = algorithm.into();
-        let similar_result = match algorithm_by_type {
-            AlgorithmByType::Linear(linear_algo) => {
-                linear_algo.find_similar_n(&search_input, filtered_iter, used_all, closest_n)
-            }
-            AlgorithmByType::NonLinear(non_linear_algo) => {
-                let non_linear_indices = store.non_linear_indices.algorithm_to_index.pin();
-                let non_linear_index_with_algo = non_linear_indices
-                    .get(&non_linear_algo)
-                    .ok_or(ServerError::NonLinearIndexNotFound(non_linear_algo))?;
-                non_linear_index_with_algo.find_similar_n(
-                    &search_input,
-                    filtered_iter,
-                    used_all,
-                    closest_n,
-                )
-            }
+    /// Entries of a store to export along with the dimension of their keys, only those matching
+    /// the condition when one is given
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn export_entries(
+        &self,
+        store_name: &StoreName,
+        condition: Option<&PredicateCondition>,
+    ) -> Result<(NonZeroUsize, Vec<(StoreKey, StoreValue)>), ServerError> {
+        let store = self.get(store_name)?;
+        let entries = match condition {
+            Some(condition) => store.get_matches(condition, Deadline::default())?,
+            None => store.get_all(),
         };
-
-        let mut keys_to_value_map: StdHashMap<StoreKeyId, &StoreValue> = StdHashMap::from_iter(
-            filtered
-                .iter()
-                .map(|(store_key, store_value)| (StoreKeyId::from(store_key), store_value)),
-        );
-
-        Ok(similar_result
-            .into_iter()
-            .flat_map(|(store_key, similarity)| {
-                keys_to_value_map
-                    .remove(&StoreKeyId::from(&store_key))
-                    .map(|value| (store_key, value.clone(), Similarity(similarity)))
-            })
-            .collect())
+        Ok((store.dimension, entries))
     }
 
-    /// Matches GETPRED - gets all matching predicates from a store
+    /// Matches COUNTPRED - counts the entries of a store matching a predicate, or estimates it
+    /// unless exact
     #[tracing::instrument(skip(self))]
-    pub(crate) fn get_pred_in_store(
+    pub(crate) fn count_pred_in_store(
         &self,
         store_name: &StoreName,
         condition: &PredicateCondition,
-    ) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
+        exact: bool,
+        deadline: Deadline,
+    ) -> Result<usize, ServerError> {
+        let store = self.get(store_name)?;
+        store.count_matches(condition, exact, deadline)
+    }
+
+    /// Matches LISTENTRIES - gets a page of the entries of a store after the cursor, only those
+    /// matching the condition when one is given
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn list_entries(
+        &self,
+        store_name: &StoreName,
+        limit: NonZeroUsize,
+        cursor: Option<&str>,
+        include_vectors: bool,
+        condition: Option<&PredicateCondition>,
+    ) -> Result<EntryPage, ServerError> {
         let store = self.get(store_name)?;
-        store.get_matches(condition)
+        store.list(limit, cursor, include_vectors, condition)
     }
 
     /// Matches GETKEY - gets all keys matching the inputs
@@ -274,7 +578,43 @@ impl StoreHandler {
         keys: Vec<StoreKey>,
     ) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
         let store = self.get(store_name)?;
-        store.get_keys(keys)
+        store.check_dimensions(store_name, &keys)?;
+        let entries = store.get_keys(keys);
+        store.touch_read(entries.iter().map(|(store_key, _)| store_key));
+        Ok(entries)
+    }
+
+    /// Matches GETKEY for clients held to a filter - gets the keys matching the inputs whose
+    /// values match the condition
+    #[tracing::instrument(skip(self, keys), fields(key_length=keys.len()))]
+    pub(crate) fn get_key_matching_in_store(
+        &self,
+        store_name: &StoreName,
+        keys: Vec<StoreKey>,
+        condition: &PredicateCondition,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
+        let store = self.get(store_name)?;
+        store.check_dimensions(store_name, &keys)?;
+        let entries: Vec<_> = store
+            .get_keys(keys)
+            .into_iter()
+            .filter(|(_, store_value)| {
+                store
+                    .predicate_indices
+                    .matches_value(condition, store_value)
+            })
+            .collect();
+        store.touch_read(entries.iter().map(|(store_key, _)| store_key));
+        Ok(entries)
+    }
+
+    /// Approximate bytes needed to return `n` entries of a store, 0 when the store does not exist
+    pub(crate) fn entries_memory(&self, store_name: &StoreName, n: usize) -> usize {
+        self.get(store_name)
+            .map(|store| {
+                n.min(store.len()) * (store.dimension.get() * size_of::<f32>() + ENTRY_OVERHEAD)
+            })
+            .unwrap_or_default()
     }
 
     /// Matches SET - adds new entries into a particular store
@@ -284,8 +624,25 @@ impl StoreHandler {
         store_name: &StoreName,
         new: Vec<(StoreKey, StoreValue)>,
     ) -> Result<StoreUpsert, ServerError> {
-        let store = self.get(store_name)?;
-        let upsert = store.add(new)?;
+        self.infer_dimension(store_name, &new)?;
+        let upsert = self.write(store_name, |store| {
+            store.check_dimensions(store_name, new.iter().map(|(store_key, _)| store_key))?;
+            store.check_finite(store_name, new.iter().map(|(store_key, _)| store_key))?;
+            let new: Vec<_> = new
+                .into_par_iter()
+                .map(|(store_key, store_value)| (store.sanitize(store_key), store_value))
+                .collect();
+            store.check_range(store_name, new.iter().map(|(store_key, _)| store_key))?;
+            store.check_norms(store_name, new.iter().map(|(store_key, _)| store_key))?;
+            let new: Vec<_> = new
+                .into_par_iter()
+                .map(|(store_key, store_value)| (store.conform(store_key), store_value))
+                .collect();
+            let _reservation = self.reserve_set_quota(store_name, store, &new)?;
+            let upsert = store.add(new)?;
+            store.evict(store_name);
+            Ok(upsert)
+        })?;
         if upsert.modified() {
             self.set_write_flag();
         }
@@ -293,18 +650,55 @@ impl StoreHandler {
     }
 
     /// matches LISTSTORES - to return statistics of all stores
-    #[tracing::instrument(skip(self))]
-    pub(crate) fn list_stores(&self) -> StdHashSet<StoreInfo> {
+    #[tracing::instrument(skip(self, limit_handler))]
+    pub(crate) fn list_stores(&self, limit_handler: &LimitHandler) -> StdHashSet<StoreInfo> {
         self.stores
             .iter(&self.stores.guard())
             .map(|(store_name, store)| StoreInfo {
                 name: store_name.clone(),
                 len: store.len(),
                 size_in_bytes: store.size(),
+                dimension: store.dimension,
+                request_limits: limit_handler.store(store_name),
             })
             .collect()
     }
 
+    /// Matches DESCRIBESTORE - returns the info of a store along with its indices
+    #[tracing::instrument(skip(self, limit_handler))]
+    pub(crate) fn describe_store(
+        &self,
+        store_name: &StoreName,
+        limit_handler: &LimitHandler,
+    ) -> Result<StoreDescription, ServerError> {
+        let store = self.get(store_name)?;
+        Ok(StoreDescription {
+            info: StoreInfo {
+                name: store_name.clone(),
+                len: store.len(),
+                size_in_bytes: store.size(),
+                dimension: store.dimension,
+                request_limits: limit_handler.store(store_name),
+            },
+            predicate_indices: store.predicate_indices.stats(),
+            non_linear_indices: store
+                .non_linear_indices
+                .current_keys()
+                .into_iter()
+                .sorted()
+                .collect(),
+            timestamp_key: store.timestamp_key.clone(),
+            storage_tier: store.storage_tier(),
+            key_element_type: store.key_element_type,
+            normalization: store.normalization,
+            index_seed: store.index_seed,
+            non_finite_vectors: store.non_finite_vectors,
+            bulk_write: store.bulk_write.load(Ordering::SeqCst),
+            eviction: store.eviction,
+            evicted: store.evicted.load(Ordering::SeqCst),
+        })
+    }
+
     /// Matches CREATESTORE - Creates a store if not exist, else return an error
     #[tracing::instrument(skip(self))]
     pub fn create_store(
@@ -313,19 +707,65 @@ impl StoreHandler {
         dimension: NonZeroUsize,
         predicates: Vec<MetadataKey>,
         non_linear_indices: StdHashSet<NonLinearAlgorithm>,
+        settings: StoreSettings,
         error_if_exists: bool,
     ) -> Result<(), ServerError> {
+        if settings.key_element_type == KeyElementType::Int8
+            && settings.normalization != VectorNormalization::None
+        {
+            return Err(ServerError::NormalizedIntegerKeys(store_name));
+        }
+        if settings
+            .eviction
+            .is_some_and(|eviction| eviction.max_entries.is_none() && eviction.max_bytes.is_none())
+        {
+            return Err(ServerError::UnboundedEviction(store_name));
+        }
+        if !self.stores.contains_key(&store_name, &self.stores.guard()) {
+            self.check_store_quota(&store_name)?;
+        }
+        let disk_vectors = match settings.storage_tier {
+            StorageTier::Memory => None,
+            StorageTier::Disk => {
+                let vector_storage = self
+                    .vector_storage
+                    .as_ref()
+                    .ok_or(ServerError::VectorStorageNotConfigured)?;
+                Some(DiskVectors::create(
+                    &vector_storage.location,
+                    dimension,
+                    settings.key_element_type,
+                    vector_storage.cache_size,
+                )?)
+            }
+        };
+        let store = Arc::new(Store {
+            infer_dimension: settings.infer_dimension,
+            key_element_type: settings.key_element_type,
+            normalization: settings.normalization,
+            index_seed: settings
+                .index_seed
+                .unwrap_or_else(|| default_index_seed(&store_name)),
+            non_finite_vectors: settings.non_finite_vectors,
+            eviction: settings.eviction,
+            namespace_counts: Mutex::new(self.store_counts(&store_name)),
+            ..Store::create(
+                dimension,
+                predicates,
+                non_linear_indices,
+                settings.timestamp_key,
+                disk_vectors,
+            )
+        });
         if self
             .stores
-            .try_insert(
-                store_name.clone(),
-                Arc::new(Store::create(dimension, predicates, non_linear_indices)),
-                &self.stores.guard(),
-            )
+            .try_insert(store_name.clone(), store.clone(), &self.stores.guard())
             .is_err()
-            && error_if_exists
         {
-            return Err(ServerError::StoreAlreadyExists(store_name));
+            store.discard(self.discarded_files.as_deref());
+            if error_if_exists {
+                return Err(ServerError::StoreAlreadyExists(store_name));
+            }
         }
         self.set_write_flag();
         Ok(())
@@ -339,8 +779,9 @@ impl StoreHandler {
         predicates: Vec<MetadataKey>,
         error_if_not_exists: bool,
     ) -> Result<usize, ServerError> {
-        let store = self.get(store_name)?;
-        let deleted = store.drop_predicates(predicates, error_if_not_exists)?;
+        let deleted = self.write(store_name, |store| {
+            store.drop_predicates(predicates, error_if_not_exists)
+        })?;
         if deleted > 0 {
             self.set_write_flag();
         };
@@ -356,741 +797,3631 @@ impl StoreHandler {
         non_linear_indices: StdHashSet<NonLinearAlgorithm>,
         error_if_not_exists: bool,
     ) -> Result<usize, ServerError> {
-        let store = self.get(store_name)?;
-        let deleted = store
-            .non_linear_indices
-            .remove_indices(non_linear_indices, error_if_not_exists)?;
+        let deleted = self.write(store_name, |store| {
+            store
+                .non_linear_indices
+                .remove_indices(non_linear_indices, error_if_not_exists)
+        })?;
         if deleted > 0 {
             self.set_write_flag();
         };
         Ok(deleted)
     }
 
-    /// Matches DROPSTORE - Drops a store if exist, else returns an error
-    #[tracing::instrument(skip(self))]
-    pub(crate) fn drop_store(
+    /// Matches APPLYMANIFEST - Creates the stores of a manifest and creates or drops the indices
+    /// of existing ones to match it. Every store is checked before anything is changed so that a
+    /// manifest conflicting with an existing store is not applied in part
+    #[tracing::instrument(skip(self, manifests), fields(stores_length=manifests.len()))]
+    pub(crate) fn apply_manifest(
         &self,
-        store_name: StoreName,
-        error_if_not_exists: bool,
-    ) -> Result<usize, ServerError> {
-        let pinned = self.stores.pin();
-        let removed = pinned.remove(&store_name).is_some();
-        if !removed && error_if_not_exists {
-            return Err(ServerError::StoreNotFound(store_name));
+        manifests: Vec<StoreManifest>,
+        dry_run: bool,
+    ) -> Result<Vec<ManifestChange>, ServerError> {
+        let mut store_names = StdHashSet::new();
+        let mut changes = Vec::new();
+        for manifest in &manifests {
+            if !store_names.insert(&manifest.store) {
+                return Err(ServerError::DuplicateManifestStore(manifest.store.clone()));
+            }
+            let (store_changes, settings) = self.plan_manifest(manifest);
+            if let Some(drift) = settings.into_iter().next() {
+                return Err(ServerError::ManifestConflict {
+                    store: drift.store,
+                    setting: drift.setting,
+                });
+            }
+            changes.extend(store_changes);
         }
-        let removed = if !removed {
-            0
-        } else {
-            self.set_write_flag();
-            1
-        };
-        Ok(removed)
-    }
-}
-
-/// A Store is a single database containing multiple N*1 arrays where N is the dimension of the
-/// store to which all arrays must conform
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Store {
-    dimension: NonZeroUsize,
-    /// Making use of a concurrent hashmap, we should be able to create an engine that manages stores
-    id_to_value: ConcurrentHashMap<StoreKeyId, (StoreKey, StoreValue)>,
-    /// Indices to filter for the store
-    predicate_indices: Arc<PredicateIndices>,
-    /// Non linear Indices
-    non_linear_indices: NonLinearAlgorithmIndices,
-}
-
-impl Store {
-    /// Creates a new empty store
-    pub(super) fn create(
-        dimension: NonZeroUsize,
-        predicates: Vec<MetadataKey>,
-        non_linear_indices: StdHashSet<NonLinearAlgorithm>,
-    ) -> Self {
-        Self {
-            dimension,
-            id_to_value: ConcurrentHashMap::new(),
-            predicate_indices: Arc::new(PredicateIndices::init(predicates)),
-            non_linear_indices: NonLinearAlgorithmIndices::create(non_linear_indices, dimension),
+        if dry_run {
+            return Ok(changes);
         }
-    }
-
-    #[tracing::instrument(skip(self))]
-    fn drop_predicates(
-        &self,
-        predicates: Vec<MetadataKey>,
-        error_if_not_exists: bool,
-    ) -> Result<usize, ServerError> {
-        self.predicate_indices
-            .remove_predicates(predicates, error_if_not_exists)
-    }
-
-    #[tracing::instrument(skip_all)]
-    fn delete(&self, keys: impl Iterator<Item = StoreKeyId>) -> usize {
-        let keys: Vec<StoreKeyId> = keys.collect();
-        let pinned = self.id_to_value.pin();
-        let removed = keys
-            .iter()
-            .flat_map(|k| pinned.remove(k))
-            .map(|(k, _)| k.0.clone())
-            .collect::<Vec<_>>();
-        self.predicate_indices.remove_store_keys(&keys);
-        self.non_linear_indices.delete(&removed);
-        removed.len()
-    }
-
-    /// filters input dimension to make sure it matches store dimension
-    #[tracing::instrument(skip(self, input), fields(input_length=input.len()))]
-    fn filter_dimension(&self, input: Vec<StoreKey>) -> Result<Vec<StoreKey>, ServerError> {
-        input
+        let mut manifests: StdHashMap<_, _> = manifests
             .into_iter()
-            .map(|key| {
-                let store_dimension = self.dimension.get();
-                let input_dimension = key.dimension();
-                if input_dimension != store_dimension {
-                    return Err(ServerError::StoreDimensionMismatch {
-                        store_dimension,
-                        input_dimension,
-                    });
+            .map(|manifest| (manifest.store.clone(), manifest))
+            .collect();
+        for change in &changes {
+            match change {
+                ManifestChange::CreateStore { store } => {
+                    let manifest = manifests
+                        .remove(store)
+                        .expect("manifest planned a store it does not declare");
+                    self.create_store(
+                        manifest.store,
+                        manifest.dimension,
+                        manifest.predicates.into_iter().collect(),
+                        manifest.non_linear_indices,
+                        StoreSettings {
+                            timestamp_key: manifest.timestamp_key,
+                            storage_tier: manifest.storage_tier,
+                            infer_dimension: false,
+                            key_element_type: manifest.key_element_type,
+                            normalization: manifest.normalization,
+                            index_seed: manifest.index_seed,
+                            non_finite_vectors: manifest.non_finite_vectors,
+                            eviction: manifest.eviction,
+                        },
+                        true,
+                    )?;
                 }
-                Ok(key)
-            })
-            .collect()
-    }
-
-    /// Deletes a bunch of store keys from the store
-    #[tracing::instrument(skip(self, del), fields(key_length=del.len()))]
-    fn delete_keys(&self, del: Vec<StoreKey>) -> Result<usize, ServerError> {
-        if del.is_empty() {
-            return Ok(0);
+                ManifestChange::CreatePredicates { store, predicates } => {
+                    self.create_pred_index(store, predicates.clone())?;
+                }
+                ManifestChange::DropPredicates { store, predicates } => {
+                    self.drop_pred_index_in_store(store, predicates.clone(), false)?;
+                }
+                ManifestChange::CreateNonLinearIndices {
+                    store,
+                    non_linear_indices,
+                } => {
+                    self.create_non_linear_algorithm_index(
+                        store,
+                        non_linear_indices.iter().copied().collect(),
+                    )?;
+                }
+                ManifestChange::DropNonLinearIndices {
+                    store,
+                    non_linear_indices,
+                } => {
+                    self.drop_non_linear_algorithm_index(
+                        store,
+                        non_linear_indices.iter().copied().collect(),
+                        false,
+                    )?;
+                }
+            }
         }
-        let keys = self.filter_dimension(del)?;
-        let res = self.delete(keys.iter().map(From::from));
-        Ok(res)
+        Ok(changes)
     }
 
-    /// Deletes a bunch of store keys from the store matching a specific predicate
-    #[tracing::instrument(skip(self))]
-    fn delete_matches(&self, condition: &PredicateCondition) -> Result<usize, ServerError> {
-        let matches = self.predicate_indices.matches(condition, self)?.into_iter();
-        Ok(self.delete(matches))
+    /// Matches DIFFMANIFEST - Compares the stores of a manifest with those of the server
+    #[tracing::instrument(skip(self, manifests), fields(stores_length=manifests.len()))]
+    pub(crate) fn diff_manifest(
+        &self,
+        manifests: &[StoreManifest],
+    ) -> Result<ManifestDrift, ServerError> {
+        let mut store_names = StdHashSet::new();
+        let mut drift = ManifestDrift::default();
+        for manifest in manifests {
+            if !store_names.insert(&manifest.store) {
+                return Err(ServerError::DuplicateManifestStore(manifest.store.clone()));
+            }
+            let (changes, settings) = self.plan_manifest(manifest);
+            drift.changes.extend(changes);
+            drift.settings.extend(settings);
+        }
+        drift.unmanaged_stores = self
+            .stores
+            .keys(&self.stores.guard())
+            .filter(|store_name| !store_names.contains(store_name))
+            .cloned()
+            .sorted()
+            .collect();
+        Ok(drift)
     }
 
-    /// Gets a bunch of store keys from the store
-    #[tracing::instrument(skip(self, val), fields(key_length=val.len()))]
-    fn get_keys(&self, val: Vec<StoreKey>) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
-        if val.is_empty() {
-            return Ok(vec![]);
+    /// Changes needed for a store to match its manifest, along with the settings it has other
+    /// than those of the manifest which cannot be changed in place
+    fn plan_manifest(&self, manifest: &StoreManifest) -> (Vec<ManifestChange>, Vec<SettingDrift>) {
+        let store_name = &manifest.store;
+        let Ok(store) = self.get(store_name) else {
+            let created = ManifestChange::CreateStore {
+                store: store_name.clone(),
+            };
+            return (vec![created], vec![]);
+        };
+        let mut settings = Vec::new();
+        let mut compare = |setting: &str, expected: Option<String>, live: Option<String>| {
+            if expected != live {
+                settings.push(SettingDrift {
+                    store: store_name.clone(),
+                    setting: setting.to_string(),
+                    manifest: expected,
+                    live,
+                });
+            }
+        };
+        // a store still waiting to infer its dimension matches the dimension of any manifest
+        if !store.infer_dimension {
+            compare(
+                "dimension",
+                Some(manifest.dimension.to_string()),
+                Some(store.dimension.to_string()),
+            );
+        }
+        compare(
+            "storage tier",
+            Some(format!("{:?}", manifest.storage_tier)),
+            Some(format!("{:?}", store.storage_tier())),
+        );
+        compare(
+            "key element type",
+            Some(format!("{:?}", manifest.key_element_type)),
+            Some(format!("{:?}", store.key_element_type)),
+        );
+        compare(
+            "normalization",
+            Some(format!("{:?}", manifest.normalization)),
+            Some(format!("{:?}", store.normalization)),
+        );
+        compare(
+            "non finite vectors",
+            Some(format!("{:?}", manifest.non_finite_vectors)),
+            Some(format!("{:?}", store.non_finite_vectors)),
+        );
+        compare(
+            "eviction",
+            manifest.eviction.map(|eviction| format!("{eviction:?}")),
+            store.eviction.map(|eviction| format!("{eviction:?}")),
+        );
+        // the seed a store was given or derived is only compared with one the manifest sets
+        if let Some(index_seed) = manifest.index_seed {
+            compare(
+                "index seed",
+                Some(index_seed.to_string()),
+                Some(store.index_seed.to_string()),
+            );
+        }
+        compare(
+            "timestamp key",
+            manifest.timestamp_key.as_ref().map(ToString::to_string),
+            store.timestamp_key.as_ref().map(ToString::to_string),
+        );
+        let mut changes = Vec::new();
+        let predicates = store.predicate_indices.current_predicates();
+        let created: Vec<_> = manifest
+            .predicates
+            .difference(&predicates)
+            .cloned()
+            .sorted()
+            .collect();
+        if !created.is_empty() {
+            changes.push(ManifestChange::CreatePredicates {
+                store: store_name.clone(),
+                predicates: created,
+            });
+        }
+        let dropped: Vec<_> = predicates
+            .difference(&manifest.predicates)
+            .cloned()
+            .sorted()
+            .collect();
+        if !dropped.is_empty() {
+            changes.push(ManifestChange::DropPredicates {
+                store: store_name.clone(),
+                predicates: dropped,
+            });
+        }
+        let non_linear_indices = store.non_linear_indices.current_keys();
+        let created: Vec<_> = manifest
+            .non_linear_indices
+            .difference(&non_linear_indices)
+            .copied()
+            .sorted()
+            .collect();
+        if !created.is_empty() {
+            changes.push(ManifestChange::CreateNonLinearIndices {
+                store: store_name.clone(),
+                non_linear_indices: created,
+            });
+        }
+        let dropped: Vec<_> = non_linear_indices
+            .difference(&manifest.non_linear_indices)
+            .copied()
+            .sorted()
+            .collect();
+        if !dropped.is_empty() {
+            changes.push(ManifestChange::DropNonLinearIndices {
+                store: store_name.clone(),
+                non_linear_indices: dropped,
+            });
         }
-        // return error if dimensions do not match
-        let keys = self.filter_dimension(val)?;
-        Ok(self.get(keys.iter().map(From::from)))
+        (changes, settings)
     }
 
-    /// Gets a bunch of store entries that matches a predicate condition
+    /// Matches COMPACTSTORE - Rebuilds a store from its entries while it keeps serving queries
+    /// and swaps the rebuilt store in its place
     #[tracing::instrument(skip(self))]
-    fn get_matches(
+    pub(crate) fn compact_store(
         &self,
-        condition: &PredicateCondition,
-    ) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
-        let matches = self.predicate_indices.matches(condition, self)?.into_iter();
-        Ok(self.get(matches))
+        store_name: &StoreName,
+    ) -> Result<StoreCompaction, ServerError> {
+        // compactions rebuild the indices of the store so they are kept off the query threadpool
+        parallel::maintenance(|| loop {
+            let store = self.get(store_name)?;
+            let size_before = store.size();
+            let version = store.version.load(Ordering::SeqCst);
+            let mut compacted = store.compacted()?;
+            let _writing = store.writing.write().expect("store write lock poisoned");
+            // another compaction swapped the store out first
+            if store.retired.load(Ordering::SeqCst) {
+                compacted.discard(self.discarded_files.as_deref());
+                continue;
+            }
+            // the copy misses writes made while it was being built so it is built again now
+            // that writes are held off
+            if store.version.load(Ordering::SeqCst) != version {
+                compacted.discard(self.discarded_files.as_deref());
+                compacted = store.compacted()?;
+            }
+            let compacted = Arc::new(compacted);
+            self.swap(store_name, &store, compacted.clone())?;
+            let size_in_bytes = compacted.size();
+            return Ok(StoreCompaction {
+                reclaimed_bytes: size_before.saturating_sub(size_in_bytes),
+                size_in_bytes,
+            });
+        })
     }
 
-    /// Used whenever there is no found predicate and so we search directly within store
+    /// Matches CHECKSTORE - compares every index of a store with its entries. Writes are held off
+    /// meanwhile so that entries written during the check are not taken for discrepancies
     #[tracing::instrument(skip(self))]
-    pub(super) fn get_match_without_predicate(
+    pub(crate) fn check_store(&self, store_name: &StoreName) -> Result<StoreCheck, ServerError> {
+        parallel::maintenance(|| loop {
+            let store = self.get(store_name)?;
+            let _writing = store.writing.write().expect("store write lock poisoned");
+            if store.retired.load(Ordering::SeqCst) {
+                continue;
+            }
+            return Ok(StoreCheck {
+                entries: store.len(),
+                indices: store.check_indices(),
+                repair_job: None,
+            });
+        })
+    }
+
+    /// Matches BENCHMARKSTORE - searches a store with the vectors of a sample of its entries,
+    /// first by a linear scan and then through each of its non linear indices, for `duration`
+    /// each. The searches are left out of the reads eviction policies go by
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn benchmark_store(
         &self,
-        predicate: &Predicate,
-    ) -> Result<StdHashSet<StoreKeyId>, ServerError> {
-        let store_val_pinned = self.id_to_value.pin();
-        let res = match predicate {
-            Predicate::Equals { key, value } => store_val_pinned
-                .into_iter()
-                .filter(|(_, (_, store_value))| {
-                    store_value.get(key).map(|v| v.eq(value)).unwrap_or(false)
-                })
-                .map(|(k, _)| k.clone())
-                .collect(),
-            Predicate::NotEquals { key, value } => store_val_pinned
-                .into_iter()
-                .filter(|(_, (_, store_value))| {
-                    store_value.get(key).map(|v| !v.eq(value)).unwrap_or(true)
-                })
-                .map(|(k, _)| k.clone())
-                .collect(),
-            Predicate::In { key, value } => store_val_pinned
-                .into_iter()
-                .filter(|(_, (_, store_value))| {
-                    store_value
-                        .get(key)
-                        .map(|v| value.contains(v))
-                        .unwrap_or(false)
-                })
-                .map(|(k, _)| k.clone())
-                .collect(),
-            Predicate::NotIn { key, value } => store_val_pinned
+        store_name: &StoreName,
+        sample_queries: NonZeroUsize,
+        concurrency: NonZeroUsize,
+        duration: Duration,
+        deadline: Deadline,
+    ) -> Result<StoreBenchmark, ServerError> {
+        if duration > benchmark::MAX_BENCHMARK_DURATION {
+            return Err(ServerError::BenchmarkLimitExceeded {
+                setting: "duration",
+                value: duration.as_millis() as u64,
+                limit: benchmark::MAX_BENCHMARK_DURATION.as_millis() as u64,
+            });
+        }
+        if concurrency.get() > benchmark::MAX_BENCHMARK_CONCURRENCY {
+            return Err(ServerError::BenchmarkLimitExceeded {
+                setting: "concurrency",
+                value: concurrency.get() as u64,
+                limit: benchmark::MAX_BENCHMARK_CONCURRENCY as u64,
+            });
+        }
+        let store = self.get(store_name)?;
+        let search_inputs = store.sample_vectors(sample_queries.get());
+        let closest_n =
+            NonZeroUsize::new(BENCHMARK_CLOSEST_N).expect("Benchmark searches ask for no results");
+        let paths = std::iter::once(SearchPath::Linear).chain(
+            store
+                .non_linear_indices
+                .current_keys()
                 .into_iter()
-                .filter(|(_, (_, store_value))| {
-                    store_value
-                        .get(key)
-                        .map(|v| !value.contains(v))
-                        .unwrap_or(true)
+                .sorted()
+                .map(SearchPath::NonLinear),
+        );
+        let paths = paths
+            .map(|path| {
+                let algorithm = match path {
+                    SearchPath::Linear => Algorithm::CosineSimilarity,
+                    SearchPath::NonLinear(NonLinearAlgorithm::KDTree) => Algorithm::KDTree,
+                };
+                benchmark::benchmark_path(
+                    path,
+                    &search_inputs,
+                    concurrency,
+                    duration,
+                    deadline,
+                    |search_input| {
+                        self.get_sim_in_store(
+                            store_name,
+                            search_input.clone(),
+                            closest_n,
+                            algorithm,
+                            None,
+                            GetSimNOptions {
+                                deadline,
+                                untracked: true,
+                                ..Default::default()
+                            },
+                        )
+                        .map(|_| ())
+                    },
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(StoreBenchmark {
+            entries: store.len(),
+            sample_queries: search_inputs.len(),
+            concurrency: concurrency.get(),
+            paths,
+        })
+    }
+
+    /// Brings an index of a store that CHECKSTORE found to disagree with its entries back in line
+    /// with them, holding off writes while it does
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn repair_store_index(
+        &self,
+        store_name: &StoreName,
+        index: &StoreIndex,
+    ) -> Result<(), ServerError> {
+        parallel::maintenance(|| loop {
+            let store = self.get(store_name)?;
+            let _writing = store.writing.write().expect("store write lock poisoned");
+            if store.retired.load(Ordering::SeqCst) {
+                continue;
+            }
+            store.repair_index(index);
+            store.version.fetch_add(1, Ordering::SeqCst);
+            self.set_write_flag();
+            return Ok(());
+        })
+    }
+
+    /// Replaces a store whose writes are held off, retiring it so that writes waiting on it move
+    /// on to the replacement
+    #[tracing::instrument(skip(self, store, replacement))]
+    fn swap(
+        &self,
+        store_name: &StoreName,
+        store: &Arc<Store>,
+        replacement: Arc<Store>,
+    ) -> Result<(), ServerError> {
+        let pinned = self.stores.pin();
+        // the store may have been dropped or recreated in the meantime
+        let swapped = pinned
+            .compute_if_present(store_name, |_, current| {
+                Some(if Arc::ptr_eq(current, store) {
+                    replacement.clone()
+                } else {
+                    current.clone()
                 })
-                .map(|(k, _)| k.clone())
-                .collect(),
-        };
-        Ok(res)
+            })
+            .is_some_and(|current| Arc::ptr_eq(current, &replacement));
+        if !swapped {
+            replacement.discard(self.discarded_files.as_deref());
+            return Err(ServerError::StoreNotFound(store_name.clone()));
+        }
+        store.retired.store(true, Ordering::SeqCst);
+        store.discard(self.discarded_files.as_deref());
+        self.set_write_flag();
+        Ok(())
     }
 
-    #[tracing::instrument(skip_all)]
-    fn get(&self, keys: impl Iterator<Item = StoreKeyId>) -> Vec<(StoreKey, StoreValue)> {
-        let pinned = self.id_to_value.pin();
-        keys.flat_map(|k| pinned.get(&k).cloned()).collect()
+    /// Gives a store created to infer its dimension that of the first keys set into it, swapping
+    /// in a store of that dimension. The keys must all share that dimension
+    #[tracing::instrument(skip(self, new), fields(entries_length=new.len()))]
+    fn infer_dimension(
+        &self,
+        store_name: &StoreName,
+        new: &[(StoreKey, StoreValue)],
+    ) -> Result<(), ServerError> {
+        let Some((first, _)) = new.first() else {
+            return Ok(());
+        };
+        loop {
+            let store = self.get(store_name)?;
+            if !store.infer_dimension {
+                return Ok(());
+            }
+            let dimension = NonZeroUsize::new(first.dimension())
+                .ok_or_else(|| ServerError::DimensionNotInferred(store_name.clone()))?;
+            if let Some((index, (store_key, _))) = new
+                .iter()
+                .enumerate()
+                .find(|(_, (store_key, _))| store_key.dimension() != dimension.get())
+            {
+                return Err(ServerError::StoreDimensionMismatch {
+                    store: store_name.clone(),
+                    store_dimension: dimension.get(),
+                    input_dimension: store_key.dimension(),
+                    index,
+                });
+            }
+            let _writing = store.writing.write().expect("store write lock poisoned");
+            // another set inferred the dimension first
+            if store.retired.load(Ordering::SeqCst) {
+                continue;
+            }
+            return self.swap(
+                store_name,
+                &store,
+                Arc::new(store.with_dimension(dimension)?),
+            );
+        }
     }
 
+    /// Names of the stores where the fraction of entries deleted since they were created or
+    /// compacted has reached the threshold
     #[tracing::instrument(skip(self))]
-    fn get_all(&self) -> Vec<(StoreKey, StoreValue)> {
-        let pinned = self.id_to_value.pin();
-        pinned
-            .into_iter()
-            .map(|(_key, (store_key, store_value))| (store_key.clone(), store_value.clone()))
+    pub(crate) fn fragmented_stores(&self, threshold: f32) -> Vec<StoreName> {
+        self.stores
+            .iter(&self.stores.guard())
+            .filter(|(_, store)| store.fragmentation() >= threshold)
+            .map(|(store_name, _)| store_name.clone())
             .collect()
     }
 
-    /// Adds a bunch of entries into the store if they match the dimensions
-    /// Returns the len of values added, if a value already existed it is updated but not counted
-    /// as a new insert
-    #[tracing::instrument(skip(self, new), fields(entry_length=new.len()))]
-    fn add(&self, new: Vec<(StoreKey, StoreValue)>) -> Result<StoreUpsert, ServerError> {
-        if new.is_empty() {
-            return Ok(StoreUpsert {
-                inserted: 0,
-                updated: 0,
-            });
-        }
-        let store_dimension: usize = self.dimension.into();
-        let check_bounds = |(store_key, store_val): &(StoreKey, StoreValue)| -> Result<(StoreKeyId, (StoreKey, StoreValue)), ServerError> {
-            let input_dimension = store_key.0.len();
-            if input_dimension != store_dimension {
-                Err(ServerError::StoreDimensionMismatch { store_dimension, input_dimension  })
-            } else {
-                Ok(((store_key).into(), (store_key.to_owned(), store_val.to_owned())))
-            }
+    /// Matches WARMUP - reads the vectors and indices of the stores, or of every store when none
+    /// are given, so that they are in memory before the first queries reach them
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn warmup(&self, store_names: StdHashSet<StoreName>) -> Result<(), ServerError> {
+        let stores: Vec<_> = if store_names.is_empty() {
+            self.stores
+                .iter(&self.stores.guard())
+                .map(|(_, store)| store.clone())
+                .collect()
+        } else {
+            store_names
+                .iter()
+                .map(|store_name| self.get(store_name))
+                .collect::<Result<_, _>>()?
         };
-        let res: Vec<(StoreKeyId, (StoreKey, StoreValue))> =
-            new.par_iter().map(check_bounds).collect::<Result<_, _>>()?;
-        let predicate_insert = res
-            .par_iter()
-            .map(|(k, (_, v))| (k.clone(), v.clone()))
-            .collect();
-        let inserted = AtomicUsize::new(0);
-        let updated = AtomicUsize::new(0);
-        let inserted_keys = res
-            .into_par_iter()
-            .flat_map_iter(|(k, v)| {
-                let pinned = self.id_to_value.pin();
-                if pinned.insert(k, v.clone()).is_some() {
-                    updated.fetch_add(1, Ordering::SeqCst);
-                } else {
-                    inserted.fetch_add(1, Ordering::SeqCst);
-                    return Some(v.0 .0);
-                }
-                None
-            })
-            .collect();
-        let predicate_indices = self.predicate_indices.clone();
-        predicate_indices.add(predicate_insert);
-        if !self.non_linear_indices.is_empty() {
-            self.non_linear_indices.insert(inserted_keys);
-        }
-        Ok(StoreUpsert {
-            inserted: inserted.into_inner(),
-            updated: updated.into_inner(),
-        })
+        parallel::maintenance(|| stores.par_iter().for_each(|store| store.warmup()));
+        Ok(())
     }
 
+    /// Matches SETBULKWRITE - turns the bulk write mode of a store on or off. Writes to a store in
+    /// bulk write mode skip updating its indices, which makes large loads faster but leaves the
+    /// entries out of predicate and non linear index searches until the mode is turned off and
+    /// the indices catch up
     #[tracing::instrument(skip(self))]
-    fn create_pred_index(&self, requested_predicates: Vec<MetadataKey>) -> usize {
-        let current_predicates = self.predicate_indices.current_predicates();
-        let new_predicates: Vec<_> = StdHashSet::from_iter(requested_predicates)
-            .difference(&current_predicates)
-            .cloned()
-            .collect();
-        let new_predicates_len = new_predicates.len();
-        if !new_predicates.is_empty() {
-            // get all the values and reindex
-            let values = self
-                .get_all()
-                .into_iter()
-                .map(|(k, v)| (StoreKeyId::from(&k), v))
-                .collect();
-            self.predicate_indices
-                .add_predicates(new_predicates, Some(values));
-        };
-        new_predicates_len
+    pub(crate) fn set_bulk_write(
+        &self,
+        store_name: &StoreName,
+        enabled: bool,
+    ) -> Result<(), ServerError> {
+        parallel::maintenance(|| loop {
+            let store = self.get(store_name)?;
+            // writes are held off so that none is left out of the indices being caught up
+            let _writing = store.writing.write().expect("store write lock poisoned");
+            if store.retired.load(Ordering::SeqCst) {
+                continue;
+            }
+            if store.bulk_write.swap(enabled, Ordering::SeqCst) == enabled {
+                return Ok(());
+            }
+            if !enabled {
+                store.flush_index_updates();
+            }
+            store.version.fetch_add(1, Ordering::SeqCst);
+            self.set_write_flag();
+            return Ok(());
+        })
     }
 
+    /// Queries recreating a store as it is on another server, dropping any store of the same name
+    /// there first. Entries are set in chunks of `chunk_size` with bulk write mode on, so the
+    /// indices are built once at the end. Gives no queries for a store the server does not hold
     #[tracing::instrument(skip(self))]
-    fn create_non_linear_algorithm_index(
+    pub(crate) fn recreate_queries(
         &self,
-        non_linear_indices: StdHashSet<NonLinearAlgorithm>,
-    ) -> usize {
-        let current_keys = self.non_linear_indices.current_keys();
-        let new_predicates: StdHashSet<_> = non_linear_indices
-            .difference(&current_keys)
-            .copied()
-            .collect();
-        let new_predicates_len = new_predicates.len();
-        if !new_predicates.is_empty() {
-            // get all the values and reindex
-            let values: Vec<_> = self.get_all().into_iter().map(|(k, _)| k.0).collect();
-            self.non_linear_indices
-                .insert_indices(new_predicates, &values, self.dimension);
+        store_name: &StoreName,
+        chunk_size: usize,
+    ) -> Vec<DBQuery> {
+        let Ok(store) = self.get(store_name) else {
+            return vec![];
         };
-        new_predicates_len
+        let mut queries = vec![
+            DBQuery::DropStore {
+                store: store_name.clone(),
+                error_if_not_exists: false,
+            },
+            DBQuery::CreateStore {
+                store: store_name.clone(),
+                dimension: store.dimension,
+                create_predicates: store.predicate_indices.current_predicates(),
+                non_linear_indices: store.non_linear_indices.current_keys(),
+                error_if_exists: true,
+                timestamp_key: store.timestamp_key.clone(),
+                storage_tier: store.storage_tier(),
+                infer_dimension: store.infer_dimension,
+                key_element_type: store.key_element_type,
+                normalization: store.normalization,
+                index_seed: Some(store.index_seed),
+                non_finite_vectors: store.non_finite_vectors,
+                eviction: store.eviction,
+            },
+            DBQuery::SetBulkWrite {
+                store: store_name.clone(),
+                enabled: true,
+            },
+        ];
+        let entries = store.get_all();
+        queries.extend(entries.chunks(chunk_size.max(1)).map(|chunk| DBQuery::Set {
+            store: store_name.clone(),
+            inputs: chunk.to_vec(),
+        }));
+        queries.push(DBQuery::SetBulkWrite {
+            store: store_name.clone(),
+            enabled: store.bulk_write.load(Ordering::SeqCst),
+        });
+        queries
     }
 
-    /// Returns the number of key value pairs in the store
+    /// Matches DROPSTORE - Drops a store if exist, else returns an error
     #[tracing::instrument(skip(self))]
-    fn len(&self) -> usize {
-        self.id_to_value.pin().len()
+    pub(crate) fn drop_store(
+        &self,
+        store_name: StoreName,
+        error_if_not_exists: bool,
+    ) -> Result<usize, ServerError> {
+        let pinned = self.stores.pin();
+        let removed = pinned
+            .remove(&store_name)
+            .inspect(|store| {
+                self.uncount_store(store);
+                match self.trash_retention {
+                    Some(_) => self.trash_store(store_name.clone(), Arc::clone(store)),
+                    None => store.discard(self.discarded_files.as_deref()),
+                }
+            })
+            .is_some();
+        if !removed && error_if_not_exists {
+            return Err(ServerError::StoreNotFound(store_name));
+        }
+        let removed = if !removed {
+            0
+        } else {
+            self.set_write_flag();
+            1
+        };
+        Ok(removed)
     }
 
-    /// TODO: Fix nested calculation of sizes using size_of_val
+    /// Matches GETMEMORYBREAKDOWN - attributes the memory of the stores to their vectors, entries
+    /// and indices, ordered from the largest store. The allocator and connections are left to
+    /// the server to fill in
     #[tracing::instrument(skip(self))]
-    fn size(&self) -> usize {
-        size_of_val(&self)
-            + size_of_val(&self.dimension)
-            + size_of_val(&self.id_to_value)
-            + self
-                .id_to_value
-                .iter(&self.id_to_value.guard())
-                .map(|(k, v)| {
-                    size_of_val(k)
-                        + size_of_val(&v.0)
-                        + v.1
-                            .iter()
-                            .map(|(inner_k, inner_val)| {
-                                size_of_val(inner_k) + size_of_val(inner_val)
-                            })
-                            .sum::<usize>()
-                })
-                .sum::<usize>()
-            + self.predicate_indices.size()
-            + self.non_linear_indices.size()
+    pub(crate) fn memory_breakdown(&self) -> MemoryBreakdown {
+        let stores = self
+            .stores
+            .iter(&self.stores.guard())
+            .map(|(store_name, store)| store.memory(store_name.clone()))
+            .sorted_by(|a, b| b.total().cmp(&a.total()).then_with(|| a.name.cmp(&b.name)))
+            .collect();
+        let trash = self
+            .trash
+            .iter(&self.trash.guard())
+            .map(|(store_name, trashed)| trashed.store.memory(store_name.clone()).total())
+            .sum();
+        MemoryBreakdown {
+            stores,
+            trash,
+            ..Default::default()
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::tests::*;
-    use pretty_assertions::assert_eq;
-    use std::num::NonZeroUsize;
+/// What a store holds for each of its keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Entry {
+    pub(super) vector: VectorRef,
+    pub(super) value: StoreValue,
+    /// Norm of the vector kept for cosine similarity. Snapshots from before norms were kept have
+    /// none, so a norm of zero is computed again whenever it is needed
+    #[serde(default)]
+    norm: f32,
+}
 
-    use super::*;
-    use ahnlich_types::metadata::MetadataKey;
-    use ahnlich_types::metadata::MetadataValue;
-    use ahnlich_types::predicate::Predicate;
-    use ndarray::array;
+/// Estimated bytes of the key id of an entry and its metadata
+pub(super) fn entry_memory(key_id: &StoreKeyId, entry: &Entry) -> usize {
+    key_id.memory()
+        + size_of::<Entry>()
+        + entry
+            .value
+            .iter()
+            .map(|(key, value)| {
+                size_of_val(key) + key.to_string().len() + predicate::value_memory(value)
+            })
+            .sum::<usize>()
+}
+
+/// Keys of the entries written to a store in bulk write mode that its indices are yet to hold
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PendingIndexUpdates {
+    // keys of inserted and updated entries whose values are missing from the predicate indices
+    predicates: StdHashSet<StoreKeyId>,
+    // keys of inserted entries whose vectors are missing from the non linear indices
+    non_linear: StdHashSet<StoreKeyId>,
+}
+
+/// A Store is a single database containing multiple N*1 arrays where N is the dimension of the
+/// store to which all arrays must conform
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Store {
+    pub(super) dimension: NonZeroUsize,
+    /// Making use of a concurrent hashmap, we should be able to create an engine that manages stores
+    pub(super) id_to_value: ConcurrentHashMap<StoreKeyId, Entry>,
+    /// Vectors of a disk tier store. Comes after the entries so that a snapshot never refers to
+    /// slots past the ones it records as written
+    #[serde(default)]
+    pub(super) disk_vectors: Option<DiskVectors>,
+    /// Indices to filter for the store
+    pub(super) predicate_indices: Arc<PredicateIndices>,
+    /// Non linear Indices
+    pub(super) non_linear_indices: NonLinearAlgorithmIndices,
+    /// Metadata key holding the Unix timestamp in seconds of each entry
+    #[serde(default)]
+    pub(super) timestamp_key: Option<MetadataKey>,
+    /// Number of entries deleted since the store was created or compacted. A store loaded from a
+    /// snapshot has its indices built afresh so this starts over
+    #[serde(skip)]
+    deleted: AtomicUsize,
+    /// Held for reading by writes so that a compaction can hold them off while it swaps the store
+    #[serde(skip)]
+    pub(super) writing: RwLock<()>,
+    /// Counts finished writes, telling a compaction whether its copy of the store is outdated
+    #[serde(skip)]
+    version: AtomicU64,
+    /// Set once a compaction or dimension inference has swapped the store out so writes move on
+    /// to its replacement
+    #[serde(skip)]
+    retired: AtomicBool,
+    /// Set for a store created without a dimension until the first keys set into it give it one
+    #[serde(default)]
+    infer_dimension: bool,
+    /// Precision the vectors are held in, which keys are rounded to as they come in
+    #[serde(default)]
+    pub(super) key_element_type: KeyElementType,
+    /// Set when every vector of the store has unit length
+    #[serde(default)]
+    normalization: VectorNormalization,
+    /// Seeds the sampling of the store so that replicas sample the same entries
+    #[serde(default)]
+    index_seed: u64,
+    /// Whether keys and search inputs holding NaN or infinite values are rejected or sanitized
+    #[serde(default)]
+    non_finite_vectors: NonFiniteVectors,
+    /// Set while writes leave the indices alone, which are caught up once it is unset
+    #[serde(default)]
+    bulk_write: AtomicBool,
+    #[serde(default)]
+    pending_index_updates: Mutex<PendingIndexUpdates>,
+    /// Bounds of a store used as a cache, past which entries are evicted
+    #[serde(default)]
+    pub(super) eviction: Option<StoreEviction>,
+    /// When the entries of a bounded store were last written or read and how often they were.
+    /// Not persisted, so the entries of a loaded store are all evicted alike until used
+    #[serde(skip)]
+    pub(super) access: ConcurrentHashMap<StoreKeyId, EntryAccess>,
+    /// Ticks on every access of a bounded store to order them
+    #[serde(skip)]
+    pub(super) access_clock: AtomicU64,
+    /// Entries evicted since the store was created or loaded
+    #[serde(skip)]
+    pub(super) evicted: AtomicU64,
+    /// Estimated bytes of the entries of a store bounded by bytes, kept up to date as they are
+    /// written and deleted. Not persisted, so counted afresh once the store is loaded
+    #[serde(skip)]
+    pub(super) bytes: AtomicUsize,
+    /// Counts of the namespace the entries of the store are counted towards, none for stores
+    /// outside a namespace and dropped ones
+    #[serde(skip)]
+    pub(super) namespace_counts: Mutex<Option<Arc<NamespaceCounts>>>,
+}
+
+impl Store {
+    /// Creates a new empty store
+    pub(super) fn create(
+        dimension: NonZeroUsize,
+        predicates: Vec<MetadataKey>,
+        non_linear_indices: StdHashSet<NonLinearAlgorithm>,
+        timestamp_key: Option<MetadataKey>,
+        disk_vectors: Option<DiskVectors>,
+    ) -> Self {
+        Self {
+            dimension,
+            id_to_value: ConcurrentHashMap::new(),
+            disk_vectors,
+            predicate_indices: Arc::new(PredicateIndices::init(predicates)),
+            non_linear_indices: NonLinearAlgorithmIndices::create(non_linear_indices, dimension),
+            timestamp_key,
+            deleted: AtomicUsize::new(0),
+            writing: RwLock::new(()),
+            version: AtomicU64::new(0),
+            retired: AtomicBool::new(false),
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: 0,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            bulk_write: AtomicBool::new(false),
+            pending_index_updates: Mutex::new(PendingIndexUpdates::default()),
+            eviction: None,
+            access: ConcurrentHashMap::new(),
+            access_clock: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+            bytes: AtomicUsize::new(0),
+            namespace_counts: Mutex::new(None),
+        }
+    }
+
+    /// Creates an empty store like this one but of another dimension
+    #[tracing::instrument(skip(self))]
+    fn with_dimension(&self, dimension: NonZeroUsize) -> Result<Self, ServerError> {
+        Ok(Self {
+            key_element_type: self.key_element_type,
+            normalization: self.normalization,
+            index_seed: self.index_seed,
+            non_finite_vectors: self.non_finite_vectors,
+            bulk_write: AtomicBool::new(self.bulk_write.load(Ordering::SeqCst)),
+            eviction: self.eviction,
+            namespace_counts: Mutex::new(self.counted_in()),
+            ..Self::create(
+                dimension,
+                self.predicate_indices
+                    .current_predicates()
+                    .into_iter()
+                    .collect(),
+                self.non_linear_indices.current_keys(),
+                self.timestamp_key.clone(),
+                self.disk_vectors
+                    .as_ref()
+                    .map(|disk_vectors| disk_vectors.create_alongside(dimension))
+                    .transpose()?,
+            )
+        })
+    }
+
+    /// Copies the entries of the store into a new store with the same indices built afresh
+    #[tracing::instrument(skip(self))]
+    fn compacted(&self) -> Result<Self, ServerError> {
+        let mut compacted = Self {
+            infer_dimension: self.infer_dimension,
+            key_element_type: self.key_element_type,
+            normalization: self.normalization,
+            index_seed: self.index_seed,
+            non_finite_vectors: self.non_finite_vectors,
+            eviction: self.eviction,
+            access_clock: AtomicU64::new(self.access_clock.load(Ordering::SeqCst)),
+            evicted: AtomicU64::new(self.evicted.load(Ordering::SeqCst)),
+            ..Self::create(
+                self.dimension,
+                self.predicate_indices
+                    .current_predicates()
+                    .into_iter()
+                    .collect(),
+                StdHashSet::new(),
+                self.timestamp_key.clone(),
+                self.disk_vectors
+                    .as_ref()
+                    .map(|disk_vectors| disk_vectors.create_alongside(self.dimension))
+                    .transpose()?,
+            )
+        };
+        compacted.add(self.get_all())?;
+        // the copy keeps the order the entries are evicted in rather than that they were added
+        let access = compacted.access.pin();
+        for (key_id, entry_access) in self.access.pin().iter() {
+            access.insert(key_id.clone(), *entry_access);
+        }
+        drop(access);
+        // non linear indices are bulk loaded once all the entries are in
+        compacted.create_non_linear_algorithm_index(self.non_linear_indices.current_keys());
+        // the indices of the copy hold every entry so only the mode carries over
+        compacted
+            .bulk_write
+            .store(self.bulk_write.load(Ordering::SeqCst), Ordering::SeqCst);
+        // the entries of the copy are already counted as those of the store it replaces
+        *compacted
+            .namespace_counts
+            .get_mut()
+            .expect("namespace counts lock poisoned") = self.counted_in();
+        Ok(compacted)
+    }
+
+    fn storage_tier(&self) -> StorageTier {
+        match self.disk_vectors {
+            Some(_) => StorageTier::Disk,
+            None => StorageTier::Memory,
+        }
+    }
+
+    /// Marks the vector file of a disk tier store to be removed along with the store, or once a
+    /// snapshot leaving it out is persisted when discarded files are kept
+    pub(super) fn discard(&self, discarded_files: Option<&DiscardedFiles>) {
+        match (&self.disk_vectors, discarded_files) {
+            (Some(disk_vectors), Some(discarded_files)) => {
+                disk_vectors.discard_after_snapshot(discarded_files)
+            }
+            (Some(disk_vectors), None) => disk_vectors.discard(),
+            (None, _) => {}
+        }
+    }
+
+    /// Resolves the vector of an entry, reading it from disk for disk tier stores
+    pub(super) fn vector(&self, vector: &VectorRef) -> StoreKey {
+        match (vector, &self.disk_vectors) {
+            (VectorRef::Half(bits), _) => vectors::from_half_bits(self.key_element_type, bits),
+            (VectorRef::Int8 { int8 }, _) => vectors::from_int8(int8),
+            (VectorRef::Memory(store_key), _) => store_key.clone(),
+            (VectorRef::Disk(slot), Some(disk_vectors)) => disk_vectors.read(*slot),
+            (VectorRef::Disk(_), None) => unreachable!("disk vector in a memory tier store"),
+        }
+    }
+
+    /// Reads every vector, which fills the page cache of a disk tier store, and walks the indices
+    #[tracing::instrument(skip(self))]
+    fn warmup(&self) {
+        let pinned = self.id_to_value.pin();
+        for (_, entry) in pinned.iter() {
+            std::hint::black_box(self.vector(&entry.vector));
+        }
+        std::hint::black_box(self.predicate_indices.size() + self.non_linear_indices.size());
+    }
+
+    /// Updates the indices with the entries written in bulk write mode that are still held.
+    /// Non linear indices missing every entry of the store are bulk loaded afresh
+    #[tracing::instrument(skip(self))]
+    fn flush_index_updates(&self) {
+        let pending = std::mem::take(
+            &mut *self
+                .pending_index_updates
+                .lock()
+                .expect("pending index updates lock poisoned"),
+        );
+        let pinned = self.id_to_value.pin();
+        let predicate_insert = pending
+            .predicates
+            .into_iter()
+            .flat_map(|key| pinned.get(&key).map(|entry| (key, entry.value.clone())))
+            .collect();
+        self.predicate_indices.add(predicate_insert);
+        let non_linear_insert: Vec<_> = pending
+            .non_linear
+            .iter()
+            .flat_map(|key| pinned.get(key).map(|entry| self.vector(&entry.vector).0))
+            .collect();
+        if non_linear_insert.is_empty() {
+            return;
+        }
+        if non_linear_insert.len() == self.len() {
+            self.non_linear_indices.insert_indices(
+                self.non_linear_indices.current_keys(),
+                &non_linear_insert,
+                self.dimension,
+            );
+        } else {
+            self.non_linear_indices.insert(non_linear_insert);
+        }
+    }
+
+    /// Compares every index with the entries of the store, leaving out the entries written in
+    /// bulk write mode that the indices are yet to catch up with
+    #[tracing::instrument(skip(self))]
+    fn check_indices(&self) -> Vec<IndexCheck> {
+        let pending = self.pending_keys();
+        let predicates = self.predicate_indices.current_predicates();
+        let non_linear_indices = self.non_linear_indices.current_keys();
+        predicates
+            .into_iter()
+            .sorted()
+            .map(|predicate| {
+                let discrepancies = self.predicate_discrepancies(&predicate, &pending.predicates);
+                IndexCheck {
+                    index: StoreIndex::Predicate(predicate),
+                    missing: discrepancies.missing.len(),
+                    orphaned: discrepancies.orphaned.len(),
+                }
+            })
+            .chain(non_linear_indices.into_iter().sorted().map(|algorithm| {
+                let (missing, orphaned) =
+                    self.non_linear_discrepancies(&algorithm, &pending.non_linear);
+                IndexCheck {
+                    index: StoreIndex::NonLinear(algorithm),
+                    missing,
+                    orphaned,
+                }
+            }))
+            .collect()
+    }
+
+    /// Brings an index back in line with the entries of the store. Predicate indices have what
+    /// they are missing added and what they should not hold removed while non linear indices are
+    /// built afresh. An index dropped since it was checked is left dropped
+    #[tracing::instrument(skip(self))]
+    fn repair_index(&self, index: &StoreIndex) {
+        let pending = self.pending_keys();
+        match index {
+            StoreIndex::Predicate(predicate) => {
+                let discrepancies = self.predicate_discrepancies(predicate, &pending.predicates);
+                self.predicate_indices.repair(predicate, discrepancies);
+            }
+            StoreIndex::NonLinear(algorithm) => {
+                if !self.non_linear_indices.current_keys().contains(algorithm)
+                    || self.non_linear_discrepancies(algorithm, &pending.non_linear) == (0, 0)
+                {
+                    return;
+                }
+                let vectors: Vec<_> = self
+                    .id_to_value
+                    .pin()
+                    .iter()
+                    .filter(|(key, _)| !pending.non_linear.contains(key))
+                    .map(|(_, entry)| self.vector(&entry.vector).0)
+                    .collect();
+                self.non_linear_indices.insert_indices(
+                    StdHashSet::from_iter([*algorithm]),
+                    &vectors,
+                    self.dimension,
+                );
+            }
+        }
+    }
+
+    /// Copy of the keys of the entries the indices are yet to catch up with
+    fn pending_keys(&self) -> PendingIndexUpdates {
+        let pending = self
+            .pending_index_updates
+            .lock()
+            .expect("pending index updates lock poisoned");
+        PendingIndexUpdates {
+            predicates: pending.predicates.clone(),
+            non_linear: pending.non_linear.clone(),
+        }
+    }
+
+    #[tracing::instrument(skip(self, pending))]
+    fn predicate_discrepancies(
+        &self,
+        predicate: &MetadataKey,
+        pending: &StdHashSet<StoreKeyId>,
+    ) -> PredicateDiscrepancies {
+        let expected = self
+            .id_to_value
+            .pin()
+            .iter()
+            .filter(|(key, _)| !pending.contains(key))
+            .flat_map(|(key, entry)| {
+                entry
+                    .value
+                    .get(predicate)
+                    .map(|value| (value.clone(), key.clone()))
+            })
+            .collect();
+        self.predicate_indices
+            .discrepancies(predicate, expected, pending)
+    }
+
+    /// Number of entries of the store a non linear index is missing and of vectors it holds that
+    /// the store does not. Vectors are told apart by the key ids their entries would have
+    #[tracing::instrument(skip(self, pending))]
+    fn non_linear_discrepancies(
+        &self,
+        algorithm: &NonLinearAlgorithm,
+        pending: &StdHashSet<StoreKeyId>,
+    ) -> (usize, usize) {
+        let Some(points) = self.non_linear_indices.points(algorithm) else {
+            return (0, 0);
+        };
+        // held by the index less the times held by the store
+        let mut surplus: StdHashMap<StoreKeyId, isize> = StdHashMap::new();
+        for point in points {
+            *surplus
+                .entry(StoreKeyId::from(&StoreKey(point)))
+                .or_default() += 1;
+        }
+        for (key, _) in self.id_to_value.pin().iter() {
+            if !pending.contains(key) {
+                *surplus.entry(key.clone()).or_default() -= 1;
+            }
+        }
+        surplus
+            .into_values()
+            .fold((0, 0), |(missing, orphaned), surplus| {
+                (
+                    missing + (-surplus).max(0) as usize,
+                    orphaned + surplus.max(0) as usize,
+                )
+            })
+    }
+
+    /// Fraction of the entries held since the store was created or compacted that were deleted
+    #[tracing::instrument(skip(self))]
+    fn fragmentation(&self) -> f32 {
+        let deleted = self.deleted.load(Ordering::SeqCst);
+        if deleted == 0 {
+            return 0.0;
+        }
+        deleted as f32 / (deleted + self.len()) as f32
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn drop_predicates(
+        &self,
+        predicates: Vec<MetadataKey>,
+        error_if_not_exists: bool,
+    ) -> Result<usize, ServerError> {
+        let removed = self
+            .predicate_indices
+            .remove_predicates(predicates, error_if_not_exists)?;
+        if removed > 0 {
+            self.recount_bytes();
+        }
+        Ok(removed)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(super) fn delete(&self, keys: impl Iterator<Item = StoreKeyId>) -> usize {
+        let keys: Vec<StoreKeyId> = keys.collect();
+        let pinned = self.id_to_value.pin();
+        let predicates = self.byte_counted_predicates();
+        let counts = self.counted_in();
+        let mut uncounted_bytes = 0;
+        let removed = keys
+            .iter()
+            .flat_map(|k| pinned.remove(k).map(|entry| (k, entry)))
+            .map(|(k, entry)| {
+                if let Some(predicates) = &predicates {
+                    let bytes = self.entry_bytes(k, entry, predicates);
+                    self.bytes.fetch_sub(bytes, Ordering::SeqCst);
+                }
+                if counts.is_some() {
+                    uncounted_bytes += self.quota_bytes(&entry.value);
+                }
+                self.vector(&entry.vector).0
+            })
+            .collect::<Vec<_>>();
+        if let Some(counts) = counts {
+            counts.sub(removed.len(), uncounted_bytes);
+        }
+        self.predicate_indices.remove_store_keys(&keys);
+        self.non_linear_indices.delete(&removed);
+        if self.eviction.is_some() {
+            let access = self.access.pin();
+            for key in &keys {
+                access.remove(key);
+            }
+        }
+        self.deleted.fetch_add(removed.len(), Ordering::SeqCst);
+        removed.len()
+    }
+
+    /// Makes sure the inputs match the store dimension, pointing out the first input that does
+    /// not. A store yet to infer its dimension is empty so any input goes
+    #[tracing::instrument(skip_all)]
+    pub(super) fn check_dimensions<'a>(
+        &self,
+        store_name: &StoreName,
+        inputs: impl IntoIterator<Item = &'a StoreKey>,
+    ) -> Result<(), ServerError> {
+        if self.infer_dimension {
+            return Ok(());
+        }
+        let store_dimension = self.dimension.get();
+        match inputs
+            .into_iter()
+            .enumerate()
+            .find(|(_, input)| input.dimension() != store_dimension)
+        {
+            Some((index, input)) => Err(ServerError::StoreDimensionMismatch {
+                store: store_name.clone(),
+                store_dimension,
+                input_dimension: input.dimension(),
+                index,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Vector of the entry with a key id
+    pub(super) fn vector_of(
+        &self,
+        store_name: &StoreName,
+        key_id: &str,
+    ) -> Result<StoreKey, ServerError> {
+        self.id_to_value
+            .pin()
+            .get(&StoreKeyId(key_id.to_string()))
+            .map(|entry| self.vector(&entry.vector))
+            .ok_or_else(|| ServerError::KeyIdNotFound {
+                store: store_name.clone(),
+                key_id: key_id.to_string(),
+            })
+    }
+
+    /// Vectors of an even sample of `size` entries, the same ones wherever the store holds the
+    /// same keys
+    fn sample_vectors(&self, size: usize) -> Vec<StoreKey> {
+        let pinned = self.id_to_value.pin();
+        let mut mixed: Vec<_> = pinned
+            .iter()
+            .map(|(key_id, entry)| (key_id.mix(self.index_seed), entry))
+            .collect();
+        mixed.sort_unstable_by_key(|(mixed, _)| *mixed);
+        mixed
+            .into_iter()
+            .take(size)
+            .map(|(_, entry)| self.vector(&entry.vector))
+            .collect()
+    }
+
+    /// Adds the vectors of search terms to a search input in proportion to their weights
+    pub(super) fn add_terms(
+        &self,
+        store_name: &StoreName,
+        search_input: StoreKey,
+        terms: &[SearchTerm],
+    ) -> Result<StoreKey, ServerError> {
+        let vectors = terms
+            .iter()
+            .map(|term| match &term.vector {
+                TermVector::Key(store_key) => Ok(store_key.clone()),
+                TermVector::KeyId(key_id) => self.vector_of(store_name, key_id),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // the search input comes first followed by the terms
+        self.check_dimensions(store_name, std::iter::once(&search_input).chain(&vectors))?;
+        let mut combined = search_input.0;
+        for (index, (term, vector)) in terms.iter().zip(vectors).enumerate() {
+            // a store yet to infer its dimension leaves dimensions unchecked
+            if vector.dimension() != combined.len() {
+                return Err(ServerError::StoreDimensionMismatch {
+                    store: store_name.clone(),
+                    store_dimension: combined.len(),
+                    input_dimension: vector.dimension(),
+                    index: index + 1,
+                });
+            }
+            combined.scaled_add(term.weight, &vector.0);
+        }
+        Ok(StoreKey(combined))
+    }
+
+    /// Checks that the inputs can be normalized when the store normalizes its keys
+    pub(super) fn check_norms<'a>(
+        &self,
+        store_name: &StoreName,
+        inputs: impl IntoIterator<Item = &'a StoreKey>,
+    ) -> Result<(), ServerError> {
+        if self.normalization == VectorNormalization::None {
+            return Ok(());
+        }
+        match inputs
+            .into_iter()
+            .position(|input| input.0.iter().all(|value| *value == 0.0))
+        {
+            Some(index) => Err(ServerError::VectorNotNormalizable {
+                store: store_name.clone(),
+                index,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the inputs hold no NaN or infinite values unless the store sanitizes them
+    pub(super) fn check_finite<'a>(
+        &self,
+        store_name: &StoreName,
+        inputs: impl IntoIterator<Item = &'a StoreKey>,
+    ) -> Result<(), ServerError> {
+        if self.non_finite_vectors == NonFiniteVectors::Sanitize {
+            return Ok(());
+        }
+        match inputs
+            .into_iter()
+            .position(|input| !vectors::is_finite(input))
+        {
+            Some(index) => Err(ServerError::NonFiniteVector {
+                store: store_name.clone(),
+                index,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the values of keys being set can be held by the element type of the store,
+    /// which for keys a store sanitizes only leaves integer stores to check
+    fn check_range<'a>(
+        &self,
+        store_name: &StoreName,
+        inputs: impl IntoIterator<Item = &'a StoreKey>,
+    ) -> Result<(), ServerError> {
+        match inputs
+            .into_iter()
+            .position(|input| !vectors::fits(self.key_element_type, input))
+        {
+            Some(index) => Err(ServerError::KeyOutOfRange {
+                store: store_name.clone(),
+                element_type: self.key_element_type,
+                index,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the NaN and infinite values of a key when the store sanitizes them
+    pub(super) fn sanitize(&self, store_key: StoreKey) -> StoreKey {
+        match self.non_finite_vectors {
+            NonFiniteVectors::Reject => store_key,
+            NonFiniteVectors::Sanitize => vectors::sanitize(self.key_element_type, store_key),
+        }
+    }
+
+    /// The algorithm to rank with in place of the one asked for. Cosine similarity between
+    /// vectors of unit length is their dot product, so normalized stores rank with that once the
+    /// search inputs are normalized as well
+    pub(super) fn kernel(&self, algorithm_by_type: AlgorithmByType) -> AlgorithmByType {
+        match (self.normalization, algorithm_by_type) {
+            (
+                VectorNormalization::L2,
+                AlgorithmByType::Linear(LinearAlgorithm::CosineSimilarity),
+            ) => AlgorithmByType::Linear(LinearAlgorithm::DotProductSimilarity),
+            _ => algorithm_by_type,
+        }
+    }
+
+    /// Deletes a bunch of store keys from the store
+    #[tracing::instrument(skip(self, del), fields(key_length=del.len()))]
+    fn delete_keys(&self, del: Vec<StoreKey>) -> usize {
+        if del.is_empty() {
+            return 0;
+        }
+        self.delete(
+            del.into_iter()
+                .map(|store_key| (&self.conform(store_key)).into()),
+        )
+    }
+
+    /// Deletes a bunch of store keys from the store matching a specific predicate
+    #[tracing::instrument(skip(self))]
+    fn delete_matches(&self, condition: &PredicateCondition) -> Result<usize, ServerError> {
+        let matches = self
+            .predicate_indices
+            .matches(condition, self, Deadline::default())?
+            .into_iter();
+        Ok(self.delete(matches))
+    }
+
+    /// Gets a bunch of store keys from the store
+    #[tracing::instrument(skip(self, val), fields(key_length=val.len()))]
+    fn get_keys(&self, val: Vec<StoreKey>) -> Vec<(StoreKey, StoreValue)> {
+        if val.is_empty() {
+            return vec![];
+        }
+        self.get(
+            val.into_iter()
+                .map(|store_key| (&self.conform(store_key)).into()),
+        )
+    }
+
+    /// Sanitizes, normalizes and rounds a key the way the store holds its vectors so that it
+    /// matches the key of its entry. Entries are only ever conformed once as doing it again could
+    /// shift them
+    pub(super) fn conform(&self, store_key: StoreKey) -> StoreKey {
+        let store_key = self.sanitize(store_key);
+        let store_key = match self.normalization {
+            VectorNormalization::None => store_key,
+            VectorNormalization::L2 => vectors::normalize(store_key),
+        };
+        vectors::round(self.key_element_type, store_key)
+    }
+
+    /// Gets a bunch of store entries that matches a predicate condition
+    #[tracing::instrument(skip(self))]
+    fn get_matches(
+        &self,
+        condition: &PredicateCondition,
+        deadline: Deadline,
+    ) -> Result<Vec<(StoreKey, StoreValue)>, ServerError> {
+        let matches = self
+            .predicate_indices
+            .matches(condition, self, deadline)?
+            .into_iter();
+        Ok(self.get(matches))
+    }
+
+    /// Gets the entries that match a predicate condition along with the norms of their vectors
+    #[tracing::instrument(skip(self))]
+    pub(super) fn get_matches_with_norms(
+        &self,
+        condition: &PredicateCondition,
+        deadline: Deadline,
+    ) -> Result<Vec<(StoreKey, StoreValue, f32)>, ServerError> {
+        let matches = self
+            .predicate_indices
+            .matches(condition, self, deadline)?
+            .into_iter();
+        Ok(self.get_with_norms(matches))
+    }
+
+    /// Used whenever there is no found predicate and so we search directly within store
+    #[tracing::instrument(skip(self))]
+    pub(super) fn get_match_without_predicate(
+        &self,
+        predicate: &Predicate,
+        deadline: Deadline,
+    ) -> Result<StdHashSet<StoreKeyId>, ServerError> {
+        let store_val_pinned = self.id_to_value.pin();
+        let res = match predicate {
+            Predicate::Equals { key, value } => deadline
+                .bound(&store_val_pinned)
+                .filter(
+                    |(
+                        _,
+                        Entry {
+                            value: store_value, ..
+                        },
+                    )| {
+                        store_value.get(key).map(|v| v.eq(value)).unwrap_or(false)
+                    },
+                )
+                .map(|(k, _)| k.clone())
+                .collect(),
+            Predicate::NotEquals { key, value } => deadline
+                .bound(&store_val_pinned)
+                .filter(
+                    |(
+                        _,
+                        Entry {
+                            value: store_value, ..
+                        },
+                    )| {
+                        store_value.get(key).map(|v| !v.eq(value)).unwrap_or(true)
+                    },
+                )
+                .map(|(k, _)| k.clone())
+                .collect(),
+            Predicate::In { key, value } => deadline
+                .bound(&store_val_pinned)
+                .filter(
+                    |(
+                        _,
+                        Entry {
+                            value: store_value, ..
+                        },
+                    )| {
+                        store_value
+                            .get(key)
+                            .map(|v| value.contains(v))
+                            .unwrap_or(false)
+                    },
+                )
+                .map(|(k, _)| k.clone())
+                .collect(),
+            Predicate::NotIn { key, value } => deadline
+                .bound(&store_val_pinned)
+                .filter(
+                    |(
+                        _,
+                        Entry {
+                            value: store_value, ..
+                        },
+                    )| {
+                        store_value
+                            .get(key)
+                            .map(|v| !value.contains(v))
+                            .unwrap_or(true)
+                    },
+                )
+                .map(|(k, _)| k.clone())
+                .collect(),
+        };
+        deadline.check()?;
+        Ok(res)
+    }
+
+    /// Keeps the ids of entries whose value passes a check
+    #[tracing::instrument(skip_all)]
+    pub(super) fn filter_ids(
+        &self,
+        ids: StdHashSet<StoreKeyId>,
+        check: impl Fn(&StoreValue) -> bool,
+    ) -> StdHashSet<StoreKeyId> {
+        let pinned = self.id_to_value.pin();
+        ids.into_iter()
+            .filter(|id| pinned.get(id).is_some_and(|entry| check(&entry.value)))
+            .collect()
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn get(&self, keys: impl Iterator<Item = StoreKeyId>) -> Vec<(StoreKey, StoreValue)> {
+        let pinned = self.id_to_value.pin();
+        keys.flat_map(|k| {
+            pinned
+                .get(&k)
+                .map(|entry| (self.vector(&entry.vector), entry.value.clone()))
+        })
+        .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub(super) fn get_all(&self) -> Vec<(StoreKey, StoreValue)> {
+        let pinned = self.id_to_value.pin();
+        pinned
+            .into_iter()
+            .map(|(_key, entry)| (self.vector(&entry.vector), entry.value.clone()))
+            .collect()
+    }
+
+    /// Gets the entries with the lowest key ids after the cursor
+    #[tracing::instrument(skip(self))]
+    fn list(
+        &self,
+        limit: NonZeroUsize,
+        cursor: Option<&str>,
+        include_vectors: bool,
+        condition: Option<&PredicateCondition>,
+    ) -> Result<EntryPage, ServerError> {
+        let pinned = self.id_to_value.pin();
+        let after = |key_id: &StoreKeyId| cursor.map_or(true, |cursor| key_id.0.as_str() > cursor);
+        let (mut key_ids, estimated_total) = match condition {
+            Some(condition) => {
+                let matches =
+                    self.predicate_indices
+                        .matches(condition, self, Deadline::default())?;
+                let total = matches.len();
+                (matches.into_iter().filter(after).collect_vec(), total)
+            }
+            None => (
+                pinned
+                    .keys()
+                    .filter(|key_id| after(key_id))
+                    .cloned()
+                    .collect(),
+                pinned.len(),
+            ),
+        };
+        let more = key_ids.len() > limit.get();
+        if more {
+            // only the lowest key ids need sorting
+            key_ids.select_nth_unstable(limit.get());
+            key_ids.truncate(limit.get());
+        }
+        key_ids.sort_unstable();
+        let next_cursor = more
+            .then(|| key_ids.last().map(|key_id| key_id.0.clone()))
+            .flatten();
+        let entries = key_ids
+            .into_iter()
+            .filter_map(|key_id| {
+                pinned.get(&key_id).map(|entry| ListedEntry {
+                    key: include_vectors.then(|| self.vector(&entry.vector)),
+                    value: entry.value.clone(),
+                    key_id: key_id.into(),
+                })
+            })
+            .collect();
+        Ok(EntryPage {
+            entries,
+            next_cursor,
+            estimated_total,
+        })
+    }
+
+    /// Counts the entries matching a condition. Unless exact, the count of a store larger than
+    /// the sample is estimated from its predicate indices and a sample of its entries without
+    /// matching each entry
+    #[tracing::instrument(skip(self))]
+    fn count_matches(
+        &self,
+        condition: &PredicateCondition,
+        exact: bool,
+        deadline: Deadline,
+    ) -> Result<usize, ServerError> {
+        if exact {
+            return Ok(self
+                .predicate_indices
+                .matches(condition, self, deadline)?
+                .len());
+        }
+        let pinned = self.id_to_value.pin();
+        let store_len = pinned.len();
+        if store_len <= COUNT_SAMPLE_SIZE {
+            return Ok(pinned
+                .values()
+                .filter(|entry| {
+                    self.predicate_indices
+                        .matches_value(condition, &entry.value)
+                })
+                .count());
+        }
+        // key ids are hashes so the entries whose ids mixed with the seed fall under this share
+        // of the range make an even sample, the same one wherever the store holds the same keys
+        let threshold = (u64::MAX / store_len as u64).saturating_mul(COUNT_SAMPLE_SIZE as u64);
+        let sample = pinned
+            .iter()
+            .filter(|(key_id, _)| key_id.mix(self.index_seed) < threshold)
+            .map(|(_, entry)| &entry.value)
+            .collect_vec();
+        let share = self
+            .predicate_indices
+            .estimate_share(condition, store_len, &sample);
+        Ok((share * store_len as f64).round() as usize)
+    }
+
+    /// Gets entries along with the norms of their vectors
+    #[tracing::instrument(skip_all)]
+    fn get_with_norms(
+        &self,
+        keys: impl Iterator<Item = StoreKeyId>,
+    ) -> Vec<(StoreKey, StoreValue, f32)> {
+        let pinned = self.id_to_value.pin();
+        keys.flat_map(|k| pinned.get(&k).map(|entry| self.with_norm(entry)))
+            .collect()
+    }
+
+    /// Gets all entries along with the norms of their vectors
+    #[tracing::instrument(skip(self))]
+    pub(super) fn get_all_with_norms(&self) -> Vec<(StoreKey, StoreValue, f32)> {
+        let pinned = self.id_to_value.pin();
+        pinned
+            .into_iter()
+            .map(|(_key, entry)| self.with_norm(entry))
+            .collect()
+    }
+
+    fn with_norm(&self, entry: &Entry) -> (StoreKey, StoreValue, f32) {
+        let store_key = self.vector(&entry.vector);
+        let norm = if entry.norm > 0.0 {
+            entry.norm
+        } else {
+            vectors::norm(&store_key)
+        };
+        (store_key, entry.value.clone(), norm)
+    }
+
+    /// Adds a bunch of entries, whose dimensions are expected to have been checked and keys
+    /// conformed to the store, into the store
+    /// Returns the len of values added, if a value already existed it is updated but not counted
+    /// as a new insert
+    #[tracing::instrument(skip(self, new), fields(entry_length=new.len()))]
+    fn add(&self, new: Vec<(StoreKey, StoreValue)>) -> Result<StoreUpsert, ServerError> {
+        if new.is_empty() {
+            return Ok(StoreUpsert {
+                inserted: 0,
+                updated: 0,
+            });
+        }
+        let res: Vec<(StoreKeyId, (StoreKey, StoreValue))> = new
+            .into_par_iter()
+            .map(|(store_key, store_val)| ((&store_key).into(), (store_key, store_val)))
+            .collect();
+        let bulk_write = self.bulk_write.load(Ordering::SeqCst);
+        // in bulk write mode only the keys are held on to, for the indices to be updated later
+        let (predicate_insert, pending_predicates): (Vec<_>, Vec<_>) = if bulk_write {
+            (Vec::new(), res.par_iter().map(|(k, _)| k.clone()).collect())
+        } else {
+            (
+                res.par_iter()
+                    .map(|(k, (_, v))| (k.clone(), v.clone()))
+                    .collect(),
+                Vec::new(),
+            )
+        };
+        let written: Vec<StoreKeyId> = match self.eviction {
+            Some(_) => res.iter().map(|(k, _)| k.clone()).collect(),
+            None => Vec::new(),
+        };
+        let vectors = self.vector_refs(&res)?;
+        let predicates = self.byte_counted_predicates();
+        let counts = self.counted_in();
+        let counted_bytes = AtomicUsize::new(0);
+        let uncounted_bytes = AtomicUsize::new(0);
+        let inserted = AtomicUsize::new(0);
+        let updated = AtomicUsize::new(0);
+        let inserted_keys = res
+            .into_par_iter()
+            .zip(vectors)
+            .flat_map_iter(|((k, (store_key, store_value)), vector)| {
+                let pinned = self.id_to_value.pin();
+                let entry = Entry {
+                    vector,
+                    value: store_value,
+                    norm: vectors::norm(&store_key),
+                };
+                let pending_key = bulk_write.then(|| k.clone());
+                let counted = predicates
+                    .as_ref()
+                    .map(|predicates| (self.entry_bytes(&k, &entry, predicates), k.clone()));
+                if counts.is_some() {
+                    counted_bytes.fetch_add(self.quota_bytes(&entry.value), Ordering::SeqCst);
+                }
+                let old = pinned.insert(k, entry);
+                if let (Some(_), Some(old)) = (&counts, old) {
+                    uncounted_bytes.fetch_add(self.quota_bytes(&old.value), Ordering::SeqCst);
+                }
+                if let (Some((bytes, k)), Some(predicates)) = (counted, &predicates) {
+                    // added before the replaced entry is taken off so the count never underflows
+                    self.bytes.fetch_add(bytes, Ordering::SeqCst);
+                    if let Some(old) = old {
+                        let old_bytes = self.entry_bytes(&k, old, predicates);
+                        self.bytes.fetch_sub(old_bytes, Ordering::SeqCst);
+                    }
+                }
+                if old.is_some() {
+                    updated.fetch_add(1, Ordering::SeqCst);
+                } else {
+                    inserted.fetch_add(1, Ordering::SeqCst);
+                    return Some((pending_key, store_key.0));
+                }
+                None
+            })
+            .collect::<Vec<_>>();
+        if bulk_write {
+            let mut pending = self
+                .pending_index_updates
+                .lock()
+                .expect("pending index updates lock poisoned");
+            pending.predicates.extend(pending_predicates);
+            if !self.non_linear_indices.is_empty() {
+                pending
+                    .non_linear
+                    .extend(inserted_keys.into_iter().flat_map(|(key, _)| key));
+            }
+        } else {
+            let predicate_indices = self.predicate_indices.clone();
+            predicate_indices.add(predicate_insert);
+            if !self.non_linear_indices.is_empty() {
+                self.non_linear_indices
+                    .insert(inserted_keys.into_iter().map(|(_, key)| key).collect());
+            }
+        }
+        self.touch(written);
+        let inserted = inserted.into_inner();
+        if let Some(counts) = counts {
+            // added before the replaced entries are taken off so the counts never underflow
+            counts.add(inserted, counted_bytes.into_inner());
+            counts.sub(0, uncounted_bytes.into_inner());
+        }
+        Ok(StoreUpsert {
+            inserted,
+            updated: updated.into_inner(),
+        })
+    }
+
+    /// Where the vectors of entries about to be added are stored. Disk tier stores write the
+    /// vectors of new entries to disk while updated entries keep the vector already written, as
+    /// an entry's vector never changes. Half precision and integer memory tier stores hold the
+    /// bits or bytes of theirs
+    #[tracing::instrument(skip_all)]
+    fn vector_refs(
+        &self,
+        entries: &[(StoreKeyId, (StoreKey, StoreValue))],
+    ) -> Result<Vec<VectorRef>, ServerError> {
+        let Some(disk_vectors) = &self.disk_vectors else {
+            return Ok(entries
+                .par_iter()
+                .map(|(_, (store_key, _))| {
+                    if self.key_element_type == KeyElementType::Int8 {
+                        return VectorRef::Int8 {
+                            int8: vectors::to_int8(store_key),
+                        };
+                    }
+                    match vectors::to_half_bits(self.key_element_type, store_key) {
+                        Some(bits) => VectorRef::Half(bits),
+                        None => VectorRef::Memory(store_key.clone()),
+                    }
+                })
+                .collect());
+        };
+        let pinned = self.id_to_value.pin();
+        let mut vectors: Vec<_> = entries
+            .iter()
+            .map(|(k, _)| pinned.get(k).map(|entry| entry.vector.clone()))
+            .collect();
+        let new: Vec<_> = entries
+            .iter()
+            .zip(&vectors)
+            .filter(|(_, vector)| vector.is_none())
+            .map(|((_, (store_key, _)), _)| store_key)
+            .collect();
+        let mut slots = disk_vectors.append(new.into_iter())?.into_iter();
+        Ok(vectors
+            .iter_mut()
+            .map(|vector| {
+                vector.take().unwrap_or_else(|| {
+                    VectorRef::Disk(slots.next().expect("a slot for every new vector"))
+                })
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn create_pred_index(&self, requested_predicates: Vec<MetadataKey>) -> usize {
+        let current_predicates = self.predicate_indices.current_predicates();
+        let new_predicates: Vec<_> = StdHashSet::from_iter(requested_predicates)
+            .difference(&current_predicates)
+            .cloned()
+            .collect();
+        let new_predicates_len = new_predicates.len();
+        if !new_predicates.is_empty() {
+            // get all the values and reindex
+            let values = self
+                .get_all()
+                .into_iter()
+                .map(|(k, v)| (StoreKeyId::from(&k), v))
+                .collect();
+            self.predicate_indices
+                .add_predicates(new_predicates, Some(values));
+            self.recount_bytes();
+        };
+        new_predicates_len
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn create_non_linear_algorithm_index(
+        &self,
+        non_linear_indices: StdHashSet<NonLinearAlgorithm>,
+    ) -> usize {
+        let current_keys = self.non_linear_indices.current_keys();
+        let new_predicates: StdHashSet<_> = non_linear_indices
+            .difference(&current_keys)
+            .copied()
+            .collect();
+        let new_predicates_len = new_predicates.len();
+        if !new_predicates.is_empty() {
+            // the new indices are loaded with every entry so the existing ones catch up first
+            // rather than have pending entries inserted into the new ones twice
+            self.flush_index_updates();
+            // get all the values and reindex
+            let values: Vec<_> = self.get_all().into_iter().map(|(k, _)| k.0).collect();
+            self.non_linear_indices
+                .insert_indices(new_predicates, &values, self.dimension);
+        };
+        new_predicates_len
+    }
+
+    /// Returns the number of key value pairs in the store
+    #[tracing::instrument(skip(self))]
+    pub(super) fn len(&self) -> usize {
+        self.id_to_value.pin().len()
+    }
+
+    /// Number of entries whose keys are not in the store yet
+    pub(super) fn count_new_keys(&self, new: &[(StoreKey, StoreValue)]) -> usize {
+        let pinned = self.id_to_value.pin();
+        new.iter()
+            .map(|(store_key, _)| StoreKeyId::from(store_key))
+            .filter(|key_id| !pinned.contains_key(key_id))
+            .collect::<StdHashSet<_>>()
+            .len()
+    }
+
+    /// TODO: Fix nested calculation of sizes using size_of_val
+    #[tracing::instrument(skip(self))]
+    pub(super) fn size(&self) -> usize {
+        size_of_val(&self)
+            + size_of_val(&self.dimension)
+            + size_of_val(&self.id_to_value)
+            + self
+                .id_to_value
+                .iter(&self.id_to_value.guard())
+                .map(|(k, v)| {
+                    size_of_val(k)
+                        + size_of_val(&v.vector)
+                        + size_of_val(&v.norm)
+                        + v.value
+                            .iter()
+                            .map(|(inner_k, inner_val)| {
+                                size_of_val(inner_k) + size_of_val(inner_val)
+                            })
+                            .sum::<usize>()
+                })
+                .sum::<usize>()
+            + self
+                .disk_vectors
+                .as_ref()
+                .map(DiskVectors::cache_size_in_bytes)
+                .unwrap_or_default()
+            + self.predicate_indices.size()
+            + self.non_linear_indices.size()
+    }
+
+    /// Estimates the bytes held by the store from the number of its entries, the dimension and
+    /// element type of its vectors and the contents of its metadata and indices
+    #[tracing::instrument(skip(self))]
+    pub(super) fn memory(&self, name: StoreName) -> StoreMemory {
+        let len = self.len();
+        let dimension = self.dimension.get();
+        let vectors = match &self.disk_vectors {
+            Some(disk_vectors) => disk_vectors.cache_size_in_bytes(),
+            None => len * dimension * vectors::element_size(self.key_element_type),
+        };
+        let entries = self
+            .id_to_value
+            .iter(&self.id_to_value.guard())
+            .map(|(key_id, entry)| entry_memory(key_id, entry))
+            .sum();
+        // the points of a non linear index are copies of the vectors held as f32
+        let non_linear_indices = self.non_linear_indices.size()
+            + self.non_linear_indices.current_keys().len() * len * dimension * size_of::<f32>();
+        StoreMemory {
+            name,
+            vectors,
+            entries,
+            predicate_indices: self.predicate_indices.memory(),
+            non_linear_indices,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use pretty_assertions::assert_eq;
+    use std::num::NonZeroUsize;
+
+    use super::*;
+    use ahnlich_types::metadata::MetadataKey;
+    use ahnlich_types::metadata::MetadataValue;
+    use ahnlich_types::predicate::Predicate;
+    use ahnlich_types::RequestLimits;
+    use ndarray::array;
     use ndarray::Array1;
     use std::collections::HashMap as StdHashMap;
+    use utils::cli::CommandLineConfig;
+
+    #[test]
+    fn test_compute_store_key_id_empty_vector() {
+        let array: Array1<f32> = Array1::zeros(0);
+        let store_key: StoreKeyId = (&StoreKey(array)).into();
+        assert_eq!(
+            store_key,
+            StoreKeyId("af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262".into())
+        );
+    }
+
+    #[test]
+    fn test_compute_store_key_id_single_element_array() {
+        let array = array![1.23];
+        let store_key: StoreKeyId = (&StoreKey(array)).into();
+        assert_eq!(
+            store_key,
+            StoreKeyId("ae69ac20168542c9058847862a41c0f24ecd5a935dfabb640bed7d591dd48ae8".into())
+        );
+    }
+
+    #[test]
+    fn test_compute_store_key_id_multiple_elements_array() {
+        let array = array![1.22, 2.11, 3.22, 3.11];
+        let store_key: StoreKeyId = (&StoreKey(array)).into();
+        assert_eq!(
+            store_key,
+            StoreKeyId("c8d293fab65705ee27956818a9b02139fa002b71e4cd416fea055344a907db67".into())
+        );
+    }
+
+    pub(crate) fn create_store_handler_no_loom(
+        predicates: Vec<MetadataKey>,
+        even_dimensions: Option<usize>,
+        odd_dimensions: Option<usize>,
+    ) -> Arc<StoreHandler> {
+        let write_flag = Arc::new(AtomicBool::new(false));
+        let handler = Arc::new(StoreHandler::new(write_flag));
+        let handles = (0..3).map(|i| {
+            let predicates = predicates.clone();
+            let shared_handler = handler.clone();
+            let handle = std::thread::spawn(move || {
+                let (store_name, size) = if i % 2 == 0 {
+                    ("Even", even_dimensions.unwrap_or(5))
+                } else {
+                    ("Odd", odd_dimensions.unwrap_or(3))
+                };
+                shared_handler.create_store(
+                    StoreName(store_name.to_string()),
+                    NonZeroUsize::new(size).unwrap(),
+                    predicates,
+                    StdHashSet::new(),
+                    StoreSettings::default(),
+                    true,
+                )
+            });
+            handle
+        });
+        for handle in handles {
+            let _ = handle.join().unwrap();
+        }
+        handler
+    }
+
+    fn create_store_handler(
+        predicates: Vec<MetadataKey>,
+    ) -> (
+        Arc<StoreHandler>,
+        Vec<Result<(), ServerError>>,
+        Vec<Result<(), ServerError>>,
+    ) {
+        let write_flag = Arc::new(AtomicBool::new(false));
+        let handler = Arc::new(StoreHandler::new(write_flag));
+        let handles = (0..3).map(|i| {
+            let predicates = predicates.clone();
+            let shared_handler = handler.clone();
+            let handle = std::thread::spawn(move || {
+                let (store_name, size) = if i % 2 == 0 { ("Even", 5) } else { ("Odd", 3) };
+                shared_handler.create_store(
+                    StoreName(store_name.to_string()),
+                    NonZeroUsize::new(size).unwrap(),
+                    predicates,
+                    StdHashSet::new(),
+                    StoreSettings::default(),
+                    true,
+                )
+            });
+            handle
+        });
+        let (oks, errs): (Vec<_>, Vec<_>) = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .partition(Result::is_ok);
+
+        (handler, oks, errs)
+    }
+
+    #[test]
+    fn test_get_and_create_store_handler() {
+        let (handler, oks, errs) = create_store_handler(vec![]);
+        assert_eq!(oks.len(), 2);
+        assert_eq!(
+            errs,
+            vec![Err(ServerError::StoreAlreadyExists(StoreName(
+                "Even".to_string()
+            )))]
+        );
+        // test out a store that does not exist
+        let fake_store = StoreName("Random".to_string());
+        assert_eq!(
+            handler.get(&fake_store).unwrap_err(),
+            ServerError::StoreNotFound(fake_store)
+        );
+    }
+
+    #[test]
+    fn test_create_and_set_in_store_fails() {
+        let (handler, _oks, _errs) = create_store_handler(vec![]);
+        let even_store = StoreName("Even".into());
+        let fake_store = StoreName("Fake".into());
+        // set in nonexistent store should fail
+        assert_eq!(
+            handler.set_in_store(&fake_store, vec![]).unwrap_err(),
+            ServerError::StoreNotFound(fake_store)
+        );
+        // set in store with wrong dimensions should fail
+        assert_eq!(
+            handler
+                .set_in_store(
+                    &even_store,
+                    vec![(
+                        StoreKey(array![0.33, 0.44, 0.5]),
+                        StdHashMap::from_iter(vec![(
+                            MetadataKey::new("author".into()),
+                            MetadataValue::RawString("Vincent".into()),
+                        ),])
+                    ),]
+                )
+                .unwrap_err(),
+            ServerError::StoreDimensionMismatch {
+                store: even_store,
+                store_dimension: 5,
+                input_dimension: 3,
+                index: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_create_and_set_in_store_passes() {
+        let (handler, _oks, _errs) = create_store_handler(vec![]);
+        let odd_store = StoreName("Odd".into());
+        let input_arr = array![0.1, 0.2, 0.3];
+        let ret = handler
+            .set_in_store(
+                &odd_store,
+                vec![(
+                    StoreKey(input_arr.clone()),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("author".into()),
+                        MetadataValue::RawString("Lex Luthor".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        assert_eq!(
+            ret,
+            StoreUpsert {
+                inserted: 1,
+                updated: 0,
+            }
+        );
+        let ret = handler
+            .set_in_store(
+                &odd_store,
+                vec![(
+                    StoreKey(input_arr.clone()),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("author".into()),
+                        MetadataValue::RawString("Clark Kent".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        assert_eq!(
+            ret,
+            StoreUpsert {
+                inserted: 0,
+                updated: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_add_index_in_store() {
+        let handler =
+            create_store_handler_no_loom(vec![MetadataKey::new("author".into())], None, None);
+        let even_store = StoreName("Even".into());
+        let input_arr_1 = array![0.1, 0.2, 0.3, 0.0, 0.0];
+        let input_arr_2 = array![0.2, 0.3, 0.4, 0.0, 0.0];
+        let input_arr_3 = array![0.3, 0.4, 0.4, 0.0, 0.0];
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    StoreKey(input_arr_1.clone()),
+                    StdHashMap::from_iter(vec![
+                        (
+                            MetadataKey::new("author".into()),
+                            MetadataValue::RawString("Lex Luthor".into()),
+                        ),
+                        (
+                            MetadataKey::new("planet".into()),
+                            MetadataValue::RawString("earth".into()),
+                        ),
+                    ]),
+                )],
+            )
+            .unwrap();
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    StoreKey(input_arr_2.clone()),
+                    StdHashMap::from_iter(vec![
+                        (
+                            MetadataKey::new("author".into()),
+                            MetadataValue::RawString("Clark Kent".into()),
+                        ),
+                        (
+                            MetadataKey::new("planet".into()),
+                            MetadataValue::RawString("krypton".into()),
+                        ),
+                    ]),
+                )],
+            )
+            .unwrap();
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    StoreKey(input_arr_3.clone()),
+                    StdHashMap::from_iter(vec![
+                        (
+                            MetadataKey::new("author".into()),
+                            MetadataValue::RawString("General Zod".into()),
+                        ),
+                        (
+                            MetadataKey::new("planet".into()),
+                            MetadataValue::RawString("krypton".into()),
+                        ),
+                    ]),
+                )],
+            )
+            .unwrap();
+        let condition = &PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("author".into()),
+            value: MetadataValue::RawString("Lex Luthor".into()),
+        });
+        let res = handler
+            .get_pred_in_store(&even_store, &condition, Deadline::default())
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        let condition = &PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("author".into()),
+            value: MetadataValue::RawString("Lex Luthor".into()),
+        });
+        let res = handler
+            .get_pred_in_store(&even_store, &condition, Deadline::default())
+            .unwrap();
+        assert_eq!(res.len(), 2);
+        let condition = &PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("author".into()),
+            value: MetadataValue::RawString("Lex Luthor".into()),
+        })
+        .or(PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("planet".into()),
+            value: MetadataValue::RawString("earth".into()),
+        }));
+        let res = handler.get_pred_in_store(&even_store, &condition, Deadline::default());
+        assert_eq!(res.unwrap().len(), 2);
+        handler
+            .create_pred_index(
+                &even_store,
+                vec![
+                    MetadataKey::new("author".into()),
+                    MetadataKey::new("planet".into()),
+                ],
+            )
+            .unwrap();
+        let res = handler
+            .get_pred_in_store(&even_store, &condition, Deadline::default())
+            .unwrap();
+        assert_eq!(res.len(), 2);
+    }
+
+    #[test]
+    fn test_get_key_in_store() {
+        let (handler, _oks, _errs) = create_store_handler(vec![]);
+        let odd_store = StoreName("Odd".into());
+        let fake_store = StoreName("Fakest".into());
+        let input_arr_1 = array![0.1, 0.2, 0.3];
+        let input_arr_2 = array![0.2, 0.3, 0.4];
+        handler
+            .set_in_store(
+                &odd_store,
+                vec![(
+                    StoreKey(input_arr_1.clone()),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("author".into()),
+                        MetadataValue::RawString("Lex Luthor".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        handler
+            .set_in_store(
+                &odd_store,
+                vec![(
+                    StoreKey(input_arr_2.clone()),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("author".into()),
+                        MetadataValue::RawString("Clark Kent".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        assert_eq!(
+            handler.get_key_in_store(&fake_store, vec![]).unwrap_err(),
+            ServerError::StoreNotFound(fake_store)
+        );
+        let ret = handler
+            .get_key_in_store(
+                &odd_store,
+                vec![StoreKey(input_arr_1), StoreKey(input_arr_2)],
+            )
+            .unwrap();
+        assert_eq!(ret.len(), 2);
+        assert_eq!(
+            ret[0]
+                .1
+                .get(&MetadataKey::new("author".into()))
+                .cloned()
+                .unwrap(),
+            MetadataValue::RawString("Lex Luthor".into())
+        );
+        assert_eq!(
+            ret[1]
+                .1
+                .get(&MetadataKey::new("author".into()))
+                .cloned()
+                .unwrap(),
+            MetadataValue::RawString("Clark Kent".into())
+        );
+    }
+
+    #[test]
+    fn test_get_pred_in_store() {
+        let handler =
+            create_store_handler_no_loom(vec![MetadataKey::new("rank".into())], None, None);
+        let even_store = StoreName("Even".into());
+        let input_arr_1 = array![0.1, 0.2, 0.3, 0.4, 0.5];
+        let input_arr_2 = array![0.2, 0.3, 0.4, 0.5, 0.6];
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    StoreKey(input_arr_1.clone()),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("rank".into()),
+                        MetadataValue::RawString("Joinin".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        handler
+            .set_in_store(
+                &even_store,
+                vec![(
+                    StoreKey(input_arr_2.clone()),
+                    StdHashMap::from_iter(vec![(
+                        MetadataKey::new("rank".into()),
+                        MetadataValue::RawString("Genin".into()),
+                    )]),
+                )],
+            )
+            .unwrap();
+        let condition = &PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("rank".into()),
+            value: MetadataValue::RawString("Hokage".into()),
+        });
+        let res = handler
+            .get_pred_in_store(&even_store, &condition, Deadline::default())
+            .unwrap();
+        assert!(res.is_empty());
+        let condition = &PredicateCondition::Value(Predicate::NotEquals {
+            key: MetadataKey::new("rank".into()),
+            value: MetadataValue::RawString("Hokage".into()),
+        });
+        let res = handler
+            .get_pred_in_store(&even_store, &condition, Deadline::default())
+            .unwrap();
+        assert_eq!(res.len(), 2);
+        let condition = &PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("rank".into()),
+            value: MetadataValue::RawString("Joinin".into()),
+        });
+        let res = handler
+            .get_pred_in_store(&even_store, &condition, Deadline::default())
+            .unwrap();
+        assert_eq!(res.len(), 1);
+    }
+
+    #[test]
+    fn test_count_pred_in_store() {
+        let handler =
+            create_store_handler_no_loom(vec![MetadataKey::new("lang".into())], None, None);
+        let even_store = StoreName("Even".into());
+        let value = |i: usize| {
+            StdHashMap::from_iter([
+                (
+                    MetadataKey::new("lang".into()),
+                    MetadataValue::RawString(if i % 4 == 0 { "de" } else { "en" }.into()),
+                ),
+                (
+                    MetadataKey::new("parity".into()),
+                    MetadataValue::RawString(format!("{}", i / 4 % 2)),
+                ),
+            ])
+        };
+        let entry = |i: usize| (StoreKey(Array1::from_elem(5, i as f32)), value(i));
+        let equals = |key: &str, value: &str| {
+            PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new(key.into()),
+                value: MetadataValue::RawString(value.into()),
+            })
+        };
+        let count = |condition: &PredicateCondition, exact: bool| {
+            handler
+                .count_pred_in_store(&even_store, condition, exact, Deadline::default())
+                .unwrap()
+        };
+        // small stores are counted exactly either way
+        handler
+            .set_in_store(&even_store, (0..100).map(entry).collect())
+            .unwrap();
+        let german_and_even = equals("lang", "de").and(equals("parity", "0"));
+        assert_eq!(count(&german_and_even, true), 13);
+        assert_eq!(count(&german_and_even, false), 13);
+
+        handler
+            .set_in_store(&even_store, (100..4000).map(entry).collect())
+            .unwrap();
+        assert_eq!(count(&equals("lang", "de"), true), 1000);
+        // taken from the index of the key
+        assert_eq!(count(&equals("lang", "de"), false), 1000);
+        // estimated from a sample
+        let estimate = count(&equals("parity", "1"), false);
+        assert!((1800..=2200).contains(&estimate), "{estimate}");
+        let estimate = count(&equals("lang", "de").or(equals("parity", "1")), false);
+        assert!((2300..=2700).contains(&estimate), "{estimate}");
+        assert_eq!(count(&equals("lang", "fr"), false), 0);
+        assert_eq!(
+            handler
+                .count_pred_in_store(
+                    &StoreName("Fakest".into()),
+                    &equals("lang", "de"),
+                    false,
+                    Deadline::default()
+                )
+                .unwrap_err(),
+            ServerError::StoreNotFound(StoreName("Fakest".into()))
+        );
+    }
 
     #[test]
-    fn test_compute_store_key_id_empty_vector() {
-        let array: Array1<f32> = Array1::zeros(0);
-        let store_key: StoreKeyId = (&StoreKey(array)).into();
+    fn test_ordered_ties() {
+        let store_name = StoreName("Ties".into());
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
+            )
+            .unwrap();
+        // four keys at each distance from the origin
+        let keys: Vec<_> = (1..=5)
+            .flat_map(|distance| {
+                let distance = distance as f32;
+                [
+                    [distance, 0.0],
+                    [-distance, 0.0],
+                    [0.0, distance],
+                    [0.0, -distance],
+                ]
+            })
+            .map(|key| StoreKey(Array1::from(key.to_vec())))
+            .collect();
+        handler
+            .set_in_store(
+                &store_name,
+                keys.iter()
+                    .map(|key| (key.clone(), StdHashMap::new()))
+                    .collect(),
+            )
+            .unwrap();
+        let search = |algorithm: Algorithm, closest_n: usize| {
+            handler
+                .get_sim_in_store(
+                    &store_name,
+                    StoreKey(Array1::zeros(2)),
+                    NonZeroUsize::new(closest_n).unwrap(),
+                    algorithm,
+                    None,
+                    GetSimNOptions::default(),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|(store_key, _, similarity)| (StoreKeyId::from(&store_key), similarity.0))
+                .collect::<Vec<_>>()
+        };
+        let mut expected: Vec<_> = keys
+            .iter()
+            .map(|key| (StoreKeyId::from(key), vectors::norm(key)))
+            .collect();
+        expected.sort_by(|first, second| first.1.total_cmp(&second.1).then(first.0.cmp(&second.0)));
+        // the scan picks the ties cut off at the end by key id as well
+        assert_eq!(search(Algorithm::EuclideanDistance, 6), expected[..6]);
+
+        // the index orders the ties among the results it finds
+        let found = search(Algorithm::KDTree, 8);
+        assert_eq!(found.len(), 8);
+        assert!(
+            found
+                .windows(2)
+                .all(|pair| pair[0].1 < pair[1].1
+                    || (pair[0].1 == pair[1].1 && pair[0].0 < pair[1].0))
+        );
+    }
+
+    #[test]
+    fn test_non_finite_vectors() {
+        let rejecting = StoreName("Rejecting".into());
+        let sanitizing = StoreName("Sanitizing".into());
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        for (store_name, non_finite_vectors) in [
+            (&rejecting, NonFiniteVectors::Reject),
+            (&sanitizing, NonFiniteVectors::Sanitize),
+        ] {
+            handler
+                .create_store(
+                    store_name.clone(),
+                    NonZeroUsize::new(2).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    StoreSettings {
+                        non_finite_vectors,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+        }
+        let entry = |key: [f32; 2]| (StoreKey(Array1::from(key.to_vec())), StdHashMap::new());
+        let search = |store_name: &StoreName, key: [f32; 2]| {
+            handler.get_sim_in_store(
+                store_name,
+                StoreKey(Array1::from(key.to_vec())),
+                NonZeroUsize::new(3).unwrap(),
+                Algorithm::EuclideanDistance,
+                None,
+                GetSimNOptions::default(),
+            )
+        };
+
         assert_eq!(
-            store_key,
-            StoreKeyId("af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262".into())
+            handler
+                .set_in_store(&rejecting, vec![entry([1.0, 1.0]), entry([f32::NAN, 1.0])])
+                .unwrap_err(),
+            ServerError::NonFiniteVector {
+                store: rejecting.clone(),
+                index: 1,
+            }
+        );
+        assert_eq!(handler.get(&rejecting).unwrap().len(), 0);
+        handler
+            .set_in_store(&rejecting, vec![entry([1.0, 1.0])])
+            .unwrap();
+        assert_eq!(
+            search(&rejecting, [f32::INFINITY, 0.0]).unwrap_err(),
+            ServerError::NonFiniteVector {
+                store: rejecting.clone(),
+                index: 0,
+            }
+        );
+
+        handler
+            .set_in_store(
+                &sanitizing,
+                vec![entry([f32::NAN, 1.0]), entry([f32::NEG_INFINITY, 2.0])],
+            )
+            .unwrap();
+        let mut keys: Vec<_> = handler
+            .get(&sanitizing)
+            .unwrap()
+            .get_all()
+            .into_iter()
+            .map(|(store_key, _)| store_key.0.to_vec())
+            .collect();
+        keys.sort_by(|a, b| a[1].total_cmp(&b[1]));
+        assert_eq!(keys, vec![vec![0.0, 1.0], vec![-f32::MAX, 2.0]]);
+        // the sanitized search input finds the entry it was sanitized into
+        let found = search(&sanitizing, [f32::NAN, 1.0]).unwrap();
+        assert_eq!(found[0].0, StoreKey(Array1::from(vec![0.0, 1.0])));
+        assert_eq!(found[0].2, Similarity(0.0));
+
+        // entries written before the store checked for them
+        let store = handler.get(&rejecting).unwrap();
+        store
+            .add(vec![entry([f32::NAN, 0.0]), entry([2.0, f32::INFINITY])])
+            .unwrap();
+        let scrubbed = handler.scrub_store(&rejecting, false).unwrap();
+        assert_eq!(scrubbed.len(), 2);
+        assert!(scrubbed
+            .iter()
+            .all(|entry| !vectors::is_finite(entry.key.as_ref().unwrap())));
+        assert_eq!(store.len(), 3);
+        // NaN is not equal to itself so the entries are told apart by key id
+        let key_ids = |entries: Vec<ListedEntry>| {
+            entries
+                .into_iter()
+                .map(|entry| entry.key_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            key_ids(handler.scrub_store(&rejecting, true).unwrap()),
+            key_ids(scrubbed)
+        );
+        assert_eq!(store.len(), 1);
+        assert_eq!(handler.scrub_store(&rejecting, false).unwrap(), vec![]);
+        assert_eq!(
+            search(&rejecting, [1.0, 1.0]).unwrap()[0].0,
+            StoreKey(Array1::from(vec![1.0, 1.0]))
+        );
+    }
+
+    #[test]
+    fn test_index_seed() {
+        let store_name = StoreName("Seeded".into());
+        let parity = MetadataKey::new("parity".into());
+        let entry = |i: usize| {
+            (
+                StoreKey(Array1::from_elem(2, i as f32)),
+                StdHashMap::from_iter([(
+                    parity.clone(),
+                    MetadataValue::RawString(format!("{}", i % 2)),
+                )]),
+            )
+        };
+        let seeded_handler = |index_seed: Option<u64>| {
+            let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+            handler
+                .create_store(
+                    store_name.clone(),
+                    NonZeroUsize::new(2).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    StoreSettings {
+                        index_seed,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+            handler
+                .set_in_store(&store_name, (0..3000).map(entry).collect())
+                .unwrap();
+            handler
+        };
+        let describe = |handler: &StoreHandler| {
+            handler
+                .describe_store(
+                    &store_name,
+                    &LimitHandler::new(&CommandLineConfig::default()),
+                )
+                .unwrap()
+                .index_seed
+        };
+        let estimate = |handler: &StoreHandler| {
+            handler
+                .count_pred_in_store(
+                    &store_name,
+                    &PredicateCondition::Value(Predicate::Equals {
+                        key: parity.clone(),
+                        value: MetadataValue::RawString("1".into()),
+                    }),
+                    false,
+                    Deadline::default(),
+                )
+                .unwrap()
+        };
+        // derived from the name when not given
+        let (first, second) = (seeded_handler(None), seeded_handler(None));
+        assert_eq!(describe(&first), default_index_seed(&store_name));
+        assert_eq!(describe(&first), describe(&second));
+        assert_eq!(estimate(&first), estimate(&second));
+
+        // the same seed samples the same entries
+        let (first, second) = (seeded_handler(Some(7)), seeded_handler(Some(7)));
+        assert_eq!(describe(&first), 7);
+        let estimate_with_seed = estimate(&first);
+        assert_eq!(estimate_with_seed, estimate(&second));
+        assert!(
+            (1200..=1800).contains(&estimate_with_seed),
+            "{estimate_with_seed}"
+        );
+    }
+
+    #[test]
+    fn test_list_entries() {
+        let handler =
+            create_store_handler_no_loom(vec![MetadataKey::new("rank".into())], None, None);
+        let even_store = StoreName("Even".into());
+        let rank = |i: usize| {
+            StdHashMap::from_iter([(
+                MetadataKey::new("rank".into()),
+                MetadataValue::RawString(format!("{}", i % 2)),
+            )])
+        };
+        let entry = |i: usize| (StoreKey(Array1::from_elem(5, i as f32)), rank(i));
+        handler
+            .set_in_store(&even_store, (0..5).map(entry).collect())
+            .unwrap();
+        let limit = NonZeroUsize::new(2).unwrap();
+        let mut cursor = None;
+        let mut listed = vec![];
+        loop {
+            let page = handler
+                .list_entries(&even_store, limit, cursor.as_deref(), false, None)
+                .unwrap();
+            assert_eq!(page.estimated_total, 5);
+            assert!(page.entries.len() <= 2);
+            assert!(page.entries.iter().all(|entry| entry.key.is_none()));
+            listed.extend(page.entries.into_iter().map(|entry| entry.key_id));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        let mut expected = (0..5)
+            .map(|i| StoreKeyId::from(&entry(i).0).0)
+            .collect_vec();
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        let odd = PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("rank".into()),
+            value: MetadataValue::RawString("1".into()),
+        });
+        let page = handler
+            .list_entries(&even_store, limit, None, true, Some(&odd))
+            .unwrap();
+        assert_eq!(page.estimated_total, 2);
+        assert_eq!(page.next_cursor, None);
+        let mut keys = page
+            .entries
+            .into_iter()
+            .map(|entry| entry.key.unwrap())
+            .collect_vec();
+        keys.sort_by(|a, b| a.0[0].total_cmp(&b.0[0]));
+        assert_eq!(keys, vec![entry(1).0, entry(3).0]);
+    }
+
+    #[test]
+    fn test_compact_store() {
+        let handler =
+            create_store_handler_no_loom(vec![MetadataKey::new("rank".into())], None, None);
+        let even_store = StoreName("Even".into());
+        let rank = |i: usize| {
+            StdHashMap::from_iter([(
+                MetadataKey::new("rank".into()),
+                MetadataValue::RawString(format!("{i}")),
+            )])
+        };
+        let entry = |i: usize| (StoreKey(Array1::from_elem(5, i as f32)), rank(i));
+        handler
+            .set_in_store(&even_store, (0..10).map(entry).collect())
+            .unwrap();
+        let deleted = handler
+            .del_key_in_store(
+                &even_store,
+                (0..8)
+                    .map(|i| StoreKey(Array1::from_elem(5, i as f32)))
+                    .collect(),
+            )
+            .unwrap();
+        assert_eq!(deleted, 8);
+        assert_eq!(handler.fragmented_stores(0.8), vec![even_store.clone()]);
+        assert!(handler.fragmented_stores(0.9).is_empty());
+
+        let compaction = handler.compact_store(&even_store).unwrap();
+        // the predicate index no longer holds the values of the deleted entries
+        assert!(compaction.reclaimed_bytes > 0);
+        assert!(handler.fragmented_stores(0.1).is_empty());
+        let res = handler
+            .get_pred_in_store(
+                &even_store,
+                &PredicateCondition::Value(Predicate::Equals {
+                    key: MetadataKey::new("rank".into()),
+                    value: MetadataValue::RawString("9".into()),
+                }),
+                Deadline::default(),
+            )
+            .unwrap();
+        assert_eq!(res, vec![entry(9)]);
+        assert!(matches!(
+            handler.compact_store(&StoreName("Missing".into())),
+            Err(ServerError::StoreNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_compact_store_keeps_concurrent_writes() {
+        let handler = create_store_handler_no_loom(vec![], None, None);
+        let even_store = StoreName("Even".into());
+        let entry = |i: usize| (StoreKey(Array1::from_elem(5, i as f32)), StdHashMap::new());
+        handler
+            .set_in_store(&even_store, (0..100).map(entry).collect())
+            .unwrap();
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let handler = handler.clone();
+                let even_store = even_store.clone();
+                std::thread::spawn(move || {
+                    for i in 0..50 {
+                        handler
+                            .set_in_store(&even_store, vec![entry(100 + writer * 50 + i)])
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..5 {
+            handler.compact_store(&even_store).unwrap();
+        }
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(handler.get(&even_store).unwrap().len(), 300);
+    }
+
+    #[test]
+    fn test_check_store() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Checked".into());
+        let rank_key = MetadataKey::new("rank".into());
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![rank_key.clone()],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
+            )
+            .unwrap();
+        let rank = |i: usize| {
+            StdHashMap::from_iter([(
+                rank_key.clone(),
+                MetadataValue::RawString(format!("{}", i % 3)),
+            )])
+        };
+        let vector = |i: usize| Array1::from(vec![i as f32, (i % 4) as f32]);
+        let entry = |i: usize| (StoreKey(vector(i)), rank(i));
+        handler
+            .set_in_store(&store_name, (0..20).map(entry).collect())
+            .unwrap();
+        let index_check = |index: StoreIndex, missing: usize, orphaned: usize| IndexCheck {
+            index,
+            missing,
+            orphaned,
+        };
+        let consistent = |entries: usize| StoreCheck {
+            entries,
+            indices: vec![
+                index_check(StoreIndex::Predicate(rank_key.clone()), 0, 0),
+                index_check(StoreIndex::NonLinear(NonLinearAlgorithm::KDTree), 0, 0),
+            ],
+            repair_job: None,
+        };
+        assert_eq!(handler.check_store(&store_name).unwrap(), consistent(20));
+
+        // an index missing an entry and holding one the store does not
+        let store = handler.get(&store_name).unwrap();
+        store
+            .predicate_indices
+            .remove_store_keys(&[StoreKeyId::from(&entry(1).0)]);
+        store
+            .predicate_indices
+            .add(vec![(StoreKeyId::from(&StoreKey(vector(50))), rank(1))]);
+        store.non_linear_indices.insert_indices(
+            StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+            &(2..20).map(vector).collect_vec(),
+            NonZeroUsize::new(2).unwrap(),
+        );
+        store.non_linear_indices.insert(vec![vector(50)]);
+        let check = handler.check_store(&store_name).unwrap();
+        assert_eq!(
+            check.indices,
+            vec![
+                index_check(StoreIndex::Predicate(rank_key.clone()), 1, 1),
+                index_check(StoreIndex::NonLinear(NonLinearAlgorithm::KDTree), 2, 1),
+            ]
+        );
+        for index in check.indices {
+            handler
+                .repair_store_index(&store_name, &index.index)
+                .unwrap();
+        }
+        assert_eq!(handler.check_store(&store_name).unwrap(), consistent(20));
+        let res = handler
+            .get_pred_in_store(
+                &store_name,
+                &PredicateCondition::Value(Predicate::Equals {
+                    key: rank_key.clone(),
+                    value: MetadataValue::RawString("1".into()),
+                }),
+                Deadline::default(),
+            )
+            .unwrap();
+        assert_eq!(res.len(), 7);
+        assert!(res.contains(&entry(1)));
+
+        // entries the indices are yet to catch up with are left out
+        handler.set_bulk_write(&store_name, true).unwrap();
+        handler
+            .set_in_store(&store_name, (20..25).map(entry).collect())
+            .unwrap();
+        assert_eq!(handler.check_store(&store_name).unwrap(), consistent(25));
+        handler.set_bulk_write(&store_name, false).unwrap();
+        assert_eq!(handler.check_store(&store_name).unwrap(), consistent(25));
+        assert_eq!(
+            handler
+                .check_store(&StoreName("Missing".into()))
+                .unwrap_err(),
+            ServerError::StoreNotFound(StoreName("Missing".into()))
+        );
+    }
+
+    #[test]
+    fn test_disk_tier_store() {
+        let mut handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let disk_store = StoreName("Disk".into());
+        let create = |handler: &StoreHandler| {
+            handler.create_store(
+                disk_store.clone(),
+                NonZeroUsize::new(3).unwrap(),
+                vec![MetadataKey::new("rank".into())],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings {
+                    storage_tier: StorageTier::Disk,
+                    ..Default::default()
+                },
+                true,
+            )
+        };
+        assert_eq!(
+            create(&handler),
+            Err(ServerError::VectorStorageNotConfigured)
+        );
+        let location = std::env::temp_dir().join("ahnlich_test_disk_tier_store");
+        std::fs::create_dir_all(&location).unwrap();
+        handler.use_vector_storage(location.clone(), 1024);
+        create(&handler).unwrap();
+
+        let rank = |i: usize| {
+            StdHashMap::from_iter([(
+                MetadataKey::new("rank".into()),
+                MetadataValue::RawString(format!("{i}")),
+            )])
+        };
+        let entry = |i: usize| (StoreKey(array![i as f32, 1.0, 0.5]), rank(i));
+        let upsert = handler
+            .set_in_store(&disk_store, (0..100).map(entry).collect())
+            .unwrap();
+        assert_eq!(upsert.inserted, 100);
+        // updates keep the vector already on disk
+        let upsert = handler
+            .set_in_store(&disk_store, vec![(entry(5).0, rank(500))])
+            .unwrap();
+        assert_eq!(upsert.updated, 1);
+        assert_eq!(
+            handler
+                .get_key_in_store(&disk_store, vec![entry(5).0])
+                .unwrap(),
+            vec![(entry(5).0, rank(500))]
+        );
+        let res = handler
+            .get_sim_in_store(
+                &disk_store,
+                StoreKey(array![42.0, 1.0, 0.5]),
+                NonZeroUsize::new(1).unwrap(),
+                Algorithm::EuclideanDistance,
+                None,
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(res[0].0, entry(42).0);
+        let res = handler
+            .get_sim_in_store(
+                &disk_store,
+                StoreKey(array![42.0, 1.0, 0.5]),
+                NonZeroUsize::new(1).unwrap(),
+                Algorithm::KDTree,
+                None,
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(res[0].0, entry(42).0);
+
+        handler
+            .del_key_in_store(&disk_store, (0..50).map(|i| entry(i).0).collect())
+            .unwrap();
+        handler.compact_store(&disk_store).unwrap();
+        assert_eq!(
+            handler
+                .get_pred_in_store(
+                    &disk_store,
+                    &PredicateCondition::Value(Predicate::Equals {
+                        key: MetadataKey::new("rank".into()),
+                        value: MetadataValue::RawString("99".into()),
+                    }),
+                    Deadline::default()
+                )
+                .unwrap(),
+            vec![entry(99)]
+        );
+        let description = handler
+            .describe_store(
+                &disk_store,
+                &LimitHandler::new(&CommandLineConfig::default()),
+            )
+            .unwrap();
+        assert_eq!(description.storage_tier, StorageTier::Disk);
+        assert_eq!(description.info.len, 50);
+
+        handler.drop_store(disk_store, true).unwrap();
+        std::fs::remove_dir_all(location).unwrap();
+    }
+
+    #[test]
+    fn test_discarded_vector_files_outlive_snapshots() {
+        let location = tempfile::tempdir().unwrap();
+        let snapshots = tempfile::tempdir().unwrap();
+        let mut handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        handler.use_vector_storage(location.path().to_path_buf(), 1024);
+        handler.keep_discarded_files();
+        let disk_store = StoreName("Disk".into());
+        let memory_store = StoreName("Memory".into());
+        for (store_name, storage_tier) in [
+            (&disk_store, StorageTier::Disk),
+            (&memory_store, StorageTier::Memory),
+        ] {
+            handler
+                .create_store(
+                    store_name.clone(),
+                    NonZeroUsize::new(2).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    StoreSettings {
+                        storage_tier,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+            handler
+                .set_in_store(
+                    store_name,
+                    vec![(StoreKey(array![1.0, 2.0]), StdHashMap::new())],
+                )
+                .unwrap();
+        }
+        let vector_files = || std::fs::read_dir(location.path()).unwrap().count();
+        let persist = |name: &str| {
+            let persist_location = snapshots.path().join(name);
+            let snapshot = handler.get_snapshot();
+            snapshot.writing();
+            let mut file = std::fs::File::create(&persist_location).unwrap();
+            utils::persistence::write_snapshot(&mut file, &snapshot).unwrap();
+            snapshot.persisted();
+            persist_location
+        };
+        let load = |persist_location: &PathBuf| {
+            let mut restored = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+            restored.use_snapshot(
+                utils::persistence::Persistence::load_snapshot(persist_location, None).unwrap(),
+            );
+            restored
+        };
+
+        let before_drop = persist("before_drop.dat");
+        assert_eq!(vector_files(), 1);
+        handler.drop_store(disk_store.clone(), true).unwrap();
+        // the snapshot persisted last still loads the vectors of the dropped store
+        assert_eq!(vector_files(), 1);
+        assert!(load(&before_drop).get(&disk_store).is_ok());
+
+        persist("after_drop.dat");
+        assert_eq!(vector_files(), 0);
+        // a store whose vector file is missing is left out of the snapshot alone
+        let restored = load(&before_drop);
+        assert!(restored.get(&disk_store).is_err());
+        assert!(restored.get(&memory_store).is_ok());
+    }
+
+    #[test]
+    fn test_half_precision_store() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Half".into());
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(3).unwrap(),
+                vec![],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings {
+                    key_element_type: KeyElementType::Float16,
+                    ..Default::default()
+                },
+                true,
+            )
+            .unwrap();
+        let keys = [
+            StoreKey(array![0.1, 0.2, 0.3]),
+            StoreKey(array![1.1, 1.2, 1.3]),
+            StoreKey(array![-0.1, -0.2, -0.3]),
+        ];
+        let upsert = handler
+            .set_in_store(
+                &store_name,
+                keys.iter()
+                    .map(|key| (key.clone(), StdHashMap::new()))
+                    .collect(),
+            )
+            .unwrap();
+        assert_eq!(upsert.inserted, 3);
+        // keys that round to the same half precision values are the same entry
+        let upsert = handler
+            .set_in_store(
+                &store_name,
+                vec![(StoreKey(array![0.09999, 0.2, 0.3]), StdHashMap::new())],
+            )
+            .unwrap();
+        assert_eq!(upsert.updated, 1);
+
+        let found = handler
+            .get_key_in_store(&store_name, vec![keys[0].clone()])
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        let rounded = &found[0].0;
+        assert_ne!(rounded, &keys[0]);
+        assert!(rounded
+            .0
+            .iter()
+            .zip(keys[0].0.iter())
+            .all(|(x, y)| (x - y).abs() < 1e-3));
+
+        let similar = handler
+            .get_sim_in_store(
+                &store_name,
+                keys[1].clone(),
+                NonZeroUsize::new(1).unwrap(),
+                Algorithm::KDTree,
+                None,
+                GetSimNOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_eq!(
+            StoreKeyId::from(&similar[0].0),
+            StoreKeyId::from(&vectors::round(KeyElementType::Float16, keys[1].clone()))
+        );
+        assert_eq!(
+            handler
+                .describe_store(
+                    &store_name,
+                    &LimitHandler::new(&CommandLineConfig::default())
+                )
+                .unwrap()
+                .key_element_type,
+            KeyElementType::Float16
+        );
+        assert_eq!(
+            handler
+                .del_key_in_store(&store_name, vec![keys[2].clone()])
+                .unwrap(),
+            1
         );
-    }
+        assert_eq!(handler.get(&store_name).unwrap().len(), 2);
 
-    #[test]
-    fn test_compute_store_key_id_single_element_array() {
-        let array = array![1.23];
-        let store_key: StoreKeyId = (&StoreKey(array)).into();
+        let store = handler.get(&store_name).unwrap();
+        let restored: Store =
+            serde_json::from_str(&serde_json::to_string(&*store).unwrap()).unwrap();
+        assert_eq!(restored.key_element_type, KeyElementType::Float16);
         assert_eq!(
-            store_key,
-            StoreKeyId("ae69ac20168542c9058847862a41c0f24ecd5a935dfabb640bed7d591dd48ae8".into())
+            StdHashSet::<StoreKeyId>::from_iter(restored.get_all().iter().map(|(k, _)| k.into())),
+            StdHashSet::from_iter(store.get_all().iter().map(|(k, _)| k.into()))
         );
     }
 
     #[test]
-    fn test_compute_store_key_id_multiple_elements_array() {
-        let array = array![1.22, 2.11, 3.22, 3.11];
-        let store_key: StoreKeyId = (&StoreKey(array)).into();
+    fn test_int8_stores() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let (int8, float32) = (StoreName("Int8".into()), StoreName("Float32".into()));
+        for (store_name, key_element_type) in [
+            (&int8, KeyElementType::Int8),
+            (&float32, KeyElementType::Float32),
+        ] {
+            handler
+                .create_store(
+                    store_name.clone(),
+                    NonZeroUsize::new(3).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    StoreSettings {
+                        key_element_type,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+        }
         assert_eq!(
-            store_key,
-            StoreKeyId("c8d293fab65705ee27956818a9b02139fa002b71e4cd416fea055344a907db67".into())
+            handler.create_store(
+                StoreName("NormalizedInt8".into()),
+                NonZeroUsize::new(3).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings {
+                    key_element_type: KeyElementType::Int8,
+                    normalization: VectorNormalization::L2,
+                    ..Default::default()
+                },
+                true,
+            ),
+            Err(ServerError::NormalizedIntegerKeys(StoreName(
+                "NormalizedInt8".into()
+            )))
         );
-    }
 
-    fn create_store_handler_no_loom(
-        predicates: Vec<MetadataKey>,
-        even_dimensions: Option<usize>,
-        odd_dimensions: Option<usize>,
-    ) -> Arc<StoreHandler> {
-        let write_flag = Arc::new(AtomicBool::new(false));
-        let handler = Arc::new(StoreHandler::new(write_flag));
-        let handles = (0..3).map(|i| {
-            let predicates = predicates.clone();
-            let shared_handler = handler.clone();
-            let handle = std::thread::spawn(move || {
-                let (store_name, size) = if i % 2 == 0 {
-                    ("Even", even_dimensions.unwrap_or(5))
-                } else {
-                    ("Odd", odd_dimensions.unwrap_or(3))
-                };
-                shared_handler.create_store(
-                    StoreName(store_name.to_string()),
-                    NonZeroUsize::new(size).unwrap(),
-                    predicates,
-                    StdHashSet::new(),
-                    true,
+        let keys = [
+            StoreKey(array![1.0, 2.0, 3.0]),
+            StoreKey(array![-128.0, 0.0, 127.0]),
+        ];
+        for store_name in [&int8, &float32] {
+            let upsert = handler
+                .set_in_store(
+                    store_name,
+                    keys.iter()
+                        .map(|key| (key.clone(), StdHashMap::new()))
+                        .collect(),
                 )
-            });
-            handle
-        });
-        for handle in handles {
-            let _ = handle.join().unwrap();
+                .unwrap();
+            assert_eq!(upsert.inserted, 2);
         }
+        for key in [array![0.5, 0.0, 0.0], array![0.0, 128.0, 0.0]] {
+            assert_eq!(
+                handler.set_in_store(
+                    &int8,
+                    vec![
+                        (keys[0].clone(), StdHashMap::new()),
+                        (StoreKey(key), StdHashMap::new())
+                    ]
+                ),
+                Err(ServerError::KeyOutOfRange {
+                    store: int8.clone(),
+                    element_type: KeyElementType::Int8,
+                    index: 1,
+                })
+            );
+        }
+        // fractions are held by other stores
         handler
-    }
+            .set_in_store(
+                &float32,
+                vec![(StoreKey(array![0.5, 0.0, 0.0]), StdHashMap::new())],
+            )
+            .unwrap();
 
-    fn create_store_handler(
-        predicates: Vec<MetadataKey>,
-    ) -> (
-        Arc<StoreHandler>,
-        Vec<Result<(), ServerError>>,
-        Vec<Result<(), ServerError>>,
-    ) {
-        let write_flag = Arc::new(AtomicBool::new(false));
-        let handler = Arc::new(StoreHandler::new(write_flag));
-        let handles = (0..3).map(|i| {
-            let predicates = predicates.clone();
-            let shared_handler = handler.clone();
-            let handle = std::thread::spawn(move || {
-                let (store_name, size) = if i % 2 == 0 { ("Even", 5) } else { ("Odd", 3) };
-                shared_handler.create_store(
-                    StoreName(store_name.to_string()),
-                    NonZeroUsize::new(size).unwrap(),
-                    predicates,
-                    StdHashSet::new(),
-                    true,
+        for store_name in [&int8, &float32] {
+            let similar = handler
+                .get_sim_in_store(
+                    store_name,
+                    StoreKey(array![1.2, 2.1, 2.9]),
+                    NonZeroUsize::new(1).unwrap(),
+                    Algorithm::CosineSimilarity,
+                    None,
+                    GetSimNOptions::default(),
                 )
-            });
-            handle
-        });
-        let (oks, errs): (Vec<_>, Vec<_>) = handles
-            .into_iter()
-            .map(|h| h.join().unwrap())
-            .partition(Result::is_ok);
-
-        (handler, oks, errs)
-    }
+                .unwrap();
+            assert_eq!(similar[0].0, keys[0]);
+            assert!((similar[0].2 .0 - 0.998).abs() < 1e-3);
+        }
+        // keys are rounded to the integers they are held as when looked up
+        let found = handler
+            .get_key_in_store(&int8, vec![StoreKey(array![-128.4, 0.2, 126.7])])
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, keys[1]);
 
-    #[test]
-    fn test_get_and_create_store_handler() {
-        let (handler, oks, errs) = create_store_handler(vec![]);
-        assert_eq!(oks.len(), 2);
-        assert_eq!(
-            errs,
-            vec![Err(ServerError::StoreAlreadyExists(StoreName(
-                "Even".to_string()
-            )))]
-        );
-        // test out a store that does not exist
-        let fake_store = StoreName("Random".to_string());
+        let store = handler.get(&int8).unwrap();
+        assert!(store
+            .id_to_value
+            .pin()
+            .values()
+            .all(|entry| matches!(entry.vector, VectorRef::Int8 { .. })));
+        let restored: Store =
+            serde_json::from_str(&serde_json::to_string(&*store).unwrap()).unwrap();
+        assert_eq!(restored.key_element_type, KeyElementType::Int8);
         assert_eq!(
-            handler.get(&fake_store).unwrap_err(),
-            ServerError::StoreNotFound(fake_store)
+            StdHashSet::<StoreKeyId>::from_iter(restored.get_all().iter().map(|(k, _)| k.into())),
+            StdHashSet::from_iter(keys.iter().map(StoreKeyId::from))
         );
     }
 
     #[test]
-    fn test_create_and_set_in_store_fails() {
-        let (handler, _oks, _errs) = create_store_handler(vec![]);
-        let even_store = StoreName("Even".into());
-        let fake_store = StoreName("Fake".into());
-        // set in nonexistent store should fail
+    fn test_normalized_store() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let (normalized, raw) = (StoreName("Normalized".into()), StoreName("Raw".into()));
+        for (store_name, normalization) in [
+            (&normalized, VectorNormalization::L2),
+            (&raw, VectorNormalization::None),
+        ] {
+            handler
+                .create_store(
+                    store_name.clone(),
+                    NonZeroUsize::new(3).unwrap(),
+                    vec![],
+                    StdHashSet::new(),
+                    StoreSettings {
+                        normalization,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+        }
+        let entry = |key: StoreKey| (key, StdHashMap::new());
         assert_eq!(
-            handler.set_in_store(&fake_store, vec![]).unwrap_err(),
-            ServerError::StoreNotFound(fake_store)
+            handler.set_in_store(
+                &normalized,
+                vec![
+                    entry(StoreKey(array![3.0, 4.0, 0.0])),
+                    entry(StoreKey(array![0.0, 0.0, 0.0])),
+                ]
+            ),
+            Err(ServerError::VectorNotNormalizable {
+                store: normalized.clone(),
+                index: 1,
+            })
         );
-        // set in store with wrong dimensions should fail
+        let keys = vec![
+            entry(StoreKey(array![3.0, 4.0, 0.0])),
+            entry(StoreKey(array![0.0, 2.0, 0.0])),
+            entry(StoreKey(array![-1.0, 1.0, 1.0])),
+        ];
+        for store_name in [&normalized, &raw] {
+            handler.set_in_store(store_name, keys.clone()).unwrap();
+        }
+        // keys pointing the same way are the same entry
+        let upsert = handler
+            .set_in_store(&normalized, vec![entry(StoreKey(array![6.0, 8.0, 0.0]))])
+            .unwrap();
+        assert_eq!(upsert.updated, 1);
         assert_eq!(
             handler
-                .set_in_store(
-                    &even_store,
-                    vec![(
-                        StoreKey(array![0.33, 0.44, 0.5]),
-                        StdHashMap::from_iter(vec![(
-                            MetadataKey::new("author".into()),
-                            MetadataValue::RawString("Vincent".into()),
-                        ),])
-                    ),]
-                )
-                .unwrap_err(),
-            ServerError::StoreDimensionMismatch {
-                store_dimension: 5,
-                input_dimension: 3
-            }
+                .get_key_in_store(&normalized, vec![StoreKey(array![0.3, 0.4, 0.0])])
+                .unwrap(),
+            vec![entry(StoreKey(array![0.6, 0.8, 0.0]))]
         );
-    }
 
-    #[test]
-    fn test_create_and_set_in_store_passes() {
-        let (handler, _oks, _errs) = create_store_handler(vec![]);
-        let odd_store = StoreName("Odd".into());
-        let input_arr = array![0.1, 0.2, 0.3];
-        let ret = handler
-            .set_in_store(
-                &odd_store,
-                vec![(
-                    StoreKey(input_arr.clone()),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("author".into()),
-                        MetadataValue::RawString("Lex Luthor".into()),
-                    )]),
-                )],
+        // cosine similarity ranks and scores the same with and without normalization
+        let search = |store_name: &StoreName, search_input: StoreKey| {
+            handler.get_sim_in_store(
+                store_name,
+                search_input,
+                NonZeroUsize::new(3).unwrap(),
+                Algorithm::CosineSimilarity,
+                None,
+                GetSimNOptions::default(),
             )
-            .unwrap();
+        };
+        let search_input = StoreKey(array![2.0, 3.0, 0.5]);
+        let from_normalized = search(&normalized, search_input.clone()).unwrap();
+        let from_raw = search(&raw, search_input).unwrap();
+        assert_eq!(from_normalized.len(), 3);
+        for ((normalized_key, _, normalized_score), (raw_key, _, raw_score)) in
+            from_normalized.iter().zip(&from_raw)
+        {
+            assert_eq!(normalized_key, &vectors::normalize(raw_key.clone()));
+            assert!((normalized_score.0 - raw_score.0).abs() < 1e-5);
+        }
         assert_eq!(
-            ret,
-            StoreUpsert {
-                inserted: 1,
-                updated: 0,
-            }
+            search(&normalized, StoreKey(array![0.0, 0.0, 0.0])),
+            Err(ServerError::VectorNotNormalizable {
+                store: normalized.clone(),
+                index: 0,
+            })
         );
-        let ret = handler
-            .set_in_store(
-                &odd_store,
-                vec![(
-                    StoreKey(input_arr.clone()),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("author".into()),
-                        MetadataValue::RawString("Clark Kent".into()),
-                    )]),
-                )],
-            )
-            .unwrap();
         assert_eq!(
-            ret,
-            StoreUpsert {
-                inserted: 0,
-                updated: 1,
-            }
+            handler
+                .describe_store(
+                    &normalized,
+                    &LimitHandler::new(&CommandLineConfig::default())
+                )
+                .unwrap()
+                .normalization,
+            VectorNormalization::L2
         );
     }
 
     #[test]
-    fn test_add_index_in_store() {
-        let handler =
-            create_store_handler_no_loom(vec![MetadataKey::new("author".into())], None, None);
+    fn test_memory_breakdown() {
+        let mut handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        handler.use_trash(Duration::from_secs(3600));
         let even_store = StoreName("Even".into());
-        let input_arr_1 = array![0.1, 0.2, 0.3, 0.0, 0.0];
-        let input_arr_2 = array![0.2, 0.3, 0.4, 0.0, 0.0];
-        let input_arr_3 = array![0.3, 0.4, 0.4, 0.0, 0.0];
-        handler
-            .set_in_store(
-                &even_store,
-                vec![(
-                    StoreKey(input_arr_1.clone()),
-                    StdHashMap::from_iter(vec![
-                        (
-                            MetadataKey::new("author".into()),
-                            MetadataValue::RawString("Lex Luthor".into()),
-                        ),
-                        (
-                            MetadataKey::new("planet".into()),
-                            MetadataValue::RawString("earth".into()),
-                        ),
-                    ]),
-                )],
-            )
-            .unwrap();
+        let odd_store = StoreName("Odd".into());
+        let author = MetadataKey::new("author".into());
         handler
-            .set_in_store(
-                &even_store,
-                vec![(
-                    StoreKey(input_arr_2.clone()),
-                    StdHashMap::from_iter(vec![
-                        (
-                            MetadataKey::new("author".into()),
-                            MetadataValue::RawString("Clark Kent".into()),
-                        ),
-                        (
-                            MetadataKey::new("planet".into()),
-                            MetadataValue::RawString("krypton".into()),
-                        ),
-                    ]),
-                )],
+            .create_store(
+                even_store.clone(),
+                NonZeroUsize::new(4).unwrap(),
+                vec![author.clone()],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
             )
             .unwrap();
         handler
-            .set_in_store(
-                &even_store,
-                vec![(
-                    StoreKey(input_arr_3.clone()),
-                    StdHashMap::from_iter(vec![
-                        (
-                            MetadataKey::new("author".into()),
-                            MetadataValue::RawString("General Zod".into()),
-                        ),
-                        (
-                            MetadataKey::new("planet".into()),
-                            MetadataValue::RawString("krypton".into()),
-                        ),
-                    ]),
-                )],
+            .create_store(
+                odd_store.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings::default(),
+                true,
             )
             .unwrap();
-        let condition = &PredicateCondition::Value(Predicate::Equals {
-            key: MetadataKey::new("author".into()),
-            value: MetadataValue::RawString("Lex Luthor".into()),
-        });
-        let res = handler.get_pred_in_store(&even_store, &condition).unwrap();
-        assert_eq!(res.len(), 1);
-        let condition = &PredicateCondition::Value(Predicate::NotEquals {
-            key: MetadataKey::new("author".into()),
-            value: MetadataValue::RawString("Lex Luthor".into()),
-        });
-        let res = handler.get_pred_in_store(&even_store, &condition).unwrap();
-        assert_eq!(res.len(), 2);
-        let condition = &PredicateCondition::Value(Predicate::NotEquals {
-            key: MetadataKey::new("author".into()),
-            value: MetadataValue::RawString("Lex Luthor".into()),
-        })
-        .or(PredicateCondition::Value(Predicate::NotEquals {
-            key: MetadataKey::new("planet".into()),
-            value: MetadataValue::RawString("earth".into()),
-        }));
-        let res = handler.get_pred_in_store(&even_store, &condition);
-        assert_eq!(res.unwrap().len(), 2);
+        let entries = (0..3)
+            .map(|i| {
+                (
+                    StoreKey(array![i as f32, 1.0, 2.0, 3.0]),
+                    StdHashMap::from_iter([(
+                        author.clone(),
+                        MetadataValue::RawString(format!("author {i}")),
+                    )]),
+                )
+            })
+            .collect();
+        handler.set_in_store(&even_store, entries).unwrap();
         handler
-            .create_pred_index(
-                &even_store,
-                vec![
-                    MetadataKey::new("author".into()),
-                    MetadataKey::new("planet".into()),
-                ],
+            .set_in_store(
+                &odd_store,
+                vec![(StoreKey(array![1.0, 2.0]), StdHashMap::new())],
             )
             .unwrap();
-        let res = handler.get_pred_in_store(&even_store, &condition).unwrap();
-        assert_eq!(res.len(), 2);
+
+        let breakdown = handler.memory_breakdown();
+        let [even, odd] = &breakdown.stores[..] else {
+            panic!("Unexpected stores {:?}", breakdown.stores)
+        };
+        // stores are ordered from the largest
+        assert_eq!((&even.name, &odd.name), (&even_store, &odd_store));
+        assert_eq!(even.vectors, 3 * 4 * size_of::<f32>());
+        assert_eq!(odd.vectors, 2 * size_of::<f32>());
+        let key_id = StoreKeyId::from(&StoreKey(array![1.0, 2.0]));
+        assert_eq!(odd.entries, key_id.memory() + size_of::<Entry>());
+        assert!(even.entries > 3 * odd.entries);
+        assert!(even.predicate_indices > 3 * key_id.memory());
+        assert_eq!(odd.predicate_indices, 0);
+        assert!(even.non_linear_indices > 3 * 4 * size_of::<f32>());
+        assert!(odd.non_linear_indices < even.non_linear_indices);
+        assert_eq!(breakdown.trash, 0);
+
+        // dropped stores are counted in the trash until they are purged
+        handler.drop_store(odd_store.clone(), true).unwrap();
+        let breakdown_after_drop = handler.memory_breakdown();
+        assert_eq!(breakdown_after_drop.stores, vec![even.clone()]);
+        assert_eq!(breakdown_after_drop.trash, odd.total());
     }
 
     #[test]
-    fn test_get_key_in_store() {
-        let (handler, _oks, _errs) = create_store_handler(vec![]);
-        let odd_store = StoreName("Odd".into());
-        let fake_store = StoreName("Fakest".into());
-        let input_arr_1 = array![0.1, 0.2, 0.3];
-        let input_arr_2 = array![0.2, 0.3, 0.4];
+    fn test_warmup() {
+        let mut handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let location = std::env::temp_dir().join("ahnlich_test_warmup");
+        std::fs::create_dir_all(&location).unwrap();
+        handler.use_vector_storage(location.clone(), 1024 * 1024);
+        let disk_store = StoreName("Disk".into());
         handler
-            .set_in_store(
-                &odd_store,
-                vec![(
-                    StoreKey(input_arr_1.clone()),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("author".into()),
-                        MetadataValue::RawString("Lex Luthor".into()),
-                    )]),
-                )],
+            .create_store(
+                disk_store.clone(),
+                NonZeroUsize::new(3).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings {
+                    storage_tier: StorageTier::Disk,
+                    ..Default::default()
+                },
+                true,
             )
             .unwrap();
+        let entry = |i: usize| (StoreKey(array![i as f32, 1.0, 0.5]), StdHashMap::new());
         handler
-            .set_in_store(
-                &odd_store,
-                vec![(
-                    StoreKey(input_arr_2.clone()),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("author".into()),
-                        MetadataValue::RawString("Clark Kent".into()),
-                    )]),
-                )],
-            )
+            .set_in_store(&disk_store, (0..100).map(entry).collect())
             .unwrap();
+        // compaction maps a new file, leaving the page cache empty
+        handler.compact_store(&disk_store).unwrap();
+        let cached = |handler: &StoreHandler| {
+            handler
+                .get(&disk_store)
+                .unwrap()
+                .disk_vectors
+                .as_ref()
+                .map(DiskVectors::cache_size_in_bytes)
+                .unwrap()
+        };
+        assert_eq!(cached(&handler), 0);
+
+        let fake_store = StoreName("Random".into());
         assert_eq!(
-            handler.get_key_in_store(&fake_store, vec![]).unwrap_err(),
-            ServerError::StoreNotFound(fake_store)
+            handler.warmup(StdHashSet::from_iter([
+                disk_store.clone(),
+                fake_store.clone()
+            ])),
+            Err(ServerError::StoreNotFound(fake_store))
         );
-        let ret = handler
-            .get_key_in_store(
-                &odd_store,
-                vec![StoreKey(input_arr_1), StoreKey(input_arr_2)],
+        assert_eq!(cached(&handler), 0);
+        handler.warmup(StdHashSet::new()).unwrap();
+        assert!(cached(&handler) >= 100 * 3 * size_of::<f32>());
+
+        handler.drop_store(disk_store, true).unwrap();
+        std::fs::remove_dir_all(location).unwrap();
+    }
+
+    #[test]
+    fn test_bulk_write() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Bulk".into());
+        let rank = MetadataKey::new("rank".into());
+        handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::new(3).unwrap(),
+                vec![rank.clone()],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings::default(),
+                true,
             )
             .unwrap();
-        assert_eq!(ret.len(), 2);
+        let entry = |i: usize| {
+            (
+                StoreKey(array![i as f32, 1.0, 0.5]),
+                StdHashMap::from_iter([(rank.clone(), MetadataValue::RawString(i.to_string()))]),
+            )
+        };
+        let condition = |i: usize| {
+            PredicateCondition::Value(Predicate::Equals {
+                key: rank.clone(),
+                value: MetadataValue::RawString(i.to_string()),
+            })
+        };
+        let closest = |handler: &StoreHandler, i: usize| {
+            handler
+                .get_sim_in_store(
+                    &store_name,
+                    entry(i).0,
+                    NonZeroUsize::new(1).unwrap(),
+                    Algorithm::KDTree,
+                    None,
+                    GetSimNOptions::default(),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|(store_key, _, _)| store_key)
+                .collect::<Vec<_>>()
+        };
         assert_eq!(
-            ret[0]
-                .1
-                .get(&MetadataKey::new("author".into()))
-                .cloned()
-                .unwrap(),
-            MetadataValue::RawString("Lex Luthor".into())
+            handler.set_bulk_write(&StoreName("Random".into()), true),
+            Err(ServerError::StoreNotFound(StoreName("Random".into())))
         );
+        handler.set_in_store(&store_name, vec![entry(0)]).unwrap();
+        handler.set_bulk_write(&store_name, true).unwrap();
+        handler
+            .set_in_store(&store_name, (1..100).map(entry).collect())
+            .unwrap();
+        handler
+            .del_key_in_store(&store_name, vec![entry(99).0])
+            .unwrap();
+        let description = handler
+            .describe_store(
+                &store_name,
+                &LimitHandler::new(&CommandLineConfig::default()),
+            )
+            .unwrap();
+        assert!(description.bulk_write);
+        assert_eq!(description.info.len, 99);
+        // the entries are held but the indices are yet to catch up
         assert_eq!(
-            ret[1]
-                .1
-                .get(&MetadataKey::new("author".into()))
-                .cloned()
-                .unwrap(),
-            MetadataValue::RawString("Clark Kent".into())
+            handler.get_key_in_store(&store_name, vec![entry(42).0]),
+            Ok(vec![entry(42)])
         );
+        assert!(handler
+            .get_pred_in_store(&store_name, &condition(42), Deadline::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(closest(&handler, 42), vec![entry(0).0]);
+
+        // compaction keeps the store in bulk write mode with its indices caught up
+        handler.compact_store(&store_name).unwrap();
+        assert_eq!(closest(&handler, 42), vec![entry(42).0]);
+        handler
+            .set_in_store(&store_name, (100..150).map(entry).collect())
+            .unwrap();
+        assert_eq!(closest(&handler, 120), vec![entry(98).0]);
+
+        handler.set_bulk_write(&store_name, false).unwrap();
+        assert!(
+            !handler
+                .describe_store(
+                    &store_name,
+                    &LimitHandler::new(&CommandLineConfig::default())
+                )
+                .unwrap()
+                .bulk_write
+        );
+        for i in [0, 42, 120] {
+            assert_eq!(
+                handler.get_pred_in_store(&store_name, &condition(i), Deadline::default()),
+                Ok(vec![entry(i)])
+            );
+            assert_eq!(closest(&handler, i), vec![entry(i).0]);
+        }
+        assert!(handler
+            .get_pred_in_store(&store_name, &condition(99), Deadline::default())
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    fn test_get_pred_in_store() {
-        let handler =
-            create_store_handler_no_loom(vec![MetadataKey::new("rank".into())], None, None);
-        let even_store = StoreName("Even".into());
-        let input_arr_1 = array![0.1, 0.2, 0.3, 0.4, 0.5];
-        let input_arr_2 = array![0.2, 0.3, 0.4, 0.5, 0.6];
+    fn test_entry_norms() {
+        let handler = create_store_handler_no_loom(vec![], None, Some(2));
+        let odd_store = StoreName("Odd".into());
         handler
             .set_in_store(
-                &even_store,
-                vec![(
-                    StoreKey(input_arr_1.clone()),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("rank".into()),
-                        MetadataValue::RawString("Joinin".into()),
-                    )]),
-                )],
+                &odd_store,
+                vec![
+                    (StoreKey(array![3.0, 4.0]), StdHashMap::new()),
+                    (StoreKey(array![0.0, 0.5]), StdHashMap::new()),
+                ],
             )
             .unwrap();
+        let norms: StdHashSet<_> = handler
+            .get(&odd_store)
+            .unwrap()
+            .get_all_with_norms()
+            .into_iter()
+            .map(|(_, _, norm)| norm.to_string())
+            .collect();
+        assert_eq!(norms, StdHashSet::from_iter(["5".into(), "0.5".into()]));
+        // entries from snapshots taken before norms were kept compute them when needed
+        let entry: Entry = serde_json::from_str("[1, {}]").unwrap();
+        assert!(matches!(entry.vector, VectorRef::Disk(1)));
+        assert_eq!(entry.norm, 0.0);
+    }
+
+    #[test]
+    fn test_infer_dimension() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Inferred".into());
         handler
+            .create_store(
+                store_name.clone(),
+                NonZeroUsize::MIN,
+                vec![],
+                StdHashSet::from_iter([NonLinearAlgorithm::KDTree]),
+                StoreSettings {
+                    infer_dimension: true,
+                    ..Default::default()
+                },
+                true,
+            )
+            .unwrap();
+        let entry = |key: StoreKey| (key, StdHashMap::new());
+        assert_eq!(
+            handler.set_in_store(
+                &store_name,
+                vec![
+                    entry(StoreKey(array![1.0, 2.0])),
+                    entry(StoreKey(array![1.0, 2.0, 3.0])),
+                ]
+            ),
+            Err(ServerError::StoreDimensionMismatch {
+                store: store_name.clone(),
+                store_dimension: 2,
+                input_dimension: 3,
+                index: 1,
+            })
+        );
+        let upsert = handler
             .set_in_store(
-                &even_store,
-                vec![(
-                    StoreKey(input_arr_2.clone()),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("rank".into()),
-                        MetadataValue::RawString("Genin".into()),
-                    )]),
-                )],
+                &store_name,
+                vec![
+                    entry(StoreKey(array![1.0, 2.0, 3.0])),
+                    entry(StoreKey(array![3.0, 2.0, 1.0])),
+                ],
             )
             .unwrap();
-        let condition = &PredicateCondition::Value(Predicate::Equals {
-            key: MetadataKey::new("rank".into()),
-            value: MetadataValue::RawString("Hokage".into()),
-        });
-        let res = handler.get_pred_in_store(&even_store, &condition).unwrap();
-        assert!(res.is_empty());
-        let condition = &PredicateCondition::Value(Predicate::NotEquals {
-            key: MetadataKey::new("rank".into()),
-            value: MetadataValue::RawString("Hokage".into()),
-        });
-        let res = handler.get_pred_in_store(&even_store, &condition).unwrap();
-        assert_eq!(res.len(), 2);
-        let condition = &PredicateCondition::Value(Predicate::Equals {
-            key: MetadataKey::new("rank".into()),
-            value: MetadataValue::RawString("Joinin".into()),
-        });
-        let res = handler.get_pred_in_store(&even_store, &condition).unwrap();
-        assert_eq!(res.len(), 1);
+        assert_eq!(upsert.inserted, 2);
+        assert_eq!(handler.get(&store_name).unwrap().dimension.get(), 3);
+        // once inferred the dimension is enforced like any other store
+        assert_eq!(
+            handler.set_in_store(
+                &store_name,
+                vec![
+                    entry(StoreKey(array![4.0, 5.0, 6.0])),
+                    entry(StoreKey(array![1.0, 2.0])),
+                ]
+            ),
+            Err(ServerError::StoreDimensionMismatch {
+                store: store_name.clone(),
+                store_dimension: 3,
+                input_dimension: 2,
+                index: 1,
+            })
+        );
+        assert_eq!(handler.get(&store_name).unwrap().len(), 2);
     }
 
     #[test]
@@ -1125,160 +4456,174 @@ mod tests {
                 )],
             )
             .unwrap();
-        let stores = handler.list_stores();
+        let stores = handler.list_stores(&LimitHandler::new(&CommandLineConfig::default()));
         assert_eq!(
             stores,
             StdHashSet::from_iter([
                 StoreInfo {
                     name: odd_store,
                     len: 2,
-                    size_in_bytes: 2144,
+                    size_in_bytes: 2168,
+                    dimension: NonZeroUsize::new(3).unwrap(),
+                    request_limits: RequestLimits {
+                        message_size: 1_048_576,
+                        batch_size: None,
+                    },
                 },
                 StoreInfo {
                     name: even_store,
                     len: 0,
                     size_in_bytes: 1744,
+                    dimension: NonZeroUsize::new(5).unwrap(),
+                    request_limits: RequestLimits {
+                        message_size: 1_048_576,
+                        batch_size: None,
+                    },
                 },
             ])
         )
     }
 
     #[test]
-    fn test_get_sim_in_store_with_predicate() {
-        let vectors = word_to_vector();
-
-        let input_arr_1 = vectors.get(MOST_SIMILAR[0]).unwrap();
-        let input_arr_2 = vectors.get(MOST_SIMILAR[1]).unwrap();
-        let input_arr_3 = vectors.get(MOST_SIMILAR[2]).unwrap();
-
-        let handler = create_store_handler_no_loom(
-            vec![MetadataKey::new("rank".into())],
-            Some(input_arr_1.0.len()),
-            Some(input_arr_1.0.len()),
+    fn test_apply_manifest() {
+        let handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        let store_name = StoreName("Manifest".into());
+        let manifest =
+            |predicates: &[&str], non_linear_indices: &[NonLinearAlgorithm]| StoreManifest {
+                store: store_name.clone(),
+                dimension: NonZeroUsize::new(3).unwrap(),
+                predicates: predicates
+                    .iter()
+                    .map(|predicate| MetadataKey::new(predicate.to_string()))
+                    .collect(),
+                non_linear_indices: non_linear_indices.iter().copied().collect(),
+                timestamp_key: None,
+                storage_tier: StorageTier::Memory,
+                key_element_type: KeyElementType::Float32,
+                normalization: VectorNormalization::None,
+                index_seed: None,
+                non_finite_vectors: NonFiniteVectors::Reject,
+                eviction: None,
+            };
+        let created = vec![ManifestChange::CreateStore {
+            store: store_name.clone(),
+        }];
+        assert_eq!(
+            handler
+                .apply_manifest(vec![manifest(&["rank"], &[])], true)
+                .unwrap(),
+            created
+        );
+        assert!(handler.get(&store_name).is_err());
+        assert_eq!(
+            handler
+                .apply_manifest(vec![manifest(&["rank"], &[])], false)
+                .unwrap(),
+            created
+        );
+        // applying the same manifest again changes nothing
+        assert_eq!(
+            handler
+                .apply_manifest(vec![manifest(&["rank"], &[])], false)
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            handler
+                .apply_manifest(
+                    vec![manifest(&["age", "name"], &[NonLinearAlgorithm::KDTree])],
+                    false
+                )
+                .unwrap(),
+            vec![
+                ManifestChange::CreatePredicates {
+                    store: store_name.clone(),
+                    predicates: vec![
+                        MetadataKey::new("age".into()),
+                        MetadataKey::new("name".into())
+                    ],
+                },
+                ManifestChange::DropPredicates {
+                    store: store_name.clone(),
+                    predicates: vec![MetadataKey::new("rank".into())],
+                },
+                ManifestChange::CreateNonLinearIndices {
+                    store: store_name.clone(),
+                    non_linear_indices: vec![NonLinearAlgorithm::KDTree],
+                },
+            ]
+        );
+        let store = handler.get(&store_name).unwrap();
+        assert_eq!(
+            store.predicate_indices.current_predicates(),
+            StdHashSet::from_iter([
+                MetadataKey::new("age".into()),
+                MetadataKey::new("name".into())
+            ])
+        );
+        assert_eq!(
+            store.non_linear_indices.current_keys(),
+            StdHashSet::from_iter([NonLinearAlgorithm::KDTree])
         );
-        let even_store = StoreName("Even".into());
-        handler
-            .set_in_store(
-                &even_store,
-                vec![(
-                    input_arr_1.clone(),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("rank".into()),
-                        MetadataValue::RawString("Chunin".into()),
-                    )]),
-                )],
-            )
-            .unwrap();
-        handler
-            .set_in_store(
-                &even_store,
-                vec![(
-                    input_arr_2.clone(),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("rank".into()),
-                        MetadataValue::RawString("Chunin".into()),
-                    )]),
-                )],
-            )
-            .unwrap();
-        handler
-            .set_in_store(
-                &even_store,
-                vec![(
-                    input_arr_3.clone(),
-                    StdHashMap::from_iter(vec![(
-                        MetadataKey::new("rank".into()),
-                        MetadataValue::RawString("Genin".into()),
-                    )]),
-                )],
-            )
-            .unwrap();
-        let condition = &PredicateCondition::Value(Predicate::Equals {
-            key: MetadataKey::new("rank".into()),
-            value: MetadataValue::RawString("Chunin".into()),
-        });
-        let search_input = StoreKey(vectors.get(SEACH_TEXT).unwrap().0.clone());
-        let algorithm = Algorithm::CosineSimilarity;
-
-        let closest_n = NonZeroUsize::new(3).unwrap();
-        let res = handler
-            .get_sim_in_store(
-                &even_store,
-                search_input.clone(),
-                closest_n,
-                algorithm,
-                Some(condition.clone()),
-            )
-            .unwrap();
-        assert_eq!(res.len(), 2);
-
-        let closest_n = NonZeroUsize::new(1).unwrap();
-        let res = handler
-            .get_sim_in_store(
-                &even_store,
-                search_input.clone(),
-                closest_n,
-                algorithm,
-                None,
-            )
-            .unwrap();
-        assert_eq!(res.len(), 1);
-        assert!(res[0].0 == *vectors.get(MOST_SIMILAR[0]).unwrap());
-
-        let condition = &PredicateCondition::Value(Predicate::NotEquals {
-            key: MetadataKey::new("rank".into()),
-            value: MetadataValue::RawString("Chunin".into()),
-        });
-        let closest_n = NonZeroUsize::new(3).unwrap();
-        let res = handler
-            .get_sim_in_store(
-                &even_store,
-                search_input.clone(),
-                closest_n,
-                algorithm,
-                Some(condition.clone()),
-            )
-            .unwrap();
-        assert_eq!(res.len(), 1);
-
-        // Add more items storekeys into the store for processing.
-        //
-        let meta_data_key = MetadataKey::new("english".into());
-        let store_values = vectors
-            .iter()
-            .filter(|(sentence, _)| SEACH_TEXT != *sentence)
-            .map(|(sentence, store_key)| {
-                let value: StdHashMap<MetadataKey, MetadataValue> = StdHashMap::from_iter(vec![(
-                    meta_data_key.clone(),
-                    MetadataValue::RawString(sentence.into()),
-                )]);
-                (store_key.clone(), value)
-            })
-            .collect();
-        handler.set_in_store(&even_store, store_values).unwrap();
-        let res = handler
-            .get_sim_in_store(
-                &even_store,
-                search_input.clone(),
-                closest_n,
-                Algorithm::EuclideanDistance,
-                None,
-            )
-            .unwrap();
-
-        assert_eq!(res.len(), 3);
 
+        let mut conflicting = manifest(&[], &[]);
+        conflicting.dimension = NonZeroUsize::new(4).unwrap();
         assert_eq!(
-            res[0].1.get(&meta_data_key).cloned().unwrap(),
-            MetadataValue::RawString(MOST_SIMILAR[0].into())
+            handler.apply_manifest(vec![conflicting], false),
+            Err(ServerError::ManifestConflict {
+                store: store_name.clone(),
+                setting: "dimension".to_string(),
+            })
         );
         assert_eq!(
-            res[1].1.get(&meta_data_key).cloned().unwrap(),
-            MetadataValue::RawString(MOST_SIMILAR[1].into())
+            handler.apply_manifest(vec![manifest(&[], &[]), manifest(&[], &[])], true),
+            Err(ServerError::DuplicateManifestStore(store_name.clone()))
         );
+    }
+
+    #[test]
+    fn test_diff_manifest() {
+        let handler =
+            create_store_handler_no_loom(vec![MetadataKey::new("rank".into())], None, None);
+        let manifest = StoreManifest {
+            store: StoreName("Odd".into()),
+            dimension: NonZeroUsize::new(4).unwrap(),
+            predicates: StdHashSet::from_iter([MetadataKey::new("rank".into())]),
+            non_linear_indices: StdHashSet::new(),
+            timestamp_key: Some(MetadataKey::new("created_at".into())),
+            storage_tier: StorageTier::Memory,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        };
+        let missing = StoreManifest {
+            store: StoreName("Missing".into()),
+            ..manifest.clone()
+        };
         assert_eq!(
-            res[2].1.get(&meta_data_key).cloned().unwrap(),
-            MetadataValue::RawString(MOST_SIMILAR[2].into())
+            handler.diff_manifest(&[manifest, missing]).unwrap(),
+            ManifestDrift {
+                changes: vec![ManifestChange::CreateStore {
+                    store: StoreName("Missing".into()),
+                }],
+                settings: vec![
+                    SettingDrift {
+                        store: StoreName("Odd".into()),
+                        setting: "dimension".into(),
+                        manifest: Some("4".into()),
+                        live: Some("3".into()),
+                    },
+                    SettingDrift {
+                        store: StoreName("Odd".into()),
+                        setting: "timestamp key".into(),
+                        manifest: Some("created_at".into()),
+                        live: None,
+                    },
+                ],
+                unmanaged_stores: vec![StoreName("Even".into())],
+            }
         );
     }
 }
@@ -0,0 +1,238 @@
+use super::store::{Store, StoreHandler};
+use crate::errors::ServerError;
+use ahnlich_types::db::TrashedStoreInfo;
+use ahnlich_types::keyval::StoreName;
+use flurry::HashMap as ConcurrentHashMap;
+use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use task_manager::Task;
+use task_manager::TaskState;
+use tokio::time::sleep;
+
+pub type Trash = Arc<ConcurrentHashMap<StoreName, TrashedStore>>;
+
+/// A dropped store along with when it was dropped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedStore {
+    pub(super) store: Arc<Store>,
+    pub(super) dropped_at: SystemTime,
+}
+
+impl StoreHandler {
+    /// Keeps dropped stores in the trash for the retention, after which `purge_trash` drops them
+    /// for good
+    pub fn use_trash(&mut self, retention: Duration) {
+        self.trash_retention = Some(retention);
+    }
+
+    /// Moves a dropped store into the trash, where a store dropped again under the same name
+    /// takes the place of the older one
+    pub(super) fn trash_store(&self, store_name: StoreName, store: Arc<Store>) {
+        let trashed = TrashedStore {
+            store,
+            dropped_at: SystemTime::now(),
+        };
+        if let Some(replaced) = self.trash.pin().insert(store_name, trashed) {
+            replaced.store.discard(self.discarded_files.as_deref());
+        }
+    }
+
+    /// Matches LISTTRASHEDSTORES - returns the dropped stores in the trash ordered by name
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn list_trashed_stores(&self) -> Vec<TrashedStoreInfo> {
+        self.trash
+            .iter(&self.trash.guard())
+            .map(|(store_name, trashed)| TrashedStoreInfo {
+                name: store_name.clone(),
+                len: trashed.store.len(),
+                size_in_bytes: trashed.store.size(),
+                dropped_at: trashed.dropped_at,
+                purge_at: trashed.dropped_at + self.trash_retention.unwrap_or_default(),
+            })
+            .sorted()
+            .collect()
+    }
+
+    /// Matches RESTORESTORE - moves a dropped store out of the trash, unless a store has since
+    /// been created under its name
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn restore_store(&self, store_name: StoreName) -> Result<(), ServerError> {
+        let trash = self.trash.pin();
+        let trashed = trash
+            .remove(&store_name)
+            .cloned()
+            .ok_or_else(|| ServerError::TrashedStoreNotFound(store_name.clone()))?;
+        if self
+            .stores
+            .try_insert(
+                store_name.clone(),
+                trashed.store.clone(),
+                &self.stores.guard(),
+            )
+            .is_err()
+        {
+            trash.insert(store_name.clone(), trashed);
+            return Err(ServerError::StoreAlreadyExists(store_name));
+        }
+        self.count_store(&store_name, &trashed.store);
+        self.set_write_flag();
+        Ok(())
+    }
+
+    /// Drops the stores in the trash for good once their retention has passed, every store in the
+    /// trash when dropped stores are not retained
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn purge_trash(&self) -> Vec<StoreName> {
+        let now = SystemTime::now();
+        let trash = self.trash.pin();
+        let expired: Vec<StoreName> = trash
+            .iter()
+            .filter(|(_, trashed)| {
+                self.trash_retention
+                    .map_or(true, |retention| trashed.dropped_at + retention <= now)
+            })
+            .map(|(store_name, _)| store_name.clone())
+            .collect();
+        for store_name in &expired {
+            if let Some(trashed) = trash.remove(store_name) {
+                trashed.store.discard(self.discarded_files.as_deref());
+            }
+        }
+        if !expired.is_empty() {
+            self.set_write_flag();
+        }
+        expired
+    }
+}
+
+/// Longest wait between checks of the trash, so that a long retention does not keep stores
+/// around for up to twice as long
+const MAX_PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Purges the stores that have been in the trash for longer than its retention
+#[derive(Debug)]
+pub(crate) struct TrashTask {
+    store_handler: Arc<StoreHandler>,
+    interval: Duration,
+}
+
+impl TrashTask {
+    pub(crate) fn new(store_handler: Arc<StoreHandler>, retention: Duration) -> Self {
+        Self {
+            store_handler,
+            interval: retention.clamp(Duration::from_secs(1), MAX_PURGE_INTERVAL),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for TrashTask {
+    fn task_name(&self) -> String {
+        "db-trash".to_string()
+    }
+
+    async fn run(&self) -> TaskState {
+        sleep(self.interval).await;
+        for store_name in self.store_handler.purge_trash() {
+            log::info!("Purged store {store_name} from the trash");
+        }
+        TaskState::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::{StoreSettings, StoresSnapshot};
+    use super::*;
+    use ahnlich_types::keyval::StoreKey;
+    use ndarray::array;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap as StdHashMap;
+    use std::collections::HashSet as StdHashSet;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::AtomicBool;
+    use utils::persistence::AhnlichPersistenceUtils;
+
+    #[test]
+    fn test_trash() {
+        let mut handler = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        handler.use_trash(Duration::from_secs(3600));
+        let odd_store = StoreName("Odd".into());
+        let create = |handler: &StoreHandler| {
+            handler.create_store(
+                odd_store.clone(),
+                NonZeroUsize::new(2).unwrap(),
+                vec![],
+                StdHashSet::new(),
+                StoreSettings::default(),
+                true,
+            )
+        };
+        create(&handler).unwrap();
+        let entries = vec![(StoreKey(array![1.0, 2.0]), StdHashMap::new())];
+        handler.set_in_store(&odd_store, entries.clone()).unwrap();
+        assert_eq!(handler.drop_store(odd_store.clone(), true), Ok(1));
+        assert!(handler.get(&odd_store).is_err());
+        let trashed = handler.list_trashed_stores();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!((&trashed[0].name, trashed[0].len), (&odd_store, 1));
+        assert_eq!(
+            trashed[0].purge_at,
+            trashed[0].dropped_at + Duration::from_secs(3600)
+        );
+        assert!(handler.purge_trash().is_empty());
+
+        handler.restore_store(odd_store.clone()).unwrap();
+        assert_eq!(
+            handler.get_key_in_store(&odd_store, vec![entries[0].0.clone()]),
+            Ok(entries.clone())
+        );
+        assert!(handler.list_trashed_stores().is_empty());
+        assert_eq!(
+            handler.restore_store(odd_store.clone()),
+            Err(ServerError::TrashedStoreNotFound(odd_store.clone()))
+        );
+
+        // a store created in place of a dropped one keeps it from being restored
+        handler.drop_store(odd_store.clone(), true).unwrap();
+        create(&handler).unwrap();
+        assert_eq!(
+            handler.restore_store(odd_store.clone()),
+            Err(ServerError::StoreAlreadyExists(odd_store.clone()))
+        );
+        assert_eq!(handler.list_trashed_stores().len(), 1);
+
+        // the trash is persisted with the stores, while snapshots of the stores alone still load
+        let snapshot = serde_json::to_string(&handler.get_snapshot()).unwrap();
+        let mut restored = StoreHandler::new(Arc::new(AtomicBool::new(false)));
+        restored.use_snapshot(serde_json::from_str(&snapshot).unwrap());
+        assert_eq!(restored.list_trashed_stores().len(), 1);
+        assert!(restored.get(&odd_store).is_ok());
+        let stores_only = serde_json::to_string(&handler.get_stores()).unwrap();
+        restored.use_snapshot(serde_json::from_str(&stores_only).unwrap());
+        assert!(restored.list_trashed_stores().is_empty());
+        assert!(restored.get(&odd_store).is_ok());
+        // as are the stores and trash when written in sections
+        let persist_location = std::env::temp_dir().join("ahnlich_test_trash_snapshot.dat");
+        let mut file = std::fs::File::create(&persist_location).unwrap();
+        utils::persistence::write_snapshot(&mut file, &handler.get_snapshot()).unwrap();
+        let summary =
+            utils::persistence::Persistence::<StoresSnapshot>::verify(&persist_location, None)
+                .unwrap();
+        assert_eq!(summary.sections.len(), 2);
+        restored.use_snapshot(
+            utils::persistence::Persistence::load_snapshot(&persist_location, None).unwrap(),
+        );
+        assert_eq!(restored.list_trashed_stores().len(), 1);
+        assert!(restored.get(&odd_store).is_ok());
+        std::fs::remove_file(persist_location).unwrap();
+
+        handler.use_trash(Duration::ZERO);
+        assert_eq!(handler.purge_trash(), vec![odd_store]);
+        assert!(handler.list_trashed_stores().is_empty());
+    }
+}
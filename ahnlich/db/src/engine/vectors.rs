@@ -0,0 +1,742 @@
+use ahnlich_types::keyval::KeyElementType;
+use ahnlich_types::keyval::StoreKey;
+use fallible_collections::vec::FallibleVec;
+use half::slice::{HalfBitsSliceExt, HalfFloatSliceExt};
+use half::vec::HalfFloatVecExt;
+use half::{bf16, f16};
+use memmap2::MmapMut;
+use ndarray::Array1;
+use serde::de::Error as DeError;
+use serde::ser::Error as SerError;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use std::collections::BTreeMap;
+use std::collections::HashMap as StdHashMap;
+use std::fmt;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bytes of vectors read from the file at once and kept together in the page cache
+const PAGE_SIZE: usize = 64 * 1024;
+
+/// Number of vectors the file has room for when it is created
+const INITIAL_CAPACITY: usize = 1024;
+
+/// Tells apart files created within the same nanosecond
+static FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Where the vector of an entry lives, which depends on the storage tier of its store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(super) enum VectorRef {
+    /// Bits of the values of the vector of a half precision store. Comes first as a snapshot could
+    /// otherwise read the bits as a serialized vector
+    Half(Box<[u16]>),
+    /// Values of the vector of an integer store. A struct variant so that a snapshot cannot read
+    /// it as the bits of a half precision vector
+    Int8 {
+        int8: Box<[i8]>,
+    },
+    Memory(StoreKey),
+    /// Slot of the vector in the file of a disk tier store
+    Disk(usize),
+}
+
+/// Euclidean length of a store key
+pub(super) fn norm(store_key: &StoreKey) -> f32 {
+    store_key.0.dot(&store_key.0).sqrt()
+}
+
+/// Smallest and largest finite values of the element type of a store
+fn bounds(element_type: KeyElementType) -> (f32, f32) {
+    match element_type {
+        KeyElementType::Float32 => (f32::MIN, f32::MAX),
+        KeyElementType::Float16 => (f16::MIN.to_f32(), f16::MAX.to_f32()),
+        KeyElementType::BFloat16 => (bf16::MIN.to_f32(), bf16::MAX.to_f32()),
+        KeyElementType::Int8 => (i8::MIN.into(), i8::MAX.into()),
+    }
+}
+
+/// Whether every finite value of a store key can be held by the element type of its store
+/// without turning infinite, or for integer stores without being rounded. Values that are not
+/// finite are left to the non finite vectors policy of the store
+pub(super) fn fits(element_type: KeyElementType, store_key: &StoreKey) -> bool {
+    let (min, max) = bounds(element_type);
+    store_key
+        .0
+        .iter()
+        .filter(|value| value.is_finite())
+        .all(|value| {
+            (min..=max).contains(value)
+                && (element_type != KeyElementType::Int8 || value.fract() == 0.0)
+        })
+}
+
+/// Scales a store key to unit length, leaving a key of zero length as it is
+pub(super) fn normalize(store_key: StoreKey) -> StoreKey {
+    let norm = norm(&store_key);
+    if norm == 0.0 {
+        return store_key;
+    }
+    StoreKey(store_key.0 / norm)
+}
+
+/// Whether every value of a store key is neither NaN nor infinite
+pub(super) fn is_finite(store_key: &StoreKey) -> bool {
+    store_key.0.iter().all(|value| value.is_finite())
+}
+
+/// Replaces NaN with zero and clamps every other value to the largest finite value of its sign
+/// that the precision of the store holds, so that rounding cannot turn it infinite either
+pub(super) fn sanitize(element_type: KeyElementType, mut store_key: StoreKey) -> StoreKey {
+    let (min, max) = bounds(element_type);
+    // NaN fails the comparison as well
+    if store_key.0.iter().all(|value| (min..=max).contains(value)) {
+        return store_key;
+    }
+    store_key.0.mapv_inplace(|value| {
+        if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(min, max)
+        }
+    });
+    store_key
+}
+
+/// Rounds the values of a store key to the precision of its store, which is what entries are
+/// keyed by so that the same key finds the same entry once rounded
+pub(super) fn round(element_type: KeyElementType, mut store_key: StoreKey) -> StoreKey {
+    if element_type == KeyElementType::Int8 {
+        store_key
+            .0
+            .mapv_inplace(|value| value.round().clamp(i8::MIN.into(), i8::MAX.into()));
+        return store_key;
+    }
+    match to_half_bits(element_type, &store_key) {
+        Some(bits) => from_half_bits(element_type, &bits),
+        None => store_key,
+    }
+}
+
+/// Converts the values of a store key to the bits of a half precision store, using the
+/// conversion instructions of the CPU where it has them. Other stores have no bits
+pub(super) fn to_half_bits(
+    element_type: KeyElementType,
+    store_key: &StoreKey,
+) -> Option<Box<[u16]>> {
+    if !matches!(
+        element_type,
+        KeyElementType::Float16 | KeyElementType::BFloat16
+    ) {
+        return None;
+    }
+    let values = store_key.0.to_vec();
+    let bits = match element_type {
+        KeyElementType::Float32 | KeyElementType::Int8 => {
+            unreachable!("only half precision stores have bits")
+        }
+        KeyElementType::Float16 => {
+            let mut halves = vec![f16::ZERO; values.len()];
+            halves.convert_from_f32_slice(&values);
+            halves.reinterpret_into()
+        }
+        KeyElementType::BFloat16 => {
+            let mut halves = vec![bf16::ZERO; values.len()];
+            halves.convert_from_f32_slice(&values);
+            halves.reinterpret_into()
+        }
+    };
+    Some(bits.into_boxed_slice())
+}
+
+pub(super) fn from_half_bits(element_type: KeyElementType, bits: &[u16]) -> StoreKey {
+    let values = match element_type {
+        KeyElementType::Float16 => bits.reinterpret_cast::<f16>().to_f32_vec(),
+        KeyElementType::BFloat16 => bits.reinterpret_cast::<bf16>().to_f32_vec(),
+        KeyElementType::Float32 | KeyElementType::Int8 => {
+            unreachable!("half precision bits of a store that is not half precision")
+        }
+    };
+    StoreKey(Array1::from(values))
+}
+
+/// Converts the values of a store key rounded for an integer store to bytes
+pub(super) fn to_int8(store_key: &StoreKey) -> Box<[i8]> {
+    store_key.0.iter().map(|value| *value as i8).collect()
+}
+
+pub(super) fn from_int8(values: &[i8]) -> StoreKey {
+    StoreKey(values.iter().map(|value| f32::from(*value)).collect())
+}
+
+/// Bytes a value takes in a vector file
+pub(super) fn element_size(element_type: KeyElementType) -> usize {
+    match element_type {
+        KeyElementType::Float32 => size_of::<f32>(),
+        KeyElementType::Float16 | KeyElementType::BFloat16 => size_of::<u16>(),
+        KeyElementType::Int8 => size_of::<i8>(),
+    }
+}
+
+fn encode_element(element_type: KeyElementType, value: f32, bytes: &mut [u8]) {
+    match element_type {
+        KeyElementType::Float32 => bytes.copy_from_slice(&value.to_ne_bytes()),
+        KeyElementType::Float16 => bytes.copy_from_slice(&f16::from_f32(value).to_ne_bytes()),
+        KeyElementType::BFloat16 => bytes.copy_from_slice(&bf16::from_f32(value).to_ne_bytes()),
+        KeyElementType::Int8 => bytes.copy_from_slice(&(value as i8).to_ne_bytes()),
+    }
+}
+
+fn decode_element(element_type: KeyElementType, bytes: &[u8]) -> f32 {
+    match element_type {
+        KeyElementType::Float32 => f32::from_ne_bytes(bytes.try_into().expect("chunk of f32 size")),
+        KeyElementType::Float16 => {
+            f16::from_ne_bytes(bytes.try_into().expect("chunk of f16 size")).to_f32()
+        }
+        KeyElementType::BFloat16 => {
+            bf16::from_ne_bytes(bytes.try_into().expect("chunk of bf16 size")).to_f32()
+        }
+        KeyElementType::Int8 => {
+            i8::from_ne_bytes(bytes.try_into().expect("chunk of i8 size")).into()
+        }
+    }
+}
+
+/// The file holding the vectors of a disk tier store mapped into memory, along with the number of
+/// vectors written to it
+struct MappedFile {
+    file: File,
+    map: MmapMut,
+    len: usize,
+}
+
+/// Vectors of a disk tier store. They are appended to a memory mapped file and read back through
+/// an LRU cache of pages, so only the cached pages count towards the memory of the server.
+/// A slot is never written twice as entries are keyed by their vector, so deleted entries keep
+/// their slot until the store is compacted into a new file
+pub(crate) struct DiskVectors {
+    path: PathBuf,
+    dimension: NonZeroUsize,
+    element_type: KeyElementType,
+    cache_size: usize,
+    mapped: RwLock<MappedFile>,
+    /// Slots a page of the cache holds
+    slots_per_page: usize,
+    /// Only held to look up and insert pages, never while a page is read from the file
+    cache: Mutex<PageCache>,
+    /// Set when the store is dropped or replaced by compaction so that the file is removed once
+    /// the last reader lets go of the store. Until the next snapshot is written, a snapshot may
+    /// still refer to the removed file
+    discarded: AtomicBool,
+}
+
+impl DiskVectors {
+    /// Creates an empty vector file within the directory
+    pub(super) fn create(
+        directory: &Path,
+        dimension: NonZeroUsize,
+        element_type: KeyElementType,
+        cache_size: usize,
+    ) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+        let counter = FILE_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = directory.join(format!("vectors-{nanos}-{counter}.bin"));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        file.set_len((INITIAL_CAPACITY * dimension.get() * element_size(element_type)) as u64)?;
+        Self::map(path, file, dimension, element_type, cache_size, 0)
+    }
+
+    /// Creates an empty vector file next to this one, used when the store is replaced
+    pub(super) fn create_alongside(&self, dimension: NonZeroUsize) -> io::Result<Self> {
+        let directory = self.path.parent().unwrap_or(Path::new("."));
+        Self::create(directory, dimension, self.element_type, self.cache_size)
+    }
+
+    fn map(
+        path: PathBuf,
+        file: File,
+        dimension: NonZeroUsize,
+        element_type: KeyElementType,
+        cache_size: usize,
+        len: usize,
+    ) -> io::Result<Self> {
+        // SAFETY: the file is created and only ever written to through this mapping
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        // pages are cached as f32 whatever the precision of the file
+        let slots_per_page = (PAGE_SIZE / (dimension.get() * size_of::<f32>())).max(1);
+        let page_bytes = slots_per_page * dimension.get() * size_of::<f32>();
+        Ok(Self {
+            path,
+            dimension,
+            element_type,
+            cache_size,
+            mapped: RwLock::new(MappedFile { file, map, len }),
+            slots_per_page,
+            cache: Mutex::new(PageCache::new((cache_size / page_bytes).max(1))),
+            discarded: AtomicBool::new(false),
+        })
+    }
+
+    fn vector_bytes(&self) -> usize {
+        self.dimension.get() * element_size(self.element_type)
+    }
+
+    /// Writes the vectors to the end of the file, growing it when full, and returns their slots
+    pub(super) fn append<'a>(
+        &self,
+        vectors: impl ExactSizeIterator<Item = &'a StoreKey>,
+    ) -> io::Result<Vec<usize>> {
+        let vector_bytes = self.vector_bytes();
+        let mut mapped = self.mapped.write().expect("vector file lock poisoned");
+        let start = mapped.len;
+        let needed = (start + vectors.len()) * vector_bytes;
+        if needed > mapped.map.len() {
+            let capacity = needed.max(mapped.map.len() * 2);
+            mapped.map.flush()?;
+            mapped.file.set_len(capacity as u64)?;
+            // SAFETY: the file is only ever written to through this mapping, which is replaced
+            // while no reader holds the lock
+            mapped.map = unsafe { MmapMut::map_mut(&mapped.file)? };
+        }
+        for (slot, vector) in (start..).zip(vectors) {
+            let bytes = &mut mapped.map[slot * vector_bytes..(slot + 1) * vector_bytes];
+            for (chunk, value) in bytes
+                .chunks_exact_mut(element_size(self.element_type))
+                .zip(vector.0.iter())
+            {
+                encode_element(self.element_type, *value, chunk);
+            }
+            mapped.len = slot + 1;
+        }
+        Ok((start..mapped.len).collect())
+    }
+
+    /// Reads the vector in a slot, going through the page cache. A page missing from the cache is
+    /// read with the cache let go of so that reads of cached pages are not held up meanwhile
+    pub(super) fn read(&self, slot: usize) -> StoreKey {
+        let dimension = self.dimension.get();
+        let (page, offset) = (
+            slot / self.slots_per_page,
+            (slot % self.slots_per_page) * dimension,
+        );
+        if let Some(values) = self
+            .cache
+            .lock()
+            .expect("page cache lock poisoned")
+            .get(page)
+        {
+            // pages cached before the slot was written stop short of it
+            if let Some(vector) = values.get(offset..offset + dimension) {
+                return StoreKey(Array1::from_iter(vector.iter().copied()));
+            }
+        }
+        let mapped = self.mapped.read().expect("vector file lock poisoned");
+        let start = page * self.slots_per_page;
+        let end = mapped.len.min(start + self.slots_per_page);
+        match self.read_page(&mapped, start, end) {
+            Some(values) => {
+                drop(mapped);
+                let vector = StoreKey(Array1::from_iter(
+                    values[offset..offset + dimension].iter().copied(),
+                ));
+                // another read of the page may have cached it meanwhile, which this replaces
+                self.cache
+                    .lock()
+                    .expect("page cache lock poisoned")
+                    .insert(page, values);
+                vector
+            }
+            // there is no memory left for the page so the cache makes way for future pages and
+            // only the vector is read
+            None => {
+                let vector = StoreKey(Array1::from_iter(self.read_values(&mapped, slot, slot + 1)));
+                drop(mapped);
+                self.cache.lock().expect("page cache lock poisoned").clear();
+                vector
+            }
+        }
+    }
+
+    fn read_page(&self, mapped: &MappedFile, start: usize, end: usize) -> Option<Vec<f32>> {
+        let mut values: Vec<f32> =
+            FallibleVec::try_with_capacity((end - start) * self.dimension.get()).ok()?;
+        values.extend(self.read_values(mapped, start, end));
+        Some(values)
+    }
+
+    fn read_values<'a>(
+        &self,
+        mapped: &'a MappedFile,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = f32> + 'a {
+        let (vector_bytes, element_type) = (self.vector_bytes(), self.element_type);
+        mapped.map[start * vector_bytes..end * vector_bytes]
+            .chunks_exact(element_size(element_type))
+            .map(move |chunk| decode_element(element_type, chunk))
+    }
+
+    /// Bytes held in memory by the page cache
+    pub(super) fn cache_size_in_bytes(&self) -> usize {
+        self.cache
+            .lock()
+            .expect("page cache lock poisoned")
+            .size_in_bytes()
+    }
+
+    /// Marks the file to be removed once the vectors are dropped
+    pub(super) fn discard(&self) {
+        self.discarded.store(true, Ordering::SeqCst);
+    }
+
+    /// Leaves the file to be removed once a snapshot that no longer refers to it is persisted
+    pub(super) fn discard_after_snapshot(&self, discarded: &DiscardedFiles) {
+        discarded
+            .files
+            .lock()
+            .expect("discarded files lock poisoned")
+            .push(self.path.clone());
+    }
+}
+
+/// Vector files of discarded disk tier stores, which the snapshot last persisted may still refer
+/// to and load. A file is removed once a snapshot begun after it was discarded is persisted
+#[derive(Debug, Default)]
+pub(crate) struct DiscardedFiles {
+    files: Mutex<Vec<PathBuf>>,
+    /// Number of the files that were discarded before the snapshot being written was begun
+    snapshotted: AtomicUsize,
+}
+
+impl DiscardedFiles {
+    /// Marks the files discarded so far as ones the snapshot about to be written leaves out
+    pub(super) fn snapshot_begun(&self) {
+        let files = self.files.lock().expect("discarded files lock poisoned");
+        self.snapshotted.store(files.len(), Ordering::SeqCst);
+    }
+
+    /// Removes the files left out of the snapshot that was just persisted
+    pub(super) fn snapshot_persisted(&self) {
+        let mut files = self.files.lock().expect("discarded files lock poisoned");
+        let snapshotted = self.snapshotted.swap(0, Ordering::SeqCst).min(files.len());
+        for path in files.drain(..snapshotted) {
+            if let Err(err) = std::fs::remove_file(&path) {
+                log::error!("Failed to remove vector file {path:?}: {err}");
+            }
+        }
+    }
+}
+
+impl Drop for DiskVectors {
+    fn drop(&mut self) {
+        if self.discarded.load(Ordering::SeqCst) {
+            if let Err(err) = std::fs::remove_file(&self.path) {
+                log::error!("Failed to remove vector file {:?}: {err}", self.path);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DiskVectors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiskVectors")
+            .field("path", &self.path)
+            .field("dimension", &self.dimension)
+            .field("element_type", &self.element_type)
+            .field("cache_size", &self.cache_size)
+            .finish()
+    }
+}
+
+/// What a snapshot holds of the vectors of a disk tier store, which are themselves left in their
+/// file
+#[derive(Serialize, Deserialize)]
+struct DiskVectorsSnapshot {
+    path: PathBuf,
+    dimension: NonZeroUsize,
+    #[serde(default)]
+    element_type: KeyElementType,
+    cache_size: usize,
+    len: usize,
+}
+
+impl Serialize for DiskVectors {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mapped = self.mapped.read().expect("vector file lock poisoned");
+        // the vectors the snapshot refers to have to be on disk before it is
+        mapped.map.flush().map_err(S::Error::custom)?;
+        DiskVectorsSnapshot {
+            path: self.path.clone(),
+            dimension: self.dimension,
+            element_type: self.element_type,
+            cache_size: self.cache_size,
+            len: mapped.len,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DiskVectors {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let snapshot = DiskVectorsSnapshot::deserialize(deserializer)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&snapshot.path)
+            .map_err(|err| {
+                D::Error::custom(format!(
+                    "could not open vector file {:?}: {err}",
+                    snapshot.path
+                ))
+            })?;
+        Self::map(
+            snapshot.path,
+            file,
+            snapshot.dimension,
+            snapshot.element_type,
+            snapshot.cache_size,
+            snapshot.len,
+        )
+        .map_err(D::Error::custom)
+    }
+}
+
+/// Least recently used pages of a vector file. A page holds the values of consecutive slots
+struct PageCache {
+    capacity: usize,
+    tick: u64,
+    pages: StdHashMap<usize, (u64, Vec<f32>)>,
+    /// Pages by when they were last used
+    recency: BTreeMap<u64, usize>,
+}
+
+impl PageCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            pages: StdHashMap::new(),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, page: usize) -> Option<&Vec<f32>> {
+        let (used, values) = self.pages.get_mut(&page)?;
+        self.recency.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.recency.insert(self.tick, page);
+        Some(values)
+    }
+
+    fn insert(&mut self, page: usize, values: Vec<f32>) {
+        if let Some((used, _)) = self.pages.remove(&page) {
+            self.recency.remove(&used);
+        }
+        while self.pages.len() >= self.capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            self.pages.remove(&evicted);
+        }
+        self.tick += 1;
+        self.pages.insert(page, (self.tick, values));
+        self.recency.insert(self.tick, page);
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.recency.clear();
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.pages
+            .values()
+            .map(|(_, values)| values.capacity() * size_of::<f32>())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_disk_vectors_append_and_read() {
+        let directory = tempfile::tempdir().unwrap();
+        let dimension = NonZeroUsize::new(3).unwrap();
+        // a cache of a single page of a single vector to go through eviction
+        let vectors =
+            DiskVectors::create(directory.path(), dimension, KeyElementType::Float32, 12).unwrap();
+        let keys: Vec<_> = (0..INITIAL_CAPACITY * 3)
+            .map(|i| StoreKey(array![i as f32, 1.0, -(i as f32)]))
+            .collect();
+        let slots = vectors.append(keys.iter().take(10)).unwrap();
+        assert_eq!(slots, (0..10).collect::<Vec<_>>());
+        // grows the file past its initial capacity
+        let slots = vectors.append(keys.iter().skip(10)).unwrap();
+        assert_eq!(slots.first(), Some(&10));
+        assert_eq!(slots.last(), Some(&(keys.len() - 1)));
+        for slot in [0, 9, 10, keys.len() - 1, 5, 5] {
+            assert_eq!(vectors.read(slot), keys[slot]);
+        }
+        assert!(vectors.cache_size_in_bytes() <= PAGE_SIZE);
+
+        let snapshot = serde_json::to_string(&vectors).unwrap();
+        let path = vectors.path.clone();
+        drop(vectors);
+        let restored: DiskVectors = serde_json::from_str(&snapshot).unwrap();
+        assert_eq!(restored.read(keys.len() - 1), keys[keys.len() - 1]);
+        restored.discard();
+        drop(restored);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_disk_vectors_concurrent_reads() {
+        let directory = tempfile::tempdir().unwrap();
+        let dimension = NonZeroUsize::new(3).unwrap();
+        // a cache of a single page with the vectors spread over three so that reads keep missing it
+        let vectors =
+            DiskVectors::create(directory.path(), dimension, KeyElementType::Float32, 12).unwrap();
+        let keys: Vec<_> = (0..3 * PAGE_SIZE / 12)
+            .map(|i| StoreKey(array![i as f32, 1.0, -(i as f32)]))
+            .collect();
+        vectors.append(keys.iter()).unwrap();
+        std::thread::scope(|scope| {
+            for offset in 0..8 {
+                let (vectors, keys) = (&vectors, &keys);
+                scope.spawn(move || {
+                    for slot in (0..100).map(|i| (i * 997 + offset) % keys.len()) {
+                        assert_eq!(vectors.read(slot), keys[slot]);
+                    }
+                });
+            }
+        });
+        assert!(vectors.cache_size_in_bytes() <= PAGE_SIZE);
+        vectors.discard();
+    }
+
+    #[test]
+    fn test_discarded_files_outlive_snapshots() {
+        let directory = tempfile::tempdir().unwrap();
+        let dimension = NonZeroUsize::new(2).unwrap();
+        let create = || {
+            DiskVectors::create(directory.path(), dimension, KeyElementType::Float32, 8).unwrap()
+        };
+        let discarded = DiscardedFiles::default();
+        let (first, second) = (create(), create());
+        let paths = [first.path.clone(), second.path.clone()];
+        first.discard_after_snapshot(&discarded);
+        drop(first);
+        // the snapshot last persisted may still refer to a dropped file
+        assert!(paths[0].exists());
+        discarded.snapshot_begun();
+        second.discard_after_snapshot(&discarded);
+        drop(second);
+        discarded.snapshot_persisted();
+        assert!(!paths[0].exists());
+        // discarded while the snapshot was being written so it may be in there
+        assert!(paths[1].exists());
+        discarded.snapshot_begun();
+        discarded.snapshot_persisted();
+        assert!(!paths[1].exists());
+    }
+
+    #[test]
+    fn test_half_precision_vectors() {
+        let store_key = StoreKey(array![0.1, 1.0, -300.5]);
+        for element_type in [KeyElementType::Float16, KeyElementType::BFloat16] {
+            let rounded = round(element_type, store_key.clone());
+            assert_ne!(rounded, store_key);
+            // rounding twice changes nothing
+            assert_eq!(round(element_type, rounded.clone()), rounded);
+            let bits = to_half_bits(element_type, &store_key).unwrap();
+            assert_eq!(from_half_bits(element_type, &bits), rounded);
+
+            let directory = tempfile::tempdir().unwrap();
+            let vectors = DiskVectors::create(
+                directory.path(),
+                NonZeroUsize::new(3).unwrap(),
+                element_type,
+                1024,
+            )
+            .unwrap();
+            assert_eq!(vectors.vector_bytes(), 3 * size_of::<u16>());
+            vectors.append([&store_key, &rounded].into_iter()).unwrap();
+            assert_eq!(vectors.read(0), rounded);
+            assert_eq!(vectors.read(1), rounded);
+            vectors.discard();
+        }
+        assert_eq!(round(KeyElementType::Float32, store_key.clone()), store_key);
+        assert!(to_half_bits(KeyElementType::Float32, &store_key).is_none());
+    }
+
+    #[test]
+    fn test_int8_vectors() {
+        let store_key = StoreKey(array![-128.0, 0.0, 127.0]);
+        assert!(fits(KeyElementType::Int8, &store_key));
+        assert!(!fits(
+            KeyElementType::Int8,
+            &StoreKey(array![1.5, 0.0, 0.0])
+        ));
+        assert!(!fits(
+            KeyElementType::Int8,
+            &StoreKey(array![128.0, 0.0, 0.0])
+        ));
+        assert!(fits(
+            KeyElementType::Float32,
+            &StoreKey(array![1.5, 0.0, 0.0])
+        ));
+        assert!(!fits(
+            KeyElementType::Float16,
+            &StoreKey(array![1e6, 0.0, 0.0])
+        ));
+        assert_eq!(
+            round(KeyElementType::Int8, StoreKey(array![-300.0, 0.4, 126.6])),
+            store_key
+        );
+        assert_eq!(from_int8(&to_int8(&store_key)), store_key);
+
+        let snapshot = serde_json::to_string(&VectorRef::Int8 {
+            int8: to_int8(&store_key),
+        })
+        .unwrap();
+        assert!(matches!(
+            serde_json::from_str(&snapshot).unwrap(),
+            VectorRef::Int8 { .. }
+        ));
+
+        let directory = tempfile::tempdir().unwrap();
+        let vectors = DiskVectors::create(
+            directory.path(),
+            NonZeroUsize::new(3).unwrap(),
+            KeyElementType::Int8,
+            1024,
+        )
+        .unwrap();
+        assert_eq!(vectors.vector_bytes(), 3);
+        vectors.append([&store_key].into_iter()).unwrap();
+        assert_eq!(vectors.read(0), store_key);
+        vectors.discard();
+    }
+}
@@ -1,8 +1,31 @@
-use ahnlich_types::keyval::StoreName;
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::keyval::{KeyElementType, StoreName};
 use ahnlich_types::metadata::MetadataKey;
+use ahnlich_types::similarity::Algorithm;
 use ahnlich_types::similarity::NonLinearAlgorithm;
 use fallible_collections::TryReserveError;
+use std::fmt;
 use thiserror::Error;
+use utils::deadline::DeadlineExceeded;
+use utils::limits::AdmissionError;
+
+/// What the stores of a namespace hold that its quota caps
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum QuotaResource {
+    Stores,
+    Vectors,
+    MemoryBytes,
+}
+
+impl fmt::Display for QuotaResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaResource::Stores => f.write_str("stores"),
+            QuotaResource::Vectors => f.write_str("vectors"),
+            QuotaResource::MemoryBytes => f.write_str("bytes"),
+        }
+    }
+}
 
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum ServerError {
@@ -14,19 +37,258 @@ pub enum ServerError {
     StoreNotFound(StoreName),
     #[error("Store {0} already exists")]
     StoreAlreadyExists(StoreName),
-    #[error("Store dimension is [{store_dimension}], input dimension of [{input_dimension}] was specified")]
+    #[error("Store {0} not found in trash")]
+    TrashedStoreNotFound(StoreName),
+    #[error("Store {store} dimension is [{store_dimension}], input {index} has dimension [{input_dimension}]")]
     StoreDimensionMismatch {
+        store: StoreName,
         store_dimension: usize,
         input_dimension: usize,
+        /// Position of the offending input within the request
+        index: usize,
+    },
+    #[error("Dimension of store {0} cannot be inferred from a key with no dimension")]
+    DimensionNotInferred(StoreName),
+    #[error("Store {store} normalizes keys, input {index} has no length to normalize")]
+    VectorNotNormalizable { store: StoreName, index: usize },
+    #[error("Store {store} rejects vectors with NaN or infinite values, input {index} has one")]
+    NonFiniteVector { store: StoreName, index: usize },
+    #[error(
+        "Store {store} holds {element_type:?} values, input {index} has a value it cannot hold"
+    )]
+    KeyOutOfRange {
+        store: StoreName,
+        element_type: KeyElementType,
+        index: usize,
     },
+    #[error("Store {0} holds Int8 values, which cannot be normalized")]
+    NormalizedIntegerKeys(StoreName),
+    #[error("Store {0} evicts entries without max_entries or max_bytes to bound it")]
+    UnboundedEviction(StoreName),
+    #[error("Score threshold {threshold} cannot be used with {algorithm:?}")]
+    InvalidScoreThreshold {
+        threshold: String,
+        algorithm: Algorithm,
+    },
+    #[error(
+        "Score thresholds, normalization and recency boosts cannot be used with reciprocal rank fusion"
+    )]
+    RankFusionScoreOptions,
+    #[error("Store {0} has no timestamp key to boost recency with, recreate it with one")]
+    TimestampKeyNotSet(StoreName),
+    #[error("Recency boost weight {0} must be between 0 and 1")]
+    InvalidRecencyWeight(String),
+    #[error("Search term weight {0} must be a finite number")]
+    InvalidTermWeight(String),
+    #[error("Store {store} has no entry with key id {key_id}")]
+    KeyIdNotFound { store: StoreName, key_id: String },
+    #[error("Job {0} not found")]
+    JobNotFound(u64),
     #[error("Could not deserialize query, error is {0}")]
     QueryDeserializeError(String),
+    #[error("Set of {size} bytes into store {store} exceeds the limit of {limit} bytes")]
+    RequestTooLarge {
+        store: StoreName,
+        size: usize,
+        limit: usize,
+    },
+    #[error("Set of {len} entries into store {store} exceeds the limit of {limit} entries")]
+    BatchTooLarge {
+        store: StoreName,
+        len: usize,
+        limit: usize,
+    },
+    #[error("Request is estimated to need {estimate} bytes, over the limit of {limit} bytes")]
+    RequestMemoryExceeded { estimate: usize, limit: usize },
+    #[error("Request is estimated to need {estimate} bytes while requests being processed hold {in_flight} of the {limit} bytes allowed, retry later")]
+    InFlightMemoryExceeded {
+        estimate: usize,
+        in_flight: usize,
+        limit: usize,
+    },
+    #[error("Namespace {namespace} would hold {usage} {resource}, over its quota of {limit}")]
+    QuotaExceeded {
+        namespace: String,
+        resource: QuotaResource,
+        usage: usize,
+        limit: usize,
+    },
+    #[error("Disk tier stores need the server to be started with a vector storage location")]
+    VectorStorageNotConfigured,
+    #[error("The server is not started with a mirror")]
+    MirrorNotConfigured,
+    #[error("Stores cannot be exported unless the server is started with an export location")]
+    ExportNotConfigured,
+    #[error("Export path {0} must be a relative path that stays within the export location")]
+    InvalidExportPath(String),
+    #[error("Export error {0}")]
+    Export(String),
+    #[error("Store {store} exists with a {setting} other than that of the manifest, drop the store to apply the manifest")]
+    ManifestConflict { store: StoreName, setting: String },
+    #[error("Store {0} is declared more than once in the manifest")]
+    DuplicateManifestStore(StoreName),
+    #[error("The server is a read only mirror, writes are only accepted from {0}")]
+    ReadOnlyMirror(std::net::IpAddr),
+    #[error("Clients held to a filter in store {0} delete by predicate rather than by key")]
+    FilteredKeyDelete(StoreName),
+    #[error("Benchmark {setting} of {value} is over the limit of {limit}")]
+    BenchmarkLimitExceeded {
+        setting: &'static str,
+        value: u64,
+        limit: u64,
+    },
+    #[error("Timeout of the request passed before the query finished")]
+    DeadlineExceeded,
+    #[error("Vector storage error {0}")]
+    VectorStorage(String),
     #[error("allocation error {0:?}")]
     Allocation(TryReserveError),
 }
 
+impl From<DeadlineExceeded> for ServerError {
+    fn from(_: DeadlineExceeded) -> Self {
+        Self::DeadlineExceeded
+    }
+}
+
+impl From<AdmissionError> for ServerError {
+    fn from(input: AdmissionError) -> Self {
+        match input {
+            AdmissionError::RequestTooLarge { estimate, limit } => {
+                ServerError::RequestMemoryExceeded { estimate, limit }
+            }
+            AdmissionError::Busy {
+                estimate,
+                in_flight,
+                limit,
+            } => ServerError::InFlightMemoryExceeded {
+                estimate,
+                in_flight,
+                limit,
+            },
+        }
+    }
+}
+
 impl From<TryReserveError> for ServerError {
     fn from(input: TryReserveError) -> Self {
         Self::Allocation(input)
     }
 }
+
+impl From<std::io::Error> for ServerError {
+    fn from(input: std::io::Error) -> Self {
+        Self::VectorStorage(input.to_string())
+    }
+}
+
+impl From<ServerError> for ErrorResponse {
+    fn from(input: ServerError) -> Self {
+        let code = match &input {
+            ServerError::PredicateNotFound(_) => ErrorCode::PredicateNotFound,
+            ServerError::NonLinearIndexNotFound(_) => ErrorCode::NonLinearIndexNotFound,
+            ServerError::StoreNotFound(_) | ServerError::TrashedStoreNotFound(_) => {
+                ErrorCode::StoreNotFound
+            }
+            ServerError::StoreAlreadyExists(_) => ErrorCode::StoreAlreadyExists,
+            ServerError::StoreDimensionMismatch { .. } | ServerError::DimensionNotInferred(_) => {
+                ErrorCode::DimensionMismatch
+            }
+            ServerError::InvalidScoreThreshold { .. }
+            | ServerError::RankFusionScoreOptions
+            | ServerError::TimestampKeyNotSet(_)
+            | ServerError::InvalidRecencyWeight(_)
+            | ServerError::InvalidTermWeight(_)
+            | ServerError::KeyIdNotFound { .. }
+            | ServerError::QueryDeserializeError(_)
+            | ServerError::VectorStorageNotConfigured
+            | ServerError::MirrorNotConfigured
+            | ServerError::ExportNotConfigured
+            | ServerError::InvalidExportPath(_)
+            | ServerError::ManifestConflict { .. }
+            | ServerError::DuplicateManifestStore(_)
+            | ServerError::VectorNotNormalizable { .. }
+            | ServerError::NonFiniteVector { .. }
+            | ServerError::KeyOutOfRange { .. }
+            | ServerError::NormalizedIntegerKeys(_)
+            | ServerError::UnboundedEviction(_)
+            | ServerError::FilteredKeyDelete(_)
+            | ServerError::BenchmarkLimitExceeded { .. } => ErrorCode::InvalidArgument,
+            ServerError::ReadOnlyMirror(_) => ErrorCode::ReadOnly,
+            ServerError::DeadlineExceeded => ErrorCode::DeadlineExceeded,
+            ServerError::JobNotFound(_) => ErrorCode::JobNotFound,
+            ServerError::RequestTooLarge { .. }
+            | ServerError::BatchTooLarge { .. }
+            | ServerError::RequestMemoryExceeded { .. } => ErrorCode::LimitExceeded,
+            ServerError::InFlightMemoryExceeded { .. } => ErrorCode::ResourceExhausted,
+            ServerError::QuotaExceeded { .. } => ErrorCode::QuotaExceeded,
+            ServerError::Allocation(_) => ErrorCode::ResourceExhausted,
+            ServerError::VectorStorage(_) | ServerError::Export(_) => ErrorCode::Internal,
+        };
+        let response = ErrorResponse::new(code, &input);
+        match input {
+            ServerError::PredicateNotFound(predicate) => {
+                response.with_metadata("predicate", predicate)
+            }
+            ServerError::NonLinearIndexNotFound(algorithm) => {
+                response.with_metadata("algorithm", algorithm)
+            }
+            ServerError::StoreNotFound(store)
+            | ServerError::StoreAlreadyExists(store)
+            | ServerError::TrashedStoreNotFound(store)
+            | ServerError::DimensionNotInferred(store)
+            | ServerError::DuplicateManifestStore(store)
+            | ServerError::FilteredKeyDelete(store) => response.with_metadata("store", store),
+            ServerError::ManifestConflict { store, setting } => response
+                .with_metadata("store", store)
+                .with_metadata("setting", setting),
+            ServerError::StoreDimensionMismatch {
+                store,
+                store_dimension,
+                input_dimension,
+                index,
+            } => response
+                .with_metadata("store", store)
+                .with_metadata("store_dimension", store_dimension)
+                .with_metadata("input_dimension", input_dimension)
+                .with_metadata("index", index),
+            ServerError::KeyIdNotFound { store, key_id } => response
+                .with_metadata("store", store)
+                .with_metadata("key_id", key_id),
+            ServerError::VectorNotNormalizable { store, index }
+            | ServerError::NonFiniteVector { store, index } => response
+                .with_metadata("store", store)
+                .with_metadata("index", index),
+            ServerError::JobNotFound(job_id) => response.with_metadata("job_id", job_id),
+            ServerError::BenchmarkLimitExceeded { setting, limit, .. } => response
+                .with_metadata("setting", setting)
+                .with_metadata("limit", limit),
+            ServerError::InvalidExportPath(path) => response.with_metadata("path", path),
+            ServerError::RequestTooLarge { store, limit, .. }
+            | ServerError::BatchTooLarge { store, limit, .. } => response
+                .with_metadata("store", store)
+                .with_metadata("limit", limit),
+            ServerError::RequestMemoryExceeded { estimate, limit } => response
+                .with_metadata("estimate", estimate)
+                .with_metadata("limit", limit),
+            ServerError::InFlightMemoryExceeded {
+                estimate,
+                in_flight,
+                limit,
+            } => response
+                .with_metadata("estimate", estimate)
+                .with_metadata("in_flight", in_flight)
+                .with_metadata("limit", limit),
+            ServerError::QuotaExceeded {
+                namespace,
+                resource,
+                limit,
+                ..
+            } => response
+                .with_metadata("namespace", namespace)
+                .with_metadata("resource", resource)
+                .with_metadata("limit", limit),
+            _ => response,
+        }
+    }
+}
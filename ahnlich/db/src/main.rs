@@ -17,6 +17,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
             let server = ahnlich_db::server::handler::Server::new(config).await?;
             server.start().await?;
         }
+        ahnlich_db::cli::Commands::VerifyBackup(args) => {
+            args.output::<ahnlich_db::engine::store::StoreHandler>()
+        }
     }
     Ok(())
 }
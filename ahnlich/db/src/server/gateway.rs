@@ -0,0 +1,318 @@
+use crate::engine::export;
+use crate::engine::store::StoreHandler;
+use ahnlich_types::db::{DBQuery, ServerDBQuery, ServerResult};
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::keyval::{
+    KeyElementType, NonFiniteVectors, StorageTier, StoreEviction, StoreKey, StoreName, StoreValue,
+    VectorNormalization,
+};
+use ahnlich_types::metadata::MetadataKey;
+use ahnlich_types::predicate::PredicateCondition;
+use ahnlich_types::similarity::{
+    Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm, RecencyBoost, Similarity,
+};
+use axum::body::{Bytes, StreamBody};
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::Router;
+use ndarray::Array1;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use utils::gateway::{
+    json_response, parse_body, query_response, status_code, trace_parent, GatewayError, Upstream,
+};
+
+/// Routes of the db HTTP gateway
+/// - `GET /ping`, `GET /info`
+/// - `GET /memory` breaks down the memory of the server
+/// - `GET /stores` lists stores, `POST /stores` creates a store
+/// - `DELETE /stores/{store}` drops a store
+/// - `POST /stores/{store}/entries` sets entries in a store
+/// - `POST /stores/{store}/query` gets the closest entries to a search input
+/// - `POST /query` runs a JSON list of any queries as a pipeline
+/// - `POST /stores/{store}/export` streams the entries of a store as Arrow record batches, only
+///   routed when given the store handler to read them from. Exports skip the client filters,
+///   memory admission and connection limits of the listener, so the store handler is not given
+///   when clients are held to filters
+pub(super) fn router(upstream: Upstream, store_handler: Option<Arc<StoreHandler>>) -> Router {
+    let router = Router::new()
+        .route("/ping", get(ping))
+        .route("/info", get(info))
+        .route("/memory", get(memory_breakdown))
+        .route("/stores", get(list_stores).post(create_store))
+        .route("/stores/:store", delete(drop_store))
+        .route("/stores/:store/entries", post(set))
+        .route("/stores/:store/query", post(get_sim_n))
+        .route("/query", post(pipeline))
+        .with_state(upstream);
+    match store_handler {
+        // exports read straight from the stores rather than through the listener, as the
+        // entries would otherwise have to be serialized whole into a single response first
+        Some(store_handler) => router.merge(
+            Router::new()
+                .route("/stores/:store/export", post(export))
+                .with_state(store_handler),
+        ),
+        None => router,
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_closest_n() -> NonZeroUsize {
+    NonZeroUsize::MIN
+}
+
+fn default_algorithm() -> Algorithm {
+    Algorithm::CosineSimilarity
+}
+
+fn default_batch_size() -> NonZeroUsize {
+    NonZeroUsize::new(8192).expect("batch size is not zero")
+}
+
+#[derive(Deserialize)]
+struct CreateStoreBody {
+    store: StoreName,
+    dimension: NonZeroUsize,
+    #[serde(default)]
+    create_predicates: HashSet<MetadataKey>,
+    #[serde(default)]
+    non_linear_indices: HashSet<NonLinearAlgorithm>,
+    #[serde(default = "default_true")]
+    error_if_exists: bool,
+    #[serde(default)]
+    timestamp_key: Option<MetadataKey>,
+    #[serde(default)]
+    storage_tier: StorageTier,
+    #[serde(default)]
+    infer_dimension: bool,
+    #[serde(default)]
+    key_element_type: KeyElementType,
+    #[serde(default)]
+    normalization: VectorNormalization,
+    #[serde(default)]
+    index_seed: Option<u64>,
+    #[serde(default)]
+    non_finite_vectors: NonFiniteVectors,
+    #[serde(default)]
+    eviction: Option<StoreEviction>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    key: Vec<f32>,
+    #[serde(default)]
+    value: StoreValue,
+}
+
+#[derive(Deserialize)]
+struct SetBody {
+    inputs: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct GetSimNBody {
+    search_input: Vec<f32>,
+    #[serde(default = "default_closest_n")]
+    closest_n: NonZeroUsize,
+    #[serde(default = "default_algorithm")]
+    algorithm: Algorithm,
+    #[serde(default)]
+    condition: Option<PredicateCondition>,
+    #[serde(default)]
+    min_score: Option<Similarity>,
+    #[serde(default)]
+    max_distance: Option<Similarity>,
+    #[serde(default)]
+    normalize_scores: bool,
+    #[serde(default)]
+    recency_boost: Option<RecencyBoost>,
+    #[serde(default)]
+    filter_strategy: FilterStrategy,
+}
+
+#[derive(Deserialize)]
+struct ExportBody {
+    #[serde(default)]
+    condition: Option<PredicateCondition>,
+    #[serde(default = "default_batch_size")]
+    batch_size: NonZeroUsize,
+}
+
+async fn send(
+    upstream: &Upstream,
+    headers: &HeaderMap,
+    queries: Vec<DBQuery>,
+) -> Result<ServerResult, GatewayError> {
+    let mut server_query =
+        ServerDBQuery::with_capacity_and_tracing_id(queries.len(), trace_parent(headers))
+            .map_err(GatewayError::Allocation)?;
+    for query in queries {
+        server_query.push(query);
+    }
+    upstream.forward(server_query).await
+}
+
+async fn single(
+    upstream: &Upstream,
+    headers: &HeaderMap,
+    query: DBQuery,
+) -> Result<Response, GatewayError> {
+    let result = send(upstream, headers, vec![query]).await?;
+    query_response(result.pop())
+}
+
+async fn ping(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, DBQuery::Ping).await
+}
+
+async fn info(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, DBQuery::InfoServer).await
+}
+
+async fn memory_breakdown(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, DBQuery::GetMemoryBreakdown).await
+}
+
+async fn list_stores(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    single(&upstream, &headers, DBQuery::ListStores).await
+}
+
+async fn create_store(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: CreateStoreBody = parse_body(&body)?;
+    let query = DBQuery::CreateStore {
+        store: body.store,
+        dimension: body.dimension,
+        create_predicates: body.create_predicates,
+        non_linear_indices: body.non_linear_indices,
+        error_if_exists: body.error_if_exists,
+        timestamp_key: body.timestamp_key,
+        storage_tier: body.storage_tier,
+        infer_dimension: body.infer_dimension,
+        key_element_type: body.key_element_type,
+        normalization: body.normalization,
+        index_seed: body.index_seed,
+        non_finite_vectors: body.non_finite_vectors,
+        eviction: body.eviction,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn drop_store(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, GatewayError> {
+    let query = DBQuery::DropStore {
+        store: StoreName(store),
+        error_if_not_exists: true,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn set(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: SetBody = parse_body(&body)?;
+    let query = DBQuery::Set {
+        store: StoreName(store),
+        inputs: body
+            .inputs
+            .into_iter()
+            .map(|entry| (StoreKey(Array1::from_vec(entry.key)), entry.value))
+            .collect(),
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn get_sim_n(
+    State(upstream): State<Upstream>,
+    Path(store): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: GetSimNBody = parse_body(&body)?;
+    let query = DBQuery::GetSimN {
+        store: StoreName(store),
+        search_input: StoreKey(Array1::from_vec(body.search_input)),
+        closest_n: body.closest_n,
+        algorithm: body.algorithm,
+        condition: body.condition,
+        min_score: body.min_score,
+        max_distance: body.max_distance,
+        normalize_scores: body.normalize_scores,
+        group_by: None,
+        group_size: NonZeroUsize::MIN,
+        additional_search_inputs: vec![],
+        fusion: FusionStrategy::Mean,
+        recency_boost: body.recency_boost,
+        filter_strategy: body.filter_strategy,
+        search_terms: vec![],
+        exclude_keys: vec![],
+        unordered_ties: false,
+    };
+    single(&upstream, &headers, query).await
+}
+
+async fn pipeline(
+    State(upstream): State<Upstream>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let queries: Vec<DBQuery> = parse_body(&body)?;
+    let result = send(&upstream, &headers, queries).await?;
+    Ok(json_response(StatusCode::OK, &result.into_inner()))
+}
+
+async fn export(
+    State(store_handler): State<Arc<StoreHandler>>,
+    Path(store): Path<String>,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    let body: ExportBody = parse_body(if body.is_empty() { b"{}" } else { &body })?;
+    let (dimension, entries) =
+        match store_handler.export_entries(&StoreName(store), body.condition.as_ref()) {
+            Ok(export) => export,
+            Err(error) => {
+                let error = ErrorResponse::from(error);
+                return Ok(json_response(status_code(error.code), &error));
+            }
+        };
+    match export::ipc_stream(dimension, entries, body.batch_size) {
+        Ok(stream) => Ok((
+            [(header::CONTENT_TYPE, export::CONTENT_TYPE)],
+            StreamBody::new(stream),
+        )
+            .into_response()),
+        Err(error) => Ok(json_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &ErrorResponse::new(ErrorCode::Internal, error),
+        )),
+    }
+}
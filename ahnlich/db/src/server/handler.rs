@@ -1,31 +1,61 @@
+use super::gateway;
 use super::task::ServerTask;
 use crate::cli::ServerConfig;
+use crate::engine::compaction::CompactionTask;
+use crate::engine::mirror::{MirrorLog, MirrorTask};
 use crate::engine::store::StoreHandler;
+use crate::engine::trash::TrashTask;
+use ahnlich_client_rs::db::DbClient;
 use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::db::ServerResult;
+use ahnlich_types::db::StoreManifest;
+use serde::Deserialize;
 use std::io::Result as IoResult;
 use std::net::SocketAddr;
-use std::sync::atomic::AtomicBool;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 use std::sync::Arc;
+use std::time::Duration;
 use task_manager::Task;
 use task_manager::TaskManager;
 use task_manager::TaskState;
 use tokio::io::BufReader;
-use tokio::net::TcpListener;
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
+use utils::audit::AuditLog;
+use utils::client::{ClientHandler, CONNECTION_BUFFER_SIZE};
+use utils::encryption::KeyProvider;
+use utils::filters::FilterHandler;
+use utils::gateway::{HttpGateway, Upstream};
+use utils::jobs::JobHandler;
+use utils::limits::{LimitHandler, MemoryAdmission};
+use utils::persistence::{Persistence, PersistenceTaskError};
+use utils::protocol::reject_connection;
+use utils::reload::{self, ConfigReloader};
+use utils::scheduler::Scheduler;
 use utils::server::AhnlichServerUtils;
 use utils::server::ServerUtilsConfig;
-use utils::{client::ClientHandler, persistence::Persistence};
+use utils::transport::{Listener, MemoryConnector, ServerStream};
 
 const SERVICE_NAME: &str = "ahnlich-db";
 
 #[derive(Debug, Clone)]
 pub struct Server {
-    listener: Arc<TcpListener>,
+    listener: Arc<Listener>,
     store_handler: Arc<StoreHandler>,
     client_handler: Arc<ClientHandler>,
+    job_handler: Arc<JobHandler>,
+    limit_handler: Arc<LimitHandler>,
+    config_reloader: Arc<ConfigReloader>,
+    filter_handler: Arc<FilterHandler>,
+    memory_admission: Arc<MemoryAdmission>,
+    deadlines_exceeded: Arc<AtomicU64>,
+    scheduler: Arc<Scheduler>,
     task_manager: Arc<TaskManager>,
+    http_gateway: Option<HttpGateway>,
+    audit_log: Option<Arc<AuditLog>>,
+    mirror_log: Option<Arc<MirrorLog>>,
+    key_provider: Option<Arc<dyn KeyProvider>>,
     config: ServerConfig,
 }
 
@@ -37,23 +67,24 @@ impl Task for Server {
 
     async fn run(&self) -> TaskState {
         if let Ok((stream, connect_addr)) = self.listener.accept().await {
-            if let Some(connected_client) = self
-                .client_handler
-                .connect(stream.peer_addr().expect("Could not get peer addr"))
-            {
-                log::info!("Connecting to {}", connect_addr);
-                let task = self.create_task(
-                    stream,
-                    self.local_addr().expect("Could not get server addr"),
-                    connected_client,
-                );
-                self.task_manager.spawn_task_loop(task).await;
+            match self.client_handler.connect(connect_addr.clone()) {
+                Ok(connected_client) => {
+                    log::info!("Connecting to {}", connect_addr);
+                    let task = self.create_task(
+                        stream,
+                        self.local_addr().expect("Could not get server addr"),
+                        connected_client,
+                    );
+                    self.task_manager.spawn_task_loop(task).await;
+                }
+                Err(rejected) => reject_connection::<ServerResult>(stream, rejected.into()).await,
             }
         }
         TaskState::Continue
     }
 }
 
+#[async_trait::async_trait]
 impl AhnlichServerUtils for Server {
     type PersistenceTask = StoreHandler;
 
@@ -64,6 +95,9 @@ impl AhnlichServerUtils for Server {
             persistence_interval: self.config.common.persistence_interval,
             allocator_size: self.config.common.allocator_size,
             threadpool_size: self.config.common.threadpool_size,
+            maintenance_threadpool_size: Some(self.config.maintenance_threadpool_size),
+            key_provider: self.key_provider.clone(),
+            systemd_notify: self.config.common.systemd_notify,
         }
     }
 
@@ -78,22 +112,113 @@ impl AhnlichServerUtils for Server {
     fn task_manager(&self) -> Arc<TaskManager> {
         self.task_manager.clone()
     }
+
+    fn http_gateway(&self) -> Option<HttpGateway> {
+        self.http_gateway.clone()
+    }
+
+    fn config_reloader(&self) -> Option<Arc<ConfigReloader>> {
+        Some(self.config_reloader.clone())
+    }
+
+    async fn spawn_background_tasks(&self, task_manager: &TaskManager) {
+        if let Some(threshold) = self.config.compaction_threshold {
+            task_manager
+                .spawn_task_loop(CompactionTask::new(
+                    self.store_handler.clone(),
+                    threshold,
+                    self.config.compaction_interval,
+                ))
+                .await;
+        }
+        if let Some(retention) = self.config.trash_retention {
+            task_manager
+                .spawn_task_loop(TrashTask::new(
+                    self.store_handler.clone(),
+                    Duration::from_secs(retention),
+                ))
+                .await;
+        }
+        if let (Some(mirror_log), Some(host)) = (&self.mirror_log, &self.config.mirror_host) {
+            match DbClient::new(host.clone(), self.config.mirror_port).await {
+                Ok(client) => {
+                    task_manager
+                        .spawn_task_loop(MirrorTask::new(
+                            mirror_log.clone(),
+                            self.store_handler.clone(),
+                            client,
+                            self.config.mirror_batch_size,
+                        ))
+                        .await
+                }
+                Err(err) => log::error!("Could not create client of mirror {err}"),
+            }
+        }
+    }
+}
+
+/// Manifest file given to the server with `--manifest`
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    stores: Vec<StoreManifest>,
+}
+
+fn read_manifest(path: &Path) -> Result<Vec<StoreManifest>, String> {
+    let manifest = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    toml::from_str::<Manifest>(&manifest)
+        .map(|manifest| manifest.stores)
+        .map_err(|e| e.to_string())
 }
 
 impl Server {
     /// creates a server while injecting a shutdown_token
     pub async fn new_with_config(config: &ServerConfig) -> IoResult<Self> {
-        let listener =
-            tokio::net::TcpListener::bind(format!("{}:{}", &config.common.host, &config.port))
-                .await?;
+        let listener = Listener::bind(
+            &config.common.host,
+            config.port,
+            config.common.unix_socket.as_deref(),
+        )
+        .await?;
         let write_flag = Arc::new(AtomicBool::new(false));
-        let client_handler = Arc::new(ClientHandler::new(config.common.maximum_clients));
+        let client_handler = Arc::new(ClientHandler::new(
+            config.common.maximum_clients,
+            config.common.maximum_clients_per_host,
+        ));
+        let limit_handler = Arc::new(LimitHandler::new(&config.common));
+        let config_reloader = Arc::new(ConfigReloader::new(
+            &config.common,
+            reload::flags::<ServerConfig>(),
+            limit_handler.clone(),
+            client_handler.clone(),
+        ));
+        config_reloader
+            .load()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()))?;
         let mut store_handler = StoreHandler::new(write_flag.clone());
+        if let Some(location) = &config.vector_storage_location {
+            std::fs::create_dir_all(location)?;
+            store_handler.use_vector_storage(location.clone(), config.vector_cache_size);
+        }
+        if let Some(retention) = config.trash_retention {
+            store_handler.use_trash(Duration::from_secs(retention));
+        }
+        for quota in &config.namespace_quotas {
+            store_handler.set_quota(quota.namespace.clone(), quota.quota);
+        }
+        let key_provider = config
+            .common
+            .key_provider()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
         if let Some(persist_location) = &config.common.persist_location {
-            match Persistence::load_snapshot(persist_location) {
+            store_handler.keep_discarded_files();
+            match Persistence::load_snapshot(persist_location, key_provider.as_deref()) {
                 Err(e) => {
                     log::error!("Failed to load snapshot from persist location {e}");
-                    if config.common.fail_on_startup_if_persist_load_fails {
+                    // starting without the snapshot would overwrite it once persisted
+                    if config.common.fail_on_startup_if_persist_load_fails
+                        || matches!(e, PersistenceTaskError::Encryption(_))
+                    {
                         return Err(std::io::Error::new(
                             std::io::ErrorKind::Other,
                             e.to_string(),
@@ -105,11 +230,68 @@ impl Server {
                 }
             }
         };
+        // stores trashed by an earlier run either outlived their retention or are no longer kept
+        store_handler.purge_trash();
+        if let Some(path) = &config.manifest {
+            let changes = read_manifest(path)
+                .and_then(|stores| {
+                    store_handler
+                        .apply_manifest(stores, false)
+                        .map_err(|e| e.to_string())
+                })
+                .map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("Failed to apply manifest {}: {e}", path.display()),
+                    )
+                })?;
+            for change in changes {
+                log::info!("Manifest applied: {change}");
+            }
+        }
+        let store_handler = Arc::new(store_handler);
+        // exports read past the listener and so past the filters clients are held to
+        let arrow_export = config.enable_arrow_export && config.common.client_filters.is_empty();
+        if config.enable_arrow_export && !arrow_export {
+            log::warn!("Arrow exports are not served as clients are held to filters");
+        }
+        let http_gateway = if config.common.enable_http_gateway {
+            Some(HttpGateway::bind(
+                SERVICE_NAME,
+                &config.common.host,
+                config.http_port,
+                gateway::router(
+                    Upstream::new(listener.local_addr()?),
+                    arrow_export.then(|| store_handler.clone()),
+                ),
+            )?)
+        } else {
+            None
+        };
         Ok(Self {
             listener: Arc::new(listener),
-            store_handler: Arc::new(store_handler),
+            store_handler,
             client_handler,
+            job_handler: Arc::new(JobHandler::new(Duration::from_secs(config.common.job_ttl))),
+            limit_handler,
+            config_reloader,
+            filter_handler: Arc::new(FilterHandler::new(&config.common)),
+            memory_admission: Arc::new(MemoryAdmission::new(
+                config.max_request_memory,
+                config.max_in_flight_memory,
+            )),
+            deadlines_exceeded: Arc::new(AtomicU64::new(0)),
+            scheduler: Arc::new(config.common.scheduler()),
             task_manager: Arc::new(TaskManager::new()),
+            http_gateway,
+            audit_log: AuditLog::open(&config.common)?.map(Arc::new),
+            mirror_log: config.mirror_host.as_ref().map(|host| {
+                Arc::new(MirrorLog::new(
+                    format!("{host}:{}", config.mirror_port),
+                    config.mirror_log_size,
+                ))
+            }),
+            key_provider,
             config: config.clone(),
         })
     }
@@ -122,30 +304,63 @@ impl Server {
             SERVICE_NAME,
             &config.common.otel_endpoint,
             &config.common.log_level,
+            config.common.log_format,
+            &config.common.sampling(),
         );
         Self::new_with_config(config).await
     }
 
     fn create_task(
         &self,
-        stream: TcpStream,
+        stream: ServerStream,
         server_addr: SocketAddr,
         connected_client: ConnectedClient,
     ) -> ServerTask {
-        let reader = BufReader::new(stream);
+        let reader = BufReader::with_capacity(CONNECTION_BUFFER_SIZE, stream);
+        let maximum_message_size = self.limit_handler.client(&connected_client).message_size as u64;
         // add client to client_handler
         ServerTask {
             reader: Arc::new(Mutex::new(reader)),
             server_addr,
             connected_client,
-            maximum_message_size: self.config.common.message_size as u64,
+            maximum_message_size,
             // "inexpensive" to clone handlers they can be passed around in an Arc
             client_handler: self.client_handler.clone(),
             store_handler: self.store_handler.clone(),
+            job_handler: self.job_handler.clone(),
+            limit_handler: self.limit_handler.clone(),
+            config_reloader: self.config_reloader.clone(),
+            filter_handler: self.filter_handler.clone(),
+            memory_admission: self.memory_admission.clone(),
+            deadlines_exceeded: self.deadlines_exceeded.clone(),
+            cancelled: AtomicBool::new(false),
+            scheduler: self.scheduler.clone(),
+            task_manager: self.task_manager.clone(),
+            audit_log: self.audit_log.clone(),
+            mirror_log: self.mirror_log.clone(),
+            mirror_source: self.config.mirror_source,
+            export_location: self.config.export_location.clone(),
         }
     }
 
     pub fn local_addr(&self) -> IoResult<SocketAddr> {
         self.listener.local_addr()
     }
+
+    /// Unix domain socket clients are served over when one is configured
+    pub fn unix_socket(&self) -> Option<&Path> {
+        self.listener.unix_socket()
+    }
+
+    /// Opens in-memory connections to the server from within the same process
+    pub fn memory_connector(&self) -> MemoryConnector {
+        self.listener.memory_connector()
+    }
+
+    /// address of the HTTP gateway when enabled
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http_gateway
+            .as_ref()
+            .and_then(|http_gateway| http_gateway.local_addr().ok())
+    }
 }
@@ -1,2 +1,3 @@
+mod gateway;
 pub mod handler;
 mod task;
@@ -1,27 +1,68 @@
-use crate::engine::store::StoreHandler;
+use crate::engine::jobs::{DelPredTask, ExportStoreTask, ParquetExport, RepairStoreTask};
+use crate::engine::mirror::MirrorLog;
+use crate::engine::search::GetSimNOptions;
+use crate::engine::store::{StoreHandler, StoreSettings};
+use crate::errors::ServerError;
+use ahnlich_types::bincode::serialized_size;
 use ahnlich_types::client::ConnectedClient;
-use ahnlich_types::db::{DBQuery, ServerDBQuery, ServerInfo, ServerResponse, ServerResult};
+use ahnlich_types::db::{
+    DBQuery, MemoryBreakdown, ServerDBQuery, ServerInfo, ServerResponse, ServerResult,
+    StoreBenchmark, StoreCheck,
+};
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::jobs::JobKind;
+use ahnlich_types::keyval::{StoreKey, StoreName, StoreValue};
+use ahnlich_types::version::MIN_CLIENT_VERSION;
 use ahnlich_types::version::VERSION;
-use std::net::SocketAddr;
+use ahnlich_types::ErrorPolicy;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use task_manager::Task;
+use task_manager::TaskManager;
 use task_manager::TaskState;
 use tokio::io::BufReader;
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tracing::Instrument;
 use utils::allocator::GLOBAL_ALLOCATOR;
+use utils::audit::{AuditLog, AuditOperation};
 use utils::client::ClientHandler;
-use utils::protocol::AhnlichProtocol;
+use utils::deadline::Deadline;
+use utils::filters::FilterHandler;
+use utils::jobs::JobHandler;
+use utils::limits::{LimitHandler, MemoryAdmission};
+use utils::protocol::{log_query, AhnlichProtocol};
+use utils::reload::ConfigReloader;
+use utils::scheduler::Scheduler;
+use utils::transport::ServerStream;
 
 #[derive(Debug)]
 pub struct ServerTask {
     pub(super) server_addr: SocketAddr,
-    pub(super) reader: Arc<Mutex<BufReader<TcpStream>>>,
+    pub(super) reader: Arc<Mutex<BufReader<ServerStream>>>,
     pub(super) store_handler: Arc<StoreHandler>,
     pub(super) client_handler: Arc<ClientHandler>,
+    pub(super) job_handler: Arc<JobHandler>,
+    pub(super) limit_handler: Arc<LimitHandler>,
+    pub(super) config_reloader: Arc<ConfigReloader>,
+    pub(super) filter_handler: Arc<FilterHandler>,
+    pub(super) memory_admission: Arc<MemoryAdmission>,
+    // queries of every client abandoned because their deadline passed
+    pub(super) deadlines_exceeded: Arc<AtomicU64>,
+    pub(super) cancelled: AtomicBool,
+    pub(super) scheduler: Arc<Scheduler>,
+    pub(super) task_manager: Arc<TaskManager>,
     pub(super) connected_client: ConnectedClient,
     pub(super) maximum_message_size: u64,
+    pub(super) audit_log: Option<Arc<AuditLog>>,
+    pub(super) mirror_log: Option<Arc<MirrorLog>>,
+    // set when the server is a read only mirror of the server at this address
+    pub(super) mirror_source: Option<IpAddr>,
+    // directory stores are exported to, exports are refused without it
+    pub(super) export_location: Option<PathBuf>,
 }
 
 #[async_trait::async_trait]
@@ -35,26 +76,82 @@ impl AhnlichProtocol for ServerTask {
     fn maximum_message_size(&self) -> u64 {
         self.maximum_message_size
     }
-    fn reader(&self) -> Arc<Mutex<BufReader<TcpStream>>> {
+    fn reader(&self) -> Arc<Mutex<BufReader<ServerStream>>> {
         self.reader.clone()
     }
+    fn cancelled(&self) -> &AtomicBool {
+        &self.cancelled
+    }
+    fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
 
-    async fn handle(&self, queries: Vec<DBQuery>) -> ServerResult {
+    async fn handle(
+        &self,
+        queries: Vec<DBQuery>,
+        error_policy: ErrorPolicy,
+        deadline: Deadline,
+    ) -> ServerResult {
         let mut result = ServerResult::with_capacity(queries.len());
         for query in queries {
-            result.push(match query {
+            if self.hung_up().await {
+                break;
+            }
+            let query = self.filter_query(query);
+            let audited = self
+                .audit_log
+                .as_ref()
+                .and_then(|_| audit_operation(&query));
+            let mirrored = match &self.mirror_log {
+                Some(_) if is_mirrored(&query) => Some(mirrored_query(query.clone())),
+                _ => None,
+            };
+            let query_type: &'static str = (&query).into();
+            let store = query.store().cloned();
+            let started = Instant::now();
+            let writable = self.check_writable(&query);
+            // held until the query has been answered
+            let admitted = self
+                .memory_admission
+                .admit(self.estimate_memory(&query))
+                .map_err(|err| ErrorResponse::from(ServerError::from(err)));
+            let response = match query {
+                _ if writable.is_err() => writable.map(|_| ServerResponse::Unit),
+                _ if admitted.is_err() => admitted.map(|_| ServerResponse::Unit),
+                _ if deadline.expired() => Err(ServerError::DeadlineExceeded.into()),
                 DBQuery::Ping => Ok(ServerResponse::Pong),
                 DBQuery::InfoServer => Ok(ServerResponse::InfoServer(self.server_info())),
-                DBQuery::ListClients => Ok(ServerResponse::ClientList(self.client_handler.list())),
-                DBQuery::ListStores => {
-                    Ok(ServerResponse::StoreList(self.store_handler.list_stores()))
+                DBQuery::GetMemoryBreakdown => {
+                    Ok(ServerResponse::MemoryBreakdown(self.memory_breakdown()))
                 }
+                DBQuery::ReloadConfig => self
+                    .config_reloader
+                    .reload()
+                    .map(ServerResponse::ConfigReloaded)
+                    .map_err(ErrorResponse::from),
+                DBQuery::ListClients => Ok(ServerResponse::ClientList(self.client_handler.list())),
+                DBQuery::ListStores => Ok(ServerResponse::StoreList(
+                    self.store_handler.list_stores(&self.limit_handler),
+                )),
+                DBQuery::DescribeStore { store } => self
+                    .store_handler
+                    .describe_store(&store, &self.limit_handler)
+                    .map(ServerResponse::StoreDescription)
+                    .map_err(ErrorResponse::from),
                 DBQuery::CreateStore {
                     store,
                     dimension,
                     create_predicates,
                     non_linear_indices,
                     error_if_exists,
+                    timestamp_key,
+                    storage_tier,
+                    infer_dimension,
+                    key_element_type,
+                    normalization,
+                    index_seed,
+                    non_finite_vectors,
+                    eviction,
                 } => self
                     .store_handler
                     .create_store(
@@ -62,15 +159,25 @@ impl AhnlichProtocol for ServerTask {
                         dimension,
                         create_predicates.into_iter().collect(),
                         non_linear_indices,
+                        StoreSettings {
+                            timestamp_key,
+                            storage_tier,
+                            infer_dimension,
+                            key_element_type,
+                            normalization,
+                            index_seed,
+                            non_finite_vectors,
+                            eviction,
+                        },
                         error_if_exists,
                     )
                     .map(|_| ServerResponse::Unit)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
                 DBQuery::CreatePredIndex { store, predicates } => self
                     .store_handler
                     .create_pred_index(&store, predicates.into_iter().collect())
                     .map(ServerResponse::CreateIndex)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
                 DBQuery::CreateNonLinearAlgorithmIndex {
                     store,
                     non_linear_indices,
@@ -78,7 +185,7 @@ impl AhnlichProtocol for ServerTask {
                     .store_handler
                     .create_non_linear_algorithm_index(&store, non_linear_indices)
                     .map(ServerResponse::CreateIndex)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
                 DBQuery::DropStore {
                     store,
                     error_if_not_exists,
@@ -86,7 +193,50 @@ impl AhnlichProtocol for ServerTask {
                     .store_handler
                     .drop_store(store, error_if_not_exists)
                     .map(ServerResponse::Del)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
+                DBQuery::ListTrashedStores => Ok(ServerResponse::TrashedStoreList(
+                    self.store_handler.list_trashed_stores(),
+                )),
+                DBQuery::RestoreStore { store } => self
+                    .store_handler
+                    .restore_store(store)
+                    .map(|_| ServerResponse::Unit)
+                    .map_err(ErrorResponse::from),
+                DBQuery::CompactStore { store } => self
+                    .store_handler
+                    .compact_store(&store)
+                    .map(ServerResponse::Compaction)
+                    .map_err(ErrorResponse::from),
+                DBQuery::CheckStore { store, repair } => self
+                    .check_store(&store, repair)
+                    .await
+                    .map(ServerResponse::StoreCheck)
+                    .map_err(ErrorResponse::from),
+                DBQuery::ScrubStore { store, delete } => self
+                    .store_handler
+                    .scrub_store(&store, delete)
+                    .map(ServerResponse::ScrubbedEntries)
+                    .map_err(ErrorResponse::from),
+                DBQuery::BenchmarkStore {
+                    store,
+                    sample_queries,
+                    concurrency,
+                    duration,
+                } => self
+                    .benchmark_store(store, sample_queries, concurrency, duration, deadline)
+                    .await
+                    .map(ServerResponse::StoreBenchmark)
+                    .map_err(ErrorResponse::from),
+                DBQuery::SetBulkWrite { store, enabled } => self
+                    .store_handler
+                    .set_bulk_write(&store, enabled)
+                    .map(|_| ServerResponse::Unit)
+                    .map_err(ErrorResponse::from),
+                DBQuery::Warmup { stores } => self
+                    .store_handler
+                    .warmup(stores)
+                    .map(|_| ServerResponse::Unit)
+                    .map_err(ErrorResponse::from),
                 DBQuery::DropPredIndex {
                     store,
                     error_if_not_exists,
@@ -99,7 +249,7 @@ impl AhnlichProtocol for ServerTask {
                         error_if_not_exists,
                     )
                     .map(ServerResponse::Del)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
                 DBQuery::DropNonLinearAlgorithmIndex {
                     store,
                     error_if_not_exists,
@@ -112,59 +262,647 @@ impl AhnlichProtocol for ServerTask {
                         error_if_not_exists,
                     )
                     .map(ServerResponse::Del)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
                 DBQuery::Set { store, inputs } => self
-                    .store_handler
-                    .set_in_store(&store, inputs)
+                    .check_set_limits(&store, &inputs)
+                    .and_then(|_| self.store_handler.set_in_store(&store, inputs))
                     .map(ServerResponse::Set)
-                    .map_err(|e| format!("{e}")),
-                DBQuery::GetKey { store, keys } => self
-                    .store_handler
-                    .get_key_in_store(&store, keys)
+                    .map_err(ErrorResponse::from),
+                DBQuery::GetKey { store, keys } => {
+                    // clients held to a filter only get the entries it allows
+                    let condition =
+                        self.filter_handler
+                            .restrict_optional(&self.connected_client, &store, None);
+                    match condition {
+                        Some(condition) => self
+                            .store_handler
+                            .get_key_matching_in_store(&store, keys, &condition),
+                        None => self.store_handler.get_key_in_store(&store, keys),
+                    }
                     .map(ServerResponse::Get)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from)
+                }
                 DBQuery::GetPred { store, condition } => self
                     .store_handler
-                    .get_pred_in_store(&store, &condition)
+                    .get_pred_in_store(&store, &condition, deadline)
                     .map(ServerResponse::Get)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
+                DBQuery::CountPred {
+                    store,
+                    condition,
+                    exact,
+                } => self
+                    .store_handler
+                    .count_pred_in_store(&store, &condition, exact, deadline)
+                    .map(ServerResponse::Count)
+                    .map_err(ErrorResponse::from),
                 DBQuery::GetSimN {
                     store,
                     search_input,
                     closest_n,
                     algorithm,
                     condition,
+                    min_score,
+                    max_distance,
+                    normalize_scores,
+                    group_by,
+                    group_size,
+                    additional_search_inputs,
+                    fusion,
+                    recency_boost,
+                    filter_strategy,
+                    search_terms,
+                    exclude_keys,
+                    unordered_ties,
+                } => self
+                    .store_handler
+                    .get_sim_in_store(
+                        &store,
+                        search_input,
+                        closest_n,
+                        algorithm,
+                        condition,
+                        GetSimNOptions {
+                            min_score,
+                            max_distance,
+                            normalize_scores,
+                            group_by,
+                            group_size,
+                            additional_search_inputs,
+                            fusion,
+                            recency_boost,
+                            filter_strategy,
+                            search_terms,
+                            exclude_keys,
+                            unordered_ties,
+                            deadline,
+                            untracked: false,
+                        },
+                    )
+                    .map(ServerResponse::GetSimN)
+                    .map_err(ErrorResponse::from),
+                DBQuery::GetSimNByKey {
+                    store,
+                    key_id,
+                    closest_n,
+                    algorithm,
+                    condition,
                 } => self
                     .store_handler
-                    .get_sim_in_store(&store, search_input, closest_n, algorithm, condition)
+                    .get_sim_by_key_in_store(
+                        &store,
+                        &key_id,
+                        closest_n,
+                        algorithm,
+                        condition,
+                        GetSimNOptions {
+                            deadline,
+                            ..Default::default()
+                        },
+                    )
                     .map(ServerResponse::GetSimN)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
+                DBQuery::BatchGetSimN {
+                    store,
+                    search_inputs,
+                    closest_n,
+                    algorithm,
+                    condition,
+                } => self
+                    .store_handler
+                    .batch_get_sim_in_store(
+                        &store,
+                        search_inputs,
+                        closest_n,
+                        algorithm,
+                        condition,
+                        deadline,
+                    )
+                    .map(ServerResponse::BatchGetSimN)
+                    .map_err(ErrorResponse::from),
+                DBQuery::ListEntries {
+                    store,
+                    limit,
+                    cursor,
+                    include_vectors,
+                } => {
+                    // clients held to a filter only page through the entries it allows
+                    let condition =
+                        self.filter_handler
+                            .restrict_optional(&self.connected_client, &store, None);
+                    self.store_handler
+                        .list_entries(
+                            &store,
+                            limit,
+                            cursor.as_deref(),
+                            include_vectors,
+                            condition.as_ref(),
+                        )
+                        .map(ServerResponse::EntryList)
+                        .map_err(ErrorResponse::from)
+                }
+                // a delete by key could not carry the filter to mirrors, so clients held to one
+                // delete by predicate instead
+                DBQuery::DelKey { store, .. }
+                    if self
+                        .filter_handler
+                        .restrict_optional(&self.connected_client, &store, None)
+                        .is_some() =>
+                {
+                    Err(ServerError::FilteredKeyDelete(store).into())
+                }
                 DBQuery::DelKey { store, keys } => self
                     .store_handler
                     .del_key_in_store(&store, keys)
                     .map(ServerResponse::Del)
-                    .map_err(|e| format!("{e}")),
+                    .map_err(ErrorResponse::from),
                 DBQuery::DelPred { store, condition } => self
                     .store_handler
                     .del_pred_in_store(&store, &condition)
                     .map(ServerResponse::Del)
-                    .map_err(|e| format!("{e}")),
-            })
+                    .map_err(ErrorResponse::from),
+                DBQuery::DelPredAsync { store, condition } => {
+                    match self.store_handler.get_pred_ids_in_store(&store, &condition) {
+                        Ok(ids) => {
+                            let job = self.job_handler.register(JobKind::DelPred, ids.len());
+                            let job_id = job.id();
+                            self.task_manager
+                                .spawn_task_loop(DelPredTask::new(
+                                    job,
+                                    self.store_handler.clone(),
+                                    store,
+                                    ids,
+                                ))
+                                .await;
+                            Ok(ServerResponse::JobStarted(job_id))
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                DBQuery::ExportStoreParquet { store, path } => self
+                    .export_store_parquet(&store, path)
+                    .await
+                    .map(ServerResponse::JobStarted)
+                    .map_err(ErrorResponse::from),
+                DBQuery::GetJob { job_id } => self
+                    .job_handler
+                    .get(job_id)
+                    .map(ServerResponse::JobStatus)
+                    .ok_or_else(|| ServerError::JobNotFound(job_id).into()),
+                DBQuery::CancelJob { job_id } => self
+                    .job_handler
+                    .cancel(job_id)
+                    .map(ServerResponse::JobStatus)
+                    .ok_or_else(|| ServerError::JobNotFound(job_id).into()),
+                DBQuery::ListJobs => Ok(ServerResponse::JobList(self.job_handler.list())),
+                DBQuery::ListQuotas => {
+                    Ok(ServerResponse::QuotaList(self.store_handler.list_quotas()))
+                }
+                DBQuery::SetQuota { namespace, quota } => {
+                    self.store_handler.set_quota(namespace, quota);
+                    Ok(ServerResponse::Unit)
+                }
+                DBQuery::ApplyManifest { stores, dry_run } => self
+                    .store_handler
+                    .apply_manifest(stores, dry_run)
+                    .map(ServerResponse::ManifestChanges)
+                    .map_err(ErrorResponse::from),
+                DBQuery::DiffManifest { stores } => self
+                    .store_handler
+                    .diff_manifest(&stores)
+                    .map(ServerResponse::ManifestDrift)
+                    .map_err(ErrorResponse::from),
+                DBQuery::MirrorStatus => self
+                    .mirror_log
+                    .as_ref()
+                    .map(|mirror_log| ServerResponse::MirrorStatus(mirror_log.status()))
+                    .ok_or_else(|| ServerError::MirrorNotConfigured.into()),
+                DBQuery::ControlMirror { action } => self
+                    .mirror_log
+                    .as_ref()
+                    .map(|mirror_log| ServerResponse::MirrorStatus(mirror_log.control(action)))
+                    .ok_or_else(|| ServerError::MirrorNotConfigured.into()),
+            };
+            if let (Some(mirror_log), Some(query), Ok(_)) = (&self.mirror_log, mirrored, &response)
+            {
+                mirror_log.record(query);
+            }
+            if let (Some(audit_log), Some(operation)) = (&self.audit_log, audited) {
+                audit_log.record(
+                    &self.connected_client,
+                    operation,
+                    response.as_ref().map(|_| ()),
+                );
+            }
+            log_query(query_type, store.as_ref(), started, &response);
+            if matches!(&response, Err(err) if err.code == ErrorCode::DeadlineExceeded) {
+                self.deadlines_exceeded.fetch_add(1, Ordering::Relaxed);
+            }
+            let failed = response.is_err();
+            result.push(response);
+            if failed && error_policy == ErrorPolicy::FailFast {
+                break;
+            }
         }
         result
     }
 }
 
+/// The operation a query is recorded as in the audit log, reads are not recorded
+fn audit_operation(query: &DBQuery) -> Option<AuditOperation> {
+    let operation = match query {
+        DBQuery::CreateStore { store, .. } => AuditOperation::admin("CREATESTORE", [store.clone()]),
+        DBQuery::DropStore { store, .. } => AuditOperation::admin("DROPSTORE", [store.clone()]),
+        DBQuery::RestoreStore { store } => AuditOperation::admin("RESTORESTORE", [store.clone()]),
+        DBQuery::CreatePredIndex { store, .. } => {
+            AuditOperation::admin("CREATEPREDINDEX", [store.clone()])
+        }
+        DBQuery::CreateNonLinearAlgorithmIndex { store, .. } => {
+            AuditOperation::admin("CREATENONLINEARALGORITHMINDEX", [store.clone()])
+        }
+        DBQuery::DropPredIndex { store, .. } => {
+            AuditOperation::admin("DROPPREDINDEX", [store.clone()])
+        }
+        DBQuery::DropNonLinearAlgorithmIndex { store, .. } => {
+            AuditOperation::admin("DROPNONLINEARALGORITHMINDEX", [store.clone()])
+        }
+        DBQuery::CompactStore { store } => AuditOperation::admin("COMPACTSTORE", [store.clone()]),
+        DBQuery::CheckStore { store, .. } => AuditOperation::admin("CHECKSTORE", [store.clone()]),
+        DBQuery::ScrubStore { store, .. } => AuditOperation::admin("SCRUBSTORE", [store.clone()]),
+        DBQuery::BenchmarkStore { store, .. } => {
+            AuditOperation::admin("BENCHMARKSTORE", [store.clone()])
+        }
+        DBQuery::ExportStoreParquet { store, .. } => {
+            AuditOperation::admin("EXPORTSTOREPARQUET", [store.clone()])
+        }
+        DBQuery::SetBulkWrite { store, .. } => {
+            AuditOperation::admin("SETBULKWRITE", [store.clone()])
+        }
+        DBQuery::Warmup { stores } => AuditOperation::admin("WARMUP", stores.iter().cloned()),
+        DBQuery::ApplyManifest { stores, .. } => AuditOperation::admin(
+            "APPLYMANIFEST",
+            stores.iter().map(|manifest| manifest.store.clone()),
+        ),
+        DBQuery::CancelJob { .. } => AuditOperation::admin("CANCELJOB", []),
+        DBQuery::SetQuota { .. } => AuditOperation::admin("SETQUOTA", []),
+        DBQuery::ControlMirror { .. } => AuditOperation::admin("CONTROLMIRROR", []),
+        DBQuery::ReloadConfig => AuditOperation::admin("RELOADCONFIG", []),
+        DBQuery::Set { store, .. } => AuditOperation::write("SET", store.clone()),
+        DBQuery::DelKey { store, .. } => AuditOperation::write("DELKEY", store.clone()),
+        DBQuery::DelPred { store, .. } => AuditOperation::write("DELPRED", store.clone()),
+        DBQuery::DelPredAsync { store, .. } => AuditOperation::write("DELPREDASYNC", store.clone()),
+        DBQuery::GetKey { .. }
+        | DBQuery::GetPred { .. }
+        | DBQuery::CountPred { .. }
+        | DBQuery::GetSimN { .. }
+        | DBQuery::GetSimNByKey { .. }
+        | DBQuery::BatchGetSimN { .. }
+        | DBQuery::ListEntries { .. }
+        | DBQuery::GetJob { .. }
+        | DBQuery::ListJobs
+        | DBQuery::ListQuotas
+        | DBQuery::MirrorStatus
+        | DBQuery::DiffManifest { .. }
+        | DBQuery::InfoServer
+        | DBQuery::GetMemoryBreakdown
+        | DBQuery::ListStores
+        | DBQuery::ListTrashedStores
+        | DBQuery::DescribeStore { .. }
+        | DBQuery::ListClients
+        | DBQuery::Ping => return None,
+    };
+    Some(operation)
+}
+
+/// Whether a query changes the stores of the server, which makes it a write its mirror has to
+/// apply as well
+fn is_mirrored(query: &DBQuery) -> bool {
+    match query {
+        DBQuery::CreateStore { .. }
+        | DBQuery::DropStore { .. }
+        | DBQuery::RestoreStore { .. }
+        | DBQuery::CreatePredIndex { .. }
+        | DBQuery::CreateNonLinearAlgorithmIndex { .. }
+        | DBQuery::DropPredIndex { .. }
+        | DBQuery::DropNonLinearAlgorithmIndex { .. }
+        | DBQuery::SetBulkWrite { .. }
+        | DBQuery::Set { .. }
+        | DBQuery::DelKey { .. }
+        | DBQuery::DelPred { .. }
+        | DBQuery::DelPredAsync { .. } => true,
+        DBQuery::ApplyManifest { dry_run, .. } => !dry_run,
+        DBQuery::ScrubStore { delete, .. } => *delete,
+        DBQuery::CompactStore { .. }
+        | DBQuery::CheckStore { .. }
+        | DBQuery::BenchmarkStore { .. }
+        | DBQuery::ExportStoreParquet { .. }
+        | DBQuery::Warmup { .. }
+        | DBQuery::CancelJob { .. }
+        | DBQuery::SetQuota { .. }
+        | DBQuery::ControlMirror { .. }
+        | DBQuery::ReloadConfig
+        | DBQuery::MirrorStatus
+        | DBQuery::DiffManifest { .. }
+        | DBQuery::GetKey { .. }
+        | DBQuery::GetPred { .. }
+        | DBQuery::CountPred { .. }
+        | DBQuery::GetSimN { .. }
+        | DBQuery::GetSimNByKey { .. }
+        | DBQuery::BatchGetSimN { .. }
+        | DBQuery::ListEntries { .. }
+        | DBQuery::GetJob { .. }
+        | DBQuery::ListJobs
+        | DBQuery::ListQuotas
+        | DBQuery::InfoServer
+        | DBQuery::GetMemoryBreakdown
+        | DBQuery::ListStores
+        | DBQuery::ListTrashedStores
+        | DBQuery::DescribeStore { .. }
+        | DBQuery::ListClients
+        | DBQuery::Ping => false,
+    }
+}
+
+/// A write as the mirror applies it. Writes may reach the mirror more than once, such as when
+/// a store copied over during a resync already holds them, so they are made not to fail when
+/// their store or index already exists or is already gone
+fn mirrored_query(query: DBQuery) -> DBQuery {
+    match query {
+        DBQuery::CreateStore {
+            store,
+            dimension,
+            create_predicates,
+            non_linear_indices,
+            timestamp_key,
+            storage_tier,
+            infer_dimension,
+            key_element_type,
+            normalization,
+            index_seed,
+            non_finite_vectors,
+            eviction,
+            ..
+        } => DBQuery::CreateStore {
+            store,
+            dimension,
+            create_predicates,
+            non_linear_indices,
+            error_if_exists: false,
+            timestamp_key,
+            storage_tier,
+            infer_dimension,
+            key_element_type,
+            normalization,
+            index_seed,
+            non_finite_vectors,
+            eviction,
+        },
+        DBQuery::DropStore { store, .. } => DBQuery::DropStore {
+            store,
+            error_if_not_exists: false,
+        },
+        DBQuery::DropPredIndex {
+            store, predicates, ..
+        } => DBQuery::DropPredIndex {
+            store,
+            predicates,
+            error_if_not_exists: false,
+        },
+        DBQuery::DropNonLinearAlgorithmIndex {
+            store,
+            non_linear_indices,
+            ..
+        } => DBQuery::DropNonLinearAlgorithmIndex {
+            store,
+            non_linear_indices,
+            error_if_not_exists: false,
+        },
+        DBQuery::DelPredAsync { store, condition } => DBQuery::DelPred { store, condition },
+        query => query,
+    }
+}
+
 impl ServerTask {
+    /// Narrows the conditions of predicate gets and counts, similarity searches and predicate
+    /// deletes to the entries the client is allowed. Done before a query is audited or mirrored
+    /// so both record the condition that was applied
+    fn filter_query(&self, mut query: DBQuery) -> DBQuery {
+        let client = &self.connected_client;
+        match &mut query {
+            DBQuery::GetPred { store, condition }
+            | DBQuery::CountPred {
+                store, condition, ..
+            }
+            | DBQuery::DelPred { store, condition }
+            | DBQuery::DelPredAsync { store, condition } => {
+                *condition = self
+                    .filter_handler
+                    .restrict(client, store, condition.clone());
+            }
+            DBQuery::GetSimN {
+                store, condition, ..
+            }
+            | DBQuery::GetSimNByKey {
+                store, condition, ..
+            }
+            | DBQuery::BatchGetSimN {
+                store, condition, ..
+            } => {
+                *condition = self
+                    .filter_handler
+                    .restrict_optional(client, store, condition.take());
+            }
+            _ => {}
+        }
+        query
+    }
+
+    /// Rejects writes to a read only mirror from anyone but the server it mirrors
+    fn check_writable(&self, query: &DBQuery) -> Result<(), ErrorResponse> {
+        let Some(source) = self.mirror_source else {
+            return Ok(());
+        };
+        let from_source = self
+            .connected_client
+            .address
+            .parse::<SocketAddr>()
+            .is_ok_and(|address| address.ip() == source);
+        if is_mirrored(query) && !from_source {
+            return Err(ServerError::ReadOnlyMirror(source).into());
+        }
+        Ok(())
+    }
+
+    /// Starts a job writing the entries of a store to a Parquet file at a path within the export
+    /// location
+    async fn export_store_parquet(
+        &self,
+        store: &StoreName,
+        path: String,
+    ) -> Result<u64, ServerError> {
+        let location = self
+            .export_location
+            .as_ref()
+            .ok_or(ServerError::ExportNotConfigured)?;
+        let relative = Path::new(&path);
+        if relative.as_os_str().is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(ServerError::InvalidExportPath(path));
+        }
+        // clients held to a filter only export the entries it allows
+        let condition = self
+            .filter_handler
+            .restrict_optional(&self.connected_client, store, None);
+        let (dimension, entries) = self
+            .store_handler
+            .export_entries(store, condition.as_ref())?;
+        let total = entries.len();
+        let export = ParquetExport::create(location.join(relative), dimension, entries)?;
+        let job = self.job_handler.register(JobKind::ExportStore, total);
+        let job_id = job.id();
+        self.task_manager
+            .spawn_task_loop(ExportStoreTask::new(job, export))
+            .await;
+        Ok(job_id)
+    }
+
+    /// Checks the indices of a store against its entries, starting a job that repairs those found
+    /// to disagree when asked to
+    async fn check_store(
+        &self,
+        store: &StoreName,
+        repair: bool,
+    ) -> Result<StoreCheck, ServerError> {
+        let mut check = self.store_handler.check_store(store)?;
+        let inconsistent: Vec<_> = check
+            .indices
+            .iter()
+            .filter(|index| !index.is_consistent())
+            .map(|index| index.index.clone())
+            .collect();
+        if repair && !inconsistent.is_empty() {
+            let job = self
+                .job_handler
+                .register(JobKind::RepairStore, inconsistent.len());
+            check.repair_job = Some(job.id());
+            self.task_manager
+                .spawn_task_loop(RepairStoreTask::new(
+                    job,
+                    self.store_handler.clone(),
+                    store.clone(),
+                    inconsistent,
+                ))
+                .await;
+        }
+        Ok(check)
+    }
+
+    /// Benchmarks a store off the async runtime, as it searches the store for seconds
+    async fn benchmark_store(
+        &self,
+        store: StoreName,
+        sample_queries: NonZeroUsize,
+        concurrency: NonZeroUsize,
+        duration: u64,
+        deadline: Deadline,
+    ) -> Result<StoreBenchmark, ServerError> {
+        let store_handler = self.store_handler.clone();
+        tokio::task::spawn_blocking(move || {
+            store_handler.benchmark_store(
+                &store,
+                sample_queries,
+                concurrency,
+                Duration::from_millis(duration),
+                deadline,
+            )
+        })
+        .await
+        .expect("Benchmark of store panicked")
+    }
+
+    /// Approximate bytes a query needs while it runs: the entries a set holds or the entries a
+    /// get or similarity search returns
+    fn estimate_memory(&self, query: &DBQuery) -> usize {
+        match query {
+            DBQuery::Set { inputs, .. } => serialized_size(inputs).unwrap_or_default() as usize,
+            DBQuery::GetKey { store, keys } => self.store_handler.entries_memory(store, keys.len()),
+            DBQuery::GetSimN {
+                store, closest_n, ..
+            }
+            | DBQuery::GetSimNByKey {
+                store, closest_n, ..
+            } => self.store_handler.entries_memory(store, closest_n.get()),
+            DBQuery::BatchGetSimN {
+                store,
+                search_inputs,
+                closest_n,
+                ..
+            } => self
+                .store_handler
+                .entries_memory(store, closest_n.get().saturating_mul(search_inputs.len())),
+            DBQuery::ListEntries { store, limit, .. } => {
+                self.store_handler.entries_memory(store, limit.get())
+            }
+            _ => 0,
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     fn server_info(&self) -> ServerInfo {
         ServerInfo {
             address: format!("{}", self.server_addr),
             version: *VERSION,
+            min_client_version: *MIN_CLIENT_VERSION,
+            max_client_version: *VERSION,
             r#type: ahnlich_types::ServerType::Database,
             limit: GLOBAL_ALLOCATOR.limit(),
             remaining: GLOBAL_ALLOCATOR.remaining(),
+            request_limits: self.limit_handler.client(&self.connected_client),
+            in_flight_memory: self.memory_admission.in_flight(),
+            deadlines_exceeded: self.deadlines_exceeded.load(Ordering::Relaxed),
+            priorities: self.scheduler.stats(),
+            connections: self.client_handler.stats(),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn memory_breakdown(&self) -> MemoryBreakdown {
+        MemoryBreakdown {
+            limit: GLOBAL_ALLOCATOR.limit(),
+            remaining: GLOBAL_ALLOCATOR.remaining(),
+            connections: self.client_handler.memory(),
+            in_flight_memory: self.memory_admission.in_flight(),
+            ..self.store_handler.memory_breakdown()
+        }
+    }
+
+    /// Checks a set against the limits of the client and store
+    fn check_set_limits(
+        &self,
+        store: &StoreName,
+        inputs: &[(StoreKey, StoreValue)],
+    ) -> Result<(), ServerError> {
+        let limits = self.limit_handler.request(&self.connected_client, store);
+        if let Some(limit) = limits.batch_size.filter(|limit| inputs.len() > *limit) {
+            return Err(ServerError::BatchTooLarge {
+                store: store.clone(),
+                len: inputs.len(),
+                limit,
+            });
+        }
+        // inputs that cannot be sized would have failed to deserialize in the first place
+        let size = serialized_size(inputs).unwrap_or_default() as usize;
+        if size > limits.message_size {
+            return Err(ServerError::RequestTooLarge {
+                store: store.clone(),
+                size,
+                limit: limits.message_size,
+            });
         }
+        Ok(())
     }
 }
 
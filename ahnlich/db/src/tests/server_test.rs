@@ -1,43 +1,96 @@
 use crate::cli::ServerConfig;
+use crate::engine::store::StoreKeyId;
+use crate::errors::ServerError;
 use crate::server::handler::Server;
+use ahnlich_client_rs::builders::db::{
+    BenchmarkStoreParams, ControlMirrorParams, CreateStoreParams, DelKeyParams, DropStoreParams,
+    SetParams,
+};
+use ahnlich_client_rs::db::DbClient;
+use ahnlich_client_rs::error::AhnlichError;
 use ahnlich_types::bincode::BinCodeSerAndDeser;
-use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::client::{ConnectedClient, ConnectionStats};
 use ahnlich_types::db::DBQuery;
+use ahnlich_types::db::EntryPage;
+use ahnlich_types::db::ListedEntry;
+use ahnlich_types::db::ManifestChange;
+use ahnlich_types::db::ManifestDrift;
+use ahnlich_types::db::MirrorAction;
+use ahnlich_types::db::MirrorState;
+use ahnlich_types::db::SearchPath;
 use ahnlich_types::db::ServerDBQuery;
 use ahnlich_types::db::ServerInfo;
 use ahnlich_types::db::ServerResponse;
 use ahnlich_types::db::ServerResult;
+use ahnlich_types::db::SettingDrift;
 use ahnlich_types::db::StoreInfo;
+use ahnlich_types::db::StoreManifest;
 use ahnlich_types::db::StoreUpsert;
+use ahnlich_types::error::ErrorCode;
+use ahnlich_types::error::ErrorResponse;
+use ahnlich_types::jobs::JobKind;
+use ahnlich_types::jobs::JobState;
+use ahnlich_types::jobs::JobStatus;
+use ahnlich_types::keyval::KeyElementType;
+use ahnlich_types::keyval::NonFiniteVectors;
+use ahnlich_types::keyval::StorageTier;
 use ahnlich_types::keyval::StoreKey;
 use ahnlich_types::keyval::StoreName;
+use ahnlich_types::keyval::VectorNormalization;
 use ahnlich_types::metadata::MetadataKey;
 use ahnlich_types::metadata::MetadataValue;
 use ahnlich_types::predicate::Predicate;
 use ahnlich_types::predicate::PredicateCondition;
 use ahnlich_types::similarity::Algorithm;
+use ahnlich_types::similarity::FilterStrategy;
+use ahnlich_types::similarity::FusionStrategy;
 use ahnlich_types::similarity::NonLinearAlgorithm;
+use ahnlich_types::similarity::RecencyBoost;
 use ahnlich_types::similarity::Similarity;
+use ahnlich_types::version::Version;
+use ahnlich_types::version::MIN_CLIENT_VERSION;
+use ahnlich_types::version::VERSION;
+use ahnlich_types::ConfigReload;
+use ahnlich_types::Priority;
+use ahnlich_types::RequestLimits;
 use futures::future::join_all;
 use ndarray::array;
 use once_cell::sync::Lazy;
 use pretty_assertions::assert_eq;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::num::NonZeroU64;
 use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::time::{timeout, Duration};
+use utils::cli::ConfigError;
+use utils::client::{ConnectionRejected, CONNECTION_BUFFER_SIZE};
 use utils::server::AhnlichServerUtils;
 
+const DEFAULT_REQUEST_LIMITS: RequestLimits = RequestLimits {
+    message_size: 1_048_576,
+    batch_size: None,
+};
+
 static CONFIG: Lazy<ServerConfig> = Lazy::new(|| ServerConfig::default().os_select_port());
 
 static CONFIG_WITH_MAX_CLIENTS: Lazy<ServerConfig> =
     Lazy::new(|| ServerConfig::default().os_select_port().maximum_clients(2));
 
+static CONFIG_WITH_MAX_CLIENTS_PER_HOST: Lazy<ServerConfig> = Lazy::new(|| {
+    ServerConfig::default()
+        .os_select_port()
+        .maximum_clients_per_host(1)
+});
+
+static CONFIG_WITHOUT_JOB_TTL: Lazy<ServerConfig> =
+    Lazy::new(|| ServerConfig::default().os_select_port().job_ttl(0));
+
 static PERSISTENCE_FILE: Lazy<PathBuf> =
     Lazy::new(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("ahnlich.dat"));
 
@@ -63,8 +116,16 @@ async fn test_maximum_client_restriction_works() {
     let first_stream = TcpStream::connect(address).await.unwrap();
     let first_stream_addr = first_stream.local_addr().unwrap();
     let other_stream = TcpStream::connect(address).await.unwrap();
-    let mut third_stream_fail = TcpStream::connect(address).await.unwrap();
-    assert_eq!(third_stream_fail.read(&mut []).await.unwrap(), 0);
+    let third_stream_fail = TcpStream::connect(address).await.unwrap();
+    let mut rejected = ServerResult::with_capacity(1);
+    rejected.push(Err(ConnectionRejected::MaximumClients(2).into()));
+    let mut reader = BufReader::new(third_stream_fail);
+    query_server_assert_result(
+        &mut reader,
+        ServerDBQuery::from_queries(&[DBQuery::Ping]),
+        rejected,
+    )
+    .await;
     let message = ServerDBQuery::from_queries(&[DBQuery::ListClients]);
     let expected_response = HashSet::from_iter([
         ConnectedClient {
@@ -83,6 +144,204 @@ async fn test_maximum_client_restriction_works() {
     cancellation_token.cancel();
 }
 
+#[tokio::test]
+async fn test_maximum_client_per_host_restriction_works() {
+    let server = Server::new(&CONFIG_WITH_MAX_CLIENTS_PER_HOST)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let first_stream = TcpStream::connect(address).await.unwrap();
+    let second_stream_fail = TcpStream::connect(address).await.unwrap();
+    let mut rejected = ServerResult::with_capacity(1);
+    rejected.push(Err(ConnectionRejected::MaximumClientsPerHost {
+        host: address.ip().to_string(),
+        limit: 1,
+    }
+    .into()));
+    let mut reader = BufReader::new(second_stream_fail);
+    query_server_assert_result(
+        &mut reader,
+        ServerDBQuery::from_queries(&[DBQuery::Ping]),
+        rejected,
+    )
+    .await;
+
+    // the host may connect again once its first client hangs up
+    drop(first_stream);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    match client.info_server(None).await.unwrap() {
+        ServerResponse::InfoServer(info) => assert_eq!(
+            info.connections,
+            ConnectionStats {
+                clients: 1,
+                maximum_clients: 1000,
+                maximum_clients_per_host: Some(1),
+                rejected: 1,
+            }
+        ),
+        response => panic!("Unexpected response {response:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_unix_socket_and_in_memory_clients() {
+    let unix_socket = std::env::temp_dir().join("ahnlich_test_db.sock");
+    let config = ServerConfig::default()
+        .os_select_port()
+        .unix_socket(unix_socket.clone());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    assert_eq!(server.unix_socket(), Some(unix_socket.as_path()));
+    // a second server cannot take the socket of a running one
+    assert!(Server::new(
+        &ServerConfig::default()
+            .os_select_port()
+            .unix_socket(unix_socket.clone())
+    )
+    .await
+    .is_err());
+    let connector = server.memory_connector();
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let unix_client = DbClient::new_unix(unix_socket).await.unwrap();
+    let memory_client = DbClient::new_in_memory(Arc::new(move || connector.connect()))
+        .await
+        .unwrap();
+    unix_client
+        .create_store(
+            CreateStoreParams::builder()
+                .store("Main".to_string())
+                .dimension(3)
+                .build(),
+        )
+        .await
+        .unwrap();
+    match memory_client.list_stores(None).await.unwrap() {
+        ServerResponse::StoreList(stores) => {
+            assert_eq!(stores.len(), 1);
+            assert!(stores
+                .iter()
+                .all(|store| store.name == StoreName("Main".to_string())));
+        }
+        response => panic!("Unexpected response {response:?}"),
+    }
+    match unix_client.list_clients(None).await.unwrap() {
+        ServerResponse::ClientList(clients) => assert_eq!(
+            clients
+                .iter()
+                .map(|client| client.host())
+                .collect::<HashSet<_>>(),
+            HashSet::from_iter(["unix".to_string(), "memory".to_string()])
+        ),
+        response => panic!("Unexpected response {response:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_server_from_config_builder() {
+    assert_eq!(
+        ServerConfig::builder()
+            .port(0)
+            .compaction(1.5, 1000)
+            .build()
+            .unwrap_err(),
+        ConfigError::invalid(
+            "compaction_threshold",
+            "Compaction threshold must be above 0 and at most 1"
+        )
+    );
+    assert_eq!(
+        ServerConfig::builder()
+            .port(0)
+            .arrow_export()
+            .build()
+            .unwrap_err(),
+        ConfigError::Missing {
+            setting: "enable_arrow_export",
+            requires: "enable_http_gateway",
+        }
+    );
+    assert!(matches!(
+        ServerConfig::builder().port(0).maximum_clients(0).build(),
+        Err(ConfigError::Invalid {
+            setting: "maximum_clients",
+            ..
+        })
+    ));
+
+    let config = ServerConfig::builder()
+        .port(0)
+        .maximum_clients(5)
+        .message_size(2_097_152)
+        .build()
+        .unwrap();
+    assert_eq!(config.port, 0);
+    assert_eq!(config.common.maximum_clients, 5);
+    // settings left out keep the defaults of the command line
+    assert_eq!(config.http_port, ServerConfig::default().http_port);
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let connector = server.memory_connector();
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new_in_memory(Arc::new(move || connector.connect()))
+        .await
+        .unwrap();
+    assert_eq!(client.ping(None).await.unwrap(), ServerResponse::Pong);
+}
+
+#[tokio::test]
+async fn test_reload_config() {
+    let config_file = std::env::temp_dir().join("ahnlich_test_reload_config.toml");
+    std::fs::write(&config_file, "maximum-clients = 10").unwrap();
+    let config = ServerConfig::default()
+        .os_select_port()
+        .config_file(config_file.clone());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    let maximum_clients = |response| match response {
+        ServerResponse::InfoServer(info) => info.connections.maximum_clients,
+        response => panic!("Unexpected response {response:?}"),
+    };
+    assert_eq!(maximum_clients(client.info_server(None).await.unwrap()), 10);
+
+    std::fs::write(&config_file, "maximum-clients = 20\nport = 1").unwrap();
+    assert_eq!(
+        client.reload_config(None).await.unwrap(),
+        ServerResponse::ConfigReloaded(ConfigReload {
+            changed: vec!["maximum-clients".to_string()],
+            requires_restart: vec!["port".to_string()],
+        })
+    );
+    assert_eq!(maximum_clients(client.info_server(None).await.unwrap()), 20);
+
+    // nothing is applied from a file with an unknown setting
+    std::fs::write(&config_file, "maximum-clients = 30\nmaximum-client = 30").unwrap();
+    assert!(client.reload_config(None).await.is_err());
+    assert_eq!(maximum_clients(client.info_server(None).await.unwrap()), 20);
+    let _ = std::fs::remove_file(config_file);
+}
+
 #[tokio::test]
 async fn test_server_client_info() {
     let server = Server::new(&CONFIG)
@@ -124,6 +383,199 @@ async fn test_server_client_info() {
     query_server_assert_result(&mut reader, message, expected.clone()).await;
 }
 
+#[tokio::test]
+async fn test_incompatible_client_version() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let incompatible_error = |client: Version| {
+        ErrorResponse::new(
+            ErrorCode::IncompatibleVersion,
+            format!(
+                "Incompatible versions, Server: {}, Client {client}",
+                *VERSION
+            ),
+        )
+        .with_metadata("min_client_version", *MIN_CLIENT_VERSION)
+        .with_metadata("max_client_version", *VERSION)
+    };
+    // clients newer than the server, on another major version or from before the layout of
+    // queries last changed are rejected
+    for client in [
+        Version {
+            major: 0,
+            minor: 0,
+            patch: 0,
+        },
+        Version {
+            minor: VERSION.minor + 1,
+            ..*VERSION
+        },
+        Version {
+            patch: VERSION.patch + 1,
+            ..*VERSION
+        },
+        Version {
+            major: VERSION.major + 1,
+            ..*VERSION
+        },
+    ] {
+        let message = ServerDBQuery::from_queries(&[DBQuery::Ping, DBQuery::ListStores]);
+        let mut expected = ServerResult::with_capacity(1);
+        expected.push(Err(incompatible_error(client)));
+        query_server_assert_result_with_version(&mut reader, message, client, expected).await;
+    }
+    // the connection remains usable for a supported version
+    let message = ServerDBQuery::from_queries(&[DBQuery::Ping, DBQuery::ListStores]);
+    let mut expected = ServerResult::with_capacity(2);
+    expected.push(Ok(ServerResponse::Pong));
+    expected.push(Ok(ServerResponse::StoreList(HashSet::new())));
+    query_server_assert_result_with_version(&mut reader, message, *MIN_CLIENT_VERSION, expected)
+        .await;
+}
+
+#[tokio::test]
+async fn test_http_gateway() {
+    let config = ServerConfig::default()
+        .os_select_port()
+        .enable_http_gateway();
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.http_addr().expect("Http gateway not enabled");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let create_store = r#"{"store": "Main", "dimension": 2, "create_predicates": ["brand"]}"#;
+    let (status, body) = http_request(address, "POST", "/stores", create_store).await;
+    assert_eq!((status, body), (200, serde_json::json!("Unit")));
+    let (status, body) = http_request(address, "POST", "/stores", create_store).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["code"], "StoreAlreadyExists");
+
+    let set = r#"{"inputs": [
+        {"key": [1.0, 0.0], "value": {"brand": {"RawString": "nike"}}},
+        {"key": [0.0, 1.0], "value": {"brand": {"RawString": "puma"}}}
+    ]}"#;
+    let (status, body) = http_request(address, "POST", "/stores/Main/entries", set).await;
+    assert_eq!(
+        (status, body),
+        (
+            200,
+            serde_json::json!({"Set": {"inserted": 2, "updated": 0}})
+        )
+    );
+
+    let get_sim_n = r#"{"search_input": [0.9, 0.1], "closest_n": 1}"#;
+    let (status, body) = http_request(address, "POST", "/stores/Main/query", get_sim_n).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["GetSimN"][0][1]["brand"]["RawString"], "nike");
+
+    let (status, body) = http_request(address, "POST", "/stores/Other/query", get_sim_n).await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], "StoreNotFound");
+
+    let (status, body) = http_request(address, "POST", "/stores/Main/query", "[]").await;
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "InvalidArgument");
+
+    let (status, body) =
+        http_request(address, "POST", "/query", r#"["Ping", "ListClients"]"#).await;
+    assert_eq!(status, 200);
+    assert_eq!(body[0], serde_json::json!({"Ok": "Pong"}));
+    assert!(body[1]["Ok"]["ClientList"].is_array());
+
+    let (status, body) = http_request(address, "DELETE", "/stores/Main", "").await;
+    assert_eq!((status, body), (200, serde_json::json!({"Del": 1})));
+}
+
+#[tokio::test]
+async fn test_arrow_export() {
+    let config = ServerConfig::default()
+        .os_select_port()
+        .enable_http_gateway()
+        .enable_arrow_export();
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.http_addr().expect("Http gateway not enabled");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let create_store = r#"{"store": "Main", "dimension": 2, "create_predicates": ["brand"]}"#;
+    http_request(address, "POST", "/stores", create_store).await;
+    let set = r#"{"inputs": [
+        {"key": [1.0, 0.0], "value": {"brand": {"RawString": "nike"}}},
+        {"key": [0.0, 1.0], "value": {"brand": {"RawString": "puma"}}}
+    ]}"#;
+    http_request(address, "POST", "/stores/Main/entries", set).await;
+
+    let export =
+        r#"{"condition": {"Value": {"Equals": {"key": "brand", "value": {"RawString": "puma"}}}}}"#;
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "POST /stores/Main/export HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{export}",
+        export.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    timeout(Duration::from_secs(1), stream.read_to_end(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200"));
+    assert!(head.contains("content-type: application/vnd.apache.arrow.stream"));
+    assert!(body.contains("puma"));
+    assert!(!body.contains("nike"));
+
+    let (status, body) = http_request(address, "POST", "/stores/Other/export", "").await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], "StoreNotFound");
+}
+
+#[tokio::test]
+async fn test_arrow_export_client_filters() {
+    let config = ServerConfig::default()
+        .os_select_port()
+        .enable_http_gateway()
+        .enable_arrow_export()
+        .client_filter("127.0.0.1=Main:tenant=acme".parse().unwrap());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.http_addr().expect("Http gateway not enabled");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let create_store = r#"{"store": "Main", "dimension": 2}"#;
+    http_request(address, "POST", "/stores", create_store).await;
+    // exports would not be held to the filter so they are not served
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(
+            b"POST /stores/Main/export HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    timeout(Duration::from_secs(1), stream.read_to_end(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&response).starts_with("HTTP/1.1 404"));
+}
+
 #[tokio::test]
 async fn test_simple_stores_list() {
     let server = Server::new(&CONFIG)
@@ -158,6 +610,14 @@ async fn test_create_stores() {
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // difference in dimensions don't matter as name is the same so this should error
         DBQuery::CreateStore {
@@ -166,6 +626,14 @@ async fn test_create_stores() {
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // Should not error despite existing
         DBQuery::CreateStore {
@@ -174,18 +642,31 @@ async fn test_create_stores() {
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
             error_if_exists: false,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::ListStores,
     ]);
     let mut expected = ServerResult::with_capacity(4);
     expected.push(Ok(ServerResponse::Unit));
-    expected.push(Err("Store Main already exists".to_string()));
+    expected.push(Err(ServerError::StoreAlreadyExists(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::StoreList(HashSet::from_iter([
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 0,
             size_in_bytes: 1720,
+            dimension: NonZeroUsize::new(3).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     let stream = TcpStream::connect(address).await.unwrap();
@@ -217,6 +698,14 @@ async fn test_del_pred() {
             create_predicates: HashSet::from_iter([MetadataKey::new("planet".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // should not error as it is correct query
         // but should delete nothing as nothing matches predicate
@@ -270,7 +759,11 @@ async fn test_del_pred() {
         DBQuery::ListStores,
     ]);
     let mut expected = ServerResult::with_capacity(9);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ErrorResponse::new(
+        ErrorCode::StoreNotFound,
+        "Store Main not found",
+    )
+    .with_metadata("store", "Main")));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Del(0)));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
@@ -281,7 +774,9 @@ async fn test_del_pred() {
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 2,
-            size_in_bytes: 2144,
+            size_in_bytes: 2168,
+            dimension: NonZeroUsize::new(2).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     expected.push(Ok(ServerResponse::Del(1)));
@@ -298,6 +793,8 @@ async fn test_del_pred() {
             name: StoreName("Main".to_string()),
             len: 0,
             size_in_bytes: 1840,
+            dimension: NonZeroUsize::new(2).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     let stream = TcpStream::connect(address).await.unwrap();
@@ -326,6 +823,14 @@ async fn test_del_key() {
             create_predicates: HashSet::from_iter([MetadataKey::new("role".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // should not error as it is correct dimensions
         // but should delete nothing as nothing exists in the store yet
@@ -354,7 +859,10 @@ async fn test_del_key() {
         DBQuery::ListStores,
     ]);
     let mut expected = ServerResult::with_capacity(8);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Del(0)));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
@@ -365,18 +873,26 @@ async fn test_del_key() {
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 2,
-            size_in_bytes: 1888,
+            size_in_bytes: 1912,
+            dimension: NonZeroUsize::new(4).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
-    expected.push(Err(
-        "Store dimension is [4], input dimension of [3] was specified".to_string(),
-    ));
+    expected.push(Err(ServerError::StoreDimensionMismatch {
+        store: StoreName("Main".to_string()),
+        store_dimension: 4,
+        input_dimension: 3,
+        index: 0,
+    }
+    .into()));
     expected.push(Ok(ServerResponse::Del(1)));
     expected.push(Ok(ServerResponse::StoreList(HashSet::from_iter([
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 1,
-            size_in_bytes: 1816,
+            size_in_bytes: 1828,
+            dimension: NonZeroUsize::new(4).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     let stream = TcpStream::connect(address).await.unwrap();
@@ -406,6 +922,14 @@ async fn test_server_with_persistence() {
             create_predicates: HashSet::from_iter([MetadataKey::new("role".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // should not error as it is correct dimensions
         // but should delete nothing as nothing exists in the store yet
@@ -440,7 +964,10 @@ async fn test_server_with_persistence() {
         DBQuery::ListStores,
     ]);
     let mut expected = ServerResult::with_capacity(8);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Del(0)));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
@@ -451,18 +978,26 @@ async fn test_server_with_persistence() {
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 2,
-            size_in_bytes: 1944,
+            size_in_bytes: 1968,
+            dimension: NonZeroUsize::new(4).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
-    expected.push(Err(
-        "Store dimension is [4], input dimension of [3] was specified".to_string(),
-    ));
+    expected.push(Err(ServerError::StoreDimensionMismatch {
+        store: StoreName("Main".to_string()),
+        store_dimension: 4,
+        input_dimension: 3,
+        index: 0,
+    }
+    .into()));
     expected.push(Ok(ServerResponse::Del(1)));
     expected.push(Ok(ServerResponse::StoreList(HashSet::from_iter([
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 1,
-            size_in_bytes: 1872,
+            size_in_bytes: 1884,
+            dimension: NonZeroUsize::new(4).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     let stream = TcpStream::connect(address).await.unwrap();
@@ -499,6 +1034,14 @@ async fn test_server_with_persistence() {
             create_predicates: HashSet::from_iter([MetadataKey::new("role".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // should not error as store exists
         DBQuery::DelKey {
@@ -512,7 +1055,10 @@ async fn test_server_with_persistence() {
     ]);
 
     let mut expected = ServerResult::with_capacity(3);
-    expected.push(Err("Store Main already exists".to_string()));
+    expected.push(Err(ServerError::StoreAlreadyExists(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Del(0)));
     expected.push(Ok(ServerResponse::Get(vec![(
         StoreKey(array![1.1, 1.2, 1.3, 1.4]),
@@ -550,6 +1096,14 @@ async fn test_set_in_store() {
             create_predicates: HashSet::from_iter([MetadataKey::new("role".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // should not error as it is correct dimensions
         DBQuery::Set {
@@ -578,15 +1132,22 @@ async fn test_set_in_store() {
         DBQuery::ListStores,
     ]);
     let mut expected = ServerResult::with_capacity(6);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 1,
         updated: 0,
     })));
-    expected.push(Err(
-        "Store dimension is [3], input dimension of [1] was specified".to_string(),
-    ));
+    expected.push(Err(ServerError::StoreDimensionMismatch {
+        store: StoreName("Main".to_string()),
+        store_dimension: 3,
+        input_dimension: 1,
+        index: 0,
+    }
+    .into()));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 1,
         updated: 1,
@@ -595,7 +1156,9 @@ async fn test_set_in_store() {
         StoreInfo {
             name: StoreName("Main".to_string()),
             len: 2,
-            size_in_bytes: 2032,
+            size_in_bytes: 2056,
+            dimension: NonZeroUsize::new(3).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     let stream = TcpStream::connect(address).await.unwrap();
@@ -619,6 +1182,14 @@ async fn test_remove_non_linear_indices() {
             create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
             non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::Set {
             store: StoreName("Main".to_string()),
@@ -653,6 +1224,18 @@ async fn test_remove_non_linear_indices() {
             algorithm: Algorithm::KDTree,
             search_input: StoreKey(array![1.1, 2.0, 3.0]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
         // should remove index
         DBQuery::DropNonLinearAlgorithmIndex {
@@ -673,6 +1256,18 @@ async fn test_remove_non_linear_indices() {
             algorithm: Algorithm::KDTree,
             search_input: StoreKey(array![1.1, 2.0, 3.0]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
         DBQuery::CreateNonLinearAlgorithmIndex {
             store: StoreName("Main".to_string()),
@@ -710,12 +1305,14 @@ async fn test_remove_non_linear_indices() {
         ),
     ])));
     expected.push(Ok(ServerResponse::Del(1)));
-    expected.push(Err(
-        "Non linear algorithm KDTree not found in store, create store with support".into(),
-    ));
-    expected.push(Err(
-        "Non linear algorithm KDTree not found in store, create store with support".into(),
-    ));
+    expected.push(Err(ServerError::NonLinearIndexNotFound(
+        NonLinearAlgorithm::KDTree,
+    )
+    .into()));
+    expected.push(Err(ServerError::NonLinearIndexNotFound(
+        NonLinearAlgorithm::KDTree,
+    )
+    .into()));
     expected.push(Ok(ServerResponse::CreateIndex(1)));
     expected.push(Ok(ServerResponse::Del(1)));
     let stream = TcpStream::connect(address).await.unwrap();
@@ -724,7 +1321,7 @@ async fn test_remove_non_linear_indices() {
 }
 
 #[tokio::test]
-async fn test_get_sim_n_non_linear() {
+async fn test_get_sim_n_group_by() {
     let server = Server::new(&CONFIG)
         .await
         .expect("Could not initialize server");
@@ -735,59 +1332,86 @@ async fn test_get_sim_n_non_linear() {
     let message = ServerDBQuery::from_queries(&[
         DBQuery::CreateStore {
             store: StoreName("Main".to_string()),
-            dimension: NonZeroUsize::new(3).unwrap(),
-            create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
             non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::Set {
             store: StoreName("Main".to_string()),
             inputs: vec![
                 (
-                    StoreKey(array![1.2, 1.3, 1.4]),
+                    StoreKey(array![1.0, 0.0]),
                     HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("silver".into()),
+                        MetadataKey::new("product".into()),
+                        MetadataValue::RawString("a".into()),
                     )]),
                 ),
                 (
-                    StoreKey(array![2.0, 2.1, 2.2]),
+                    StoreKey(array![0.0, 2.0]),
                     HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("gold".into()),
+                        MetadataKey::new("product".into()),
+                        MetadataValue::RawString("a".into()),
                     )]),
                 ),
                 (
-                    StoreKey(array![5.0, 5.1, 5.2]),
+                    StoreKey(array![3.0, 0.0]),
                     HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("bronze".into()),
+                        MetadataKey::new("product".into()),
+                        MetadataValue::RawString("b".into()),
                     )]),
                 ),
             ],
         },
-        // should return result restricted to 2
+        // should return only the closest entry of each product
         DBQuery::GetSimN {
             store: StoreName("Main".to_string()),
-            closest_n: NonZeroUsize::new(2).unwrap(),
-            algorithm: Algorithm::KDTree,
-            search_input: StoreKey(array![1.1, 2.0, 3.0]),
+            closest_n: NonZeroUsize::new(3).unwrap(),
+            algorithm: Algorithm::EuclideanDistance,
+            search_input: StoreKey(array![0.0, 0.0]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: Some(MetadataKey::new("product".into())),
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
-        // return just 1 entry regardless of closest_n
-        // due to precondition satisfying just one
         DBQuery::GetSimN {
             store: StoreName("Main".to_string()),
-            closest_n: NonZeroUsize::new(2).unwrap(),
+            closest_n: NonZeroUsize::new(3).unwrap(),
             algorithm: Algorithm::KDTree,
-            search_input: StoreKey(array![5.0, 2.1, 2.2]),
-            condition: Some(PredicateCondition::Value(Predicate::Equals {
-                key: MetadataKey::new("medal".into()),
-                value: MetadataValue::RawString("gold".into()),
-            })),
+            search_input: StoreKey(array![0.0, 0.0]),
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: Some(MetadataKey::new("product".into())),
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
     ]);
-    let mut expected = ServerResult::with_capacity(5);
+    let mut expected = ServerResult::with_capacity(4);
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 3,
@@ -795,37 +1419,48 @@ async fn test_get_sim_n_non_linear() {
     })));
     expected.push(Ok(ServerResponse::GetSimN(vec![
         (
-            StoreKey(array![2.0, 2.1, 2.2]),
+            StoreKey(array![1.0, 0.0]),
             HashMap::from_iter([(
-                MetadataKey::new("medal".into()),
-                MetadataValue::RawString("gold".into()),
+                MetadataKey::new("product".into()),
+                MetadataValue::RawString("a".into()),
             )]),
-            Similarity(1.4599998),
+            Similarity(1.0),
         ),
         (
-            StoreKey(array![1.2, 1.3, 1.4]),
+            StoreKey(array![3.0, 0.0]),
             HashMap::from_iter([(
-                MetadataKey::new("medal".into()),
-                MetadataValue::RawString("silver".into()),
+                MetadataKey::new("product".into()),
+                MetadataValue::RawString("b".into()),
             )]),
-            Similarity(3.0600002),
+            Similarity(3.0),
+        ),
+    ])));
+    // KDTree similarity is the squared distance
+    expected.push(Ok(ServerResponse::GetSimN(vec![
+        (
+            StoreKey(array![1.0, 0.0]),
+            HashMap::from_iter([(
+                MetadataKey::new("product".into()),
+                MetadataValue::RawString("a".into()),
+            )]),
+            Similarity(1.0),
+        ),
+        (
+            StoreKey(array![3.0, 0.0]),
+            HashMap::from_iter([(
+                MetadataKey::new("product".into()),
+                MetadataValue::RawString("b".into()),
+            )]),
+            Similarity(9.0),
         ),
     ])));
-    expected.push(Ok(ServerResponse::GetSimN(vec![(
-        StoreKey(array![2.0, 2.1, 2.2]),
-        HashMap::from_iter([(
-            MetadataKey::new("medal".into()),
-            MetadataValue::RawString("gold".into()),
-        )]),
-        Similarity(9.0),
-    )])));
     let stream = TcpStream::connect(address).await.unwrap();
     let mut reader = BufReader::new(stream);
     query_server_assert_result(&mut reader, message, expected).await
 }
 
 #[tokio::test]
-async fn test_get_sim_n() {
+async fn test_get_sim_n_fused_search_inputs() {
     let server = Server::new(&CONFIG)
         .await
         .expect("Could not initialize server");
@@ -834,74 +1469,456 @@ async fn test_get_sim_n() {
     // Allow some time for the server to start
     tokio::time::sleep(Duration::from_millis(100)).await;
     let message = ServerDBQuery::from_queries(&[
-        // should error as store does not yet exist
-        DBQuery::GetSimN {
-            store: StoreName("Main".to_string()),
-            search_input: StoreKey(array![]),
-            closest_n: NonZeroUsize::new(2).unwrap(),
-            algorithm: Algorithm::CosineSimilarity,
-            condition: None,
-        },
         DBQuery::CreateStore {
             store: StoreName("Main".to_string()),
-            dimension: NonZeroUsize::new(3).unwrap(),
-            create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::Set {
             store: StoreName("Main".to_string()),
             inputs: vec![
-                (
-                    StoreKey(array![1.2, 1.3, 1.4]),
-                    HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("silver".into()),
-                    )]),
-                ),
-                (
-                    StoreKey(array![2.0, 2.1, 2.2]),
-                    HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("gold".into()),
-                    )]),
-                ),
-                (
-                    StoreKey(array![5.0, 5.1, 5.2]),
-                    HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("bronze".into()),
-                    )]),
-                ),
+                (StoreKey(array![1.0, 0.0]), HashMap::new()),
+                (StoreKey(array![0.0, 1.0]), HashMap::new()),
+                (StoreKey(array![0.6, 0.8]), HashMap::new()),
             ],
         },
-        // error due to non linear algorithm not existing
+        // the entry close to both search inputs should win on average
         DBQuery::GetSimN {
             store: StoreName("Main".to_string()),
-            closest_n: NonZeroUsize::new(2).unwrap(),
-            algorithm: Algorithm::KDTree,
-            search_input: StoreKey(array![1.1, 2.0, 3.0]),
+            closest_n: NonZeroUsize::new(1).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            search_input: StoreKey(array![1.0, 0.0]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![StoreKey(array![0.0, 1.0])],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
-        // error due to dimension mismatch
         DBQuery::GetSimN {
             store: StoreName("Main".to_string()),
-            closest_n: NonZeroUsize::new(2).unwrap(),
-            algorithm: Algorithm::EuclideanDistance,
-            search_input: StoreKey(array![1.1, 2.0]),
+            closest_n: NonZeroUsize::new(1).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            search_input: StoreKey(array![1.0, 0.0]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: true,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![StoreKey(array![0.0, 1.0])],
+            fusion: FusionStrategy::ReciprocalRankFusion,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
-        // return just 1 entry regardless of closest_n
-        // due to precondition satisfying just one
-        DBQuery::GetSimN {
-            store: StoreName("Main".to_string()),
-            closest_n: NonZeroUsize::new(2).unwrap(),
+    ]);
+    let mut expected = ServerResult::with_capacity(4);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 3,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::GetSimN(vec![(
+        StoreKey(array![0.6, 0.8]),
+        HashMap::new(),
+        Similarity(0.7),
+    )])));
+    expected.push(Err(ServerError::RankFusionScoreOptions.into()));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await
+}
+
+#[tokio::test]
+async fn test_get_sim_n_recency_boost() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let published = MetadataKey::new("published".into());
+    let published_at = |timestamp: &str| {
+        HashMap::from_iter([(
+            published.clone(),
+            MetadataValue::RawString(timestamp.into()),
+        )])
+    };
+    let boost = RecencyBoost {
+        half_life: NonZeroU64::new(86400).unwrap(),
+        weight: 0.5,
+    };
+    let get_sim_n = |store: &str| DBQuery::GetSimN {
+        store: StoreName(store.to_string()),
+        closest_n: NonZeroUsize::new(1).unwrap(),
+        algorithm: Algorithm::CosineSimilarity,
+        search_input: StoreKey(array![1.0, 0.0]),
+        condition: None,
+        min_score: None,
+        max_distance: None,
+        normalize_scores: false,
+        group_by: None,
+        group_size: NonZeroUsize::new(1).unwrap(),
+        additional_search_inputs: vec![],
+        fusion: FusionStrategy::Mean,
+        recency_boost: Some(boost),
+        filter_strategy: FilterStrategy::Auto,
+        search_terms: vec![],
+        exclude_keys: vec![],
+        unordered_ties: false,
+    };
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("News".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: Some(published.clone()),
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::CreateStore {
+            store: StoreName("Undated".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("News".to_string()),
+            inputs: vec![
+                (StoreKey(array![1.0, 0.0]), published_at("0")),
+                // dated in the future so it counts as brand new
+                (StoreKey(array![0.0, 1.0]), published_at("4102444800")),
+            ],
+        },
+        // the newer entry outranks the more similar but much older one
+        get_sim_n("News"),
+        get_sim_n("Undated"),
+    ]);
+    let mut expected = ServerResult::with_capacity(5);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 2,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::GetSimN(vec![(
+        StoreKey(array![0.0, 1.0]),
+        published_at("4102444800"),
+        Similarity(0.75),
+    )])));
+    expected.push(Err(ServerError::TimestampKeyNotSet(StoreName(
+        "Undated".to_string(),
+    ))
+    .into()));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await
+}
+
+#[tokio::test]
+async fn test_get_sim_n_non_linear() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
+            non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![
+                (
+                    StoreKey(array![1.2, 1.3, 1.4]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("silver".into()),
+                    )]),
+                ),
+                (
+                    StoreKey(array![2.0, 2.1, 2.2]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("gold".into()),
+                    )]),
+                ),
+                (
+                    StoreKey(array![5.0, 5.1, 5.2]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("bronze".into()),
+                    )]),
+                ),
+            ],
+        },
+        // should return result restricted to 2
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::KDTree,
+            search_input: StoreKey(array![1.1, 2.0, 3.0]),
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+        // return just 1 entry regardless of closest_n
+        // due to precondition satisfying just one
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::KDTree,
+            search_input: StoreKey(array![5.0, 2.1, 2.2]),
+            condition: Some(PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new("medal".into()),
+                value: MetadataValue::RawString("gold".into()),
+            })),
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+    ]);
+    let mut expected = ServerResult::with_capacity(5);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 3,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::GetSimN(vec![
+        (
+            StoreKey(array![2.0, 2.1, 2.2]),
+            HashMap::from_iter([(
+                MetadataKey::new("medal".into()),
+                MetadataValue::RawString("gold".into()),
+            )]),
+            Similarity(1.4599998),
+        ),
+        (
+            StoreKey(array![1.2, 1.3, 1.4]),
+            HashMap::from_iter([(
+                MetadataKey::new("medal".into()),
+                MetadataValue::RawString("silver".into()),
+            )]),
+            Similarity(3.0600002),
+        ),
+    ])));
+    expected.push(Ok(ServerResponse::GetSimN(vec![(
+        StoreKey(array![2.0, 2.1, 2.2]),
+        HashMap::from_iter([(
+            MetadataKey::new("medal".into()),
+            MetadataValue::RawString("gold".into()),
+        )]),
+        Similarity(9.0),
+    )])));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await
+}
+
+#[tokio::test]
+async fn test_get_sim_n() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        // should error as store does not yet exist
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            search_input: StoreKey(array![]),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![
+                (
+                    StoreKey(array![1.2, 1.3, 1.4]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("silver".into()),
+                    )]),
+                ),
+                (
+                    StoreKey(array![2.0, 2.1, 2.2]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("gold".into()),
+                    )]),
+                ),
+                (
+                    StoreKey(array![5.0, 5.1, 5.2]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("bronze".into()),
+                    )]),
+                ),
+            ],
+        },
+        // error due to non linear algorithm not existing
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::KDTree,
+            search_input: StoreKey(array![1.1, 2.0, 3.0]),
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+        // error due to dimension mismatch
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::EuclideanDistance,
+            search_input: StoreKey(array![1.1, 2.0]),
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+        // return just 1 entry regardless of closest_n
+        // due to precondition satisfying just one
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(2).unwrap(),
             algorithm: Algorithm::CosineSimilarity,
             search_input: StoreKey(array![5.0, 2.1, 2.2]),
             condition: Some(PredicateCondition::Value(Predicate::Equals {
                 key: MetadataKey::new("medal".into()),
                 value: MetadataValue::RawString("gold".into()),
             })),
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
         // Get closest 2 without precondition using DotProduct
         DBQuery::GetSimN {
@@ -910,6 +1927,18 @@ async fn test_get_sim_n() {
             algorithm: Algorithm::DotProductSimilarity,
             search_input: StoreKey(array![1.0, 2.1, 2.2]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
         // Get closest 2 without precondition using EuclideanDistance
         DBQuery::GetSimN {
@@ -918,6 +1947,18 @@ async fn test_get_sim_n() {
             algorithm: Algorithm::EuclideanDistance,
             search_input: StoreKey(array![1.0, 2.1, 2.2]),
             condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
         // get closest one where medal is not gold
         DBQuery::GetSimN {
@@ -929,21 +1970,41 @@ async fn test_get_sim_n() {
                 key: MetadataKey::new("medal".into()),
                 value: MetadataValue::RawString("gold".into()),
             })),
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         },
     ]);
     let mut expected = ServerResult::with_capacity(8);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 3,
         updated: 0,
     })));
-    expected.push(Err(
-        "Non linear algorithm KDTree not found in store, create store with support".into(),
-    ));
-    expected.push(Err(
-        "Store dimension is [3], input dimension of [2] was specified".into(),
-    ));
+    expected.push(Err(ServerError::NonLinearIndexNotFound(
+        NonLinearAlgorithm::KDTree,
+    )
+    .into()));
+    expected.push(Err(ServerError::StoreDimensionMismatch {
+        store: StoreName("Main".to_string()),
+        store_dimension: 3,
+        input_dimension: 2,
+        index: 0,
+    }
+    .into()));
     expected.push(Ok(ServerResponse::GetSimN(vec![(
         StoreKey(array![2.0, 2.1, 2.2]),
         HashMap::from_iter([(
@@ -1002,7 +2063,7 @@ async fn test_get_sim_n() {
 }
 
 #[tokio::test]
-async fn test_get_pred() {
+async fn test_del_pred_async() {
     let server = Server::new(&CONFIG)
         .await
         .expect("Could not initialize server");
@@ -1010,41 +2071,541 @@ async fn test_get_pred() {
     let _ = tokio::spawn(async move { server.start().await });
     // Allow some time for the server to start
     tokio::time::sleep(Duration::from_millis(100)).await;
+    let jupiter = PredicateCondition::Value(Predicate::Equals {
+        key: MetadataKey::new("planet".into()),
+        value: MetadataValue::RawString("jupiter".into()),
+    });
     let message = ServerDBQuery::from_queries(&[
-        // should error as store does not yet exist
-        DBQuery::GetPred {
+        // should error as store does not exist
+        DBQuery::DelPredAsync {
             store: StoreName("Main".to_string()),
-            condition: PredicateCondition::Value(Predicate::Equals {
-                key: MetadataKey::new("medal".into()),
-                value: MetadataValue::RawString("gold".into()),
-            }),
+            condition: jupiter.clone(),
         },
         DBQuery::CreateStore {
             store: StoreName("Main".to_string()),
-            dimension: NonZeroUsize::new(3).unwrap(),
-            create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::from_iter([MetadataKey::new("planet".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::Set {
             store: StoreName("Main".to_string()),
             inputs: vec![
                 (
-                    StoreKey(array![1.2, 1.3, 1.4]),
+                    StoreKey(array![1.4, 1.5]),
                     HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("silver".into()),
+                        MetadataKey::new("planet".into()),
+                        MetadataValue::RawString("jupiter".into()),
                     )]),
                 ),
                 (
-                    StoreKey(array![1.3, 1.4, 1.5]),
+                    StoreKey(array![1.5, 1.6]),
                     HashMap::from_iter([(
-                        MetadataKey::new("medal".into()),
-                        MetadataValue::RawString("bronze".into()),
+                        MetadataKey::new("planet".into()),
+                        MetadataValue::RawString("jupiter".into()),
                     )]),
                 ),
-            ],
-        },
+                (
+                    StoreKey(array![1.6, 1.7]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("planet".into()),
+                        MetadataValue::RawString("mars".into()),
+                    )]),
+                ),
+            ],
+        },
+        // should return a job id immediately
+        DBQuery::DelPredAsync {
+            store: StoreName("Main".to_string()),
+            condition: jupiter.clone(),
+        },
+    ]);
+    let mut expected = ServerResult::with_capacity(4);
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 3,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::JobStarted(1)));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+    // Allow some time for the background deletion to complete
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::GetJob { job_id: 1 },
+        // should error as job does not exist
+        DBQuery::GetJob { job_id: 2 },
+        // should leave the finished job as is
+        DBQuery::CancelJob { job_id: 1 },
+        DBQuery::ListJobs,
+        DBQuery::GetPred {
+            store: StoreName("Main".to_string()),
+            condition: jupiter,
+        },
+        DBQuery::GetKey {
+            store: StoreName("Main".to_string()),
+            keys: vec![StoreKey(array![1.6, 1.7])],
+        },
+    ]);
+    let completed = JobStatus {
+        id: 1,
+        kind: JobKind::DelPred,
+        state: JobState::Completed,
+        processed: 2,
+        total: 2,
+    };
+    let mut expected = ServerResult::with_capacity(6);
+    expected.push(Ok(ServerResponse::JobStatus(completed.clone())));
+    expected.push(Err(ServerError::JobNotFound(2).into()));
+    expected.push(Ok(ServerResponse::JobStatus(completed.clone())));
+    expected.push(Ok(ServerResponse::JobList(vec![completed])));
+    expected.push(Ok(ServerResponse::Get(vec![])));
+    expected.push(Ok(ServerResponse::Get(vec![(
+        StoreKey(array![1.6, 1.7]),
+        HashMap::from_iter([(
+            MetadataKey::new("planet".into()),
+            MetadataValue::RawString("mars".into()),
+        )]),
+    )])));
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_export_store_parquet() {
+    let export_location = std::env::temp_dir().join("ahnlich_test_exports");
+    let _ = std::fs::remove_dir_all(&export_location);
+    let config = ServerConfig::default()
+        .os_select_port()
+        .export_location(export_location.clone());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![
+                (
+                    StoreKey(array![1.2, 1.3]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("planet".into()),
+                        MetadataValue::RawString("jupiter".into()),
+                    )]),
+                ),
+                (StoreKey(array![1.6, 1.7]), HashMap::new()),
+            ],
+        },
+        DBQuery::ExportStoreParquet {
+            store: StoreName("Main".to_string()),
+            path: "main/export.parquet".to_string(),
+        },
+        // should error as the path leaves the export location
+        DBQuery::ExportStoreParquet {
+            store: StoreName("Main".to_string()),
+            path: "../export.parquet".to_string(),
+        },
+        DBQuery::ExportStoreParquet {
+            store: StoreName("Other".to_string()),
+            path: "other.parquet".to_string(),
+        },
+    ]);
+    let mut expected = ServerResult::with_capacity(5);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 2,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::JobStarted(1)));
+    expected.push(Err(ServerError::InvalidExportPath(
+        "../export.parquet".to_string(),
+    )
+    .into()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Other".to_string(),
+    ))
+    .into()));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+    // Allow some time for the background export to complete
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[DBQuery::GetJob { job_id: 1 }]);
+    let mut expected = ServerResult::with_capacity(1);
+    expected.push(Ok(ServerResponse::JobStatus(JobStatus {
+        id: 1,
+        kind: JobKind::ExportStore,
+        state: JobState::Completed,
+        processed: 2,
+        total: 2,
+    })));
+    query_server_assert_result(&mut reader, message, expected).await;
+
+    let file = std::fs::File::open(export_location.join("main/export.parquet")).unwrap();
+    let batches: Vec<_> =
+        parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(batch.schema().field(1).name(), "planet");
+    assert_eq!(batch.column(1).null_count(), 1);
+    let _ = std::fs::remove_dir_all(&export_location);
+
+    // exports are refused when the server has nowhere to write them
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[DBQuery::ExportStoreParquet {
+        store: StoreName("Main".to_string()),
+        path: "export.parquet".to_string(),
+    }]);
+    let mut expected = ServerResult::with_capacity(1);
+    expected.push(Err(ServerError::ExportNotConfigured.into()));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_export_store_parquet_client_filters() {
+    let export_location = std::env::temp_dir().join("ahnlich_test_filtered_exports");
+    let _ = std::fs::remove_dir_all(&export_location);
+    let config = ServerConfig::default()
+        .os_select_port()
+        .export_location(export_location.clone())
+        .client_filter("127.0.0.1=Main:tenant=acme".parse().unwrap());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let entry = |key: StoreKey, tenant: &str| {
+        (
+            key,
+            HashMap::from_iter([(
+                MetadataKey::new("tenant".into()),
+                MetadataValue::RawString(tenant.into()),
+            )]),
+        )
+    };
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![
+                entry(StoreKey(array![1.2, 1.3]), "acme"),
+                entry(StoreKey(array![1.6, 1.7]), "globex"),
+                entry(StoreKey(array![1.8, 1.9]), "globex"),
+            ],
+        },
+        DBQuery::ExportStoreParquet {
+            store: StoreName("Main".to_string()),
+            path: "main.parquet".to_string(),
+        },
+    ]);
+    let mut expected = ServerResult::with_capacity(3);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 3,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::JobStarted(1)));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+    // Allow some time for the background export to complete
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[DBQuery::GetJob { job_id: 1 }]);
+    let mut expected = ServerResult::with_capacity(1);
+    expected.push(Ok(ServerResponse::JobStatus(JobStatus {
+        id: 1,
+        kind: JobKind::ExportStore,
+        state: JobState::Completed,
+        processed: 1,
+        total: 1,
+    })));
+    query_server_assert_result(&mut reader, message, expected).await;
+
+    // only the entry of the tenant the client is held to is written
+    let file = std::fs::File::open(export_location.join("main.parquet")).unwrap();
+    let batches: Vec<_> =
+        parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+    assert_eq!(
+        batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+        1
+    );
+    let _ = std::fs::remove_dir_all(&export_location);
+}
+
+#[tokio::test]
+async fn test_apply_manifest() {
+    let manifest_path = std::env::temp_dir().join("ahnlich_test_manifest.toml");
+    std::fs::write(
+        &manifest_path,
+        r#"
+[[stores]]
+store = "Main"
+dimension = 2
+predicates = ["planet"]
+non_linear_indices = ["KDTree"]
+"#,
+    )
+    .unwrap();
+    let config = ServerConfig::default()
+        .os_select_port()
+        .manifest(manifest_path.clone());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let _ = std::fs::remove_file(&manifest_path);
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let manifest = |predicates: &[&str], dimension: usize| StoreManifest {
+        store: StoreName("Main".to_string()),
+        dimension: NonZeroUsize::new(dimension).unwrap(),
+        predicates: predicates
+            .iter()
+            .map(|predicate| MetadataKey::new(predicate.to_string()))
+            .collect(),
+        non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
+        timestamp_key: None,
+        storage_tier: StorageTier::Memory,
+        key_element_type: KeyElementType::Float32,
+        normalization: VectorNormalization::None,
+        index_seed: None,
+        non_finite_vectors: NonFiniteVectors::Reject,
+        eviction: None,
+    };
+    let message = ServerDBQuery::from_queries(&[
+        // the store was created on startup so there is nothing left to change
+        DBQuery::ApplyManifest {
+            stores: vec![manifest(&["planet"], 2)],
+            dry_run: false,
+        },
+        DBQuery::ApplyManifest {
+            stores: vec![manifest(&["galaxy"], 2)],
+            dry_run: true,
+        },
+        DBQuery::ApplyManifest {
+            stores: vec![manifest(&["galaxy"], 2)],
+            dry_run: false,
+        },
+        DBQuery::ApplyManifest {
+            stores: vec![manifest(&["galaxy"], 2)],
+            dry_run: true,
+        },
+        // should error as the dimension of a store cannot be changed in place
+        DBQuery::ApplyManifest {
+            stores: vec![manifest(&["galaxy"], 3)],
+            dry_run: false,
+        },
+        DBQuery::DiffManifest {
+            stores: vec![manifest(&["galaxy"], 3)],
+        },
+        DBQuery::DiffManifest { stores: vec![] },
+    ]);
+    let changes = vec![
+        ManifestChange::CreatePredicates {
+            store: StoreName("Main".to_string()),
+            predicates: vec![MetadataKey::new("galaxy".into())],
+        },
+        ManifestChange::DropPredicates {
+            store: StoreName("Main".to_string()),
+            predicates: vec![MetadataKey::new("planet".into())],
+        },
+    ];
+    let mut expected = ServerResult::with_capacity(8);
+    expected.push(Ok(ServerResponse::ManifestChanges(vec![])));
+    expected.push(Ok(ServerResponse::ManifestChanges(changes.clone())));
+    expected.push(Ok(ServerResponse::ManifestChanges(changes)));
+    expected.push(Ok(ServerResponse::ManifestChanges(vec![])));
+    expected.push(Err(ServerError::ManifestConflict {
+        store: StoreName("Main".to_string()),
+        setting: "dimension".to_string(),
+    }
+    .into()));
+    expected.push(Ok(ServerResponse::ManifestDrift(ManifestDrift {
+        changes: vec![],
+        settings: vec![SettingDrift {
+            store: StoreName("Main".to_string()),
+            setting: "dimension".to_string(),
+            manifest: Some("3".to_string()),
+            live: Some("2".to_string()),
+        }],
+        unmanaged_stores: vec![],
+    })));
+    expected.push(Ok(ServerResponse::ManifestDrift(ManifestDrift {
+        changes: vec![],
+        settings: vec![],
+        unmanaged_stores: vec![StoreName("Main".to_string())],
+    })));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_finished_jobs_expire() {
+    let server = Server::new(&CONFIG_WITHOUT_JOB_TTL)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::from_iter([MetadataKey::new("planet".into())]),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::DelPredAsync {
+            store: StoreName("Main".to_string()),
+            condition: PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new("planet".into()),
+                value: MetadataValue::RawString("jupiter".into()),
+            }),
+        },
+    ]);
+    let mut expected = ServerResult::with_capacity(2);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::JobStarted(1)));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+    // Allow some time for the background deletion to complete
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    // job is forgotten as soon as it finishes
+    let message =
+        ServerDBQuery::from_queries(&[DBQuery::ListJobs, DBQuery::CancelJob { job_id: 1 }]);
+    let mut expected = ServerResult::with_capacity(2);
+    expected.push(Ok(ServerResponse::JobList(vec![])));
+    expected.push(Err(ServerError::JobNotFound(1).into()));
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
+#[tokio::test]
+async fn test_get_pred() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        // should error as store does not yet exist
+        DBQuery::GetPred {
+            store: StoreName("Main".to_string()),
+            condition: PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new("medal".into()),
+                value: MetadataValue::RawString("gold".into()),
+            }),
+        },
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::from_iter([MetadataKey::new("medal".into())]),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![
+                (
+                    StoreKey(array![1.2, 1.3, 1.4]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("silver".into()),
+                    )]),
+                ),
+                (
+                    StoreKey(array![1.3, 1.4, 1.5]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("medal".into()),
+                        MetadataValue::RawString("bronze".into()),
+                    )]),
+                ),
+            ],
+        },
         // should not error but return 0
         DBQuery::GetPred {
             store: StoreName("Main".to_string()),
@@ -1069,7 +2630,10 @@ async fn test_get_pred() {
         },
     ]);
     let mut expected = ServerResult::with_capacity(8);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 2,
@@ -1116,6 +2680,14 @@ async fn test_get_key() {
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::Set {
             store: StoreName("Main".to_string()),
@@ -1162,15 +2734,22 @@ async fn test_get_key() {
         DBQuery::InfoServer,
     ]);
     let mut expected = ServerResult::with_capacity(7);
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 2,
         updated: 0,
     })));
-    expected.push(Err(
-        "Store dimension is [2], input dimension of [3] was specified".to_string(),
-    ));
+    expected.push(Err(ServerError::StoreDimensionMismatch {
+        store: StoreName("Main".to_string()),
+        store_dimension: 2,
+        input_dimension: 3,
+        index: 0,
+    }
+    .into()));
     expected.push(Ok(ServerResponse::Get(vec![])));
     expected.push(Ok(ServerResponse::Get(vec![
         (
@@ -1190,10 +2769,17 @@ async fn test_get_key() {
     ])));
     expected.push(Ok(ServerResponse::InfoServer(ServerInfo {
         address: "127.0.0.1:1369".to_string(),
-        version: *ahnlich_types::version::VERSION,
+        version: *VERSION,
+        min_client_version: *MIN_CLIENT_VERSION,
+        max_client_version: *VERSION,
         r#type: ahnlich_types::ServerType::Database,
         limit: CONFIG.common.allocator_size,
         remaining: 1073609219,
+        request_limits: DEFAULT_REQUEST_LIMITS,
+        in_flight_memory: 0,
+        deadlines_exceeded: 0,
+        priorities: vec![],
+        connections: ConnectionStats::default(),
     })));
     let stream = TcpStream::connect(address).await.unwrap();
     let mut reader = BufReader::new(stream);
@@ -1221,6 +2807,14 @@ async fn test_create_pred_index() {
             create_predicates: HashSet::from_iter([MetadataKey::new("galaxy".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::Set {
             store: StoreName("Main".to_string()),
@@ -1309,7 +2903,10 @@ async fn test_create_pred_index() {
         },
     ]);
     let mut expected = ServerResult::with_capacity(8);
-    expected.push(Err("Store Main not found".into()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Set(StoreUpsert {
         inserted: 2,
@@ -1409,6 +3006,14 @@ async fn test_drop_pred_index() {
             create_predicates: HashSet::from_iter([MetadataKey::new("galaxy".into())]),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         // should not error even though predicate does not exist
         DBQuery::DropPredIndex {
@@ -1430,12 +3035,16 @@ async fn test_drop_pred_index() {
         },
     ]);
     let mut expected = ServerResult::with_capacity(5);
-    expected.push(Err("Store Main not found".into()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Unit));
     expected.push(Ok(ServerResponse::Del(0)));
-    expected.push(Err(
-        "Predicate planet not found in store, attempt CREATEPREDINDEX with predicate".into(),
-    ));
+    expected.push(Err(ServerError::PredicateNotFound(MetadataKey::new(
+        "planet".into(),
+    ))
+    .into()));
     expected.push(Ok(ServerResponse::Del(1)));
     let stream = TcpStream::connect(address).await.unwrap();
     let mut reader = BufReader::new(stream);
@@ -1463,6 +3072,14 @@ async fn test_drop_stores() {
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         },
         DBQuery::ListStores,
         // should not error
@@ -1484,17 +3101,608 @@ async fn test_drop_stores() {
             name: StoreName("Main".to_string()),
             len: 0,
             size_in_bytes: 1720,
+            dimension: NonZeroUsize::new(3).unwrap(),
+            request_limits: DEFAULT_REQUEST_LIMITS,
         },
     ]))));
     expected.push(Ok(ServerResponse::Del(1)));
-    expected.push(Err("Store Main not found".to_string()));
+    expected.push(Err(ServerError::StoreNotFound(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
     let stream = TcpStream::connect(address).await.unwrap();
     let mut reader = BufReader::new(stream);
     query_server_assert_result(&mut reader, message, expected).await
 }
 
 #[tokio::test]
-async fn test_run_server_echos() {
+async fn test_audit_log() {
+    let audit_log = std::env::temp_dir().join("ahnlich_test_audit_log.log");
+    let _ = std::fs::remove_file(&audit_log);
+    let config = ServerConfig::default()
+        .os_select_port()
+        .audit_log(audit_log.clone());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![(StoreKey(array![1.0, 2.0]), HashMap::new())],
+        },
+        // reads are not recorded
+        DBQuery::ListStores,
+        DBQuery::DropStore {
+            store: StoreName("Main".to_string()),
+            error_if_not_exists: true,
+        },
+        DBQuery::DropStore {
+            store: StoreName("Main".to_string()),
+            error_if_not_exists: true,
+        },
+    ]);
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    let serialized_message = message.serialize().unwrap();
+    reader.write_all(&serialized_message).await.unwrap();
+    let mut header = [0u8; ahnlich_types::bincode::RESPONSE_HEADER_LEN];
+    timeout(Duration::from_secs(1), reader.read_exact(&mut header))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let records: Vec<serde_json::Value> = std::fs::read_to_string(&audit_log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let operations: Vec<_> = records
+        .iter()
+        .map(|record| {
+            (
+                record["operation"].as_str().unwrap(),
+                record["category"].as_str().unwrap(),
+                record["outcome"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        operations,
+        vec![
+            ("CREATESTORE", "admin", "success"),
+            ("SET", "write", "success"),
+            ("DROPSTORE", "admin", "success"),
+            ("DROPSTORE", "admin", "failure"),
+        ]
+    );
+    assert_eq!(records[0]["stores"], serde_json::json!(["Main"]));
+    assert_eq!(records[3]["error"], "Store Main not found");
+    std::fs::remove_file(audit_log).unwrap();
+}
+
+#[tokio::test]
+async fn test_mirror() {
+    let mirror = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let mirror_address = mirror.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { mirror.start().await });
+    let config = ServerConfig::default()
+        .os_select_port()
+        .mirror(mirror_address.ip().to_string(), mirror_address.port());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the servers to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    let mirror_client = DbClient::new(mirror_address.ip().to_string(), mirror_address.port())
+        .await
+        .unwrap();
+    client
+        .create_store(
+            CreateStoreParams::builder()
+                .store("Main".to_string())
+                .dimension(2)
+                .create_predicates(HashSet::from_iter([MetadataKey::new("rank".into())]))
+                .build(),
+        )
+        .await
+        .unwrap();
+    for index in 0..3 {
+        client
+            .set(
+                SetParams::builder()
+                    .store("Main".to_string())
+                    .inputs(vec![(
+                        StoreKey(array![index as f32, 1.0]),
+                        HashMap::from_iter([(
+                            MetadataKey::new("rank".into()),
+                            MetadataValue::RawString(index.to_string()),
+                        )]),
+                    )])
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+    client
+        .del_key(
+            DelKeyParams::builder()
+                .store("Main".to_string())
+                .keys(vec![StoreKey(array![0.0, 1.0])])
+                .build(),
+        )
+        .await
+        .unwrap();
+
+    let mut mirrored = None;
+    for _ in 0..50 {
+        if let ServerResponse::StoreList(stores) = mirror_client.list_stores(None).await.unwrap() {
+            mirrored = stores
+                .into_iter()
+                .next()
+                .map(|store| (store.name, store.len));
+        }
+        if mirrored == Some((StoreName("Main".to_string()), 2)) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(mirrored, Some((StoreName("Main".to_string()), 2)));
+    let ServerResponse::MirrorStatus(status) = client.mirror_status(None).await.unwrap() else {
+        panic!("Expected the mirror status");
+    };
+    assert_eq!(status.target, mirror_address.to_string());
+    assert_eq!(status.state, MirrorState::Running);
+    assert_eq!(status.pending, 0);
+    assert_eq!(status.failed, 0);
+
+    let ServerResponse::MirrorStatus(status) = client
+        .control_mirror(
+            ControlMirrorParams::builder()
+                .action(MirrorAction::Pause)
+                .build(),
+        )
+        .await
+        .unwrap()
+    else {
+        panic!("Expected the mirror status");
+    };
+    assert_eq!(status.state, MirrorState::Paused);
+    client
+        .drop_store(DropStoreParams::builder().store("Main".to_string()).build())
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let ServerResponse::MirrorStatus(status) = client.mirror_status(None).await.unwrap() else {
+        panic!("Expected the mirror status");
+    };
+    assert_eq!(status.pending, 1);
+    // the drop waits in the log while the mirror is paused
+    let ServerResponse::StoreList(stores) = mirror_client.list_stores(None).await.unwrap() else {
+        panic!("Expected the store list");
+    };
+    assert_eq!(stores.len(), 1);
+    // the mirror is not configured with another mirror
+    assert_eq!(
+        mirror_client
+            .mirror_status(None)
+            .await
+            .unwrap_err()
+            .to_string(),
+        "db error The server is not started with a mirror"
+    );
+}
+
+#[tokio::test]
+async fn test_read_only_mirror() {
+    let config = ServerConfig::default()
+        .os_select_port()
+        .mirror_source("10.0.0.1".parse().unwrap());
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(2).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::ListStores,
+    ]);
+    let mut expected = ServerResult::with_capacity(2);
+    expected.push(Err(ErrorResponse::new(
+        ErrorCode::ReadOnly,
+        "The server is a read only mirror, writes are only accepted from 10.0.0.1",
+    )));
+    expected.push(Ok(ServerResponse::StoreList(HashSet::new())));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await
+}
+
+#[tokio::test]
+async fn test_memory_admission() {
+    let config = ServerConfig::default()
+        .os_select_port()
+        .memory_limits(Some(600), None);
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let keys: Vec<_> = (0..3)
+        .map(|index| StoreKey(array![index as f32, 1.0, 1.0]))
+        .collect();
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: keys
+                .iter()
+                .map(|key| (key.clone(), HashMap::new()))
+                .collect(),
+        },
+        DBQuery::GetKey {
+            store: StoreName("Main".to_string()),
+            keys: vec![keys[0].clone()],
+        },
+        DBQuery::GetKey {
+            store: StoreName("Main".to_string()),
+            keys: keys.clone(),
+        },
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(10).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            search_input: keys[0].clone(),
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+        DBQuery::InfoServer,
+    ]);
+    let mut expected = ServerResult::with_capacity(6);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 3,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::Get(vec![(
+        keys[0].clone(),
+        HashMap::new(),
+    )])));
+    // the estimate only counts entries the store holds
+    for _ in 0..2 {
+        expected.push(Err(ServerError::RequestMemoryExceeded {
+            estimate: 804,
+            limit: 600,
+        }
+        .into()));
+    }
+    expected.push(Ok(ServerResponse::InfoServer(ServerInfo {
+        address: address.to_string(),
+        version: *VERSION,
+        min_client_version: *MIN_CLIENT_VERSION,
+        max_client_version: *VERSION,
+        r#type: ahnlich_types::ServerType::Database,
+        limit: CONFIG.common.allocator_size,
+        remaining: 0,
+        request_limits: DEFAULT_REQUEST_LIMITS,
+        in_flight_memory: 0,
+        deadlines_exceeded: 0,
+        priorities: vec![],
+        connections: ConnectionStats::default(),
+    })));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await
+}
+
+#[tokio::test]
+async fn test_request_id_in_errors() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    let request_id = |tracing_id: Option<&str>| {
+        let client = &client;
+        let params = DropStoreParams::builder()
+            .store("Main".to_string())
+            .tracing_id(tracing_id.map(String::from))
+            .build();
+        async move {
+            match client.drop_store(params).await.unwrap_err() {
+                AhnlichError::DbError(error) => error.request_id,
+                error => panic!("Unexpected error {error}"),
+            }
+        }
+    };
+    assert_eq!(
+        request_id(Some(
+            "00-80e1afed08e019fc1110464cfa66635c-7a085853722dc6d2-01"
+        ))
+        .await
+        .as_deref(),
+        Some("80e1afed08e019fc1110464cfa66635c")
+    );
+    let generated = request_id(None).await.unwrap();
+    assert_eq!(generated.len(), 32);
+    assert_ne!(request_id(None).await.unwrap(), generated);
+}
+
+#[tokio::test]
+async fn test_request_priorities() {
+    let config = ServerConfig::default()
+        .os_select_port()
+        .max_concurrent_requests(1, 1000);
+    let server = Server::new(&config)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    for priority in [Priority::Interactive, Priority::Bulk, Priority::Bulk] {
+        let mut pipeline = client.pipeline(1, None).await.unwrap();
+        pipeline.priority(priority);
+        pipeline.ping();
+        pipeline.exec().await.unwrap();
+    }
+    let info = match client.info_server(None).await.unwrap() {
+        ServerResponse::InfoServer(info) => info,
+        response => panic!("Unexpected response {response:?}"),
+    };
+    let priorities: Vec<_> = info.priorities.iter().map(|stats| stats.priority).collect();
+    assert_eq!(
+        priorities,
+        vec![Priority::Interactive, Priority::Normal, Priority::Bulk]
+    );
+    assert_eq!(info.priorities[0].requests, 1);
+    // requests of the client pool checking its connections are sent at normal priority
+    assert_eq!(info.priorities[2].requests, 2);
+    assert!(info.priorities.iter().all(|stats| stats.queued == 0));
+}
+
+#[tokio::test]
+async fn test_memory_breakdown() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    client
+        .create_store(
+            CreateStoreParams::builder()
+                .store("Main".to_string())
+                .dimension(2)
+                .create_predicates(HashSet::from_iter([MetadataKey::new("rank".into())]))
+                .build(),
+        )
+        .await
+        .unwrap();
+    client
+        .set(
+            SetParams::builder()
+                .store("Main".to_string())
+                .inputs(vec![(
+                    StoreKey(array![1.0, 2.0]),
+                    HashMap::from_iter([(
+                        MetadataKey::new("rank".into()),
+                        MetadataValue::RawString("1".into()),
+                    )]),
+                )])
+                .build(),
+        )
+        .await
+        .unwrap();
+    let breakdown = match client.get_memory_breakdown(None).await.unwrap() {
+        ServerResponse::MemoryBreakdown(breakdown) => breakdown,
+        response => panic!("Unexpected response {response:?}"),
+    };
+    assert_eq!(breakdown.limit, CONFIG.common.allocator_size);
+    assert_eq!(breakdown.stores.len(), 1);
+    assert_eq!(breakdown.stores[0].name, StoreName("Main".to_string()));
+    assert_eq!(breakdown.stores[0].vectors, 2 * size_of::<f32>());
+    assert!(breakdown.stores[0].predicate_indices > 0);
+    assert_eq!(breakdown.trash, 0);
+    // every connection of the client pool holds a read buffer
+    assert!(breakdown.connections.clients >= 1);
+    assert_eq!(
+        breakdown.connections.buffers,
+        breakdown.connections.clients * CONNECTION_BUFFER_SIZE
+    );
+}
+#[tokio::test]
+async fn test_benchmark_store() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    client
+        .create_store(
+            CreateStoreParams::builder()
+                .store("Main".to_string())
+                .dimension(2)
+                .non_linear_indices(HashSet::from_iter([NonLinearAlgorithm::KDTree]))
+                .build(),
+        )
+        .await
+        .unwrap();
+    client
+        .set(
+            SetParams::builder()
+                .store("Main".to_string())
+                .inputs(
+                    (0..3)
+                        .map(|key| (StoreKey(array![key as f32, 1.0]), HashMap::new()))
+                        .collect(),
+                )
+                .build(),
+        )
+        .await
+        .unwrap();
+    let params = BenchmarkStoreParams::builder()
+        .store("Main".to_string())
+        .sample_queries(5)
+        .concurrency(2)
+        .duration(Duration::from_millis(100))
+        .build();
+    let benchmark = match client.benchmark_store(params).await.unwrap() {
+        ServerResponse::StoreBenchmark(benchmark) => benchmark,
+        response => panic!("Unexpected response {response:?}"),
+    };
+    // a store smaller than the sample is searched with every entry
+    assert_eq!(
+        (
+            benchmark.entries,
+            benchmark.sample_queries,
+            benchmark.concurrency
+        ),
+        (3, 3, 2)
+    );
+    assert_eq!(
+        benchmark
+            .paths
+            .iter()
+            .map(|path| path.path.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            SearchPath::Linear,
+            SearchPath::NonLinear(NonLinearAlgorithm::KDTree)
+        ]
+    );
+    for path in benchmark.paths {
+        assert!(path.queries > 0);
+        assert!(path.p50_latency <= path.p99_latency && path.p99_latency <= path.max_latency);
+    }
+
+    let params = BenchmarkStoreParams::builder()
+        .store("Main".to_string())
+        .duration(Duration::from_secs(600))
+        .build();
+    match client.benchmark_store(params).await.unwrap_err() {
+        AhnlichError::DbError(error) => assert_eq!(error.code, ErrorCode::InvalidArgument),
+        error => panic!("Unexpected error {error}"),
+    }
+}
+
+#[tokio::test]
+async fn test_query_deadline() {
+    let server = Server::new(&CONFIG)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    // the deadline of a request without time to spare passes before any query runs
+    let mut message = ServerDBQuery::from_queries(&[DBQuery::Ping, DBQuery::ListStores]);
+    message.set_timeout(Duration::ZERO);
+    let mut expected = ServerResult::with_capacity(2);
+    for _ in 0..2 {
+        expected.push(Err(ServerError::DeadlineExceeded.into()));
+    }
+    query_server_assert_result(&mut reader, message, expected).await;
+
+    let client = DbClient::new(address.ip().to_string(), address.port())
+        .await
+        .unwrap();
+    match client.info_server(None).await.unwrap() {
+        ServerResponse::InfoServer(info) => assert_eq!(info.deadlines_exceeded, 2),
+        response => panic!("Unexpected response {response:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_run_server_echos() {
     let server = Server::new(&CONFIG)
         .await
         .expect("Could not initialize server");
@@ -1508,10 +3716,17 @@ async fn test_run_server_echos() {
             let mut expected = ServerResult::with_capacity(2);
             expected.push(Ok(ServerResponse::InfoServer(ServerInfo {
                 address: "127.0.0.1:1369".to_string(),
-                version: *ahnlich_types::version::VERSION,
+                version: *VERSION,
+                min_client_version: *MIN_CLIENT_VERSION,
+                max_client_version: *VERSION,
                 r#type: ahnlich_types::ServerType::Database,
                 limit: CONFIG.common.allocator_size,
                 remaining: 1073614873,
+                request_limits: DEFAULT_REQUEST_LIMITS,
+                in_flight_memory: 0,
+                deadlines_exceeded: 0,
+                priorities: vec![],
+                connections: ConnectionStats::default(),
             })));
             expected.push(Ok(ServerResponse::Pong));
             let stream = TcpStream::connect(address).await.unwrap();
@@ -1524,10 +3739,17 @@ async fn test_run_server_echos() {
             expected.push(Ok(ServerResponse::Pong));
             expected.push(Ok(ServerResponse::InfoServer(ServerInfo {
                 address: "127.0.0.1:1369".to_string(),
-                version: *ahnlich_types::version::VERSION,
+                version: *VERSION,
+                min_client_version: *MIN_CLIENT_VERSION,
+                max_client_version: *VERSION,
                 r#type: ahnlich_types::ServerType::Database,
                 limit: CONFIG.common.allocator_size,
                 remaining: 1073614873,
+                request_limits: DEFAULT_REQUEST_LIMITS,
+                in_flight_memory: 0,
+                deadlines_exceeded: 0,
+                priorities: vec![],
+                connections: ConnectionStats::default(),
             })));
             let stream = TcpStream::connect(address).await.unwrap();
             let mut reader = BufReader::new(stream);
@@ -1537,13 +3759,366 @@ async fn test_run_server_echos() {
     join_all(tasks).await;
 }
 
+async fn http_request(
+    address: std::net::SocketAddr,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> (u16, serde_json::Value) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    timeout(Duration::from_secs(1), stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+static CONFIG_WITH_REQUEST_LIMITS: Lazy<ServerConfig> = Lazy::new(|| {
+    ServerConfig::default()
+        .os_select_port()
+        .batch_size(3)
+        .client_limit("127.0.0.1=2048".parse().unwrap())
+        .store_limit("Small=100,1".parse().unwrap())
+});
+
+#[tokio::test]
+async fn test_request_limits() {
+    let server = Server::new(&CONFIG_WITH_REQUEST_LIMITS)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let entry = |key: f32| {
+        (
+            StoreKey(array![key, key, key]),
+            HashMap::from_iter([(
+                MetadataKey::new("name".into()),
+                MetadataValue::RawString("Ahnlich".into()),
+            )]),
+        )
+    };
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::InfoServer,
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::CreateStore {
+            store: StoreName("Small".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::ListStores,
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![entry(1.0), entry(2.0), entry(3.0)],
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![entry(1.0), entry(2.0), entry(3.0), entry(4.0)],
+        },
+        DBQuery::Set {
+            store: StoreName("Small".to_string()),
+            inputs: vec![entry(1.0), entry(2.0)],
+        },
+        DBQuery::Set {
+            store: StoreName("Small".to_string()),
+            inputs: vec![(
+                StoreKey(array![1.0, 1.0, 1.0]),
+                HashMap::from_iter([(
+                    MetadataKey::new("name".into()),
+                    MetadataValue::RawString("Ahnlich".repeat(20)),
+                )]),
+            )],
+        },
+    ]);
+    let mut expected = ServerResult::with_capacity(8);
+    expected.push(Ok(ServerResponse::InfoServer(ServerInfo {
+        address: "127.0.0.1:1369".to_string(),
+        version: *VERSION,
+        min_client_version: *MIN_CLIENT_VERSION,
+        max_client_version: *VERSION,
+        r#type: ahnlich_types::ServerType::Database,
+        limit: CONFIG.common.allocator_size,
+        remaining: 1073609219,
+        request_limits: RequestLimits {
+            message_size: 2048,
+            batch_size: None,
+        },
+        in_flight_memory: 0,
+        deadlines_exceeded: 0,
+        priorities: vec![],
+        connections: ConnectionStats::default(),
+    })));
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::StoreList(HashSet::from_iter([
+        StoreInfo {
+            name: StoreName("Main".to_string()),
+            len: 0,
+            size_in_bytes: 1720,
+            dimension: NonZeroUsize::new(3).unwrap(),
+            request_limits: RequestLimits {
+                message_size: 1_048_576,
+                batch_size: Some(3),
+            },
+        },
+        StoreInfo {
+            name: StoreName("Small".to_string()),
+            len: 0,
+            size_in_bytes: 1720,
+            dimension: NonZeroUsize::new(3).unwrap(),
+            request_limits: RequestLimits {
+                message_size: 100,
+                batch_size: Some(1),
+            },
+        },
+    ]))));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 3,
+        updated: 0,
+    })));
+    expected.push(Err(ServerError::BatchTooLarge {
+        store: StoreName("Main".to_string()),
+        len: 4,
+        limit: 3,
+    }
+    .into()));
+    expected.push(Err(ServerError::BatchTooLarge {
+        store: StoreName("Small".to_string()),
+        len: 2,
+        limit: 1,
+    }
+    .into()));
+    expected.push(Err(ServerError::RequestTooLarge {
+        store: StoreName("Small".to_string()),
+        size: 209,
+        limit: 100,
+    }
+    .into()));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+
+    // the client limit applies to the whole message before it is read
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::InfoServer,
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: (0..64).map(|key| entry(key as f32)).collect(),
+        },
+    ]);
+    let size = message.serialize().unwrap().len() - ahnlich_types::bincode::RESPONSE_HEADER_LEN;
+    let mut expected = ServerResult::with_capacity(1);
+    expected.push(Err(ErrorResponse::new(
+        ErrorCode::LimitExceeded,
+        "Message cannot exceed 2048 bytes, configure `message_size` or `client-limit` for higher",
+    )
+    .with_metadata("limit", 2048)
+    .with_metadata("size", size)));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+    let mut buf = [0u8; 1];
+    assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+}
+
+static CONFIG_WITH_CLIENT_FILTERS: Lazy<ServerConfig> = Lazy::new(|| {
+    ServerConfig::default()
+        .os_select_port()
+        .client_filter("127.0.0.1=Main:tenant=acme".parse().unwrap())
+});
+
+#[tokio::test]
+async fn test_client_filters() {
+    let server = Server::new(&CONFIG_WITH_CLIENT_FILTERS)
+        .await
+        .expect("Could not initialize server");
+    let address = server.local_addr().expect("Could not get local addr");
+    let _ = tokio::spawn(async move { server.start().await });
+    // Allow some time for the server to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let entry = |key: StoreKey, tenant: &str| {
+        (
+            key,
+            HashMap::from_iter([
+                (
+                    MetadataKey::new("author".into()),
+                    MetadataValue::RawString("jo".into()),
+                ),
+                (
+                    MetadataKey::new("tenant".into()),
+                    MetadataValue::RawString(tenant.into()),
+                ),
+            ]),
+        )
+    };
+    let by_jo = PredicateCondition::Value(Predicate::Equals {
+        key: MetadataKey::new("author".into()),
+        value: MetadataValue::RawString("jo".into()),
+    });
+    let message = ServerDBQuery::from_queries(&[
+        DBQuery::CreateStore {
+            store: StoreName("Main".to_string()),
+            dimension: NonZeroUsize::new(3).unwrap(),
+            create_predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        },
+        DBQuery::Set {
+            store: StoreName("Main".to_string()),
+            inputs: vec![
+                entry(StoreKey(array![1.0, 0.0, 0.0]), "acme"),
+                entry(StoreKey(array![0.0, 1.0, 0.0]), "globex"),
+            ],
+        },
+        DBQuery::GetPred {
+            store: StoreName("Main".to_string()),
+            condition: by_jo.clone(),
+        },
+        DBQuery::GetSimN {
+            store: StoreName("Main".to_string()),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            search_input: StoreKey(array![1.0, 0.0, 0.0]),
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        },
+        DBQuery::CountPred {
+            store: StoreName("Main".to_string()),
+            condition: by_jo.clone(),
+            exact: true,
+        },
+        DBQuery::ListEntries {
+            store: StoreName("Main".to_string()),
+            limit: NonZeroUsize::new(10).unwrap(),
+            cursor: None,
+            include_vectors: true,
+        },
+        // only the entry itself is allowed, which is left out of its results
+        DBQuery::GetSimNByKey {
+            store: StoreName("Main".to_string()),
+            key_id: StoreKeyId::from(&StoreKey(array![1.0, 0.0, 0.0])).into(),
+            closest_n: NonZeroUsize::new(2).unwrap(),
+            algorithm: Algorithm::CosineSimilarity,
+            condition: None,
+        },
+        // the entry of the other tenant is left alone
+        DBQuery::DelPred {
+            store: StoreName("Main".to_string()),
+            condition: by_jo,
+        },
+        // the entry of the other tenant cannot be reached by its key either
+        DBQuery::GetKey {
+            store: StoreName("Main".to_string()),
+            keys: vec![StoreKey(array![0.0, 1.0, 0.0])],
+        },
+        DBQuery::DelKey {
+            store: StoreName("Main".to_string()),
+            keys: vec![StoreKey(array![0.0, 1.0, 0.0])],
+        },
+    ]);
+    let (key, value) = entry(StoreKey(array![1.0, 0.0, 0.0]), "acme");
+    let mut expected = ServerResult::with_capacity(10);
+    expected.push(Ok(ServerResponse::Unit));
+    expected.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 2,
+        updated: 0,
+    })));
+    expected.push(Ok(ServerResponse::Get(vec![(key.clone(), value.clone())])));
+    expected.push(Ok(ServerResponse::GetSimN(vec![(
+        key.clone(),
+        value.clone(),
+        Similarity(1.0),
+    )])));
+    expected.push(Ok(ServerResponse::Count(1)));
+    expected.push(Ok(ServerResponse::EntryList(EntryPage {
+        entries: vec![ListedEntry {
+            key_id: StoreKeyId::from(&key).into(),
+            key: Some(key),
+            value,
+        }],
+        next_cursor: None,
+        estimated_total: 1,
+    })));
+    expected.push(Ok(ServerResponse::GetSimN(vec![])));
+    expected.push(Ok(ServerResponse::Del(1)));
+    expected.push(Ok(ServerResponse::Get(vec![])));
+    expected.push(Err(ServerError::FilteredKeyDelete(StoreName(
+        "Main".to_string(),
+    ))
+    .into()));
+    let stream = TcpStream::connect(address).await.unwrap();
+    let mut reader = BufReader::new(stream);
+    query_server_assert_result(&mut reader, message, expected).await;
+}
+
 async fn query_server_assert_result(
     reader: &mut BufReader<TcpStream>,
     query: ServerDBQuery,
     expected_result: ServerResult,
+) {
+    query_server_assert_result_with_version(reader, query, *VERSION, expected_result).await
+}
+
+async fn query_server_assert_result_with_version(
+    reader: &mut BufReader<TcpStream>,
+    query: ServerDBQuery,
+    version: Version,
+    expected_result: ServerResult,
 ) {
     // Message to send
-    let serialized_message = query.serialize().unwrap();
+    let serialized_message = query.serialize_with_version(&version).unwrap();
 
     // Send the message
     reader.write_all(&serialized_message).await.unwrap();
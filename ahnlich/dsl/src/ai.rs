@@ -10,13 +10,18 @@ use crate::{
     },
 };
 use ahnlich_types::{
-    ai::{AIModel, AIQuery, PreprocessAction},
+    ai::{AIModel, AIQuery, ImagePreprocessing, PreprocessAction, TextTruncation},
     keyval::StoreName,
     metadata::MetadataKey,
+    similarity::{FilterStrategy, FusionStrategy},
 };
 use pest::Parser;
 
-use crate::{error::DslError, predicate::parse_predicate_expression};
+use crate::{
+    error::{Diagnostic, DslError},
+    predicate::parse_predicate_expression,
+    validate::Validator,
+};
 
 fn parse_to_preprocess_action(input: &str) -> Result<PreprocessAction, DslError> {
     match input.to_lowercase().trim() {
@@ -26,7 +31,13 @@ fn parse_to_preprocess_action(input: &str) -> Result<PreprocessAction, DslError>
     }
 }
 
-fn parse_to_ai_model(input: &str) -> Result<AIModel, DslError> {
+pub fn parse_to_ai_model(input: &str) -> Result<AIModel, DslError> {
+    // models from the registry of the AI proxy keep the case of their name
+    if let Some((prefix, name)) = input.trim().split_at_checked(7) {
+        if prefix.eq_ignore_ascii_case("custom:") {
+            return Ok(AIModel::Custom(name.to_string()));
+        }
+    }
     match input.to_lowercase().trim() {
         "all-minilm-l6-v2" => Ok(AIModel::AllMiniLML6V2),
         "all-minilm-l12-v2" => Ok(AIModel::AllMiniLML12V2),
@@ -44,7 +55,10 @@ pub const COMMANDS: &[&str] = &[
     "ping",
     "listclients",
     "liststores",
+    "listsupportedmodels",
     "infoserver",
+    "getmemorybreakdown",
+    "reloadconfig",
     "purgestores",
     "dropstore",                     // store_name if exists can be handled dynamically
     "createpredindex",               // (key_1, key_2) in store_name
@@ -58,6 +72,50 @@ pub const COMMANDS: &[&str] = &[
     "set", // (([This is the life of Haks paragraphed], {name: Haks, category: dev}), ([This is the life of Deven paragraphed], {name: Deven, category: dev})) in store
 ];
 
+fn command_rule(command: &str) -> Option<Rule> {
+    let rule = match command {
+        "ping" => Rule::ping,
+        "liststores" => Rule::list_stores,
+        "listsupportedmodels" => Rule::list_supported_models,
+        "infoserver" => Rule::info_server,
+        "getmemorybreakdown" => Rule::get_memory_breakdown,
+        "reloadconfig" => Rule::reload_config,
+        "purgestores" => Rule::purge_stores,
+        "dropstore" => Rule::drop_store,
+        "createpredindex" => Rule::create_pred_index,
+        "droppredindex" => Rule::drop_pred_index,
+        "createnonlinearalgorithmindex" => Rule::create_non_linear_algorithm_index,
+        "dropnonlinearalgorithmindex" => Rule::drop_non_linear_algorithm_index,
+        "delkey" => Rule::ai_del_key,
+        "getpred" => Rule::get_pred,
+        "getsimn" => Rule::ai_get_sim_n,
+        "createstore" => Rule::ai_create_store,
+        "set" => Rule::ai_set_in_store,
+        _ => return None,
+    };
+    Some(rule)
+}
+
+/// Same as [`parse_ai_query`] but errors are located in the input with suggestions for
+/// misspelt commands, keywords and model names
+pub fn parse_with_diagnostics(input: &str) -> Result<Vec<AIQuery>, Diagnostic> {
+    parse_ai_query(input).map_err(|err| err.into_diagnostic(input, COMMANDS, command_rule))
+}
+
+/// Parses `input` and checks the queries for mistakes the server would reject, like image inputs
+/// to a store created earlier in the input with a text model, without running them
+pub fn validate(input: &str) -> Result<Vec<AIQuery>, Diagnostic> {
+    let queries = parse_with_diagnostics(input)?;
+    Validator::new(None).validate(input, Rule::ai_query, queries, Validator::check_ai_query)
+}
+
+/// Same as [`validate`] but also checks that every store used exists, either in `stores` or by
+/// being created earlier in the input
+pub fn validate_with_stores(input: &str, stores: &[StoreName]) -> Result<Vec<AIQuery>, Diagnostic> {
+    let queries = parse_with_diagnostics(input)?;
+    Validator::new(Some(stores)).validate(input, Rule::ai_query, queries, Validator::check_ai_query)
+}
+
 pub fn parse_ai_query(input: &str) -> Result<Vec<AIQuery>, DslError> {
     let pairs = QueryParser::parse(Rule::ai_query, input).map_err(Box::new)?;
     let statements = pairs.into_iter().collect::<Vec<_>>();
@@ -68,7 +126,10 @@ pub fn parse_ai_query(input: &str) -> Result<Vec<AIQuery>, DslError> {
         let query = match statement.as_rule() {
             Rule::ping => AIQuery::Ping,
             Rule::list_stores => AIQuery::ListStores,
+            Rule::list_supported_models => AIQuery::ListSupportedModels,
             Rule::info_server => AIQuery::InfoServer,
+            Rule::get_memory_breakdown => AIQuery::GetMemoryBreakdown,
+            Rule::reload_config => AIQuery::ReloadConfig,
             Rule::purge_stores => AIQuery::PurgeStores,
             Rule::ai_set_in_store => {
                 let mut inner_pairs = statement.into_inner();
@@ -156,6 +217,11 @@ pub fn parse_ai_query(input: &str) -> Result<Vec<AIQuery>, DslError> {
                     non_linear_indices,
                     error_if_exists,
                     store_original,
+                    image_preprocessing: ImagePreprocessing::default(),
+                    text_truncation: TextTruncation::default(),
+                    timestamp_key: None,
+                    multimodal_fusion: None,
+                    eviction: None,
                 }
             }
             Rule::ai_get_sim_n => {
@@ -203,6 +269,16 @@ pub fn parse_ai_query(input: &str) -> Result<Vec<AIQuery>, DslError> {
                     algorithm,
                     condition,
                     preprocess_action,
+                    include_system_metadata: false,
+                    min_score: None,
+                    max_distance: None,
+                    normalize_scores: false,
+                    group_by: None,
+                    group_size: NonZeroUsize::new(1).unwrap(),
+                    additional_search_inputs: vec![],
+                    fusion: FusionStrategy::Mean,
+                    recency_boost: None,
+                    filter_strategy: FilterStrategy::Auto,
                 }
             }
             Rule::get_pred => {
@@ -217,6 +293,7 @@ pub fn parse_ai_query(input: &str) -> Result<Vec<AIQuery>, DslError> {
                 AIQuery::GetPred {
                     store: StoreName(store.to_string()),
                     condition: parse_predicate_expression(predicate_conditions)?,
+                    include_system_metadata: false,
                 }
             }
             Rule::ai_del_key => {
@@ -10,10 +10,19 @@ use crate::{
         parse_drop_non_linear_algorithm_index, parse_drop_pred_index, parse_drop_store,
     },
 };
-use ahnlich_types::{db::DBQuery, keyval::StoreName, metadata::MetadataKey};
+use ahnlich_types::{
+    db::DBQuery,
+    keyval::{KeyElementType, NonFiniteVectors, StorageTier, StoreName, VectorNormalization},
+    metadata::MetadataKey,
+    similarity::{FilterStrategy, FusionStrategy},
+};
 use pest::Parser;
 
-use crate::{error::DslError, predicate::parse_predicate_expression};
+use crate::{
+    error::{Diagnostic, DslError},
+    predicate::parse_predicate_expression,
+    validate::Validator,
+};
 
 // Parse raw strings separated by ; into a Vec<DBQuery>. Examples include but are not restricted
 // to
@@ -22,6 +31,8 @@ pub const COMMANDS: &[&str] = &[
     "listclients",
     "liststores",
     "infoserver",
+    "getmemorybreakdown",
+    "reloadconfig",
     "dropstore",                     // store_name if exists can be handled dynamically
     "createpredindex",               // (key_1, key_2) in store_name
     "droppredindex",                 // if exists (key1, key2) in store_name
@@ -31,10 +42,56 @@ pub const COMMANDS: &[&str] = &[
     "delkey",                        // ([1.2, 3.0], [5.6, 7.8]) in my_store
     "getpred",                       // ((author = dickens) or (country != Nigeria)) in my_store
     "getsimn", // 4 with [0.65, 2.78] using cosinesimilarity in my_store where (author = dickens)
+    "getsimnbykey", // 4 with key af1349b9f5f9a1a6 using cosinesimilarity in my_store where (author = dickens)
     "createstore", // if not exists my_store dimension 21 predicates (author, country) nonlinearalgorithmindex (kdtree)
     "set", // (([1.0, 2.1, 3.2], {name: Haks, category: dev}), ([3.1, 4.8, 5.0], {name: Deven, category: dev})) in store
 ];
 
+fn command_rule(command: &str) -> Option<Rule> {
+    let rule = match command {
+        "ping" => Rule::ping,
+        "listclients" => Rule::list_clients,
+        "liststores" => Rule::list_stores,
+        "infoserver" => Rule::info_server,
+        "getmemorybreakdown" => Rule::get_memory_breakdown,
+        "reloadconfig" => Rule::reload_config,
+        "dropstore" => Rule::drop_store,
+        "createpredindex" => Rule::create_pred_index,
+        "droppredindex" => Rule::drop_pred_index,
+        "createnonlinearalgorithmindex" => Rule::create_non_linear_algorithm_index,
+        "dropnonlinearalgorithmindex" => Rule::drop_non_linear_algorithm_index,
+        "getkey" => Rule::get_key,
+        "delkey" => Rule::del_key,
+        "getpred" => Rule::get_pred,
+        "getsimn" => Rule::get_sim_n,
+        "getsimnbykey" => Rule::get_sim_n_by_key,
+        "createstore" => Rule::create_store,
+        "set" => Rule::set_in_store,
+        _ => return None,
+    };
+    Some(rule)
+}
+
+/// Same as [`parse_db_query`] but errors are located in the input with suggestions for
+/// misspelt commands and keywords
+pub fn parse_with_diagnostics(input: &str) -> Result<Vec<DBQuery>, Diagnostic> {
+    parse_db_query(input).map_err(|err| err.into_diagnostic(input, COMMANDS, command_rule))
+}
+
+/// Parses `input` and checks the queries for mistakes the server would reject, like keys that do
+/// not match the dimension of a store created earlier in the input, without running them
+pub fn validate(input: &str) -> Result<Vec<DBQuery>, Diagnostic> {
+    let queries = parse_with_diagnostics(input)?;
+    Validator::new(None).validate(input, Rule::db_query, queries, Validator::check_db_query)
+}
+
+/// Same as [`validate`] but also checks that every store used exists, either in `stores` or by
+/// being created earlier in the input
+pub fn validate_with_stores(input: &str, stores: &[StoreName]) -> Result<Vec<DBQuery>, Diagnostic> {
+    let queries = parse_with_diagnostics(input)?;
+    Validator::new(Some(stores)).validate(input, Rule::db_query, queries, Validator::check_db_query)
+}
+
 pub fn parse_db_query(input: &str) -> Result<Vec<DBQuery>, DslError> {
     let pairs = QueryParser::parse(Rule::db_query, input).map_err(Box::new)?;
     let statements = pairs.into_iter().collect::<Vec<_>>();
@@ -47,6 +104,8 @@ pub fn parse_db_query(input: &str) -> Result<Vec<DBQuery>, DslError> {
             Rule::list_clients => DBQuery::ListClients,
             Rule::list_stores => DBQuery::ListStores,
             Rule::info_server => DBQuery::InfoServer,
+            Rule::get_memory_breakdown => DBQuery::GetMemoryBreakdown,
+            Rule::reload_config => DBQuery::ReloadConfig,
             Rule::set_in_store => {
                 let mut inner_pairs = statement.into_inner();
                 let store_keys_to_store_values = inner_pairs
@@ -75,11 +134,16 @@ pub fn parse_db_query(input: &str) -> Result<Vec<DBQuery>, DslError> {
                     .next()
                     .ok_or(DslError::UnexpectedSpan((start_pos, end_pos)))?
                     .as_str();
-                let dimension = inner_pairs
-                    .next()
-                    .ok_or(DslError::UnexpectedSpan((start_pos, end_pos)))?
-                    .as_str()
-                    .parse::<NonZeroUsize>()?;
+                // without a dimension the store takes that of the first keys set into it
+                let mut dimension = None;
+                if let Some(next_pair) = inner_pairs.peek() {
+                    if next_pair.as_rule() == Rule::non_zero {
+                        dimension = inner_pairs
+                            .next()
+                            .map(|pair| pair.as_str().parse::<NonZeroUsize>())
+                            .transpose()?;
+                    }
+                };
                 let mut create_predicates = HashSet::new();
                 if let Some(next_pair) = inner_pairs.peek() {
                     if next_pair.as_rule() == Rule::metadata_keys {
@@ -106,10 +170,18 @@ pub fn parse_db_query(input: &str) -> Result<Vec<DBQuery>, DslError> {
                 };
                 DBQuery::CreateStore {
                     store: StoreName(store.to_string()),
-                    dimension,
+                    dimension: dimension.unwrap_or(NonZeroUsize::MIN),
                     create_predicates,
                     non_linear_indices,
                     error_if_exists,
+                    timestamp_key: None,
+                    storage_tier: StorageTier::Memory,
+                    infer_dimension: dimension.is_none(),
+                    key_element_type: KeyElementType::Float32,
+                    normalization: VectorNormalization::None,
+                    index_seed: None,
+                    non_finite_vectors: NonFiniteVectors::Reject,
+                    eviction: None,
                 }
             }
             Rule::get_sim_n => {
@@ -144,6 +216,53 @@ pub fn parse_db_query(input: &str) -> Result<Vec<DBQuery>, DslError> {
                     closest_n,
                     algorithm,
                     condition,
+                    min_score: None,
+                    max_distance: None,
+                    normalize_scores: false,
+                    group_by: None,
+                    group_size: NonZeroUsize::new(1).unwrap(),
+                    additional_search_inputs: vec![],
+                    fusion: FusionStrategy::Mean,
+                    recency_boost: None,
+                    filter_strategy: FilterStrategy::Auto,
+                    search_terms: vec![],
+                    exclude_keys: vec![],
+                    unordered_ties: false,
+                }
+            }
+            Rule::get_sim_n_by_key => {
+                let mut inner_pairs = statement.into_inner();
+                let closest_n = inner_pairs
+                    .next()
+                    .ok_or(DslError::UnexpectedSpan((start_pos, end_pos)))?
+                    .as_str()
+                    .parse::<NonZeroUsize>()?;
+                let key_id = inner_pairs
+                    .next()
+                    .ok_or(DslError::UnexpectedSpan((start_pos, end_pos)))?
+                    .as_str()
+                    .to_lowercase();
+                let algorithm = to_algorithm(
+                    inner_pairs
+                        .next()
+                        .ok_or(DslError::UnexpectedSpan((start_pos, end_pos)))?
+                        .as_str(),
+                )?;
+                let store = inner_pairs
+                    .next()
+                    .ok_or(DslError::UnexpectedSpan((start_pos, end_pos)))?
+                    .as_str();
+                let condition = if let Some(predicate_conditions) = inner_pairs.next() {
+                    Some(parse_predicate_expression(predicate_conditions)?)
+                } else {
+                    None
+                };
+                DBQuery::GetSimNByKey {
+                    store: StoreName(store.to_string()),
+                    key_id,
+                    closest_n,
+                    algorithm,
+                    condition,
                 }
             }
             Rule::get_pred => {
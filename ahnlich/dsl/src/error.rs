@@ -1,6 +1,9 @@
+use std::fmt;
 use std::num::ParseIntError;
 
-use crate::parser::Rule;
+use crate::parser::{QueryParser, Rule};
+use pest::error::{ErrorVariant, InputLocation};
+use pest::Parser;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -22,3 +25,279 @@ pub enum DslError {
     #[error("Unexpected preprocessing {0:?}")]
     UnsupportedPreprocessingMode(String),
 }
+
+const ALGORITHMS: &[&str] = &[
+    "kdtree",
+    "cosinesimilarity",
+    "dotproductsimilarity",
+    "euclideandistance",
+];
+
+const AI_MODELS: &[&str] = &[
+    "all-minilm-l6-v2",
+    "all-minilm-l12-v2",
+    "bge-base-en-v1.5",
+    "bge-large-en-v1.5",
+    "resnet-50",
+    "clip-vit-b32-image",
+];
+
+const PREPROCESS_ACTIONS: &[&str] = &["nopreprocessing", "modelpreprocessing"];
+
+// keywords that can appear after the command of a statement
+const KEYWORDS: &[&str] = &[
+    "and",
+    "dimension",
+    "exists",
+    "if",
+    "in",
+    "indexmodel",
+    "nonlinearalgorithmindex",
+    "not",
+    "or",
+    "predicates",
+    "preprocessaction",
+    "querymodel",
+    "storeoriginal",
+    "using",
+    "where",
+    "with",
+];
+
+/// Syntax error located in the input of a query, rendered with the offending line and a
+/// suggestion when the offending token is close to a known keyword
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// 1-based line of the offending token
+    pub line: usize,
+    /// 1-based column of the offending token, counted in characters
+    pub column: usize,
+    pub token: Option<String>,
+    pub suggestion: Option<String>,
+    source_line: String,
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{gutter}--> {}:{}", self.line, self.column)?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        let underline = "^".repeat(self.token.as_ref().map_or(1, |t| t.chars().count().max(1)));
+        write!(f, "{gutter} | {}{underline}", " ".repeat(self.column - 1))?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n{gutter} = help: did you mean `{suggestion}`?")?;
+        }
+        Ok(())
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn new(
+        input: &str,
+        position: usize,
+        message: String,
+        token: Option<String>,
+    ) -> Self {
+        let position = position.min(input.len());
+        let line_start = input[..position].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[position..]
+            .find('\n')
+            .map_or(input.len(), |i| position + i);
+        Self {
+            message,
+            line: input[..position].matches('\n').count() + 1,
+            column: input[line_start..position].chars().count() + 1,
+            token,
+            suggestion: None,
+            source_line: input[line_start..line_end].to_string(),
+        }
+    }
+
+    fn suggest_from(mut self, candidates: &[&str]) -> Self {
+        self.suggestion = self
+            .token
+            .as_deref()
+            .and_then(|token| suggest(token, candidates.iter().copied()))
+            .map(ToString::to_string);
+        self
+    }
+}
+
+/// Closest candidate to a misspelt `token`, if any is close enough to be a likely typo
+pub fn suggest<'a>(token: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let token = token.to_lowercase();
+    let max_distance = (token.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&token, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance > 0 && *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn token_at(input: &str, position: usize) -> Option<String> {
+    let token: String = input[position.min(input.len())..]
+        .chars()
+        .take_while(|c| !c.is_whitespace() && !"()[]{},;:".contains(*c))
+        .collect();
+    (!token.is_empty()).then_some(token)
+}
+
+fn describe(rule: &Rule) -> String {
+    format!("{rule:?}").replace('_', " ")
+}
+
+/// furthest position the parser reached before failing and what it expected to find there
+fn failure(error: &pest::error::Error<Rule>) -> (usize, Vec<String>) {
+    if let Some(attempts) = error.parse_attempts() {
+        let mut expected: Vec<_> = attempts
+            .expected_tokens()
+            .into_iter()
+            .map(|token| token.to_string().to_lowercase())
+            // keep literal keywords, leaving out character ranges and builtin rules
+            .filter(|token| {
+                token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+            })
+            .map(|token| format!("`{token}`"))
+            .collect();
+        // rules are only named when no keyword was expected as they tend to be whole statements
+        if expected.is_empty() {
+            expected = attempts
+                .call_stacks()
+                .iter()
+                .filter_map(|stack| stack.deepest.get_rule().map(describe))
+                .collect();
+        }
+        return (attempts.max_position, expected);
+    }
+    let position = match error.location {
+        InputLocation::Pos(position) => position,
+        InputLocation::Span((start, _)) => start,
+    };
+    let expected = match &error.variant {
+        ErrorVariant::ParsingError { positives, .. } => positives.iter().map(describe).collect(),
+        ErrorVariant::CustomError { .. } => vec![],
+    };
+    (position, expected)
+}
+
+fn unexpected_message(expected: &[String], token: &Option<String>, context: &str) -> String {
+    let found = token
+        .as_ref()
+        .map_or("end of input".to_string(), |token| format!("`{token}`"));
+    if expected.is_empty() {
+        format!("unexpected {found}{context}")
+    } else {
+        format!("expected {}, found {found}{context}", expected.join(" or "))
+    }
+}
+
+impl DslError {
+    /// Locates the error within `input`. `commands` are the statements of the query language and
+    /// `command_rule` gives the grammar rule of a statement, which is used to find exactly where
+    /// an invalid statement stops matching
+    pub(crate) fn into_diagnostic(
+        self,
+        input: &str,
+        commands: &[&str],
+        command_rule: fn(&str) -> Option<Rule>,
+    ) -> Diagnostic {
+        let locate = |needle: &str| input.to_lowercase().find(needle).unwrap_or(0);
+        match self {
+            DslError::RuleParse(error) => {
+                let (position, expected) = failure(&error);
+                let token = token_at(input, position);
+                let message = unexpected_message(&expected, &token, "");
+                Diagnostic::new(input, position, message, token).suggest_from(KEYWORDS)
+            }
+            DslError::UnexpectedSpan((start, end)) => {
+                let statement = &input[start..end];
+                let offset = start + (statement.len() - statement.trim_start().len());
+                let statement = statement.trim();
+                let Some(command) = token_at(statement, 0) else {
+                    return Diagnostic::new(input, offset, "empty statement".to_string(), None);
+                };
+                let Some(rule) = command_rule(&command.to_lowercase()) else {
+                    return Diagnostic::new(
+                        input,
+                        offset,
+                        format!("unknown command `{command}`"),
+                        Some(command),
+                    )
+                    .suggest_from(commands);
+                };
+                // reparse the statement on its own, tracking parse attempts, to find where it
+                // stops matching and what was expected there
+                pest::set_error_detail(true);
+                let (position, expected) = match QueryParser::parse(rule, statement) {
+                    Ok(pairs) => (pairs.last().map_or(0, |pair| pair.as_span().end()), vec![]),
+                    Err(error) => failure(&error),
+                };
+                let token = token_at(statement, position);
+                let message =
+                    unexpected_message(&expected, &token, &format!(" in {command} statement"));
+                let keywords: Vec<&str> = expected
+                    .iter()
+                    .filter_map(|expected| expected.strip_prefix('`')?.strip_suffix('`'))
+                    .collect();
+                let candidates = if keywords.is_empty() {
+                    KEYWORDS
+                } else {
+                    &keywords
+                };
+                Diagnostic::new(input, offset + position, message, token).suggest_from(candidates)
+            }
+            DslError::UnsupportedAlgorithm(algorithm) => Diagnostic::new(
+                input,
+                locate(&algorithm),
+                format!("unsupported algorithm `{algorithm}`"),
+                Some(algorithm),
+            )
+            .suggest_from(ALGORITHMS),
+            DslError::UnsupportedAIModel(model) => Diagnostic::new(
+                input,
+                locate(&model),
+                format!("unsupported ai model `{model}`"),
+                Some(model),
+            )
+            .suggest_from(AI_MODELS),
+            DslError::UnsupportedPreprocessingMode(action) => Diagnostic::new(
+                input,
+                locate(&action),
+                format!("unsupported preprocess action `{action}`"),
+                Some(action),
+            )
+            .suggest_from(PREPROCESS_ACTIONS),
+            DslError::UnexpectedHex(hex) => Diagnostic::new(
+                input,
+                locate(&hex.to_lowercase()),
+                format!("invalid image hex `{hex}`"),
+                Some(hex),
+            ),
+            err @ (DslError::NonZeroUsizeParse(_) | DslError::UnsupportedRule(_)) => {
+                Diagnostic::new(input, 0, err.to_string(), None)
+            }
+        }
+    }
+}
@@ -9,3 +9,4 @@ mod predicate;
 mod shared;
 #[cfg(test)]
 mod tests;
+mod validate;
@@ -1,6 +1,6 @@
 use crate::error::DslError;
 use ahnlich_types::{
-    ai::{AIModel, AIQuery, PreprocessAction},
+    ai::{AIModel, AIQuery, ImagePreprocessing, PreprocessAction, TextTruncation},
     keyval::{StoreInput, StoreName},
     metadata::MetadataKey,
 };
@@ -13,10 +13,10 @@ use std::{
 use ahnlich_types::{
     metadata::MetadataValue,
     predicate::{Predicate, PredicateCondition},
-    similarity::{Algorithm, NonLinearAlgorithm},
+    similarity::{Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm},
 };
 
-use crate::ai::parse_ai_query;
+use crate::ai::{parse_ai_query, parse_with_diagnostics, validate, validate_with_stores};
 
 #[test]
 fn test_single_query_parse() {
@@ -39,10 +39,14 @@ fn test_single_query_parse() {
 
 #[test]
 fn test_multi_query_parse() {
-    let input = r#" INFOSERVER ; listSTORES;"#;
+    let input = r#" INFOSERVER ; listSTORES; ListSupportedModels"#;
     assert_eq!(
         parse_ai_query(input).expect("Could not parse query input"),
-        vec![AIQuery::InfoServer, AIQuery::ListStores]
+        vec![
+            AIQuery::InfoServer,
+            AIQuery::ListStores,
+            AIQuery::ListSupportedModels
+        ]
     );
 }
 
@@ -141,6 +145,11 @@ fn test_create_store_parse() {
             non_linear_indices: HashSet::new(),
             error_if_exists: true,
             store_original: false,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         }]
     );
     let input = r#"CREATEstore IF NOT EXISTS storename QUERYMODEL resnet-50 INDEXMODEL all-minilm-l6-v2 PREDICATES (department, faculty) STOREORIGINAL"#;
@@ -157,6 +166,11 @@ fn test_create_store_parse() {
             non_linear_indices: HashSet::new(),
             error_if_exists: false,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         }]
     );
     let input = r#"createstore school QUERYMODEL all-minilm-l6-v2 INDEXMODEL resnet-50 NONLINEARALGORITHMINDEX (kdtree) STOREORIGINAL"#;
@@ -170,6 +184,29 @@ fn test_create_store_parse() {
             non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
             error_if_exists: true,
             store_original: true,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
+        }]
+    );
+    let input = r#"createstore papers QUERYMODEL custom:SciBERT-v1 INDEXMODEL custom:SciBERT-v1"#;
+    assert_eq!(
+        parse_ai_query(input).expect("Could not parse query input"),
+        vec![AIQuery::CreateStore {
+            store: StoreName("papers".to_string()),
+            query_model: AIModel::Custom("SciBERT-v1".to_string()),
+            index_model: AIModel::Custom("SciBERT-v1".to_string()),
+            predicates: HashSet::new(),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            store_original: false,
+            image_preprocessing: ImagePreprocessing::default(),
+            text_truncation: TextTruncation::default(),
+            timestamp_key: None,
+            multimodal_fusion: None,
+            eviction: None,
         }]
     );
 }
@@ -209,6 +246,16 @@ fn test_get_sim_n_parse() {
             algorithm: Algorithm::CosineSimilarity,
             condition: None,
             preprocess_action: PreprocessAction::ModelPreprocessing,
+            include_system_metadata: false,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
         }]
     );
     let input = r#"GETSIMN 8 with [testing the limits of life] using euclideandistance in other where ((year != 2012) AND (month not in (december, october)))"#;
@@ -233,6 +280,16 @@ fn test_get_sim_n_parse() {
                 }))
             ),
             preprocess_action: PreprocessAction::NoPreprocessing,
+            include_system_metadata: false,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
         }]
     );
 }
@@ -284,6 +341,7 @@ fn test_get_pred_parse() {
                 key: MetadataKey::new("surname".into()),
                 value: MetadataValue::RawString("charles".to_string())
             })),
+            include_system_metadata: false,
         }]
     );
     let input = r#"GETPRED ((pages in (0, 1, 2)) AND (author != dickens) OR (author NOT in (jk-rowlins, rick-riodan)) ) in bookshelf"#;
@@ -311,7 +369,8 @@ fn test_get_pred_parse() {
                         MetadataValue::RawString("rick-riodan".to_string()),
                     ]),
                 }))
-            )
+            ),
+            include_system_metadata: false,
         }]
     );
 }
@@ -382,3 +441,41 @@ fn test_set_in_store_parse() {
         }]
     );
 }
+
+#[test]
+fn test_parse_with_diagnostics() {
+    let input = "createstore x querymodel all-minilm-l6-v3 indexmodel resnet-50";
+    let diagnostic = parse_with_diagnostics(input).unwrap_err();
+    assert_eq!((diagnostic.line, diagnostic.column), (1, 26));
+    assert_eq!(diagnostic.token.as_deref(), Some("all-minilm-l6-v3"));
+    assert_eq!(diagnostic.suggestion.as_deref(), Some("all-minilm-l6-v2"));
+
+    let input = "getsimn 4 with [hello] usin cosinesimilarity in store";
+    let diagnostic = parse_with_diagnostics(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "expected `using`, found `usin` in getsimn statement"
+    );
+    assert_eq!(diagnostic.suggestion.as_deref(), Some("using"));
+
+    let input = "purgestore";
+    let diagnostic = parse_with_diagnostics(input).unwrap_err();
+    assert_eq!(diagnostic.suggestion.as_deref(), Some("purgestores"));
+}
+
+#[test]
+fn test_validate() {
+    let input = "createstore books querymodel all-minilm-l6-v2 indexmodel all-minilm-l6-v2; set (([a tale of two cities], {author: dickens})) in books preprocessaction nopreprocessing";
+    assert_eq!(validate(input).unwrap().len(), 2);
+
+    let input = "createstore books querymodel all-minilm-l6-v2 indexmodel all-minilm-l6-v2; getsimn 2 with [/x0a0b] using cosinesimilarity in books";
+    let diagnostic = validate(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "query model of store `books` expects RawString inputs but found Image"
+    );
+
+    let input = "purgestores; getpred ((author = dickens)) in books";
+    let diagnostic = validate_with_stores(input, &[StoreName("books".to_string())]).unwrap_err();
+    assert_eq!(diagnostic.message, "store `books` does not exist");
+}
@@ -1,7 +1,9 @@
 use crate::error::DslError;
 use ahnlich_types::{
     db::DBQuery,
-    keyval::{StoreKey, StoreName},
+    keyval::{
+        KeyElementType, NonFiniteVectors, StorageTier, StoreKey, StoreName, VectorNormalization,
+    },
     metadata::MetadataKey,
 };
 use ndarray::Array1;
@@ -14,10 +16,10 @@ use std::{
 use ahnlich_types::{
     metadata::MetadataValue,
     predicate::{Predicate, PredicateCondition},
-    similarity::{Algorithm, NonLinearAlgorithm},
+    similarity::{Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm},
 };
 
-use crate::db::parse_db_query;
+use crate::db::{parse_db_query, parse_with_diagnostics, validate, validate_with_stores};
 
 #[test]
 fn test_single_query_parse() {
@@ -45,6 +47,16 @@ fn test_multi_query_parse() {
         parse_db_query(input).expect("Could not parse query input"),
         vec![DBQuery::InfoServer, DBQuery::ListStores]
     );
+    let input = r#"infoserver; GetMemoryBreakdown"#;
+    assert_eq!(
+        parse_db_query(input).expect("Could not parse query input"),
+        vec![DBQuery::InfoServer, DBQuery::GetMemoryBreakdown]
+    );
+    let input = r#"ReloadConfig"#;
+    assert_eq!(
+        parse_db_query(input).expect("Could not parse query input"),
+        vec![DBQuery::ReloadConfig]
+    );
 }
 
 #[test]
@@ -72,7 +84,15 @@ fn test_create_store_parse() {
             dimension: NonZeroUsize::new(23).unwrap(),
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
-            error_if_exists: true
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         }]
     );
     let input = r#"CREATEstore IF NOT EXISTS testing DIMENSION 43"#;
@@ -83,7 +103,15 @@ fn test_create_store_parse() {
             dimension: NonZeroUsize::new(43).unwrap(),
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::new(),
-            error_if_exists: false
+            error_if_exists: false,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         }]
     );
     let input = r#"CREATEstore IF NOT EXISTS school DIMENSION 39 PREDICATES (department, faculty)"#;
@@ -97,7 +125,15 @@ fn test_create_store_parse() {
                 MetadataKey::new("faculty".to_string()),
             ]),
             non_linear_indices: HashSet::new(),
-            error_if_exists: false
+            error_if_exists: false,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         }]
     );
     let input = r#"CREATEstore school DIMENSION 39 NONLINEARALGORITHMINDEX (kdtree)"#;
@@ -108,7 +144,15 @@ fn test_create_store_parse() {
             dimension: NonZeroUsize::new(39).unwrap(),
             create_predicates: HashSet::new(),
             non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
-            error_if_exists: true
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         }]
     );
     let input = r#"CREATEstore school DIMENSION 77 PREDICATES(name, surname) NONLINEARALGORITHMINDEX (kdtree)"#;
@@ -122,7 +166,35 @@ fn test_create_store_parse() {
                 MetadataKey::new("surname".to_string()),
             ]),
             non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
-            error_if_exists: true
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: false,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
+        }]
+    );
+    // without a dimension it is inferred from the first set
+    let input = r#"CREATEstore school PREDICATES(name)"#;
+    assert_eq!(
+        parse_db_query(input).expect("Could not parse query input"),
+        vec![DBQuery::CreateStore {
+            store: StoreName("school".to_string()),
+            dimension: NonZeroUsize::MIN,
+            create_predicates: HashSet::from_iter([MetadataKey::new("name".to_string())]),
+            non_linear_indices: HashSet::new(),
+            error_if_exists: true,
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            infer_dimension: true,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: None,
+            non_finite_vectors: NonFiniteVectors::Reject,
+            eviction: None,
         }]
     );
 }
@@ -227,7 +299,19 @@ fn test_get_sim_n_parse() {
             search_input: StoreKey(Array1::from_iter([34.1, 72.2])),
             closest_n: NonZeroUsize::new(5).unwrap(),
             algorithm: Algorithm::CosineSimilarity,
-            condition: None
+            condition: None,
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
         }]
     );
     let input = r#"GETSIMN 8 with [3.7, 9.6] using euclideandistance in other where ((year != 2012) AND (month not in (december, october)))"#;
@@ -251,6 +335,39 @@ fn test_get_sim_n_parse() {
                     ]),
                 }))
             ),
+            min_score: None,
+            max_distance: None,
+            normalize_scores: false,
+            group_by: None,
+            group_size: NonZeroUsize::new(1).unwrap(),
+            additional_search_inputs: vec![],
+            fusion: FusionStrategy::Mean,
+            recency_boost: None,
+            filter_strategy: FilterStrategy::Auto,
+            search_terms: vec![],
+            exclude_keys: vec![],
+            unordered_ties: false,
+        }]
+    );
+}
+
+#[test]
+fn test_get_sim_n_by_key_parse() {
+    let input = r#"GETSIMNBYKEY 3 with [0.1, 0.2] using cosinesimilarity in store1"#;
+    assert!(parse_db_query(input).is_err());
+    let input =
+        r#"GETSIMNBYKEY 3 with key AF1349b9 using euclideandistance in store1 where (author = hi)"#;
+    assert_eq!(
+        parse_db_query(input).expect("Could not parse query input"),
+        vec![DBQuery::GetSimNByKey {
+            store: StoreName("store1".to_string()),
+            key_id: "af1349b9".to_string(),
+            closest_n: NonZeroUsize::new(3).unwrap(),
+            algorithm: Algorithm::EuclideanDistance,
+            condition: Some(PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new("author".into()),
+                value: MetadataValue::RawString("hi".to_string())
+            })),
         }]
     );
 }
@@ -411,3 +528,96 @@ fn test_get_pred_parse() {
         }]
     );
 }
+
+#[test]
+fn test_parse_with_diagnostics() {
+    let input = "ping; getsmn 4 with [1.0] using cosinesimilarity in store";
+    let diagnostic = parse_with_diagnostics(input).unwrap_err();
+    assert_eq!(diagnostic.message, "unknown command `getsmn`");
+    assert_eq!((diagnostic.line, diagnostic.column), (1, 7));
+    assert_eq!(diagnostic.suggestion.as_deref(), Some("getsimn"));
+
+    // the dimension is optional so a misspelt one is simply not expected
+    let input = "ping;\ncreatestore main dimensio 3";
+    let diagnostic = parse_with_diagnostics(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "unexpected `dimensio` in createstore statement"
+    );
+    assert_eq!((diagnostic.line, diagnostic.column), (2, 18));
+    assert_eq!(diagnostic.token.as_deref(), Some("dimensio"));
+    assert_eq!(diagnostic.suggestion.as_deref(), Some("dimension"));
+    assert_eq!(
+        diagnostic.to_string(),
+        "error: unexpected `dimensio` in createstore statement
+ --> 2:18
+  |
+2 | createstore main dimensio 3
+  |                  ^^^^^^^^
+  = help: did you mean `dimension`?"
+    );
+
+    let input = "getsimn 4 with [1.0] using cosinesimilarity";
+    let diagnostic = parse_with_diagnostics(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "expected `in`, found end of input in getsimn statement"
+    );
+    assert_eq!(diagnostic.suggestion, None);
+}
+
+#[test]
+fn test_validate() {
+    let input = "createstore main dimension 2; set (([1.0, 2.0], {page: 1})) in main; getsimn 2 with [1.0, 2.0] using cosinesimilarity in main";
+    assert_eq!(validate(input).unwrap().len(), 3);
+
+    let input = "createstore main dimension 2; set (([1.0, 2.0, 3.0], {page: 1})) in main";
+    let diagnostic = validate(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "key of dimension 3 does not match the dimension 2 of store `main`"
+    );
+    assert_eq!(diagnostic.column, 31);
+
+    let input =
+        "createstore main; set (([1.0, 2.0, 3.0], {page: 1})) in main; getkey ([1.0, 2.0]) in main";
+    let diagnostic = validate(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "key of dimension 2 does not match the dimension 3 of store `main`"
+    );
+
+    let input = "getkey ([1.0, 2.0], [1.0]) in other";
+    let diagnostic = validate(input).unwrap_err();
+    assert_eq!(diagnostic.message, "keys have different dimensions 2 and 1");
+
+    let input = "createstore main dimension 2; getsimn 2 with [1.0, 2.0] using kdtree in main";
+    let diagnostic = validate(input).unwrap_err();
+    assert_eq!(diagnostic.token.as_deref(), Some("kdtree"));
+    assert_eq!(diagnostic.column, 63);
+    let input = "createstore main dimension 2; createnonlinearalgorithmindex (kdtree) in main; getsimn 2 with [1.0, 2.0] using kdtree in main";
+    assert!(validate(input).is_ok());
+
+    let input = "getpred ((page in (1, /x0a))) in main";
+    let diagnostic = validate(input).unwrap_err();
+    assert_eq!(
+        diagnostic.message,
+        "values compared to `page` mix text and images"
+    );
+
+    // store existence is only checked when the existing stores are known
+    let input = "getkey ([1.0, 2.0]) in mian";
+    assert!(validate(input).is_ok());
+    let diagnostic = validate_with_stores(input, &[StoreName("main".to_string())]).unwrap_err();
+    assert_eq!(diagnostic.message, "store `mian` does not exist");
+    assert_eq!(diagnostic.column, 24);
+    let input = "dropstore main; getkey ([1.0, 2.0]) in main";
+    assert!(validate_with_stores(input, &[StoreName("main".to_string())]).is_err());
+    let input = "createstore main dimension 2";
+    assert_eq!(
+        validate_with_stores(input, &[StoreName("main".to_string())])
+            .unwrap_err()
+            .message,
+        "store `main` already exists"
+    );
+}
@@ -1,2 +1,3 @@
 mod ai;
 mod db;
+mod properties;
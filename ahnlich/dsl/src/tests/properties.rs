@@ -0,0 +1,116 @@
+use crate::{ai, db};
+use proptest::prelude::*;
+
+/// Valid queries mutated into the inputs the parsers are checked against
+const DB_QUERIES: &[&str] = &[
+    "PING",
+    "CREATESTORE IF NOT EXISTS bookshelf DIMENSION 2 PREDICATES (author, country) NONLINEARALGORITHMINDEX (kdtree)",
+    "SET (([1.0, 2.1], {name: Haks, category: dev}), ([3.1, 4.8], {name: Deven, category: dev})) in store",
+    "GETPRED ((pages in (0, 1, 2)) AND (author != dickens) OR (author NOT in (jk-rowlins, rick-riodan)) ) in bookshelf",
+    "GETSIMN 4 WITH [0.2, 0.1] USING cosinesimilarity IN store WHERE (author = dickens)",
+    "DELKEY ([1.2, 3.0], [5.6, 7.8]) IN store",
+    "DROPPREDINDEX IF EXISTS (off) in storememe",
+];
+
+const AI_QUERIES: &[&str] = &[
+    "CREATEstore IF NOT EXISTS storename QUERYMODEL resnet-50 INDEXMODEL all-minilm-l6-v2 PREDICATES (department, faculty) STOREORIGINAL",
+    "GETSIMN 8 with [testing the limits of life] using euclideandistance in other where ((year != 2012) AND (month not in (december, october)))",
+    "set (([This is the life of Haks paragraphed], {name: Haks, category: dev}), ([This is the life of Deven paragraphed], {name: Deven, category: dev})) in store preprocessaction nopreprocessing",
+    "DELKEY ([hi this is store input], [this does not get parsed yet]) in 1234",
+];
+
+/// Pieces of the grammar, so that inputs get past the first keyword more often than random text
+const TOKENS: &[&str] = &[
+    "getsimn",
+    "getpred",
+    "getkey",
+    "set",
+    "delkey",
+    "createstore",
+    "dropstore",
+    "with",
+    "using",
+    "in",
+    "not",
+    "where",
+    "and",
+    "or",
+    "if",
+    "exists",
+    "dimension",
+    "predicates",
+    "querymodel",
+    "indexmodel",
+    "cosinesimilarity",
+    "kdtree",
+    "(",
+    ")",
+    "[",
+    "]",
+    "{",
+    "}",
+    ",",
+    ":",
+    ";",
+    "=",
+    "!=",
+    "0",
+    "1.5",
+    "18446744073709551616",
+    "/xff",
+    "store",
+    " ",
+];
+
+fn mutated(queries: &'static [&'static str]) -> impl Strategy<Value = String> {
+    (
+        prop::sample::select(queries),
+        any::<prop::sample::Index>(),
+        any::<prop::sample::Index>(),
+        ".{0,4}",
+    )
+        .prop_map(|(query, start, end, replacement)| {
+            let boundaries: Vec<usize> = query
+                .char_indices()
+                .map(|(position, _)| position)
+                .chain([query.len()])
+                .collect();
+            let (start, end) = (*start.get(&boundaries), *end.get(&boundaries));
+            let (start, end) = (start.min(end), start.max(end));
+            format!("{}{replacement}{}", &query[..start], &query[end..])
+        })
+}
+
+fn token_soup() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(TOKENS), 0..24).prop_map(|tokens| tokens.join(""))
+}
+
+proptest! {
+    #[test]
+    fn test_arbitrary_input_does_not_panic(input in ".{0,256}") {
+        let _ = db::parse_db_query(&input);
+        let _ = db::validate(&input);
+        let _ = ai::parse_ai_query(&input);
+        let _ = ai::validate(&input);
+    }
+
+    #[test]
+    fn test_token_soup_does_not_panic(input in token_soup()) {
+        let _ = db::parse_db_query(&input);
+        let _ = db::validate(&input);
+        let _ = ai::parse_ai_query(&input);
+        let _ = ai::validate(&input);
+    }
+
+    #[test]
+    fn test_mutated_db_queries_do_not_panic(input in mutated(DB_QUERIES)) {
+        let _ = db::parse_db_query(&input);
+        let _ = db::validate(&input);
+    }
+
+    #[test]
+    fn test_mutated_ai_queries_do_not_panic(input in mutated(AI_QUERIES)) {
+        let _ = ai::parse_ai_query(&input);
+        let _ = ai::validate(&input);
+    }
+}
@@ -0,0 +1,508 @@
+//! Checks on parsed queries for mistakes the server would otherwise reject, such as keys of the
+//! wrong dimension or a kdtree search on a store without a kdtree index. Stores created earlier
+//! in the same input are tracked so a whole script can be checked without a running server.
+use std::collections::{HashMap, HashSet};
+
+use ahnlich_types::{
+    ai::{AIModel, AIQuery, AIStoreInputType},
+    db::DBQuery,
+    keyval::{StoreInput, StoreName},
+    metadata::MetadataValue,
+    predicate::{Predicate, PredicateCondition},
+    similarity::{Algorithm, NonLinearAlgorithm},
+};
+use pest::Parser;
+
+use crate::{
+    error::Diagnostic,
+    parser::{QueryParser, Rule},
+};
+
+/// Problem found in a statement, located at `token` when it appears in the statement
+pub(crate) struct Finding {
+    message: String,
+    token: Option<String>,
+}
+
+impl Finding {
+    fn new(message: String) -> Self {
+        Self {
+            message,
+            token: None,
+        }
+    }
+
+    fn at(mut self, token: impl ToString) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+}
+
+/// What a statement earlier in the input tells us about a store it created
+#[derive(Debug, Default)]
+struct CreatedStore {
+    dimension: Option<usize>,
+    index_input: Option<AIStoreInputType>,
+    query_input: Option<AIStoreInputType>,
+    non_linear_indices: HashSet<NonLinearAlgorithm>,
+}
+
+pub(crate) struct Validator {
+    /// stores known to exist before the input runs, existence is not checked when `None`
+    existing: Option<HashSet<StoreName>>,
+    created: HashMap<StoreName, CreatedStore>,
+}
+
+impl Validator {
+    pub(crate) fn new(existing: Option<&[StoreName]>) -> Self {
+        Self {
+            existing: existing.map(|stores| stores.iter().cloned().collect()),
+            created: HashMap::new(),
+        }
+    }
+
+    /// Runs `check` over every query, returning the queries if none of them has a problem
+    pub(crate) fn validate<Q>(
+        mut self,
+        input: &str,
+        rule: Rule,
+        queries: Vec<Q>,
+        check: fn(&mut Self, &Q) -> Result<(), Finding>,
+    ) -> Result<Vec<Q>, Diagnostic> {
+        let spans: Vec<(usize, usize)> = match QueryParser::parse(rule, input) {
+            Ok(pairs) => pairs
+                .map(|pair| (pair.as_span().start(), pair.as_span().end()))
+                .collect(),
+            Err(_) => vec![],
+        };
+        for (index, query) in queries.iter().enumerate() {
+            if let Err(finding) = check(&mut self, query) {
+                let (start, end) = spans.get(index).copied().unwrap_or((0, input.len()));
+                let statement = input[start..end].to_lowercase();
+                let offset = finding
+                    .token
+                    .as_ref()
+                    .and_then(|token| statement.find(&token.to_lowercase()))
+                    .unwrap_or(statement.len() - statement.trim_start().len());
+                return Err(Diagnostic::new(
+                    input,
+                    start + offset,
+                    finding.message,
+                    finding.token,
+                ));
+            }
+        }
+        Ok(queries)
+    }
+
+    /// The store if it was created earlier in the input, erroring if it is known not to exist
+    fn store(&mut self, store: &StoreName) -> Result<Option<&mut CreatedStore>, Finding> {
+        if self.created.contains_key(store) {
+            return Ok(self.created.get_mut(store));
+        }
+        match &self.existing {
+            Some(existing) if !existing.contains(store) => {
+                Err(Finding::new(format!("store `{store}` does not exist")).at(store))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn create(
+        &mut self,
+        store: &StoreName,
+        created: CreatedStore,
+        error_if_exists: bool,
+    ) -> Result<(), Finding> {
+        let exists = self.created.contains_key(store)
+            || self
+                .existing
+                .as_ref()
+                .is_some_and(|existing| existing.contains(store));
+        if exists && error_if_exists {
+            return Err(Finding::new(format!("store `{store}` already exists")).at(store));
+        }
+        if !exists {
+            self.created.insert(store.clone(), created);
+        }
+        Ok(())
+    }
+
+    fn drop_store(&mut self, store: &StoreName, error_if_not_exists: bool) -> Result<(), Finding> {
+        if error_if_not_exists {
+            self.store(store)?;
+        }
+        self.created.remove(store);
+        if let Some(existing) = self.existing.as_mut() {
+            existing.remove(store);
+        }
+        Ok(())
+    }
+
+    fn add_indices(
+        &mut self,
+        store: &StoreName,
+        indices: &HashSet<NonLinearAlgorithm>,
+    ) -> Result<(), Finding> {
+        if let Some(created) = self.store(store)? {
+            created.non_linear_indices.extend(indices.iter().copied());
+        }
+        Ok(())
+    }
+
+    fn check_algorithm(&mut self, store: &StoreName, algorithm: Algorithm) -> Result<(), Finding> {
+        let created = self.store(store)?;
+        if let (Algorithm::KDTree, Some(created)) = (algorithm, created) {
+            if !created
+                .non_linear_indices
+                .contains(&NonLinearAlgorithm::KDTree)
+            {
+                return Err(Finding::new(format!(
+                    "store `{store}` has no kdtree index, create one with \
+                     createnonlinearalgorithmindex"
+                ))
+                .at("kdtree"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Keys of a statement must all have the same dimension, which must be that of the store
+    fn check_dimensions(
+        &mut self,
+        store: &StoreName,
+        dimensions: impl IntoIterator<Item = usize>,
+    ) -> Result<(), Finding> {
+        let store_dimension = self.store(store)?.and_then(|created| created.dimension);
+        let mut expected = store_dimension;
+        for found in dimensions {
+            match expected {
+                Some(expected) if expected != found => {
+                    let message = match store_dimension {
+                        Some(_) => format!(
+                            "key of dimension {found} does not match the dimension {expected} of \
+                             store `{store}`"
+                        ),
+                        None => format!("keys have different dimensions {expected} and {found}"),
+                    };
+                    return Err(Finding::new(message));
+                }
+                Some(_) => {}
+                None => expected = Some(found),
+            }
+        }
+        Ok(())
+    }
+
+    fn check_inputs<'a>(
+        &mut self,
+        store: &StoreName,
+        inputs: impl IntoIterator<Item = &'a StoreInput>,
+        expected: fn(&CreatedStore) -> Option<AIStoreInputType>,
+        model: &str,
+    ) -> Result<(), Finding> {
+        let Some(expected) = self.store(store)?.and_then(|created| expected(created)) else {
+            return Ok(());
+        };
+        for input in inputs {
+            let found = AIStoreInputType::from(input);
+            if found != expected {
+                return Err(Finding::new(format!(
+                    "{model} of store `{store}` expects {expected} inputs but found {found}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_db_query(&mut self, query: &DBQuery) -> Result<(), Finding> {
+        match query {
+            DBQuery::CreateStore {
+                store,
+                dimension,
+                non_linear_indices,
+                error_if_exists,
+                infer_dimension,
+                ..
+            } => self.create(
+                store,
+                CreatedStore {
+                    dimension: (!infer_dimension).then_some(dimension.get()),
+                    non_linear_indices: non_linear_indices.clone(),
+                    ..Default::default()
+                },
+                *error_if_exists,
+            ),
+            DBQuery::Set { store, inputs } => {
+                self.check_dimensions(store, inputs.iter().map(|(key, _)| key.dimension()))?;
+                // a store created to infer its dimension takes that of the first keys set into it
+                if let (Some(created), Some((key, _))) =
+                    (self.created.get_mut(store), inputs.first())
+                {
+                    created.dimension.get_or_insert(key.dimension());
+                }
+                Ok(())
+            }
+            DBQuery::GetKey { store, keys } | DBQuery::DelKey { store, keys } => {
+                self.check_dimensions(store, keys.iter().map(|key| key.dimension()))
+            }
+            DBQuery::GetSimN {
+                store,
+                search_input,
+                algorithm,
+                condition,
+                additional_search_inputs,
+                ..
+            } => {
+                self.check_dimensions(
+                    store,
+                    std::iter::once(search_input)
+                        .chain(additional_search_inputs)
+                        .map(|key| key.dimension()),
+                )?;
+                self.check_algorithm(store, *algorithm)?;
+                condition.as_ref().map_or(Ok(()), check_condition)
+            }
+            DBQuery::BatchGetSimN {
+                store,
+                search_inputs,
+                algorithm,
+                condition,
+                ..
+            } => {
+                self.check_dimensions(store, search_inputs.iter().map(|key| key.dimension()))?;
+                self.check_algorithm(store, *algorithm)?;
+                condition.as_ref().map_or(Ok(()), check_condition)
+            }
+            DBQuery::GetSimNByKey {
+                store,
+                algorithm,
+                condition,
+                ..
+            } => {
+                self.check_algorithm(store, *algorithm)?;
+                condition.as_ref().map_or(Ok(()), check_condition)
+            }
+            DBQuery::GetPred { store, condition }
+            | DBQuery::CountPred {
+                store, condition, ..
+            }
+            | DBQuery::DelPred { store, condition }
+            | DBQuery::DelPredAsync { store, condition } => {
+                self.store(store)?;
+                check_condition(condition)
+            }
+            DBQuery::CreateNonLinearAlgorithmIndex {
+                store,
+                non_linear_indices,
+            } => self.add_indices(store, non_linear_indices),
+            DBQuery::CreatePredIndex { store, .. }
+            | DBQuery::DropPredIndex { store, .. }
+            | DBQuery::DropNonLinearAlgorithmIndex { store, .. }
+            | DBQuery::DescribeStore { store }
+            | DBQuery::CompactStore { store }
+            | DBQuery::CheckStore { store, .. }
+            | DBQuery::ScrubStore { store, .. }
+            | DBQuery::BenchmarkStore { store, .. }
+            | DBQuery::ListEntries { store, .. }
+            | DBQuery::ExportStoreParquet { store, .. }
+            | DBQuery::SetBulkWrite { store, .. } => self.store(store).map(|_| ()),
+            DBQuery::Warmup { stores } => stores
+                .iter()
+                .try_for_each(|store| self.store(store).map(|_| ())),
+            DBQuery::ApplyManifest { stores, dry_run } if !dry_run => {
+                stores.iter().try_for_each(|manifest| {
+                    self.create(
+                        &manifest.store,
+                        CreatedStore {
+                            dimension: Some(manifest.dimension.get()),
+                            non_linear_indices: manifest.non_linear_indices.clone(),
+                            ..Default::default()
+                        },
+                        false,
+                    )
+                })
+            }
+            DBQuery::DropStore {
+                store,
+                error_if_not_exists,
+            } => self.drop_store(store, *error_if_not_exists),
+            DBQuery::GetJob { .. }
+            | DBQuery::CancelJob { .. }
+            | DBQuery::ListJobs
+            | DBQuery::ListQuotas
+            | DBQuery::SetQuota { .. }
+            | DBQuery::MirrorStatus
+            | DBQuery::ControlMirror { .. }
+            | DBQuery::ListTrashedStores
+            | DBQuery::ApplyManifest { .. }
+            | DBQuery::DiffManifest { .. }
+            | DBQuery::RestoreStore { .. }
+            | DBQuery::InfoServer
+            | DBQuery::GetMemoryBreakdown
+            | DBQuery::ReloadConfig
+            | DBQuery::ListStores
+            | DBQuery::ListClients
+            | DBQuery::Ping => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_ai_query(&mut self, query: &AIQuery) -> Result<(), Finding> {
+        match query {
+            AIQuery::CreateStore {
+                store,
+                query_model,
+                index_model,
+                non_linear_indices,
+                error_if_exists,
+                multimodal_fusion,
+                ..
+            } => self.create(
+                store,
+                CreatedStore {
+                    // stores with a fusion take multimodal inputs to index and may be searched
+                    // with any input
+                    index_input: match multimodal_fusion {
+                        Some(_) => Some(AIStoreInputType::Multimodal),
+                        None => model_input_type(index_model),
+                    },
+                    query_input: match multimodal_fusion {
+                        Some(_) => None,
+                        None => model_input_type(query_model),
+                    },
+                    non_linear_indices: non_linear_indices.clone(),
+                    ..Default::default()
+                },
+                *error_if_exists,
+            ),
+            AIQuery::Set { store, inputs, .. } => self.check_inputs(
+                store,
+                inputs.iter().map(|(input, _)| input),
+                |created| created.index_input.clone(),
+                "index model",
+            ),
+            AIQuery::GetSimN {
+                store,
+                search_input,
+                condition,
+                algorithm,
+                additional_search_inputs,
+                ..
+            } => {
+                self.check_inputs(
+                    store,
+                    std::iter::once(search_input).chain(additional_search_inputs),
+                    |created| created.query_input.clone(),
+                    "query model",
+                )?;
+                self.check_algorithm(store, *algorithm)?;
+                condition.as_ref().map_or(Ok(()), check_condition)
+            }
+            // prompts are cached as text inputs
+            AIQuery::CacheStore { store, prompt, .. } => self.check_inputs(
+                store,
+                std::iter::once(&StoreInput::RawString(prompt.clone())),
+                |created| created.index_input.clone(),
+                "index model",
+            ),
+            AIQuery::CacheLookup { store, prompt, .. } => self.check_inputs(
+                store,
+                std::iter::once(&StoreInput::RawString(prompt.clone())),
+                |created| created.query_input.clone(),
+                "query model",
+            ),
+            AIQuery::GetPred {
+                store, condition, ..
+            }
+            | AIQuery::StartChunkedGet {
+                store, condition, ..
+            } => {
+                self.store(store)?;
+                check_condition(condition)
+            }
+            AIQuery::CreateNonLinearAlgorithmIndex {
+                store,
+                non_linear_indices,
+            } => self.add_indices(store, non_linear_indices),
+            AIQuery::CreatePredIndex { store, .. }
+            | AIQuery::DropPredIndex { store, .. }
+            | AIQuery::DropNonLinearAlgorithmIndex { store, .. }
+            | AIQuery::DelKey { store, .. }
+            | AIQuery::GetKey { store, .. }
+            | AIQuery::MigrateStore { source: store, .. }
+            | AIQuery::AnswerQuestion { store, .. }
+            | AIQuery::StartChunkedSet { store, .. }
+            | AIQuery::DescribeStore { store } => self.store(store).map(|_| ()),
+            AIQuery::DropStore {
+                store,
+                error_if_not_exists,
+            } => self.drop_store(store, *error_if_not_exists),
+            AIQuery::Warmup { stores, .. } => stores
+                .iter()
+                .try_for_each(|store| self.store(store).map(|_| ())),
+            AIQuery::PurgeStores => {
+                self.created.clear();
+                self.existing = self.existing.as_ref().map(|_| HashSet::new());
+                Ok(())
+            }
+            AIQuery::GetJob { .. }
+            | AIQuery::CancelJob { .. }
+            | AIQuery::SetChunk { .. }
+            | AIQuery::FinishChunkedSet { .. }
+            | AIQuery::GetChunk { .. }
+            | AIQuery::EndChunkedTransfer { .. }
+            | AIQuery::ListJobs
+            | AIQuery::InfoServer
+            | AIQuery::GetMemoryBreakdown
+            | AIQuery::ReloadConfig
+            | AIQuery::ListClients
+            | AIQuery::ListStores
+            | AIQuery::ListSupportedModels
+            | AIQuery::GetUsageStats { .. }
+            | AIQuery::Classify { .. }
+            | AIQuery::Ping => Ok(()),
+        }
+    }
+}
+
+/// The input type of custom models is only known to the AI proxy serving them
+fn model_input_type(model: &AIModel) -> Option<AIStoreInputType> {
+    match model {
+        AIModel::Resnet50 | AIModel::ClipVitB32Image => Some(AIStoreInputType::Image),
+        AIModel::AllMiniLML6V2
+        | AIModel::AllMiniLML12V2
+        | AIModel::BGEBaseEnV15
+        | AIModel::BGELargeEnV15
+        | AIModel::ClipVitB32Text => Some(AIStoreInputType::RawString),
+        AIModel::Custom(_) => None,
+    }
+}
+
+fn check_condition(condition: &PredicateCondition) -> Result<(), Finding> {
+    match condition {
+        PredicateCondition::Value(predicate) => check_predicate(predicate),
+        PredicateCondition::And(first, second) | PredicateCondition::Or(first, second) => {
+            check_condition(first)?;
+            check_condition(second)
+        }
+    }
+}
+
+/// `in` and `not in` match values of a single type, a mix of text and images is a mistake
+fn check_predicate(predicate: &Predicate) -> Result<(), Finding> {
+    let (Predicate::In { key, value } | Predicate::NotIn { key, value }) = predicate else {
+        return Ok(());
+    };
+    let has_text = value
+        .iter()
+        .any(|value| matches!(value, MetadataValue::RawString(_)));
+    let has_image = value
+        .iter()
+        .any(|value| matches!(value, MetadataValue::Image(_)));
+    if has_text && has_image {
+        return Err(
+            Finding::new(format!("values compared to `{key}` mix text and images")).at(key),
+        );
+    }
+    Ok(())
+}
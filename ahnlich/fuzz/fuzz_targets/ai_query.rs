@@ -0,0 +1,18 @@
+#![no_main]
+
+use ahnlich_types::ai::AIServerQuery;
+use ahnlich_types::bincode::{BinCodeSerAndDeser, RESPONSE_HEADER_LEN};
+use libfuzzer_sys::fuzz_target;
+
+// bodies of AI proxy queries as the proxy reads them after their header
+fuzz_target!(|data: &[u8]| {
+    if let Ok(query) = AIServerQuery::deserialize(data) {
+        // whatever is read has to be written back the same
+        let serialized = query.serialize().expect("Could not serialize query");
+        assert_eq!(
+            AIServerQuery::deserialize(&serialized[RESPONSE_HEADER_LEN..])
+                .expect("Could not deserialize serialized query"),
+            query
+        );
+    }
+});
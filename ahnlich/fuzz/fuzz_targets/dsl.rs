@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    let _ = dsl::db::parse_db_query(input);
+    let _ = dsl::db::validate(input);
+    let _ = dsl::ai::parse_ai_query(input);
+    let _ = dsl::ai::validate(input);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use ahnlich_db::engine::store::StoreHandler;
+use libfuzzer_sys::fuzz_target;
+use utils::persistence::{read_snapshot_from, AhnlichPersistenceUtils};
+
+type Stores = <StoreHandler as AhnlichPersistenceUtils>::PersistenceObject;
+
+// snapshots of the database as it loads them on startup
+fuzz_target!(|data: &[u8]| {
+    let _ = read_snapshot_from::<Stores>(data, None);
+});
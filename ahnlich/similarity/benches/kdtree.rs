@@ -0,0 +1,61 @@
+use ahnlich_similarity::kdtree::KDTree;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ndarray::Array;
+use ndarray::Array1;
+use std::num::NonZeroUsize;
+
+fn random_points(size: usize, dimension: usize) -> Vec<Array1<f32>> {
+    (0..size)
+        .map(|_| Array::from((0..dimension).map(|_| rand::random()).collect::<Vec<f32>>()))
+        .collect()
+}
+
+fn empty_tree(dimension: usize) -> KDTree {
+    let dimension = NonZeroUsize::new(dimension).unwrap();
+    KDTree::new(dimension, dimension).unwrap()
+}
+
+fn bench_build(c: &mut Criterion) {
+    let sizes = [1000, 10000, 100000];
+    let dimension = 128;
+
+    let mut group = c.benchmark_group("kdtree_build_insert_multi");
+    for size in sizes {
+        let points = random_points(size, dimension);
+        group.bench_function(format!("size_{size}"), |b| {
+            b.iter_batched(
+                || (empty_tree(dimension), points.clone()),
+                |(tree, points)| tree.insert_multi(points).unwrap(),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("kdtree_build_bulk_load");
+    for size in sizes {
+        let points = random_points(size, dimension);
+        group.bench_function(format!("size_{size}"), |b| {
+            b.iter_batched(
+                || (empty_tree(dimension), points.clone()),
+                |(tree, points)| tree.bulk_load(points).unwrap(),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn criterion_config(seconds: u64, sample_size: usize) -> Criterion {
+    Criterion::default()
+        .measurement_time(std::time::Duration::new(seconds, 0))
+        .sample_size(sample_size)
+}
+
+// group comparing building a tree of 1k, 10k and 100k points one by one and in bulk
+criterion_group! {
+    name = build;
+    config = criterion_config(30, 10);
+    targets = bench_build
+}
+criterion_main!(build);
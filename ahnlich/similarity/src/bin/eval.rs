@@ -0,0 +1,82 @@
+use ahnlich_similarity::eval::{evaluate, Dataset, IndexParams};
+use clap::{Parser, ValueEnum};
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Measures the recall@k, queries per second and memory of an index on a dataset of fvecs files
+/// such as SIFT, e.g
+/// ahnlich-eval --base sift_base.fvecs --queries sift_query.fvecs --ground-truth sift_groundtruth.ivecs --index kdtree --depth 8 --depth 16
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// fvecs file of the vectors to index
+    #[arg(long)]
+    base: PathBuf,
+
+    /// fvecs file of the vectors to search with
+    #[arg(long)]
+    queries: PathBuf,
+
+    /// ivecs file of the ids of the true nearest neighbours of each query. Found by comparing
+    /// each query to every base vector when not given
+    #[arg(long)]
+    ground_truth: Option<PathBuf>,
+
+    /// Number of neighbours searched for
+    #[arg(long, default_value_t = NonZeroUsize::new(10).unwrap())]
+    k: NonZeroUsize,
+
+    #[arg(long, value_enum, default_value_t = Index::Kdtree)]
+    index: Index,
+
+    /// Depths of dimensions a kdtree compares, each evaluated in turn. Defaults to the dimension
+    /// of the dataset
+    #[arg(long)]
+    depth: Vec<NonZeroUsize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Index {
+    Linear,
+    Kdtree,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let load_start = Instant::now();
+    let dataset = Dataset::load(&cli.base, &cli.queries, cli.ground_truth.as_deref(), cli.k)?;
+    let dimension = dataset.base.first().map_or(0, |vector| vector.len());
+    println!(
+        "loaded {} base vectors and {} queries of dimension {dimension} in {:.2?}",
+        dataset.base.len(),
+        dataset.queries.len(),
+        load_start.elapsed()
+    );
+
+    let params = match cli.index {
+        Index::Linear => vec![IndexParams::Linear],
+        Index::Kdtree if cli.depth.is_empty() => vec![IndexParams::KDTree {
+            depth: NonZeroUsize::new(dimension).ok_or("dataset has no base vectors")?,
+        }],
+        Index::Kdtree => cli
+            .depth
+            .iter()
+            .map(|depth| IndexParams::KDTree { depth: *depth })
+            .collect(),
+    };
+    for params in params {
+        let report =
+            evaluate(&dataset, params, cli.k).map_err(|err| format!("{params:?}: {err:?}"))?;
+        println!(
+            "{params:?}: recall@{} {:.4}, {:.1} queries/s, built in {:.2?}, {} bytes",
+            report.k,
+            report.recall,
+            report.queries_per_second,
+            report.build_time,
+            report.index_bytes
+        );
+    }
+    Ok(())
+}
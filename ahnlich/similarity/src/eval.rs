@@ -0,0 +1,330 @@
+/// Measures how well an index finds the nearest neighbours of a dataset, such as the SIFT and GIST
+/// datasets in the fvecs and ivecs formats, along with how fast it answers and how much memory it
+/// holds, so that index settings can be tuned against each other
+use crate::error::Error;
+use crate::kdtree::KDTree;
+use ndarray::Array1;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Base vectors to index, queries to search them with and the ids of the true nearest base
+/// vectors of each query, nearest first
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub base: Vec<Array1<f32>>,
+    pub queries: Vec<Array1<f32>>,
+    pub ground_truth: Vec<Vec<usize>>,
+}
+
+impl Dataset {
+    /// Reads base vectors and queries from fvecs files. The ground truth is read from an ivecs
+    /// file when there is one and is otherwise found by comparing each query to every base
+    /// vector, keeping `k` neighbours
+    pub fn load(
+        base: &Path,
+        queries: &Path,
+        ground_truth: Option<&Path>,
+        k: NonZeroUsize,
+    ) -> io::Result<Self> {
+        let base = read_fvecs(base)?;
+        let queries = read_fvecs(queries)?;
+        let ground_truth = match ground_truth {
+            Some(ground_truth) => read_ivecs(ground_truth)?,
+            None => exact_neighbours(&base, &queries, k),
+        };
+        if ground_truth.len() < queries.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "ground truth has {} entries for {} queries",
+                    ground_truth.len(),
+                    queries.len()
+                ),
+            ));
+        }
+        Ok(Self {
+            base,
+            queries,
+            ground_truth,
+        })
+    }
+}
+
+/// Index to evaluate along with the settings it is built with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexParams {
+    /// Compares each query to every base vector, the baseline other indices are weighed against
+    Linear,
+    KDTree {
+        depth: NonZeroUsize,
+    },
+}
+
+/// How an index did on a dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalReport {
+    pub k: usize,
+    /// Share of the true `k` nearest neighbours of the queries found, averaged over queries
+    pub recall: f64,
+    pub queries_per_second: f64,
+    pub build_time: Duration,
+    /// Approximate bytes held by the index, not counting the base vectors for a linear index
+    pub index_bytes: usize,
+}
+
+/// Builds the index on the base vectors of the dataset and searches it for the `k` nearest
+/// neighbours of each query, one query at a time
+pub fn evaluate(
+    dataset: &Dataset,
+    params: IndexParams,
+    k: NonZeroUsize,
+) -> Result<EvalReport, Error> {
+    let build_start = Instant::now();
+    let index = match params {
+        IndexParams::Linear => None,
+        IndexParams::KDTree { depth } => {
+            // an empty base leaves the queries to be checked against the depth instead
+            let dimension = dataset
+                .base
+                .first()
+                .and_then(|vector| NonZeroUsize::new(vector.len()))
+                .unwrap_or(depth);
+            let kdtree = KDTree::new(dimension, depth)?;
+            kdtree.bulk_load(dataset.base.clone())?;
+            Some(kdtree)
+        }
+    };
+    let build_time = build_start.elapsed();
+
+    let search_start = Instant::now();
+    let results = dataset
+        .queries
+        .iter()
+        .map(|query| match &index {
+            None => Ok(linear_search(&dataset.base, query, k)
+                .into_iter()
+                .map(|id| squared_distance(&dataset.base[id], query))
+                .collect()),
+            Some(kdtree) => kdtree.n_nearest(query, k, None).map(|found| {
+                found
+                    .iter()
+                    .map(|(point, _)| squared_distance(point, query))
+                    .collect::<Vec<_>>()
+            }),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let search_time = search_start.elapsed();
+
+    let recall = results
+        .iter()
+        .zip(&dataset.ground_truth)
+        .zip(&dataset.queries)
+        .map(|((distances, ground_truth), query)| {
+            recall_at_k(distances, &dataset.base, ground_truth, query, k)
+        })
+        .sum::<f64>()
+        / dataset.queries.len().max(1) as f64;
+    Ok(EvalReport {
+        k: k.get(),
+        recall,
+        queries_per_second: dataset.queries.len() as f64 / search_time.as_secs_f64().max(1e-9),
+        build_time,
+        index_bytes: index.as_ref().map_or(0, KDTree::size),
+    })
+}
+
+/// Share of the true `k` nearest neighbours among the results, where a result counts when it is
+/// no further than the kth true neighbour. Going by distance counts a duplicate of a neighbour
+/// as well as the neighbour itself, which datasets such as SIFT have
+fn recall_at_k(
+    distances: &[f32],
+    base: &[Array1<f32>],
+    ground_truth: &[usize],
+    query: &Array1<f32>,
+    k: NonZeroUsize,
+) -> f64 {
+    let k = k.get().min(ground_truth.len());
+    let Some(kth) = k.checked_sub(1).and_then(|kth| ground_truth.get(kth)) else {
+        return 1.0;
+    };
+    let furthest = squared_distance(&base[*kth], query);
+    // distances are computed the same way on both sides but leave room for rounding
+    let tolerance = furthest.abs() * 1e-5 + f32::EPSILON;
+    let found = distances
+        .iter()
+        .take(k)
+        .filter(|distance| **distance <= furthest + tolerance)
+        .count();
+    found as f64 / k as f64
+}
+
+/// Ids of the `k` base vectors nearest to each query by euclidean distance, nearest first
+pub fn exact_neighbours(
+    base: &[Array1<f32>],
+    queries: &[Array1<f32>],
+    k: NonZeroUsize,
+) -> Vec<Vec<usize>> {
+    queries
+        .par_iter()
+        .map(|query| linear_search(base, query, k))
+        .collect()
+}
+
+fn linear_search(base: &[Array1<f32>], query: &Array1<f32>, k: NonZeroUsize) -> Vec<usize> {
+    let mut ranked: Vec<_> = base
+        .iter()
+        .enumerate()
+        .map(|(id, vector)| (squared_distance(vector, query), id))
+        .collect();
+    let k = k.get().min(ranked.len());
+    if k < ranked.len() {
+        ranked.select_nth_unstable_by(k, |a, b| a.0.total_cmp(&b.0));
+        ranked.truncate(k);
+    }
+    ranked.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    ranked.into_iter().map(|(_, id)| id).collect()
+}
+
+fn squared_distance(a: &Array1<f32>, b: &Array1<f32>) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Reads vectors of the fvecs format, where each vector is its dimension as a little endian i32
+/// followed by that many little endian f32
+pub fn read_fvecs(path: &Path) -> io::Result<Vec<Array1<f32>>> {
+    read_vecs(path, f32::from_le_bytes)
+        .map(|vectors| vectors.into_iter().map(Array1::from).collect())
+}
+
+/// Reads vectors of the ivecs format, which ground truths come in, where each vector is its
+/// dimension as a little endian i32 followed by that many little endian i32
+pub fn read_ivecs(path: &Path) -> io::Result<Vec<Vec<usize>>> {
+    let vectors = read_vecs(path, i32::from_le_bytes)?;
+    vectors
+        .into_iter()
+        .map(|vector| {
+            vector
+                .into_iter()
+                .map(|id| {
+                    usize::try_from(id).map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidData, format!("negative id {id}"))
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn read_vecs<T>(path: &Path, parse: impl Fn([u8; 4]) -> T) -> io::Result<Vec<Vec<T>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut vectors = Vec::new();
+    let mut word = [0u8; 4];
+    loop {
+        match reader.read_exact(&mut word) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+        let dimension = usize::try_from(i32::from_le_bytes(word)).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("negative dimension in {}", path.display()),
+            )
+        })?;
+        let mut vector = Vec::with_capacity(dimension);
+        for _ in 0..dimension {
+            reader.read_exact(&mut word)?;
+            vector.push(parse(word));
+        }
+        vectors.push(vector);
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+
+    fn write_vecs(path: &Path, vectors: &[Vec<[u8; 4]>]) {
+        let mut file = File::create(path).unwrap();
+        for vector in vectors {
+            file.write_all(&(vector.len() as i32).to_le_bytes())
+                .unwrap();
+            for value in vector {
+                file.write_all(value).unwrap();
+            }
+        }
+    }
+
+    fn random_points(size: usize, dimension: usize) -> Vec<Array1<f32>> {
+        (0..size)
+            .map(|_| Array::from((0..dimension).map(|_| rand::random()).collect::<Vec<f32>>()))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_vecs() {
+        let dir = std::env::temp_dir().join(format!("ahnlich-eval-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fvecs = dir.join("base.fvecs");
+        write_vecs(
+            &fvecs,
+            &[
+                vec![1.0f32.to_le_bytes(), 2.5f32.to_le_bytes()],
+                vec![(-3.0f32).to_le_bytes(), 0.0f32.to_le_bytes()],
+            ],
+        );
+        assert_eq!(
+            read_fvecs(&fvecs).unwrap(),
+            vec![Array1::from(vec![1.0, 2.5]), Array1::from(vec![-3.0, 0.0])]
+        );
+        let ivecs = dir.join("groundtruth.ivecs");
+        write_vecs(&ivecs, &[vec![1i32.to_le_bytes(), 0i32.to_le_bytes()]]);
+        assert_eq!(read_ivecs(&ivecs).unwrap(), vec![vec![1, 0]]);
+
+        // a vector cut short
+        let mut file = File::options().append(true).open(&fvecs).unwrap();
+        file.write_all(&2i32.to_le_bytes()).unwrap();
+        file.write_all(&1.0f32.to_le_bytes()).unwrap();
+        assert_eq!(
+            read_fvecs(&fvecs).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let k = NonZeroUsize::new(5).unwrap();
+        let base = random_points(500, 4);
+        let queries = random_points(20, 4);
+        let dataset = Dataset {
+            ground_truth: exact_neighbours(&base, &queries, k),
+            base,
+            queries,
+        };
+        let linear = evaluate(&dataset, IndexParams::Linear, k).unwrap();
+        assert_eq!(linear.recall, 1.0);
+        assert_eq!(linear.k, 5);
+        assert_eq!(linear.index_bytes, 0);
+
+        let kdtree = evaluate(
+            &dataset,
+            IndexParams::KDTree {
+                depth: NonZeroUsize::new(4).unwrap(),
+            },
+            k,
+        )
+        .unwrap();
+        assert!((0.0..=1.0).contains(&kdtree.recall));
+        assert!(kdtree.index_bytes > 0);
+        assert!(kdtree.queries_per_second > 0.0);
+    }
+}
@@ -4,6 +4,7 @@ use crate::error::Error;
 use crate::utils::Array1F32Ordered;
 use crossbeam::epoch::{self, Atomic, Guard, Owned, Shared};
 use ndarray::Array1;
+use rayon::prelude::*;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering as CmpOrdering;
@@ -14,6 +15,9 @@ use std::mem::size_of_val;
 use std::num::NonZeroUsize;
 use std::sync::atomic::Ordering;
 
+/// Subtrees of a bulk load with more points than this are built in parallel
+const PARALLEL_BUILD_THRESHOLD: usize = 1024;
+
 #[derive(Debug)]
 pub struct KDNode {
     point: Array1<f32>,
@@ -142,9 +146,7 @@ impl PartialOrd for OrderedArray {
 
 impl Ord for OrderedArray {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.1
-            .partial_cmp(&other.1)
-            .unwrap_or(std::cmp::Ordering::Less)
+        self.1.total_cmp(&other.1)
     }
 }
 
@@ -262,6 +264,25 @@ impl KDTree {
         s
     }
 
+    /// Every point held by the KDTree, in no particular order
+    #[tracing::instrument(skip_all)]
+    pub fn points(&self) -> Vec<Array1<f32>> {
+        let guard = epoch::pin();
+        let mut points = Vec::new();
+        let mut nodes = vec![&self.root];
+        while let Some(node) = nodes.pop() {
+            let shared = node.load(Ordering::Acquire, &guard);
+            if shared.is_null() {
+                continue;
+            }
+            let current = unsafe { shared.deref() };
+            points.push(current.point.clone());
+            nodes.push(&current.left);
+            nodes.push(&current.right);
+        }
+        points
+    }
+
     #[tracing::instrument(skip_all)]
     fn assert_shape(&self, input: &Array1<f32>) -> Result<(), Error> {
         let dim = self.dimension.get();
@@ -339,9 +360,11 @@ impl KDTree {
                     if point == current.point {
                         break;
                     }
+                    // NaN compares as neither less nor greater so it goes right rather than
+                    // panicking
                     match point[dim]
                         .partial_cmp(&current.point[dim])
-                        .expect("Partial cmp does not exist")
+                        .unwrap_or(CmpOrdering::Greater)
                     {
                         CmpOrdering::Less => {
                             self.insert_recursive(&current.left, point, depth + 1, guard);
@@ -357,6 +380,94 @@ impl KDTree {
         }
     }
 
+    /// Loads many points at once into an empty tree by splitting them on the median of each
+    /// dimension in turn, which builds a balanced tree much faster than inserting them one by
+    /// one. Points are inserted one by one instead when the tree already has points
+    #[tracing::instrument(skip_all)]
+    pub fn bulk_load(&self, mut points: Vec<Array1<f32>>) -> Result<(), Error> {
+        if points.is_empty() {
+            return Ok(());
+        }
+        points
+            .iter()
+            .try_for_each(|point| self.assert_shape(point))?;
+        let guard = epoch::pin();
+        if !self.root.load(Ordering::Acquire, &guard).is_null() {
+            return self.insert_multi(points);
+        }
+        // insert leaves out points that are already in the tree
+        points.par_sort_unstable_by(|first, second| {
+            first
+                .iter()
+                .zip(second.iter())
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(CmpOrdering::Equal)
+        });
+        points.dedup();
+        let root = self.build_recursive(&mut points.clone(), 0);
+        if self
+            .root
+            .compare_exchange(
+                Shared::null(),
+                root,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                &guard,
+            )
+            .is_err()
+        {
+            // another insert got to the empty tree first
+            return self.insert_multi(points);
+        }
+        Ok(())
+    }
+
+    /// Builds the subtree of the points with the median point in the dimension of the depth at
+    /// its root. Points equal to the median in that dimension go to the right subtree the same
+    /// way insert sends them
+    fn build_recursive(&self, points: &mut [Array1<f32>], depth: usize) -> Owned<KDNode> {
+        let dim = depth % self.depth.get();
+        let mid = points.len() / 2;
+        points.select_nth_unstable_by(mid, |a, b| a[dim].total_cmp(&b[dim]));
+        let median = points[mid][dim];
+        // move the points before the median that are equal to it in this dimension after those
+        // that are less so that the first of them can be the root
+        let mut pivot = mid;
+        let mut index = 0;
+        while index < pivot {
+            if points[index][dim] < median {
+                index += 1;
+            } else {
+                pivot -= 1;
+                points.swap(index, pivot);
+            }
+        }
+        points.swap(pivot, mid);
+        let parallel = points.len() > PARALLEL_BUILD_THRESHOLD;
+        let (left, rest) = points.split_at_mut(pivot);
+        let (point, right) = rest
+            .split_first_mut()
+            .expect("Median is always within the points");
+        let build = |points: &mut [Array1<f32>]| {
+            if points.is_empty() {
+                Atomic::null()
+            } else {
+                Atomic::from(self.build_recursive(points, depth + 1))
+            }
+        };
+        let (left, right) = if parallel {
+            rayon::join(|| build(left), || build(right))
+        } else {
+            (build(left), build(right))
+        };
+        Owned::new(KDNode {
+            point: std::mem::take(point),
+            left,
+            right,
+        })
+    }
+
     /// delete multiple entries from the KDTree
     #[tracing::instrument(skip_all)]
     pub fn delete_multi(&self, delete_multi: &[Array1<f32>]) -> Result<usize, Error> {
@@ -530,7 +641,11 @@ impl KDTree {
         }: NearestRecuriveArgs,
     ) {
         if let Some(shared) = unsafe { node.load(Ordering::Acquire, guard).as_ref() } {
-            let distance = self.squared_distance(reference_point, &shared.point);
+            // a NaN distance ranks as far as can be
+            let distance = match self.squared_distance(reference_point, &shared.point) {
+                distance if distance.is_nan() => f32::INFINITY,
+                distance => distance,
+            };
             if heap.len() < n.get() && Self::is_in_accept_list(accept_list, &shared.point) {
                 heap.push(Reverse(OrderedArray(shared.point.clone(), distance)));
             } else if let Some(Reverse(OrderedArray(_, max_distance))) = heap.peek() {
@@ -823,4 +938,101 @@ mod tests {
         // ensure size changes but only one node got removed
         assert_eq!(res, vec![(array![1.1, 2.0, 3.0], 0.010000004),]);
     }
+
+    // every point in the left subtree of a node is less than it in the dimension of its depth
+    // and every point in the right subtree is not
+    fn assert_split(node: &TempKDNode, depth: usize, tree_depth: usize) -> usize {
+        let dim = depth % tree_depth;
+        let mut count = 1;
+        if let Some(left) = &node.left {
+            let mut stack = vec![left.as_ref()];
+            while let Some(child) = stack.pop() {
+                assert!(child.point[dim] < node.point[dim]);
+                stack.extend(
+                    child
+                        .left
+                        .as_deref()
+                        .into_iter()
+                        .chain(child.right.as_deref()),
+                );
+            }
+            count += assert_split(left, depth + 1, tree_depth);
+        }
+        if let Some(right) = &node.right {
+            let mut stack = vec![right.as_ref()];
+            while let Some(child) = stack.pop() {
+                assert!(child.point[dim] >= node.point[dim]);
+                stack.extend(
+                    child
+                        .left
+                        .as_deref()
+                        .into_iter()
+                        .chain(child.right.as_deref()),
+                );
+            }
+            count += assert_split(right, depth + 1, tree_depth);
+        }
+        count
+    }
+
+    #[test]
+    fn test_points() {
+        let dimension = NonZeroUsize::new(2).unwrap();
+        let kdtree = KDTree::new(dimension, dimension).unwrap();
+        assert!(kdtree.points().is_empty());
+        let mut inserted: Vec<_> = (0..20)
+            .map(|i| array![(i * 7 % 20) as f32, (i % 3) as f32])
+            .collect();
+        kdtree.insert_multi(inserted.clone()).unwrap();
+        let sort = |points: &mut Vec<Array1<f32>>| {
+            points.sort_by(|a, b| a.iter().partial_cmp(b.iter()).unwrap())
+        };
+        let mut points = kdtree.points();
+        sort(&mut points);
+        sort(&mut inserted);
+        assert_eq!(points, inserted);
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        let dimension = NonZeroUsize::new(4).unwrap();
+        let kdtree = KDTree::new(dimension, NonZeroUsize::new(3).unwrap()).unwrap();
+        let mut points: Vec<Array1<f32>> = (0..3000)
+            .map(|_| {
+                // few distinct values so that many points share the median of a dimension
+                Array::from(
+                    (0..4)
+                        .map(|_| (rand::random::<f32>() * 8.0).floor())
+                        .collect::<Vec<f32>>(),
+                )
+            })
+            .collect();
+        points.push(points[0].clone());
+        let unique: HashSet<Vec<u32>> = points
+            .iter()
+            .map(|point| point.iter().map(|value| value.to_bits()).collect())
+            .collect();
+        kdtree.bulk_load(points.clone()).unwrap();
+
+        let built: TempKDTree = (&kdtree).into();
+        let count = assert_split(built.root.as_ref().unwrap(), 0, 3);
+        // duplicates are only loaded once
+        assert_eq!(count, unique.len());
+        for point in points.iter().take(100) {
+            let res = kdtree
+                .n_nearest(point, NonZeroUsize::new(1).unwrap(), None)
+                .unwrap();
+            assert_eq!(res, vec![(point.clone(), 0.0)]);
+        }
+        assert_eq!(kdtree.delete(&points[1]).unwrap(), Some(points[1].clone()));
+
+        // a tree that already has points has new ones inserted one by one
+        let extra = array![9.0, 9.0, 9.0, 9.0];
+        kdtree.bulk_load(vec![extra.clone()]).unwrap();
+        let res = kdtree
+            .n_nearest(&extra, NonZeroUsize::new(1).unwrap(), None)
+            .unwrap();
+        assert_eq!(res, vec![(extra, 0.0)]);
+        assert!(kdtree.bulk_load(vec![array![1.0, 2.0]]).is_err());
+    }
 }
@@ -1,4 +1,5 @@
 pub mod error;
+pub mod eval;
 pub mod hnsw;
 pub mod kdtree;
 pub mod utils;
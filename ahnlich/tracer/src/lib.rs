@@ -1,3 +1,7 @@
+mod sampling;
+
+use clap::ValueEnum;
+use sampling::ServerSampler;
 use std::collections::HashMap;
 use tracing_subscriber::fmt::format::FmtSpan;
 
@@ -5,45 +9,151 @@ use opentelemetry::{global, trace::TraceContextExt, Context, KeyValue};
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::{
     propagation::TraceContextPropagator,
-    trace::{self, Sampler},
+    trace::{self, IdGenerator, RandomIdGenerator},
     Resource,
 };
-use std::sync::Once;
+use std::sync::{Once, OnceLock, RwLock};
 use tracing::subscriber::set_global_default;
 use tracing_log::LogTracer;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{layer::SubscriberExt, reload, EnvFilter, Layer, Registry};
+
+pub use sampling::SamplingConfig;
 
 static INIT_ONCE: Once = Once::new();
 
+/// Filter of the subscriber logging through tracing, reloaded as the log level changes
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Logger writing text lines when not tracing, replaced as the log level changes
+static TEXT_LOGGER: OnceLock<RwLock<env_logger::Logger>> = OnceLock::new();
+
+/// Format of the lines logged by the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// A json object per line, carrying the request id and client address of the request being
+    /// processed along with the fields of the event
+    Json,
+}
+
+/// Forwards records to the current [`TEXT_LOGGER`]
+struct TextLogger;
+
+impl log::Log for TextLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        text_logger().is_some_and(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &log::Record) {
+        if let Some(logger) = text_logger() {
+            logger.log(record)
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = text_logger() {
+            logger.flush()
+        }
+    }
+}
+
+fn text_logger() -> Option<std::sync::RwLockReadGuard<'static, env_logger::Logger>> {
+    TEXT_LOGGER.get().and_then(|logger| logger.read().ok())
+}
+
+fn build_text_logger(log_level: &str) -> env_logger::Logger {
+    env_logger::Builder::new().parse_filters(log_level).build()
+}
+
 fn init_logger(log_level: &str) {
     INIT_ONCE.call_once(|| {
-        let mut builder = env_logger::Builder::new();
-        builder.parse_filters(log_level);
-        builder.init();
+        let logger = build_text_logger(log_level);
+        log::set_max_level(logger.filter());
+        let _ = TEXT_LOGGER.set(RwLock::new(logger));
+        log::set_logger(&TextLogger).expect("Failed to set logger");
     });
 }
 
+fn init_json_logger(log_level: &str) {
+    INIT_ONCE.call_once(|| {
+        LogTracer::init().expect("Failed to set logger");
+        let subscriber = Registry::default()
+            .with(reloadable_filter(log_level))
+            .with(json_layer());
+        set_global_default(subscriber).expect("Failed to set default subscriber");
+    });
+}
+
+fn reloadable_filter(log_level: &str) -> reload::Layer<EnvFilter, Registry> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(log_level));
+    let _ = FILTER_HANDLE.set(handle);
+    filter
+}
+
+/// Changes the level the server logs at without restarting it, taking the same directives as
+/// `--log-level`. Only checks the directives when logging is yet to be initialised
+pub fn set_log_level(log_level: &str) -> Result<(), String> {
+    // both loggers take the directives of an env filter
+    let filter = EnvFilter::try_new(log_level).map_err(|err| err.to_string())?;
+    if let Some(handle) = FILTER_HANDLE.get() {
+        return handle.reload(filter).map_err(|err| err.to_string());
+    }
+    match TEXT_LOGGER.get() {
+        Some(logger) => {
+            let reloaded = build_text_logger(log_level);
+            log::set_max_level(reloaded.filter());
+            *logger.write().map_err(|err| err.to_string())? = reloaded;
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Logs events as json lines along with the fields of the spans they happened in
+fn json_layer<S>() -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(false)
+        .with_span_list(true)
+        .with_thread_names(true)
+}
+
 pub fn init_log_or_trace(
     enable_tracing: bool,
     service_name: &'static str,
     otel_endpoint: &Option<String>,
     log_level: &str,
+    log_format: LogFormat,
+    sampling: &SamplingConfig,
 ) {
     if enable_tracing {
         LogTracer::init().expect("Failed to set logger");
         let otel_url = otel_endpoint
             .to_owned()
             .unwrap_or("http://127.0.0.1:4317".to_string());
-        init_tracing(service_name, log_level, &otel_url);
+        init_tracing(service_name, log_level, &otel_url, log_format, sampling);
     } else {
-        init_logger(log_level);
+        match log_format {
+            LogFormat::Text => init_logger(log_level),
+            LogFormat::Json => init_json_logger(log_level),
+        }
     }
     log::info!("Starting {}", service_name);
 }
 
-fn init_tracing(service_name: &'static str, log_level: &str, otel_url: &str) {
-    let env_filter = EnvFilter::new(log_level);
+fn init_tracing(
+    service_name: &'static str,
+    log_level: &str,
+    otel_url: &str,
+    log_format: LogFormat,
+    sampling: &SamplingConfig,
+) {
+    let env_filter = reloadable_filter(log_level);
 
     let otel_layer = tracing_opentelemetry::layer().with_tracer(
         opentelemetry_otlp::new_pipeline()
@@ -55,7 +165,7 @@ fn init_tracing(service_name: &'static str, log_level: &str, otel_url: &str) {
             )
             .with_trace_config(
                 trace::config()
-                    .with_sampler(Sampler::AlwaysOn)
+                    .with_sampler(ServerSampler::new(sampling))
                     .with_resource(Resource::new(vec![KeyValue::new(
                         "service.name",
                         service_name,
@@ -65,13 +175,17 @@ fn init_tracing(service_name: &'static str, log_level: &str, otel_url: &str) {
             .expect("could not build otel pipeline"),
     );
 
-    let json_layer = tracing_subscriber::fmt::layer()
-        .with_level(true)
-        .with_ansi(true)
-        .with_span_events(FmtSpan::CLOSE)
-        .with_thread_names(true);
+    let fmt_layer = match log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_level(true)
+            .with_ansi(true)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_thread_names(true)
+            .boxed(),
+        LogFormat::Json => json_layer().boxed(),
+    };
 
-    let subscriber = Registry::default().with(env_filter).with(json_layer);
+    let subscriber = Registry::default().with(env_filter).with(fmt_layer);
 
     set_global_default(subscriber.with(otel_layer)).expect("Failed to set default subscriber");
     global::set_text_map_propagator(TraceContextPropagator::new());
@@ -129,6 +243,15 @@ impl Traceparent {
     }
 }
 
+/// Correlation id of a request. The trace id of the traceparent sent with the request so that
+/// logs match its traces, or a new random id when it was sent without one
+pub fn request_id(trace_parent: Option<&str>) -> String {
+    match trace_parent.map(Traceparent::parse) {
+        Some(Ok(trace_parent)) => format!("{:032x}", trace_parent.trace_id),
+        _ => format!("{:032x}", RandomIdGenerator::default().new_trace_id()),
+    }
+}
+
 pub fn trace_parent_to_span(trace_parent: String) -> Result<Context, String> {
     let _ = Traceparent::parse(&trace_parent)?;
     let mut carrier = HashMap::new();
@@ -152,4 +275,17 @@ mod tests {
             Traceparent::parse("00-80e1afed08e019fc1110464cfa66635c-7a085853722dc6d2-01").is_ok()
         );
     }
+
+    #[test]
+    fn test_request_id() {
+        assert_eq!(
+            request_id(Some(
+                "00-80e1afed08e019fc1110464cfa66635c-7a085853722dc6d2-01"
+            )),
+            "80e1afed08e019fc1110464cfa66635c"
+        );
+        let generated = request_id(None);
+        assert_eq!(generated.len(), 32);
+        assert_ne!(generated, request_id(None));
+    }
 }
@@ -0,0 +1,209 @@
+use opentelemetry::trace::{
+    Link, SamplingDecision, SamplingResult, SpanKind, TraceContextExt, TraceId,
+};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::trace::{Sampler, ShouldSample};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Which traces are sent to the collector
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingConfig {
+    /// Share of the traces started on the server that are sampled, from 0 to 1
+    pub ratio: f64,
+    /// Follows the decision of the traceparent a client sent instead of sampling the request
+    /// again
+    pub parent_based: bool,
+    /// Most traces started every second that are sampled
+    pub rate_limit: Option<u32>,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            ratio: 1.0,
+            parent_based: true,
+            rate_limit: None,
+        }
+    }
+}
+
+/// Token bucket allowing up to `per_second` traces every second
+#[derive(Debug)]
+struct RateLimiter {
+    per_second: f64,
+    // tokens left along with when they were last topped up
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(per_second: u32) -> Self {
+        let per_second = per_second as f64;
+        Self {
+            per_second,
+            bucket: Mutex::new((per_second, Instant::now())),
+        }
+    }
+
+    fn try_acquire(&self) -> bool {
+        let mut bucket = self.bucket.lock().expect("rate limiter lock poisoned");
+        let (tokens, topped_up) = &mut *bucket;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*topped_up).as_secs_f64() * self.per_second)
+            .min(self.per_second);
+        *topped_up = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Samples the traces that start on the server by ratio and then by rate. Spans started within
+/// another span of the server, such as those of model threads, always follow the decision made
+/// for the request so that traces are never sent in part
+#[derive(Debug, Clone)]
+pub(crate) struct ServerSampler {
+    ratio: Sampler,
+    parent_based: bool,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl ServerSampler {
+    pub(crate) fn new(config: &SamplingConfig) -> Self {
+        Self {
+            ratio: Sampler::TraceIdRatioBased(config.ratio),
+            parent_based: config.parent_based,
+            rate_limiter: config
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+        }
+    }
+}
+
+impl ShouldSample for ServerSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let parent = parent_context
+            .filter(|cx| cx.has_active_span())
+            .map(|cx| cx.span().span_context().clone())
+            .filter(|parent| !parent.is_remote() || self.parent_based);
+        let decision = match &parent {
+            Some(parent) if parent.is_sampled() => SamplingDecision::RecordAndSample,
+            Some(_) => SamplingDecision::Drop,
+            None => {
+                let decision = self
+                    .ratio
+                    .should_sample(parent_context, trace_id, name, span_kind, attributes, links)
+                    .decision;
+                match &self.rate_limiter {
+                    Some(rate_limiter)
+                        if decision == SamplingDecision::RecordAndSample
+                            && !rate_limiter.try_acquire() =>
+                    {
+                        SamplingDecision::Drop
+                    }
+                    _ => decision,
+                }
+            }
+        };
+        SamplingResult {
+            decision,
+            attributes: Vec::new(),
+            trace_state: parent
+                .map(|parent| parent.trace_state().clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceState};
+
+    fn sample(sampler: &ServerSampler, parent: Option<(bool, bool)>) -> SamplingDecision {
+        let parent_context = parent.map(|(sampled, remote)| {
+            Context::new().with_remote_span_context(SpanContext::new(
+                TraceId::from_bytes(1u128.to_be_bytes()),
+                SpanId::from_bytes(1u64.to_be_bytes()),
+                if sampled {
+                    TraceFlags::SAMPLED
+                } else {
+                    TraceFlags::default()
+                },
+                remote,
+                TraceState::default(),
+            ))
+        });
+        sampler
+            .should_sample(
+                parent_context.as_ref(),
+                TraceId::from_bytes(u128::MAX.to_be_bytes()),
+                "query-processor",
+                &SpanKind::Internal,
+                &[],
+                &[],
+            )
+            .decision
+    }
+
+    #[test]
+    fn test_server_sampler() {
+        let never = ServerSampler::new(&SamplingConfig {
+            ratio: 0.0,
+            parent_based: false,
+            rate_limit: None,
+        });
+        assert_eq!(sample(&never, None), SamplingDecision::Drop);
+        // spans within a sampled span of the server are sampled along with it
+        assert_eq!(
+            sample(&never, Some((true, false))),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(sample(&never, Some((true, true))), SamplingDecision::Drop);
+
+        let parent_based = ServerSampler::new(&SamplingConfig {
+            ratio: 1.0,
+            parent_based: true,
+            rate_limit: None,
+        });
+        assert_eq!(
+            sample(&parent_based, Some((false, true))),
+            SamplingDecision::Drop
+        );
+        assert_eq!(
+            sample(&parent_based, Some((true, true))),
+            SamplingDecision::RecordAndSample
+        );
+
+        let rate_limited = ServerSampler::new(&SamplingConfig {
+            ratio: 1.0,
+            parent_based: true,
+            rate_limit: Some(2),
+        });
+        assert_eq!(
+            sample(&rate_limited, None),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(
+            sample(&rate_limited, None),
+            SamplingDecision::RecordAndSample
+        );
+        assert_eq!(sample(&rate_limited, None), SamplingDecision::Drop);
+        // children of sampled traces do not count towards the limit
+        assert_eq!(
+            sample(&rate_limited, Some((true, false))),
+            SamplingDecision::RecordAndSample
+        );
+    }
+}
@@ -1,17 +1,24 @@
-use ahnlich_types::ai::{AIModel, AIStoreInputType, PreprocessAction};
-use ahnlich_types::keyval::StoreInput;
+use ahnlich_types::ai::{
+    AIModel, AIStoreInputType, ChunkedEntry, ImageFormat, ImagePreprocessing, ImageResize,
+    MultimodalFusion, PreprocessAction, TextTruncation,
+};
+use ahnlich_types::keyval::{EvictionPolicy, StoreEviction, StoreInput};
 use ahnlich_types::predicate::Predicate;
 use ahnlich_types::predicate::PredicateCondition;
-use ahnlich_types::similarity::{Algorithm, NonLinearAlgorithm};
+use ahnlich_types::similarity::{
+    Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm, RecencyBoost, Similarity,
+};
 use ahnlich_types::{
     ai::{AIQuery, AIServerQuery},
     keyval::StoreName,
     metadata::{MetadataKey, MetadataValue},
 };
+use ahnlich_types::{ErrorPolicy, Priority};
 use serde_reflection::Registry;
 use serde_reflection::{Samples, Tracer, TracerConfig};
 use std::collections::{HashMap as StdHashMap, HashSet};
-use std::num::NonZeroUsize;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::time::Duration;
 
 pub fn trace_ai_query_enum() -> Registry {
     let mut tracer = Tracer::new(TracerConfig::default());
@@ -53,11 +60,25 @@ pub fn trace_ai_query_enum() -> Registry {
         non_linear_indices: test_non_linear_indices,
         error_if_exists: false,
         store_original: true,
+        image_preprocessing: ImagePreprocessing {
+            exif_orientation: true,
+            resize: ImageResize::Letterbox,
+            convert_format: Some(ImageFormat::Png),
+        },
+        text_truncation: TextTruncation::SplitAndAverage,
+        timestamp_key: Some(MetadataKey::new("published".into())),
+        multimodal_fusion: Some(MultimodalFusion::Weighted { text_weight: 0.7 }),
+        eviction: Some(StoreEviction {
+            max_entries: NonZeroUsize::new(10_000),
+            max_bytes: NonZeroUsize::new(64 << 20),
+            policy: EvictionPolicy::LFU,
+        }),
     };
 
     let get_pred = AIQuery::GetPred {
         store: sample_store_name.clone(),
         condition: test_predicate_condition.clone(),
+        include_system_metadata: false,
     };
 
     let get_sim_n = AIQuery::GetSimN {
@@ -67,6 +88,49 @@ pub fn trace_ai_query_enum() -> Registry {
         closest_n: NonZeroUsize::new(4).unwrap(),
         algorithm: Algorithm::CosineSimilarity,
         preprocess_action: PreprocessAction::ModelPreprocessing,
+        include_system_metadata: false,
+        min_score: Some(Similarity(0.5)),
+        max_distance: Some(Similarity(1.0)),
+        normalize_scores: false,
+        group_by: Some(MetadataKey::new("brand".into())),
+        group_size: NonZeroUsize::new(2).unwrap(),
+        additional_search_inputs: vec![test_search_input.clone()],
+        fusion: FusionStrategy::ReciprocalRankFusion,
+        recency_boost: Some(RecencyBoost {
+            half_life: NonZeroU64::new(86400).unwrap(),
+            weight: 0.3,
+        }),
+        filter_strategy: FilterStrategy::Post,
+    };
+
+    let answer_question = AIQuery::AnswerQuestion {
+        store: sample_store_name.clone(),
+        question: "Who is the author?".to_string(),
+        condition: Some(test_predicate_condition.clone()),
+        closest_n: NonZeroUsize::new(3).unwrap(),
+        algorithm: Algorithm::CosineSimilarity,
+        include_system_metadata: false,
+    };
+
+    let cache_lookup = AIQuery::CacheLookup {
+        store: sample_store_name.clone(),
+        prompt: "What is the capital of France?".to_string(),
+        threshold: Similarity(0.95),
+    };
+
+    let cache_store = AIQuery::CacheStore {
+        store: sample_store_name.clone(),
+        prompt: "What is the capital of France?".to_string(),
+        response: "Paris".to_string(),
+        ttl_secs: Some(3600),
+    };
+
+    let classify = AIQuery::Classify {
+        input: test_search_input_bin.clone(),
+        input_model: AIModel::ClipVitB32Image,
+        labels: vec!["cat".to_string(), "dog".to_string()],
+        label_model: AIModel::ClipVitB32Text,
+        preprocess_action: PreprocessAction::ModelPreprocessing,
     };
 
     let create_index = AIQuery::CreatePredIndex {
@@ -83,7 +147,16 @@ pub fn trace_ai_query_enum() -> Registry {
     let set = AIQuery::Set {
         store: sample_store_name.clone(),
         preprocess_action: PreprocessAction::NoPreprocessing,
-        inputs: vec![(test_search_input_bin.clone(), store_value)],
+        inputs: vec![(test_search_input_bin.clone(), store_value.clone())],
+    };
+    let start_chunked_set = AIQuery::StartChunkedSet {
+        store: sample_store_name.clone(),
+        entries: vec![ChunkedEntry {
+            input_type: AIStoreInputType::Image,
+            size: 20_000_000,
+            value: store_value,
+        }],
+        preprocess_action: PreprocessAction::ModelPreprocessing,
     };
 
     let del_key = AIQuery::DelKey {
@@ -94,9 +167,22 @@ pub fn trace_ai_query_enum() -> Registry {
         store: sample_store_name.clone(),
         error_if_not_exists: true,
     };
+    let get_job = AIQuery::GetJob { job_id: 1 };
+    let cancel_job = AIQuery::CancelJob { job_id: 1 };
+    let migrate_store = AIQuery::MigrateStore {
+        source: StoreName("Main".to_string()),
+        destination: StoreName("Migrated".to_string()),
+        new_index_model: AIModel::BGEBaseEnV15,
+        batch_size: NonZeroUsize::new(100).unwrap(),
+    };
+    let warmup = AIQuery::Warmup {
+        stores: HashSet::from_iter([sample_store_name.clone()]),
+        models: HashSet::from_iter([AIModel::AllMiniLML6V2]),
+    };
     let trace_id = "00-djf9039023r3-1er".to_string();
     let server_query_with_trace_id = AIServerQuery::with_capacity_and_tracing_id(2, Some(trace_id));
-    let server_query = AIServerQuery::from_queries(&[del_key.clone(), set.clone()]);
+    let mut server_query = AIServerQuery::from_queries(&[del_key.clone(), set.clone()]);
+    server_query.set_timeout(Duration::from_secs(5));
 
     // trace each query variant
     let _ = tracer
@@ -107,6 +193,22 @@ pub fn trace_ai_query_enum() -> Registry {
         .trace_value(&mut samples, &get_sim_n)
         .expect("Error tracing the variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &answer_question)
+        .expect("Error tracing the variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &cache_lookup)
+        .expect("Error tracing the cache lookup variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &cache_store)
+        .expect("Error tracing the cache store variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &classify)
+        .expect("Error tracing the variant");
+
     let _ = tracer
         .trace_value(&mut samples, &get_pred)
         .expect("Error tracing the variant");
@@ -124,9 +226,24 @@ pub fn trace_ai_query_enum() -> Registry {
     let _ = tracer
         .trace_value(&mut samples, &del_key)
         .expect("Error tracing the variant");
+    let _ = tracer
+        .trace_value(&mut samples, &get_job)
+        .expect("Error tracing the get job variant");
+    let _ = tracer
+        .trace_value(&mut samples, &cancel_job)
+        .expect("Error tracing the cancel job variant");
+    let _ = tracer
+        .trace_value(&mut samples, &migrate_store)
+        .expect("Error tracing the migrate store variant");
+    let _ = tracer
+        .trace_value(&mut samples, &start_chunked_set)
+        .expect("Error tracing the start chunked set variant");
     let _ = tracer
         .trace_value(&mut samples, &drop_store)
         .expect("Error tracing the variant");
+    let _ = tracer
+        .trace_value(&mut samples, &warmup)
+        .expect("Error tracing the warmup variant");
     // end of trace each query variant
     let _ = tracer
         .trace_value(&mut samples, &server_query)
@@ -145,6 +262,27 @@ pub fn trace_ai_query_enum() -> Registry {
     tracer
         .trace_simple_type::<NonLinearAlgorithm>()
         .expect("Error tracing NonLinearAlgorithm");
+    tracer
+        .trace_simple_type::<FusionStrategy>()
+        .expect("Error tracing FusionStrategy");
+    tracer
+        .trace_simple_type::<FilterStrategy>()
+        .expect("Error tracing FilterStrategy");
+    tracer
+        .trace_simple_type::<ErrorPolicy>()
+        .expect("Error tracing ErrorPolicy");
+    tracer
+        .trace_simple_type::<Priority>()
+        .expect("Error tracing Priority");
+    tracer
+        .trace_simple_type::<ImageResize>()
+        .expect("Error tracing ImageResize");
+    tracer
+        .trace_simple_type::<ImageFormat>()
+        .expect("Error tracing ImageFormat");
+    tracer
+        .trace_simple_type::<TextTruncation>()
+        .expect("Error tracing TextTruncation");
     // predicate conditions
     let _ = tracer
         .trace_type::<PredicateCondition>(&samples)
@@ -1,17 +1,28 @@
 use ahnlich_types::predicate::Predicate;
 use ahnlich_types::predicate::PredicateCondition;
 use ahnlich_types::similarity::Algorithm;
+use ahnlich_types::similarity::FilterStrategy;
+use ahnlich_types::similarity::FusionStrategy;
 use ahnlich_types::similarity::NonLinearAlgorithm;
+use ahnlich_types::similarity::RecencyBoost;
+use ahnlich_types::similarity::SearchTerm;
+use ahnlich_types::similarity::Similarity;
+use ahnlich_types::similarity::TermVector;
 use ahnlich_types::{
-    db::{DBQuery, ServerDBQuery},
-    keyval::{StoreKey, StoreName},
+    db::{DBQuery, MirrorAction, NamespaceQuota, ServerDBQuery, StoreManifest},
+    keyval::{
+        EvictionPolicy, KeyElementType, NonFiniteVectors, StorageTier, StoreEviction, StoreKey,
+        StoreName, VectorNormalization,
+    },
     metadata::{MetadataKey, MetadataValue},
 };
+use ahnlich_types::{ErrorPolicy, Priority};
 use serde_reflection::Registry;
 use serde_reflection::{Samples, Tracer, TracerConfig};
 use std::collections::HashMap as StdHashMap;
 use std::collections::HashSet;
-use std::num::NonZeroUsize;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::time::Duration;
 
 pub fn trace_db_query_enum() -> Registry {
     let input_arr_1 = ndarray::array![0.1, 0.2, 0.3, 0.4, 0.5];
@@ -37,6 +48,18 @@ pub fn trace_db_query_enum() -> Registry {
         create_predicates: test_create_predicates.clone(),
         non_linear_indices: test_non_linear_indices,
         error_if_exists: true,
+        timestamp_key: Some(MetadataKey::new("published".into())),
+        storage_tier: StorageTier::Memory,
+        infer_dimension: false,
+        key_element_type: KeyElementType::Float32,
+        normalization: VectorNormalization::None,
+        index_seed: Some(42),
+        non_finite_vectors: NonFiniteVectors::Sanitize,
+        eviction: Some(StoreEviction {
+            max_entries: NonZeroUsize::new(10000),
+            max_bytes: NonZeroUsize::new(1048576),
+            policy: EvictionPolicy::LRU,
+        }),
     };
 
     let get_key = DBQuery::GetKey {
@@ -47,12 +70,61 @@ pub fn trace_db_query_enum() -> Registry {
         store: sample_store_name.clone(),
         keys: sample_store_keys.clone(),
     };
+    let list_entries = DBQuery::ListEntries {
+        store: sample_store_name.clone(),
+        limit: NonZeroUsize::new(100).unwrap(),
+        cursor: Some("af1349b9f5f9a1a6a0404dea36dcc949".into()),
+        include_vectors: true,
+    };
     let get_sim_n = DBQuery::GetSimN {
         store: sample_store_name.clone(),
         search_input: store_key.clone(),
         closest_n: NonZeroUsize::new(2).unwrap(),
         algorithm: ahnlich_types::similarity::Algorithm::CosineSimilarity,
         condition: Some(test_predicate_condition.clone()),
+        min_score: Some(Similarity(0.5)),
+        max_distance: Some(Similarity(1.0)),
+        normalize_scores: false,
+        group_by: Some(MetadataKey::new("brand".into())),
+        group_size: NonZeroUsize::new(2).unwrap(),
+        additional_search_inputs: vec![store_key.clone()],
+        fusion: FusionStrategy::ReciprocalRankFusion,
+        recency_boost: Some(RecencyBoost {
+            half_life: NonZeroU64::new(86400).unwrap(),
+            weight: 0.3,
+        }),
+        filter_strategy: FilterStrategy::Post,
+        search_terms: vec![
+            SearchTerm {
+                vector: TermVector::Key(store_key.clone()),
+                weight: 1.0,
+            },
+            SearchTerm {
+                vector: TermVector::KeyId("af1349b9f5f9a1a6a0404dea36dcc949".into()),
+                weight: -1.0,
+            },
+        ],
+        exclude_keys: vec![
+            TermVector::Key(store_key.clone()),
+            TermVector::KeyId("af1349b9f5f9a1a6a0404dea36dcc949".into()),
+        ],
+        unordered_ties: true,
+    };
+
+    let get_sim_n_by_key = DBQuery::GetSimNByKey {
+        store: sample_store_name.clone(),
+        key_id: "af1349b9f5f9a1a6a0404dea36dcc949".into(),
+        closest_n: NonZeroUsize::new(2).unwrap(),
+        algorithm: ahnlich_types::similarity::Algorithm::CosineSimilarity,
+        condition: Some(test_predicate_condition.clone()),
+    };
+
+    let batch_get_sim_n = DBQuery::BatchGetSimN {
+        store: sample_store_name.clone(),
+        search_inputs: vec![store_key.clone(), store_key.clone()],
+        closest_n: NonZeroUsize::new(2).unwrap(),
+        algorithm: ahnlich_types::similarity::Algorithm::CosineSimilarity,
+        condition: Some(test_predicate_condition.clone()),
     };
 
     //StoreValue = StdHashMap<MetadataKey, MetadataValue>
@@ -80,44 +152,209 @@ pub fn trace_db_query_enum() -> Registry {
         store: sample_store_name.clone(),
         condition: test_predicate_condition.clone(),
     };
+    let countpred_variant = DBQuery::CountPred {
+        store: sample_store_name.clone(),
+        condition: test_predicate_condition.clone(),
+        exact: false,
+    };
     let deletepred_variant = DBQuery::DelPred {
         store: sample_store_name.clone(),
         condition: test_predicate_condition.clone(),
     };
 
-    let server_query =
+    let deletepred_async_variant = DBQuery::DelPredAsync {
+        store: sample_store_name.clone(),
+        condition: test_predicate_condition.clone(),
+    };
+    let get_job_variant = DBQuery::GetJob { job_id: 1 };
+    let cancel_job_variant = DBQuery::CancelJob { job_id: 1 };
+    let describe_store_variant = DBQuery::DescribeStore {
+        store: sample_store_name.clone(),
+    };
+    let compact_store_variant = DBQuery::CompactStore {
+        store: sample_store_name.clone(),
+    };
+    let check_store_variant = DBQuery::CheckStore {
+        store: sample_store_name.clone(),
+        repair: true,
+    };
+    let scrub_store_variant = DBQuery::ScrubStore {
+        store: sample_store_name.clone(),
+        delete: true,
+    };
+    let benchmark_store_variant = DBQuery::BenchmarkStore {
+        store: sample_store_name.clone(),
+        sample_queries: NonZeroUsize::new(100).unwrap(),
+        concurrency: NonZeroUsize::new(4).unwrap(),
+        duration: 5000,
+    };
+    let export_store_parquet_variant = DBQuery::ExportStoreParquet {
+        store: sample_store_name.clone(),
+        path: "main/export.parquet".to_string(),
+    };
+    let set_bulk_write_variant = DBQuery::SetBulkWrite {
+        store: sample_store_name.clone(),
+        enabled: true,
+    };
+    let warmup_variant = DBQuery::Warmup {
+        stores: HashSet::from_iter([sample_store_name.clone()]),
+    };
+    let restore_store_variant = DBQuery::RestoreStore {
+        store: sample_store_name.clone(),
+    };
+    let set_quota_variant = DBQuery::SetQuota {
+        namespace: "acme".to_string(),
+        quota: NamespaceQuota {
+            max_stores: Some(10),
+            max_vectors: Some(100000),
+            max_memory_bytes: Some(1024),
+        },
+    };
+    let control_mirror_variant = DBQuery::ControlMirror {
+        action: MirrorAction::Resync,
+    };
+    let apply_manifest_variant = DBQuery::ApplyManifest {
+        stores: vec![StoreManifest {
+            store: sample_store_name.clone(),
+            dimension: NonZeroUsize::new(5).unwrap(),
+            predicates: HashSet::from_iter([MetadataKey::new("author".into())]),
+            non_linear_indices: HashSet::from_iter([NonLinearAlgorithm::KDTree]),
+            timestamp_key: Some(MetadataKey::new("created_at".into())),
+            storage_tier: StorageTier::Memory,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: Some(42),
+            non_finite_vectors: NonFiniteVectors::Sanitize,
+            eviction: Some(StoreEviction {
+                max_entries: NonZeroUsize::new(10000),
+                max_bytes: None,
+                policy: EvictionPolicy::LFU,
+            }),
+        }],
+        dry_run: true,
+    };
+    let diff_manifest_variant = DBQuery::DiffManifest {
+        stores: vec![StoreManifest {
+            store: sample_store_name.clone(),
+            dimension: NonZeroUsize::new(5).unwrap(),
+            predicates: HashSet::from_iter([MetadataKey::new("author".into())]),
+            non_linear_indices: HashSet::new(),
+            timestamp_key: None,
+            storage_tier: StorageTier::Memory,
+            key_element_type: KeyElementType::Float32,
+            normalization: VectorNormalization::None,
+            index_seed: Some(42),
+            non_finite_vectors: NonFiniteVectors::Sanitize,
+            eviction: None,
+        }],
+    };
+
+    let mut server_query =
         ServerDBQuery::from_queries(&[deletepred_variant.clone(), set_query.clone()]);
+    server_query.set_timeout(Duration::from_secs(5));
     let trace_id = "00-djf9039023r3-1er".to_string();
     let server_query_with_trace_id = ServerDBQuery::with_capacity_and_tracing_id(2, Some(trace_id))
         .expect("Could not create server query");
 
-    let _ = tracer
+    tracer
         .trace_value(&mut samples, &create_store)
         .expect("Error tracing the variant");
-    let _ = tracer
+    tracer
         .trace_value(&mut samples, &get_key)
         .expect("Error tracing the getkey variant");
-    let _ = tracer
+    tracer
         .trace_value(&mut samples, &delete_key)
         .expect("Error tracing the deleteKey variant");
-    let _ = tracer
+    tracer
+        .trace_value(&mut samples, &list_entries)
+        .expect("Error tracing the listentries variant");
+    tracer
         .trace_value(&mut samples, &get_sim_n)
         .expect("Error tracing the GetSimN variant");
-    let _ = tracer
+    tracer
+        .trace_value(&mut samples, &get_sim_n_by_key)
+        .expect("Error tracing the GetSimNByKey variant");
+    tracer
+        .trace_value(&mut samples, &batch_get_sim_n)
+        .expect("Error tracing the BatchGetSimN variant");
+    tracer
         .trace_value(&mut samples, &set_query)
         .expect("Error tracing the setquery varient");
-    let _ = tracer
+    tracer
         .trace_value(&mut samples, &getpred_variant)
         .expect("Error tracing the getpred variant");
-    let _ = tracer
+    tracer
+        .trace_value(&mut samples, &countpred_variant)
+        .expect("Error tracing the countpred variant");
+    tracer
         .trace_value(&mut samples, &deletepred_variant)
         .expect("Error tracing the deletepred variant");
+    tracer
+        .trace_value(&mut samples, &deletepred_async_variant)
+        .expect("Error tracing the deletepred async variant");
+    tracer
+        .trace_value(&mut samples, &get_job_variant)
+        .expect("Error tracing the getjob variant");
+    tracer
+        .trace_value(&mut samples, &cancel_job_variant)
+        .expect("Error tracing the canceljob variant");
+    tracer
+        .trace_value(&mut samples, &describe_store_variant)
+        .expect("Error tracing the describestore variant");
+
+    tracer
+        .trace_value(&mut samples, &compact_store_variant)
+        .expect("Error tracing the compactstore variant");
+
+    tracer
+        .trace_value(&mut samples, &check_store_variant)
+        .expect("Error tracing the checkstore variant");
+
+    tracer
+        .trace_value(&mut samples, &scrub_store_variant)
+        .expect("Error tracing the scrubstore variant");
+
+    tracer
+        .trace_value(&mut samples, &benchmark_store_variant)
+        .expect("Error tracing the benchmarkstore variant");
+
+    tracer
+        .trace_value(&mut samples, &export_store_parquet_variant)
+        .expect("Error tracing the exportstoreparquet variant");
+
+    tracer
+        .trace_value(&mut samples, &set_bulk_write_variant)
+        .expect("Error tracing the setbulkwrite variant");
+
+    tracer
+        .trace_value(&mut samples, &warmup_variant)
+        .expect("Error tracing the warmup variant");
+
+    tracer
+        .trace_value(&mut samples, &restore_store_variant)
+        .expect("Error tracing the restorestore variant");
 
-    let _ = tracer
+    tracer
+        .trace_value(&mut samples, &set_quota_variant)
+        .expect("Error tracing the setquota variant");
+
+    tracer
+        .trace_value(&mut samples, &control_mirror_variant)
+        .expect("Error tracing the controlmirror variant");
+
+    tracer
+        .trace_value(&mut samples, &apply_manifest_variant)
+        .expect("Error tracing the applymanifest variant");
+
+    tracer
+        .trace_value(&mut samples, &diff_manifest_variant)
+        .expect("Error tracing the diffmanifest variant");
+
+    tracer
         .trace_value(&mut samples, &server_query)
         .expect("Error tracing the server_query");
 
-    let _ = tracer
+    tracer
         .trace_value(&mut samples, &server_query_with_trace_id)
         .expect("Error tracing the server_query_with_trace_id");
 
@@ -131,26 +368,56 @@ pub fn trace_db_query_enum() -> Registry {
     tracer
         .trace_simple_type::<NonLinearAlgorithm>()
         .expect("Error tracing NonLinearAlgorithm");
+    tracer
+        .trace_simple_type::<FusionStrategy>()
+        .expect("Error tracing FusionStrategy");
+    tracer
+        .trace_simple_type::<FilterStrategy>()
+        .expect("Error tracing FilterStrategy");
+    tracer
+        .trace_simple_type::<StorageTier>()
+        .expect("Error tracing StorageTier");
+    tracer
+        .trace_simple_type::<KeyElementType>()
+        .expect("Error tracing KeyElementType");
+    tracer
+        .trace_simple_type::<VectorNormalization>()
+        .expect("Error tracing VectorNormalization");
+    tracer
+        .trace_simple_type::<NonFiniteVectors>()
+        .expect("Error tracing NonFiniteVectors");
+    tracer
+        .trace_simple_type::<EvictionPolicy>()
+        .expect("Error tracing EvictionPolicy");
+    tracer
+        .trace_simple_type::<MirrorAction>()
+        .expect("Error tracing MirrorAction");
+    tracer
+        .trace_simple_type::<ErrorPolicy>()
+        .expect("Error tracing ErrorPolicy");
+    tracer
+        .trace_simple_type::<Priority>()
+        .expect("Error tracing Priority");
     tracer
         .trace_simple_type::<Predicate>()
         .expect("Error tracing Predicate");
     //
     // predicate conditions
-    let _ = tracer
+    tracer
         .trace_type::<PredicateCondition>(&samples)
         .expect("Error tracing predicate condition");
 
-    let _ = tracer
+    tracer
         .trace_type::<DBQuery>(&samples)
         .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
         .unwrap();
 
-    let _ = tracer
+    tracer
         .trace_type::<ServerDBQuery>(&samples)
         .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
         .unwrap();
 
-    let _ = tracer
+    tracer
         .trace_type::<MetadataValue>(&samples)
         .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
         .unwrap();
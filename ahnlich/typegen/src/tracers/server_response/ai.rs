@@ -1,14 +1,20 @@
-use ahnlich_types::ai::AIStoreInputType;
+use ahnlich_types::ai::{
+    AIExecutionProvider, AIMemoryBreakdown, AIModelInfo, AIStoreDescription, AIStoreInputType,
+    AnswerSpan, CachedResponse, ChunkedEntry, ModelMemory, MultimodalFusion, QuestionAnswer, Usage,
+    UsageStats,
+};
 use ahnlich_types::keyval::StoreInput;
 use ahnlich_types::similarity::Similarity;
 use ahnlich_types::{
     ai::{AIModel, AIServerResponse, AIServerResult, AIStoreInfo},
-    client::ConnectedClient,
+    client::{ConnectedClient, ConnectionMemory, ConnectionStats},
     db::{ServerInfo, StoreUpsert},
+    error::{ErrorCode, ErrorResponse},
+    jobs::{JobKind, JobState, JobStatus},
     keyval::StoreName,
     metadata::{MetadataKey, MetadataValue},
     version::Version,
-    ServerType,
+    ConfigReload, Priority, PriorityStats, RequestLimits, ServerType,
 };
 use serde_reflection::Registry;
 use serde_reflection::{Samples, Tracer, TracerConfig};
@@ -23,6 +29,11 @@ pub fn trace_ai_server_response_enum() -> Registry {
 
     // trace complex variants
 
+    let request_limits = RequestLimits {
+        message_size: 1_048_576,
+        batch_size: Some(100),
+    };
+
     let connected_clients = HashSet::from_iter([ConnectedClient {
         address: "127.0.0.1".to_string(),
         time_connected: SystemTime::now(),
@@ -35,8 +46,58 @@ pub fn trace_ai_server_response_enum() -> Registry {
         query_model: AIModel::AllMiniLML6V2,
         index_model: AIModel::AllMiniLML6V2,
         embedding_size: 20,
+        request_limits,
     }]));
 
+    let store_description = AIServerResponse::StoreDescription(AIStoreDescription {
+        name: StoreName("testing".to_owned()),
+        query_model: AIModel::ClipVitB32Text,
+        index_model: AIModel::ClipVitB32Image,
+        embedding_size: 512,
+        index_inputs: vec![AIStoreInputType::Multimodal],
+        query_inputs: vec![AIStoreInputType::RawString, AIStoreInputType::Multimodal],
+        multimodal_fusion: Some(MultimodalFusion::Average),
+    });
+
+    let supported_model_list = AIServerResponse::SupportedModelList(vec![AIModelInfo {
+        model: AIModel::AllMiniLML6V2,
+        input_type: AIStoreInputType::RawString,
+        embedding_size: 384,
+        execution_provider: Some(AIExecutionProvider::CUDA),
+    }]);
+
+    let config_reloaded_variant = AIServerResponse::ConfigReloaded(ConfigReload {
+        changed: vec!["log-level".to_owned()],
+        requires_restart: vec!["port".to_owned()],
+    });
+
+    let memory_breakdown_variant = AIServerResponse::MemoryBreakdown(AIMemoryBreakdown {
+        limit: 1073741824,
+        remaining: 1063741824,
+        models: vec![ModelMemory {
+            model: AIModel::AllMiniLML6V2,
+            loaded: true,
+            replicas: 1,
+            weights_bytes: 90405214,
+        }],
+        connections: ConnectionMemory {
+            clients: 1,
+            buffers: 8192,
+        },
+    });
+
+    let usage = Usage {
+        inputs: 3,
+        tokens: 42,
+        images: 0,
+        inference_ms: 12,
+    };
+    let usage_stats = AIServerResponse::UsageStats(UsageStats {
+        stores: StdHashMap::from_iter([(StoreName("testing".to_owned()), usage)]),
+        clients: StdHashMap::from_iter([("127.0.0.1".to_owned(), usage)]),
+        models: StdHashMap::from_iter([(AIModel::AllMiniLML6V2, usage)]),
+    });
+
     let info_server = AIServerResponse::InfoServer(ServerInfo {
         address: "127.0.0.1".to_owned(),
         version: Version {
@@ -44,9 +105,36 @@ pub fn trace_ai_server_response_enum() -> Registry {
             minor: 0,
             patch: 1,
         },
+        min_client_version: Version {
+            major: 1,
+            minor: 0,
+            patch: 0,
+        },
+        max_client_version: Version {
+            major: 1,
+            minor: 0,
+            patch: 1,
+        },
         r#type: ServerType::AI,
         limit: 121,
         remaining: 20,
+        request_limits,
+        in_flight_memory: 1024,
+        deadlines_exceeded: 1,
+        priorities: vec![PriorityStats {
+            priority: Priority::Bulk,
+            requests: 10,
+            queued: 1,
+            wait_ms: 50,
+            latency_ms: 200,
+            max_latency_ms: 40,
+        }],
+        connections: ConnectionStats {
+            clients: 90,
+            maximum_clients: 100,
+            maximum_clients_per_host: Some(10),
+            rejected: 3,
+        },
     });
 
     let set_variant = AIServerResponse::Set(StoreUpsert {
@@ -81,6 +169,50 @@ pub fn trace_ai_server_response_enum() -> Registry {
         Similarity(0.999_f32),
     )]);
 
+    let answer_variant = AIServerResponse::Answer {
+        answer: Some(QuestionAnswer {
+            text: "Lex Luthor".to_string(),
+            span: Some(AnswerSpan {
+                source: 0,
+                start: 4,
+                end: 14,
+            }),
+            score: Some(Similarity(0.8)),
+        }),
+        sources: vec![(
+            Some(store_input.clone()),
+            store_value.clone(),
+            Similarity(0.9),
+        )],
+    };
+
+    let cache_hit_variant = AIServerResponse::CacheHit(Some(CachedResponse {
+        prompt: Some("What is the capital of France?".to_string()),
+        response: "Paris".to_string(),
+        similarity: Similarity(0.97),
+        expires_at: Some(1_700_003_600),
+    }));
+
+    let classify_variant = AIServerResponse::Classify(vec![("cat".to_string(), Similarity(0.9))]);
+
+    let job_status = JobStatus {
+        id: 1,
+        kind: JobKind::DelPred,
+        state: JobState::Running,
+        processed: 1000,
+        total: 2500,
+    };
+    let job_status_variant = AIServerResponse::JobStatus(job_status.clone());
+    let job_list_variant = AIServerResponse::JobList(vec![job_status]);
+    let chunked_get_variant = AIServerResponse::ChunkedGetStarted {
+        transfer_id: 1,
+        entries: vec![ChunkedEntry {
+            input_type: AIStoreInputType::Image,
+            size: 20_000_000,
+            value: store_value.clone(),
+        }],
+    };
+
     let _ = tracer
         .trace_value(&mut samples, &client_list)
         .expect("Error tracing ClientList variant");
@@ -89,10 +221,30 @@ pub fn trace_ai_server_response_enum() -> Registry {
         .trace_value(&mut samples, &store_list)
         .expect("Error tracing StoreList variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &store_description)
+        .expect("Error tracing StoreDescription variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &supported_model_list)
+        .expect("Error tracing SupportedModelList variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &usage_stats)
+        .expect("Error tracing UsageStats variant");
+
     let _ = tracer
         .trace_value(&mut samples, &info_server)
         .expect("Error tracing InfoServer variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &memory_breakdown_variant)
+        .expect("Error tracing MemoryBreakdown variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &config_reloaded_variant)
+        .expect("Error tracing ConfigReloaded variant");
+
     let _ = tracer
         .trace_value(&mut samples, &set_variant)
         .expect("Error tracing Set variant");
@@ -105,6 +257,47 @@ pub fn trace_ai_server_response_enum() -> Registry {
         .trace_value(&mut samples, &getsimn_variant)
         .expect("Error tracing GetSimN variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &answer_variant)
+        .expect("Error tracing Answer variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &cache_hit_variant)
+        .expect("Error tracing CacheHit variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &classify_variant)
+        .expect("Error tracing Classify variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &job_status_variant)
+        .expect("Error tracing JobStatus variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &job_list_variant)
+        .expect("Error tracing JobList variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &chunked_get_variant)
+        .expect("Error tracing ChunkedGetStarted variant");
+
+    tracer
+        .trace_simple_type::<JobKind>()
+        .expect("Error tracing JobKind");
+    tracer
+        .trace_simple_type::<JobState>()
+        .expect("Error tracing JobState");
+    tracer
+        .trace_simple_type::<Priority>()
+        .expect("Error tracing Priority");
+
+    tracer
+        .trace_simple_type::<ErrorCode>()
+        .expect("Error tracing ErrorCode");
+    tracer
+        .trace_simple_type::<AIExecutionProvider>()
+        .expect("Error tracing AIExecutionProvider");
+
     // trace server response
 
     let _ = tracer
@@ -113,7 +306,7 @@ pub fn trace_ai_server_response_enum() -> Registry {
         .unwrap();
 
     let _ = tracer
-        .trace_type::<Result<AIServerResponse, String>>(&samples)
+        .trace_type::<Result<AIServerResponse, ErrorResponse>>(&samples)
         .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
         .unwrap();
 
@@ -1,16 +1,28 @@
-use ahnlich_types::similarity::Similarity;
+use ahnlich_types::similarity::{NonLinearAlgorithm, Similarity};
 use ahnlich_types::{
-    client::ConnectedClient,
-    db::{ServerInfo, ServerResponse, ServerResult, StoreInfo, StoreUpsert},
-    keyval::{StoreKey, StoreName},
+    client::{ConnectedClient, ConnectionMemory, ConnectionStats},
+    db::{
+        EntryPage, IndexCheck, ListedEntry, ManifestChange, ManifestDrift, MemoryBreakdown,
+        MirrorState, MirrorStatus, NamespaceQuota, NamespaceUsage, PredicateIndexStats, SearchPath,
+        SearchPathBenchmark, ServerInfo, ServerResponse, ServerResult, SettingDrift,
+        StoreBenchmark, StoreCheck, StoreCompaction, StoreDescription, StoreIndex, StoreInfo,
+        StoreMemory, StoreUpsert, TrashedStoreInfo,
+    },
+    error::{ErrorCode, ErrorResponse},
+    jobs::{JobKind, JobState, JobStatus},
+    keyval::{
+        EvictionPolicy, KeyElementType, NonFiniteVectors, StorageTier, StoreEviction, StoreKey,
+        StoreName, VectorNormalization,
+    },
     metadata::{MetadataKey, MetadataValue},
     version::Version,
-    ServerType,
+    ConfigReload, Priority, PriorityStats, RequestLimits, ServerType,
 };
 use serde_reflection::Registry;
 use serde_reflection::{Samples, Tracer, TracerConfig};
 use std::collections::HashMap as StdHashMap;
 use std::collections::HashSet;
+use std::num::NonZeroUsize;
 use std::time::SystemTime;
 
 pub fn trace_db_server_response_enum() -> Registry {
@@ -20,6 +32,11 @@ pub fn trace_db_server_response_enum() -> Registry {
 
     // trace complex variants
 
+    let request_limits = RequestLimits {
+        message_size: 1_048_576,
+        batch_size: Some(100),
+    };
+
     let connected_clients = HashSet::from_iter([ConnectedClient {
         address: "127.0.0.1".to_string(),
         time_connected: SystemTime::now(),
@@ -27,11 +44,39 @@ pub fn trace_db_server_response_enum() -> Registry {
 
     let client_list = ServerResponse::ClientList(connected_clients.clone());
 
-    let store_list = ServerResponse::StoreList(HashSet::from_iter([StoreInfo {
+    let store_info = StoreInfo {
         name: StoreName("testing".to_owned()),
         len: 12,
         size_in_bytes: 91,
-    }]));
+        dimension: NonZeroUsize::new(3).unwrap(),
+        request_limits,
+    };
+    let store_list = ServerResponse::StoreList(HashSet::from_iter([store_info.clone()]));
+
+    let store_description = ServerResponse::StoreDescription(StoreDescription {
+        info: store_info,
+        predicate_indices: vec![PredicateIndexStats {
+            key: MetadataKey::new(String::from("username")),
+            entries: 12,
+            distinct_values: 2,
+            most_frequent: vec![(MetadataValue::RawString(String::from("buster_matthews")), 9)],
+            last_updated: SystemTime::now(),
+        }],
+        non_linear_indices: vec![NonLinearAlgorithm::KDTree],
+        timestamp_key: Some(MetadataKey::new(String::from("published"))),
+        storage_tier: StorageTier::Disk,
+        key_element_type: KeyElementType::Float16,
+        normalization: VectorNormalization::L2,
+        index_seed: 42,
+        non_finite_vectors: NonFiniteVectors::Sanitize,
+        bulk_write: true,
+        eviction: Some(StoreEviction {
+            max_entries: NonZeroUsize::new(10000),
+            max_bytes: NonZeroUsize::new(1048576),
+            policy: EvictionPolicy::LFU,
+        }),
+        evicted: 12,
+    });
 
     let info_server = ServerResponse::InfoServer(ServerInfo {
         address: "127.0.0.1".to_owned(),
@@ -40,9 +85,36 @@ pub fn trace_db_server_response_enum() -> Registry {
             minor: 0,
             patch: 1,
         },
+        min_client_version: Version {
+            major: 1,
+            minor: 0,
+            patch: 0,
+        },
+        max_client_version: Version {
+            major: 1,
+            minor: 0,
+            patch: 1,
+        },
         r#type: ServerType::Database,
         limit: 121,
         remaining: 20,
+        request_limits,
+        in_flight_memory: 1024,
+        deadlines_exceeded: 1,
+        priorities: vec![PriorityStats {
+            priority: Priority::Bulk,
+            requests: 10,
+            queued: 1,
+            wait_ms: 50,
+            latency_ms: 200,
+            max_latency_ms: 40,
+        }],
+        connections: ConnectionStats {
+            clients: 90,
+            maximum_clients: 100,
+            maximum_clients_per_host: Some(10),
+            rejected: 3,
+        },
     });
 
     let set_variant = ServerResponse::Set(StoreUpsert {
@@ -66,6 +138,15 @@ pub fn trace_db_server_response_enum() -> Registry {
     );
 
     let get_variant = ServerResponse::Get(vec![(store_key.clone(), store_value.clone())]);
+    let entry_list_variant = ServerResponse::EntryList(EntryPage {
+        entries: vec![ListedEntry {
+            key_id: "af1349b9f5f9a1a6a0404dea36dcc949".to_string(),
+            key: Some(store_key.clone()),
+            value: store_value.clone(),
+        }],
+        next_cursor: Some("af1349b9f5f9a1a6a0404dea36dcc949".to_string()),
+        estimated_total: 20,
+    });
 
     // getsminN
 
@@ -75,6 +156,149 @@ pub fn trace_db_server_response_enum() -> Registry {
         Similarity(0.999_f32),
     )]);
 
+    let batch_getsimn_variant = ServerResponse::BatchGetSimN(vec![vec![(
+        store_key.clone(),
+        store_value.clone(),
+        Similarity(0.999_f32),
+    )]]);
+
+    let compaction_variant = ServerResponse::Compaction(StoreCompaction {
+        reclaimed_bytes: 4096,
+        size_in_bytes: 1024,
+    });
+
+    let store_check_variant = ServerResponse::StoreCheck(StoreCheck {
+        entries: 2500,
+        indices: vec![
+            IndexCheck {
+                index: StoreIndex::Predicate(MetadataKey::new(String::from("username"))),
+                missing: 0,
+                orphaned: 3,
+            },
+            IndexCheck {
+                index: StoreIndex::NonLinear(NonLinearAlgorithm::KDTree),
+                missing: 12,
+                orphaned: 0,
+            },
+        ],
+        repair_job: Some(2),
+    });
+
+    let scrubbed_entries_variant = ServerResponse::ScrubbedEntries(vec![ListedEntry {
+        key_id: "af1349b9f5f9a1a6a0404dea36dcc949".to_string(),
+        key: Some(store_key.clone()),
+        value: store_value.clone(),
+    }]);
+
+    let store_benchmark_variant = ServerResponse::StoreBenchmark(StoreBenchmark {
+        entries: 25000,
+        sample_queries: 100,
+        concurrency: 4,
+        paths: vec![
+            SearchPathBenchmark {
+                path: SearchPath::Linear,
+                queries: 1200,
+                queries_per_second: 240,
+                p50_latency: 15800,
+                p90_latency: 19400,
+                p99_latency: 27100,
+                max_latency: 31000,
+            },
+            SearchPathBenchmark {
+                path: SearchPath::NonLinear(NonLinearAlgorithm::KDTree),
+                queries: 51000,
+                queries_per_second: 10200,
+                p50_latency: 350,
+                p90_latency: 520,
+                p99_latency: 910,
+                max_latency: 2400,
+            },
+        ],
+    });
+
+    let config_reloaded_variant = ServerResponse::ConfigReloaded(ConfigReload {
+        changed: vec!["log-level".to_owned()],
+        requires_restart: vec!["port".to_owned()],
+    });
+
+    let memory_breakdown_variant = ServerResponse::MemoryBreakdown(MemoryBreakdown {
+        limit: 1073741824,
+        remaining: 1063741824,
+        stores: vec![StoreMemory {
+            name: StoreName("testing".to_owned()),
+            vectors: 6144,
+            entries: 4096,
+            predicate_indices: 2048,
+            non_linear_indices: 0,
+        }],
+        trash: 0,
+        connections: ConnectionMemory {
+            clients: 1,
+            buffers: 8192,
+        },
+        in_flight_memory: 0,
+    });
+
+    let job_status = JobStatus {
+        id: 1,
+        kind: JobKind::DelPred,
+        state: JobState::Running,
+        processed: 1000,
+        total: 2500,
+    };
+    let job_status_variant = ServerResponse::JobStatus(job_status.clone());
+    let job_list_variant = ServerResponse::JobList(vec![job_status]);
+    let trashed_store_list_variant = ServerResponse::TrashedStoreList(vec![TrashedStoreInfo {
+        name: StoreName("testing".to_owned()),
+        len: 2,
+        size_in_bytes: 256,
+        dropped_at: SystemTime::now(),
+        purge_at: SystemTime::now(),
+    }]);
+    let quota_list_variant = ServerResponse::QuotaList(vec![NamespaceUsage {
+        namespace: "acme".to_string(),
+        quota: NamespaceQuota {
+            max_stores: Some(10),
+            max_vectors: Some(100000),
+            max_memory_bytes: Some(1073741824),
+        },
+        stores: 2,
+        vectors: 5000,
+        memory_bytes: 2048,
+    }]);
+    let mirror_status_variant = ServerResponse::MirrorStatus(MirrorStatus {
+        target: "127.0.0.1:1369".to_string(),
+        state: MirrorState::Running,
+        logged: 120,
+        applied: 100,
+        failed: 1,
+        pending: 20,
+        lag_ms: 250,
+        last_error: Some("Store Main not found".to_string()),
+    });
+    let manifest_changes_variant = ServerResponse::ManifestChanges(vec![
+        ManifestChange::CreateStore {
+            store: StoreName("Main".to_string()),
+        },
+        ManifestChange::CreatePredicates {
+            store: StoreName("Other".to_string()),
+            predicates: vec![MetadataKey::new("author".into())],
+        },
+    ]);
+    let manifest_drift_variant = ServerResponse::ManifestDrift(ManifestDrift {
+        changes: vec![ManifestChange::DropNonLinearIndices {
+            store: StoreName("Main".to_string()),
+            non_linear_indices: vec![NonLinearAlgorithm::KDTree],
+        }],
+        settings: vec![SettingDrift {
+            store: StoreName("Main".to_string()),
+            setting: "dimension".to_string(),
+            manifest: Some("768".to_string()),
+            live: Some("512".to_string()),
+        }],
+        unmanaged_stores: vec![StoreName("Scratch".to_string())],
+    });
+
     let _ = tracer
         .trace_value(&mut samples, &client_list)
         .expect("Error tracing ClientList variant");
@@ -83,6 +307,10 @@ pub fn trace_db_server_response_enum() -> Registry {
         .trace_value(&mut samples, &store_list)
         .expect("Error tracing StoreList variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &store_description)
+        .expect("Error tracing StoreDescription variant");
+
     let _ = tracer
         .trace_value(&mut samples, &info_server)
         .expect("Error tracing InfoServer variant");
@@ -95,10 +323,102 @@ pub fn trace_db_server_response_enum() -> Registry {
         .trace_value(&mut samples, &get_variant)
         .expect("Error tracing Get variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &entry_list_variant)
+        .expect("Error tracing EntryList variant");
+
     let _ = tracer
         .trace_value(&mut samples, &getsimn_variant)
         .expect("Error tracing GetSimN variant");
 
+    let _ = tracer
+        .trace_value(&mut samples, &batch_getsimn_variant)
+        .expect("Error tracing BatchGetSimN variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &compaction_variant)
+        .expect("Error tracing Compaction variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &store_check_variant)
+        .expect("Error tracing StoreCheck variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &scrubbed_entries_variant)
+        .expect("Error tracing ScrubbedEntries variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &store_benchmark_variant)
+        .expect("Error tracing StoreBenchmark variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &memory_breakdown_variant)
+        .expect("Error tracing MemoryBreakdown variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &config_reloaded_variant)
+        .expect("Error tracing ConfigReloaded variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &job_status_variant)
+        .expect("Error tracing JobStatus variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &job_list_variant)
+        .expect("Error tracing JobList variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &trashed_store_list_variant)
+        .expect("Error tracing TrashedStoreList variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &quota_list_variant)
+        .expect("Error tracing QuotaList variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &mirror_status_variant)
+        .expect("Error tracing MirrorStatus variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &manifest_changes_variant)
+        .expect("Error tracing ManifestChanges variant");
+
+    let _ = tracer
+        .trace_value(&mut samples, &manifest_drift_variant)
+        .expect("Error tracing ManifestDrift variant");
+
+    tracer
+        .trace_simple_type::<JobKind>()
+        .expect("Error tracing JobKind");
+
+    tracer
+        .trace_simple_type::<JobState>()
+        .expect("Error tracing JobState");
+    tracer
+        .trace_simple_type::<Priority>()
+        .expect("Error tracing Priority");
+
+    tracer
+        .trace_simple_type::<MirrorState>()
+        .expect("Error tracing MirrorState");
+
+    tracer
+        .trace_simple_type::<ErrorCode>()
+        .expect("Error tracing ErrorCode");
+
+    tracer
+        .trace_simple_type::<StorageTier>()
+        .expect("Error tracing StorageTier");
+    tracer
+        .trace_simple_type::<KeyElementType>()
+        .expect("Error tracing KeyElementType");
+    tracer
+        .trace_simple_type::<VectorNormalization>()
+        .expect("Error tracing VectorNormalization");
+    tracer
+        .trace_simple_type::<NonFiniteVectors>()
+        .expect("Error tracing NonFiniteVectors");
+
     // trace server response
 
     let _ = tracer
@@ -107,7 +427,22 @@ pub fn trace_db_server_response_enum() -> Registry {
         .unwrap();
 
     let _ = tracer
-        .trace_type::<Result<ServerResponse, String>>(&samples)
+        .trace_type::<ManifestChange>(&samples)
+        .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
+        .unwrap();
+
+    let _ = tracer
+        .trace_type::<StoreIndex>(&samples)
+        .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
+        .unwrap();
+
+    let _ = tracer
+        .trace_type::<SearchPath>(&samples)
+        .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
+        .unwrap();
+
+    let _ = tracer
+        .trace_type::<Result<ServerResponse, ErrorResponse>>(&samples)
         .inspect_err(|err| println!("Failed to parse type {}", err.explanation()))
         .unwrap();
 
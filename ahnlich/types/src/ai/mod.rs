@@ -1,15 +1,21 @@
 mod preprocess;
 mod query;
 mod server;
-pub use preprocess::PreprocessAction;
+pub use preprocess::{
+    ImageFormat, ImagePreprocessing, ImageResize, PreprocessAction, TextTruncation,
+};
 pub use query::{AIQuery, AIServerQuery};
 use serde::{Deserialize, Serialize};
-pub use server::{AIServerResponse, AIServerResult, AIStoreInfo};
+pub use server::{
+    AIMemoryBreakdown, AIModelInfo, AIServerResponse, AIServerResult, AIStoreDescription,
+    AIStoreInfo, AnswerSpan, CachedResponse, ModelMemory, QuestionAnswer, Usage, UsageStats,
+};
+use std::borrow::Cow;
 use std::fmt;
 
-use crate::keyval::StoreInput;
+use crate::keyval::{StoreInput, StoreValue, MULTIMODAL_TEXT_LEN_SIZE};
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum AIModel {
     AllMiniLML6V2,
     AllMiniLML12V2,
@@ -18,12 +24,37 @@ pub enum AIModel {
     Resnet50,
     ClipVitB32Image,
     ClipVitB32Text,
+    // A model declared in the model registry of the AI proxy, referred to by its name
+    Custom(String),
+}
+
+/// Hardware backends models can be run on
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AIExecutionProvider {
+    TensorRT,
+    CUDA,
+    DirectML,
+    CoreML,
+    CPU,
+}
+
+impl fmt::Display for AIExecutionProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TensorRT => write!(f, "TensorRT"),
+            Self::CUDA => write!(f, "CUDA"),
+            Self::DirectML => write!(f, "DirectML"),
+            Self::CoreML => write!(f, "CoreML"),
+            Self::CPU => write!(f, "CPU"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum AIStoreInputType {
     RawString,
     Image,
+    Multimodal,
 }
 
 impl From<&StoreInput> for AIStoreInputType {
@@ -31,6 +62,7 @@ impl From<&StoreInput> for AIStoreInputType {
         match value {
             StoreInput::RawString(_) => AIStoreInputType::RawString,
             StoreInput::Image(_) => AIStoreInputType::Image,
+            StoreInput::Multimodal { .. } => AIStoreInputType::Multimodal,
         }
     }
 }
@@ -40,6 +72,85 @@ impl fmt::Display for AIStoreInputType {
         match self {
             Self::RawString => write!(f, "RawString"),
             Self::Image => write!(f, "Image"),
+            Self::Multimodal => write!(f, "Multimodal"),
+        }
+    }
+}
+
+/// How a store combines the embeddings of the text and image of a multimodal input, each
+/// scaled to unit length, into the single key of the entry
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum MultimodalFusion {
+    /// The text embedding followed by the image embedding, doubling the dimension of the store.
+    /// Such stores are only searched with multimodal inputs
+    Concat,
+    /// Mean of the text and image embeddings
+    Average,
+    /// Sum of the text embedding scaled by `text_weight`, between 0 and 1, and the image
+    /// embedding scaled by the rest
+    Weighted { text_weight: f32 },
+}
+
+impl PartialEq for MultimodalFusion {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Concat, Self::Concat) | (Self::Average, Self::Average) => true,
+            (
+                Self::Weighted { text_weight: first },
+                Self::Weighted {
+                    text_weight: second,
+                },
+            ) => (first - second).abs() < f32::EPSILON,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MultimodalFusion {}
+
+impl StoreInput {
+    /// Bytes of the input as sent in a chunked transfer. Those of a multimodal input are the
+    /// length of its text as 8 little endian bytes followed by its text and its image
+    pub fn as_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            Self::Image(bytes) => Cow::Borrowed(bytes),
+            Self::RawString(text) => Cow::Borrowed(text.as_bytes()),
+            Self::Multimodal { text, image } => {
+                let mut bytes = Vec::with_capacity(self.len());
+                bytes.extend_from_slice(&(text.len() as u64).to_le_bytes());
+                bytes.extend_from_slice(text.as_bytes());
+                bytes.extend_from_slice(image);
+                Cow::Owned(bytes)
+            }
         }
     }
+
+    /// Input of a type from the bytes it was sent as in a chunked transfer, `None` when they do
+    /// not hold such an input
+    pub fn from_bytes(input_type: &AIStoreInputType, mut bytes: Vec<u8>) -> Option<Self> {
+        match input_type {
+            AIStoreInputType::Image => Some(Self::Image(bytes)),
+            AIStoreInputType::RawString => String::from_utf8(bytes).ok().map(Self::RawString),
+            AIStoreInputType::Multimodal => {
+                let text_len = bytes.get(..MULTIMODAL_TEXT_LEN_SIZE)?;
+                let text_len = u64::from_le_bytes(text_len.try_into().ok()?);
+                let text_end = MULTIMODAL_TEXT_LEN_SIZE.checked_add(text_len.try_into().ok()?)?;
+                if text_end > bytes.len() {
+                    return None;
+                }
+                let image = bytes.split_off(text_end);
+                let text = String::from_utf8(bytes.split_off(MULTIMODAL_TEXT_LEN_SIZE)).ok()?;
+                Some(Self::Multimodal { text, image })
+            }
+        }
+    }
+}
+
+/// Entry of a chunked transfer, its input is sent separately in chunks
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChunkedEntry {
+    pub input_type: AIStoreInputType,
+    /// size of the input in bytes
+    pub size: usize,
+    pub value: StoreValue,
 }
@@ -15,3 +15,66 @@ impl fmt::Display for PreprocessAction {
         }
     }
 }
+
+/// How images whose dimensions differ from those expected by a model are resized
+#[derive(
+    Copy, Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum ImageResize {
+    // Images are left as is and must match the dimensions of the model
+    #[default]
+    None,
+    // Images are stretched to the dimensions of the model
+    Stretch,
+    // Images are scaled to fit the dimensions of the model keeping their aspect ratio, with the
+    // rest padded in black
+    Letterbox,
+}
+
+/// How texts longer than the max input tokens of a model are handled when they are embedded with
+/// ModelPreprocessing. With NoPreprocessing such texts are always rejected
+#[derive(
+    Copy, Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum TextTruncation {
+    // Texts are rejected
+    Error,
+    // The start of texts is dropped
+    TruncateHead,
+    // The end of texts is dropped
+    #[default]
+    TruncateTail,
+    // Texts are split into chunks of the max input tokens which are embedded and mean pooled
+    SplitAndAverage,
+}
+
+impl fmt::Display for TextTruncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::TruncateHead => write!(f, "TruncateHead"),
+            Self::TruncateTail => write!(f, "TruncateTail"),
+            Self::SplitAndAverage => write!(f, "SplitAndAverage"),
+        }
+    }
+}
+
+#[derive(Copy, Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+/// ImagePreprocessing is applied to the images of a store before any model preprocessing
+#[derive(
+    Copy, Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(default)]
+pub struct ImagePreprocessing {
+    // rotates and flips images to the orientation given in their EXIF metadata
+    pub exif_orientation: bool,
+    pub resize: ImageResize,
+    // format the original images of a store are converted to before being stored
+    pub convert_format: Option<ImageFormat>,
+}
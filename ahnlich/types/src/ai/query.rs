@@ -1,15 +1,22 @@
-use super::{AIModel, PreprocessAction};
-use crate::keyval::{StoreInput, StoreName, StoreValue};
+use super::{
+    AIModel, ChunkedEntry, ImagePreprocessing, MultimodalFusion, PreprocessAction, TextTruncation,
+};
+use crate::keyval::{StoreEviction, StoreInput, StoreName, StoreValue};
 use crate::metadata::MetadataKey;
 use crate::predicate::PredicateCondition;
-use crate::similarity::{Algorithm, NonLinearAlgorithm};
+use crate::similarity::{
+    Algorithm, FilterStrategy, FusionStrategy, NonLinearAlgorithm, RecencyBoost, Similarity,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::num::NonZeroUsize;
+use std::time::Duration;
+use strum::IntoStaticStr;
 
 use crate::bincode::{BinCodeSerAndDeser, BinCodeSerAndDeserQuery};
+use crate::{ErrorPolicy, Priority};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, IntoStaticStr)]
 pub enum AIQuery {
     CreateStore {
         store: StoreName,
@@ -19,10 +26,23 @@ pub enum AIQuery {
         non_linear_indices: HashSet<NonLinearAlgorithm>,
         error_if_exists: bool,
         store_original: bool,
+        image_preprocessing: ImagePreprocessing,
+        // applied to texts longer than the max input tokens of the index and query models
+        text_truncation: TextTruncation,
+        // metadata key holding the Unix timestamp in seconds of each entry, used to boost newer
+        // entries in GetSimN
+        timestamp_key: Option<MetadataKey>,
+        // lets the store take multimodal inputs, embedding their text and image with whichever
+        // of the index and query models takes each and fusing them into one key. Both models
+        // must then embed into the same space, such as ClipVitB32Text and ClipVitB32Image
+        multimodal_fusion: Option<MultimodalFusion>,
+        // bounds the store as a cache that evicts entries past them, such as a semantic cache
+        eviction: Option<StoreEviction>,
     },
     GetPred {
         store: StoreName,
         condition: PredicateCondition,
+        include_system_metadata: bool,
     },
     GetSimN {
         store: StoreName,
@@ -31,6 +51,16 @@ pub enum AIQuery {
         closest_n: NonZeroUsize,
         algorithm: Algorithm,
         preprocess_action: PreprocessAction,
+        include_system_metadata: bool,
+        min_score: Option<Similarity>,
+        max_distance: Option<Similarity>,
+        normalize_scores: bool,
+        group_by: Option<MetadataKey>,
+        group_size: NonZeroUsize,
+        additional_search_inputs: Vec<StoreInput>,
+        fusion: FusionStrategy,
+        recency_boost: Option<RecencyBoost>,
+        filter_strategy: FilterStrategy,
     },
     CreatePredIndex {
         store: StoreName,
@@ -66,18 +96,154 @@ pub enum AIQuery {
     GetKey {
         store: StoreName,
         keys: Vec<StoreInput>,
+        include_system_metadata: bool,
     },
     InfoServer,
     ListClients,
     ListStores,
     PurgeStores,
     Ping,
+    // Zero-shot classification of `input` against candidate `labels`. The input is embedded with
+    // `input_model` and the labels with `label_model`, which must share an embedding size
+    Classify {
+        input: StoreInput,
+        input_model: AIModel,
+        labels: Vec<String>,
+        label_model: AIModel,
+        preprocess_action: PreprocessAction,
+    },
+    // Retrieves the `closest_n` entries of `store` to `question` and answers it from their text
+    // inputs with the answer model of the proxy, which must be started with one
+    AnswerQuestion {
+        store: StoreName,
+        question: String,
+        condition: Option<PredicateCondition>,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        include_system_metadata: bool,
+    },
+    // Returns the response cached in `store` for the prompt most similar to `prompt` by cosine
+    // similarity, when it is at least `threshold` similar and has not expired. Expired responses
+    // found along the way are deleted
+    CacheLookup {
+        store: StoreName,
+        prompt: String,
+        threshold: Similarity,
+    },
+    // Caches `response` for `prompt` in `store`, replacing that of the same prompt in stores
+    // created with `store_original`, to expire `ttl_secs` seconds from now when given
+    CacheStore {
+        store: StoreName,
+        prompt: String,
+        response: String,
+        ttl_secs: Option<u64>,
+    },
+    GetJob {
+        job_id: u64,
+    },
+    CancelJob {
+        job_id: u64,
+    },
+    ListJobs,
+    // Re-embeds the original inputs of `source` with `new_index_model` into a new `destination`
+    // store in the background, returning a job id to poll with GetJob
+    MigrateStore {
+        source: StoreName,
+        destination: StoreName,
+        new_index_model: AIModel,
+        batch_size: NonZeroUsize,
+    },
+    // Starts a set of inputs too large for a single message. The input of each entry is then
+    // sent in order with SetChunk and the entries are stored on FinishChunkedSet
+    StartChunkedSet {
+        store: StoreName,
+        entries: Vec<ChunkedEntry>,
+        preprocess_action: PreprocessAction,
+    },
+    SetChunk {
+        transfer_id: u64,
+        entry: usize,
+        data: Vec<u8>,
+    },
+    FinishChunkedSet {
+        transfer_id: u64,
+    },
+    // Starts a get of entries whose inputs are too large for a single message, the input of
+    // each entry is then read with GetChunk
+    StartChunkedGet {
+        store: StoreName,
+        condition: PredicateCondition,
+        include_system_metadata: bool,
+    },
+    GetChunk {
+        transfer_id: u64,
+        entry: usize,
+        offset: usize,
+        length: usize,
+    },
+    // Discards a chunked transfer, an unfinished set is dropped without storing its entries
+    EndChunkedTransfer {
+        transfer_id: u64,
+    },
+    // Re-applies the settings of the configuration file of the proxy that can change while it
+    // runs, reporting those that changed and those that take a restart
+    ReloadConfig,
+    // Attributes the memory of the server to its models and the connections of clients
+    GetMemoryBreakdown,
+    // Models of a store with the inputs it accepts to index and search and the dimension of its
+    // keys
+    DescribeStore {
+        store: StoreName,
+    },
+    ListSupportedModels,
+    // Inputs, tokens, images and inference time used per store, client and model since the
+    // proxy started or the last reset, resetting the counters after reading them if `reset`
+    GetUsageStats {
+        reset: bool,
+    },
+    // Warms up the stores on the database, or all stores when none are given, and loads the
+    // models, or all supported models when none are given, ahead of the first queries
+    Warmup {
+        stores: HashSet<StoreName>,
+        models: HashSet<AIModel>,
+    },
+}
+
+impl AIQuery {
+    /// The store a query acts on, None for queries on the server or on several stores
+    pub fn store(&self) -> Option<&StoreName> {
+        match self {
+            AIQuery::CreateStore { store, .. }
+            | AIQuery::GetPred { store, .. }
+            | AIQuery::GetSimN { store, .. }
+            | AIQuery::AnswerQuestion { store, .. }
+            | AIQuery::CacheLookup { store, .. }
+            | AIQuery::CacheStore { store, .. }
+            | AIQuery::CreatePredIndex { store, .. }
+            | AIQuery::CreateNonLinearAlgorithmIndex { store, .. }
+            | AIQuery::DropPredIndex { store, .. }
+            | AIQuery::DropNonLinearAlgorithmIndex { store, .. }
+            | AIQuery::Set { store, .. }
+            | AIQuery::DelKey { store, .. }
+            | AIQuery::DropStore { store, .. }
+            | AIQuery::GetKey { store, .. }
+            | AIQuery::MigrateStore { source: store, .. }
+            | AIQuery::StartChunkedSet { store, .. }
+            | AIQuery::StartChunkedGet { store, .. }
+            | AIQuery::DescribeStore { store } => Some(store),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct AIServerQuery {
     queries: Vec<AIQuery>,
     trace_id: Option<String>,
+    error_policy: ErrorPolicy,
+    // milliseconds the server works on the queries before failing those left
+    timeout_ms: Option<u64>,
+    priority: Priority,
 }
 
 impl AIServerQuery {
@@ -85,12 +251,18 @@ impl AIServerQuery {
         Self {
             queries: Vec::with_capacity(len),
             trace_id: None,
+            error_policy: ErrorPolicy::default(),
+            timeout_ms: None,
+            priority: Priority::default(),
         }
     }
     pub fn with_capacity_and_tracing_id(len: usize, trace_id: Option<String>) -> Self {
         Self {
             queries: Vec::with_capacity(len),
             trace_id,
+            error_policy: ErrorPolicy::default(),
+            timeout_ms: None,
+            priority: Priority::default(),
         }
     }
 
@@ -98,10 +270,42 @@ impl AIServerQuery {
         self.queries.push(entry)
     }
 
+    /// the queries to send, for changing them in place before they are sent
+    pub fn queries_mut(&mut self) -> &mut [AIQuery] {
+        &mut self.queries
+    }
+
+    pub fn set_trace_id(&mut self, trace_id: Option<String>) {
+        self.trace_id = trace_id
+    }
+
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.error_policy = error_policy
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout_ms = Some(timeout.as_millis() as u64)
+    }
+
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority
+    }
+
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
     pub fn from_queries(queries: &[AIQuery]) -> Self {
         Self {
             queries: queries.to_vec(),
             trace_id: None,
+            error_policy: ErrorPolicy::default(),
+            timeout_ms: None,
+            priority: Priority::default(),
         }
     }
 }
@@ -117,4 +321,13 @@ impl BinCodeSerAndDeserQuery for AIServerQuery {
     fn get_traceparent(&self) -> Option<String> {
         self.trace_id.clone()
     }
+    fn get_error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+    fn get_timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+    fn get_priority(&self) -> Priority {
+        self.priority
+    }
 }
@@ -1,14 +1,17 @@
-use super::AIModel;
+use super::{AIExecutionProvider, AIModel, AIStoreInputType, ChunkedEntry, MultimodalFusion};
 use crate::bincode::{BinCodeSerAndDeser, BinCodeSerAndDeserResponse};
-use crate::client::ConnectedClient;
+use crate::client::{ConnectedClient, ConnectionMemory};
 use crate::db::{ServerInfo, StoreUpsert};
+use crate::error::ErrorResponse;
+use crate::jobs::JobStatus;
 use crate::keyval::StoreInput;
 use crate::keyval::StoreName;
 use crate::keyval::StoreValue;
 use crate::similarity::Similarity;
+use crate::{ConfigReload, RequestLimits};
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -30,6 +33,55 @@ pub enum AIServerResponse {
     Del(usize),
     // number of created indexes
     CreateIndex(usize),
+    StoreDescription(AIStoreDescription),
+    SupportedModelList(Vec<AIModelInfo>),
+    UsageStats(UsageStats),
+    // Labels with their softmax normalized scores, which sum to 1, ordered from the best match
+    Classify(Vec<(String, Similarity)>),
+    // The answer is None when none of the sources retrieved for the question have a text input
+    Answer {
+        answer: Option<QuestionAnswer>,
+        sources: Vec<(Option<StoreInput>, StoreValue, Similarity)>,
+    },
+    // None when no unexpired prompt of the store is similar enough
+    CacheHit(Option<CachedResponse>),
+    JobStatus(JobStatus),
+    // Jobs that are running or finished recently, ordered by id
+    JobList(Vec<JobStatus>),
+    // id of a job started in the background
+    JobStarted(u64),
+    // id of a chunked set to send the chunks of its entries to
+    ChunkedSetStarted(u64),
+    // id of a chunked get along with the entries whose inputs can be read from it
+    ChunkedGetStarted {
+        transfer_id: u64,
+        entries: Vec<ChunkedEntry>,
+    },
+    Chunk(Vec<u8>),
+    MemoryBreakdown(AIMemoryBreakdown),
+    ConfigReloaded(ConfigReload),
+}
+
+/// AIMemoryBreakdown attributes the memory of the server to what holds it. Model sessions are
+/// allocated by the ONNX runtime outside of the allocator of the server, so they do not count
+/// towards its limit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AIMemoryBreakdown {
+    pub limit: usize,
+    pub remaining: usize,
+    // supported models in the order they are configured
+    pub models: Vec<ModelMemory>,
+    pub connections: ConnectionMemory,
+}
+
+/// ModelMemory shows the bytes of the weights held across the loaded replicas of a model, which
+/// is about what its sessions take, and none for a model that is not loaded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModelMemory {
+    pub model: AIModel,
+    pub loaded: bool,
+    pub replicas: usize,
+    pub weights_bytes: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -38,8 +90,113 @@ pub struct AIStoreInfo {
     pub query_model: AIModel,
     pub index_model: AIModel,
     pub embedding_size: usize,
+    pub request_limits: RequestLimits,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AIStoreDescription {
+    pub name: StoreName,
+    pub query_model: AIModel,
+    pub index_model: AIModel,
+    // dimension of the keys of the store
+    pub embedding_size: usize,
+    // input types accepted by Set, in order
+    pub index_inputs: Vec<AIStoreInputType>,
+    // input types accepted by GetSimN and the other searches, in order
+    pub query_inputs: Vec<AIStoreInputType>,
+    pub multimodal_fusion: Option<MultimodalFusion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AIModelInfo {
+    pub model: AIModel,
+    pub input_type: AIStoreInputType,
+    pub embedding_size: usize,
+    // execution provider the model session was created with, None if the model is not loaded
+    pub execution_provider: Option<AIExecutionProvider>,
+}
+
+/// Model usage counted on behalf of a store, client or model
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Usage {
+    // number of inputs embedded
+    pub inputs: u64,
+    // tokens of text inputs, excluding padding
+    pub tokens: u64,
+    pub images: u64,
+    pub inference_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UsageStats {
+    pub stores: HashMap<StoreName, Usage>,
+    // keyed by the host of the client
+    pub clients: HashMap<String, Usage>,
+    #[serde(with = "models_usage")]
+    pub models: HashMap<AIModel, Usage>,
 }
-pub type AIServerResultInner = Vec<Result<AIServerResponse, String>>;
+
+/// Custom models cannot be the keys of maps in human readable formats such as JSON which only
+/// take string keys, so there the usage of models is a list of model and usage pairs instead.
+/// Binary formats keep the map
+mod models_usage {
+    use super::{AIModel, Usage};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashMap;
+
+    pub(super) fn serialize<S: Serializer>(
+        models: &HashMap<AIModel, Usage>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_seq(models)
+        } else {
+            serializer.collect_map(models)
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<AIModel, Usage>, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(Vec::<(AIModel, Usage)>::deserialize(deserializer)?
+                .into_iter()
+                .collect())
+        } else {
+            HashMap::deserialize(deserializer)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuestionAnswer {
+    pub text: String,
+    // where an extracted answer was found, None for generated answers
+    pub span: Option<AnswerSpan>,
+    // confidence between 0 and 1 of an extracted answer
+    pub score: Option<Similarity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CachedResponse {
+    // the cached prompt matched, None if the store was created with `store_original` as false
+    pub prompt: Option<String>,
+    pub response: String,
+    pub similarity: Similarity,
+    // Unix timestamp in seconds the response expires at, None if it does not
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AnswerSpan {
+    // index of the source in the answer sources
+    pub source: usize,
+    // byte range of the answer in the text of the source
+    pub start: usize,
+    pub end: usize,
+}
+
+pub type AIServerResultInner = Vec<Result<AIServerResponse, ErrorResponse>>;
 // ServerResult: Given that an array of queries are sent in, we expect that an array of responses
 // be returned each being a potential error
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -56,11 +213,11 @@ impl AIServerResult {
         }
     }
 
-    pub fn pop(mut self) -> Option<Result<AIServerResponse, String>> {
+    pub fn pop(mut self) -> Option<Result<AIServerResponse, ErrorResponse>> {
         self.results.pop()
     }
 
-    pub fn push(&mut self, entry: Result<AIServerResponse, String>) {
+    pub fn push(&mut self, entry: Result<AIServerResponse, ErrorResponse>) {
         self.results.push(entry)
     }
     pub fn len(&self) -> usize {
@@ -77,9 +234,19 @@ impl AIServerResult {
 }
 
 impl BinCodeSerAndDeserResponse for AIServerResult {
-    fn from_error(err: String) -> Self {
+    fn from_error(err: ErrorResponse) -> Self {
         Self {
             results: vec![Err(err)],
         }
     }
+
+    fn with_request_id(self, request_id: &str) -> Self {
+        Self {
+            results: self
+                .results
+                .into_iter()
+                .map(|result| result.map_err(|err| err.with_request_id(request_id)))
+                .collect(),
+        }
+    }
 }
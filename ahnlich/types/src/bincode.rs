@@ -1,9 +1,13 @@
+use crate::error::ErrorResponse;
+use crate::version::Version;
 use crate::version::VERSION;
+use crate::{ErrorPolicy, Priority};
 use bincode::config::DefaultOptions;
 use bincode::config::Options;
 use fallible_collections::vec::FallibleVec;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::time::Duration;
 
 pub const MAGIC_BYTES: &[u8] = b"AHNLICH;";
 /// corresponds to the size of the Version struct
@@ -27,10 +31,16 @@ where
     Self: Serialize + DeserializeOwned + Send,
 {
     fn serialize(&self) -> Result<Vec<u8>, BincodeSerError> {
+        self.serialize_with_version(&VERSION)
+    }
+
+    /// serializes with `version` in the header in place of the current version, used by clients
+    /// talking to an older server
+    fn serialize_with_version(&self, version: &Version) -> Result<Vec<u8>, BincodeSerError> {
         let config = DefaultOptions::new()
             .with_fixint_encoding()
             .with_little_endian();
-        let serialized_version_data = config.serialize(&*VERSION)?;
+        let serialized_version_data = config.serialize(version)?;
         let serialized_data = config.serialize(self)?;
         let data_length = serialized_data.len() as u64;
         // serialization appends the length buffer to be read first
@@ -53,6 +63,14 @@ where
     }
 }
 
+/// size in bytes `value` takes up in a message
+pub fn serialized_size<T: Serialize + ?Sized>(value: &T) -> Result<u64, bincode::Error> {
+    DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .serialized_size(value)
+}
+
 pub trait BinCodeSerAndDeserQuery: BinCodeSerAndDeser
 where
     Self::Inner: Serialize + DeserializeOwned,
@@ -60,10 +78,17 @@ where
     type Inner;
     fn into_inner(self) -> Self::Inner;
     fn get_traceparent(&self) -> Option<String>;
+    fn get_error_policy(&self) -> ErrorPolicy;
+    /// How long the server works on the queries before failing those it did not get to
+    fn get_timeout(&self) -> Option<Duration>;
+    /// Class the server schedules the queries in
+    fn get_priority(&self) -> Priority;
 }
 
 pub trait BinCodeSerAndDeserResponse: BinCodeSerAndDeser {
-    fn from_error(err: String) -> Self;
+    fn from_error(err: ErrorResponse) -> Self;
+    /// Tags the errors of the response with the correlation id of the request
+    fn with_request_id(self, request_id: &str) -> Self;
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -73,3 +98,124 @@ pub enum BincodeSerError {
     #[error("allocation error {0:?}")]
     Allocation(fallible_collections::TryReserveError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BinCodeSerAndDeser, RESPONSE_HEADER_LEN};
+    use crate::ai::AIServerQuery;
+    use crate::db::{DBQuery, ServerDBQuery};
+    use crate::keyval::{StoreKey, StoreName};
+    use crate::metadata::{MetadataKey, MetadataValue};
+    use crate::predicate::{Predicate, PredicateCondition};
+    use ndarray::Array1;
+    use proptest::prelude::*;
+
+    fn store_name() -> impl Strategy<Value = StoreName> {
+        "[A-Za-z/]{1,12}".prop_map(StoreName)
+    }
+
+    fn store_key() -> impl Strategy<Value = StoreKey> {
+        // NaN is left out as it is never equal to itself
+        prop::collection::vec(-1e6f32..1e6, 1..8)
+            .prop_map(|values| StoreKey(Array1::from_vec(values)))
+    }
+
+    fn metadata_value() -> impl Strategy<Value = MetadataValue> {
+        prop_oneof![
+            ".{0,16}".prop_map(MetadataValue::RawString),
+            prop::collection::vec(any::<u8>(), 0..16).prop_map(MetadataValue::Image),
+        ]
+    }
+
+    fn predicate() -> impl Strategy<Value = Predicate> {
+        let key = "[a-z_]{1,8}".prop_map(MetadataKey::new);
+        let values = || prop::collection::hash_set(metadata_value(), 0..4);
+        prop_oneof![
+            (key.clone(), metadata_value())
+                .prop_map(|(key, value)| Predicate::Equals { key, value }),
+            (key.clone(), metadata_value())
+                .prop_map(|(key, value)| Predicate::NotEquals { key, value }),
+            (key.clone(), values()).prop_map(|(key, value)| Predicate::In { key, value }),
+            (key, values()).prop_map(|(key, value)| Predicate::NotIn { key, value }),
+        ]
+    }
+
+    fn condition() -> impl Strategy<Value = PredicateCondition> {
+        predicate()
+            .prop_map(PredicateCondition::Value)
+            .prop_recursive(4, 16, 2, |inner| {
+                prop_oneof![
+                    (inner.clone(), inner.clone()).prop_map(|(first, second)| first.and(second)),
+                    (inner.clone(), inner).prop_map(|(first, second)| first.or(second)),
+                ]
+            })
+    }
+
+    fn db_query() -> impl Strategy<Value = DBQuery> {
+        prop_oneof![
+            Just(DBQuery::Ping),
+            Just(DBQuery::ListStores),
+            (store_name(), prop::collection::vec(store_key(), 0..4))
+                .prop_map(|(store, keys)| DBQuery::GetKey { store, keys }),
+            (store_name(), condition())
+                .prop_map(|(store, condition)| DBQuery::GetPred { store, condition }),
+            (store_name(), condition(), any::<bool>()).prop_map(|(store, condition, exact)| {
+                DBQuery::CountPred {
+                    store,
+                    condition,
+                    exact,
+                }
+            }),
+            (
+                store_name(),
+                prop::collection::vec(
+                    (
+                        store_key(),
+                        prop::collection::hash_map(
+                            "[a-z_]{1,8}".prop_map(MetadataKey::new),
+                            metadata_value(),
+                            0..4
+                        )
+                    ),
+                    0..4
+                )
+            )
+                .prop_map(|(store, inputs)| DBQuery::Set { store, inputs }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_db_queries_round_trip(queries in prop::collection::vec(db_query(), 0..4)) {
+            let query = ServerDBQuery::from_queries(&queries);
+            let serialized = query.serialize().unwrap();
+            prop_assert_eq!(
+                ServerDBQuery::deserialize(&serialized[RESPONSE_HEADER_LEN..]).unwrap(),
+                query
+            );
+        }
+
+        #[test]
+        fn test_arbitrary_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..1024)) {
+            let _ = ServerDBQuery::deserialize(&bytes);
+            let _ = AIServerQuery::deserialize(&bytes);
+        }
+
+        // a query cut short or with a byte changed on the way is rejected or read as another
+        // query, without the server crashing or allocating what it claims to hold
+        #[test]
+        fn test_corrupted_db_queries_do_not_panic(
+            queries in prop::collection::vec(db_query(), 1..4),
+            position in any::<prop::sample::Index>(),
+            byte in any::<u8>(),
+            length in any::<prop::sample::Index>(),
+        ) {
+            let serialized = ServerDBQuery::from_queries(&queries).serialize().unwrap();
+            let mut body = serialized[RESPONSE_HEADER_LEN..].to_vec();
+            let position = position.index(body.len());
+            body[position] = byte;
+            let _ = ServerDBQuery::deserialize(&body);
+            let _ = ServerDBQuery::deserialize(&body[..length.index(body.len())]);
+        }
+    }
+}
@@ -2,8 +2,29 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::net::SocketAddr;
 use std::time::SystemTime;
 
+/// ConnectionMemory shows the memory held by the connections of clients to a server, which each
+/// hold a read buffer whether or not they are sending a request
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionMemory {
+    pub clients: usize,
+    pub buffers: usize,
+}
+
+/// ConnectionStats shows how close a server is to the limits on the connections of clients and
+/// how many connections it turned away for going past them
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionStats {
+    pub clients: usize,
+    pub maximum_clients: usize,
+    // most connections of clients from a single host, unbounded when None
+    pub maximum_clients_per_host: Option<usize>,
+    // connections rejected since the server started
+    pub rejected: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialOrd, Ord)]
 pub struct ConnectedClient {
     pub address: String,
@@ -12,6 +33,20 @@ pub struct ConnectedClient {
     pub time_connected: SystemTime,
 }
 
+impl ConnectedClient {
+    /// The host a client connects from, shared by all connections of the same client. Clients
+    /// of Unix domain sockets and in-memory channels share `unix` and `memory` as their host
+    pub fn host(&self) -> String {
+        self.address
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|_| match self.address.split_once('#') {
+                Some((transport, _)) => transport.to_string(),
+                None => self.address.clone(),
+            })
+    }
+}
+
 // NOTE: ConnectedClient should be unique purely by address assuming we are not doing any TCP magic
 // to allow port reuse
 impl Hash for ConnectedClient {
@@ -2,4 +2,10 @@ mod query;
 mod server;
 
 pub use query::{Query as DBQuery, ServerQuery as ServerDBQuery};
-pub use server::{ServerInfo, ServerResponse, ServerResult, StoreInfo, StoreUpsert};
+pub use server::{
+    EntryPage, IndexCheck, ListedEntry, ManifestChange, ManifestDrift, MemoryBreakdown,
+    MirrorAction, MirrorState, MirrorStatus, NamespaceQuota, NamespaceUsage, PredicateIndexStats,
+    SearchPath, SearchPathBenchmark, ServerInfo, ServerResponse, ServerResult, SettingDrift,
+    StoreBenchmark, StoreCheck, StoreCompaction, StoreDescription, StoreIndex, StoreInfo,
+    StoreManifest, StoreMemory, StoreUpsert, TrashedStoreInfo,
+};
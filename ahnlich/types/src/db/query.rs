@@ -2,14 +2,27 @@ use fallible_collections::FallibleVec;
 use fallible_collections::TryReserveError;
 use std::collections::HashSet;
 use std::num::NonZeroUsize;
+use std::time::Duration;
 
+use super::server::{MirrorAction, NamespaceQuota, StoreManifest};
 use crate::bincode::{BinCodeSerAndDeser, BinCodeSerAndDeserQuery};
-use crate::keyval::{StoreKey, StoreName, StoreValue};
+use crate::keyval::{
+    KeyElementType, NonFiniteVectors, StorageTier, StoreEviction, StoreKey, StoreName, StoreValue,
+    VectorNormalization,
+};
 use crate::metadata::MetadataKey;
 use crate::predicate::PredicateCondition;
 use crate::similarity::Algorithm;
+use crate::similarity::FilterStrategy;
+use crate::similarity::FusionStrategy;
 use crate::similarity::NonLinearAlgorithm;
+use crate::similarity::RecencyBoost;
+use crate::similarity::SearchTerm;
+use crate::similarity::Similarity;
+use crate::similarity::TermVector;
+use crate::{ErrorPolicy, Priority};
 use serde::{Deserialize, Serialize};
+use strum::IntoStaticStr;
 
 /// All possible queries for the server to respond to
 ///
@@ -18,7 +31,7 @@ use serde::{Deserialize, Serialize};
 /// - Length encoding must use fixed int and not var int
 /// - Endianess must be Little Endian.
 /// - First 8 bytes must contain length of the entire vec of queries
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, IntoStaticStr)]
 pub enum Query {
     CreateStore {
         store: StoreName,
@@ -26,6 +39,21 @@ pub enum Query {
         create_predicates: HashSet<MetadataKey>,
         non_linear_indices: HashSet<NonLinearAlgorithm>,
         error_if_exists: bool,
+        /// Metadata key holding the Unix timestamp in seconds of each entry, used to boost
+        /// newer entries in GETSIMN
+        timestamp_key: Option<MetadataKey>,
+        storage_tier: StorageTier,
+        /// Ignore `dimension` and take that of the first keys set into the store instead
+        infer_dimension: bool,
+        /// Precision the store keys are held in
+        key_element_type: KeyElementType,
+        normalization: VectorNormalization,
+        /// Seeds the sampling of the store so that replicas holding the same entries sample them
+        /// the same. Derived from the store name when not given
+        index_seed: Option<u64>,
+        non_finite_vectors: NonFiniteVectors,
+        /// Bounds the store as a cache that evicts entries past them
+        eviction: Option<StoreEviction>,
     },
     GetKey {
         store: StoreName,
@@ -41,6 +69,33 @@ pub enum Query {
         closest_n: NonZeroUsize,
         algorithm: Algorithm,
         condition: Option<PredicateCondition>,
+        /// Drop results scoring lower than this. Only valid for similarity algorithms unless
+        /// `normalize_scores` is set, in which case it applies to the normalized score
+        min_score: Option<Similarity>,
+        /// Drop results further away than this. Only valid for distance algorithms
+        max_distance: Option<Similarity>,
+        /// Map scores to the 0-1 range where 1 is the most similar regardless of algorithm
+        normalize_scores: bool,
+        /// Diversify results by returning at most `group_size` matches per value of this key
+        group_by: Option<MetadataKey>,
+        group_size: NonZeroUsize,
+        /// Further search inputs whose results are fused with those of `search_input`
+        additional_search_inputs: Vec<StoreKey>,
+        fusion: FusionStrategy,
+        /// Blend scores with the recency of entries. Requires the store to have a timestamp key
+        recency_boost: Option<RecencyBoost>,
+        /// How `condition` is applied when searching a non linear algorithm index
+        filter_strategy: FilterStrategy,
+        /// Vectors added to `search_input` with their weights to search with their sum instead.
+        /// A search input of zeros searches with the terms alone, e.g the centroid of several
+        /// keys each weighted 1/n
+        search_terms: Vec<SearchTerm>,
+        /// Entries left out of the results, e.g those already seen. They are left out before
+        /// ranking so that as many results as asked for are still returned
+        exclude_keys: Vec<TermVector>,
+        /// Leave results scoring the same in the order they are found in instead of ordering
+        /// them by key id, which saves hashing their keys
+        unordered_ties: bool,
     },
     CreatePredIndex {
         store: StoreName,
@@ -72,6 +127,8 @@ pub enum Query {
         store: StoreName,
         condition: PredicateCondition,
     },
+    // Moves the store into the trash when the server keeps dropped stores, from where it can be
+    // restored until it is purged
     DropStore {
         store: StoreName,
         error_if_not_exists: bool,
@@ -80,12 +137,185 @@ pub enum Query {
     ListStores,
     ListClients,
     Ping,
+    // Counts the entries matching a condition. Unless exact the count is estimated from the
+    // predicate indices and a sample of the store without matching every entry
+    CountPred {
+        store: StoreName,
+        condition: PredicateCondition,
+        exact: bool,
+    },
+    // Searches with the vector of a stored entry, found by the key id ListEntries lists it with,
+    // leaving the entry itself out of the results
+    GetSimNByKey {
+        store: StoreName,
+        key_id: String,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+    },
+    // Runs independent searches for many search inputs at once, finding the candidates matching
+    // the condition a single time for all of them
+    BatchGetSimN {
+        store: StoreName,
+        search_inputs: Vec<StoreKey>,
+        closest_n: NonZeroUsize,
+        algorithm: Algorithm,
+        condition: Option<PredicateCondition>,
+    },
+    // Pages through the entries of a store in the order of their key ids without a predicate,
+    // for debugging. The cursor of a page is passed back to get the one after it
+    ListEntries {
+        store: StoreName,
+        limit: NonZeroUsize,
+        cursor: Option<String>,
+        include_vectors: bool,
+    },
+    // Deletes matching entries in the background, returning a job id to poll with GetJob
+    DelPredAsync {
+        store: StoreName,
+        condition: PredicateCondition,
+    },
+    GetJob {
+        job_id: u64,
+    },
+    CancelJob {
+        job_id: u64,
+    },
+    ListJobs,
+    // Quotas of every namespace with one along with what the stores of each hold
+    ListQuotas,
+    // Replaces the quota of a namespace, an unbounded quota removes it. Quotas set this way last
+    // until the server restarts
+    SetQuota {
+        namespace: String,
+        quota: NamespaceQuota,
+    },
+    // Dropped stores in the trash, ordered by name
+    ListTrashedStores,
+    RestoreStore {
+        store: StoreName,
+    },
+    // Rebuilds the indices of a store from its remaining entries to give back the memory held
+    // onto after large deletions
+    CompactStore {
+        store: StoreName,
+    },
+    // Compares the predicate and non linear indices of a store with its entries, reporting the
+    // entries each index is missing and those it holds that the store does not. When asked to
+    // repair, the indices found to disagree are repaired in the background by a job whose id is
+    // returned to poll with GetJob
+    CheckStore {
+        store: StoreName,
+        repair: bool,
+    },
+    // Lists the entries of a store whose vectors hold NaN or infinite values, deleting them when
+    // asked to
+    ScrubStore {
+        store: StoreName,
+        delete: bool,
+    },
+    // Load tests similarity searches of a store on the server, away from the noise of clients
+    // and the network. The linear scan of the store and each of its non linear indices are
+    // searched in turn for `duration` milliseconds, by `concurrency` searches at a time with
+    // the vectors of `sample_queries` of its entries as search inputs
+    BenchmarkStore {
+        store: StoreName,
+        sample_queries: NonZeroUsize,
+        concurrency: NonZeroUsize,
+        duration: u64,
+    },
+    // Writes the entries of a store to a Parquet file at a path within the export location of
+    // the server in the background, returning a job id to poll with GetJob
+    ExportStoreParquet {
+        store: StoreName,
+        path: String,
+    },
+    // Turns the bulk write mode of a store on or off. Sets into a store in bulk write mode leave
+    // its indices alone, which speeds up large loads, until the mode is turned off and the
+    // indices catch up. Entries set in the meantime are missed by predicates and non linear
+    // algorithms
+    SetBulkWrite {
+        store: StoreName,
+        enabled: bool,
+    },
+    // Reads every vector and index of the stores, or of all stores when none are given, so
+    // that the first queries after a restart do not wait on cold caches
+    Warmup {
+        stores: HashSet<StoreName>,
+    },
+    // Creates the stores of a manifest that do not exist and creates or drops indices of those
+    // that do to match it, returning the changes. Nothing is changed on a dry run, and nothing
+    // is changed either when a store exists with a dimension or setting other than that of the
+    // manifest. Stores left out of the manifest are left alone
+    ApplyManifest {
+        stores: Vec<StoreManifest>,
+        dry_run: bool,
+    },
+    // Compares the stores of a manifest with those of the server without changing them,
+    // returning the changes ApplyManifest would make, the settings it would be rejected over and
+    // the stores the manifest leaves out
+    DiffManifest {
+        stores: Vec<StoreManifest>,
+    },
+    // Shows how far the remote mirror of the server lags behind it
+    MirrorStatus,
+    ControlMirror {
+        action: MirrorAction,
+    },
+    // Re-applies the settings of the configuration file of the server that can change while it
+    // runs, reporting those that changed and those that take a restart
+    ReloadConfig,
+    // Attributes the memory of the server to its stores and their indices, the trash and the
+    // connections of clients
+    GetMemoryBreakdown,
+    // Describes a single store along with statistics of its predicate indices
+    DescribeStore {
+        store: StoreName,
+    },
+}
+
+impl Query {
+    /// The store a query acts on, None for queries on the server or on several stores
+    pub fn store(&self) -> Option<&StoreName> {
+        match self {
+            Query::CreateStore { store, .. }
+            | Query::GetKey { store, .. }
+            | Query::GetPred { store, .. }
+            | Query::CountPred { store, .. }
+            | Query::GetSimN { store, .. }
+            | Query::GetSimNByKey { store, .. }
+            | Query::BatchGetSimN { store, .. }
+            | Query::CreatePredIndex { store, .. }
+            | Query::CreateNonLinearAlgorithmIndex { store, .. }
+            | Query::DropPredIndex { store, .. }
+            | Query::DropNonLinearAlgorithmIndex { store, .. }
+            | Query::Set { store, .. }
+            | Query::DelKey { store, .. }
+            | Query::ListEntries { store, .. }
+            | Query::DelPred { store, .. }
+            | Query::DelPredAsync { store, .. }
+            | Query::DropStore { store, .. }
+            | Query::RestoreStore { store, .. }
+            | Query::CompactStore { store, .. }
+            | Query::CheckStore { store, .. }
+            | Query::ScrubStore { store, .. }
+            | Query::BenchmarkStore { store, .. }
+            | Query::ExportStoreParquet { store, .. }
+            | Query::SetBulkWrite { store, .. }
+            | Query::DescribeStore { store } => Some(store),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ServerQuery {
     queries: Vec<Query>,
     trace_id: Option<String>,
+    error_policy: ErrorPolicy,
+    // milliseconds the server works on the queries before failing those left
+    timeout_ms: Option<u64>,
+    priority: Priority,
 }
 
 impl ServerQuery {
@@ -93,6 +323,9 @@ impl ServerQuery {
         Ok(Self {
             queries: FallibleVec::try_with_capacity(len)?,
             trace_id: None,
+            error_policy: ErrorPolicy::default(),
+            timeout_ms: None,
+            priority: Priority::default(),
         })
     }
     pub fn with_capacity_and_tracing_id(
@@ -102,6 +335,9 @@ impl ServerQuery {
         Ok(Self {
             queries: FallibleVec::try_with_capacity(len)?,
             trace_id,
+            error_policy: ErrorPolicy::default(),
+            timeout_ms: None,
+            priority: Priority::default(),
         })
     }
 
@@ -109,10 +345,42 @@ impl ServerQuery {
         self.queries.push(entry)
     }
 
+    /// the queries to send, for changing them in place before they are sent
+    pub fn queries_mut(&mut self) -> &mut [Query] {
+        &mut self.queries
+    }
+
+    pub fn set_trace_id(&mut self, trace_id: Option<String>) {
+        self.trace_id = trace_id
+    }
+
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.error_policy = error_policy
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout_ms = Some(timeout.as_millis() as u64)
+    }
+
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority
+    }
+
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
     pub fn from_queries(queries: &[Query]) -> Self {
         Self {
             queries: queries.to_vec(),
             trace_id: None,
+            error_policy: ErrorPolicy::default(),
+            timeout_ms: None,
+            priority: Priority::default(),
         }
     }
 }
@@ -128,4 +396,13 @@ impl BinCodeSerAndDeserQuery for ServerQuery {
     fn get_traceparent(&self) -> Option<String> {
         self.trace_id.clone()
     }
+    fn get_error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+    fn get_timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+    fn get_priority(&self) -> Priority {
+        self.priority
+    }
 }
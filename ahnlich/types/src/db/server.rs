@@ -1,15 +1,26 @@
 use crate::bincode::{BinCodeSerAndDeser, BinCodeSerAndDeserResponse};
-use crate::client::ConnectedClient;
+use crate::client::{ConnectedClient, ConnectionMemory, ConnectionStats};
+use crate::error::ErrorResponse;
+use crate::jobs::JobStatus;
+use crate::keyval::KeyElementType;
+use crate::keyval::NonFiniteVectors;
+use crate::keyval::StorageTier;
+use crate::keyval::StoreEviction;
 use crate::keyval::StoreKey;
 use crate::keyval::StoreName;
 use crate::keyval::StoreValue;
-use crate::similarity::Similarity;
+use crate::keyval::VectorNormalization;
+use crate::metadata::{MetadataKey, MetadataValue};
+use crate::similarity::{NonLinearAlgorithm, Similarity};
 use crate::version::Version;
 use crate::ServerType;
+use crate::{ConfigReload, PriorityStats, RequestLimits};
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashSet;
 use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::time::SystemTime;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ServerResponse {
@@ -29,6 +40,32 @@ pub enum ServerResponse {
     Del(usize),
     // number of created indexes
     CreateIndex(usize),
+    StoreDescription(StoreDescription),
+    Compaction(StoreCompaction),
+    // id of a job started in the background
+    JobStarted(u64),
+    JobStatus(JobStatus),
+    // Jobs that are running or finished recently, ordered by id
+    JobList(Vec<JobStatus>),
+    // Namespaces with a quota, ordered by namespace
+    QuotaList(Vec<NamespaceUsage>),
+    TrashedStoreList(Vec<TrashedStoreInfo>),
+    MirrorStatus(MirrorStatus),
+    // Changes made to bring the stores in line with a manifest, or that would be made on a dry
+    // run, in the order of the stores of the manifest
+    ManifestChanges(Vec<ManifestChange>),
+    ManifestDrift(ManifestDrift),
+    EntryList(EntryPage),
+    // number of matching entries, which is an estimate unless asked to be exact
+    Count(usize),
+    // Results of each search input of a BatchGetSimN, in the order of the inputs
+    BatchGetSimN(Vec<Vec<(StoreKey, StoreValue, Similarity)>>),
+    StoreCheck(StoreCheck),
+    // Entries found with NaN or infinite values in their vectors, ordered by key id
+    ScrubbedEntries(Vec<ListedEntry>),
+    StoreBenchmark(StoreBenchmark),
+    MemoryBreakdown(MemoryBreakdown),
+    ConfigReloaded(ConfigReload),
 }
 
 /// StoreUpsert shows how many entries were inserted and updated during a store add call
@@ -44,33 +81,408 @@ impl StoreUpsert {
     }
 }
 
-/// StoreInfo just shows store name, size and length
+/// StoreCompaction shows how much memory compacting a store gave back
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StoreCompaction {
+    pub reclaimed_bytes: usize,
+    // size of the store after it was compacted
+    pub size_in_bytes: usize,
+}
+
+/// StoreCheck shows where the indices of a store disagree with its entries. Entries written in
+/// bulk write mode that the indices are yet to catch up with are left out
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StoreCheck {
+    pub entries: usize,
+    // every index of the store, predicate indices first and each kind ordered by name
+    pub indices: Vec<IndexCheck>,
+    // id of the job repairing the indices that disagree, set when a repair was asked for and
+    // there was something to repair
+    pub repair_job: Option<u64>,
+}
+
+/// IndexCheck shows how many entries an index of a store disagrees with it on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IndexCheck {
+    pub index: StoreIndex,
+    // entries of the store the index does not hold
+    pub missing: usize,
+    // entries the index holds that the store does not, or holds under another value
+    pub orphaned: usize,
+}
+
+impl IndexCheck {
+    pub fn is_consistent(&self) -> bool {
+        self.missing == 0 && self.orphaned == 0
+    }
+}
+
+/// StoreIndex is one of the indices of a store
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StoreIndex {
+    Predicate(MetadataKey),
+    NonLinear(NonLinearAlgorithm),
+}
+
+/// StoreBenchmark shows how similarity searches of a store fared in a load test on the server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StoreBenchmark {
+    pub entries: usize,
+    // entries whose vectors were searched with, fewer than asked for in a smaller store
+    pub sample_queries: usize,
+    pub concurrency: usize,
+    // the linear scan followed by each non linear index of the store
+    pub paths: Vec<SearchPathBenchmark>,
+}
+
+/// SearchPathBenchmark shows the throughput and latencies of the searches going through one
+/// path of a store. Latencies are in microseconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SearchPathBenchmark {
+    pub path: SearchPath,
+    pub queries: usize,
+    pub queries_per_second: u64,
+    pub p50_latency: u64,
+    pub p90_latency: u64,
+    pub p99_latency: u64,
+    pub max_latency: u64,
+}
+
+/// SearchPath is how a similarity search goes through the entries of a store
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchPath {
+    // comparing the search input with every entry by cosine similarity
+    Linear,
+    NonLinear(NonLinearAlgorithm),
+}
+
+/// MemoryBreakdown attributes the memory of the server to what holds it, so that operators can
+/// see what to drop as it nears its allocator limit. The sizes of stores are estimated from
+/// their statistics
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemoryBreakdown {
+    pub limit: usize,
+    pub remaining: usize,
+    // stores ordered from the largest
+    pub stores: Vec<StoreMemory>,
+    // dropped stores held in the trash until they are restored or purged
+    pub trash: usize,
+    pub connections: ConnectionMemory,
+    // estimated bytes of the requests the server is processing
+    pub in_flight_memory: usize,
+}
+
+/// StoreMemory shows the estimated bytes held by a store, broken down by what holds them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StoreMemory {
+    pub name: StoreName,
+    // vectors of the entries, only those in the page cache for disk tier stores
+    pub vectors: usize,
+    // key ids and metadata of the entries
+    pub entries: usize,
+    pub predicate_indices: usize,
+    pub non_linear_indices: usize,
+}
+
+impl StoreMemory {
+    pub fn total(&self) -> usize {
+        self.vectors + self.entries + self.predicate_indices + self.non_linear_indices
+    }
+}
+
+/// StoreInfo just shows store name, size, length, the dimension of its keys and the limits on
+/// requests into it
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct StoreInfo {
     pub name: StoreName,
     pub len: usize,
     pub size_in_bytes: usize,
+    pub dimension: NonZeroUsize,
+    pub request_limits: RequestLimits,
+}
+
+/// TrashedStoreInfo shows a dropped store kept in the trash until it is restored or purged
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TrashedStoreInfo {
+    pub name: StoreName,
+    pub len: usize,
+    pub size_in_bytes: usize,
+    pub dropped_at: SystemTime,
+    // the store is purged for good at this time unless it is restored first
+    pub purge_at: SystemTime,
+}
+
+/// MirrorAction controls the mirroring of a server to a remote one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MirrorAction {
+    // Holds writes back from the mirror, they are still logged to be sent once resumed
+    Pause,
+    Resume,
+    // Drops the logged writes and copies every store over to the mirror afresh, which a mirror
+    // that diverged or missed writes needs
+    Resync,
+}
+
+/// MirrorState is what the mirroring of a server to a remote one is doing
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MirrorState {
+    Running,
+    Paused,
+    // Waiting to copy every store over to the mirror before sending writes again
+    Resyncing,
+}
+
+/// MirrorStatus shows how far a remote mirror of the server lags behind it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MirrorStatus {
+    // host:port of the mirror
+    pub target: String,
+    pub state: MirrorState,
+    // writes logged since the server started or last resynced the mirror
+    pub logged: u64,
+    // logged writes the mirror applied or rejected
+    pub applied: u64,
+    // writes the mirror rejected, which leave it diverged from the server until it is resynced
+    pub failed: u64,
+    // writes waiting to be sent to the mirror
+    pub pending: usize,
+    // milliseconds the oldest pending write has waited
+    pub lag_ms: u64,
+    pub last_error: Option<String>,
+}
+
+/// StoreDescription shows the info of a store along with its indices and statistics of the
+/// values in each predicate index
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StoreDescription {
+    pub info: StoreInfo,
+    // ordered by key
+    pub predicate_indices: Vec<PredicateIndexStats>,
+    pub non_linear_indices: Vec<NonLinearAlgorithm>,
+    pub timestamp_key: Option<MetadataKey>,
+    pub storage_tier: StorageTier,
+    pub key_element_type: KeyElementType,
+    pub normalization: VectorNormalization,
+    pub index_seed: u64,
+    pub non_finite_vectors: NonFiniteVectors,
+    // whether sets are leaving the indices to catch up later
+    pub bulk_write: bool,
+    pub eviction: Option<StoreEviction>,
+    // entries evicted since the store was created or loaded
+    pub evicted: u64,
+}
+
+/// StoreManifest declares a store along with its indices as ApplyManifest should leave it. Fields
+/// left out of a manifest file take the defaults of CreateStore
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StoreManifest {
+    pub store: StoreName,
+    pub dimension: NonZeroUsize,
+    #[serde(default)]
+    pub predicates: HashSet<MetadataKey>,
+    #[serde(default)]
+    pub non_linear_indices: HashSet<NonLinearAlgorithm>,
+    #[serde(default)]
+    pub timestamp_key: Option<MetadataKey>,
+    #[serde(default)]
+    pub storage_tier: StorageTier,
+    #[serde(default)]
+    pub key_element_type: KeyElementType,
+    #[serde(default)]
+    pub normalization: VectorNormalization,
+    #[serde(default)]
+    pub index_seed: Option<u64>,
+    #[serde(default)]
+    pub non_finite_vectors: NonFiniteVectors,
+    #[serde(default)]
+    pub eviction: Option<StoreEviction>,
+}
+
+/// ManifestChange is a change ApplyManifest makes to a store, indices are ordered
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ManifestChange {
+    CreateStore {
+        store: StoreName,
+    },
+    CreatePredicates {
+        store: StoreName,
+        predicates: Vec<MetadataKey>,
+    },
+    DropPredicates {
+        store: StoreName,
+        predicates: Vec<MetadataKey>,
+    },
+    CreateNonLinearIndices {
+        store: StoreName,
+        non_linear_indices: Vec<NonLinearAlgorithm>,
+    },
+    DropNonLinearIndices {
+        store: StoreName,
+        non_linear_indices: Vec<NonLinearAlgorithm>,
+    },
+}
+
+impl std::fmt::Display for ManifestChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn join(items: &[impl std::fmt::Display]) -> String {
+            items
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        match self {
+            Self::CreateStore { store } => write!(f, "create store {store}"),
+            Self::CreatePredicates { store, predicates } => {
+                write!(f, "create predicates {} on store {store}", join(predicates))
+            }
+            Self::DropPredicates { store, predicates } => {
+                write!(f, "drop predicates {} on store {store}", join(predicates))
+            }
+            Self::CreateNonLinearIndices {
+                store,
+                non_linear_indices,
+            } => write!(
+                f,
+                "create non linear indices {} on store {store}",
+                join(non_linear_indices)
+            ),
+            Self::DropNonLinearIndices {
+                store,
+                non_linear_indices,
+            } => write!(
+                f,
+                "drop non linear indices {} on store {store}",
+                join(non_linear_indices)
+            ),
+        }
+    }
+}
+
+/// SettingDrift is a setting a store has other than that of its manifest, which ApplyManifest
+/// cannot change in place. Unset settings such as a missing timestamp key are `None`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SettingDrift {
+    pub store: StoreName,
+    pub setting: String,
+    pub manifest: Option<String>,
+    pub live: Option<String>,
+}
+
+/// ListedEntry is an entry of a store as listed by ListEntries
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ListedEntry {
+    // Hash of the key the store refers to the entry by
+    pub key_id: String,
+    // Only included when asked for
+    pub key: Option<StoreKey>,
+    pub value: StoreValue,
+}
+
+/// EntryPage is a page of the entries of a store ordered by key id
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntryPage {
+    pub entries: Vec<ListedEntry>,
+    // Passed to ListEntries to get the next page, None once the last page is reached
+    pub next_cursor: Option<String>,
+    // Entries of the store when the page was read. Writes in between pages may change it
+    pub estimated_total: usize,
+}
+
+/// ManifestDrift is how the stores of a server differ from a manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestDrift {
+    // Changes ApplyManifest would make, in the order of the stores of the manifest
+    pub changes: Vec<ManifestChange>,
+    // Settings ApplyManifest would be rejected over
+    pub settings: Vec<SettingDrift>,
+    // Stores of the server the manifest does not declare, ordered by name
+    pub unmanaged_stores: Vec<StoreName>,
+}
+
+impl ManifestDrift {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.settings.is_empty() && self.unmanaged_stores.is_empty()
+    }
+}
+
+/// NamespaceQuota caps what the stores of a namespace hold together, a cap is unbounded when None
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct NamespaceQuota {
+    pub max_stores: Option<usize>,
+    // number of entries across the stores
+    pub max_vectors: Option<usize>,
+    pub max_memory_bytes: Option<usize>,
+}
+
+impl NamespaceQuota {
+    pub fn is_unbounded(&self) -> bool {
+        self.max_stores.is_none() && self.max_vectors.is_none() && self.max_memory_bytes.is_none()
+    }
+}
+
+/// NamespaceUsage shows the quota of a namespace next to what its stores currently hold
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NamespaceUsage {
+    pub namespace: String,
+    pub quota: NamespaceQuota,
+    pub stores: usize,
+    pub vectors: usize,
+    // Estimated bytes of the vectors and metadata of the entries, what max_memory_bytes caps
+    pub memory_bytes: usize,
+}
+
+/// PredicateIndexStats shows how the values of a predicate index are distributed, which is what
+/// decides whether the index or a scan is used for a predicate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PredicateIndexStats {
+    pub key: MetadataKey,
+    // number of entries with a value for the key
+    pub entries: usize,
+    pub distinct_values: usize,
+    // the most frequent values and their number of entries, from the most frequent
+    pub most_frequent: Vec<(MetadataValue, usize)>,
+    // last time entries were added to or removed from the index
+    pub last_updated: SystemTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialOrd, Ord)]
 pub struct ServerInfo {
     pub address: String,
     pub version: Version,
+    // range of client versions the server services queries from
+    pub min_client_version: Version,
+    pub max_client_version: Version,
     pub r#type: ServerType,
     pub limit: usize,
     pub remaining: usize,
+    // limits on the requests of the client asking for the info
+    pub request_limits: RequestLimits,
+    // estimated bytes of the requests the server is processing
+    pub in_flight_memory: usize,
+    // queries abandoned because the timeout of their request passed
+    pub deadlines_exceeded: u64,
+    // requests run at each priority
+    pub priorities: Vec<PriorityStats>,
+    pub connections: ConnectionStats,
 }
 
-/// ignore `remaining` field during comparison for server info as a server might allocate memory
+/// ignore `remaining`, `in_flight_memory`, `deadlines_exceeded`, `priorities` and `connections`
+/// fields during comparison for server info as a server might allocate memory
 impl PartialEq for ServerInfo {
     fn eq(&self, other: &Self) -> bool {
         self.version.eq(&other.version)
+            && self.min_client_version.eq(&other.min_client_version)
+            && self.max_client_version.eq(&other.max_client_version)
             && self.r#type.eq(&other.r#type)
             && self.limit.eq(&other.limit)
+            && self.request_limits.eq(&other.request_limits)
     }
 }
 
-pub type ServerResultInner = Vec<Result<ServerResponse, String>>;
+pub type ServerResultInner = Vec<Result<ServerResponse, ErrorResponse>>;
 // ServerResult: Given that an array of queries are sent in, we expect that an array of responses
 // be returned each being a potential error
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -87,11 +499,11 @@ impl ServerResult {
         }
     }
 
-    pub fn pop(mut self) -> Option<Result<ServerResponse, String>> {
+    pub fn pop(mut self) -> Option<Result<ServerResponse, ErrorResponse>> {
         self.results.pop()
     }
 
-    pub fn push(&mut self, entry: Result<ServerResponse, String>) {
+    pub fn push(&mut self, entry: Result<ServerResponse, ErrorResponse>) {
         self.results.push(entry)
     }
 
@@ -101,9 +513,19 @@ impl ServerResult {
 }
 
 impl BinCodeSerAndDeserResponse for ServerResult {
-    fn from_error(err: String) -> Self {
+    fn from_error(err: ErrorResponse) -> Self {
         Self {
             results: vec![Err(err)],
         }
     }
+
+    fn with_request_id(self, request_id: &str) -> Self {
+        Self {
+            results: self
+                .results
+                .into_iter()
+                .map(|result| result.map_err(|err| err.with_request_id(request_id)))
+                .collect(),
+        }
+    }
 }
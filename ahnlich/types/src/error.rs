@@ -0,0 +1,83 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap as StdHashMap;
+use std::fmt;
+
+/// ErrorCode identifies why a query failed so that clients can handle specific failures without
+/// matching on error messages
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    // The server failed to process the request
+    Internal,
+    InvalidArgument,
+    StoreNotFound,
+    StoreAlreadyExists,
+    PredicateNotFound,
+    NonLinearIndexNotFound,
+    DimensionMismatch,
+    JobNotFound,
+    ReservedKey,
+    ResourceExhausted,
+    ModelError,
+    // A server the request depends on could not be reached or gave an unexpected response
+    Unavailable,
+    // The client version is outside the range of versions supported by the server
+    IncompatibleVersion,
+    // The request is larger than the limits of the client or store allow
+    LimitExceeded,
+    // The stores of a namespace would hold more than its quota allows
+    QuotaExceeded,
+    // The server is a read only mirror of another server
+    ReadOnly,
+    // The timeout of the request passed before the query finished
+    DeadlineExceeded,
+}
+
+/// ErrorResponse is returned in place of a response for a query that failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+    // Details of the failure e.g the name of a store that was not found
+    pub metadata: StdHashMap<String, String>,
+    // Correlation id of the request the query was sent in, matching the server logs
+    pub request_id: Option<String>,
+}
+
+/// ignore `request_id` field during comparison as it differs for every request
+impl PartialEq for ErrorResponse {
+    fn eq(&self, other: &Self) -> bool {
+        self.code.eq(&other.code)
+            && self.message.eq(&other.message)
+            && self.metadata.eq(&other.metadata)
+    }
+}
+
+impl Eq for ErrorResponse {}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: impl ToString) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+            metadata: StdHashMap::new(),
+            request_id: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: impl ToString) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: impl ToString) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
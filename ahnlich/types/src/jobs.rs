@@ -0,0 +1,32 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// JobKind is the long running operation a job was started for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    DelPred,
+    MigrateStore,
+    ExportStore,
+    RepairStore,
+}
+
+/// JobState shows where a job running in the background is at
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum JobState {
+    Running,
+    Completed,
+    // Stopped before it could process everything, either by CancelJob or during shutdown
+    Cancelled,
+    Failed(String),
+}
+
+/// JobStatus shows the progress of a job running in the background
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct JobStatus {
+    pub id: u64,
+    pub kind: JobKind,
+    pub state: JobState,
+    // number of entries processed so far out of the total
+    pub processed: usize,
+    pub total: usize,
+}
@@ -4,11 +4,23 @@ use ndarray::Array1;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap as StdHashMap;
 use std::fmt;
+use std::num::NonZeroUsize;
 /// Name of a Store
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct StoreName(pub String);
 
+impl StoreName {
+    /// Namespace of the store, the part of its name before the first `/` e.g `acme` for
+    /// `acme/products`. Stores without one fall outside of every namespace
+    pub fn namespace(&self) -> Option<&str> {
+        self.0
+            .split_once('/')
+            .map(|(namespace, _)| namespace)
+            .filter(|namespace| !namespace.is_empty())
+    }
+}
+
 impl fmt::Display for StoreName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
@@ -48,35 +60,130 @@ impl PartialEq for StoreKey {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Where the vectors of the entries of a store are held
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum StorageTier {
+    #[default]
+    Memory,
+    /// Vectors are held in a memory mapped file and read through an in-memory page cache while
+    /// metadata and indices stay in memory, trading latency for stores larger than memory
+    Disk,
+}
+
+/// Precision the values of the store keys of a store are held in. Keys are always sent and
+/// returned as f32 and are rounded to the precision of the store when they come in
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum KeyElementType {
+    #[default]
+    Float32,
+    /// IEEE 754 half precision, keeping more precision within a narrower range of values
+    Float16,
+    /// Brain floating point, keeping the range of f32 with less precision
+    BFloat16,
+    /// Signed bytes, such as quantized embeddings. Keys set into the store must hold whole
+    /// numbers from -128 to 127
+    Int8,
+}
+
+/// What is done to the store keys of a store as they are written
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum VectorNormalization {
+    #[default]
+    None,
+    /// Keys are scaled to unit length so that keys pointing the same way are the same entry and
+    /// cosine similarity is computed as a dot product. Keys of zero length are rejected
+    L2,
+}
+
+/// What a store does with keys holding NaN or infinite values, which have no place in a
+/// similarity ordering
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum NonFiniteVectors {
+    /// Requests with such keys or search inputs fail
+    #[default]
+    Reject,
+    /// NaN is replaced with zero and infinities with the largest finite value of their sign that
+    /// the key element type of the store holds
+    Sanitize,
+}
+
+/// Which entries a bounded store evicts first once it holds more than its bounds
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum EvictionPolicy {
+    /// Least recently used, those written or read the longest time ago
+    #[default]
+    LRU,
+    /// Least frequently used, those written and read the fewest times with the least recently
+    /// used first among them
+    LFU,
+}
+
+/// Bounds a store as a cache, such as of the embeddings of LLM prompts. Entries are evicted by
+/// the policy after each set leaving the store past either bound
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StoreEviction {
+    pub max_entries: Option<NonZeroUsize>,
+    /// Estimated bytes of the store as reported by GetMemoryBreakdown
+    pub max_bytes: Option<NonZeroUsize>,
+    pub policy: EvictionPolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum StoreInput {
     RawString(String),
     Image(Vec<u8>),
+    /// A text and an image embedded as a single entry by a store with a multimodal fusion
+    Multimodal { text: String, image: Vec<u8> },
 }
 
 #[allow(clippy::len_without_is_empty)]
 impl StoreInput {
+    /// Size of the input in bytes, as sent in a chunked transfer
     pub fn len(&self) -> usize {
         match self {
             Self::Image(value) => value.len(),
             Self::RawString(s) => s.len(),
+            Self::Multimodal { text, image } => MULTIMODAL_TEXT_LEN_SIZE + text.len() + image.len(),
+        }
+    }
+
+    /// Splits the input into the metadata value saving it and, for a multimodal input whose
+    /// image is saved as the value, its text
+    pub fn into_metadata(self) -> (MetadataValue, Option<String>) {
+        match self {
+            Self::RawString(s) => (MetadataValue::RawString(s), None),
+            Self::Image(binary) => (MetadataValue::Image(binary), None),
+            Self::Multimodal { text, image } => (MetadataValue::Image(image), Some(text)),
+        }
+    }
+
+    /// Rebuilds an input split by [`StoreInput::into_metadata`]
+    pub fn from_metadata(value: MetadataValue, text: Option<String>) -> Self {
+        match (value, text) {
+            (MetadataValue::Image(image), Some(text)) => Self::Multimodal { text, image },
+            (value, _) => value.into(),
         }
     }
 }
+
+/// Bytes holding the length of the text of a multimodal input sent in a chunked transfer
+pub(crate) const MULTIMODAL_TEXT_LEN_SIZE: usize = 8;
 impl fmt::Display for StoreInput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::RawString(_) => write!(f, "RawString"),
             Self::Image(_) => write!(f, "Image"),
-        }
-    }
-}
-
-impl From<StoreInput> for MetadataValue {
-    fn from(value: StoreInput) -> Self {
-        match value {
-            StoreInput::Image(binary) => MetadataValue::Image(binary),
-            StoreInput::RawString(s) => MetadataValue::RawString(s),
+            Self::Multimodal { .. } => write!(f, "Multimodal"),
         }
     }
 }
@@ -89,3 +196,24 @@ impl From<MetadataValue> for StoreInput {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_input_metadata_round_trip() {
+        let inputs = [
+            StoreInput::RawString("a dog".to_string()),
+            StoreInput::Image(vec![1, 2, 3]),
+            StoreInput::Multimodal {
+                text: "a dog".to_string(),
+                image: vec![1, 2, 3],
+            },
+        ];
+        for input in inputs {
+            let (value, text) = input.clone().into_metadata();
+            assert_eq!(StoreInput::from_metadata(value, text), input);
+        }
+    }
+}
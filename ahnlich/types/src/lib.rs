@@ -2,16 +2,93 @@ pub mod ai;
 pub mod bincode;
 pub mod client;
 pub mod db;
+pub mod error;
+pub mod jobs;
 pub mod keyval;
 pub mod metadata;
 pub mod predicate;
 pub mod similarity;
 pub mod version;
+#[cfg(test)]
+mod tests;
 
 use serde::{Deserialize, Serialize};
 
+/// ErrorPolicy decides what a server does with the rest of a pipeline once one of its queries
+/// fails
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    // Run every query, failed queries only yield an error in their slot
+    #[default]
+    ContinueOnError,
+    // Stop at the first failed query, the results returned end with its error
+    FailFast,
+}
+
+/// Priority decides which requests a server runs first when it is running as many as it allows
+/// at once. Requests that wait too long run ahead of those of a higher priority so that none are
+/// held back for good
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum Priority {
+    // Requests a user is waiting on, such as searches
+    Interactive,
+    #[default]
+    Normal,
+    // Requests that can wait, such as batch ingestion
+    Bulk,
+}
+
+/// PriorityStats counts the requests a server ran at a priority since it started
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PriorityStats {
+    pub priority: Priority,
+    pub requests: u64,
+    // requests waiting for their turn to run
+    pub queued: u64,
+    // total milliseconds requests waited for their turn
+    pub wait_ms: u64,
+    // total milliseconds from the arrival of requests to their response
+    pub latency_ms: u64,
+    pub max_latency_ms: u64,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ServerType {
     Database,
     AI,
 }
+
+/// ConfigReload reports what reloading the configuration file of a server did, naming settings
+/// after their flags
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConfigReload {
+    // settings whose new values apply to the running server
+    pub changed: Vec<String>,
+    // settings left unapplied as they only take effect once the server restarts with them
+    pub requires_restart: Vec<String>,
+}
+
+/// RequestLimits caps the requests a server accepts, either from a client or into a store
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestLimits {
+    // size in bytes of a request
+    pub message_size: usize,
+    // number of entries a single set can hold, unbounded when None
+    pub batch_size: Option<usize>,
+}
+
+impl RequestLimits {
+    /// the stricter of both limits, used when a client and a store both have limits
+    pub fn min(self, other: Self) -> Self {
+        let batch_size = match (self.batch_size, other.batch_size) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Self {
+            message_size: self.message_size.min(other.message_size),
+            batch_size,
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt;
+
+/// Prefix reserved for metadata keys that are written and managed by ahnlich services
+/// themselves. User supplied metadata must never use a key within this namespace
+pub const SYSTEM_METADATA_NAMESPACE: &str = "_ahnlich.";
+
 /// New types for store metadata key and values
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 #[serde(transparent)]
@@ -9,6 +14,28 @@ impl MetadataKey {
     pub fn new(input: String) -> Self {
         Self(input)
     }
+
+    /// Creates a key within the system metadata namespace e.g `input` becomes `_ahnlich.input`
+    pub fn system(name: &str) -> Self {
+        Self(format!("{SYSTEM_METADATA_NAMESPACE}{name}"))
+    }
+
+    /// Returns true if the key falls within the reserved system metadata namespace
+    pub fn is_system(&self) -> bool {
+        self.0.starts_with(SYSTEM_METADATA_NAMESPACE)
+    }
+}
+
+impl From<&str> for MetadataKey {
+    fn from(input: &str) -> Self {
+        Self(input.to_string())
+    }
+}
+
+impl From<String> for MetadataKey {
+    fn from(input: String) -> Self {
+        Self(input)
+    }
 }
 
 impl fmt::Display for MetadataKey {
@@ -22,3 +49,35 @@ pub enum MetadataValue {
     RawString(String),
     Image(Vec<u8>),
 }
+
+impl From<&str> for MetadataValue {
+    fn from(input: &str) -> Self {
+        Self::RawString(input.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(input: String) -> Self {
+        Self::RawString(input)
+    }
+}
+
+impl From<Vec<u8>> for MetadataValue {
+    fn from(input: Vec<u8>) -> Self {
+        Self::Image(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_metadata_key() {
+        let key = MetadataKey::system("input_key");
+        assert_eq!(key.to_string(), "_ahnlich.input_key");
+        assert!(key.is_system());
+        assert!(!MetadataKey::new("author".to_string()).is_system());
+        assert!(!MetadataKey::new("_ahnlich_input_key".to_string()).is_system());
+    }
+}
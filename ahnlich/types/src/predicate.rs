@@ -27,7 +27,61 @@ pub enum Predicate {
     },
 }
 
+/// Predicates and the conditions combining them can be built without spelling out each variant
+///
+/// ```
+/// use ahnlich_types::predicate::{Predicate, PredicateCondition};
+///
+/// let condition = Predicate::equals("role", "mage").and(Predicate::in_("lang", ["de", "en"]));
+/// assert!(matches!(condition, PredicateCondition::And(..)));
+/// ```
 impl Predicate {
+    /// Matches entries whose value of `key` is `value`
+    pub fn equals(key: impl Into<MetadataKey>, value: impl Into<MetadataValue>) -> Self {
+        Self::Equals {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Matches entries whose value of `key` is not `value`
+    pub fn not_equals(key: impl Into<MetadataKey>, value: impl Into<MetadataValue>) -> Self {
+        Self::NotEquals {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    /// Matches entries whose value of `key` is any of `values`
+    pub fn in_<V: Into<MetadataValue>>(
+        key: impl Into<MetadataKey>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        Self::In {
+            key: key.into(),
+            value: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Matches entries whose value of `key` is none of `values`
+    pub fn not_in<V: Into<MetadataValue>>(
+        key: impl Into<MetadataKey>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        Self::NotIn {
+            key: key.into(),
+            value: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn and(self, other: impl Into<PredicateCondition>) -> PredicateCondition {
+        PredicateCondition::from(self).and(other)
+    }
+
+    pub fn or(self, other: impl Into<PredicateCondition>) -> PredicateCondition {
+        PredicateCondition::from(self).or(other)
+    }
+
     pub fn get_key(&self) -> &MetadataKey {
         match self {
             Predicate::Equals { key, .. } => key,
@@ -57,12 +111,85 @@ pub enum PredicateCondition {
     Or(Box<PredicateCondition>, Box<PredicateCondition>),
 }
 
+impl From<Predicate> for PredicateCondition {
+    fn from(predicate: Predicate) -> Self {
+        Self::Value(predicate)
+    }
+}
+
 impl PredicateCondition {
-    pub fn and(self, other: PredicateCondition) -> Self {
-        Self::And(Box::new(self), Box::new(other))
+    pub fn and(self, other: impl Into<PredicateCondition>) -> Self {
+        Self::And(Box::new(self), Box::new(other.into()))
+    }
+
+    pub fn or(self, other: impl Into<PredicateCondition>) -> Self {
+        Self::Or(Box::new(self), Box::new(other.into()))
     }
 
-    pub fn or(self, other: PredicateCondition) -> Self {
-        Self::Or(Box::new(self), Box::new(other))
+    /// Condition met when all of the conditions are, None when there are none
+    pub fn all<C: Into<PredicateCondition>>(
+        conditions: impl IntoIterator<Item = C>,
+    ) -> Option<Self> {
+        conditions.into_iter().map(Into::into).reduce(Self::and)
+    }
+
+    /// Condition met when any of the conditions is, None when there are none
+    pub fn any<C: Into<PredicateCondition>>(
+        conditions: impl IntoIterator<Item = C>,
+    ) -> Option<Self> {
+        conditions.into_iter().map(Into::into).reduce(Self::or)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_condition() {
+        let role = Predicate::Equals {
+            key: MetadataKey::new("role".to_string()),
+            value: MetadataValue::RawString("mage".to_string()),
+        };
+        let lang = Predicate::In {
+            key: MetadataKey::new("lang".to_string()),
+            value: HashSet::from_iter([
+                MetadataValue::RawString("de".to_string()),
+                MetadataValue::RawString("en".to_string()),
+            ]),
+        };
+        let icon = Predicate::NotEquals {
+            key: MetadataKey::new("icon".to_string()),
+            value: MetadataValue::Image(vec![1, 2]),
+        };
+        let condition = Predicate::equals("role", "mage")
+            .and(Predicate::in_("lang", ["de", "en"]))
+            .or(Predicate::not_equals("icon", vec![1u8, 2]));
+        assert_eq!(
+            condition,
+            PredicateCondition::Or(
+                Box::new(PredicateCondition::And(
+                    Box::new(PredicateCondition::Value(role.clone())),
+                    Box::new(PredicateCondition::Value(lang.clone())),
+                )),
+                Box::new(PredicateCondition::Value(icon)),
+            )
+        );
+        assert_eq!(
+            Predicate::not_in("lang", vec![String::from("de")]),
+            Predicate::NotIn {
+                key: MetadataKey::new("lang".to_string()),
+                value: HashSet::from_iter([MetadataValue::RawString("de".to_string())]),
+            }
+        );
+        assert_eq!(
+            PredicateCondition::all([role.clone(), lang.clone()]),
+            Some(role.clone().and(lang.clone()))
+        );
+        assert_eq!(
+            PredicateCondition::any([role.clone(), lang.clone()]),
+            Some(role.or(lang))
+        );
+        assert_eq!(PredicateCondition::all(Vec::<Predicate>::new()), None);
     }
 }
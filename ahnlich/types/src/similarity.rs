@@ -1,3 +1,6 @@
+use std::num::NonZeroU64;
+
+use crate::keyval::StoreKey;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -40,6 +43,35 @@ impl std::fmt::Display for NonLinearAlgorithm {
     }
 }
 
+/// Strategy used to combine the scores of several search inputs into a single ranking
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum FusionStrategy {
+    /// Average of the scores for every search input
+    Mean,
+    /// Best score for any of the search inputs
+    Max,
+    /// Sum of 1 / (60 + rank) for every search input. Only ranks are considered so it is
+    /// unaffected by the scale of scores
+    ReciprocalRankFusion,
+}
+
+/// How a predicate condition is combined with a search over a non linear algorithm index.
+/// Linear algorithms always search only the entries matching the condition
+#[derive(
+    Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub enum FilterStrategy {
+    /// Search the index for matching entries only, which nears a linear scan when few match
+    Pre,
+    /// Search the whole index for more results than needed and drop those not matching, fetching
+    /// more until enough match
+    Post,
+    /// Post-filter when a large enough share of the store matches the condition, otherwise
+    /// pre-filter
+    #[default]
+    Auto,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Similarity(pub f32);
 
@@ -50,3 +82,46 @@ impl PartialEq for Similarity {
 }
 
 impl Eq for Similarity {}
+
+/// Boosts newer entries by blending the normalized score of each result with the exponential
+/// decay of its age, read as Unix seconds from the timestamp key of the store
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RecencyBoost {
+    /// Age in seconds at which the recency of an entry is worth half that of a brand new one
+    pub half_life: NonZeroU64,
+    /// Share of the final score given to recency, between 0 and 1
+    pub weight: f32,
+}
+
+impl PartialEq for RecencyBoost {
+    fn eq(&self, other: &Self) -> bool {
+        self.half_life == other.half_life && (self.weight - other.weight).abs() < f32::EPSILON
+    }
+}
+
+impl Eq for RecencyBoost {}
+
+/// Vector of a search term
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TermVector {
+    Key(StoreKey),
+    /// The key of an entry of the store by the id ListEntries returns for it, so that stored
+    /// vectors need not be fetched to search with them
+    KeyId(String),
+}
+
+/// Vector added to the search input of GETSIMN in proportion to its weight before searching, so
+/// that e.g king - man + woman is a search input of king with man weighted -1 and woman 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchTerm {
+    pub vector: TermVector,
+    pub weight: f32,
+}
+
+impl PartialEq for SearchTerm {
+    fn eq(&self, other: &Self) -> bool {
+        self.vector == other.vector && (self.weight - other.weight).abs() < f32::EPSILON
+    }
+}
+
+impl Eq for SearchTerm {}
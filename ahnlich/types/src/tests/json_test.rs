@@ -0,0 +1,204 @@
+//! Golden files of the JSON form of queries and responses, guarding clients that log or store
+//! them as JSON against fields and variants being renamed. Run with `UPDATE_GOLDEN=1` to rewrite
+//! the golden files after a deliberate change to the types
+use crate::ai::{AIModel, AIQuery, AIServerQuery, AIServerResponse, AIServerResult};
+use crate::ai::{AIStoreInfo, PreprocessAction, Usage, UsageStats};
+use crate::db::{DBQuery, ServerDBQuery, ServerResponse, ServerResult, StoreInfo, StoreUpsert};
+use crate::error::{ErrorCode, ErrorResponse};
+use crate::keyval::{StoreInput, StoreKey, StoreName, StoreValue};
+use crate::metadata::{MetadataKey, MetadataValue};
+use crate::predicate::{Predicate, PredicateCondition};
+use crate::similarity::{Algorithm, Similarity};
+use crate::{ErrorPolicy, Priority, RequestLimits};
+use ndarray::array;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Checks that `value` serializes to the golden file `name` and that the golden file
+/// deserializes back to `value`. Maps and sets in `value` hold a single entry at most so that
+/// their order is the same on every run
+fn assert_golden<T>(name: &str, value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tests/golden")
+        .join(format!("{name}.json"));
+    let json = serde_json::to_string_pretty(value).unwrap();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, format!("{json}\n")).unwrap();
+    }
+    let golden = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        serde_json::from_str::<serde_json::Value>(&golden).unwrap(),
+        "{name}.json no longer matches the JSON of the value"
+    );
+    assert_eq!(&serde_json::from_str::<T>(&golden).unwrap(), value);
+}
+
+fn metadata(key: &str, value: MetadataValue) -> StoreValue {
+    HashMap::from_iter([(MetadataKey::new(key.to_string()), value)])
+}
+
+#[test]
+fn test_db_query_golden() {
+    let mut query = ServerDBQuery::with_capacity_and_tracing_id(
+        6,
+        Some("00-80e1afed08e019fc1110464cfa66635c-7a085853722dc6d2-01".to_string()),
+    )
+    .unwrap();
+    query.set_error_policy(ErrorPolicy::FailFast);
+    query.set_timeout(Duration::from_millis(1500));
+    query.set_priority(Priority::Interactive);
+    query.push(DBQuery::Set {
+        store: StoreName("Main".to_string()),
+        inputs: vec![(
+            StoreKey(array![1.0, 0.5, -2.25]),
+            metadata("medal", MetadataValue::RawString("gold".to_string())),
+        )],
+    });
+    query.push(DBQuery::GetPred {
+        store: StoreName("Main".to_string()),
+        condition: Predicate::equals("medal", "gold")
+            .and(Predicate::in_("country", ["NG"]))
+            .or(Predicate::not_in(
+                "rank",
+                [MetadataValue::Image(vec![0, 255])],
+            )),
+    });
+    query.push(DBQuery::GetSimNByKey {
+        store: StoreName("Main".to_string()),
+        key_id: "13646096770106105413".to_string(),
+        closest_n: NonZeroUsize::new(3).unwrap(),
+        algorithm: Algorithm::CosineSimilarity,
+        condition: Some(Predicate::not_equals("medal", "bronze").into()),
+    });
+    query.push(DBQuery::BenchmarkStore {
+        store: StoreName("Main".to_string()),
+        sample_queries: NonZeroUsize::new(100).unwrap(),
+        concurrency: NonZeroUsize::new(4).unwrap(),
+        duration: 5000,
+    });
+    query.push(DBQuery::DropStore {
+        store: StoreName("Main".to_string()),
+        error_if_not_exists: true,
+    });
+    query.push(DBQuery::Ping);
+    assert_golden("db_query", &query);
+}
+
+#[test]
+fn test_db_result_golden() {
+    let mut result = ServerResult::with_capacity(5);
+    result.push(Ok(ServerResponse::Set(StoreUpsert {
+        inserted: 1,
+        updated: 0,
+    })));
+    result.push(Ok(ServerResponse::GetSimN(vec![(
+        StoreKey(array![1.0, 0.5, -2.25]),
+        metadata("medal", MetadataValue::RawString("gold".to_string())),
+        Similarity(0.875),
+    )])));
+    result.push(Ok(ServerResponse::StoreList(HashSet::from_iter([
+        StoreInfo {
+            name: StoreName("Main".to_string()),
+            len: 1,
+            size_in_bytes: 1056,
+            dimension: NonZeroUsize::new(3).unwrap(),
+            request_limits: RequestLimits {
+                message_size: 1048576,
+                batch_size: None,
+            },
+        },
+    ]))));
+    result.push(Ok(ServerResponse::Unit));
+    result.push(Err(ErrorResponse::new(
+        ErrorCode::StoreNotFound,
+        "Store \"Main\" not found",
+    )
+    .with_metadata("store", "Main")));
+    assert_golden("db_result", &result);
+}
+
+#[test]
+fn test_ai_query_golden() {
+    let mut query = AIServerQuery::with_capacity(4);
+    query.push(AIQuery::Set {
+        store: StoreName("Main".to_string()),
+        inputs: vec![
+            (
+                StoreInput::RawString("Adidas Yeezy".to_string()),
+                metadata("brand", MetadataValue::RawString("Adidas".to_string())),
+            ),
+            (
+                StoreInput::Multimodal {
+                    text: "Nike Air Jordans".to_string(),
+                    image: vec![137, 80, 78, 71],
+                },
+                HashMap::new(),
+            ),
+        ],
+        preprocess_action: PreprocessAction::ModelPreprocessing,
+    });
+    query.push(AIQuery::GetPred {
+        store: StoreName("Main".to_string()),
+        condition: PredicateCondition::Value(Predicate::Equals {
+            key: MetadataKey::new("brand".to_string()),
+            value: MetadataValue::RawString("Nike".to_string()),
+        }),
+        include_system_metadata: false,
+    });
+    query.push(AIQuery::Warmup {
+        stores: HashSet::from_iter([StoreName("Main".to_string())]),
+        models: HashSet::from_iter([AIModel::Custom("e5-small".to_string())]),
+    });
+    query.push(AIQuery::GetUsageStats { reset: true });
+    assert_golden("ai_query", &query);
+}
+
+#[test]
+fn test_ai_result_golden() {
+    let usage = Usage {
+        inputs: 2,
+        tokens: 9,
+        images: 1,
+        inference_ms: 31,
+    };
+    let mut result = AIServerResult::with_capacity(4);
+    result.push(Ok(AIServerResponse::Get(vec![
+        (
+            Some(StoreInput::RawString("Adidas Yeezy".to_string())),
+            metadata("brand", MetadataValue::RawString("Adidas".to_string())),
+        ),
+        (None, HashMap::new()),
+    ])));
+    result.push(Ok(AIServerResponse::StoreList(HashSet::from_iter([
+        AIStoreInfo {
+            name: StoreName("Main".to_string()),
+            query_model: AIModel::AllMiniLML6V2,
+            index_model: AIModel::AllMiniLML6V2,
+            embedding_size: 384,
+            request_limits: RequestLimits {
+                message_size: 1048576,
+                batch_size: Some(500),
+            },
+        },
+    ]))));
+    // custom models used to fail to serialize as the keys of a JSON map
+    result.push(Ok(AIServerResponse::UsageStats(UsageStats {
+        stores: HashMap::from_iter([(StoreName("Main".to_string()), usage)]),
+        clients: HashMap::from_iter([("127.0.0.1".to_string(), usage)]),
+        models: HashMap::from_iter([(AIModel::Custom("e5-small".to_string()), usage)]),
+    })));
+    result.push(Err(ErrorResponse::new(
+        ErrorCode::ModelError,
+        "Model \"e5-small\" is not supported",
+    )));
+    assert_golden("ai_result", &result);
+}
@@ -0,0 +1,2 @@
+mod json_test;
+mod wire_test;
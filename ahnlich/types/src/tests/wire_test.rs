@@ -0,0 +1,149 @@
+//! Messages encoded by clients and servers on 0.0.0, guarding the positions of the variants of
+//! queries and responses. Variants are only ever appended, so those that kept their layout since
+//! still decode as themselves
+use crate::ai::AIQuery;
+use crate::bincode::{BinCodeSerAndDeser, LENGTH_HEADER_SIZE, MAGIC_BYTES, RESPONSE_HEADER_LEN};
+use crate::db::{DBQuery, ServerResponse, ServerResult};
+use crate::keyval::{StoreInput, StoreKey, StoreName, StoreValue};
+use crate::metadata::{MetadataKey, MetadataValue};
+use crate::predicate::{Predicate, PredicateCondition};
+use crate::version::{Version, MIN_CLIENT_VERSION, VERSION};
+use bincode::config::{DefaultOptions, Options};
+use ndarray::array;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+
+const OLD_VERSION: Version = Version {
+    major: 0,
+    minor: 0,
+    patch: 0,
+};
+
+/// Checks the header of a message encoded on 0.0.0 and returns its body
+fn old_body(message: &[u8]) -> &[u8] {
+    assert_eq!(&message[..MAGIC_BYTES.len()], MAGIC_BYTES);
+    let version = Version::deserialize_magic_bytes(
+        &message[MAGIC_BYTES.len()..RESPONSE_HEADER_LEN - LENGTH_HEADER_SIZE],
+    )
+    .unwrap();
+    assert_eq!(version, OLD_VERSION);
+    let length = u64::from_le_bytes(
+        message[RESPONSE_HEADER_LEN - LENGTH_HEADER_SIZE..RESPONSE_HEADER_LEN]
+            .try_into()
+            .unwrap(),
+    );
+    assert_eq!(length as usize, message.len() - RESPONSE_HEADER_LEN);
+    &message[RESPONSE_HEADER_LEN..]
+}
+
+/// Queries on 0.0.0 were followed by the trace id alone
+fn old_queries<Q: DeserializeOwned>(message: &[u8]) -> Vec<Q> {
+    let config = DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian();
+    let (queries, trace_id): (Vec<Q>, Option<String>) =
+        config.deserialize(old_body(message)).unwrap();
+    assert_eq!(trace_id, None);
+    queries
+}
+
+fn medal() -> StoreValue {
+    HashMap::from_iter([(
+        MetadataKey::new("medal".to_string()),
+        MetadataValue::RawString("gold".to_string()),
+    )])
+}
+
+#[test]
+fn test_clients_before_the_layout_changed_are_rejected() {
+    assert!(OLD_VERSION < *MIN_CLIENT_VERSION);
+    assert!(!VERSION.is_compatible(&OLD_VERSION));
+    assert!(VERSION.is_compatible(&MIN_CLIENT_VERSION));
+}
+
+#[test]
+fn test_old_db_queries_keep_their_variants() {
+    let store = StoreName("Main".to_string());
+    let key = StoreKey(array![1.0, 0.5, -2.25]);
+    let condition = PredicateCondition::Value(Predicate::Equals {
+        key: MetadataKey::new("medal".to_string()),
+        value: MetadataValue::RawString("gold".to_string()),
+    });
+    let queries: Vec<DBQuery> = old_queries(include_bytes!("golden/db_query_0.0.0.bin"));
+    assert_eq!(
+        queries,
+        vec![
+            DBQuery::Ping,
+            DBQuery::ListStores,
+            DBQuery::GetKey {
+                store: store.clone(),
+                keys: vec![key.clone()],
+            },
+            DBQuery::GetPred {
+                store: store.clone(),
+                condition: condition.clone(),
+            },
+            DBQuery::CreatePredIndex {
+                store: store.clone(),
+                predicates: HashSet::from_iter([MetadataKey::new("medal".to_string())]),
+            },
+            DBQuery::Set {
+                store: store.clone(),
+                inputs: vec![(key.clone(), medal())],
+            },
+            DBQuery::DelKey {
+                store: store.clone(),
+                keys: vec![key],
+            },
+            DBQuery::DelPred {
+                store: store.clone(),
+                condition,
+            },
+            DBQuery::DropStore {
+                store,
+                error_if_not_exists: true,
+            },
+            DBQuery::InfoServer,
+            DBQuery::ListClients,
+        ]
+    );
+}
+
+#[test]
+fn test_old_ai_queries_keep_their_variants() {
+    let store = StoreName("Main".to_string());
+    let queries: Vec<AIQuery> = old_queries(include_bytes!("golden/ai_query_0.0.0.bin"));
+    assert_eq!(
+        queries,
+        vec![
+            AIQuery::Ping,
+            AIQuery::ListStores,
+            AIQuery::DelKey {
+                store: store.clone(),
+                key: StoreInput::RawString("Jordan One".to_string()),
+            },
+            AIQuery::DropStore {
+                store,
+                error_if_not_exists: true,
+            },
+            AIQuery::PurgeStores,
+            AIQuery::InfoServer,
+            AIQuery::ListClients,
+        ]
+    );
+}
+
+#[test]
+fn test_old_db_responses_keep_their_variants() {
+    let result =
+        ServerResult::deserialize(old_body(include_bytes!("golden/db_result_0.0.0.bin"))).unwrap();
+    let mut expected = ServerResult::with_capacity(4);
+    expected.push(Ok(ServerResponse::Pong));
+    expected.push(Ok(ServerResponse::Get(vec![(
+        StoreKey(array![1.0, 0.5, -2.25]),
+        medal(),
+    )])));
+    expected.push(Ok(ServerResponse::Del(1)));
+    expected.push(Ok(ServerResponse::CreateIndex(1)));
+    assert_eq!(result, expected);
+}
@@ -3,6 +3,7 @@ use bincode::config::Options;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 use serde::Serialize;
+use std::fmt;
 
 pub static VERSION: Lazy<Version> = Lazy::new(|| {
     let version_string: &str = env!("CARGO_PKG_VERSION");
@@ -23,6 +24,18 @@ pub static VERSION: Lazy<Version> = Lazy::new(|| {
     .unwrap_or_else(|| panic!("Could not parse CARGO_PKG_VERSION into Version"))
 });
 
+/// Oldest client version a server services. Older minor and patch versions within the same major
+/// version are accepted so that clients can be upgraded independently of servers. Queries and
+/// responses are encoded positionally, so a release may only append variants to them. A release
+/// that changes the layout of an existing variant or struct moves this up to itself, as older
+/// clients would be decoded as something else. 0.1.0 added fields to GETSIMN and CREATESTORE
+/// among others
+pub static MIN_CLIENT_VERSION: Lazy<Version> = Lazy::new(|| Version {
+    major: 0,
+    minor: 1,
+    patch: 0,
+});
+
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Version {
     pub major: u8,
@@ -38,9 +51,72 @@ impl Version {
         config.deserialize(bytes)
     }
 
-    /// what versions are compatible. For now we assume that the versions should always be exact
-    /// but ultimately we want major versions being the same to be enough
+    /// whether a server on this version can service a client on `other`. The client has to share
+    /// the same major version, must not be older than MIN_CLIENT_VERSION and must not be newer
+    /// than the server, as a newer client may send queries the server does not understand
     pub fn is_compatible(&self, other: &Self) -> bool {
-        self == other
+        self.major == other.major && *MIN_CLIENT_VERSION <= *other && other <= self
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_older_minor_and_patch_versions_are_compatible() {
+        let server = Version {
+            major: 1,
+            minor: 4,
+            patch: 2,
+        };
+        for client in [
+            server,
+            Version {
+                major: 1,
+                minor: 4,
+                patch: 0,
+            },
+            Version {
+                major: 1,
+                minor: 0,
+                patch: 9,
+            },
+        ] {
+            assert!(server.is_compatible(&client), "{client} should be accepted");
+        }
+        for client in [
+            Version {
+                major: 1,
+                minor: 5,
+                patch: 0,
+            },
+            Version {
+                major: 1,
+                minor: 4,
+                patch: 3,
+            },
+            Version {
+                major: 0,
+                minor: 4,
+                patch: 2,
+            },
+            Version {
+                major: 2,
+                minor: 0,
+                patch: 0,
+            },
+        ] {
+            assert!(
+                !server.is_compatible(&client),
+                "{client} should be rejected"
+            );
+        }
     }
 }
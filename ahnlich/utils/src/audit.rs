@@ -0,0 +1,263 @@
+use crate::cli::CommandLineConfig;
+use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::error::ErrorResponse;
+use ahnlich_types::keyval::StoreName;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Kinds of operations the audit log records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditCategory {
+    /// Creating and dropping stores and indices along with other operations on the server
+    Admin,
+    /// Adding and removing the entries of stores
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// An operation to record in the audit log, named after its command e.g `DROPSTORE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditOperation {
+    pub category: AuditCategory,
+    pub name: &'static str,
+    // stores the operation acts on, empty for operations on the server
+    pub stores: Vec<StoreName>,
+}
+
+impl AuditOperation {
+    pub fn admin(name: &'static str, stores: impl IntoIterator<Item = StoreName>) -> Self {
+        Self {
+            category: AuditCategory::Admin,
+            name,
+            stores: stores.into_iter().collect(),
+        }
+    }
+
+    pub fn write(name: &'static str, store: StoreName) -> Self {
+        Self {
+            category: AuditCategory::Write,
+            name,
+            stores: vec![store],
+        }
+    }
+}
+
+/// A line of the audit log
+#[derive(Debug, Serialize)]
+struct AuditRecord<'a> {
+    // milliseconds since the unix epoch
+    timestamp: u64,
+    client: &'a str,
+    category: AuditCategory,
+    operation: &'static str,
+    stores: &'a [StoreName],
+    outcome: AuditOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+#[derive(Debug)]
+struct AuditFile {
+    file: File,
+    size: u64,
+}
+
+/// Appends a json line for every recorded operation to a file that is only ever appended to.
+/// Once the file grows past `max_size` it is rotated to `<file>.1`, pushing older logs up to
+/// `<file>.<max_files>` after which they are removed
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    categories: HashSet<AuditCategory>,
+    // operations on other stores are not recorded, every store when empty
+    stores: HashSet<StoreName>,
+    file: Mutex<AuditFile>,
+}
+
+impl AuditLog {
+    /// Opens the audit log of the config, None when the server is not configured to keep one
+    pub fn open(config: &CommandLineConfig) -> io::Result<Option<Self>> {
+        let Some(path) = &config.audit_log else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            path: path.clone(),
+            max_size: config.audit_log_max_size,
+            max_files: config.audit_log_max_files,
+            categories: config.audit_categories.iter().copied().collect(),
+            stores: config
+                .audit_stores
+                .iter()
+                .map(|store| StoreName(store.clone()))
+                .collect(),
+            file: Mutex::new(Self::open_file(path)?),
+        }))
+    }
+
+    fn open_file(path: &Path) -> io::Result<AuditFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(AuditFile { file, size })
+    }
+
+    /// Whether an operation passes the filters of the log. Operations on the server rather than
+    /// on stores pass the store filter
+    fn records(&self, operation: &AuditOperation) -> bool {
+        self.categories.contains(&operation.category)
+            && (self.stores.is_empty()
+                || operation.stores.is_empty()
+                || operation
+                    .stores
+                    .iter()
+                    .any(|store| self.stores.contains(store)))
+    }
+
+    /// Records an operation of a client along with its outcome. Failing to write the record is
+    /// logged rather than failing the operation
+    pub fn record(
+        &self,
+        client: &ConnectedClient,
+        operation: AuditOperation,
+        outcome: Result<(), &ErrorResponse>,
+    ) {
+        if !self.records(&operation) {
+            return;
+        }
+        let record = AuditRecord {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as u64)
+                .unwrap_or_default(),
+            client: &client.address,
+            category: operation.category,
+            operation: operation.name,
+            stores: &operation.stores,
+            outcome: match outcome {
+                Ok(()) => AuditOutcome::Success,
+                Err(_) => AuditOutcome::Failure,
+            },
+            error: outcome.err().map(|error| error.message.as_str()),
+        };
+        if let Err(err) = self.append(&record) {
+            log::error!("Could not write to audit log {err}");
+        }
+    }
+
+    fn append(&self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut audit_file = self.file.lock().expect("audit log lock poisoned");
+        if audit_file.size > 0 && audit_file.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+            *audit_file = Self::open_file(&self.path)?;
+        }
+        audit_file.file.write_all(&line)?;
+        audit_file.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> ConnectedClient {
+        ConnectedClient {
+            address: "127.0.0.1:5000".to_string(),
+            time_connected: SystemTime::now(),
+        }
+    }
+
+    fn lines(path: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_log_filters_and_rotates() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("audit.log");
+        let config = CommandLineConfig {
+            audit_log: Some(path.clone()),
+            audit_categories: vec![AuditCategory::Admin],
+            audit_stores: vec!["Main".to_string()],
+            audit_log_max_size: 400,
+            audit_log_max_files: 2,
+            ..Default::default()
+        };
+        let audit_log = AuditLog::open(&config).unwrap().unwrap();
+        let main = StoreName("Main".to_string());
+
+        audit_log.record(
+            &client(),
+            AuditOperation::write("SET", main.clone()),
+            Ok(()),
+        );
+        audit_log.record(
+            &client(),
+            AuditOperation::admin("DROPSTORE", [StoreName("Other".to_string())]),
+            Ok(()),
+        );
+        let error = ErrorResponse::new(
+            ahnlich_types::error::ErrorCode::StoreNotFound,
+            "Store Main not found",
+        );
+        audit_log.record(
+            &client(),
+            AuditOperation::admin("DROPSTORE", [main.clone()]),
+            Err(&error),
+        );
+        let recorded = lines(&path);
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0]["operation"], "DROPSTORE");
+        assert_eq!(recorded[0]["client"], "127.0.0.1:5000");
+        assert_eq!(recorded[0]["stores"], serde_json::json!(["Main"]));
+        assert_eq!(recorded[0]["outcome"], "failure");
+        assert_eq!(recorded[0]["error"], "Store Main not found");
+
+        for _ in 0..10 {
+            audit_log.record(&client(), AuditOperation::admin("PURGESTORES", []), Ok(()));
+        }
+        assert!(audit_log.rotated_path(1).exists());
+        assert!(audit_log.rotated_path(2).exists());
+        assert!(!audit_log.rotated_path(3).exists());
+        assert!(std::fs::metadata(&path).unwrap().len() <= 400);
+    }
+}
@@ -1,9 +1,44 @@
+use crate::audit::AuditCategory;
+use crate::encryption::{key_provider, EncryptionError, KeyProvider};
+use crate::filters::ClientFilter;
+use crate::limits::LimitOverride;
+use crate::persistence::{AhnlichPersistenceUtils, Persistence, PersistenceTaskError};
+use crate::scheduler::Scheduler;
 use clap::{ArgAction, Args};
-use std::sync::OnceLock;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+use tracer::{LogFormat, SamplingConfig};
 
 static DEFAULT_CONFIG: OnceLock<CommandLineConfig> = OnceLock::new();
 const MIN_ALLOCATION_SIZE: usize = 10 * 1024 * 1024; // 10mb
 
+/// Setting rejected when a server configuration is built programmatically, where clap does not
+/// get to validate it
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    #[error("Invalid {setting}, {reason}")]
+    Invalid {
+        setting: &'static str,
+        reason: String,
+    },
+    #[error("{setting} requires {requires} to be set")]
+    Missing {
+        setting: &'static str,
+        requires: &'static str,
+    },
+}
+
+impl ConfigError {
+    pub fn invalid(setting: &'static str, reason: impl Into<String>) -> Self {
+        Self::Invalid {
+            setting,
+            reason: reason.into(),
+        }
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct CommandLineConfig {
     /// Host
@@ -11,6 +46,11 @@ pub struct CommandLineConfig {
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).host.clone())]
     pub host: String,
 
+    /// Unix domain socket to serve clients over alongside the host and port. A socket left
+    /// behind by a server that did not shut down cleanly is replaced
+    #[arg(long)]
+    pub unix_socket: Option<PathBuf>,
+
     /// Allows server to persist data to disk on occassion
     #[arg(long, action=ArgAction::SetTrue, default_value_t =
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).enable_persistence.clone())]
@@ -44,6 +84,28 @@ pub struct CommandLineConfig {
     #[arg(long, default_value_t =
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).message_size.clone())]
     pub message_size: usize,
+
+    /// limits the number of entries a single set can hold, unbounded by default
+    #[arg(long)]
+    pub batch_size: Option<usize>,
+
+    /// overrides `message_size` and `batch_size` for a client host, can be repeated
+    /// e.g --client-limit 10.0.0.5=4194304,1000
+    #[arg(long = "client-limit", value_name = "HOST=MESSAGE_SIZE[,BATCH_SIZE]")]
+    pub client_limits: Vec<LimitOverride>,
+
+    /// overrides `message_size` and `batch_size` for sets into a store, can be repeated
+    /// e.g --store-limit Main=4194304,1000
+    #[arg(long = "store-limit", value_name = "STORE=MESSAGE_SIZE[,BATCH_SIZE]")]
+    pub store_limits: Vec<LimitOverride>,
+
+    /// holds a client host to a predicate in a store, or in every store with `*`, which is ANDed
+    /// with the conditions of its gets, similarity searches and deletes, though the database
+    /// refuses its deletes by key as mirrors could not be held to the filter. Can be repeated e.g
+    /// --client-filter 10.0.0.5=articles:tenant_id=acme
+    #[arg(long = "client-filter", value_name = "HOST=STORE:KEY=VALUE")]
+    pub client_filters: Vec<ClientFilter>,
+
     /// Allows enables tracing
     #[arg(long, action=ArgAction::SetTrue, default_value_t =
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).enable_tracing.clone())]
@@ -52,22 +114,119 @@ pub struct CommandLineConfig {
     #[arg(long, requires_if("true", "enable_tracing"))]
     pub otel_endpoint: Option<String>,
 
+    /// Share of the requests that are traced, from 0 to 1
+    #[arg(long, env = "AHNLICH_TRACE_SAMPLE_RATIO", value_parser = validate_sample_ratio, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).trace_sample_ratio)]
+    pub trace_sample_ratio: f64,
+
+    /// Traces requests whose client sent a sampled traceparent and skips those it did not
+    /// sample, regardless of the sample ratio
+    #[arg(long, env = "AHNLICH_TRACE_PARENT_BASED", action = ArgAction::Set, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).trace_parent_based)]
+    pub trace_parent_based: bool,
+
+    /// Most requests traced every second
+    #[arg(long, env = "AHNLICH_TRACE_RATE_LIMIT")]
+    pub trace_rate_limit: Option<u32>,
+
     ///  Log level
     #[arg(long, default_value_t =
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).log_level.clone())]
     pub log_level: String,
 
+    ///  Format of the log lines, json lines carry the request id and client of each request
+    #[arg(long, value_enum, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).log_format)]
+    pub log_format: LogFormat,
+
     ///  Maximum client connections allowed
     ///  Defaults to 1000
     #[arg(long, default_value_t =
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).maximum_clients.clone())]
     pub maximum_clients: usize,
 
+    ///  Most client connections allowed from a single host, past which its connections are
+    ///  rejected. Unbounded by default
+    #[arg(long)]
+    pub maximum_clients_per_host: Option<usize>,
+
+    ///  Most requests processed at once, past which requests wait for their turn by priority
+    ///  Unbounded by default
+    #[arg(long)]
+    pub max_concurrent_requests: Option<usize>,
+
+    ///  Milliseconds a request waits before it goes ahead of those of a higher priority
+    ///  Defaults to 1000
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).priority_aging)]
+    pub priority_aging: u64,
+
     ///  CPU threadpool size
     ///  Defaults to 16
     #[arg(long, default_value_t =
     DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).threadpool_size.clone())]
     pub threadpool_size: usize,
+
+    ///  Seconds a finished background job is kept around for polling before it is forgotten
+    ///  Defaults to 3600 (1 hour)
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).job_ttl.clone())]
+    pub job_ttl: u64,
+
+    /// Serves a HTTP/JSON gateway on `http_port` for clients that cannot speak the ahnlich
+    /// protocol
+    #[arg(long, action=ArgAction::SetTrue, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).enable_http_gateway.clone())]
+    pub enable_http_gateway: bool,
+
+    /// Notifies systemd over `NOTIFY_SOCKET` once the server is ready to serve, after loading its
+    /// persisted stores and models, and again as it stops. For services of `Type=notify`
+    #[arg(long, action=ArgAction::SetTrue, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).systemd_notify.clone())]
+    pub systemd_notify: bool,
+
+    /// TOML file of settings applied over their flags, keyed by flag name e.g
+    /// `log-level = "debug"`. Only the settings that can change while the server runs are
+    /// accepted, and they are re-applied on SIGHUP or a ReloadConfig query
+    #[arg(long)]
+    pub config_file: Option<std::path::PathBuf>,
+
+    /// Appends a json line to this file for every admin and write operation, recording the
+    /// client, operation, stores, time and outcome
+    #[arg(long)]
+    pub audit_log: Option<std::path::PathBuf>,
+
+    /// Kinds of operations recorded in the audit log, can be repeated
+    #[arg(long = "audit-category", value_enum, default_values_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).audit_categories.clone())]
+    pub audit_categories: Vec<AuditCategory>,
+
+    /// Only records operations on these stores in the audit log, can be repeated. Operations on
+    /// the server itself are always recorded
+    #[arg(long = "audit-store", value_name = "STORE")]
+    pub audit_stores: Vec<String>,
+
+    /// Size in bytes the audit log grows to before it is rotated
+    /// Defaults to 100MiB (100 * 1024 * 1024)
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).audit_log_max_size)]
+    pub audit_log_max_size: u64,
+
+    /// Number of rotated audit logs kept next to the audit log
+    #[arg(long, default_value_t =
+    DEFAULT_CONFIG.get_or_init(CommandLineConfig::default).audit_log_max_files)]
+    pub audit_log_max_files: usize,
+
+    /// Encrypts snapshots with AES-256-GCM using keys from this file, written as
+    /// `<id>:<64 hex characters>` separated by commas or lines with the current key first. The
+    /// file is read again on every persist so keys are rotated by adding one to the top
+    #[arg(long, conflicts_with = "encryption_key_env")]
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// Encrypts snapshots with keys from this environment variable, in the format of
+    /// `encryption_key_file`
+    #[arg(long)]
+    pub encryption_key_env: Option<String>,
 }
 
 impl Default for CommandLineConfig {
@@ -80,16 +239,107 @@ impl Default for CommandLineConfig {
             persistence_interval: 1000 * 60 * 5,
             allocator_size: 1_073_741_824,
             message_size: 1_048_576,
+            batch_size: None,
+            client_limits: vec![],
+            store_limits: vec![],
+            client_filters: vec![],
 
             enable_tracing: false,
             otel_endpoint: None,
+            trace_sample_ratio: 1.0,
+            trace_parent_based: true,
+            trace_rate_limit: None,
             log_level: String::from("info,hf_hub=warn"),
+            log_format: LogFormat::Text,
             maximum_clients: 1000,
+            maximum_clients_per_host: None,
+            unix_socket: None,
+            max_concurrent_requests: None,
+            priority_aging: 1000,
             threadpool_size: 16,
+            job_ttl: 60 * 60,
+            enable_http_gateway: false,
+            systemd_notify: false,
+            config_file: None,
+            audit_log: None,
+            audit_categories: vec![AuditCategory::Admin, AuditCategory::Write],
+            audit_stores: vec![],
+            audit_log_max_size: 100 * 1024 * 1024,
+            audit_log_max_files: 5,
+            encryption_key_file: None,
+            encryption_key_env: None,
+        }
+    }
+}
+
+impl CommandLineConfig {
+    /// Checks the settings clap would otherwise validate when they are parsed from flags
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        validate_allocator_size(&self.allocator_size.to_string())
+            .map_err(|err| ConfigError::invalid("allocator_size", err))?;
+        validate_sample_ratio(&self.trace_sample_ratio.to_string())
+            .map_err(|err| ConfigError::invalid("trace_sample_ratio", err))?;
+        if self.enable_persistence {
+            if self.persist_location.is_none() {
+                return Err(ConfigError::Missing {
+                    setting: "enable_persistence",
+                    requires: "persist_location",
+                });
+            }
+            validate_persistence(self.allocator_size, self.persist_location.as_ref())
+                .map_err(|err| ConfigError::invalid("persist_location", err))?;
+        }
+        if self.encryption_key_file.is_some() && self.encryption_key_env.is_some() {
+            return Err(ConfigError::invalid(
+                "encryption_key_file",
+                "it cannot be set along with encryption_key_env",
+            ));
+        }
+        for (setting, value) in [
+            ("message_size", self.message_size),
+            ("maximum_clients", self.maximum_clients),
+            ("threadpool_size", self.threadpool_size),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::invalid(setting, "it must be above 0"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Provider of the keys snapshots are encrypted with, None when they are not encrypted
+    pub fn key_provider(&self) -> Result<Option<Arc<dyn KeyProvider>>, EncryptionError> {
+        key_provider(
+            self.encryption_key_file.as_deref(),
+            self.encryption_key_env.as_deref(),
+        )
+    }
+
+    pub fn scheduler(&self) -> Scheduler {
+        Scheduler::new(
+            self.max_concurrent_requests,
+            Duration::from_millis(self.priority_aging),
+        )
+    }
+
+    pub fn sampling(&self) -> SamplingConfig {
+        SamplingConfig {
+            ratio: self.trace_sample_ratio,
+            parent_based: self.trace_parent_based,
+            rate_limit: self.trace_rate_limit,
         }
     }
 }
 
+fn validate_sample_ratio(val: &str) -> Result<f64, String> {
+    let ratio: f64 = val.parse::<f64>().map_err(|err| err.to_string())?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err("Sample ratio must be from 0 to 1".to_string())
+    }
+}
+
 fn validate_allocator_size(val: &str) -> Result<usize, String> {
     let size: usize = val.parse::<usize>().map_err(|err| err.to_string())?;
 
@@ -122,3 +372,57 @@ pub fn validate_persistence(
 
     Ok(())
 }
+
+#[derive(Args, Debug, Clone)]
+pub struct VerifyBackupArgs {
+    /// Snapshot to verify, such as a backup of the persist location
+    pub path: PathBuf,
+
+    /// Keys the snapshot may be encrypted with, see `encryption_key_file` of the server
+    #[arg(long, conflicts_with = "encryption_key_env")]
+    pub encryption_key_file: Option<PathBuf>,
+
+    /// Environment variable holding the keys the snapshot may be encrypted with
+    #[arg(long)]
+    pub encryption_key_env: Option<String>,
+}
+
+impl VerifyBackupArgs {
+    /// Loads the snapshot as a server would on startup without starting one, printing the
+    /// sections it holds. Exits with an error when the snapshot does not load
+    pub fn output<T: AhnlichPersistenceUtils>(&self) {
+        if let Err(err) = self.verify::<T>() {
+            eprintln!("{} is not a valid snapshot: {err}", self.path.display());
+            std::process::exit(1);
+        }
+    }
+
+    fn verify<T: AhnlichPersistenceUtils>(&self) -> Result<(), PersistenceTaskError> {
+        let key_provider = key_provider(
+            self.encryption_key_file.as_deref(),
+            self.encryption_key_env.as_deref(),
+        )?;
+        let summary =
+            Persistence::<T::PersistenceObject>::verify(&self.path, key_provider.as_deref())?;
+        if let Some(key_id) = &summary.key_id {
+            println!("{} is encrypted with key {key_id}", self.path.display());
+        }
+        if summary.version == 0 {
+            println!(
+                "{} is valid, it has no checksums as it was written before snapshots were checksummed",
+                self.path.display()
+            );
+            return Ok(());
+        }
+        println!(
+            "{} is valid, format version {} with {} sections",
+            self.path.display(),
+            summary.version,
+            summary.sections.len()
+        );
+        for (name, length) in summary.sections {
+            println!("  {name} ({length} bytes)");
+        }
+        Ok(())
+    }
+}
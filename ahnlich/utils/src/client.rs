@@ -1,55 +1,148 @@
-use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::client::{ConnectedClient, ConnectionMemory, ConnectionStats};
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
 use flurry::HashSet as ConcurrentHashSet;
-use std::collections::HashSet as StdHashSet;
-use std::net::SocketAddr;
+use std::collections::{HashMap as StdHashMap, HashSet as StdHashSet};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::time::SystemTime;
+use thiserror::Error;
+
+/// Capacity of the buffer each connection reads its requests through
+pub const CONNECTION_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Share of a connection limit past which a warning is logged as connections near it
+const NEAR_LIMIT_RATIO: f64 = 0.9;
+
+/// Stands for no limit on the clients of a host
+const UNBOUNDED: usize = usize::MAX;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionRejected {
+    #[error("Server has reached its maximum of {0} clients")]
+    MaximumClients(usize),
+    #[error("Host {host} has reached its maximum of {limit} clients")]
+    MaximumClientsPerHost { host: String, limit: usize },
+}
+
+impl From<ConnectionRejected> for ErrorResponse {
+    fn from(err: ConnectionRejected) -> Self {
+        ErrorResponse::new(ErrorCode::ResourceExhausted, err.to_string())
+    }
+}
 
 /// Datastructure to keep track of clients that have connected to a server while allowing limiting
-/// the maximum number
+/// the maximum number, overall and from a single host
 #[derive(Debug)]
 pub struct ClientHandler {
     clients: ConcurrentHashSet<ConnectedClient>,
-    maximum_clients: usize,
+    // limits can change as the configuration of the server is reloaded
+    maximum_clients: AtomicUsize,
+    // connections of each host, kept when unlimited so that a limit can be set later on
+    hosts: Mutex<StdHashMap<String, usize>>,
+    maximum_clients_per_host: AtomicUsize,
+    rejected: AtomicU64,
 }
 
 impl ClientHandler {
-    pub fn new(maximum_clients: usize) -> Self {
+    pub fn new(maximum_clients: usize, maximum_clients_per_host: Option<usize>) -> Self {
         Self {
             clients: ConcurrentHashSet::with_capacity(maximum_clients),
-            maximum_clients,
+            maximum_clients: AtomicUsize::new(maximum_clients),
+            hosts: Mutex::new(StdHashMap::new()),
+            maximum_clients_per_host: AtomicUsize::new(
+                maximum_clients_per_host.unwrap_or(UNBOUNDED),
+            ),
+            rejected: AtomicU64::new(0),
         }
     }
 
+    pub fn maximum_clients(&self) -> usize {
+        self.maximum_clients.load(Ordering::Relaxed)
+    }
+
+    pub fn maximum_clients_per_host(&self) -> Option<usize> {
+        Some(self.maximum_clients_per_host.load(Ordering::Relaxed))
+            .filter(|&limit| limit != UNBOUNDED)
+    }
+
+    /// Changes the limits on clients, leaving connected clients past them connected
+    pub fn set_limits(&self, maximum_clients: usize, maximum_clients_per_host: Option<usize>) {
+        self.maximum_clients
+            .store(maximum_clients, Ordering::Relaxed);
+        self.maximum_clients_per_host.store(
+            maximum_clients_per_host.unwrap_or(UNBOUNDED),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Admits a client connecting from `address`, rejecting it when the server or its host is at
+    /// their maximum clients
     #[tracing::instrument(skip(self))]
-    pub fn connect(&self, addr: SocketAddr) -> Option<ConnectedClient> {
+    pub fn connect(&self, address: String) -> Result<ConnectedClient, ConnectionRejected> {
         let pinned = self.clients.pin();
         log::debug!("Current client len {}", pinned.len());
-        if self.is_maxed_out() {
-            log::error!(
-                "Maximum clients count {} reached or exceeded with {}",
-                pinned.len(),
-                self.maximum_clients
-            );
-            return None;
-        };
         let client = ConnectedClient {
-            address: format!("{addr}"),
+            address,
             time_connected: SystemTime::now(),
         };
+        let maximum_clients = self.maximum_clients();
+        if self.is_maxed_out() {
+            return Err(self.reject(ConnectionRejected::MaximumClients(maximum_clients)));
+        };
+        {
+            let host = client.host();
+            let mut hosts = self.hosts.lock().expect("Client hosts lock poisoned");
+            let connected = hosts.entry(host.clone()).or_default();
+            if let Some(limit) = self.maximum_clients_per_host() {
+                if *connected >= limit {
+                    return Err(
+                        self.reject(ConnectionRejected::MaximumClientsPerHost { host, limit })
+                    );
+                }
+                if near_limit(*connected + 1, limit) {
+                    log::warn!(
+                        "Host {host} is nearing its maximum clients with {} of {limit}",
+                        *connected + 1
+                    );
+                }
+            }
+            *connected += 1;
+        }
         pinned.insert(client.clone());
-        Some(client)
+        if near_limit(pinned.len(), maximum_clients) {
+            log::warn!(
+                "Server is nearing its maximum clients with {} of {maximum_clients}",
+                pinned.len(),
+            );
+        }
+        Ok(client)
+    }
+
+    fn reject(&self, rejected: ConnectionRejected) -> ConnectionRejected {
+        log::error!("Rejected client connection: {rejected}");
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        rejected
     }
 
     #[tracing::instrument(skip(self))]
     pub fn disconnect(&self, client: &ConnectedClient) {
         let pinned = self.clients.pin();
-        pinned.remove(client);
+        if pinned.remove(client) {
+            let mut hosts = self.hosts.lock().expect("Client hosts lock poisoned");
+            let host = client.host();
+            if let Some(connected) = hosts.get_mut(&host) {
+                *connected = connected.saturating_sub(1);
+                if *connected == 0 {
+                    hosts.remove(&host);
+                }
+            }
+        }
     }
 
     #[tracing::instrument(skip(self))]
     pub fn is_maxed_out(&self) -> bool {
         let pinned = self.clients.pin();
-        if pinned.len() >= self.maximum_clients {
+        if pinned.len() >= self.maximum_clients() {
             return true;
         }
         false
@@ -60,4 +153,31 @@ impl ClientHandler {
         let pinned = self.clients.pin();
         pinned.into_iter().cloned().collect()
     }
+
+    /// The read buffers held by the connected clients
+    #[tracing::instrument(skip(self))]
+    pub fn memory(&self) -> ConnectionMemory {
+        let clients = self.clients.pin().len();
+        ConnectionMemory {
+            clients,
+            buffers: clients * CONNECTION_BUFFER_SIZE,
+        }
+    }
+
+    /// How close the server is to its limits on clients and how many it has rejected
+    #[tracing::instrument(skip(self))]
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            clients: self.clients.pin().len(),
+            maximum_clients: self.maximum_clients(),
+            maximum_clients_per_host: self.maximum_clients_per_host(),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Whether `connected` has just crossed the share of `limit` warned about, so that the warning
+/// is logged once on the way up rather than for every connection past it
+fn near_limit(connected: usize, limit: usize) -> bool {
+    connected == (limit as f64 * NEAR_LIMIT_RATIO).ceil() as usize
 }
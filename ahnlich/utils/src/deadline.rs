@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Entries a scan goes through between checks of its deadline
+const CHECK_INTERVAL: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Deadline of the request passed")]
+pub struct DeadlineExceeded;
+
+/// When the client of a request stops waiting for it, after which the work left on the request
+/// is abandoned. The default deadline never passes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Deadline `timeout` from now, one that never passes when there is no timeout
+    pub fn after(timeout: Option<Duration>) -> Self {
+        Self(timeout.and_then(|timeout| Instant::now().checked_add(timeout)))
+    }
+
+    pub fn expired(&self) -> bool {
+        self.0.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub fn check(&self) -> Result<(), DeadlineExceeded> {
+        if self.expired() {
+            return Err(DeadlineExceeded);
+        }
+        Ok(())
+    }
+
+    /// Cuts a scan short once the deadline passes. The deadline is only checked every so many
+    /// entries, so callers check it again once the scan ends to tell a cut scan from a full one
+    pub fn bound<I: IntoIterator>(self, iter: I) -> impl Iterator<Item = I::Item> {
+        let mut scanned = 0usize;
+        iter.into_iter().take_while(move |_| {
+            scanned += 1;
+            scanned % CHECK_INTERVAL != 0 || !self.expired()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline() {
+        assert!(Deadline::default().check().is_ok());
+        assert_eq!(Deadline::default().bound(0..5000).count(), 5000);
+        let deadline = Deadline::after(Some(Duration::from_secs(60)));
+        assert!(deadline.check().is_ok());
+        let passed = Deadline::after(Some(Duration::ZERO));
+        assert_eq!(passed.check(), Err(DeadlineExceeded));
+        assert_eq!(passed.bound(0..5000).count(), CHECK_INTERVAL - 1);
+    }
+}
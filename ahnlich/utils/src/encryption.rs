@@ -0,0 +1,488 @@
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// First line of an encrypted snapshot
+pub(crate) const ENCRYPTED_MAGIC: &[u8] = b"ahnlich-encrypted ";
+
+/// Version of the encrypted format that is written
+const ENCRYPTED_VERSION: u32 = 1;
+
+/// Plaintext sealed at once, so that snapshots are encrypted without holding them in memory
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+const TAG_LEN: usize = 16;
+
+// the nonce of a chunk is a random prefix of the snapshot followed by the index of the chunk
+// and whether it is the last chunk, so chunks cannot be reordered or cut off unnoticed. Every
+// chunk is written as the last flag, its length and then the sealed chunk
+const NONCE_PREFIX_LEN: usize = NONCE_LEN - 5;
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("Could not read encryption keys {0}")]
+    KeysUnavailable(String),
+    #[error("Encryption keys are invalid, {0}. Keys are `<id>:<64 hex characters>` separated by commas or lines with the current key first")]
+    InvalidKeys(String),
+    #[error("Snapshot is encrypted with key {0}, start with a key provider holding the key")]
+    MissingKeyProvider(String),
+    #[error("Snapshot is encrypted with key {0} which is not among the encryption keys, add it back until the snapshot is persisted with the current key")]
+    UnknownKey(String),
+    #[error("Snapshot could not be decrypted with key {0}, the key is not the one it was encrypted with or the snapshot is corrupted")]
+    DecryptionFailed(String),
+    #[error("Encrypted snapshot has an invalid header")]
+    InvalidHeader,
+    #[error("Unsupported encrypted snapshot version {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// A 256 bit AES-GCM key along with the id snapshots encrypted with it record
+#[derive(Clone)]
+pub struct EncryptionKey {
+    pub id: String,
+    key: [u8; 32],
+}
+
+impl EncryptionKey {
+    pub fn new(id: String, key: [u8; 32]) -> Self {
+        Self { id, key }
+    }
+
+    fn sealing_key(&self) -> LessSafeKey {
+        LessSafeKey::new(
+            UnboundKey::new(&AES_256_GCM, &self.key).expect("AES-256-GCM keys are 32 bytes"),
+        )
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Source of the keys snapshots are encrypted with, such as a key management service. Keys are
+/// looked up whenever a snapshot is written or loaded so that rotated keys are picked up without
+/// a restart
+pub trait KeyProvider: fmt::Debug + Send + Sync {
+    /// Key new snapshots are encrypted with
+    fn current_key(&self) -> Result<EncryptionKey, EncryptionError>;
+
+    /// Key a snapshot was encrypted with. A rotated out key has to be kept until the snapshot
+    /// encrypted with it is persisted again with the current key
+    fn key(&self, id: &str) -> Result<EncryptionKey, EncryptionError>;
+}
+
+/// Parses keys as `<id>:<64 hex characters>` separated by commas or lines, the first being the
+/// current key and the rest rotated out keys
+fn parse_keys(keys: &str) -> Result<Vec<EncryptionKey>, EncryptionError> {
+    let keys = keys
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            let (id, hex_key) = key
+                .split_once(':')
+                .ok_or_else(|| EncryptionError::InvalidKeys("a key has no id".to_string()))?;
+            let mut key = [0; 32];
+            hex::decode_to_slice(hex_key, &mut key).map_err(|_| {
+                EncryptionError::InvalidKeys(format!("key {id} is not 64 hex characters"))
+            })?;
+            Ok(EncryptionKey::new(id.to_string(), key))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err(EncryptionError::InvalidKeys(
+            "there are no keys".to_string(),
+        ));
+    }
+    Ok(keys)
+}
+
+fn find_key(keys: Vec<EncryptionKey>, id: &str) -> Result<EncryptionKey, EncryptionError> {
+    keys.into_iter()
+        .find(|key| key.id == id)
+        .ok_or_else(|| EncryptionError::UnknownKey(id.to_string()))
+}
+
+/// Reads keys from a file, which is read again every time so that a key is rotated by adding
+/// it to the top of the file
+#[derive(Debug)]
+pub struct FileKeyProvider {
+    path: PathBuf,
+}
+
+impl FileKeyProvider {
+    pub fn new(path: PathBuf) -> Result<Self, EncryptionError> {
+        let provider = Self { path };
+        provider.keys()?;
+        Ok(provider)
+    }
+
+    fn keys(&self) -> Result<Vec<EncryptionKey>, EncryptionError> {
+        let keys = std::fs::read_to_string(&self.path).map_err(|err| {
+            EncryptionError::KeysUnavailable(format!("{}: {err}", self.path.display()))
+        })?;
+        parse_keys(&keys)
+    }
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn current_key(&self) -> Result<EncryptionKey, EncryptionError> {
+        Ok(self.keys()?.swap_remove(0))
+    }
+
+    fn key(&self, id: &str) -> Result<EncryptionKey, EncryptionError> {
+        find_key(self.keys()?, id)
+    }
+}
+
+/// Keys read once from an environment variable
+#[derive(Debug)]
+pub struct EnvKeyProvider {
+    keys: Vec<EncryptionKey>,
+}
+
+impl EnvKeyProvider {
+    pub fn new(variable: &str) -> Result<Self, EncryptionError> {
+        let keys = std::env::var(variable)
+            .map_err(|err| EncryptionError::KeysUnavailable(format!("{variable}: {err}")))?;
+        Ok(Self {
+            keys: parse_keys(&keys)?,
+        })
+    }
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn current_key(&self) -> Result<EncryptionKey, EncryptionError> {
+        Ok(self.keys[0].clone())
+    }
+
+    fn key(&self, id: &str) -> Result<EncryptionKey, EncryptionError> {
+        find_key(self.keys.clone(), id)
+    }
+}
+
+/// The key provider of a key file or environment variable, None when snapshots are not
+/// encrypted
+pub fn key_provider(
+    key_file: Option<&Path>,
+    key_env: Option<&str>,
+) -> Result<Option<Arc<dyn KeyProvider>>, EncryptionError> {
+    Ok(match (key_file, key_env) {
+        (Some(path), _) => Some(Arc::new(FileKeyProvider::new(path.to_path_buf())?)),
+        (None, Some(variable)) => Some(Arc::new(EnvKeyProvider::new(variable)?)),
+        (None, None) => None,
+    })
+}
+
+/// Second line of an encrypted snapshot, also authenticated along with every chunk
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedHeader {
+    key_id: String,
+    nonce_prefix: String,
+}
+
+fn nonce(prefix: &[u8; NONCE_PREFIX_LEN], index: u32, last: bool) -> Nonce {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    Nonce::assume_unique_for_key(nonce)
+}
+
+/// Encrypts everything written to it in chunks, `finish` has to be called to seal the last one
+pub(crate) struct EncryptingWriter<W: Write> {
+    writer: W,
+    key: LessSafeKey,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    header: Vec<u8>,
+    index: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    pub(crate) fn new(mut writer: W, key: &EncryptionKey) -> io::Result<Self> {
+        let mut nonce_prefix = [0; NONCE_PREFIX_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce_prefix)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Could not generate a nonce"))?;
+        let header = serde_json::to_vec(&EncryptedHeader {
+            key_id: key.id.clone(),
+            nonce_prefix: hex::encode(nonce_prefix),
+        })?;
+        writer.write_all(ENCRYPTED_MAGIC)?;
+        writeln!(writer, "{ENCRYPTED_VERSION}")?;
+        writer.write_all(&header)?;
+        writer.write_all(b"\n")?;
+        Ok(Self {
+            writer,
+            key: key.sealing_key(),
+            nonce_prefix,
+            header,
+            index: 0,
+            buffer: Vec::with_capacity(CHUNK_SIZE + TAG_LEN),
+        })
+    }
+
+    fn seal(&mut self, last: bool) -> io::Result<()> {
+        self.key
+            .seal_in_place_append_tag(
+                nonce(&self.nonce_prefix, self.index, last),
+                Aad::from(&self.header),
+                &mut self.buffer,
+            )
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Could not encrypt snapshot"))?;
+        self.writer.write_all(&[last as u8])?;
+        self.writer
+            .write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.index = self.index.checked_add(1).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "Snapshot is too large to encrypt")
+        })?;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.seal(true)?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // a full chunk is only sealed once more is written, as the last chunk is sealed apart
+        if self.buffer.len() == CHUNK_SIZE {
+            self.seal(false)?;
+        }
+        let written = buf.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Decrypts a snapshot written by [`EncryptingWriter`] chunk by chunk
+pub(crate) struct DecryptingReader<R: Read> {
+    reader: R,
+    key: LessSafeKey,
+    key_id: String,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    header: Vec<u8>,
+    index: u32,
+    chunk: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: BufRead> DecryptingReader<R> {
+    /// Reads the header of an encrypted snapshot after its magic and decrypts the first chunk,
+    /// so that a wrong key is caught before anything is read
+    pub(crate) fn new(
+        mut reader: R,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> Result<Self, EncryptionError> {
+        let mut version = String::new();
+        reader
+            .read_line(&mut version)
+            .map_err(|_| EncryptionError::InvalidHeader)?;
+        let version = version
+            .trim_end()
+            .parse()
+            .map_err(|_| EncryptionError::InvalidHeader)?;
+        if version > ENCRYPTED_VERSION {
+            return Err(EncryptionError::UnsupportedVersion(version));
+        }
+        let mut header = vec![];
+        reader
+            .read_until(b'\n', &mut header)
+            .map_err(|_| EncryptionError::InvalidHeader)?;
+        if header.pop() != Some(b'\n') {
+            return Err(EncryptionError::InvalidHeader);
+        }
+        let EncryptedHeader {
+            key_id,
+            nonce_prefix,
+        } = serde_json::from_slice(&header).map_err(|_| EncryptionError::InvalidHeader)?;
+        let mut prefix = [0; NONCE_PREFIX_LEN];
+        hex::decode_to_slice(nonce_prefix, &mut prefix)
+            .map_err(|_| EncryptionError::InvalidHeader)?;
+        let key_provider =
+            key_provider.ok_or_else(|| EncryptionError::MissingKeyProvider(key_id.clone()))?;
+        let key = key_provider.key(&key_id)?;
+        let mut decrypting = Self {
+            reader,
+            key: key.sealing_key(),
+            key_id,
+            nonce_prefix: prefix,
+            header,
+            index: 0,
+            chunk: vec![],
+            position: 0,
+            done: false,
+        };
+        decrypting
+            .open_chunk()
+            .map_err(|_| EncryptionError::DecryptionFailed(decrypting.key_id.clone()))?;
+        Ok(decrypting)
+    }
+}
+
+impl<R: Read> DecryptingReader<R> {
+    pub(crate) fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    fn open_chunk(&mut self) -> io::Result<()> {
+        let mut framing = [0; 5];
+        self.reader.read_exact(&mut framing)?;
+        let last = framing[0] == 1;
+        let length = u32::from_le_bytes(framing[1..].try_into().expect("length is 4 bytes"));
+        // chunks are never written past this, a longer one is corrupted
+        if length as usize > CHUNK_SIZE + TAG_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                EncryptionError::DecryptionFailed(self.key_id.clone()),
+            ));
+        }
+        self.chunk.resize(length as usize, 0);
+        self.reader.read_exact(&mut self.chunk)?;
+        // a flipped last flag changes the nonce so the chunk no longer opens
+        let opened = self
+            .key
+            .open_in_place(
+                nonce(&self.nonce_prefix, self.index, last),
+                Aad::from(&self.header),
+                &mut self.chunk,
+            )
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    EncryptionError::DecryptionFailed(self.key_id.clone()),
+                )
+            })?
+            .len();
+        self.chunk.truncate(opened);
+        self.position = 0;
+        self.done = last;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.open_chunk()?;
+        }
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Keys(Vec<EncryptionKey>);
+
+    impl KeyProvider for Keys {
+        fn current_key(&self) -> Result<EncryptionKey, EncryptionError> {
+            Ok(self.0[0].clone())
+        }
+
+        fn key(&self, id: &str) -> Result<EncryptionKey, EncryptionError> {
+            find_key(self.0.clone(), id)
+        }
+    }
+
+    fn encrypt(data: &[u8], key: &EncryptionKey) -> Vec<u8> {
+        let mut encrypted = vec![];
+        let mut writer = EncryptingWriter::new(&mut encrypted, key).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap();
+        encrypted
+    }
+
+    fn decrypt(encrypted: &[u8], keys: &Keys) -> Result<Vec<u8>, EncryptionError> {
+        let mut reader = io::BufReader::new(&encrypted[ENCRYPTED_MAGIC.len()..]);
+        let mut decrypting = DecryptingReader::new(&mut reader, Some(keys))?;
+        let mut decrypted = vec![];
+        decrypting
+            .read_to_end(&mut decrypted)
+            .map_err(|_| EncryptionError::DecryptionFailed(decrypting.key_id().to_string()))?;
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let keys = parse_keys(&format!(
+            "new:{}, old:{}\n",
+            "ab".repeat(32),
+            "01".repeat(32)
+        ))
+        .unwrap();
+        assert_eq!(keys[0].id, "new");
+        assert_eq!(keys[1].key, [1; 32]);
+        assert!(matches!(
+            parse_keys(&format!("new:{}", "ab".repeat(31))),
+            Err(EncryptionError::InvalidKeys(_))
+        ));
+        assert!(matches!(
+            parse_keys(" , "),
+            Err(EncryptionError::InvalidKeys(_))
+        ));
+    }
+
+    #[test]
+    fn test_encryption_round_trip() {
+        let old = EncryptionKey::new("old".to_string(), [1; 32]);
+        let new = EncryptionKey::new("new".to_string(), [2; 32]);
+        // spans several chunks with a partial last one
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let encrypted = encrypt(&data, &old);
+        assert!(!encrypted.windows(64).any(|window| data[..64] == *window));
+
+        // rotated out keys still decrypt
+        let keys = Keys(vec![new.clone(), old.clone()]);
+        assert_eq!(decrypt(&encrypted, &keys).unwrap(), data);
+        assert_eq!(
+            decrypt(&encrypt(&[], &new), &keys).unwrap(),
+            Vec::<u8>::new()
+        );
+
+        assert!(matches!(
+            decrypt(&encrypted, &Keys(vec![new.clone()])),
+            Err(EncryptionError::UnknownKey(id)) if id == "old"
+        ));
+        let wrong = Keys(vec![EncryptionKey::new("old".to_string(), [3; 32])]);
+        assert!(matches!(
+            decrypt(&encrypted, &wrong),
+            Err(EncryptionError::DecryptionFailed(id)) if id == "old"
+        ));
+        // dropping the last chunk is caught
+        let cut = &encrypted[..encrypted.len() - 10 - TAG_LEN - 5];
+        assert!(decrypt(cut, &keys).is_err());
+        let mut tampered = encrypted.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(decrypt(&tampered, &keys).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::cli::CommandLineConfig;
+use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::keyval::StoreName;
+use ahnlich_types::metadata::{MetadataKey, MetadataValue};
+use ahnlich_types::predicate::{Predicate, PredicateCondition};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Predicate a client host is held to in a store, parsed from `HOST=STORE:KEY=VALUE`. A store of
+/// `*` holds the client to it in every store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientFilter {
+    pub host: String,
+    // None for every store
+    pub store: Option<StoreName>,
+    pub key: MetadataKey,
+    pub value: MetadataValue,
+}
+
+impl FromStr for ClientFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("Expected HOST=STORE:KEY=VALUE, got {s}");
+        let (host, filter) = s.split_once('=').ok_or_else(expected)?;
+        let (filter, value) = filter.rsplit_once('=').ok_or_else(expected)?;
+        let (store, key) = filter.rsplit_once(':').ok_or_else(expected)?;
+        if host.is_empty() || store.is_empty() || key.is_empty() {
+            return Err(expected());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            store: (store != "*").then(|| StoreName(store.to_string())),
+            key: MetadataKey::new(key.to_string()),
+            value: MetadataValue::RawString(value.to_string()),
+        })
+    }
+}
+
+/// Holds clients to the predicates they are given in each store, which are ANDed with the
+/// conditions of their queries so that they only read and delete the entries they are allowed to
+#[derive(Debug, Default)]
+pub struct FilterHandler {
+    // keyed by client host, all connections from a host are held to its filters
+    clients: HashMap<String, Vec<ClientFilter>>,
+}
+
+impl FilterHandler {
+    pub fn new(config: &CommandLineConfig) -> Self {
+        let mut clients: HashMap<_, Vec<_>> = HashMap::new();
+        for filter in &config.client_filters {
+            clients
+                .entry(filter.host.clone())
+                .or_default()
+                .push(filter.clone());
+        }
+        Self { clients }
+    }
+
+    fn defaults(&self, client: &ConnectedClient, store: &StoreName) -> Option<PredicateCondition> {
+        self.clients
+            .get(&client.host())
+            .into_iter()
+            .flatten()
+            .filter(|filter| filter.store.as_ref().map_or(true, |name| name == store))
+            .map(|filter| {
+                PredicateCondition::Value(Predicate::Equals {
+                    key: filter.key.clone(),
+                    value: filter.value.clone(),
+                })
+            })
+            .reduce(PredicateCondition::and)
+    }
+
+    /// The condition of a query by `client` on `store`, narrowed to the entries it is allowed
+    pub fn restrict(
+        &self,
+        client: &ConnectedClient,
+        store: &StoreName,
+        condition: PredicateCondition,
+    ) -> PredicateCondition {
+        match self.defaults(client, store) {
+            Some(defaults) => condition.and(defaults),
+            None => condition,
+        }
+    }
+
+    /// The optional condition of a query by `client` on `store`, narrowed to the entries it is
+    /// allowed
+    pub fn restrict_optional(
+        &self,
+        client: &ConnectedClient,
+        store: &StoreName,
+        condition: Option<PredicateCondition>,
+    ) -> Option<PredicateCondition> {
+        match condition {
+            Some(condition) => Some(self.restrict(client, store, condition)),
+            None => self.defaults(client, store),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_filter_handler() {
+        let filter: ClientFilter = "10.0.0.5=articles:tenant_id=acme".parse().unwrap();
+        assert_eq!(filter.store, Some(StoreName("articles".to_string())));
+        assert!("10.0.0.5=articles".parse::<ClientFilter>().is_err());
+
+        let config = CommandLineConfig {
+            client_filters: vec![filter, "10.0.0.5=*:region=eu".parse().unwrap()],
+            ..Default::default()
+        };
+        let handler = FilterHandler::new(&config);
+        let client = ConnectedClient {
+            address: "10.0.0.5:4000".to_string(),
+            time_connected: SystemTime::now(),
+        };
+        let equals = |key: &str, value: &str| {
+            PredicateCondition::Value(Predicate::Equals {
+                key: MetadataKey::new(key.to_string()),
+                value: MetadataValue::RawString(value.to_string()),
+            })
+        };
+        assert_eq!(
+            handler.restrict(
+                &client,
+                &StoreName("articles".to_string()),
+                equals("author", "jo")
+            ),
+            equals("author", "jo").and(equals("tenant_id", "acme").and(equals("region", "eu")))
+        );
+        assert_eq!(
+            handler.restrict_optional(&client, &StoreName("other".to_string()), None),
+            Some(equals("region", "eu"))
+        );
+        let other = ConnectedClient {
+            address: "10.0.0.6:4000".to_string(),
+            time_connected: SystemTime::now(),
+        };
+        assert_eq!(
+            handler.restrict_optional(&other, &StoreName("articles".to_string()), None),
+            None
+        );
+    }
+}
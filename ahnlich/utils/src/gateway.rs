@@ -0,0 +1,190 @@
+//! HTTP/JSON gateway for tools that cannot speak the bincode protocol. Requests are transcoded
+//! into queries and forwarded to the server's own listener, so they go through the same handling,
+//! tracing and limits as queries from any other client
+use ahnlich_types::bincode::{
+    BinCodeSerAndDeser, BincodeSerError, LENGTH_HEADER_SIZE, RESPONSE_HEADER_LEN,
+};
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use fallible_collections::TryReserveError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Result as IoResult;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use task_manager::Task;
+use task_manager::TaskState;
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[derive(Error, Debug)]
+pub enum GatewayError {
+    #[error("std io error {0}")]
+    Standard(#[from] std::io::Error),
+    #[error("{0}")]
+    BinCodeSerAndDeser(#[from] BincodeSerError),
+    #[error("bincode deserialize error {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("Invalid request body {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("allocation error {0:?}")]
+    Allocation(TryReserveError),
+    #[error("empty response")]
+    EmptyResponse,
+}
+
+impl IntoResponse for GatewayError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            GatewayError::Json(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidArgument),
+            _ => (StatusCode::BAD_GATEWAY, ErrorCode::Unavailable),
+        };
+        json_response(status, &ErrorResponse::new(code, self))
+    }
+}
+
+/// The server listener that the gateway forwards queries to
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    addr: SocketAddr,
+}
+
+impl Upstream {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr }
+    }
+
+    /// sends queries over a new connection to the server and reads back the result
+    pub async fn forward<Q, R>(&self, queries: Q) -> Result<R, GatewayError>
+    where
+        Q: BinCodeSerAndDeser,
+        R: BinCodeSerAndDeser,
+    {
+        let mut stream = TcpStream::connect(self.addr).await?;
+        let message = <Q as BinCodeSerAndDeser>::serialize(&queries)?;
+        stream.write_all(&message).await?;
+        let mut header = [0u8; RESPONSE_HEADER_LEN];
+        stream.read_exact(&mut header).await?;
+        let mut length_header = [0u8; LENGTH_HEADER_SIZE];
+        length_header.copy_from_slice(&header[RESPONSE_HEADER_LEN - LENGTH_HEADER_SIZE..]);
+        let mut response = vec![0u8; u64::from_le_bytes(length_header) as usize];
+        stream.read_exact(&mut response).await?;
+        Ok(<R as BinCodeSerAndDeser>::deserialize(&response)?)
+    }
+}
+
+/// trace parent propagated from the `traceparent` header of a request, if any
+pub fn trace_parent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string)
+}
+
+pub fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, GatewayError> {
+    Ok(serde_json::from_slice(body)?)
+}
+
+pub fn json_response(status: StatusCode, body: &impl Serialize) -> Response {
+    match serde_json::to_vec(body) {
+        Ok(body) => (status, [(header::CONTENT_TYPE, "application/json")], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// responds with the result of a single query, failed queries get the closest HTTP status to
+/// their error code
+pub fn query_response<T: Serialize>(
+    result: Option<Result<T, ErrorResponse>>,
+) -> Result<Response, GatewayError> {
+    match result {
+        Some(Ok(response)) => Ok(json_response(StatusCode::OK, &response)),
+        Some(Err(error)) => Ok(json_response(status_code(error.code), &error)),
+        None => Err(GatewayError::EmptyResponse),
+    }
+}
+
+/// closest HTTP status to an error code
+pub fn status_code(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorCode::InvalidArgument
+        | ErrorCode::DimensionMismatch
+        | ErrorCode::ReservedKey
+        | ErrorCode::IncompatibleVersion => StatusCode::BAD_REQUEST,
+        ErrorCode::StoreNotFound
+        | ErrorCode::PredicateNotFound
+        | ErrorCode::NonLinearIndexNotFound
+        | ErrorCode::JobNotFound => StatusCode::NOT_FOUND,
+        ErrorCode::StoreAlreadyExists => StatusCode::CONFLICT,
+        ErrorCode::ResourceExhausted => StatusCode::INSUFFICIENT_STORAGE,
+        ErrorCode::ModelError => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::LimitExceeded => StatusCode::PAYLOAD_TOO_LARGE,
+        ErrorCode::QuotaExceeded | ErrorCode::ReadOnly => StatusCode::FORBIDDEN,
+        ErrorCode::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+    }
+}
+
+/// Serves the routes of a gateway until the server shuts down
+#[derive(Debug, Clone)]
+pub struct HttpGateway {
+    service_name: &'static str,
+    listener: Arc<std::net::TcpListener>,
+    // routers are not Sync so the gateway hands out a clone of it to each run
+    router: Arc<Mutex<Router>>,
+}
+
+impl HttpGateway {
+    pub fn bind(
+        service_name: &'static str,
+        host: &str,
+        port: u16,
+        router: Router,
+    ) -> IoResult<Self> {
+        let listener = std::net::TcpListener::bind((host, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            service_name,
+            listener: Arc::new(listener),
+            router: Arc::new(Mutex::new(router)),
+        })
+    }
+
+    pub fn local_addr(&self) -> IoResult<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+#[async_trait::async_trait]
+impl Task for HttpGateway {
+    fn task_name(&self) -> String {
+        format!("{}-http-gateway", self.service_name)
+    }
+
+    async fn run(&self) -> TaskState {
+        let router = self
+            .router
+            .lock()
+            .expect("http gateway router lock poisoned")
+            .clone();
+        let server = self
+            .listener
+            .try_clone()
+            .and_then(|listener| axum::Server::from_tcp(listener).map_err(std::io::Error::other));
+        match server {
+            Ok(server) => {
+                log::info!("Serving http gateway on {:?}", self.local_addr());
+                if let Err(e) = server.serve(router.into_make_service()).await {
+                    log::error!("Http gateway stopped {e}");
+                }
+            }
+            Err(e) => log::error!("Could not start http gateway {e}"),
+        }
+        TaskState::Break
+    }
+}
@@ -0,0 +1,127 @@
+use ahnlich_types::jobs::{JobKind, JobState, JobStatus};
+use flurry::HashMap as ConcurrentHashMap;
+use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// A long running operation tracked by the JobHandler. The operation itself reports progress and
+/// checks `is_running` between units of work so that it stops once cancelled
+#[derive(Debug)]
+pub struct Job {
+    id: u64,
+    kind: JobKind,
+    state: RwLock<JobState>,
+    processed: AtomicUsize,
+    total: usize,
+    finished_at: OnceLock<Instant>,
+}
+
+impl Job {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn status(&self) -> JobStatus {
+        JobStatus {
+            id: self.id,
+            kind: self.kind,
+            state: self.state.read().expect("job state lock poisoned").clone(),
+            processed: self.processed.load(Ordering::SeqCst),
+            total: self.total,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.state.read().expect("job state lock poisoned") == JobState::Running
+    }
+
+    /// Records that `processed` more entries have been handled
+    pub fn progress(&self, processed: usize) {
+        self.processed.fetch_add(processed, Ordering::SeqCst);
+    }
+
+    /// Moves the job to a final state, a job that already finished keeps its state
+    pub fn finish(&self, state: JobState) {
+        let mut current = self.state.write().expect("job state lock poisoned");
+        if *current == JobState::Running {
+            *current = state;
+            let _ = self.finished_at.set(Instant::now());
+        }
+    }
+
+    fn expired(&self, ttl: Duration) -> bool {
+        self.finished_at
+            .get()
+            .is_some_and(|finished_at| finished_at.elapsed() >= ttl)
+    }
+}
+
+/// Datastructure to keep track of jobs started by a server so that they can be listed, polled
+/// and cancelled. Finished jobs are forgotten once they have been finished for longer than the ttl
+#[derive(Debug)]
+pub struct JobHandler {
+    next_id: AtomicU64,
+    jobs: ConcurrentHashMap<u64, Arc<Job>>,
+    ttl: Duration,
+}
+
+impl JobHandler {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            jobs: ConcurrentHashMap::new(),
+            ttl,
+        }
+    }
+
+    /// Registers a new running job that has `total` entries to process
+    #[tracing::instrument(skip(self))]
+    pub fn register(&self, kind: JobKind, total: usize) -> Arc<Job> {
+        self.evict_expired();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let job = Arc::new(Job {
+            id,
+            kind,
+            state: RwLock::new(JobState::Running),
+            processed: AtomicUsize::new(0),
+            total,
+            finished_at: OnceLock::new(),
+        });
+        self.jobs.pin().insert(id, job.clone());
+        job
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub fn get(&self, job_id: u64) -> Option<JobStatus> {
+        self.evict_expired();
+        self.jobs.pin().get(&job_id).map(|job| job.status())
+    }
+
+    /// Lists every job that has not expired ordered by id
+    #[tracing::instrument(skip(self))]
+    pub fn list(&self) -> Vec<JobStatus> {
+        self.evict_expired();
+        let mut jobs: Vec<_> = self.jobs.pin().values().map(|job| job.status()).collect();
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// Cancels a running job, returning its status after cancellation. Cancelling a finished job
+    /// leaves it as is
+    #[tracing::instrument(skip(self))]
+    pub fn cancel(&self, job_id: u64) -> Option<JobStatus> {
+        self.evict_expired();
+        self.jobs.pin().get(&job_id).map(|job| {
+            job.finish(JobState::Cancelled);
+            job.status()
+        })
+    }
+
+    fn evict_expired(&self) {
+        let pinned = self.jobs.pin();
+        pinned.retain(|_, job| !job.expired(self.ttl));
+    }
+}
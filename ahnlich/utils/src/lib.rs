@@ -1,7 +1,18 @@
 pub mod allocator;
+pub mod audit;
 pub mod cli;
 pub mod client;
+pub mod deadline;
+pub mod encryption;
+pub mod filters;
+pub mod gateway;
+pub mod jobs;
+pub mod limits;
 pub mod parallel;
 pub mod persistence;
 pub mod protocol;
+pub mod reload;
+pub mod scheduler;
 pub mod server;
+pub mod systemd;
+pub mod transport;
@@ -0,0 +1,225 @@
+use crate::cli::CommandLineConfig;
+use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::keyval::StoreName;
+use ahnlich_types::RequestLimits;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+/// Request limits for a single client host or store, parsed from `NAME=MESSAGE_SIZE[,BATCH_SIZE]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitOverride {
+    pub name: String,
+    pub limits: RequestLimits,
+}
+
+impl FromStr for LimitOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, limits) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected NAME=MESSAGE_SIZE[,BATCH_SIZE], got {s}"))?;
+        if name.is_empty() {
+            return Err(format!("Missing name in limit override {s}"));
+        }
+        let (message_size, batch_size) = match limits.split_once(',') {
+            Some((message_size, batch_size)) => (message_size, Some(batch_size)),
+            None => (limits, None),
+        };
+        let message_size = message_size
+            .trim()
+            .parse()
+            .map_err(|err| format!("Invalid message size in {s}: {err}"))?;
+        let batch_size = batch_size
+            .map(|batch_size| batch_size.trim().parse())
+            .transpose()
+            .map_err(|err| format!("Invalid batch size in {s}: {err}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            limits: RequestLimits {
+                message_size,
+                batch_size,
+            },
+        })
+    }
+}
+
+/// Resolves the request limits of clients and stores, falling back to the server wide
+/// `message_size` and `batch_size` for those without an override
+#[derive(Debug)]
+pub struct LimitHandler {
+    // replaced as the configuration of the server is reloaded
+    limits: RwLock<Limits>,
+}
+
+#[derive(Debug)]
+struct Limits {
+    default: RequestLimits,
+    // keyed by client host, all connections from a host share its limits
+    clients: HashMap<String, RequestLimits>,
+    stores: HashMap<StoreName, RequestLimits>,
+}
+
+impl Limits {
+    fn new(config: &CommandLineConfig) -> Self {
+        Self {
+            default: RequestLimits {
+                message_size: config.message_size,
+                batch_size: config.batch_size,
+            },
+            clients: config
+                .client_limits
+                .iter()
+                .map(|entry| (entry.name.clone(), entry.limits))
+                .collect(),
+            stores: config
+                .store_limits
+                .iter()
+                .map(|entry| (StoreName(entry.name.clone()), entry.limits))
+                .collect(),
+        }
+    }
+}
+
+impl LimitHandler {
+    pub fn new(config: &CommandLineConfig) -> Self {
+        Self {
+            limits: RwLock::new(Limits::new(config)),
+        }
+    }
+
+    /// Applies the limits of `config` to the requests that follow
+    pub fn reload(&self, config: &CommandLineConfig) {
+        *self.limits.write().expect("Limits lock poisoned") = Limits::new(config);
+    }
+
+    pub fn client(&self, client: &ConnectedClient) -> RequestLimits {
+        let limits = self.limits.read().expect("Limits lock poisoned");
+        limits
+            .clients
+            .get(&client.host())
+            .copied()
+            .unwrap_or(limits.default)
+    }
+
+    pub fn store(&self, store: &StoreName) -> RequestLimits {
+        let limits = self.limits.read().expect("Limits lock poisoned");
+        limits.stores.get(store).copied().unwrap_or(limits.default)
+    }
+
+    /// limits on a request from `client` into `store`, the stricter of both apply
+    pub fn request(&self, client: &ConnectedClient, store: &StoreName) -> RequestLimits {
+        self.client(client).min(self.store(store))
+    }
+}
+
+/// Why a request was not admitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmissionError {
+    /// The request alone is estimated to need more than a request may
+    RequestTooLarge { estimate: usize, limit: usize },
+    /// The request would take the memory of the requests in flight past the limit
+    Busy {
+        estimate: usize,
+        in_flight: usize,
+        limit: usize,
+    },
+}
+
+/// Admits requests by the memory they are estimated to need before they run, so that requests
+/// the server cannot hold are rejected rather than running out of memory part way through
+#[derive(Debug, Default)]
+pub struct MemoryAdmission {
+    max_request: Option<usize>,
+    max_in_flight: Option<usize>,
+    // estimated bytes of the requests being processed
+    in_flight: AtomicUsize,
+}
+
+/// Memory held by an admitted request, released once the permit is dropped
+#[derive(Debug)]
+pub struct MemoryPermit<'a> {
+    admission: &'a MemoryAdmission,
+    estimate: usize,
+}
+
+impl MemoryAdmission {
+    pub fn new(max_request: Option<usize>, max_in_flight: Option<usize>) -> Self {
+        Self {
+            max_request,
+            max_in_flight,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    /// Estimated bytes of the requests being processed
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    pub fn admit(&self, estimate: usize) -> Result<MemoryPermit<'_>, AdmissionError> {
+        if let Some(limit) = self.max_request.filter(|limit| estimate > *limit) {
+            return Err(AdmissionError::RequestTooLarge { estimate, limit });
+        }
+        self.in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |in_flight| {
+                let total = in_flight.saturating_add(estimate);
+                match self.max_in_flight {
+                    Some(limit) if total > limit => None,
+                    _ => Some(total),
+                }
+            })
+            .map_err(|in_flight| AdmissionError::Busy {
+                estimate,
+                in_flight,
+                limit: self.max_in_flight.unwrap_or_default(),
+            })?;
+        Ok(MemoryPermit {
+            admission: self,
+            estimate,
+        })
+    }
+}
+
+impl Drop for MemoryPermit<'_> {
+    fn drop(&mut self) {
+        self.admission
+            .in_flight
+            .fetch_sub(self.estimate, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_admission() {
+        let admission = MemoryAdmission::new(Some(100), Some(150));
+        assert_eq!(
+            admission.admit(101).unwrap_err(),
+            AdmissionError::RequestTooLarge {
+                estimate: 101,
+                limit: 100
+            }
+        );
+        let permit = admission.admit(100).unwrap();
+        assert_eq!(admission.in_flight(), 100);
+        assert_eq!(
+            admission.admit(60).unwrap_err(),
+            AdmissionError::Busy {
+                estimate: 60,
+                in_flight: 100,
+                limit: 150
+            }
+        );
+        let other = admission.admit(50).unwrap();
+        assert_eq!(admission.in_flight(), 150);
+        drop(permit);
+        drop(other);
+        assert_eq!(admission.in_flight(), 0);
+        assert!(MemoryAdmission::default().admit(usize::MAX).is_ok());
+    }
+}
@@ -1,9 +1,10 @@
-use rayon::ThreadPoolBuilder;
-use std::sync::Once;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Once, OnceLock};
 
 static INIT_THREADPOOL_ONCE: Once = Once::new();
+static MAINTENANCE_THREADPOOL: OnceLock<ThreadPool> = OnceLock::new();
 
-// Initialize global rayon threadpool
+// Initialize global rayon threadpool, which queries run on
 pub(crate) fn init_threadpool(num_threads: usize) {
     INIT_THREADPOOL_ONCE.call_once(|| {
         ThreadPoolBuilder::new()
@@ -13,6 +14,28 @@ pub(crate) fn init_threadpool(num_threads: usize) {
     });
 }
 
+// Initialize the rayon threadpool background maintenance runs on, kept apart from the global
+// threadpool so that rebuilding a large index does not hold up queries
+pub(crate) fn init_maintenance_threadpool(num_threads: usize) {
+    MAINTENANCE_THREADPOOL.get_or_init(|| {
+        ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|index| format!("maintenance-{index}"))
+            .build()
+            .expect("Cannot build server maintenance threadpool")
+    });
+}
+
+/// Runs maintenance such as compaction and index builds on the maintenance threadpool, along
+/// with any parallel iterators within it. It runs on the calling thread when the server has not
+/// set up the threadpool, as is the case in tests
+pub fn maintenance<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    match MAINTENANCE_THREADPOOL.get() {
+        Some(threadpool) => threadpool.install(op),
+        None => op(),
+    }
+}
+
 // Calculates chunk size to use for an iterable input in order for it to be able to fit into all
 // possible rayon threads
 pub fn chunk_size(input_length: usize) -> usize {
@@ -20,3 +43,16 @@ pub fn chunk_size(input_length: usize) -> usize {
     let minimum_factor = std::cmp::min(input_length, num_threads);
     (input_length + minimum_factor - 1) / minimum_factor
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_threadpool() {
+        init_maintenance_threadpool(2);
+        let thread_name = maintenance(|| std::thread::current().name().map(String::from));
+        assert!(thread_name.unwrap().starts_with("maintenance-"));
+        assert_eq!(maintenance(rayon::current_num_threads), 2);
+    }
+}
@@ -1,8 +1,13 @@
+use crate::encryption::{
+    DecryptingReader, EncryptingWriter, EncryptionError, KeyProvider, ENCRYPTED_MAGIC,
+};
+use ahnlich_types::keyval::StoreName;
+use flurry::HashMap as ConcurrentHashMap;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -15,7 +20,7 @@ use tokio::time::sleep;
 use tokio::time::Duration;
 
 pub trait AhnlichPersistenceUtils {
-    type PersistenceObject: Serialize + DeserializeOwned + Send + Sync + 'static;
+    type PersistenceObject: Serialize + DeserializeOwned + SnapshotSections + Send + Sync + 'static;
 
     fn write_flag(&self) -> Arc<AtomicBool>;
 
@@ -34,6 +39,343 @@ pub enum PersistenceTaskError {
     FileError(#[from] std::io::Error),
     #[error("SerdeError {0}")]
     SerdeError(#[from] serde_json::error::Error),
+    #[error("Snapshot is of format version {0} while only up to version {SNAPSHOT_VERSION} is supported, it was written by a newer server")]
+    UnsupportedVersion(u32),
+    #[error("Snapshot is truncated, it ends before the checksum of the whole snapshot. Restore it from a backup")]
+    Truncated,
+    #[error("Snapshot has data past its end, it was likely overwritten in part. Restore it from a backup")]
+    TrailingData,
+    #[error("Section {name} of snapshot is corrupted, its checksum is {found:08x} instead of {expected:08x}. Restore it from a backup")]
+    CorruptedSection {
+        name: String,
+        expected: u32,
+        found: u32,
+    },
+    #[error("Snapshot is corrupted, its checksum is {found:08x} instead of {expected:08x}. Restore it from a backup")]
+    Corrupted { expected: u32, found: u32 },
+    #[error("Snapshot is corrupted, it has {found} sections instead of {expected}. Restore it from a backup")]
+    MissingSections { expected: usize, found: usize },
+    #[error("Section {0} of snapshot could not be read {1}")]
+    InvalidSection(String, serde_json::error::Error),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
+/// First line of a snapshot, snapshots without it are a json document of the whole object as
+/// written before snapshots were checksummed
+const SNAPSHOT_MAGIC: &[u8] = b"ahnlich-snapshot ";
+
+/// Version of the snapshot format that is written
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A line of a snapshot after its format header. Every section is a line of json preceded by
+/// the line describing it, and the end of the snapshot holds the checksum of every line before
+/// it so that sections cut off or dropped are caught
+#[derive(Debug, Serialize, Deserialize)]
+enum SnapshotEntry {
+    Section {
+        name: String,
+        length: u64,
+        checksum: u32,
+    },
+    End {
+        sections: usize,
+        checksum: u32,
+    },
+}
+
+/// Writes the sections of a snapshot, checksumming each of them along with the whole snapshot
+pub struct SnapshotWriter<W: Write> {
+    writer: W,
+    hasher: crc32fast::Hasher,
+    sections: usize,
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    fn new(mut writer: W) -> Result<Self, PersistenceTaskError> {
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writeln!(writer, "{SNAPSHOT_VERSION}")?;
+        Ok(Self {
+            writer,
+            hasher: crc32fast::Hasher::new(),
+            sections: 0,
+        })
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> Result<(), PersistenceTaskError> {
+        self.hasher.update(line);
+        self.hasher.update(b"\n");
+        self.writer.write_all(line)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Writes a section, whose name is used to point out which part of a snapshot is corrupted
+    pub fn section(
+        &mut self,
+        name: String,
+        value: &impl Serialize,
+    ) -> Result<(), PersistenceTaskError> {
+        let data = serde_json::to_vec(value)?;
+        let entry = serde_json::to_vec(&SnapshotEntry::Section {
+            name,
+            length: data.len() as u64,
+            checksum: crc32fast::hash(&data),
+        })?;
+        self.write_line(&entry)?;
+        self.write_line(&data)?;
+        self.sections += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), PersistenceTaskError> {
+        let end = SnapshotEntry::End {
+            sections: self.sections,
+            checksum: self.hasher.clone().finalize(),
+        };
+        serde_json::to_writer(&mut self.writer, &end)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A section of a snapshot whose checksum matched
+#[derive(Debug)]
+pub struct SnapshotSection {
+    pub name: String,
+    data: Vec<u8>,
+}
+
+impl SnapshotSection {
+    pub fn parse<V: DeserializeOwned>(&self) -> Result<V, PersistenceTaskError> {
+        serde_json::from_slice(&self.data)
+            .map_err(|err| PersistenceTaskError::InvalidSection(self.name.clone(), err))
+    }
+}
+
+/// Reads the sections of a snapshot one at a time, verifying the checksum of each section as
+/// it is read and that of the whole snapshot once the last section is read
+pub struct SnapshotReader<R: BufRead> {
+    reader: R,
+    hasher: crc32fast::Hasher,
+    // names and sizes of the sections read so far
+    sections: Vec<(String, u64)>,
+}
+
+impl<R: BufRead> SnapshotReader<R> {
+    fn read_line(&mut self) -> Result<Vec<u8>, PersistenceTaskError> {
+        let mut line = vec![];
+        self.reader.read_until(b'\n', &mut line)?;
+        if line.pop() != Some(b'\n') {
+            return Err(PersistenceTaskError::Truncated);
+        }
+        Ok(line)
+    }
+
+    /// The next section, None once every section was read and the whole snapshot verified
+    pub fn next_section(&mut self) -> Result<Option<SnapshotSection>, PersistenceTaskError> {
+        let line = self.read_line()?;
+        match serde_json::from_slice(&line).map_err(|_| PersistenceTaskError::Truncated)? {
+            SnapshotEntry::Section {
+                name,
+                length,
+                checksum,
+            } => {
+                self.hasher.update(&line);
+                self.hasher.update(b"\n");
+                // read up to the length rather than allocating it upfront, as a corrupted length
+                // can be far more than the snapshot holds
+                let mut data = vec![];
+                let expected = length.saturating_add(1);
+                (&mut self.reader).take(expected).read_to_end(&mut data)?;
+                if data.len() as u64 != expected {
+                    return Err(PersistenceTaskError::Truncated);
+                }
+                self.hasher.update(&data);
+                if data.pop() != Some(b'\n') {
+                    return Err(PersistenceTaskError::Truncated);
+                }
+                let found = crc32fast::hash(&data);
+                if found != checksum {
+                    return Err(PersistenceTaskError::CorruptedSection {
+                        name,
+                        expected: checksum,
+                        found,
+                    });
+                }
+                self.sections.push((name.clone(), length));
+                Ok(Some(SnapshotSection { name, data }))
+            }
+            SnapshotEntry::End { sections, checksum } => {
+                let found = self.hasher.clone().finalize();
+                if found != checksum {
+                    return Err(PersistenceTaskError::Corrupted {
+                        expected: checksum,
+                        found,
+                    });
+                }
+                if sections != self.sections.len() {
+                    return Err(PersistenceTaskError::MissingSections {
+                        expected: sections,
+                        found: self.sections.len(),
+                    });
+                }
+                if !self.reader.fill_buf()?.is_empty() {
+                    return Err(PersistenceTaskError::TrailingData);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Splits a snapshot into sections, such as a section per store, that are checksummed on their
+/// own so that a corrupted snapshot names the part of it that is corrupted
+pub trait SnapshotSections: Sized {
+    fn write_sections<W: Write>(
+        &self,
+        writer: &mut SnapshotWriter<W>,
+    ) -> Result<(), PersistenceTaskError>;
+
+    /// Rebuilds the snapshot from every section read until `next_section` returns None
+    fn read_sections<R: BufRead>(
+        reader: &mut SnapshotReader<R>,
+    ) -> Result<Self, PersistenceTaskError>;
+
+    /// Called right before the sections are written by the persistence task
+    fn writing(&self) {}
+
+    /// Called once the snapshot written by the persistence task has replaced the one persisted
+    /// before it
+    fn persisted(&self) {}
+}
+
+impl<V> SnapshotSections for Arc<ConcurrentHashMap<StoreName, V>>
+where
+    V: Serialize + DeserializeOwned + Send + Sync,
+{
+    fn write_sections<W: Write>(
+        &self,
+        writer: &mut SnapshotWriter<W>,
+    ) -> Result<(), PersistenceTaskError> {
+        for (name, store) in self.iter(&self.guard()) {
+            writer.section(name.to_string(), store)?;
+        }
+        Ok(())
+    }
+
+    fn read_sections<R: BufRead>(
+        reader: &mut SnapshotReader<R>,
+    ) -> Result<Self, PersistenceTaskError> {
+        let stores = ConcurrentHashMap::new();
+        while let Some(section) = reader.next_section()? {
+            let store = section.parse()?;
+            stores.insert(StoreName(section.name), store, &stores.guard());
+        }
+        Ok(Arc::new(stores))
+    }
+}
+
+/// What was found in a snapshot that loaded
+#[derive(Debug)]
+pub struct SnapshotSummary {
+    /// Format version of the snapshot, 0 for snapshots from before they were checksummed
+    pub version: u32,
+    /// Names of the sections along with their size in bytes
+    pub sections: Vec<(String, u64)>,
+    /// Key the snapshot is encrypted with, None when it is not encrypted
+    pub key_id: Option<String>,
+}
+
+/// Writes a snapshot in the current format
+pub fn write_snapshot<W: Write, T: SnapshotSections>(
+    writer: W,
+    object: &T,
+) -> Result<(), PersistenceTaskError> {
+    let mut writer = SnapshotWriter::new(writer)?;
+    object.write_sections(&mut writer)?;
+    writer.finish()
+}
+
+/// Reads a snapshot along with what was found in it. Snapshots from before they were
+/// checksummed are read as a json document of the whole object
+fn read_snapshot<T: SnapshotSections + DeserializeOwned>(
+    mut reader: impl BufRead,
+    key_id: Option<String>,
+) -> Result<(T, SnapshotSummary), PersistenceTaskError> {
+    if !reader.fill_buf()?.starts_with(SNAPSHOT_MAGIC) {
+        let summary = SnapshotSummary {
+            version: 0,
+            sections: vec![],
+            key_id,
+        };
+        return Ok((serde_json::from_reader(reader)?, summary));
+    }
+    reader.consume(SNAPSHOT_MAGIC.len());
+    let mut version = String::new();
+    reader.read_line(&mut version)?;
+    let version = version
+        .trim_end()
+        .parse()
+        .map_err(|_| PersistenceTaskError::Truncated)?;
+    if version > SNAPSHOT_VERSION {
+        return Err(PersistenceTaskError::UnsupportedVersion(version));
+    }
+    let mut reader = SnapshotReader {
+        reader,
+        hasher: crc32fast::Hasher::new(),
+        sections: vec![],
+    };
+    let object = T::read_sections(&mut reader)?;
+    let summary = SnapshotSummary {
+        version,
+        sections: reader.sections,
+        key_id,
+    };
+    Ok((object, summary))
+}
+
+/// Reads a snapshot from a file, decrypting it when it is encrypted. A snapshot that is not
+/// encrypted still loads when there are keys, it is encrypted once it is persisted again
+fn read_snapshot_file<T: SnapshotSections + DeserializeOwned>(
+    persist_location: &Path,
+    key_provider: Option<&dyn KeyProvider>,
+) -> Result<(T, SnapshotSummary), PersistenceTaskError> {
+    read_snapshot_from(BufReader::new(File::open(persist_location)?), key_provider)
+}
+
+/// Reads a snapshot as `read_snapshot_file` would from anything holding one, such as the bytes
+/// of a snapshot when fuzzing how snapshots are loaded
+pub fn read_snapshot_from<T: SnapshotSections + DeserializeOwned>(
+    mut reader: impl BufRead,
+    key_provider: Option<&dyn KeyProvider>,
+) -> Result<(T, SnapshotSummary), PersistenceTaskError> {
+    if !reader.fill_buf()?.starts_with(ENCRYPTED_MAGIC) {
+        if key_provider.is_some() {
+            log::warn!("Snapshot is not encrypted, it is encrypted once it is persisted again");
+        }
+        return read_snapshot(reader, None);
+    }
+    reader.consume(ENCRYPTED_MAGIC.len());
+    let decrypting = DecryptingReader::new(reader, key_provider)?;
+    let key_id = decrypting.key_id().to_string();
+    read_snapshot(BufReader::new(decrypting), Some(key_id))
+}
+
+/// Writes a snapshot, encrypted with the current key of the key provider if there is one
+fn write_snapshot_file<T: SnapshotSections>(
+    writer: impl Write,
+    object: &T,
+    key_provider: Option<&dyn KeyProvider>,
+) -> Result<(), PersistenceTaskError> {
+    let Some(key_provider) = key_provider else {
+        return write_snapshot(writer, object);
+    };
+    let mut encrypting = EncryptingWriter::new(writer, &key_provider.current_key()?)?;
+    write_snapshot(&mut encrypting, object)?;
+    encrypting.finish()?;
+    Ok(())
 }
 
 #[derive(Debug, Clone)]
@@ -42,10 +384,11 @@ pub struct Persistence<T> {
     persistence_interval: u64,
     persist_location: std::path::PathBuf,
     persist_object: T,
+    key_provider: Option<Arc<dyn KeyProvider>>,
 }
 
 #[async_trait::async_trait]
-impl<T: Sync + Serialize + DeserializeOwned> Task for Persistence<T> {
+impl<T: Sync + Serialize + DeserializeOwned + SnapshotSections> Task for Persistence<T> {
     fn task_name(&self) -> String {
         "persistence".to_string()
     }
@@ -70,11 +413,19 @@ impl<T: Sync + Serialize + DeserializeOwned> Task for Persistence<T> {
             let _ =
                 self.write_flag
                     .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst);
-            if let Err(e) = serde_json::to_writer(&writer, &self.persist_object) {
+            self.persist_object.writing();
+            if let Err(e) = write_snapshot_file(
+                BufWriter::new(&writer),
+                &self.persist_object,
+                self.key_provider.as_deref(),
+            ) {
                 log::error!("Error writing stores to temp file {e}");
             } else {
                 match std::fs::rename(temp_path, persist_location) {
-                    Ok(_) => log::debug!("Persisted stores to disk"),
+                    Ok(_) => {
+                        self.persist_object.persisted();
+                        log::debug!("Persisted stores to disk")
+                    }
                     Err(e) => log::error!("Error writing temp file to persist location {e}"),
                 };
             }
@@ -83,19 +434,32 @@ impl<T: Sync + Serialize + DeserializeOwned> Task for Persistence<T> {
     }
 }
 
-impl<T: Serialize + DeserializeOwned> Persistence<T> {
-    pub fn load_snapshot(persist_location: &std::path::PathBuf) -> Result<T, PersistenceTaskError> {
-        let file = File::open(persist_location)?;
-        let reader = BufReader::new(file);
-        let loaded: T = serde_json::from_reader(reader)?;
+impl<T: Serialize + DeserializeOwned + SnapshotSections> Persistence<T> {
+    /// Loads a snapshot, which is only returned once every checksum of it matched so a
+    /// corrupted snapshot is never loaded in part
+    pub fn load_snapshot(
+        persist_location: &Path,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> Result<T, PersistenceTaskError> {
+        let (loaded, _) = read_snapshot_file(persist_location, key_provider)?;
         Ok(loaded)
     }
 
+    /// Verifies a snapshot offline by loading it as the server would, returning what it holds
+    pub fn verify(
+        persist_location: &Path,
+        key_provider: Option<&dyn KeyProvider>,
+    ) -> Result<SnapshotSummary, PersistenceTaskError> {
+        let (_, summary) = read_snapshot_file::<T>(persist_location, key_provider)?;
+        Ok(summary)
+    }
+
     pub fn task(
         write_flag: Arc<AtomicBool>,
         persistence_interval: u64,
         persist_location: &std::path::PathBuf,
         persist_object: T,
+        key_provider: Option<Arc<dyn KeyProvider>>,
     ) -> Self {
         let _ = OpenOptions::new()
             .append(true)
@@ -107,6 +471,7 @@ impl<T: Serialize + DeserializeOwned> Persistence<T> {
             persistence_interval,
             persist_object,
             persist_location: persist_location.clone(),
+            key_provider,
         }
     }
 
@@ -115,3 +480,177 @@ impl<T: Serialize + DeserializeOwned> Persistence<T> {
         self.write_flag.load(Ordering::SeqCst)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
+
+    type Stores = Arc<ConcurrentHashMap<StoreName, Vec<u32>>>;
+
+    fn contents(stores: &Stores) -> BTreeMap<String, Vec<u32>> {
+        stores
+            .iter(&stores.guard())
+            .map(|(name, store)| (name.to_string(), store.clone()))
+            .collect()
+    }
+
+    fn stores() -> Stores {
+        let stores = ConcurrentHashMap::new();
+        stores.insert(StoreName("Main".to_string()), vec![1, 2], &stores.guard());
+        stores.insert(StoreName("Other".to_string()), vec![3], &stores.guard());
+        Arc::new(stores)
+    }
+
+    #[test]
+    fn test_snapshot_checksums() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("ahnlich.dat");
+        let mut snapshot = vec![];
+        write_snapshot(&mut snapshot, &stores()).unwrap();
+        std::fs::write(&path, &snapshot).unwrap();
+        let summary = Persistence::<Stores>::verify(&path, None).unwrap();
+        assert_eq!(summary.version, SNAPSHOT_VERSION);
+        assert_eq!(summary.sections.len(), 2);
+        let loaded = Persistence::<Stores>::load_snapshot(&path, None).unwrap();
+        assert_eq!(
+            loaded.get(&StoreName("Main".to_string()), &loaded.guard()),
+            Some(&vec![1, 2])
+        );
+
+        // a flipped byte within the data of a store names the store
+        let position = snapshot
+            .windows(5)
+            .position(|window| window == b"[1,2]")
+            .unwrap();
+        let mut corrupted = snapshot.clone();
+        corrupted[position + 1] = b'7';
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(
+            Persistence::<Stores>::load_snapshot(&path, None),
+            Err(PersistenceTaskError::CorruptedSection { name, .. }) if name == "Main"
+        ));
+
+        // a section that is dropped entirely is caught by the checksum of the whole snapshot
+        let lines: Vec<_> = snapshot.split_inclusive(|byte| *byte == b'\n').collect();
+        let dropped = [lines[0], lines[3], lines[4], lines[5]].concat();
+        std::fs::write(&path, dropped).unwrap();
+        assert!(matches!(
+            Persistence::<Stores>::load_snapshot(&path, None),
+            Err(PersistenceTaskError::Corrupted { .. })
+        ));
+
+        std::fs::write(&path, &snapshot[..snapshot.len() - 10]).unwrap();
+        assert!(matches!(
+            Persistence::<Stores>::load_snapshot(&path, None),
+            Err(PersistenceTaskError::Truncated)
+        ));
+
+        std::fs::write(&path, [&snapshot[..], b"{}"].concat()).unwrap();
+        assert!(matches!(
+            Persistence::<Stores>::load_snapshot(&path, None),
+            Err(PersistenceTaskError::TrailingData)
+        ));
+
+        // snapshots from before they were checksummed still load
+        std::fs::write(&path, serde_json::to_vec(&stores()).unwrap()).unwrap();
+        assert_eq!(
+            Persistence::<Stores>::verify(&path, None).unwrap().version,
+            0
+        );
+        assert_eq!(
+            Persistence::<Stores>::load_snapshot(&path, None)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_encrypted_snapshot() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("ahnlich.dat");
+        let key_file = directory.path().join("keys");
+        std::fs::write(&key_file, format!("first:{}\n", "01".repeat(32))).unwrap();
+        let first = crate::encryption::key_provider(Some(&key_file), None)
+            .unwrap()
+            .unwrap();
+        let mut file = File::create(&path).unwrap();
+        write_snapshot_file(&mut file, &stores(), Some(first.as_ref())).unwrap();
+        assert!(!std::fs::read(&path)
+            .unwrap()
+            .windows(4)
+            .any(|window| window == b"Main"));
+        let summary = Persistence::<Stores>::verify(&path, Some(first.as_ref())).unwrap();
+        assert_eq!(summary.key_id.as_deref(), Some("first"));
+        assert_eq!(summary.sections.len(), 2);
+
+        // rotating the key keeps the snapshot loading until it is persisted with the new key
+        std::fs::write(
+            &key_file,
+            format!("second:{}\nfirst:{}", "02".repeat(32), "01".repeat(32)),
+        )
+        .unwrap();
+        let loaded = Persistence::<Stores>::load_snapshot(&path, Some(first.as_ref())).unwrap();
+        assert_eq!(loaded.len(), 2);
+
+        std::fs::write(&key_file, format!("first:{}", "03".repeat(32))).unwrap();
+        assert!(matches!(
+            Persistence::<Stores>::load_snapshot(&path, Some(first.as_ref())),
+            Err(PersistenceTaskError::Encryption(
+                EncryptionError::DecryptionFailed(id)
+            )) if id == "first"
+        ));
+        assert!(matches!(
+            Persistence::<Stores>::load_snapshot(&path, None),
+            Err(PersistenceTaskError::Encryption(
+                EncryptionError::MissingKeyProvider(id)
+            )) if id == "first"
+        ));
+
+        // snapshots that are not encrypted load with a key provider
+        let mut file = File::create(&path).unwrap();
+        write_snapshot_file(&mut file, &stores(), None).unwrap();
+        let summary = Persistence::<Stores>::verify(&path, Some(first.as_ref())).unwrap();
+        assert_eq!(summary.key_id, None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_arbitrary_bytes_do_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..1024)) {
+            let _ = read_snapshot_from::<Stores>(&bytes[..], None);
+        }
+
+        #[test]
+        fn test_lines_after_the_header_do_not_panic(
+            lines in prop::collection::vec("[ -~]{0,64}", 0..6),
+        ) {
+            let snapshot = format!("ahnlich-snapshot 1\n{}\n", lines.join("\n"));
+            let _ = read_snapshot_from::<Stores>(snapshot.as_bytes(), None);
+        }
+
+        // every byte is covered by a checksum so a corrupted snapshot never loads in part
+        #[test]
+        fn test_corrupted_snapshots_do_not_load(
+            position in any::<prop::sample::Index>(),
+            byte in any::<u8>(),
+        ) {
+            let mut snapshot = vec![];
+            write_snapshot(&mut snapshot, &stores()).unwrap();
+            let position = position.index(snapshot.len());
+            snapshot[position] = byte;
+            if let Ok((loaded, _)) = read_snapshot_from::<Stores>(&snapshot[..], None) {
+                prop_assert_eq!(contents(&loaded), contents(&stores()));
+            }
+        }
+
+        #[test]
+        fn test_truncated_snapshots_do_not_load(length in any::<prop::sample::Index>()) {
+            let mut snapshot = vec![];
+            write_snapshot(&mut snapshot, &stores()).unwrap();
+            let length = length.index(snapshot.len());
+            prop_assert!(read_snapshot_from::<Stores>(&snapshot[..length], None).is_err());
+        }
+    }
+}
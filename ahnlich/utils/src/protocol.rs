@@ -1,3 +1,6 @@
+use crate::deadline::Deadline;
+use crate::scheduler::Scheduler;
+use crate::transport::ServerStream;
 use ahnlich_types::bincode::BinCodeSerAndDeser;
 use ahnlich_types::bincode::BinCodeSerAndDeserQuery;
 use ahnlich_types::bincode::BinCodeSerAndDeserResponse;
@@ -5,8 +8,13 @@ use ahnlich_types::bincode::LENGTH_HEADER_SIZE;
 use ahnlich_types::bincode::MAGIC_BYTES;
 use ahnlich_types::bincode::VERSION_LENGTH;
 use ahnlich_types::client::ConnectedClient;
+use ahnlich_types::error::ErrorCode;
+use ahnlich_types::error::ErrorResponse;
+use ahnlich_types::keyval::StoreName;
 use ahnlich_types::version::Version;
+use ahnlich_types::version::MIN_CLIENT_VERSION;
 use ahnlich_types::version::VERSION;
+use ahnlich_types::ErrorPolicy;
 use fallible_collections::vec::FallibleVec;
 use futures::FutureExt;
 use std::any::Any;
@@ -14,12 +22,13 @@ use std::fmt::Debug;
 use std::io::Error;
 use std::io::ErrorKind;
 use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::MutexGuard;
 
 use task_manager::TaskState;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tracing::Instrument;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
@@ -35,7 +44,10 @@ where
 
     fn connected_client(&self) -> &ConnectedClient;
     fn maximum_message_size(&self) -> u64;
-    fn reader(&self) -> Arc<Mutex<BufReader<TcpStream>>>;
+    fn reader(&self) -> Arc<Mutex<BufReader<ServerStream>>>;
+    /// set once the client hangs up on a pipeline that is being processed
+    fn cancelled(&self) -> &AtomicBool;
+    fn scheduler(&self) -> &Arc<Scheduler>;
 
     fn prefix_log(&self, message: impl std::fmt::Display) -> String {
         format!("ClIENT [{}]: {}", &self.connected_client().address, message)
@@ -72,24 +84,26 @@ where
                         return self.handle_error(reader, error, false).await;
                     }
                 };
-                if !VERSION.is_compatible(&version) {
-                    let error = format!(
-                        "Incompatible versions, Server: {:?}, Client {version:?}",
-                        *VERSION
-                    );
-                    return self.handle_error(reader, error, false).await;
-                }
                 // cap the message size to be of length 1MiB
                 if let Err(error) = reader.read_exact(&mut length_buf).await {
                     return self.handle_error(reader, error, false).await;
                 };
                 let data_length = u64::from_le_bytes(length_buf);
                 if data_length > self.maximum_message_size() {
-                    let error = format!(
-                        "Message cannot exceed {} bytes, configure `message_size` for higher",
-                        self.maximum_message_size()
-                    );
-                    return self.handle_error(reader, error, true).await;
+                    // the body is left unread so the connection cannot be used after this
+                    let error = ErrorResponse::new(
+                        ErrorCode::LimitExceeded,
+                        format!(
+                            "Message cannot exceed {} bytes, configure `message_size` or \
+                             `client-limit` for higher",
+                            self.maximum_message_size()
+                        ),
+                    )
+                    .with_metadata("limit", self.maximum_message_size())
+                    .with_metadata("size", data_length);
+                    log::error!("{}", self.prefix_log(&error));
+                    self.respond_with_error(&mut reader, error).await;
+                    return TaskState::Break;
                 };
 
                 let mut data: Vec<_> = match FallibleVec::try_with_capacity(data_length as usize) {
@@ -117,11 +131,33 @@ where
                     let error = format!("Could not read data buffer {e}");
                     return self.handle_error(reader, error.to_string(), false).await;
                 };
+                // the message is read in full before the version is checked so that the client can
+                // resend the query as a version the server supports over the same connection
+                if !VERSION.is_compatible(&version) {
+                    let error = ErrorResponse::new(
+                        ErrorCode::IncompatibleVersion,
+                        format!(
+                            "Incompatible versions, Server: {}, Client {version}",
+                            *VERSION
+                        ),
+                    )
+                    .with_metadata("min_client_version", *MIN_CLIENT_VERSION)
+                    .with_metadata("max_client_version", *VERSION);
+                    log::error!("{}", self.prefix_log(&error));
+                    self.respond_with_error(&mut reader, error).await;
+                    return TaskState::Continue;
+                }
                 match Self::ServerQuery::deserialize(&data) {
                     Ok(queries) => {
                         log::debug!("Got Queries {:?}", queries);
-                        let span = tracing::info_span!("query-processor");
-                        if let Some(trace_parent) = queries.get_traceparent() {
+                        let trace_parent = queries.get_traceparent();
+                        let request_id = tracer::request_id(trace_parent.as_deref());
+                        let span = tracing::info_span!(
+                            "query-processor",
+                            request_id = %request_id,
+                            client = %self.connected_client().address
+                        );
+                        if let Some(trace_parent) = trace_parent {
                             let parent_context = match tracer::trace_parent_to_span(trace_parent)
                                 .map_err(|err| Error::new(ErrorKind::Other, err))
                             {
@@ -131,14 +167,33 @@ where
                             span.set_parent(parent_context);
                         }
 
-                        let results =
-                            AssertUnwindSafe(self.handle(queries.into_inner()).instrument(span))
-                                .catch_unwind()
-                                .await
-                                .map_err(convert_error);
+                        let error_policy = queries.get_error_policy();
+                        let deadline = Deadline::after(queries.get_timeout());
+                        // held until the response is written so that latency covers all of it
+                        let _ticket = self.scheduler().acquire(queries.get_priority()).await;
+                        let handled = self
+                            .handle(queries.into_inner(), error_policy, deadline)
+                            .instrument(span);
+                        let results = AssertUnwindSafe(async {
+                            tokio::select! {
+                                biased;
+                                results = handled => results,
+                                _ = watch_hang_up(reader.get_ref(), self.cancelled()) => {
+                                    unreachable!("watching for a hang up never ends")
+                                }
+                            }
+                        })
+                        .catch_unwind()
+                        .await
+                        .map_err(convert_error);
+                        if self.cancelled().load(Ordering::Relaxed) {
+                            log::debug!("{}", self.prefix_log("Hung up on pipeline"));
+                            return TaskState::Break;
+                        }
 
                         match results {
                             Ok(results) => {
+                                let results = results.with_request_id(&request_id);
                                 if let Ok(binary_results) = results.serialize() {
                                     if let Err(error) =
                                         reader.get_mut().write_all(&binary_results).await
@@ -170,34 +225,99 @@ where
 
     async fn handle_error(
         &self,
-        mut reader: MutexGuard<'_, BufReader<TcpStream>>,
+        mut reader: MutexGuard<'_, BufReader<ServerStream>>,
         error: impl ToString + Send,
         respond_with_error: bool,
     ) -> TaskState {
         let error = self.prefix_log(error.to_string());
         log::error!("{error}");
         if respond_with_error {
-            match Self::ServerResponse::from_error(error.to_string()).serialize() {
-                Err(e) => log::error!(
-                    "{}",
-                    self.prefix_log(format!("Could not deserialize error response, {}", e))
-                ),
-                Ok(deserialize_error) => {
-                    if let Err(error) = reader.get_mut().write_all(&deserialize_error).await {
-                        log::error!("{}", self.prefix_log(format!("{error}")));
-                    }
-                }
-            };
+            self.respond_with_error(&mut reader, ErrorResponse::new(ErrorCode::Internal, error))
+                .await;
         }
         TaskState::Break
     }
 
+    async fn respond_with_error(
+        &self,
+        reader: &mut MutexGuard<'_, BufReader<ServerStream>>,
+        error: ErrorResponse,
+    ) {
+        match Self::ServerResponse::from_error(error).serialize() {
+            Err(e) => log::error!(
+                "{}",
+                self.prefix_log(format!("Could not deserialize error response, {}", e))
+            ),
+            Ok(deserialize_error) => {
+                if let Err(error) = reader.get_mut().write_all(&deserialize_error).await {
+                    log::error!("{}", self.prefix_log(format!("{error}")));
+                }
+            }
+        };
+    }
+
+    /// whether the client hung up on the pipeline, which is checked before each query so that
+    /// the rest of the pipeline is dropped once it has. Processing yields to do so as a hang up
+    /// is only noticed while it waits
+    async fn hung_up(&self) -> bool {
+        tokio::task::yield_now().await;
+        self.cancelled().load(Ordering::Relaxed)
+    }
+
+    /// handles queries in order, stopping at the first failed query when the error policy is
+    /// fail fast or once the client hangs up. Queries still running or yet to run once the
+    /// deadline passes fail
     async fn handle(
         &self,
         queries: <<Self as AhnlichProtocol>::ServerQuery as BinCodeSerAndDeserQuery>::Inner,
+        error_policy: ErrorPolicy,
+        deadline: Deadline,
     ) -> Self::ServerResponse;
 }
 
+/// Flags `cancelled` once the client closes the connection and waits forever otherwise. Clients
+/// wait on the response to a pipeline before sending another, so the connection only becomes
+/// readable while a pipeline is processed when the client hangs up
+async fn watch_hang_up(stream: &ServerStream, cancelled: &AtomicBool) {
+    stream.hung_up().await;
+    cancelled.store(true, Ordering::Relaxed);
+    std::future::pending().await
+}
+
+/// Turns away a connection the server will not take, answering it with `error` in place of the
+/// response to its first request before closing it
+pub async fn reject_connection<R: BinCodeSerAndDeserResponse>(
+    mut stream: ServerStream,
+    error: ErrorResponse,
+) {
+    match BinCodeSerAndDeser::serialize(&R::from_error(error)) {
+        Err(e) => log::error!("Could not serialize connection rejection, {e}"),
+        Ok(rejection) => {
+            if let Err(e) = stream.write_all(&rejection).await {
+                log::debug!("Could not answer rejected connection, {e}");
+            }
+            let _ = stream.shutdown().await;
+        }
+    }
+}
+
+/// Logs a query once it has been processed, along with the request id and client of the span it
+/// is processed in
+pub fn log_query<T>(
+    query_type: &'static str,
+    store: Option<&StoreName>,
+    started: Instant,
+    response: &Result<T, ErrorResponse>,
+) {
+    tracing::debug!(
+        query_type,
+        store = store.map(|store| store.0.as_str()),
+        duration_ms = started.elapsed().as_millis() as u64,
+        error = response.as_ref().err().map(|error| error.message.as_str()),
+        "Processed query"
+    );
+}
+
 fn convert_error(err: Box<dyn Any + Send + 'static>) -> String {
     if let Some(s) = err.downcast_ref::<String>() {
         s.to_string()
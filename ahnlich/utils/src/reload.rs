@@ -0,0 +1,401 @@
+use crate::cli::CommandLineConfig;
+use crate::client::ClientHandler;
+use crate::limits::{LimitHandler, LimitOverride};
+use ahnlich_types::error::{ErrorCode, ErrorResponse};
+use ahnlich_types::ConfigReload;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use task_manager::{Task, TaskState};
+use thiserror::Error;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// Settings of the configuration file every server applies while running, named after their
+/// flags. Servers register their own on top with [`ConfigReloader::with_setting`]
+const RELOADABLE: [&str; 7] = [
+    "log-level",
+    "message-size",
+    "batch-size",
+    "client-limit",
+    "store-limit",
+    "maximum-clients",
+    "maximum-clients-per-host",
+];
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReloadError {
+    #[error("Server was not started with a config file")]
+    NoConfigFile,
+    #[error("Could not read config file {path}, {error}")]
+    Read { path: PathBuf, error: String },
+    #[error("Invalid config file {path}, {error}")]
+    Parse { path: PathBuf, error: String },
+    #[error("Unknown setting {0} in config file")]
+    UnknownSetting(String),
+    #[error("Setting {0} cannot change while the server runs, pass it as a flag instead")]
+    RequiresRestart(String),
+    #[error("Invalid value for setting {setting}, {error}")]
+    InvalidValue { setting: String, error: String },
+}
+
+impl From<ReloadError> for ErrorResponse {
+    fn from(err: ReloadError) -> Self {
+        ErrorResponse::new(ErrorCode::InvalidArgument, err.to_string())
+    }
+}
+
+/// Long names of the flags of a server, which settings of the configuration file are named after
+pub fn flags<A: clap::Args>() -> HashSet<String> {
+    A::augment_args(clap::Command::new("server"))
+        .get_arguments()
+        .filter_map(|arg| arg.get_long().map(str::to_string))
+        .collect()
+}
+
+/// Applies the settings of the configuration file of a server over those it was started with,
+/// on start and again whenever it is reloaded
+#[derive(Debug)]
+pub struct ConfigReloader {
+    path: Option<PathBuf>,
+    flags: HashSet<String>,
+    // settings left out of the file fall back to those the server was started with
+    initial: CommandLineConfig,
+    initial_settings: BTreeMap<&'static str, u64>,
+    settings: BTreeMap<&'static str, Arc<AtomicU64>>,
+    limit_handler: Arc<LimitHandler>,
+    client_handler: Arc<ClientHandler>,
+    applied: Mutex<Applied>,
+}
+
+#[derive(Debug, Clone)]
+struct Applied {
+    config: CommandLineConfig,
+    settings: BTreeMap<&'static str, u64>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config: &CommandLineConfig,
+        flags: HashSet<String>,
+        limit_handler: Arc<LimitHandler>,
+        client_handler: Arc<ClientHandler>,
+    ) -> Self {
+        Self {
+            path: config.config_file.clone(),
+            flags,
+            initial: config.clone(),
+            initial_settings: BTreeMap::new(),
+            settings: BTreeMap::new(),
+            limit_handler,
+            client_handler,
+            applied: Mutex::new(Applied {
+                config: config.clone(),
+                settings: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Registers a whole number setting of the server named `flag`, which the server reads from
+    /// `value` as it runs
+    pub fn with_setting(mut self, flag: &'static str, value: Arc<AtomicU64>) -> Self {
+        let initial = value.load(Ordering::Relaxed);
+        self.initial_settings.insert(flag, initial);
+        self.applied
+            .get_mut()
+            .expect("Applied config lock poisoned")
+            .settings
+            .insert(flag, initial);
+        self.settings.insert(flag, value);
+        self
+    }
+
+    pub fn config_file(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    /// Applies the configuration file as the server starts, failing on any setting that cannot
+    /// change while it runs so that it is passed as a flag instead
+    pub fn load(&self) -> Result<(), ReloadError> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        let reload = self.reload()?;
+        if let Some(setting) = reload.requires_restart.into_iter().next() {
+            return Err(ReloadError::RequiresRestart(setting));
+        }
+        Ok(())
+    }
+
+    /// Reads the configuration file again and applies its settings that differ from those in
+    /// effect. Nothing is applied when the file holds an unknown or invalid setting
+    pub fn reload(&self) -> Result<ConfigReload, ReloadError> {
+        let path = self.path.as_ref().ok_or(ReloadError::NoConfigFile)?;
+        let contents = std::fs::read_to_string(path).map_err(|err| ReloadError::Read {
+            path: path.clone(),
+            error: err.to_string(),
+        })?;
+        let table: toml::Table = toml::from_str(&contents).map_err(|err| ReloadError::Parse {
+            path: path.clone(),
+            error: err.to_string(),
+        })?;
+        let mut next = Applied {
+            config: self.initial.clone(),
+            settings: self.initial_settings.clone(),
+        };
+        let mut requires_restart = Vec::new();
+        for (setting, value) in &table {
+            if let Some(current) = self.settings.get_key_value(setting.as_str()) {
+                next.settings
+                    .insert(current.0, whole_number(setting, value)?);
+            } else if RELOADABLE.contains(&setting.as_str()) {
+                apply(&mut next.config, setting, value)?;
+            } else if self.flags.contains(setting) {
+                requires_restart.push(setting.clone());
+            } else {
+                return Err(ReloadError::UnknownSetting(setting.clone()));
+            }
+        }
+
+        let mut applied = self.applied.lock().expect("Applied config lock poisoned");
+        let changed = changes(&applied, &next);
+        if changed.iter().any(|setting| setting == "log-level") {
+            tracer::set_log_level(&next.config.log_level).map_err(|error| {
+                ReloadError::InvalidValue {
+                    setting: "log-level".to_string(),
+                    error,
+                }
+            })?;
+        }
+        self.limit_handler.reload(&next.config);
+        self.client_handler.set_limits(
+            next.config.maximum_clients,
+            next.config.maximum_clients_per_host,
+        );
+        for (setting, value) in &next.settings {
+            self.settings[setting].store(*value, Ordering::Relaxed);
+        }
+        *applied = next;
+        Ok(ConfigReload {
+            changed,
+            requires_restart,
+        })
+    }
+}
+
+/// Settings whose values differ between `applied` and `next`, named after their flags
+fn changes(applied: &Applied, next: &Applied) -> Vec<String> {
+    let (from, to) = (&applied.config, &next.config);
+    let common = [
+        ("log-level", from.log_level != to.log_level),
+        ("message-size", from.message_size != to.message_size),
+        ("batch-size", from.batch_size != to.batch_size),
+        ("client-limit", from.client_limits != to.client_limits),
+        ("store-limit", from.store_limits != to.store_limits),
+        (
+            "maximum-clients",
+            from.maximum_clients != to.maximum_clients,
+        ),
+        (
+            "maximum-clients-per-host",
+            from.maximum_clients_per_host != to.maximum_clients_per_host,
+        ),
+    ];
+    common
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(setting, _)| setting)
+        .chain(
+            next.settings
+                .iter()
+                .filter(|(setting, value)| applied.settings.get(*setting) != Some(value))
+                .map(|(setting, _)| *setting),
+        )
+        .map(str::to_string)
+        .collect()
+}
+
+fn apply(
+    config: &mut CommandLineConfig,
+    setting: &str,
+    value: &toml::Value,
+) -> Result<(), ReloadError> {
+    match setting {
+        "log-level" => {
+            config.log_level = value
+                .as_str()
+                .ok_or_else(|| invalid(setting, "expected a string"))?
+                .to_string()
+        }
+        "message-size" => config.message_size = whole_number(setting, value)?,
+        "batch-size" => config.batch_size = Some(whole_number(setting, value)?),
+        "maximum-clients" => config.maximum_clients = whole_number(setting, value)?,
+        "maximum-clients-per-host" => {
+            config.maximum_clients_per_host = Some(whole_number(setting, value)?)
+        }
+        "client-limit" => config.client_limits = limit_overrides(setting, value)?,
+        "store-limit" => config.store_limits = limit_overrides(setting, value)?,
+        _ => unreachable!("{setting} is not a reloadable setting"),
+    }
+    Ok(())
+}
+
+fn whole_number<T: TryFrom<i64>>(setting: &str, value: &toml::Value) -> Result<T, ReloadError> {
+    value
+        .as_integer()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| invalid(setting, "expected a positive whole number"))
+}
+
+/// Overrides given as a single `NAME=MESSAGE_SIZE[,BATCH_SIZE]` string or an array of them, as
+/// the flag can be repeated
+fn limit_overrides(setting: &str, value: &toml::Value) -> Result<Vec<LimitOverride>, ReloadError> {
+    let entries = match value {
+        toml::Value::Array(entries) => entries.iter().collect(),
+        entry => vec![entry],
+    };
+    entries
+        .into_iter()
+        .map(|entry| {
+            entry
+                .as_str()
+                .ok_or_else(|| invalid(setting, "expected a string"))?
+                .parse()
+                .map_err(|error: String| invalid(setting, &error))
+        })
+        .collect()
+}
+
+fn invalid(setting: &str, error: &str) -> ReloadError {
+    ReloadError::InvalidValue {
+        setting: setting.to_string(),
+        error: error.to_string(),
+    }
+}
+
+/// Reloads the configuration file of a server whenever it receives SIGHUP
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    reloader: Arc<ConfigReloader>,
+    hangup: tokio::sync::Mutex<Signal>,
+}
+
+impl ConfigWatcher {
+    pub fn new(reloader: Arc<ConfigReloader>) -> std::io::Result<Self> {
+        Ok(Self {
+            reloader,
+            hangup: tokio::sync::Mutex::new(signal(SignalKind::hangup())?),
+        })
+    }
+}
+
+#[async_trait]
+impl Task for ConfigWatcher {
+    fn task_name(&self) -> String {
+        "config-watcher".to_string()
+    }
+
+    async fn run(&self) -> TaskState {
+        if self.hangup.lock().await.recv().await.is_none() {
+            return TaskState::Break;
+        }
+        match self.reloader.reload() {
+            Ok(reload) => log::info!(
+                "Reloaded config file, changed {:?}, requires restart {:?}",
+                reload.changed,
+                reload.requires_restart
+            ),
+            Err(e) => log::error!("Could not reload config file, {e}"),
+        }
+        TaskState::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ahnlich_types::keyval::StoreName;
+
+    fn reloader(contents: &str) -> (ConfigReloader, Arc<LimitHandler>, Arc<ClientHandler>) {
+        let path = tempfile::NamedTempFile::new()
+            .unwrap()
+            .into_temp_path()
+            .keep()
+            .unwrap();
+        std::fs::write(&path, contents).unwrap();
+        let config = CommandLineConfig {
+            config_file: Some(path),
+            ..CommandLineConfig::default()
+        };
+        let limit_handler = Arc::new(LimitHandler::new(&config));
+        let client_handler = Arc::new(ClientHandler::new(config.maximum_clients, None));
+        let flags = HashSet::from_iter(["port".to_string()]);
+        let reloader = ConfigReloader::new(
+            &config,
+            flags,
+            limit_handler.clone(),
+            client_handler.clone(),
+        );
+        (reloader, limit_handler, client_handler)
+    }
+
+    #[test]
+    fn test_reload_applies_changed_settings() {
+        let idle_time = Arc::new(AtomicU64::new(300));
+        let (reloader, limit_handler, client_handler) = reloader("maximum-clients = 10");
+        let reloader = reloader.with_setting("ai-model-idle-time", idle_time.clone());
+        reloader.load().unwrap();
+        assert_eq!(client_handler.maximum_clients(), 10);
+
+        let path = reloader.config_file().unwrap().clone();
+        std::fs::write(
+            &path,
+            "message-size = 64\nstore-limit = [\"Main=128,2\"]\nai-model-idle-time = 5\nport = 1",
+        )
+        .unwrap();
+        let reload = reloader.reload().unwrap();
+        assert_eq!(
+            reload,
+            ConfigReload {
+                changed: vec![
+                    "message-size".to_string(),
+                    "store-limit".to_string(),
+                    "maximum-clients".to_string(),
+                    "ai-model-idle-time".to_string(),
+                ],
+                requires_restart: vec!["port".to_string()],
+            }
+        );
+        // settings left out of the file fall back to those the server started with
+        assert_eq!(client_handler.maximum_clients(), 1000);
+        assert_eq!(idle_time.load(Ordering::Relaxed), 5);
+        let store = limit_handler.store(&StoreName("Main".to_string()));
+        assert_eq!((store.message_size, store.batch_size), (128, Some(2)));
+        assert_eq!(
+            limit_handler
+                .store(&StoreName("Other".to_string()))
+                .message_size,
+            64
+        );
+        assert_eq!(reloader.reload().unwrap().changed, Vec::<String>::new());
+
+        std::fs::write(&path, "message-size = 32\nthreads = 4").unwrap();
+        assert_eq!(
+            reloader.reload(),
+            Err(ReloadError::UnknownSetting("threads".to_string()))
+        );
+        assert_eq!(
+            limit_handler
+                .store(&StoreName("Other".to_string()))
+                .message_size,
+            64
+        );
+        std::fs::write(&path, "port = 1").unwrap();
+        assert_eq!(
+            reloader.load(),
+            Err(ReloadError::RequiresRestart("port".to_string()))
+        );
+        let _ = std::fs::remove_file(path);
+    }
+}
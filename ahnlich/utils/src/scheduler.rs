@@ -0,0 +1,224 @@
+use ahnlich_types::{Priority, PriorityStats};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+const PRIORITIES: [Priority; 3] = [Priority::Interactive, Priority::Normal, Priority::Bulk];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counters {
+    requests: u64,
+    wait: Duration,
+    latency: Duration,
+    max_latency: Duration,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    queued_at: Instant,
+    wake: oneshot::Sender<Ticket>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    running: usize,
+    // waiting requests of each priority, in the order of `PRIORITIES`
+    queues: [VecDeque<Waiter>; 3],
+    counters: [Counters; 3],
+}
+
+/// Runs requests up to a limit at once. Requests past the limit wait in a queue per priority and
+/// are let through by priority, except that a request that waited for `aging` goes ahead of the
+/// rest so that bulk requests still progress while interactive ones keep coming
+#[derive(Debug)]
+pub struct Scheduler {
+    limit: Option<usize>,
+    aging: Duration,
+    state: Mutex<State>,
+}
+
+impl Scheduler {
+    /// Scheduler running up to `limit` requests at once, or any number of them when there is no
+    /// limit
+    pub fn new(limit: Option<usize>, aging: Duration) -> Self {
+        Self {
+            limit,
+            aging,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Waits for the turn of a request, which lasts until the ticket is dropped
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> Ticket {
+        let arrived = Instant::now();
+        let receiver = {
+            let mut state = self.state.lock().expect("scheduler lock poisoned");
+            if self.limit.map_or(true, |limit| state.running < limit) {
+                state.running += 1;
+                None
+            } else {
+                let (wake, receiver) = oneshot::channel();
+                state.queues[priority as usize].push_back(Waiter {
+                    queued_at: arrived,
+                    wake,
+                });
+                Some(receiver)
+            }
+        };
+        match receiver {
+            // a waiter is only dropped along with the scheduler, which the caller holds on to
+            Some(receiver) => receiver.await.expect("scheduler dropped a waiting request"),
+            None => Ticket {
+                scheduler: Some(self.clone()),
+                priority,
+                arrived,
+                started: arrived,
+            },
+        }
+    }
+
+    /// Requests run at each priority, along with those waiting
+    pub fn stats(&self) -> Vec<PriorityStats> {
+        let state = self.state.lock().expect("scheduler lock poisoned");
+        PRIORITIES
+            .iter()
+            .map(|priority| {
+                let counters = state.counters[*priority as usize];
+                PriorityStats {
+                    priority: *priority,
+                    requests: counters.requests,
+                    queued: state.queues[*priority as usize].len() as u64,
+                    wait_ms: counters.wait.as_millis() as u64,
+                    latency_ms: counters.latency.as_millis() as u64,
+                    max_latency_ms: counters.max_latency.as_millis() as u64,
+                }
+            })
+            .collect()
+    }
+
+    fn finish(self: &Arc<Self>, ticket: &Ticket) {
+        let mut state = self.state.lock().expect("scheduler lock poisoned");
+        let counters = &mut state.counters[ticket.priority as usize];
+        let latency = ticket.arrived.elapsed();
+        counters.requests += 1;
+        counters.wait += ticket.started.duration_since(ticket.arrived);
+        counters.latency += latency;
+        counters.max_latency = counters.max_latency.max(latency);
+        // the turn is handed to the next request rather than given up so that requests arriving
+        // in the meantime cannot skip the queue
+        while let Some((priority, waiter)) = self.next_waiter(&mut state) {
+            let ticket = Ticket {
+                scheduler: Some(self.clone()),
+                priority,
+                arrived: waiter.queued_at,
+                started: Instant::now(),
+            };
+            match waiter.wake.send(ticket) {
+                Ok(()) => return,
+                // the request stopped waiting, so its ticket is dropped without handing over
+                Err(mut ticket) => ticket.scheduler = None,
+            }
+        }
+        state.running -= 1;
+    }
+
+    fn next_waiter(&self, state: &mut State) -> Option<(Priority, Waiter)> {
+        let now = Instant::now();
+        let aged = PRIORITIES
+            .iter()
+            .filter_map(|priority| {
+                state.queues[*priority as usize]
+                    .front()
+                    .filter(|waiter| now.duration_since(waiter.queued_at) >= self.aging)
+                    .map(|waiter| (waiter.queued_at, *priority))
+            })
+            .min()
+            .map(|(_, priority)| priority);
+        let priority = aged.or_else(|| {
+            PRIORITIES
+                .into_iter()
+                .find(|priority| !state.queues[*priority as usize].is_empty())
+        })?;
+        state.queues[priority as usize]
+            .pop_front()
+            .map(|waiter| (priority, waiter))
+    }
+}
+
+/// Turn of a request to run, handed to the next waiting request once dropped
+#[derive(Debug)]
+pub struct Ticket {
+    scheduler: Option<Arc<Scheduler>>,
+    priority: Priority,
+    arrived: Instant,
+    started: Instant,
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        if let Some(scheduler) = self.scheduler.take() {
+            scheduler.finish(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run_in_order(scheduler: Arc<Scheduler>, priorities: &[Priority]) -> Vec<Priority> {
+        let first = scheduler.acquire(Priority::Normal).await;
+        let (done, mut finished) = tokio::sync::mpsc::unbounded_channel();
+        for (queued, priority) in priorities.iter().enumerate() {
+            let (waiting, done, priority) = (scheduler.clone(), done.clone(), *priority);
+            tokio::spawn(async move {
+                let _ticket = waiting.acquire(priority).await;
+                done.send(priority).unwrap();
+            });
+            // wait for the request to be queued before the next one arrives
+            while scheduler
+                .stats()
+                .iter()
+                .map(|stats| stats.queued)
+                .sum::<u64>()
+                == queued as u64
+            {
+                tokio::task::yield_now().await;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        drop(first);
+        let mut order = Vec::new();
+        for _ in priorities {
+            order.push(finished.recv().await.unwrap());
+        }
+        order
+    }
+
+    #[tokio::test]
+    async fn test_scheduler() {
+        let scheduler = Arc::new(Scheduler::new(Some(1), Duration::from_secs(60)));
+        assert_eq!(
+            run_in_order(
+                scheduler.clone(),
+                &[Priority::Bulk, Priority::Normal, Priority::Interactive]
+            )
+            .await,
+            vec![Priority::Interactive, Priority::Normal, Priority::Bulk]
+        );
+        let stats = scheduler.stats();
+        assert_eq!(stats[0].requests, 1);
+        assert_eq!(stats[1].requests, 2);
+        assert_eq!(stats[2].requests, 1);
+        assert!(stats[2].wait_ms >= stats[0].wait_ms);
+        assert!(stats.iter().all(|stats| stats.queued == 0));
+
+        // requests that waited long enough go first regardless of their priority
+        let scheduler = Arc::new(Scheduler::new(Some(1), Duration::ZERO));
+        assert_eq!(
+            run_in_order(scheduler, &[Priority::Bulk, Priority::Interactive]).await,
+            vec![Priority::Bulk, Priority::Interactive]
+        );
+    }
+}
@@ -1,7 +1,11 @@
 use crate::allocator::GLOBAL_ALLOCATOR;
+use crate::encryption::KeyProvider;
+use crate::gateway::HttpGateway;
 use crate::parallel;
 use crate::persistence::AhnlichPersistenceUtils;
 use crate::persistence::Persistence;
+use crate::reload::{ConfigReloader, ConfigWatcher};
+use crate::systemd;
 use async_trait::async_trait;
 use std::sync::atomic::AtomicBool;
 use std::{io::Result as IoResult, sync::Arc};
@@ -15,9 +19,15 @@ pub struct ServerUtilsConfig<'a> {
     // persistence stuff
     pub persistence_interval: u64,
     pub persist_location: &'a Option<std::path::PathBuf>,
+    // snapshots are encrypted with its current key when set
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
     // global allocator
     pub allocator_size: usize,
     pub threadpool_size: usize,
+    // servers without background maintenance leave out its threadpool
+    pub maintenance_threadpool_size: Option<usize>,
+    // notifies systemd once ready to serve and as it stops
+    pub systemd_notify: bool,
 }
 
 #[async_trait]
@@ -35,10 +45,27 @@ pub trait AhnlichServerUtils: Task + Sized + Send + Sync + 'static {
 
     fn task_manager(&self) -> Arc<TaskManager>;
 
+    /// HTTP/JSON gateway to serve alongside the server when enabled
+    fn http_gateway(&self) -> Option<HttpGateway> {
+        None
+    }
+
+    /// Applies the configuration file of the server, reloaded on SIGHUP when there is one
+    fn config_reloader(&self) -> Option<Arc<ConfigReloader>> {
+        None
+    }
+
+    /// Spawns the tasks a server runs in the background besides persistence
+    async fn spawn_background_tasks(&self, _task_manager: &TaskManager) {}
+
     /// Runs through several processes to start up the server
     /// - Sets global allocator cap
     /// - Spawns Persistence listeneer thread
+    /// - Spawns the HTTP gateway if enabled
+    /// - Spawns the watcher reloading the configuration file on SIGHUP when there is one
+    /// - Spawns any other background tasks of the server
     /// - Accepts incoming connections to the listener and processes streams
+    /// - Notifies systemd that the server is ready when enabled
     /// - Listens for ctrl_c signal to trigger spawned tasks cancellation
     /// - Cancellation triggers clean up of loggers and tracers
     async fn start(self) -> IoResult<()> {
@@ -49,6 +76,9 @@ pub trait AhnlichServerUtils: Task + Sized + Send + Sync + 'static {
             .unwrap_or_else(|_| panic!("Could not set up {service_name} with allocator_size"));
         log::debug!("Set max size for global allocator to: {global_allocator_cap}");
         parallel::init_threadpool(self.config().threadpool_size);
+        if let Some(maintenance_threadpool_size) = self.config().maintenance_threadpool_size {
+            parallel::init_maintenance_threadpool(maintenance_threadpool_size);
+        }
         let task_manager = self.task_manager();
 
         if let Some(persist_location) = self.config().persist_location {
@@ -57,11 +87,33 @@ pub trait AhnlichServerUtils: Task + Sized + Send + Sync + 'static {
                 self.config().persistence_interval,
                 persist_location,
                 self.store_handler().get_snapshot(),
+                self.config().key_provider,
             );
             task_manager.spawn_task_loop(persistence_task).await;
         };
+        if let Some(http_gateway) = self.http_gateway() {
+            task_manager.spawn_task_loop(http_gateway).await;
+        }
+        if let Some(reloader) = self
+            .config_reloader()
+            .filter(|reloader| reloader.config_file().is_some())
+        {
+            task_manager
+                .spawn_task_loop(ConfigWatcher::new(reloader)?)
+                .await;
+        }
+        self.spawn_background_tasks(&task_manager).await;
+        let systemd_notify = self.config().systemd_notify;
         task_manager.spawn_task_loop(self).await;
+        // persisted stores and models are loaded as the server is created, so it is ready once
+        // it accepts connections
+        if systemd_notify {
+            systemd::notify("READY=1");
+        }
         task_manager.wait().await;
+        if systemd_notify {
+            systemd::notify("STOPPING=1");
+        }
         tracer::shutdown_tracing();
         log::info!("Shutdown complete");
         Ok(())
@@ -0,0 +1,80 @@
+use std::io::Result as IoResult;
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// First descriptor systemd passes listeners on from, as `SD_LISTEN_FDS_START`
+const LISTEN_FDS_START: RawFd = 3;
+
+static LISTEN_FDS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Takes the listeners systemd passed on to the process through socket activation, in the order
+/// of the socket unit. Empty when the process was not socket activated, and on every call after
+/// the first as the descriptors are only owned once
+pub fn listen_fds() -> Vec<OwnedFd> {
+    let activated = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    if !activated || LISTEN_FDS_TAKEN.swap(true, Ordering::SeqCst) {
+        return Vec::new();
+    }
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse::<RawFd>().ok())
+        .unwrap_or(0);
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        // SAFETY: systemd hands these descriptors over to the process, and they are only taken
+        // once
+        .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+        .collect()
+}
+
+/// Sends `state` to the service manager over `NOTIFY_SOCKET`, as with `sd_notify`. Failing to
+/// notify is logged rather than stopping the server
+pub fn notify(state: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        log::warn!("Could not notify systemd of {state}, NOTIFY_SOCKET is not set");
+        return;
+    };
+    if let Err(e) = send(&socket, state) {
+        log::warn!("Could not notify systemd of {state}, {e}");
+    }
+}
+
+fn send(socket: &std::ffi::OsStr, state: &str) -> IoResult<usize> {
+    use std::os::unix::ffi::OsStrExt;
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(state.as_bytes(), &address)
+        }
+        _ => datagram.send_to(state.as_bytes(), socket),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_sends_state_to_notify_socket() {
+        let path = std::env::temp_dir().join("ahnlich_test_notify.sock");
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        std::env::set_var("NOTIFY_SOCKET", &path);
+        notify("READY=1");
+        let mut buf = [0u8; 16];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_listen_fds_without_socket_activation() {
+        assert!(listen_fds().is_empty());
+    }
+}
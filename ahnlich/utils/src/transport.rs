@@ -0,0 +1,257 @@
+use crate::client::CONNECTION_BUFFER_SIZE;
+use crate::systemd;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::net::SocketAddr;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Mutex;
+
+/// Stream a client is served over, a TCP connection, a Unix domain socket or an in-memory
+/// channel from within the same process
+#[derive(Debug)]
+pub enum ServerStream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+    Memory(DuplexStream),
+}
+
+impl ServerStream {
+    /// Resolves once the client has hung up. Hang ups are only noticed over TCP, as the other
+    /// streams cannot be peeked at without taking the data off them
+    pub async fn hung_up(&self) {
+        let mut buf = [0u8; 1];
+        match self {
+            ServerStream::Tcp(stream) => {
+                if matches!(stream.peek(&mut buf).await, Ok(0) | Err(_)) {
+                    return;
+                }
+                std::future::pending().await
+            }
+            ServerStream::Unix(_) | ServerStream::Memory(_) => std::future::pending().await,
+        }
+    }
+}
+
+impl AsyncRead for ServerStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        match self.get_mut() {
+            ServerStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            ServerStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            ServerStream::Memory(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ServerStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        match self.get_mut() {
+            ServerStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            ServerStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            ServerStream::Memory(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut() {
+            ServerStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            ServerStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            ServerStream::Memory(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        match self.get_mut() {
+            ServerStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            ServerStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            ServerStream::Memory(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Opens in-memory connections to a server running in the same process, for embedding a server
+/// or testing against one without going through the network
+#[derive(Debug, Clone)]
+pub struct MemoryConnector(UnboundedSender<DuplexStream>);
+
+impl MemoryConnector {
+    /// Opens a connection, returning the end of it the client speaks the protocol over
+    pub fn connect(&self) -> IoResult<DuplexStream> {
+        let (client, server) = tokio::io::duplex(CONNECTION_BUFFER_SIZE);
+        self.0.send(server).map_err(|_| {
+            Error::new(
+                ErrorKind::ConnectionRefused,
+                "Server no longer accepts in-memory connections",
+            )
+        })?;
+        Ok(client)
+    }
+}
+
+/// Accepts clients over TCP, over a Unix domain socket when one is bound and over in-memory
+/// channels opened with a [`MemoryConnector`]
+#[derive(Debug)]
+pub struct Listener {
+    tcp: TcpListener,
+    unix: Option<UnixSocket>,
+    memory: Mutex<UnboundedReceiver<DuplexStream>>,
+    connector: MemoryConnector,
+    // numbers the clients of the streams without a peer address
+    connections: AtomicU64,
+}
+
+#[derive(Debug)]
+struct UnixSocket {
+    listener: UnixListener,
+    path: Option<PathBuf>,
+    // sockets passed on by systemd are left for it to clean up
+    inherited: bool,
+}
+
+impl Listener {
+    /// Binds `host` and `port` over TCP along with `unix_socket` when given. A socket left
+    /// behind at `unix_socket` by a server that did not shut down cleanly is replaced.
+    /// When socket activated by systemd, the TCP listener and Unix domain socket it passes on
+    /// are served in place of binding them
+    pub async fn bind(host: &str, port: u16, unix_socket: Option<&Path>) -> IoResult<Self> {
+        let (inherited_tcp, inherited_unix) = inherited_listeners()?;
+        let tcp = match inherited_tcp {
+            Some(tcp) => tcp,
+            None => TcpListener::bind(format!("{host}:{port}")).await?,
+        };
+        let unix = match (inherited_unix, unix_socket) {
+            (Some(listener), _) => Some(UnixSocket {
+                path: listener
+                    .local_addr()?
+                    .as_pathname()
+                    .map(|path| path.to_path_buf()),
+                listener,
+                inherited: true,
+            }),
+            (None, Some(path)) => Some(UnixSocket {
+                listener: bind_unix(path)?,
+                path: Some(path.to_path_buf()),
+                inherited: false,
+            }),
+            (None, None) => None,
+        };
+        let (sender, receiver) = unbounded_channel();
+        Ok(Self {
+            tcp,
+            unix,
+            memory: Mutex::new(receiver),
+            connector: MemoryConnector(sender),
+            connections: AtomicU64::new(0),
+        })
+    }
+
+    pub fn local_addr(&self) -> IoResult<SocketAddr> {
+        self.tcp.local_addr()
+    }
+
+    /// Path of the Unix domain socket when one is bound
+    pub fn unix_socket(&self) -> Option<&Path> {
+        self.unix.as_ref().and_then(|unix| unix.path.as_deref())
+    }
+
+    pub fn memory_connector(&self) -> MemoryConnector {
+        self.connector.clone()
+    }
+
+    /// Accepts the next client, returning its stream along with its address. Clients of Unix
+    /// domain sockets and in-memory channels are addressed as `unix#<n>` and `memory#<n>`
+    pub async fn accept(&self) -> IoResult<(ServerStream, String)> {
+        let unix = async {
+            match &self.unix {
+                Some(unix) => unix.listener.accept().await,
+                None => std::future::pending().await,
+            }
+        };
+        let mut memory = self.memory.lock().await;
+        tokio::select! {
+            accepted = self.tcp.accept() => {
+                let (stream, addr) = accepted?;
+                Ok((ServerStream::Tcp(stream), format!("{addr}")))
+            }
+            accepted = unix => {
+                let (stream, _) = accepted?;
+                Ok((ServerStream::Unix(stream), self.address("unix")))
+            }
+            Some(stream) = memory.recv() => {
+                Ok((ServerStream::Memory(stream), self.address("memory")))
+            }
+        }
+    }
+
+    fn address(&self, transport: &str) -> String {
+        let connection = self.connections.fetch_add(1, Ordering::Relaxed);
+        format!("{transport}#{connection}")
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Some(UnixSocket {
+            path: Some(path),
+            inherited: false,
+            ..
+        }) = &self.unix
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Sorts the listeners systemd passed on through socket activation into the first TCP listener
+/// and the first Unix domain socket
+fn inherited_listeners() -> IoResult<(Option<TcpListener>, Option<UnixListener>)> {
+    let (mut tcp, mut unix) = (None, None);
+    for fd in systemd::listen_fds() {
+        let listener = std::net::TcpListener::from(fd);
+        // only sockets of the internet families have an address a TCP listener can read
+        if listener.local_addr().is_ok() {
+            if tcp.is_none() {
+                listener.set_nonblocking(true)?;
+                tcp = Some(TcpListener::from_std(listener)?);
+                continue;
+            }
+        } else if unix.is_none() {
+            let listener = std::os::unix::net::UnixListener::from(OwnedFd::from(listener));
+            listener.set_nonblocking(true)?;
+            unix = Some(UnixListener::from_std(listener)?);
+            continue;
+        }
+        log::warn!("Ignoring a listener passed on by systemd past the first of its kind");
+    }
+    if tcp.is_some() || unix.is_some() {
+        log::info!("Serving listeners passed on by systemd socket activation");
+    }
+    Ok((tcp, unix))
+}
+
+fn bind_unix(path: &Path) -> IoResult<UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+    // only a socket no server listens on is replaced, so that a mistyped path cannot delete a
+    // file nor take the socket of a running server
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(Error::new(
+                    ErrorKind::AddrInUse,
+                    format!("A server already listens on {}", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+    }
+    UnixListener::bind(path)
+}
@@ -0,0 +1,54 @@
+use crate::decode_response;
+use crate::error::WasmError;
+use ahnlich_types::ai::{AIServerQuery, AIServerResult};
+use ahnlich_types::bincode::BinCodeSerAndDeser;
+use ahnlich_types::ErrorPolicy;
+use wasm_bindgen::prelude::*;
+
+pub(crate) fn parse(input: &str) -> Result<String, WasmError> {
+    let queries = dsl::ai::parse_ai_query(input)?;
+    Ok(serde_json::to_string(&queries)?)
+}
+
+pub(crate) fn build_request(
+    input: &str,
+    tracing_id: Option<String>,
+    fail_fast: bool,
+) -> Result<Vec<u8>, WasmError> {
+    let queries = dsl::ai::parse_ai_query(input)?;
+    let mut server_query = AIServerQuery::with_capacity_and_tracing_id(queries.len(), tracing_id);
+    for query in queries {
+        server_query.push(query);
+    }
+    if fail_fast {
+        server_query.set_error_policy(ErrorPolicy::FailFast);
+    }
+    Ok(server_query.serialize()?)
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<String, WasmError> {
+    let result: AIServerResult = decode_response(bytes)?;
+    Ok(serde_json::to_string(&result.into_inner())?)
+}
+
+/// Parses AI queries, returning them as JSON
+#[wasm_bindgen(js_name = parseAiQuery)]
+pub fn parse_ai_query(input: &str) -> Result<String, JsError> {
+    Ok(parse(input)?)
+}
+
+/// Parses AI queries into a request that can be written as is to a AI proxy
+#[wasm_bindgen(js_name = buildAiRequest)]
+pub fn build_ai_request(
+    input: &str,
+    tracing_id: Option<String>,
+    fail_fast: bool,
+) -> Result<Vec<u8>, JsError> {
+    Ok(build_request(input, tracing_id, fail_fast)?)
+}
+
+/// Decodes a response read from a AI proxy, returning the result of each query as JSON
+#[wasm_bindgen(js_name = decodeAiResponse)]
+pub fn decode_ai_response(bytes: &[u8]) -> Result<String, JsError> {
+    Ok(decode(bytes)?)
+}
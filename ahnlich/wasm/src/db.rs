@@ -0,0 +1,55 @@
+use crate::decode_response;
+use crate::error::WasmError;
+use ahnlich_types::bincode::{BinCodeSerAndDeser, BincodeSerError};
+use ahnlich_types::db::{ServerDBQuery, ServerResult};
+use ahnlich_types::ErrorPolicy;
+use wasm_bindgen::prelude::*;
+
+pub(crate) fn parse(input: &str) -> Result<String, WasmError> {
+    let queries = dsl::db::parse_db_query(input)?;
+    Ok(serde_json::to_string(&queries)?)
+}
+
+pub(crate) fn build_request(
+    input: &str,
+    tracing_id: Option<String>,
+    fail_fast: bool,
+) -> Result<Vec<u8>, WasmError> {
+    let queries = dsl::db::parse_db_query(input)?;
+    let mut server_query = ServerDBQuery::with_capacity_and_tracing_id(queries.len(), tracing_id)
+        .map_err(BincodeSerError::Allocation)?;
+    for query in queries {
+        server_query.push(query);
+    }
+    if fail_fast {
+        server_query.set_error_policy(ErrorPolicy::FailFast);
+    }
+    Ok(server_query.serialize()?)
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<String, WasmError> {
+    let result: ServerResult = decode_response(bytes)?;
+    Ok(serde_json::to_string(&result.into_inner())?)
+}
+
+/// Parses DB queries, returning them as JSON
+#[wasm_bindgen(js_name = parseDbQuery)]
+pub fn parse_db_query(input: &str) -> Result<String, JsError> {
+    Ok(parse(input)?)
+}
+
+/// Parses DB queries into a request that can be written as is to a DB server
+#[wasm_bindgen(js_name = buildDbRequest)]
+pub fn build_db_request(
+    input: &str,
+    tracing_id: Option<String>,
+    fail_fast: bool,
+) -> Result<Vec<u8>, JsError> {
+    Ok(build_request(input, tracing_id, fail_fast)?)
+}
+
+/// Decodes a response read from a DB server, returning the result of each query as JSON
+#[wasm_bindgen(js_name = decodeDbResponse)]
+pub fn decode_db_response(bytes: &[u8]) -> Result<String, JsError> {
+    Ok(decode(bytes)?)
+}
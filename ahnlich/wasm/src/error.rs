@@ -0,0 +1,19 @@
+use ahnlich_types::bincode::BincodeSerError;
+use dsl::error::DslError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WasmError {
+    #[error("{0}")]
+    Dsl(#[from] DslError),
+    #[error("{0}")]
+    BinCodeSerAndDeser(#[from] BincodeSerError),
+    #[error("bincode deserialize error {0}")]
+    Bincode(#[from] bincode::Error),
+    #[error("json serialize error {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid response header")]
+    InvalidHeader,
+    #[error("Response is {actual} bytes, header specifies {expected}")]
+    InvalidLength { expected: u64, actual: usize },
+}
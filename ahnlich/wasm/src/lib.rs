@@ -0,0 +1,40 @@
+//! Browser builds of the DSL and wire types. Queries written in the DSL are parsed and validated
+//! client side, then serialized into the bytes a server expects including the header, and
+//! responses are decoded back into JSON
+//!
+//! Build with `wasm-pack build --target web` from this directory
+pub mod ai;
+pub mod db;
+pub mod error;
+#[cfg(test)]
+mod tests;
+
+use ahnlich_types::bincode::{
+    BinCodeSerAndDeser, LENGTH_HEADER_SIZE, MAGIC_BYTES, RESPONSE_HEADER_LEN,
+};
+use error::WasmError;
+
+/// strips the header from a response read off the wire, checking that it is complete
+fn response_body(bytes: &[u8]) -> Result<&[u8], WasmError> {
+    if bytes.len() < RESPONSE_HEADER_LEN || !bytes.starts_with(MAGIC_BYTES) {
+        return Err(WasmError::InvalidHeader);
+    }
+    let mut length_header = [0u8; LENGTH_HEADER_SIZE];
+    length_header
+        .copy_from_slice(&bytes[RESPONSE_HEADER_LEN - LENGTH_HEADER_SIZE..RESPONSE_HEADER_LEN]);
+    let expected = u64::from_le_bytes(length_header);
+    let body = &bytes[RESPONSE_HEADER_LEN..];
+    if body.len() as u64 != expected {
+        return Err(WasmError::InvalidLength {
+            expected,
+            actual: body.len(),
+        });
+    }
+    Ok(body)
+}
+
+fn decode_response<T: BinCodeSerAndDeser>(bytes: &[u8]) -> Result<T, WasmError> {
+    Ok(<T as BinCodeSerAndDeser>::deserialize(response_body(
+        bytes,
+    )?)?)
+}
@@ -0,0 +1,52 @@
+use crate::error::WasmError;
+use crate::{ai, db};
+use ahnlich_types::ai::{AIQuery, AIServerQuery};
+use ahnlich_types::bincode::{BinCodeSerAndDeser, RESPONSE_HEADER_LEN};
+use ahnlich_types::db::{DBQuery, ServerDBQuery, ServerResponse, ServerResult};
+use ahnlich_types::ErrorPolicy;
+use pretty_assertions::assert_eq;
+
+#[test]
+fn test_build_db_request() {
+    let request = db::build_request("ping; liststores", Some("trace".to_string()), true).unwrap();
+    let mut expected =
+        ServerDBQuery::with_capacity_and_tracing_id(2, Some("trace".to_string())).unwrap();
+    expected.push(DBQuery::Ping);
+    expected.push(DBQuery::ListStores);
+    expected.set_error_policy(ErrorPolicy::FailFast);
+    assert_eq!(request, expected.serialize().unwrap());
+    assert_eq!(
+        <ServerDBQuery as BinCodeSerAndDeser>::deserialize(&request[RESPONSE_HEADER_LEN..])
+            .unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn test_build_ai_request() {
+    let request = ai::build_request("purgestores", None, false).unwrap();
+    let expected = AIServerQuery::from_queries(&[AIQuery::PurgeStores]);
+    assert_eq!(request, expected.serialize().unwrap());
+}
+
+#[test]
+fn test_parse_invalid_query() {
+    assert!(matches!(db::parse("random"), Err(WasmError::Dsl(_))));
+    assert_eq!(db::parse("ping").unwrap(), r#"["Ping"]"#);
+}
+
+#[test]
+fn test_decode_db_response() {
+    let mut result = ServerResult::with_capacity(1);
+    result.push(Ok(ServerResponse::Pong));
+    let response = result.serialize().unwrap();
+    assert_eq!(db::decode(&response).unwrap(), r#"[{"Ok":"Pong"}]"#);
+    assert!(matches!(
+        db::decode(&response[..response.len() - 1]),
+        Err(WasmError::InvalidLength { .. })
+    ));
+    assert!(matches!(
+        db::decode(&response[1..]),
+        Err(WasmError::InvalidHeader)
+    ));
+}
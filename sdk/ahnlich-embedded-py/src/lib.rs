@@ -0,0 +1,295 @@
+use ahnlich_db::engine::store::{GetSimNOptions, StoreHandler, StoreSettings};
+use ahnlich_types::error::ErrorResponse;
+use ahnlich_types::keyval::{StoreKey, StoreName, StoreValue};
+use ahnlich_types::metadata::{MetadataKey, MetadataValue};
+use ahnlich_types::predicate::{Predicate, PredicateCondition};
+use ahnlich_types::similarity::{Algorithm, NonLinearAlgorithm};
+use numpy::{IntoPyArray, PyArray1, PyReadonlyArray1, PyReadonlyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// Metadata values accepted from python, either `str` or `bytes`
+#[derive(FromPyObject)]
+enum Value {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl From<Value> for MetadataValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Text(text) => MetadataValue::RawString(text),
+            Value::Binary(binary) => MetadataValue::Image(binary),
+        }
+    }
+}
+
+fn to_py_err(err: impl Into<ErrorResponse>) -> PyErr {
+    let err: ErrorResponse = err.into();
+    PyValueError::new_err(err.message)
+}
+
+fn to_algorithm(input: &str) -> PyResult<Algorithm> {
+    match input.to_lowercase().trim() {
+        "kdtree" => Ok(Algorithm::KDTree),
+        "cosinesimilarity" => Ok(Algorithm::CosineSimilarity),
+        "dotproductsimilarity" => Ok(Algorithm::DotProductSimilarity),
+        "euclideandistance" => Ok(Algorithm::EuclideanDistance),
+        e => Err(PyValueError::new_err(format!("Unsupported algorithm {e}"))),
+    }
+}
+
+fn to_non_linear(input: &str) -> PyResult<NonLinearAlgorithm> {
+    match input.to_lowercase().trim() {
+        "kdtree" => Ok(NonLinearAlgorithm::KDTree),
+        e => Err(PyValueError::new_err(format!(
+            "Unsupported non linear algorithm {e}"
+        ))),
+    }
+}
+
+fn store_value_to_py(py: Python<'_>, value: StoreValue) -> PyResult<Bound<'_, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    for (key, value) in value {
+        match value {
+            MetadataValue::RawString(text) => dict.set_item(key.to_string(), text)?,
+            MetadataValue::Image(binary) => {
+                dict.set_item(key.to_string(), PyBytes::new_bound(py, &binary))?
+            }
+        }
+    }
+    Ok(dict)
+}
+
+/// Condition matched against the metadata of entries in a store
+#[pyclass(frozen, name = "Predicate")]
+#[derive(Clone)]
+struct PyPredicate {
+    condition: PredicateCondition,
+}
+
+impl From<Predicate> for PyPredicate {
+    fn from(predicate: Predicate) -> Self {
+        Self {
+            condition: PredicateCondition::Value(predicate),
+        }
+    }
+}
+
+#[pymethods]
+impl PyPredicate {
+    #[staticmethod]
+    fn equals(key: String, value: Value) -> Self {
+        Predicate::Equals {
+            key: MetadataKey::new(key),
+            value: value.into(),
+        }
+        .into()
+    }
+
+    #[staticmethod]
+    fn not_equals(key: String, value: Value) -> Self {
+        Predicate::NotEquals {
+            key: MetadataKey::new(key),
+            value: value.into(),
+        }
+        .into()
+    }
+
+    #[staticmethod]
+    fn in_(key: String, values: Vec<Value>) -> Self {
+        Predicate::In {
+            key: MetadataKey::new(key),
+            value: values.into_iter().map(Into::into).collect(),
+        }
+        .into()
+    }
+
+    #[staticmethod]
+    fn not_in(key: String, values: Vec<Value>) -> Self {
+        Predicate::NotIn {
+            key: MetadataKey::new(key),
+            value: values.into_iter().map(Into::into).collect(),
+        }
+        .into()
+    }
+
+    fn and_(&self, other: &Self) -> Self {
+        Self {
+            condition: self.condition.clone().and(other.condition.clone()),
+        }
+    }
+
+    fn or_(&self, other: &Self) -> Self {
+        Self {
+            condition: self.condition.clone().or(other.condition.clone()),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.condition)
+    }
+}
+
+/// In memory engine holding stores within the python process. Queries release the GIL while
+/// they run against the stores
+#[pyclass(frozen)]
+struct Engine {
+    store_handler: StoreHandler,
+}
+
+#[pymethods]
+impl Engine {
+    #[new]
+    fn new() -> Self {
+        Self {
+            // nothing is persisted so the write flag is never read
+            store_handler: StoreHandler::new(Arc::new(AtomicBool::new(false))),
+        }
+    }
+
+    #[pyo3(signature = (store, dimension, predicates=vec![], non_linear_indices=vec![], error_if_exists=true))]
+    fn create_store(
+        &self,
+        py: Python<'_>,
+        store: String,
+        dimension: usize,
+        predicates: Vec<String>,
+        non_linear_indices: Vec<String>,
+        error_if_exists: bool,
+    ) -> PyResult<()> {
+        let dimension = NonZeroUsize::new(dimension)
+            .ok_or_else(|| PyValueError::new_err("dimension must be greater than 0"))?;
+        let non_linear_indices = non_linear_indices
+            .iter()
+            .map(|algorithm| to_non_linear(algorithm))
+            .collect::<PyResult<HashSet<_>>>()?;
+        let predicates = predicates.into_iter().map(MetadataKey::new).collect();
+        py.allow_threads(|| {
+            self.store_handler.create_store(
+                StoreName(store),
+                dimension,
+                predicates,
+                non_linear_indices,
+                StoreSettings::default(),
+                error_if_exists,
+            )
+        })
+        .map_err(to_py_err)
+    }
+
+    /// Stores each row of `keys` with the metadata at the same position in `values`, returning
+    /// the number of entries inserted and updated. Rows are read directly from the numpy buffer
+    /// without going through python lists
+    fn set(
+        &self,
+        py: Python<'_>,
+        store: String,
+        keys: PyReadonlyArray2<'_, f32>,
+        values: Vec<HashMap<String, Value>>,
+    ) -> PyResult<(usize, usize)> {
+        let keys = keys.as_array();
+        if keys.nrows() != values.len() {
+            return Err(PyValueError::new_err(format!(
+                "Got {} keys but {} values",
+                keys.nrows(),
+                values.len()
+            )));
+        }
+        let inputs: Vec<(StoreKey, StoreValue)> = keys
+            .rows()
+            .into_iter()
+            .zip(values)
+            .map(|(key, value)| {
+                let value = value
+                    .into_iter()
+                    .map(|(key, value)| (MetadataKey::new(key), value.into()))
+                    .collect();
+                (StoreKey(key.to_owned()), value)
+            })
+            .collect();
+        let upsert = py
+            .allow_threads(|| self.store_handler.set_in_store(&StoreName(store), inputs))
+            .map_err(to_py_err)?;
+        Ok((upsert.inserted, upsert.updated))
+    }
+
+    /// Returns the `closest_n` entries to `search_input` as tuples of key, metadata and score
+    #[pyo3(signature = (store, search_input, closest_n=1, algorithm="cosinesimilarity", condition=None))]
+    fn get_sim_n<'py>(
+        &self,
+        py: Python<'py>,
+        store: String,
+        search_input: PyReadonlyArray1<'py, f32>,
+        closest_n: usize,
+        algorithm: &str,
+        condition: Option<PyPredicate>,
+    ) -> PyResult<Vec<(Bound<'py, PyArray1<f32>>, Bound<'py, PyDict>, f32)>> {
+        let closest_n = NonZeroUsize::new(closest_n)
+            .ok_or_else(|| PyValueError::new_err("closest_n must be greater than 0"))?;
+        let algorithm = to_algorithm(algorithm)?;
+        let search_input = StoreKey(search_input.as_array().to_owned());
+        let results = py
+            .allow_threads(|| {
+                self.store_handler.get_sim_in_store(
+                    &StoreName(store),
+                    search_input,
+                    closest_n,
+                    algorithm,
+                    condition.map(|predicate| predicate.condition),
+                    GetSimNOptions::default(),
+                )
+            })
+            .map_err(to_py_err)?;
+        results
+            .into_iter()
+            .map(|(key, value, similarity)| {
+                Ok((
+                    key.0.into_pyarray_bound(py),
+                    store_value_to_py(py, value)?,
+                    similarity.0,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns the entries matching `condition` as tuples of key and metadata
+    fn get_pred<'py>(
+        &self,
+        py: Python<'py>,
+        store: String,
+        condition: PyPredicate,
+    ) -> PyResult<Vec<(Bound<'py, PyArray1<f32>>, Bound<'py, PyDict>)>> {
+        let results = py
+            .allow_threads(|| {
+                self.store_handler
+                    .get_pred_in_store(&StoreName(store), &condition.condition)
+            })
+            .map_err(to_py_err)?;
+        results
+            .into_iter()
+            .map(|(key, value)| Ok((key.0.into_pyarray_bound(py), store_value_to_py(py, value)?)))
+            .collect()
+    }
+
+    /// Deletes the entries matching `condition`, returning how many were deleted
+    fn del_pred(&self, py: Python<'_>, store: String, condition: PyPredicate) -> PyResult<usize> {
+        py.allow_threads(|| {
+            self.store_handler
+                .del_pred_in_store(&StoreName(store), &condition.condition)
+        })
+        .map_err(to_py_err)
+    }
+}
+
+#[pymodule]
+fn ahnlich_embedded(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Engine>()?;
+    m.add_class::<PyPredicate>()?;
+    Ok(())
+}